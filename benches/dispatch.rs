@@ -0,0 +1,103 @@
+//! Per-request cost of routing and the middleware chain, without a socket.
+//!
+//! ```text
+//! cargo bench --bench dispatch
+//! cargo bench --bench dispatch -- router/
+//! ```
+//!
+//! Runs on criterion: each case is sampled until the estimate is stable, and reports
+//! are written under `target/criterion`, so a later run is compared against the last
+//! one. Compare runs on the same machine only.
+
+use std::{future::Future, hint::black_box, pin::Pin, sync::Arc};
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use rttp::{
+    Request, Response, Router, StatusCode,
+    context::Context,
+    middleware::{Middleware, MiddlewareHandler, Next, from_middleware},
+};
+use tokio::runtime::Runtime;
+
+// Forwards every request unchanged, to measure the cost of one hop.
+struct PassThrough;
+
+impl Middleware for PassThrough {
+    fn handle(&self, ctx: Context, next: Next) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        next.run(ctx)
+    }
+}
+
+fn router() -> Router {
+    let mut router = Router::new();
+    for resource in ["users", "posts", "comments", "tags", "teams"] {
+        router.get(&format!("/{resource}"), |_ctx| async {
+            Response::new(StatusCode::Ok)
+        });
+        router.get(&format!("/{resource}/:id"), |_ctx| async {
+            Response::new(StatusCode::Ok)
+        });
+    }
+    router
+}
+
+fn request(path: &str) -> Request {
+    Request::builder().path(path).build()
+}
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("failed to build the benchmark runtime")
+}
+
+fn routing(c: &mut Criterion) {
+    let runtime = runtime();
+    let router = router();
+    let mut group = c.benchmark_group("router");
+    for (name, path) in [
+        ("static", "/teams"),
+        ("param", "/teams/42"),
+        ("not_found", "/missing"),
+    ] {
+        group.bench_function(name, |b| {
+            b.to_async(&runtime)
+                .iter(|| async { black_box(router.route(request(path)).await) });
+        });
+    }
+
+    let mut cached = Router::new();
+    cached.get("/teams/:id", |_ctx| async { Response::new(StatusCode::Ok) });
+    cached.match_cache(1024);
+    group.bench_function("param_cached", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { black_box(cached.route(request("/teams/42")).await) });
+    });
+    group.finish();
+}
+
+fn pipeline(c: &mut Criterion) {
+    let runtime = runtime();
+    let router = Arc::new(router());
+    let mut group = c.benchmark_group("pipeline");
+    for depth in [0, 4, 16] {
+        let mut chain: Vec<MiddlewareHandler> = (0..depth)
+            .map(|_| from_middleware(Arc::new(PassThrough)))
+            .collect();
+        chain.push(from_middleware(Arc::clone(&router)));
+        let chain: Arc<[MiddlewareHandler]> = chain.into();
+        group.bench_with_input(BenchmarkId::new("middleware", depth), &chain, |b, chain| {
+            b.to_async(&runtime).iter(|| async {
+                black_box(
+                    Next::new(Arc::clone(chain))
+                        .run(Context::new(request("/teams/42")))
+                        .await,
+                )
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, routing, pipeline);
+criterion_main!(benches);
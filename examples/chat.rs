@@ -0,0 +1,112 @@
+//! WebSocket chat rooms fanned out through a topic [`Broadcast`] hub.
+//!
+//! Members connect to `/rooms/:room?name=<nickname>`; every text frame they send is
+//! published to the room's topic and relayed, as JSON, to everyone connected to that room
+//! (the sender included). `POST /rooms/:room/messages` lets plain-HTTP clients, such as
+//! bots or a `curl` session, post into a room without holding a socket open.
+//!
+//! ```text
+//! RUST_LOG=info cargo run --example chat
+//! websocat 'ws://127.0.0.1:8080/rooms/lobby?name=ada'
+//! curl -X POST -H 'Content-Type: application/json' -d '{"from":"bot","text":"hi"}' \
+//!     http://127.0.0.1:8080/rooms/lobby/messages
+//! ```
+
+use std::sync::Arc;
+
+use rttp::{
+    Response, Router, Server, StatusCode,
+    codec::{Body, CodecMiddleware, CodecRegistry, JsonCodec},
+    context::Context,
+    middleware::{LoggerMiddleware, MiddlewareHandler, Next, from_middleware},
+    realtime::{Broadcast, LagPolicy, Message, Received},
+};
+use serde::{Deserialize, Serialize};
+
+/// A chat message as delivered to members.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub from: String,
+    pub text: String,
+}
+
+/// Close code sent to a socket that connects without a `name` (RFC 6455 policy violation).
+pub const POLICY_VIOLATION: u16 = 1008;
+
+/// Builds the application pipeline.
+pub fn app() -> Vec<MiddlewareHandler> {
+    let rooms: Broadcast<ChatMessage> = Broadcast::new(256, LagPolicy::NotifyLagged);
+    let mut router = Router::new();
+
+    let hub = rooms.clone();
+    router.ws("/rooms/:room", move |ctx, mut ws| {
+        let hub = hub.clone();
+        async move {
+            let room = ctx.params().get("room").unwrap_or_default().to_owned();
+            let Some(name) = ctx.request().query_param("name").map(str::to_owned) else {
+                let _ = ws.close(POLICY_VIOLATION, "a name is required").await;
+                return;
+            };
+            // Subscribe before reading so the member sees their own first message.
+            let mut subscriber = hub.subscribe(&room);
+            loop {
+                tokio::select! {
+                    incoming = ws.next() => match incoming {
+                        Some(Ok(Message::Text(text))) => {
+                            hub.publish(&room, ChatMessage { from: name.clone(), text });
+                        }
+                        Some(Ok(_)) => {}
+                        Some(Err(_)) | None => break,
+                    },
+                    outgoing = subscriber.recv() => match outgoing {
+                        Ok(Received::Message(message)) => {
+                            let json = serde_json::to_string(&message).expect("serializable");
+                            if ws.send(json).await.is_err() {
+                                break;
+                            }
+                        }
+                        Ok(Received::Lagged(missed)) => {
+                            tracing::warn!(%room, %name, missed, "chat member fell behind");
+                        }
+                        Err(_) => break,
+                    },
+                }
+            }
+        }
+    });
+
+    router.post("/rooms/:room/messages", move |ctx: Context| {
+        let hub = rooms.clone();
+        async move {
+            let Body(message) = match Body::<ChatMessage>::from_context(&ctx) {
+                Ok(body) => body,
+                Err(e) => return e.into_response(),
+            };
+            let room = ctx.params().get("room").unwrap_or_default();
+            let delivered = hub.publish(room, message);
+            Response::new(StatusCode::Accepted).body(delivered.to_string())
+        }
+    });
+
+    vec![
+        from_middleware(Arc::new(LoggerMiddleware)),
+        from_middleware(Arc::new(CodecMiddleware::new(
+            CodecRegistry::new().register(JsonCodec),
+        ))),
+        from_middleware(Arc::new(router)),
+    ]
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let pipeline: Arc<[_]> = app().into();
+    Server::bind("127.0.0.1:8080")
+        .await?
+        .run(move |req| Next::new(Arc::clone(&pipeline)).run(Context::new(req)))
+        .await?;
+    Ok(())
+}
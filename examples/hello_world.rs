@@ -0,0 +1,45 @@
+//! The smallest rttp application: one route, one middleware.
+//!
+//! ```text
+//! RUST_LOG=info cargo run --example hello_world
+//! curl http://127.0.0.1:8080/hello/world
+//! ```
+
+use std::sync::Arc;
+
+use rttp::{
+    Response, Router, Server, StatusCode,
+    context::Context,
+    middleware::{LoggerMiddleware, MiddlewareHandler, Next, from_middleware},
+};
+
+/// Builds the application pipeline: request logging followed by the router.
+pub fn app() -> Vec<MiddlewareHandler> {
+    let mut router = Router::new();
+    router.get("/", |_ctx| async {
+        Response::new(StatusCode::Ok).body("Hello, World!")
+    });
+    router.get("/hello/:name", |ctx: Context| async move {
+        let name = ctx.params().get("name").unwrap_or("stranger").to_owned();
+        Response::new(StatusCode::Ok).body(format!("Hello, {name}!"))
+    });
+
+    vec![
+        from_middleware(Arc::new(LoggerMiddleware)),
+        from_middleware(Arc::new(router)),
+    ]
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let pipeline: Arc<[_]> = app().into();
+    Server::bind("127.0.0.1:8080")
+        .await?
+        .run(move |req| Next::new(Arc::clone(&pipeline)).run(Context::new(req)))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,131 @@
+//! OpenAI-compatible chat completions proxy with streamed responses.
+//!
+//! Demonstrates [`LlmProxy`]: client keys checked against an allow-list, a per-key rate
+//! limit, and `"stream": true` completions relayed to the caller chunk by chunk as the
+//! provider produces them.
+//!
+//! So the example runs offline, [`MockProvider`] plays the upstream: a [`Transport`]
+//! that answers with a canned completion, streamed as server-sent events word by word.
+//! Drop the `.transport(..)` call to forward to the real `upstream` URL instead.
+//!
+//! ```text
+//! RUST_LOG=info cargo run --example llm_proxy
+//! curl -N -H 'Authorization: Bearer demo-key' -H 'Content-Type: application/json' \
+//!     -d '{"model":"mock","stream":true,"messages":[{"role":"user","content":"hi"}]}' \
+//!     http://127.0.0.1:8080/v1/chat/completions
+//! ```
+
+use std::{sync::Arc, time::Duration};
+
+use rttp::{
+    Router, Server,
+    context::Context,
+    llm::{BodyReader, LlmProxy, StreamingResponse, Transport, TransportFuture},
+    middleware::{LoggerMiddleware, MiddlewareHandler, Next, from_middleware},
+};
+use serde_json::{Value, json};
+
+/// The key clients must present as `Authorization: Bearer <key>`.
+pub const CLIENT_KEY: &str = "demo-key";
+
+/// The completion [`MockProvider`] returns for every request.
+pub const REPLY: &str = "Hello from the mock provider!";
+
+/// A stand-in provider that answers every completion with [`REPLY`].
+pub struct MockProvider {
+    /// Pause between streamed words, to make the relay visible with `curl -N`.
+    pub delay: Duration,
+}
+
+impl Transport for MockProvider {
+    fn post<'a>(
+        &'a self,
+        _url: &'a str,
+        _headers: &'a [(String, String)],
+        body: &'a [u8],
+    ) -> TransportFuture<'a, StreamingResponse> {
+        let request: Value = serde_json::from_slice(body).unwrap_or_default();
+        let model = request["model"].as_str().unwrap_or("mock").to_owned();
+        let chunks = if request["stream"].as_bool().unwrap_or(false) {
+            let mut events: Vec<Vec<u8>> = REPLY
+                .split_inclusive(' ')
+                .map(|word| {
+                    let delta = json!({
+                        "object": "chat.completion.chunk",
+                        "model": model,
+                        "choices": [{ "index": 0, "delta": { "content": word } }],
+                    });
+                    format!("data: {delta}\n\n").into_bytes()
+                })
+                .collect();
+            events.push(b"data: [DONE]\n\n".to_vec());
+            events
+        } else {
+            let completion = json!({
+                "object": "chat.completion",
+                "model": model,
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": REPLY },
+                    "finish_reason": "stop",
+                }],
+            });
+            vec![completion.to_string().into_bytes()]
+        };
+        let body = Chunks {
+            chunks: chunks.into_iter(),
+            delay: self.delay,
+        };
+        Box::pin(async move {
+            Ok(StreamingResponse {
+                status: 200,
+                body: Box::new(body),
+            })
+        })
+    }
+}
+
+// Hands out prepared chunks, pausing before each.
+struct Chunks {
+    chunks: std::vec::IntoIter<Vec<u8>>,
+    delay: Duration,
+}
+
+impl BodyReader for Chunks {
+    fn chunk(&mut self) -> TransportFuture<'_, Option<Vec<u8>>> {
+        Box::pin(async move {
+            tokio::time::sleep(self.delay).await;
+            Ok(self.chunks.next())
+        })
+    }
+}
+
+/// Builds the application pipeline, with `delay` between streamed words.
+pub fn app(delay: Duration) -> Vec<MiddlewareHandler> {
+    let mut router = Router::new();
+    LlmProxy::new("https://api.openai.com/v1/chat/completions")
+        .api_key("sk-provider-key")
+        .allow_key(CLIENT_KEY)
+        .rate_limit(60, Duration::from_secs(60))
+        .transport(Arc::new(MockProvider { delay }))
+        .mount(&mut router);
+
+    vec![
+        from_middleware(Arc::new(LoggerMiddleware)),
+        from_middleware(Arc::new(router)),
+    ]
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let pipeline: Arc<[_]> = app(Duration::from_millis(150)).into();
+    Server::bind("127.0.0.1:8080")
+        .await?
+        .run(move |req| Next::new(Arc::clone(&pipeline)).run(Context::new(req)))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,341 @@
+//! JSON CRUD API over a pooled database connection, with cached reads.
+//!
+//! Demonstrates path parameters, the codec layer ([`Body`] extraction and content
+//! negotiation), typed queries through a [`Pool`], and [`CacheMiddleware`] answering
+//! repeat reads until a write purges them by tag.
+//!
+//! There is no database driver in the tree yet, so [`MemoryDb`] stands in for one: a
+//! [`Connection`] that understands exactly the statements this app runs. Swap it for a
+//! real driver and the handlers stay as they are.
+//!
+//! ```text
+//! RUST_LOG=info cargo run --example todo_api
+//! curl -X POST -H 'Content-Type: application/json' -d '{"title":"write docs"}' \
+//!     http://127.0.0.1:8080/todos
+//! curl -i http://127.0.0.1:8080/todos
+//! ```
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rttp::{
+    Response, Router, Server, StatusCode,
+    cache::{Cache, CacheMiddleware, CacheTagsExt, MemoryCache},
+    codec::{Body, CodecMiddleware, CodecRegistry, FormCodec, JsonCodec},
+    context::Context,
+    database::{Connection, DbError, DbFuture, FromRow, Pool, PoolOptions, Row, Value, query_as},
+    middleware::{LoggerMiddleware, MiddlewareHandler, Next, from_middleware},
+};
+use serde::{Deserialize, Serialize};
+
+/// A stored todo item.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Todo {
+    pub id: i64,
+    pub title: String,
+    pub done: bool,
+}
+
+/// Request body for creating a todo.
+#[derive(Debug, Deserialize)]
+pub struct NewTodo {
+    pub title: String,
+}
+
+/// Request body for updating a todo; omitted fields are left unchanged.
+#[derive(Debug, Deserialize)]
+pub struct TodoPatch {
+    pub title: Option<String>,
+    pub done: Option<bool>,
+}
+
+const SELECT_ALL: &str = "SELECT id, title, done FROM todos ORDER BY id";
+const SELECT_ONE: &str = "SELECT id, title, done FROM todos WHERE id = $1";
+const INSERT: &str = "INSERT INTO todos (title) VALUES ($1) RETURNING id, title, done";
+const UPDATE: &str = "UPDATE todos SET title = COALESCE($2, title), done = COALESCE($3, done) \
+                      WHERE id = $1 RETURNING id, title, done";
+const DELETE: &str = "DELETE FROM todos WHERE id = $1 RETURNING id";
+
+/// The cache tag on every response that lists or shows todos.
+const TAG: &str = "todos";
+
+// ── Stand-in driver ───────────────────────────────────────────────────────────
+
+/// An in-process "database server" holding the `todos` table.
+#[derive(Clone, Default)]
+pub struct MemoryDb {
+    table: Arc<Mutex<Table>>,
+}
+
+#[derive(Default)]
+struct Table {
+    rows: Vec<Todo>,
+    next_id: i64,
+}
+
+/// A connection to a [`MemoryDb`].
+pub struct MemoryConn {
+    table: Arc<Mutex<Table>>,
+}
+
+impl MemoryDb {
+    /// Opens a connection.
+    pub fn connect(&self) -> MemoryConn {
+        MemoryConn {
+            table: Arc::clone(&self.table),
+        }
+    }
+}
+
+impl Connection for MemoryConn {
+    fn ping(&mut self) -> DbFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn query<'a>(&'a mut self, sql: &'a str, params: &'a [Value]) -> DbFuture<'a, Vec<Row>> {
+        let result = self.run(sql, params);
+        Box::pin(async move { result })
+    }
+}
+
+impl MemoryConn {
+    fn run(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, DbError> {
+        let mut table = self.table.lock().unwrap();
+        let id = match params.first() {
+            Some(Value::Int(id)) => Some(*id),
+            _ => None,
+        };
+        let position = |table: &Table| table.rows.iter().position(|todo| Some(todo.id) == id);
+        let rows: Vec<Todo> = match sql {
+            SELECT_ALL => table.rows.clone(),
+            SELECT_ONE => position(&table)
+                .map(|at| table.rows[at].clone())
+                .into_iter()
+                .collect(),
+            INSERT => {
+                let Some(Value::Text(title)) = params.first() else {
+                    return Err(DbError::Backend("title must be text".into()));
+                };
+                table.next_id += 1;
+                let todo = Todo {
+                    id: table.next_id,
+                    title: title.clone(),
+                    done: false,
+                };
+                table.rows.push(todo.clone());
+                vec![todo]
+            }
+            UPDATE => match position(&table) {
+                Some(at) => {
+                    let todo = &mut table.rows[at];
+                    if let Some(Value::Text(title)) = params.get(1) {
+                        todo.title = title.clone();
+                    }
+                    if let Some(Value::Bool(done)) = params.get(2) {
+                        todo.done = *done;
+                    }
+                    vec![todo.clone()]
+                }
+                None => Vec::new(),
+            },
+            DELETE => position(&table)
+                .map(|at| table.rows.remove(at))
+                .into_iter()
+                .collect(),
+            _ => return Err(DbError::Backend(format!("unsupported statement {sql:?}"))),
+        };
+        let columns: Arc<[String]> = Arc::from(["id".to_owned(), "title".into(), "done".into()]);
+        Ok(rows
+            .into_iter()
+            .map(|todo| {
+                let values = vec![todo.id.into(), todo.title.into(), todo.done.into()];
+                Row::new(Arc::clone(&columns), values)
+            })
+            .collect())
+    }
+}
+
+// ── Application ───────────────────────────────────────────────────────────────
+
+// Parses the `:id` path parameter.
+fn todo_id(ctx: &Context) -> Option<i64> {
+    ctx.params().get("id")?.parse().ok()
+}
+
+// Answers `503` for a database error.
+fn unavailable(e: &DbError) -> Response {
+    tracing::error!(error = %e, "database error");
+    Response::new(StatusCode::ServiceUnavailable)
+}
+
+/// Builds the application pipeline over `db`.
+pub async fn app(db: MemoryDb) -> Vec<MiddlewareHandler> {
+    let pool: Pool<MemoryConn> = PoolOptions::new()
+        .max_size(8)
+        .connect(move || {
+            let db = db.clone();
+            async move { Ok(db.connect()) }
+        })
+        .await
+        .expect("the stand-in database always connects");
+    let cache: Arc<dyn Cache> = Arc::new(MemoryCache::new(1_000));
+    let mut router = Router::new();
+
+    let db = pool.clone();
+    router.get("/todos", move |ctx: Context| {
+        let db = db.clone();
+        async move {
+            let todos = match db.acquire().await {
+                Ok(mut conn) => query_as::<Todo>(SELECT_ALL).fetch_all(&mut conn).await,
+                Err(e) => Err(e),
+            };
+            match todos {
+                Ok(todos) => {
+                    ctx.cache_tags([TAG]);
+                    Body(todos).respond(&ctx, StatusCode::Ok)
+                }
+                Err(e) => unavailable(&e),
+            }
+        }
+    });
+
+    let (db, purge) = (pool.clone(), Arc::clone(&cache));
+    router.post("/todos", move |ctx: Context| {
+        let (db, purge) = (db.clone(), Arc::clone(&purge));
+        async move {
+            let Body(new) = match Body::<NewTodo>::from_context(&ctx) {
+                Ok(body) => body,
+                Err(e) => return e.into_response(),
+            };
+            let todo = match db.acquire().await {
+                Ok(mut conn) => {
+                    query_as::<Todo>(INSERT)
+                        .bind(new.title)
+                        .fetch_one(&mut conn)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            match todo {
+                Ok(todo) => {
+                    let _ = purge.invalidate_tag(TAG).await;
+                    Body(todo).respond(&ctx, StatusCode::Created)
+                }
+                Err(e) => unavailable(&e),
+            }
+        }
+    });
+
+    let db = pool.clone();
+    router.get("/todos/:id", move |ctx: Context| {
+        let db = db.clone();
+        async move {
+            let Some(id) = todo_id(&ctx) else {
+                return Response::new(StatusCode::NotFound);
+            };
+            let todo = match db.acquire().await {
+                Ok(mut conn) => {
+                    query_as::<Todo>(SELECT_ONE)
+                        .bind(id)
+                        .fetch_optional(&mut conn)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            match todo {
+                Ok(Some(todo)) => {
+                    ctx.cache_tags([TAG]);
+                    Body(todo).respond(&ctx, StatusCode::Ok)
+                }
+                Ok(None) => Response::new(StatusCode::NotFound),
+                Err(e) => unavailable(&e),
+            }
+        }
+    });
+
+    let (db, purge) = (pool.clone(), Arc::clone(&cache));
+    router.patch("/todos/:id", move |ctx: Context| {
+        let (db, purge) = (db.clone(), Arc::clone(&purge));
+        async move {
+            let Body(patch) = match Body::<TodoPatch>::from_context(&ctx) {
+                Ok(body) => body,
+                Err(e) => return e.into_response(),
+            };
+            let Some(id) = todo_id(&ctx) else {
+                return Response::new(StatusCode::NotFound);
+            };
+            let todo = match db.acquire().await {
+                Ok(mut conn) => {
+                    query_as::<Todo>(UPDATE)
+                        .bind(id)
+                        .bind(patch.title.map_or(Value::Null, Value::from))
+                        .bind(patch.done.map_or(Value::Null, Value::from))
+                        .fetch_optional(&mut conn)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            match todo {
+                Ok(Some(todo)) => {
+                    let _ = purge.invalidate_tag(TAG).await;
+                    Body(todo).respond(&ctx, StatusCode::Ok)
+                }
+                Ok(None) => Response::new(StatusCode::NotFound),
+                Err(e) => unavailable(&e),
+            }
+        }
+    });
+
+    let (db, purge) = (pool, Arc::clone(&cache));
+    router.delete("/todos/:id", move |ctx: Context| {
+        let (db, purge) = (db.clone(), Arc::clone(&purge));
+        async move {
+            let Some(id) = todo_id(&ctx) else {
+                return Response::new(StatusCode::NotFound);
+            };
+            let removed = match db.acquire().await {
+                Ok(mut conn) => {
+                    rttp::database::query(DELETE)
+                        .bind(id)
+                        .fetch_optional(&mut conn)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            match removed {
+                Ok(Some(_)) => {
+                    let _ = purge.invalidate_tag(TAG).await;
+                    Response::new(StatusCode::NoContent)
+                }
+                Ok(None) => Response::new(StatusCode::NotFound),
+                Err(e) => unavailable(&e),
+            }
+        }
+    });
+
+    let caching = CacheMiddleware::new(cache).default_ttl(Duration::from_secs(60));
+    vec![
+        from_middleware(Arc::new(LoggerMiddleware)),
+        from_middleware(Arc::new(CodecMiddleware::new(
+            CodecRegistry::new().register(JsonCodec).register(FormCodec),
+        ))),
+        from_middleware(Arc::new(caching)),
+        from_middleware(Arc::new(router)),
+    ]
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let pipeline: Arc<[_]> = app(MemoryDb::default()).await.into();
+    Server::bind("127.0.0.1:8080")
+        .await?
+        .run(move |req| Next::new(Arc::clone(&pipeline)).run(Context::new(req)))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,88 @@
+//! Procedural macros for [rttp](https://docs.rs/rttp).
+//!
+//! Use them through their re-exports in `rttp`; the generated code refers to `::rttp`
+//! paths.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, LitStr, parse_macro_input};
+
+/// Derives `rttp::database::FromRow` for a struct.
+///
+/// Named fields are read from the column of the same name, or the one given with
+/// `#[row(rename = "column")]`; tuple-struct fields are read by position. Every field
+/// type must implement `FromValue`.
+#[proc_macro_derive(FromRow, attributes(row))]
+pub fn derive_from_row(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_from_row(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_from_row(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "FromRow can only be derived for structs",
+        ));
+    };
+
+    let body = match &data.fields {
+        Fields::Named(fields) => {
+            let mut inits = Vec::new();
+            for field in &fields.named {
+                let ident = field.ident.as_ref().expect("named field");
+                let column = match rename(&field.attrs)? {
+                    Some(name) => name.value(),
+                    None => ident.to_string().trim_start_matches("r#").to_owned(),
+                };
+                inits.push(quote! { #ident: row.try_get(#column)? });
+            }
+            quote! { Self { #(#inits),* } }
+        }
+        Fields::Unnamed(fields) => {
+            let mut inits = Vec::new();
+            for (index, field) in fields.unnamed.iter().enumerate() {
+                if let Some(name) = rename(&field.attrs)? {
+                    return Err(syn::Error::new_spanned(
+                        name,
+                        "`rename` is only supported on named fields",
+                    ));
+                }
+                inits.push(quote! { row.try_get(#index)? });
+            }
+            quote! { Self(#(#inits),*) }
+        }
+        Fields::Unit => quote! { Self },
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::rttp::database::FromRow for #name #ty_generics #where_clause {
+            fn from_row(
+                row: &::rttp::database::Row,
+            ) -> ::core::result::Result<Self, ::rttp::database::DbError> {
+                ::core::result::Result::Ok(#body)
+            }
+        }
+    })
+}
+
+// Reads `#[row(rename = "...")]`, rejecting unknown keys.
+fn rename(attrs: &[syn::Attribute]) -> syn::Result<Option<LitStr>> {
+    let mut rename = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("row")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                rename = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported `row` attribute, expected `rename`"))
+            }
+        })?;
+    }
+    Ok(rename)
+}
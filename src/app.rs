@@ -0,0 +1,502 @@
+//! Application bootstrap — one builder for the pieces every service wires up in `main`.
+//!
+//! [`App`] assembles the middleware pipeline (server timing, metrics, health, shared
+//! state, configuration, global middleware, then the router), binds a [`Server`], and shuts
+//! down gracefully: on Ctrl-C or `SIGTERM` it stops accepting connections, waits for
+//! in-flight requests to finish, and runs the registered shutdown hooks.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rttp::{App, Response, Router, StatusCode, context::Context};
+//! use rttp::app::StateExt;
+//! use rttp::middleware::{Health, LoggerMiddleware, Metrics};
+//!
+//! #[derive(Clone)]
+//! struct Greeting(&'static str);
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), rttp::ServerError> {
+//!     let mut router = Router::new();
+//!     router.get("/", |ctx: Context| async move {
+//!         let greeting = ctx.state::<Greeting>().map_or("hi", |g| g.0);
+//!         Response::new(StatusCode::Ok).body(greeting)
+//!     });
+//!
+//!     App::new()
+//!         .router(router)
+//!         .state(Greeting("Hello!"))
+//!         .middleware(LoggerMiddleware)
+//!         .health(Health::new())
+//!         .metrics(Metrics::new())
+//!         .run("0.0.0.0:8080")
+//!         .await
+//! }
+//! ```
+
+use std::{
+    any::Any,
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+use crate::{
+    Response, Router, Server, ServerError,
+    config::Config,
+    context::{Context, Extensions},
+    http::request::ParseLimits,
+    middleware::{
+        Health, HealthMiddleware, Metrics, MetricsMiddleware, Middleware, MiddlewareHandler, Next,
+        ServerTimingMiddleware, from_middleware,
+    },
+};
+
+type ShutdownHook = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+// Inserts one piece of shared state into a request's extensions.
+type StateInsert = Arc<dyn Fn(&mut Extensions) + Send + Sync>;
+
+/// Builds and runs an application.
+///
+/// Requests pass through, in order: the [`server_timing`](Self::server_timing)
+/// collector, the [`metrics`](Self::metrics) recorder, the [`health`](Self::health) endpoint, [`state`](Self::state) injection, each
+/// [`middleware`](Self::middleware) and [`config`](Self::config) in the order added,
+/// and finally the [`router`](Self::router).
+#[must_use]
+pub struct App {
+    router: Option<Router>,
+    middleware: Vec<MiddlewareHandler>,
+    state: Vec<StateInsert>,
+    health: Option<Health>,
+    metrics: Option<Metrics>,
+    server_timing: bool,
+    limits: ParseLimits,
+    #[cfg(feature = "tls")]
+    tls: Option<crate::server::tls::TlsConfig>,
+    grace_period: Duration,
+    on_shutdown: Vec<ShutdownHook>,
+    in_flight: Arc<InFlight>,
+}
+
+impl App {
+    /// Creates an application with no routes and a 30 second shutdown grace period.
+    pub fn new() -> Self {
+        Self {
+            router: None,
+            middleware: Vec::new(),
+            state: Vec::new(),
+            health: None,
+            metrics: None,
+            server_timing: true,
+            limits: ParseLimits::default(),
+            #[cfg(feature = "tls")]
+            tls: None,
+            grace_period: Duration::from_secs(30),
+            on_shutdown: Vec::new(),
+            in_flight: Arc::default(),
+        }
+    }
+
+    /// Routes requests that no earlier layer answered.
+    pub fn router(mut self, router: Router) -> Self {
+        self.router = Some(router);
+        self
+    }
+
+    /// Shares `value` with every handler, as `ctx.state::<T>()` through [`StateExt`].
+    ///
+    /// Each request gets a clone, so wrap large or mutable state in an [`Arc`].
+    pub fn state<T: Clone + Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.state
+            .push(Arc::new(move |extensions: &mut Extensions| {
+                extensions.insert(value.clone());
+            }));
+        self
+    }
+
+    /// Shares a loaded configuration with every handler, as `ctx.config::<T>()`
+    /// through [`ConfigExt`](crate::config::ConfigExt).
+    pub fn config<T: Send + Sync + 'static>(self, config: Config<T>) -> Self {
+        self.middleware(config)
+    }
+
+    /// Adds a middleware that runs for every request, after those added before it.
+    pub fn middleware(self, middleware: impl Middleware + 'static) -> Self {
+        self.layer(from_middleware(Arc::new(middleware)))
+    }
+
+    /// Adds an already type-erased middleware, as [`middleware`](Self::middleware).
+    pub fn layer(mut self, handler: MiddlewareHandler) -> Self {
+        self.middleware.push(handler);
+        self
+    }
+
+    /// Serves the checks registered with `health` at `GET /health`.
+    pub fn health(mut self, health: Health) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Records request metrics into `metrics` and serves them at `GET /metrics`.
+    pub fn metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Turns the `Server-Timing` header off or on (the default), which carries the
+    /// durations handlers record with [`TimingExt::timing`](crate::middleware::TimingExt).
+    pub fn server_timing(mut self, enabled: bool) -> Self {
+        self.server_timing = enabled;
+        self
+    }
+
+    /// Sets the query-parameter and cookie caps, as [`Server::parse_limits`].
+    pub fn parse_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Serves HTTPS, as [`Server::tls`].
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, config: crate::server::tls::TlsConfig) -> Self {
+        self.tls = Some(config);
+        self
+    }
+
+    /// Sets how long shutdown waits for in-flight requests before giving up on them.
+    pub fn grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    /// Runs `hook` once the server has stopped and in-flight requests have finished,
+    /// for draining other subsystems such as a
+    /// [`TaskQueue`](crate::background::TaskQueue). Hooks run in the order added.
+    pub fn on_shutdown<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_shutdown.push(Box::new(move || Box::pin(hook())));
+        self
+    }
+
+    /// Assembles the middleware pipeline without starting a server, for tests with
+    /// [`TestClient::pipeline`](crate::testing::TestClient::pipeline).
+    pub fn into_pipeline(self) -> Vec<MiddlewareHandler> {
+        self.split().0
+    }
+
+    fn split(self) -> (Vec<MiddlewareHandler>, Parts) {
+        let mut pipeline = vec![from_middleware(Arc::new(Track(Arc::clone(
+            &self.in_flight,
+        ))))];
+        if self.server_timing {
+            pipeline.push(from_middleware(Arc::new(ServerTimingMiddleware::new())));
+        }
+        if let Some(metrics) = self.metrics {
+            pipeline.push(from_middleware(Arc::new(
+                MetricsMiddleware::new(metrics).endpoint("/metrics"),
+            )));
+        }
+        if let Some(health) = self.health {
+            pipeline.push(from_middleware(Arc::new(HealthMiddleware::new(health))));
+        }
+        if !self.state.is_empty() {
+            pipeline.push(from_middleware(Arc::new(State(self.state))));
+        }
+        pipeline.extend(self.middleware);
+        if let Some(router) = self.router {
+            pipeline.push(from_middleware(Arc::new(router)));
+        }
+        let parts = Parts {
+            limits: self.limits,
+            #[cfg(feature = "tls")]
+            tls: self.tls,
+            grace_period: self.grace_period,
+            on_shutdown: self.on_shutdown,
+            in_flight: self.in_flight,
+        };
+        (pipeline, parts)
+    }
+
+    /// Serves on `addr` until Ctrl-C or `SIGTERM`, then shuts down gracefully.
+    ///
+    /// # Errors
+    ///
+    /// As for [`Server::bind`] and [`Server::run`].
+    pub async fn run(self, addr: impl AsRef<str>) -> Result<(), ServerError> {
+        self.run_until(addr, shutdown_signal()).await
+    }
+
+    /// Serves on `addr` until `shutdown` resolves, then stops accepting connections,
+    /// waits up to the [grace period](Self::grace_period) for in-flight requests, and
+    /// runs the [shutdown hooks](Self::on_shutdown).
+    ///
+    /// # Errors
+    ///
+    /// As for [`Server::bind`] and [`Server::run`].
+    pub async fn run_until(
+        self,
+        addr: impl AsRef<str>,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), ServerError> {
+        let server = Server::bind(addr).await?;
+        self.serve(server, shutdown).await
+    }
+
+    /// As [`run_until`](Self::run_until), on an already bound server.
+    ///
+    /// # Errors
+    ///
+    /// As for [`Server::run`].
+    pub async fn serve(
+        self,
+        server: Server,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), ServerError> {
+        let (pipeline, parts) = self.split();
+        let pipeline: Arc<[MiddlewareHandler]> = pipeline.into();
+        let server = server.parse_limits(parts.limits);
+        #[cfg(feature = "tls")]
+        let server = match parts.tls {
+            Some(tls) => server.tls(tls)?,
+            None => server,
+        };
+        server
+            .run_until(
+                move |request| Next::new(Arc::clone(&pipeline)).run(Context::new(request)),
+                shutdown,
+            )
+            .await?;
+
+        let drained = tokio::time::timeout(parts.grace_period, parts.in_flight.idle()).await;
+        if drained.is_err() {
+            warn!(
+                requests = parts.in_flight.count.load(Ordering::SeqCst),
+                "shutdown grace period elapsed with requests still in flight"
+            );
+        }
+        for hook in parts.on_shutdown {
+            hook().await;
+        }
+        info!("shutdown complete");
+        Ok(())
+    }
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// What `serve` needs besides the pipeline.
+struct Parts {
+    limits: ParseLimits,
+    #[cfg(feature = "tls")]
+    tls: Option<crate::server::tls::TlsConfig>,
+    grace_period: Duration,
+    on_shutdown: Vec<ShutdownHook>,
+    in_flight: Arc<InFlight>,
+}
+
+// Completes on Ctrl-C or, on Unix, `SIGTERM`.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
+    }
+}
+
+// Counts requests being handled, so shutdown can wait for them.
+#[derive(Default)]
+struct InFlight {
+    count: AtomicUsize,
+    idle: Notify,
+}
+
+impl InFlight {
+    async fn idle(&self) {
+        loop {
+            let notified = self.idle.notified();
+            if self.count.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+// Decrements the in-flight count when the request finishes or is dropped.
+struct InFlightGuard(Arc<InFlight>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+struct Track(Arc<InFlight>);
+
+impl Middleware for Track {
+    fn handle(&self, ctx: Context, next: Next) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        self.0.count.fetch_add(1, Ordering::SeqCst);
+        let guard = InFlightGuard(Arc::clone(&self.0));
+        Box::pin(async move {
+            let response = next.run(ctx).await;
+            drop(guard);
+            response
+        })
+    }
+}
+
+struct State(Vec<StateInsert>);
+
+impl Middleware for State {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        for insert in &self.0 {
+            insert(ctx.extensions_mut());
+        }
+        next.run(ctx)
+    }
+}
+
+/// Adds access to [`App::state`] values to [`Context`].
+pub trait StateExt {
+    /// Returns the shared value of type `T`, if the app provides one.
+    fn state<T: Any + Send + Sync>(&self) -> Option<&T>;
+}
+
+impl StateExt for Context {
+    fn state<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.extensions().get::<T>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+        sync::oneshot,
+    };
+
+    use super::*;
+    use crate::{StatusCode, config::ConfigExt, testing::TestClient};
+
+    #[derive(Clone)]
+    struct Greeting(String);
+
+    #[tokio::test]
+    async fn wires_state_config_health_and_metrics() {
+        let mut router = Router::new();
+        router.get("/", |ctx: Context| async move {
+            let greeting = ctx.state::<Greeting>().map(|g| g.0.clone());
+            let limit = ctx.config::<u32>().map(|c| *c);
+            Response::new(StatusCode::Ok).body(format!("{greeting:?} {limit:?}"))
+        });
+        let metrics = Metrics::new();
+        let app = App::new()
+            .router(router)
+            .state(Greeting("hello".into()))
+            .config(Config::new(5_u32))
+            .health(Health::new())
+            .metrics(metrics.clone());
+
+        let mut client = TestClient::pipeline(app.into_pipeline());
+        client
+            .get("/")
+            .await
+            .assert_status(StatusCode::Ok)
+            .assert_text("Some(\"hello\") Some(5)");
+        client.get("/health").await.assert_status(StatusCode::Ok);
+        let scrape = client.get("/metrics").await.text();
+        assert!(scrape.contains("status=\"200\""), "{scrape}");
+    }
+
+    #[tokio::test]
+    async fn shutdown_drains_requests_then_runs_hooks() {
+        let (started_tx, started_rx) = oneshot::channel::<()>();
+        let started_tx = Arc::new(std::sync::Mutex::new(Some(started_tx)));
+        let finished = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let mut router = Router::new();
+        let done = Arc::clone(&finished);
+        router.get("/slow", move |_ctx: Context| {
+            let started = started_tx.lock().unwrap().take();
+            let done = Arc::clone(&done);
+            async move {
+                if let Some(started) = started {
+                    let _ = started.send(());
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                done.store(true, Ordering::SeqCst);
+                Response::new(StatusCode::Ok).body("done")
+            }
+        });
+
+        let (hook_tx, hook_rx) = oneshot::channel();
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+        let server = Server::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr();
+        let app = App::new().router(router).on_shutdown(move || async move {
+            let _ = hook_tx.send(finished.load(Ordering::SeqCst));
+        });
+        let running = tokio::spawn(app.serve(server, async move {
+            let _ = stop_rx.await;
+        }));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /slow HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        started_rx.await.unwrap();
+        stop_tx.send(()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.ends_with("done"), "{response}");
+        tokio::time::timeout(Duration::from_secs(5), running)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(
+            hook_rx.await.expect("the shutdown hook should run"),
+            "hooks run after in-flight requests finish"
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! Dead-letter storage for jobs that failed permanently.
+
+use std::{collections::VecDeque, sync::Arc, time::SystemTime};
+
+use tracing::warn;
+
+use super::{Job, JobId};
+
+/// A job that exhausted its retries or failed with a non-retryable error, as listed by
+/// [`TaskQueue::dead_letters`](super::TaskQueue::dead_letters).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetter {
+    /// The job's ID; kept if the job is requeued.
+    pub id: JobId,
+    /// The job's [`name`](Job::name).
+    pub name: String,
+    /// The named queue it ran in.
+    pub queue: String,
+    /// How many times the job ran.
+    pub attempts: u32,
+    /// The error from the last attempt.
+    pub error: String,
+    /// When the last attempt failed.
+    pub failed_at: SystemTime,
+}
+
+// Oldest first; the oldest entry is dropped once `capacity` is reached.
+pub(super) struct DeadLetters {
+    capacity: usize,
+    entries: VecDeque<(DeadLetter, Arc<dyn Job>)>,
+}
+
+impl DeadLetters {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::new(),
+        }
+    }
+
+    pub(super) fn push(&mut self, letter: DeadLetter, job: Arc<dyn Job>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            if let Some((dropped, _)) = self.entries.pop_front() {
+                warn!(job = %dropped.name, id = %dropped.id, "dead-letter queue full; dropping oldest job");
+            }
+        }
+        self.entries.push_back((letter, job));
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(super) fn list(&self) -> Vec<DeadLetter> {
+        self.entries
+            .iter()
+            .map(|(letter, _)| letter.clone())
+            .collect()
+    }
+
+    pub(super) fn take(&mut self, id: JobId) -> Option<(DeadLetter, Arc<dyn Job>)> {
+        let index = self
+            .entries
+            .iter()
+            .position(|(letter, _)| letter.id == id)?;
+        self.entries.remove(index)
+    }
+
+    pub(super) fn take_all(&mut self) -> Vec<(DeadLetter, Arc<dyn Job>)> {
+        self.entries.drain(..).collect()
+    }
+
+    pub(super) fn clear(&mut self) -> usize {
+        let count = self.entries.len();
+        self.entries.clear();
+        count
+    }
+}
@@ -0,0 +1,213 @@
+//! Spawning background jobs from request handlers.
+//!
+//! The usual shape of an async API: the handler queues the work, answers
+//! `202 Accepted` at once with the job's ID and a status URL, and the client polls that
+//! URL until the job finishes.
+//!
+//! - [`TaskQueueMiddleware`] — makes a [`TaskQueue`] available to every request.
+//! - [`BackgroundExt`] — adds `ctx.spawn_background(job)` to [`Context`].
+//! - [`accepted`] — builds the `202 Accepted` response.
+//! - [`job_status`] — a handler for the status route, e.g. `GET /jobs/:id`.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use rttp::{Response, Router, StatusCode, context::Context, middleware::from_middleware};
+//! use rttp::background::{
+//!     BackgroundExt, JobError, TaskQueueMiddleware, TaskQueueOptions, accepted, job_status,
+//! };
+//!
+//! async fn export(ctx: Context) -> Response {
+//!     let job = || async {
+//!         // build the report
+//!         Ok::<_, JobError>(())
+//!     };
+//!     match ctx.spawn_background(job) {
+//!         Ok(id) => accepted(id, "/jobs"),
+//!         Err(_) => Response::new(StatusCode::ServiceUnavailable),
+//!     }
+//! }
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let queue = TaskQueueOptions::new().start();
+//! let mut router = Router::new();
+//! router.post("/reports", export);
+//! router.get("/jobs/:id", job_status(queue.clone()));
+//! let jobs = from_middleware(Arc::new(TaskQueueMiddleware::new(queue)));
+//! # }
+//! ```
+
+use std::pin::Pin;
+
+use serde_json::json;
+
+use super::{Job, JobId, JobStatus, QueueError, TaskQueue};
+use crate::{
+    Response, StatusCode,
+    context::Context,
+    middleware::{Middleware, Next},
+    router::IntoHandler,
+};
+
+/// Middleware that stores a [`TaskQueue`] handle in each request's extensions, where
+/// [`BackgroundExt`] finds it.
+pub struct TaskQueueMiddleware {
+    queue: TaskQueue,
+}
+
+impl TaskQueueMiddleware {
+    /// Creates a middleware that hands out `queue`. Jobs go to the named queue the
+    /// handle spawns onto (see [`TaskQueue::named`]).
+    pub fn new(queue: TaskQueue) -> Self {
+        Self { queue }
+    }
+}
+
+impl Middleware for TaskQueueMiddleware {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        ctx.extensions_mut().insert(self.queue.clone());
+        next.run(ctx)
+    }
+}
+
+/// Adds background job access to [`Context`].
+pub trait BackgroundExt {
+    /// Returns the queue installed by [`TaskQueueMiddleware`], if any.
+    fn task_queue(&self) -> Option<&TaskQueue>;
+
+    /// Queues `job` without waiting and returns its ID, for use with [`accepted`].
+    ///
+    /// # Errors
+    ///
+    /// [`QueueError::NotInstalled`] without a [`TaskQueueMiddleware`], otherwise the
+    /// error from [`TaskQueue::try_spawn`]. A full queue is better answered with
+    /// `503 Service Unavailable` than by holding the request open.
+    fn spawn_background(&self, job: impl Job) -> Result<JobId, QueueError>;
+}
+
+impl BackgroundExt for Context {
+    fn task_queue(&self) -> Option<&TaskQueue> {
+        self.extensions().get::<TaskQueue>()
+    }
+
+    fn spawn_background(&self, job: impl Job) -> Result<JobId, QueueError> {
+        self.task_queue()
+            .ok_or(QueueError::NotInstalled)?
+            .try_spawn(job)
+    }
+}
+
+/// Builds the `202 Accepted` response for a queued job: a `Location` header pointing at
+/// `{status_path}/{id}` and a JSON body with the ID and that URL.
+pub fn accepted(id: JobId, status_path: &str) -> Response {
+    let location = format!("{}/{id}", status_path.trim_end_matches('/'));
+    Response::new(StatusCode::Accepted)
+        .header("Location", location.clone())
+        .header("Content-Type", "application/json")
+        .body(json!({ "id": id.0, "status_url": location }).to_string())
+}
+
+/// Returns a handler that reports the status of the job named by the `:id` path
+/// parameter, as JSON such as `{"id":7,"status":"failed","error":"..."}`.
+///
+/// Unknown, forgotten, or malformed IDs get `404 Not Found`.
+pub fn job_status(queue: TaskQueue) -> impl IntoHandler {
+    move |ctx: Context| {
+        let status = ctx
+            .params()
+            .get("id")
+            .and_then(|id| id.parse::<JobId>().ok())
+            .and_then(|id| queue.status(id).map(|status| (id, status)));
+        async move {
+            let Some((id, status)) = status else {
+                return Response::new(StatusCode::NotFound);
+            };
+            let mut body = json!({ "id": id.0, "status": status.as_str() });
+            if let JobStatus::Failed { error } = &status {
+                body["error"] = json!(error);
+            }
+            Response::new(StatusCode::Ok)
+                .header("Content-Type", "application/json")
+                .body(body.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        Request,
+        background::{JobError, TaskQueueOptions},
+        context::PathParams,
+        middleware::{MiddlewareHandler, from_middleware},
+    };
+
+    fn request() -> Request {
+        Request::parse(b"POST /reports HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap()
+            .0
+    }
+
+    fn json(res: &Response) -> Value {
+        serde_json::from_slice(res.body_ref()).unwrap()
+    }
+
+    async fn poll(queue: &TaskQueue, id: &str) -> Response {
+        let mut params = PathParams::new();
+        params.insert("id", id);
+        let ctx = Context::with_params(request(), params);
+        job_status(queue.clone()).call(ctx).await
+    }
+
+    #[tokio::test]
+    async fn handlers_spawn_jobs_and_clients_poll_them() {
+        let queue = TaskQueueOptions::new().start();
+        let handler: MiddlewareHandler = Arc::new(|ctx: Context, _next: Next| {
+            Box::pin(async move {
+                let fail = || async { Err(JobError::failed("disk full")) };
+                match ctx.spawn_background(fail) {
+                    Ok(id) => accepted(id, "/jobs/"),
+                    Err(_) => Response::new(StatusCode::ServiceUnavailable),
+                }
+            })
+        });
+        let middleware = from_middleware(Arc::new(TaskQueueMiddleware::new(queue.clone())));
+        let ctx = Context::new(request());
+        let res = Next::new(vec![middleware, handler]).run(ctx).await;
+
+        assert_eq!(res.status(), StatusCode::Accepted);
+        assert_eq!(res.headers().get("Location"), Some("/jobs/1"));
+        assert_eq!(json(&res)["status_url"], "/jobs/1");
+
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+        let res = poll(&queue, "1").await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        let body = json(&res);
+        assert_eq!(
+            (body["id"].as_u64(), body["status"].as_str()),
+            (Some(1), Some("failed"))
+        );
+        assert_eq!(body["error"], "job failed: disk full");
+
+        assert_eq!(poll(&queue, "2").await.status(), StatusCode::NotFound);
+        assert_eq!(poll(&queue, "abc").await.status(), StatusCode::NotFound);
+    }
+
+    #[test]
+    fn spawning_without_the_middleware_fails() {
+        let ctx = Context::new(request());
+        let result = ctx.spawn_background(|| async { Ok(()) });
+        assert!(matches!(result, Err(QueueError::NotInstalled)));
+    }
+}
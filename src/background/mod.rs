@@ -1,16 +1,197 @@
 //! Background tasks — async task queues and scheduled jobs.
 //!
+//! ## Implemented
+//!
+//! - [`TaskQueue`] — in-process queue over a bounded Tokio channel, drained by a
+//!   configurable pool of workers. Configured with [`TaskQueueOptions`]: worker count,
+//!   queue capacity, and a default per-job timeout. [`TaskQueue::shutdown`] stops
+//!   accepting work and waits for everything already queued to finish.
+//!   [`TaskQueue::spawn_after`] and [`TaskQueue::spawn_at`] hold a job until a delay
+//!   elapses or a wall-clock time arrives.
+//! - Named queues — [`TaskQueueOptions::queue`] adds queues such as `critical` and
+//!   `bulk`, each with a [`QueueConfig`] priority, concurrency limit, and capacity;
+//!   [`TaskQueue::named`] spawns onto one. Limits and priorities can be changed while
+//!   running.
+//! - [`Job`] — the unit of work, implemented for async closures too.
+//! - [`RetryPolicy`] — max attempts and exponential backoff with jitter, retrying only
+//!   errors it classifies as transient. Set per queue or per job.
+//! - Dead-letter queue — jobs that fail permanently are kept as [`DeadLetter`]s that can
+//!   be listed, requeued, or purged through the [`TaskQueue`].
+//! - [`TaskQueue::status`] — a [`JobStatus`] per job, kept for a while after it finishes.
+//! - [`BackgroundExt::spawn_background`] — queues a job from a request handler; with
+//!   [`accepted`] and the [`job_status`] route it gives the `202 Accepted` and poll
+//!   shape of an async API. See the [`handler`] module.
+//! - [`RedisQueue`] — persistent queue in Redis, shared by every process that uses it,
+//!   with delayed jobs and retries held in a sorted set until due. See the [`redis`]
+//!   module.
+//!
 //! ## Planned Features
 //!
-//! - In-process async task queue (via Tokio channels)
-//! - Scheduled / cron jobs
-//! - Retry logic with exponential backoff
-//! - Dead letter queue for failed tasks
-//! - Optional Redis-backed persistent queue
+//! - Cron jobs
+//!
+//! ## Status: IN PROGRESS
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use rttp::background::{JobError, TaskQueueOptions};
 //!
-//! ## Status: PLANNED
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), rttp::background::QueueError> {
+//! let queue = TaskQueueOptions::new().workers(2).start();
+//!
+//! queue
+//!     .spawn(|| async {
+//!         // send an email, resize an image, ...
+//!         Ok::<_, JobError>(())
+//!     })
+//!     .await?;
+//!
+//! queue.shutdown(Duration::from_secs(30)).await?; // runs the job before returning
+//! assert_eq!(queue.stats().completed, 1);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod dead_letter;
+pub mod handler;
+pub mod queue;
+pub mod redis;
+pub mod retry;
+pub mod status;
+
+pub use dead_letter::DeadLetter;
+pub use handler::{BackgroundExt, TaskQueueMiddleware, accepted, job_status};
+pub use queue::{
+    DEFAULT_QUEUE, NamedQueueStats, QueueConfig, QueueStats, TaskQueue, TaskQueueOptions,
+};
+pub use redis::{RedisQueue, RedisQueueStats, RedisWorkers};
+pub use retry::RetryPolicy;
+pub use status::JobStatus;
+
+use std::{fmt, future::Future, num::ParseIntError, pin::Pin, str::FromStr, time::Duration};
+
+use thiserror::Error;
+
+use crate::redis::RedisError;
+
+/// Errors produced by running a [`Job`].
+#[derive(Debug, Error)]
+pub enum JobError {
+    #[error("job failed: {0}")]
+    Failed(String),
+
+    #[error("job timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("job panicked: {0}")]
+    Panicked(String),
+
+    /// A failure that retrying cannot fix, such as invalid input.
+    #[error("job failed permanently: {0}")]
+    Permanent(String),
+}
+
+impl JobError {
+    /// Creates a [`JobError::Failed`] from any displayable error.
+    pub fn failed(error: impl fmt::Display) -> Self {
+        Self::Failed(error.to_string())
+    }
+
+    /// Creates a [`JobError::Permanent`] from any displayable error.
+    pub fn permanent(error: impl fmt::Display) -> Self {
+        Self::Permanent(error.to_string())
+    }
+
+    /// Returns `true` for failures worth retrying: [`Failed`](Self::Failed) and
+    /// [`Timeout`](Self::Timeout).
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Failed(_) | Self::Timeout(_))
+    }
+}
+
+/// Errors produced by a [`TaskQueue`].
+#[derive(Debug, Error)]
+pub enum QueueError {
+    #[error("the task queue is full")]
+    Full,
+
+    #[error("the task queue is shut down")]
+    Closed,
+
+    #[error("timed out draining the task queue with {remaining} job(s) unfinished")]
+    DrainTimeout { remaining: usize },
+
+    #[error("no dead-lettered job with id {0}")]
+    NotFound(JobId),
+
+    #[error("no task queue named {0:?}")]
+    UnknownQueue(String),
+
+    #[error("no task queue on this request; install a TaskQueueMiddleware")]
+    NotInstalled,
+
+    #[error("task queue storage error: {0}")]
+    Redis(#[from] RedisError),
+
+    #[error("job payload does not serialize: {0}")]
+    Payload(#[from] serde_json::Error),
+}
+
+/// Boxed future returned by [`Job::run`].
+pub type JobFuture<'a> = Pin<Box<dyn Future<Output = Result<(), JobError>> + Send + 'a>>;
+
+/// A unit of background work.
+///
+/// Implemented for any `Fn() -> impl Future<Output = Result<(), JobError>>` closure.
+pub trait Job: Send + Sync + 'static {
+    /// Does the work.
+    fn run(&self) -> JobFuture<'_>;
+
+    /// A name for logs. Defaults to the type name.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    /// How long one run may take before it is cancelled with [`JobError::Timeout`].
+    /// `None` (the default) uses the queue's
+    /// [`job_timeout`](TaskQueueOptions::job_timeout).
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// How failed runs are retried. `None` (the default) uses the queue's
+    /// [`retry`](TaskQueueOptions::retry) policy.
+    fn retry_policy(&self) -> Option<RetryPolicy> {
+        None
+    }
+}
+
+impl<F, Fut> Job for F
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), JobError>> + Send + 'static,
+{
+    fn run(&self) -> JobFuture<'_> {
+        Box::pin(self())
+    }
+}
+
+/// Identifies a job accepted by a [`TaskQueue`]; unique per queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct JobId(pub u64);
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
 
-// TODO: Implement background task system
+impl FromStr for JobId {
+    type Err = ParseIntError;
 
-/// Placeholder — will become the `TaskQueue` type.
-pub struct TaskQueue;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
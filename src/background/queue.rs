@@ -0,0 +1,1330 @@
+//! In-process task queue.
+//!
+//! A [`TaskQueue`] holds one or more named queues, drained by a shared, fixed set of
+//! worker tasks. Each job runs in its own Tokio task, so a panic or timeout fails that
+//! job without taking its worker down.
+//!
+//! Workers take the oldest job from the highest-priority queue that has one waiting
+//! and is below its concurrency limit (see [`QueueConfig`]), so bulk work queued behind
+//! latency-sensitive jobs waits its turn instead of delaying them.
+//!
+//! Delayed jobs, and failed jobs that their [`RetryPolicy`] allows to run again, are
+//! held by a scheduler task that puts each one back on its queue once it is due;
+//! workers are free in the meantime. Jobs that fail for good are kept in a bounded
+//! dead-letter list.
+
+use std::{
+    any::Any,
+    cmp::Reverse,
+    collections::{BTreeMap, VecDeque},
+    pin::pin,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use tokio::{
+    sync::{Notify, mpsc},
+    task::JoinHandle,
+};
+use tracing::{debug, warn};
+
+use super::{
+    DeadLetter, Job, JobError, JobId, JobStatus, QueueError, RetryPolicy, dead_letter::DeadLetters,
+    status::Statuses,
+};
+use crate::clock::{self, Clock};
+
+/// The name of the queue every [`TaskQueue`] has, used unless
+/// [`named`](TaskQueue::named) selects another.
+pub const DEFAULT_QUEUE: &str = "default";
+
+/// Configuration for a [`TaskQueue`].
+///
+/// Defaults: 4 workers, room for 1024 waiting jobs per named queue, no job timeout, no
+/// retries, up to 1000 dead-lettered jobs, statuses kept for the last 10,000 finished
+/// jobs, and only the [`DEFAULT_QUEUE`].
+#[derive(Debug, Clone)]
+pub struct TaskQueueOptions {
+    workers: usize,
+    capacity: usize,
+    job_timeout: Option<Duration>,
+    retry: RetryPolicy,
+    dead_letter_capacity: usize,
+    status_capacity: usize,
+    queues: Vec<(String, QueueConfig)>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for TaskQueueOptions {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            capacity: 1024,
+            job_timeout: None,
+            retry: RetryPolicy::none(),
+            dead_letter_capacity: 1000,
+            status_capacity: 10_000,
+            queues: Vec::new(),
+            clock: clock::system(),
+        }
+    }
+}
+
+impl TaskQueueOptions {
+    /// Creates the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how many jobs run at once, across all named queues.
+    #[must_use]
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Sets how many accepted jobs may wait for a worker in each named queue, unless the
+    /// queue sets its own [`capacity`](QueueConfig::capacity). Once full,
+    /// [`spawn`](TaskQueue::spawn) waits and [`try_spawn`](TaskQueue::try_spawn) fails.
+    #[must_use]
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets how long a job may run before it is cancelled, unless the job sets its own
+    /// [`timeout`](Job::timeout). `None` lets jobs run forever.
+    #[must_use]
+    pub fn job_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.job_timeout = timeout;
+        self
+    }
+
+    /// Sets how failed jobs are retried, unless the job sets its own
+    /// [`retry_policy`](Job::retry_policy).
+    #[must_use]
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Sets how many permanently failed jobs are kept; the oldest is dropped to make
+    /// room. Zero disables the dead-letter queue.
+    #[must_use]
+    pub fn dead_letter_capacity(mut self, capacity: usize) -> Self {
+        self.dead_letter_capacity = capacity;
+        self
+    }
+
+    /// Sets how many finished jobs [`TaskQueue::status`] remembers; older ones are
+    /// forgotten. Queued, scheduled, and running jobs are always tracked.
+    #[must_use]
+    pub fn status_capacity(mut self, capacity: usize) -> Self {
+        self.status_capacity = capacity;
+        self
+    }
+
+    /// Times delayed jobs, retry backoff, and dead-letter timestamps by `clock` instead
+    /// of the system clock. Job [`timeout`](Self::job_timeout)s always use real time.
+    #[must_use]
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Adds a named queue, or reconfigures it if the name was already added. Passing
+    /// [`DEFAULT_QUEUE`] configures the default queue.
+    #[must_use]
+    pub fn queue(mut self, name: impl Into<String>, config: QueueConfig) -> Self {
+        let name = name.into();
+        match self.queues.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) => *existing = config,
+            None => self.queues.push((name, config)),
+        }
+        self
+    }
+
+    /// Starts the workers and returns a handle to the [`DEFAULT_QUEUE`].
+    ///
+    /// Must be called within a Tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` or any queue's capacity is zero.
+    pub fn start(self) -> TaskQueue {
+        assert!(self.workers > 0, "task queue needs at least one worker");
+        let mut lanes = vec![Lane::new(DEFAULT_QUEUE.to_owned(), QueueConfig::new())];
+        for (name, config) in self.queues {
+            match lanes.iter_mut().find(|lane| lane.name == name) {
+                Some(lane) => *lane = Lane::new(name, config),
+                None => lanes.push(Lane::new(name, config)),
+            }
+        }
+        for lane in &mut lanes {
+            let capacity = *lane.capacity.get_or_insert(self.capacity);
+            assert!(capacity > 0, "task queue capacity must be non-zero");
+        }
+        let mut state = State {
+            lanes,
+            order: Vec::new(),
+            closed: false,
+            scheduling: true,
+        };
+        state.sort();
+
+        let (delay_sender, delay_receiver) = mpsc::unbounded_channel();
+        let shared = Arc::new(Shared {
+            state: Mutex::new(state),
+            work: Notify::new(),
+            space: Notify::new(),
+            scheduler: Mutex::new(Some(delay_sender)),
+            next_id: AtomicU64::new(1),
+            counters: Counters::default(),
+            job_timeout: self.job_timeout,
+            retry: self.retry,
+            dead_letters: Mutex::new(DeadLetters::new(self.dead_letter_capacity)),
+            statuses: Mutex::new(Statuses::new(self.status_capacity)),
+            workers: Mutex::new(Vec::new()),
+            clock: self.clock,
+        });
+        let mut tasks: Vec<_> = (0..self.workers)
+            .map(|_| tokio::spawn(work(Arc::clone(&shared))))
+            .collect();
+        tasks.push(tokio::spawn(schedule(Arc::clone(&shared), delay_receiver)));
+        *lock(&shared.workers) = tasks;
+        TaskQueue { shared, lane: 0 }
+    }
+}
+
+/// Settings for one named queue in a [`TaskQueue`].
+///
+/// Defaults: priority 0, no concurrency limit beyond the worker count, and the
+/// queue-wide [`capacity`](TaskQueueOptions::capacity).
+///
+/// # Examples
+///
+/// ```
+/// use rttp::background::{QueueConfig, TaskQueueOptions};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let queue = TaskQueueOptions::new()
+///     .workers(8)
+///     .queue("critical", QueueConfig::new().priority(10))
+///     .queue("bulk", QueueConfig::new().priority(-10).concurrency(2))
+///     .start();
+///
+/// let bulk = queue.named("bulk").unwrap();
+/// // bulk.spawn(rebuild_search_index).await?;
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueConfig {
+    priority: i32,
+    concurrency: Option<usize>,
+    capacity: Option<usize>,
+}
+
+impl QueueConfig {
+    /// Creates the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the priority; workers serve higher values first. Queues with equal priority
+    /// are served in the order they were added.
+    #[must_use]
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Caps how many of this queue's jobs run at once. Zero pauses the queue.
+    #[must_use]
+    pub fn concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = Some(limit);
+        self
+    }
+
+    /// Sets how many jobs may wait in this queue.
+    #[must_use]
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+}
+
+/// A point-in-time view of a [`TaskQueue`], across all its named queues.
+///
+/// `queued`, `running`, `scheduled`, and `dead_letters` are gauges; the remaining fields
+/// are counters since the queue was started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    /// Accepted jobs waiting for a worker.
+    pub queued: usize,
+    /// Jobs currently running.
+    pub running: usize,
+    /// Jobs waiting for their start time: delayed jobs and retries in backoff.
+    pub scheduled: usize,
+    /// Jobs held in the dead-letter queue.
+    pub dead_letters: usize,
+    /// Jobs that finished successfully.
+    pub completed: u64,
+    /// Failed attempts that were retried.
+    pub retried: u64,
+    /// Jobs whose last attempt failed and that will not be retried.
+    pub failed: u64,
+}
+
+/// The current settings and load of one named queue, from
+/// [`TaskQueue::queue_stats`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamedQueueStats {
+    /// The queue's name.
+    pub name: String,
+    /// Its current priority.
+    pub priority: i32,
+    /// Its current concurrency limit, if any.
+    pub concurrency: Option<usize>,
+    /// Jobs waiting in it.
+    pub queued: usize,
+    /// Its jobs currently running.
+    pub running: usize,
+}
+
+/// A handle to an in-process job queue. Cloning is cheap and clones share the queue.
+///
+/// Build one with [`TaskQueueOptions::start`]. A handle spawns onto one named queue,
+/// the [`DEFAULT_QUEUE`] unless obtained from [`named`](Self::named); everything else
+/// (stats, dead letters, shutdown) covers the whole queue.
+#[derive(Clone)]
+pub struct TaskQueue {
+    shared: Arc<Shared>,
+    // Index into `State::lanes` that spawned jobs go to.
+    lane: usize,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    // Wakes idle workers when a job may have become runnable.
+    work: Notify,
+    // Wakes spawners waiting for room in a full queue.
+    space: Notify,
+    // Feeds the scheduler; `None` once shut down.
+    scheduler: Mutex<Option<mpsc::UnboundedSender<Delayed>>>,
+    next_id: AtomicU64,
+    counters: Counters,
+    job_timeout: Option<Duration>,
+    retry: RetryPolicy,
+    dead_letters: Mutex<DeadLetters>,
+    statuses: Mutex<Statuses>,
+    // The workers followed by the scheduler.
+    workers: Mutex<Vec<JoinHandle<()>>>,
+    clock: Arc<dyn Clock>,
+}
+
+struct State {
+    // Named queues in the order they were added; the default queue is first.
+    lanes: Vec<Lane>,
+    // Indices into `lanes`, highest priority first.
+    order: Vec<usize>,
+    // Set by shutdown; no new jobs are accepted.
+    closed: bool,
+    // Cleared when the scheduler exits, after which no more jobs can appear.
+    scheduling: bool,
+}
+
+impl State {
+    fn sort(&mut self) {
+        let lanes = &self.lanes;
+        self.order = (0..lanes.len()).collect();
+        self.order.sort_by_key(|&i| Reverse(lanes[i].priority));
+    }
+
+    fn find(&self, name: &str) -> Result<usize, QueueError> {
+        self.lanes
+            .iter()
+            .position(|lane| lane.name == name)
+            .ok_or_else(|| QueueError::UnknownQueue(name.to_owned()))
+    }
+}
+
+struct Lane {
+    name: String,
+    priority: i32,
+    concurrency: Option<usize>,
+    // Always set once the queue has started.
+    capacity: Option<usize>,
+    jobs: VecDeque<Envelope>,
+    running: usize,
+}
+
+impl Lane {
+    fn new(name: String, config: QueueConfig) -> Self {
+        Self {
+            name,
+            priority: config.priority,
+            concurrency: config.concurrency,
+            capacity: config.capacity,
+            jobs: VecDeque::new(),
+            running: 0,
+        }
+    }
+
+    fn is_ready(&self) -> bool {
+        !self.jobs.is_empty() && self.concurrency.is_none_or(|limit| self.running < limit)
+    }
+
+    fn is_full(&self) -> bool {
+        self.capacity
+            .is_some_and(|capacity| self.jobs.len() >= capacity)
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    scheduled: AtomicUsize,
+    completed: AtomicU64,
+    retried: AtomicU64,
+    failed: AtomicU64,
+}
+
+struct Envelope {
+    id: JobId,
+    lane: usize,
+    job: Arc<dyn Job>,
+    // Runs that have already failed.
+    attempts: u32,
+}
+
+// An envelope to put back on its queue at the given instant.
+type Delayed = (Instant, Envelope);
+
+impl Shared {
+    // Adds `envelope` to its queue. Jobs that were already accepted (retries, delayed
+    // jobs) pass `accepting = false` to skip the capacity and shutdown checks.
+    fn push(&self, envelope: Envelope, accepting: bool) -> Result<(), (QueueError, Envelope)> {
+        let mut state = lock(&self.state);
+        if accepting {
+            if state.closed {
+                return Err((QueueError::Closed, envelope));
+            }
+            if state.lanes[envelope.lane].is_full() {
+                return Err((QueueError::Full, envelope));
+            }
+        }
+        // Recorded before a worker can see the job, so it cannot overwrite `Running`.
+        lock(&self.statuses).set(envelope.id, JobStatus::Queued);
+        state.lanes[envelope.lane].jobs.push_back(envelope);
+        drop(state);
+        self.work.notify_one();
+        Ok(())
+    }
+
+    // Like `push`, but waits for room instead of failing with `Full`.
+    async fn push_waiting(&self, mut envelope: Envelope) -> Result<(), (QueueError, Envelope)> {
+        loop {
+            let mut space = pin!(self.space.notified());
+            space.as_mut().enable();
+            match self.push(envelope, true) {
+                Err((QueueError::Full, returned)) => envelope = returned,
+                result => return result,
+            }
+            space.await;
+        }
+    }
+
+    // Takes the next job a worker may run, marking it running in its queue.
+    fn next_job(&self) -> Option<Envelope> {
+        let mut state = lock(&self.state);
+        let index = state
+            .order
+            .iter()
+            .copied()
+            .find(|&i| state.lanes[i].is_ready())?;
+        let lane = &mut state.lanes[index];
+        lane.running += 1;
+        let envelope = lane.jobs.pop_front();
+        drop(state);
+        self.space.notify_waiters();
+        envelope
+    }
+
+    fn finish(&self, lane: usize) {
+        lock(&self.state).lanes[lane].running -= 1;
+        // The queue may have been at its concurrency limit.
+        self.work.notify_one();
+    }
+
+    // True once shut down with nothing left that a worker could pick up.
+    fn is_drained(&self) -> bool {
+        let state = lock(&self.state);
+        state.closed && !state.scheduling && state.lanes.iter().all(|l| l.jobs.is_empty())
+    }
+
+    fn set_status(&self, id: JobId, status: JobStatus) {
+        lock(&self.statuses).set(id, status);
+    }
+
+    fn lane_name(&self, lane: usize) -> String {
+        lock(&self.state).lanes[lane].name.clone()
+    }
+
+    // Hands `envelope` to the scheduler to run at `at`, or returns it once the scheduler
+    // is shut down.
+    fn delay_until(&self, envelope: Envelope, at: Instant) -> Result<(), Envelope> {
+        let Some(scheduler) = lock(&self.scheduler).clone() else {
+            return Err(envelope);
+        };
+        self.counters.scheduled.fetch_add(1, Ordering::Relaxed);
+        self.set_status(envelope.id, JobStatus::Scheduled);
+        scheduler.send((at, envelope)).map_err(|e| {
+            self.counters.scheduled.fetch_sub(1, Ordering::Relaxed);
+            e.0.1
+        })
+    }
+}
+
+impl TaskQueue {
+    /// Returns a handle that spawns onto the queue called `name`.
+    ///
+    /// # Errors
+    ///
+    /// [`QueueError::UnknownQueue`] if no queue has that name.
+    pub fn named(&self, name: &str) -> Result<TaskQueue, QueueError> {
+        let lane = lock(&self.shared.state).find(name)?;
+        Ok(Self {
+            shared: Arc::clone(&self.shared),
+            lane,
+        })
+    }
+
+    /// The name of the queue this handle spawns onto.
+    pub fn name(&self) -> String {
+        self.shared.lane_name(self.lane)
+    }
+
+    /// Queues `job`, waiting for room if the queue is full.
+    ///
+    /// # Errors
+    ///
+    /// [`QueueError::Closed`] after [`shutdown`](Self::shutdown).
+    pub async fn spawn(&self, job: impl Job) -> Result<JobId, QueueError> {
+        let envelope = self.envelope(job);
+        let id = envelope.id;
+        self.shared
+            .push_waiting(envelope)
+            .await
+            .map_err(|(e, _)| e)?;
+        Ok(id)
+    }
+
+    /// Queues `job` without waiting.
+    ///
+    /// # Errors
+    ///
+    /// [`QueueError::Full`] when no room is left, or [`QueueError::Closed`] after
+    /// [`shutdown`](Self::shutdown).
+    pub fn try_spawn(&self, job: impl Job) -> Result<JobId, QueueError> {
+        let envelope = self.envelope(job);
+        let id = envelope.id;
+        self.shared.push(envelope, true).map_err(|(e, _)| e)?;
+        Ok(id)
+    }
+
+    /// Queues `job` to run once `delay` has elapsed.
+    ///
+    /// Delayed jobs wait in memory and do not count against the queue's
+    /// [`capacity`](TaskQueueOptions::capacity) until they are due. A
+    /// [`shutdown`](Self::shutdown) waits for them like any other queued job, so ones due
+    /// after its timeout are lost.
+    ///
+    /// # Errors
+    ///
+    /// [`QueueError::Closed`] after [`shutdown`](Self::shutdown).
+    pub fn spawn_after(&self, delay: Duration, job: impl Job) -> Result<JobId, QueueError> {
+        let envelope = self.envelope(job);
+        let id = envelope.id;
+        self.shared
+            .delay_until(envelope, self.shared.clock.now() + delay)
+            .map_err(|_| {
+                lock(&self.shared.statuses).remove(id);
+                QueueError::Closed
+            })?;
+        Ok(id)
+    }
+
+    /// Queues `job` to run at wall-clock time `at`, or as soon as possible if `at` has
+    /// passed. See [`spawn_after`](Self::spawn_after).
+    ///
+    /// # Errors
+    ///
+    /// [`QueueError::Closed`] after [`shutdown`](Self::shutdown).
+    pub fn spawn_at(&self, at: SystemTime, job: impl Job) -> Result<JobId, QueueError> {
+        let delay = at
+            .duration_since(self.shared.clock.system_time())
+            .unwrap_or_default();
+        self.spawn_after(delay, job)
+    }
+
+    /// Changes the concurrency limit of the queue called `name`; `None` removes it and
+    /// `Some(0)` pauses the queue. Jobs already running are not interrupted.
+    ///
+    /// # Errors
+    ///
+    /// [`QueueError::UnknownQueue`] if no queue has that name.
+    pub fn set_concurrency(&self, name: &str, limit: Option<usize>) -> Result<(), QueueError> {
+        let mut state = lock(&self.shared.state);
+        let lane = state.find(name)?;
+        state.lanes[lane].concurrency = limit;
+        drop(state);
+        self.shared.work.notify_waiters();
+        Ok(())
+    }
+
+    /// Changes the priority of the queue called `name`.
+    ///
+    /// # Errors
+    ///
+    /// [`QueueError::UnknownQueue`] if no queue has that name.
+    pub fn set_priority(&self, name: &str, priority: i32) -> Result<(), QueueError> {
+        let mut state = lock(&self.shared.state);
+        let lane = state.find(name)?;
+        state.lanes[lane].priority = priority;
+        state.sort();
+        Ok(())
+    }
+
+    /// Returns the status of job `id`, or `None` if this queue never accepted it or has
+    /// forgotten it (see [`status_capacity`](TaskQueueOptions::status_capacity)).
+    pub fn status(&self, id: JobId) -> Option<JobStatus> {
+        lock(&self.shared.statuses).get(id)
+    }
+
+    /// Returns the current gauges and counters.
+    pub fn stats(&self) -> QueueStats {
+        let (queued, running) = lock(&self.shared.state)
+            .lanes
+            .iter()
+            .fold((0, 0), |(queued, running), lane| {
+                (queued + lane.jobs.len(), running + lane.running)
+            });
+        let counters = &self.shared.counters;
+        QueueStats {
+            queued,
+            running,
+            scheduled: counters.scheduled.load(Ordering::Relaxed),
+            dead_letters: lock(&self.shared.dead_letters).len(),
+            completed: counters.completed.load(Ordering::Relaxed),
+            retried: counters.retried.load(Ordering::Relaxed),
+            failed: counters.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns each named queue's settings and load, highest priority first.
+    pub fn queue_stats(&self) -> Vec<NamedQueueStats> {
+        let state = lock(&self.shared.state);
+        state
+            .order
+            .iter()
+            .map(|&i| {
+                let lane = &state.lanes[i];
+                NamedQueueStats {
+                    name: lane.name.clone(),
+                    priority: lane.priority,
+                    concurrency: lane.concurrency,
+                    queued: lane.jobs.len(),
+                    running: lane.running,
+                }
+            })
+            .collect()
+    }
+
+    /// Lists the jobs in the dead-letter queue, oldest first.
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        lock(&self.shared.dead_letters).list()
+    }
+
+    /// Moves a dead-lettered job back onto the named queue it failed in, with a fresh
+    /// attempt count, waiting for room if that queue is full. The job keeps its
+    /// [`JobId`].
+    ///
+    /// # Errors
+    ///
+    /// [`QueueError::NotFound`] if no dead-lettered job has `id`, or
+    /// [`QueueError::Closed`] after [`shutdown`](Self::shutdown), in which case the job
+    /// stays dead-lettered.
+    pub async fn requeue(&self, id: JobId) -> Result<(), QueueError> {
+        if self.is_closed() {
+            return Err(QueueError::Closed);
+        }
+        let (letter, job) = lock(&self.shared.dead_letters)
+            .take(id)
+            .ok_or(QueueError::NotFound(id))?;
+        self.resend(letter, job).await
+    }
+
+    /// Requeues every dead-lettered job, returning how many were requeued.
+    ///
+    /// # Errors
+    ///
+    /// [`QueueError::Closed`] after [`shutdown`](Self::shutdown).
+    pub async fn requeue_all(&self) -> Result<usize, QueueError> {
+        if self.is_closed() {
+            return Err(QueueError::Closed);
+        }
+        let entries = lock(&self.shared.dead_letters).take_all();
+        let count = entries.len();
+        for (letter, job) in entries {
+            self.resend(letter, job).await?;
+        }
+        Ok(count)
+    }
+
+    /// Drops every dead-lettered job, returning how many were dropped.
+    pub fn purge_dead_letters(&self) -> usize {
+        lock(&self.shared.dead_letters).clear()
+    }
+
+    /// Returns `true` once [`shutdown`](Self::shutdown) has been called.
+    pub fn is_closed(&self) -> bool {
+        lock(&self.shared.state).closed
+    }
+
+    /// Stops accepting jobs and waits up to `timeout` for every queued and running job
+    /// to finish.
+    ///
+    /// Call it after the server stops accepting requests (see
+    /// [`Server::run_until`](crate::server::Server::run_until)) so work that handlers
+    /// already queued is not lost. Later calls return immediately.
+    ///
+    /// # Errors
+    ///
+    /// [`QueueError::DrainTimeout`] if jobs were still queued or running when `timeout`
+    /// elapsed; they are cancelled.
+    pub async fn shutdown(&self, timeout: Duration) -> Result<(), QueueError> {
+        lock(&self.shared.state).closed = true;
+        lock(&self.shared.scheduler).take();
+        self.shared.work.notify_waiters();
+        self.shared.space.notify_waiters();
+        let workers = std::mem::take(&mut *lock(&self.shared.workers));
+        let aborts: Vec<_> = workers.iter().map(JoinHandle::abort_handle).collect();
+        let drain = async {
+            for worker in workers {
+                let _ = worker.await;
+            }
+        };
+        if tokio::time::timeout(timeout, drain).await.is_ok() {
+            return Ok(());
+        }
+        let stats = self.stats();
+        let remaining = stats.queued + stats.running + stats.scheduled;
+        warn!(
+            remaining,
+            "task queue drain timed out; cancelling unfinished jobs"
+        );
+        for abort in aborts {
+            abort.abort();
+        }
+        Err(QueueError::DrainTimeout { remaining })
+    }
+
+    fn envelope(&self, job: impl Job) -> Envelope {
+        Envelope {
+            id: JobId(self.shared.next_id.fetch_add(1, Ordering::Relaxed)),
+            lane: self.lane,
+            job: Arc::new(job),
+            attempts: 0,
+        }
+    }
+
+    async fn resend(&self, letter: DeadLetter, job: Arc<dyn Job>) -> Result<(), QueueError> {
+        let lane = lock(&self.shared.state).find(&letter.queue).unwrap_or(0);
+        let envelope = Envelope {
+            id: letter.id,
+            lane,
+            job,
+            attempts: 0,
+        };
+        if let Err((e, envelope)) = self.shared.push_waiting(envelope).await {
+            lock(&self.shared.dead_letters).push(letter, envelope.job);
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+async fn work(shared: Arc<Shared>) {
+    loop {
+        let mut notified = pin!(shared.work.notified());
+        notified.as_mut().enable();
+        if let Some(envelope) = shared.next_job() {
+            let lane = envelope.lane;
+            process(&shared, envelope).await;
+            shared.finish(lane);
+        } else if shared.is_drained() {
+            // Let the other idle workers see it too.
+            shared.work.notify_waiters();
+            return;
+        } else {
+            notified.await;
+        }
+    }
+}
+
+// Runs `envelope`'s job, then retries or dead-letters it if it fails.
+async fn process(shared: &Shared, mut envelope: Envelope) {
+    let counters = &shared.counters;
+    let name = envelope.job.name().to_owned();
+    let id = envelope.id;
+    let timeout = envelope.job.timeout().or(shared.job_timeout);
+    loop {
+        shared.set_status(id, JobStatus::Running);
+        let result = execute(Arc::clone(&envelope.job), timeout).await;
+        let error = match result {
+            Ok(()) => {
+                debug!(job = %name, %id, "job completed");
+                counters.completed.fetch_add(1, Ordering::Relaxed);
+                shared.set_status(id, JobStatus::Completed);
+                return;
+            }
+            Err(e) => e,
+        };
+        envelope.attempts += 1;
+        let attempts = envelope.attempts;
+
+        let policy = envelope
+            .job
+            .retry_policy()
+            .unwrap_or_else(|| shared.retry.clone());
+        if !policy.should_retry(attempts, &error) {
+            warn!(job = %name, %id, attempts, error = %error, "job failed");
+            counters.failed.fetch_add(1, Ordering::Relaxed);
+            let letter = DeadLetter {
+                id,
+                name,
+                queue: shared.lane_name(envelope.lane),
+                attempts,
+                error: error.to_string(),
+                failed_at: shared.clock.system_time(),
+            };
+            shared.set_status(
+                id,
+                JobStatus::Failed {
+                    error: letter.error.clone(),
+                },
+            );
+            lock(&shared.dead_letters).push(letter, envelope.job);
+            return;
+        }
+
+        let delay = policy.backoff(attempts);
+        warn!(job = %name, %id, attempts, error = %error, retry_in = ?delay, "job failed, retrying");
+        counters.retried.fetch_add(1, Ordering::Relaxed);
+        let at = shared.clock.now() + delay;
+        match shared.delay_until(envelope, at) {
+            Ok(()) => return,
+            // Shutting down: wait out the backoff here so the retry still drains.
+            Err(returned) => envelope = returned,
+        }
+        shared.clock.sleep_until(at).await;
+    }
+}
+
+// Holds delayed envelopes until they are due, then puts them back on their queues.
+// After shutdown closes `input` it keeps going until every pending job has been
+// queued, then tells the workers no more are coming.
+async fn schedule(shared: Arc<Shared>, mut input: mpsc::UnboundedReceiver<Delayed>) {
+    let counters = &shared.counters;
+    let mut pending = BTreeMap::<(Instant, JobId), Envelope>::new();
+    let mut open = true;
+    loop {
+        let next = pending.keys().next().map(|&(at, _)| at);
+        tokio::select! {
+            received = input.recv(), if open => match received {
+                Some((at, envelope)) => {
+                    pending.insert((at, envelope.id), envelope);
+                }
+                None => open = false,
+            },
+            () = shared.clock.sleep_until(next.unwrap_or_else(|| shared.clock.now())), if next.is_some() => {
+                let now = shared.clock.now();
+                while let Some(entry) = pending.first_entry() {
+                    if entry.key().0 > now {
+                        break;
+                    }
+                    counters.scheduled.fetch_sub(1, Ordering::Relaxed);
+                    // Already accepted, so it may exceed the queue's capacity.
+                    let _ = shared.push(entry.remove(), false);
+                }
+            }
+            else => break,
+        }
+    }
+    lock(&shared.state).scheduling = false;
+    shared.work.notify_waiters();
+}
+
+// Runs one attempt of `job` in its own task, so panics and timeouts stay contained.
+pub(super) async fn execute(job: Arc<dyn Job>, timeout: Option<Duration>) -> Result<(), JobError> {
+    let mut task = AbortOnDrop(tokio::spawn(async move { job.run().await }));
+    let joined = match timeout {
+        Some(limit) => match tokio::time::timeout(limit, &mut task.0).await {
+            Ok(joined) => joined,
+            Err(_) => return Err(JobError::Timeout(limit)),
+        },
+        None => (&mut task.0).await,
+    };
+    match joined {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => Err(JobError::Panicked(panic_message(e.into_panic()))),
+        Err(_) => Err(JobError::Failed("job was cancelled".to_owned())),
+    }
+}
+
+// Cancels the job's task when its worker stops waiting for it (timeout or shutdown).
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| (*s).to_owned())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_owned())
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use super::*;
+    use crate::background::JobFuture;
+
+    fn counting(counter: &Arc<AtomicUsize>) -> impl Job {
+        let counter = Arc::clone(counter);
+        move || {
+            let counter = Arc::clone(&counter);
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_jobs_and_drains_on_shutdown() {
+        let queue = TaskQueueOptions::new().workers(2).start();
+        let done = Arc::new(AtomicUsize::new(0));
+        let mut ids = Vec::new();
+        for _ in 0..10 {
+            ids.push(queue.spawn(counting(&done)).await.unwrap());
+        }
+        assert_eq!(ids.first(), Some(&JobId(1)));
+        assert_eq!(ids.last(), Some(&JobId(10)));
+
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(done.load(Ordering::SeqCst), 10);
+        let stats = queue.stats();
+        assert_eq!((stats.queued, stats.running, stats.completed), (0, 0, 10));
+
+        assert!(queue.is_closed());
+        assert!(matches!(
+            queue.spawn(counting(&done)).await,
+            Err(QueueError::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn try_spawn_reports_a_full_queue() {
+        let gate = Arc::new(tokio::sync::Notify::new());
+        let queue = TaskQueueOptions::new().workers(1).capacity(1).start();
+        let blocker = {
+            let gate = Arc::clone(&gate);
+            move || {
+                let gate = Arc::clone(&gate);
+                async move {
+                    gate.notified().await;
+                    Ok(())
+                }
+            }
+        };
+        queue.try_spawn(blocker.clone()).unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await; // picked up by the worker
+        queue.try_spawn(blocker.clone()).unwrap();
+        assert!(matches!(queue.try_spawn(blocker), Err(QueueError::Full)));
+        assert_eq!(queue.stats().queued, 1);
+
+        gate.notify_one();
+        gate.notify_one();
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(queue.stats().completed, 2);
+    }
+
+    struct Slow;
+
+    impl Job for Slow {
+        fn run(&self) -> JobFuture<'_> {
+            Box::pin(async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok(())
+            })
+        }
+
+        fn timeout(&self) -> Option<Duration> {
+            Some(Duration::from_millis(10))
+        }
+    }
+
+    #[tokio::test]
+    async fn failures_are_contained() {
+        let queue = TaskQueueOptions::new().workers(1).start();
+        let done = Arc::new(AtomicUsize::new(0));
+        queue.spawn(Slow).await.unwrap();
+        queue
+            .spawn(|| async { Err(JobError::failed("smtp unavailable")) })
+            .await
+            .unwrap();
+        queue.spawn(|| async { panic!("boom") }).await.unwrap();
+        queue.spawn(counting(&done)).await.unwrap();
+
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+        let stats = queue.stats();
+        assert_eq!((stats.completed, stats.failed), (1, 3));
+        assert_eq!(done.load(Ordering::SeqCst), 1, "worker survived the panic");
+    }
+
+    #[tokio::test]
+    async fn drain_timeout_cancels_remaining_jobs() {
+        let queue = TaskQueueOptions::new().workers(1).start();
+        let finished = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&finished);
+        queue
+            .spawn(move || {
+                let flag = Arc::clone(&flag);
+                async move {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                    flag.store(true, Ordering::SeqCst);
+                    Ok(())
+                }
+            })
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let result = queue.shutdown(Duration::from_millis(20)).await;
+        assert!(matches!(
+            result,
+            Err(QueueError::DrainTimeout { remaining: 1 })
+        ));
+        assert!(!finished.load(Ordering::SeqCst));
+    }
+
+    // Fails until it has been called `succeed_on` times.
+    fn flaky(calls: &Arc<AtomicUsize>, succeed_on: usize) -> impl Job + Clone {
+        let calls = Arc::clone(calls);
+        move || {
+            let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            async move {
+                if call >= succeed_on {
+                    Ok(())
+                } else {
+                    Err(JobError::failed(format!("attempt {call} failed")))
+                }
+            }
+        }
+    }
+
+    fn fast_retries(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy::exponential(max_attempts)
+            .initial_backoff(Duration::from_millis(5))
+            .jitter(false)
+    }
+
+    #[tokio::test]
+    async fn retries_failed_jobs_with_backoff() {
+        let queue = TaskQueueOptions::new()
+            .workers(1)
+            .retry(fast_retries(3))
+            .start();
+        let calls = Arc::new(AtomicUsize::new(0));
+        queue.spawn(flaky(&calls, 3)).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        assert_eq!(queue.stats().scheduled, 1, "waiting out the first backoff");
+
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        let stats = queue.stats();
+        assert_eq!(
+            (
+                stats.completed,
+                stats.retried,
+                stats.failed,
+                stats.scheduled
+            ),
+            (1, 2, 0, 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn permanent_failures_are_dead_lettered_and_requeued() {
+        let queue = TaskQueueOptions::new()
+            .workers(1)
+            .retry(fast_retries(2))
+            .start();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let id = queue.spawn(flaky(&calls, 3)).await.unwrap();
+        queue
+            .spawn(|| async { Err(JobError::permanent("malformed payload")) })
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The permanent failure skips the backoff, so it is dead-lettered first.
+        let dead = queue.dead_letters();
+        assert_eq!(dead.len(), 2);
+        assert_eq!(dead[0].attempts, 1, "not retried");
+        assert_eq!((dead[1].id, dead[1].attempts), (id, 2));
+        assert_eq!(dead[1].error, "job failed: attempt 2 failed");
+        let stats = queue.stats();
+        assert_eq!((stats.failed, stats.retried, stats.dead_letters), (2, 1, 2));
+
+        queue.requeue(id).await.unwrap();
+        assert!(matches!(
+            queue.requeue(id).await,
+            Err(QueueError::NotFound(_))
+        ));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(queue.stats().completed, 1);
+        assert_eq!(queue.purge_dead_letters(), 1);
+        assert!(queue.dead_letters().is_empty());
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn dead_letter_queue_is_bounded() {
+        let queue = TaskQueueOptions::new().dead_letter_capacity(2).start();
+        for _ in 0..3 {
+            queue
+                .spawn(|| async { Err(JobError::failed("down")) })
+                .await
+                .unwrap();
+        }
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+        let ids: Vec<_> = queue.dead_letters().iter().map(|d| d.id).collect();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(queue.stats().failed, 3);
+    }
+
+    #[tokio::test]
+    async fn delayed_jobs_run_when_due() {
+        let clock = crate::clock::MockClock::new();
+        let queue = TaskQueueOptions::new()
+            .workers(1)
+            .clock(Arc::new(clock.clone()))
+            .start();
+        let (tx, mut ran) = mpsc::unbounded_channel();
+        let record = |label: &'static str| {
+            let tx = tx.clone();
+            move || {
+                let _ = tx.send(label);
+                async { Ok(()) }
+            }
+        };
+        let tick = Duration::from_millis(20);
+        queue.spawn_after(2 * tick, record("later")).unwrap();
+        queue
+            .spawn_at(clock.system_time() + tick, record("soon"))
+            .unwrap();
+        queue.spawn(record("now")).await.unwrap();
+        assert_eq!(ran.recv().await, Some("now"));
+        queue
+            .spawn_at(SystemTime::UNIX_EPOCH, record("overdue"))
+            .unwrap();
+        assert_eq!(ran.recv().await, Some("overdue"));
+        assert_eq!(queue.stats().scheduled, 2);
+
+        clock.advance(tick);
+        assert_eq!(ran.recv().await, Some("soon"));
+        assert_eq!(queue.stats().scheduled, 1);
+        clock.advance(tick);
+        assert_eq!(ran.recv().await, Some("later"));
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert!(matches!(
+            queue.spawn_after(Duration::ZERO, record("closed")),
+            Err(QueueError::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn delays_follow_the_clock() {
+        let clock = crate::clock::MockClock::new();
+        let queue = TaskQueueOptions::new()
+            .workers(1)
+            .clock(Arc::new(clock.clone()))
+            .start();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let hour = Duration::from_secs(3600);
+        queue
+            .spawn_after(hour, recorder(&order, "in an hour"))
+            .unwrap();
+        queue
+            .spawn_at(clock.system_time() + 2 * hour, recorder(&order, "in two"))
+            .unwrap();
+
+        clock.advance(hour);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(*lock(&order), ["in an hour"]);
+        assert_eq!(queue.stats().scheduled, 1);
+
+        clock.advance(hour);
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(*lock(&order), ["in an hour", "in two"]);
+    }
+
+    fn recorder(order: &Arc<Mutex<Vec<String>>>, label: &str) -> impl Job {
+        let order = Arc::clone(order);
+        let label = label.to_owned();
+        move || {
+            lock(&order).push(label.clone());
+            async { Ok(()) }
+        }
+    }
+
+    #[tokio::test]
+    async fn workers_serve_higher_priority_queues_first() {
+        let queue = TaskQueueOptions::new()
+            .workers(1)
+            .queue("critical", QueueConfig::new().priority(10))
+            .queue("bulk", QueueConfig::new().priority(-10))
+            .start();
+        let gate = Arc::new(tokio::sync::Notify::new());
+        let blocker = Arc::clone(&gate);
+        queue
+            .spawn(move || {
+                let gate = Arc::clone(&blocker);
+                async move {
+                    gate.notified().await;
+                    Ok(())
+                }
+            })
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let bulk = queue.named("bulk").unwrap();
+        let critical = queue.named("critical").unwrap();
+        bulk.spawn(recorder(&order, "bulk")).await.unwrap();
+        queue.spawn(recorder(&order, "default")).await.unwrap();
+        critical.spawn(recorder(&order, "critical")).await.unwrap();
+        assert_eq!(
+            queue
+                .queue_stats()
+                .iter()
+                .map(|q| (q.name.as_str(), q.queued))
+                .collect::<Vec<_>>(),
+            [("critical", 1), ("default", 1), ("bulk", 1)]
+        );
+
+        gate.notify_one();
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(*lock(&order), ["critical", "default", "bulk"]);
+        assert!(matches!(
+            queue.named("missing"),
+            Err(QueueError::UnknownQueue(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn concurrency_limits_apply_per_queue_and_change_at_runtime() {
+        let queue = TaskQueueOptions::new()
+            .workers(4)
+            .queue("bulk", QueueConfig::new().concurrency(1))
+            .start();
+        let bulk = queue.named("bulk").unwrap();
+        assert_eq!(bulk.name(), "bulk");
+        let (running, peak) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let slow = {
+            let (running, peak) = (Arc::clone(&running), Arc::clone(&peak));
+            move || {
+                let (running, peak) = (Arc::clone(&running), Arc::clone(&peak));
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(())
+                }
+            }
+        };
+        for _ in 0..3 {
+            bulk.spawn(slow.clone()).await.unwrap();
+        }
+        let done = Arc::new(AtomicUsize::new(0));
+        queue.spawn(counting(&done)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(done.load(Ordering::SeqCst), 1, "not stuck behind bulk jobs");
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+
+        queue.set_concurrency("bulk", Some(0)).unwrap();
+        bulk.spawn(slow.clone()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(queue.stats().queued, 1, "paused");
+
+        queue.set_concurrency("bulk", None).unwrap();
+        queue.set_priority("bulk", 5).unwrap();
+        assert_eq!(queue.queue_stats()[0].name, "bulk");
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(queue.stats().completed, 5);
+        assert!(queue.set_priority("missing", 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn tracks_job_status_through_its_lifecycle() {
+        let queue = TaskQueueOptions::new()
+            .workers(1)
+            .status_capacity(1)
+            .start();
+        let gate = Arc::new(tokio::sync::Notify::new());
+        let blocker = Arc::clone(&gate);
+        let running = queue
+            .spawn(move || {
+                let gate = Arc::clone(&blocker);
+                async move {
+                    gate.notified().await;
+                    Ok(())
+                }
+            })
+            .await
+            .unwrap();
+        let queued = queue.spawn(|| async { Ok(()) }).await.unwrap();
+        let delayed = queue
+            .spawn_after(Duration::from_millis(20), || async { Ok(()) })
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(queue.status(running), Some(JobStatus::Running));
+        assert_eq!(queue.status(queued), Some(JobStatus::Queued));
+        assert_eq!(queue.status(delayed), Some(JobStatus::Scheduled));
+        assert_eq!(queue.status(JobId(99)), None);
+
+        gate.notify_one();
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(queue.status(delayed), Some(JobStatus::Completed));
+        assert_eq!(queue.status(running), None, "forgotten beyond capacity");
+    }
+}
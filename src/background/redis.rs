@@ -0,0 +1,559 @@
+//! A persistent job queue in Redis, with delayed jobs.
+//!
+//! [`RedisQueue`] keeps jobs in Redis, so they outlive the process and several
+//! processes can share one queue. Closures cannot be stored, so a job is a kind plus a
+//! JSON payload; workers started with [`RedisQueue::start`] run each job with the
+//! handler registered for its kind.
+//!
+//! Jobs that are ready wait in a list. Delayed jobs, and failed jobs waiting out their
+//! [`RetryPolicy`] backoff, wait in a sorted set scored by the Unix time in
+//! milliseconds when they are due. Each time a worker polls it moves the due ones onto
+//! the list; `ZREM` decides which poller moves a job, so it is queued once however many
+//! workers see it. Jobs that fail for good are appended to a dead-letter list.
+//!
+//! Delivery is at most once: a job taken by a worker that dies before finishing it is
+//! lost.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use rttp::{background::RedisQueue, redis::RedisClient};
+//! use serde_json::{Value, json};
+//!
+//! # async fn example() -> Result<(), rttp::background::QueueError> {
+//! let queue = RedisQueue::new(RedisClient::new("127.0.0.1:6379"), "mail")
+//!     .handler("reminder", |payload: Value| async move {
+//!         // email the reminder to payload["to"]
+//!         Ok(())
+//!     });
+//! let workers = queue.start(2);
+//!
+//! let tomorrow = Duration::from_secs(24 * 3600);
+//! queue
+//!     .push_after(tomorrow, "reminder", &json!({ "to": "ada@example.com" }))
+//!     .await?;
+//!
+//! workers.shutdown().await; // the reminder stays in Redis until a worker runs it
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{debug, warn};
+
+use super::{JobError, JobFuture, JobId, QueueError, RetryPolicy, queue::execute};
+use crate::{
+    clock::{self, Clock},
+    redis::RedisClient,
+};
+
+// Due jobs moved onto the ready list per poll.
+const PROMOTE_BATCH: usize = 100;
+
+type Handler = Arc<dyn Fn(Value) -> JobFuture<'static> + Send + Sync>;
+
+/// A job queue kept in Redis.
+///
+/// Defaults: keys under `rttp:jobs:{name}:`, no job timeout, no retries, and workers
+/// that poll every second while the queue is empty.
+#[derive(Clone)]
+pub struct RedisQueue {
+    client: Arc<RedisClient>,
+    prefix: String,
+    handlers: HashMap<String, Handler>,
+    job_timeout: Option<Duration>,
+    retry: RetryPolicy,
+    poll_interval: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+/// The jobs in a [`RedisQueue`], as counted by [`RedisQueue::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RedisQueueStats {
+    /// Jobs waiting for a worker.
+    pub ready: usize,
+    /// Jobs waiting for their start time: delayed jobs and retries in backoff.
+    pub scheduled: usize,
+    /// Jobs that failed permanently.
+    pub dead: usize,
+}
+
+// A job as stored in Redis.
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    id: u64,
+    kind: String,
+    payload: Value,
+    attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl RedisQueue {
+    /// Creates the queue called `name`, stored through `client`.
+    pub fn new(client: RedisClient, name: &str) -> Self {
+        Self {
+            client: Arc::new(client),
+            prefix: format!("rttp:jobs:{name}:"),
+            handlers: HashMap::new(),
+            job_timeout: None,
+            retry: RetryPolicy::none(),
+            poll_interval: Duration::from_secs(1),
+            clock: clock::system(),
+        }
+    }
+
+    /// Replaces the key prefix, `rttp:jobs:{name}:` by default.
+    #[must_use]
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Runs jobs of `kind` with `handler`, which receives the job's payload.
+    ///
+    /// Only processes that start workers need handlers; jobs of a kind with no handler
+    /// fail permanently.
+    #[must_use]
+    pub fn handler<F, Fut>(mut self, kind: impl Into<String>, handler: F) -> Self
+    where
+        F: Fn(Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), JobError>> + Send + 'static,
+    {
+        self.handlers.insert(
+            kind.into(),
+            Arc::new(move |payload| Box::pin(handler(payload))),
+        );
+        self
+    }
+
+    /// Sets how long one run may take before it is cancelled with
+    /// [`JobError::Timeout`].
+    #[must_use]
+    pub fn job_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.job_timeout = timeout;
+        self
+    }
+
+    /// Sets how failed jobs are retried.
+    #[must_use]
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Sets how long an idle worker waits before polling again.
+    #[must_use]
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Times delayed jobs, retry backoff, and polling by `clock` instead of the system
+    /// clock. Job timeouts always use real time.
+    #[must_use]
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Queues a job of `kind` to run as soon as a worker takes it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QueueError::Payload`] if `payload` does not serialize, or
+    /// [`QueueError::Redis`] if Redis cannot be reached.
+    pub async fn push(&self, kind: &str, payload: &impl Serialize) -> Result<JobId, QueueError> {
+        let record = self.record(kind, payload).await?;
+        self.client
+            .rpush(&self.key("ready"), &serde_json::to_vec(&record)?)
+            .await?;
+        Ok(JobId(record.id))
+    }
+
+    /// Queues a job of `kind` to run once `delay` has elapsed.
+    ///
+    /// # Errors
+    ///
+    /// See [`push`](Self::push).
+    pub async fn push_after(
+        &self,
+        delay: Duration,
+        kind: &str,
+        payload: &impl Serialize,
+    ) -> Result<JobId, QueueError> {
+        self.push_at(self.clock.system_time() + delay, kind, payload)
+            .await
+    }
+
+    /// Queues a job of `kind` to run at wall-clock time `at`, or as soon as possible if
+    /// `at` has passed.
+    ///
+    /// # Errors
+    ///
+    /// See [`push`](Self::push).
+    pub async fn push_at(
+        &self,
+        at: SystemTime,
+        kind: &str,
+        payload: &impl Serialize,
+    ) -> Result<JobId, QueueError> {
+        let record = self.record(kind, payload).await?;
+        self.schedule(&record, at).await?;
+        Ok(JobId(record.id))
+    }
+
+    /// Moves delayed jobs that are due onto the ready list, returning how many it moved.
+    /// Workers call this each time they poll.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QueueError::Redis`] if Redis cannot be reached.
+    pub async fn promote_due(&self) -> Result<usize, QueueError> {
+        let (scheduled, ready) = (self.key("scheduled"), self.key("ready"));
+        let now = unix_millis(self.clock.system_time());
+        let mut moved = 0;
+        for job in self
+            .client
+            .zrange_by_score(&scheduled, now, PROMOTE_BATCH)
+            .await?
+        {
+            // Another poller may have claimed it since the range was read.
+            if self.client.zrem(&scheduled, &job).await? {
+                self.client.rpush(&ready, &job).await?;
+                moved += 1;
+            }
+        }
+        Ok(moved)
+    }
+
+    /// Counts the jobs waiting, scheduled, and dead-lettered.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QueueError::Redis`] if Redis cannot be reached.
+    pub async fn stats(&self) -> Result<RedisQueueStats, QueueError> {
+        let count = |n: i64| usize::try_from(n).unwrap_or(0);
+        Ok(RedisQueueStats {
+            ready: count(self.client.llen(&self.key("ready")).await?),
+            scheduled: count(self.client.zcard(&self.key("scheduled")).await?),
+            dead: count(self.client.llen(&self.key("dead")).await?),
+        })
+    }
+
+    /// Starts `workers` tasks that take jobs from the queue and run them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a Tokio runtime.
+    pub fn start(&self, workers: usize) -> RedisWorkers {
+        let queue = Arc::new(self.clone());
+        let (stop, stopped) = watch::channel(false);
+        let tasks = (0..workers.max(1))
+            .map(|_| tokio::spawn(work(Arc::clone(&queue), stopped.clone())))
+            .collect();
+        RedisWorkers { stop, tasks }
+    }
+
+    async fn record(&self, kind: &str, payload: &impl Serialize) -> Result<Record, QueueError> {
+        let payload = serde_json::to_value(payload)?;
+        let id = self.client.incr_by(&self.key("ids"), 1).await?;
+        Ok(Record {
+            id: u64::try_from(id).unwrap_or_default(),
+            kind: kind.to_owned(),
+            payload,
+            attempts: 0,
+            error: None,
+        })
+    }
+
+    async fn schedule(&self, record: &Record, at: SystemTime) -> Result<(), QueueError> {
+        self.client
+            .zadd(
+                &self.key("scheduled"),
+                unix_millis(at),
+                &serde_json::to_vec(record)?,
+            )
+            .await?;
+        Ok(())
+    }
+
+    // Takes the next ready job, after queueing any that have come due.
+    async fn next(&self) -> Result<Option<Vec<u8>>, QueueError> {
+        self.promote_due().await?;
+        Ok(self.client.lpop(&self.key("ready")).await?)
+    }
+
+    // Runs one job, then reschedules it for a retry or dead-letters it if it fails.
+    async fn run(&self, job: &[u8]) {
+        let mut record: Record = match serde_json::from_slice(job) {
+            Ok(record) => record,
+            Err(error) => {
+                warn!(queue = %self.prefix, %error, "dropping malformed job");
+                return;
+            }
+        };
+        let (kind, id) = (record.kind.clone(), JobId(record.id));
+        let result = match self.handlers.get(&kind) {
+            Some(handler) => {
+                let handler = Arc::clone(handler);
+                let payload = record.payload.clone();
+                execute(Arc::new(move || handler(payload.clone())), self.job_timeout).await
+            }
+            None => Err(JobError::permanent(format!(
+                "no handler for jobs of kind {kind:?}"
+            ))),
+        };
+        let error = match result {
+            Ok(()) => {
+                debug!(job = %kind, %id, "job completed");
+                return;
+            }
+            Err(error) => error,
+        };
+        record.attempts += 1;
+        let attempts = record.attempts;
+
+        let stored = if self.retry.should_retry(attempts, &error) {
+            let delay = self.retry.backoff(attempts);
+            warn!(job = %kind, %id, attempts, %error, retry_in = ?delay, "job failed, retrying");
+            self.schedule(&record, self.clock.system_time() + delay)
+                .await
+        } else {
+            warn!(job = %kind, %id, attempts, %error, "job failed");
+            record.error = Some(error.to_string());
+            match serde_json::to_vec(&record) {
+                Ok(letter) => self
+                    .client
+                    .rpush(&self.key("dead"), &letter)
+                    .await
+                    .map(drop)
+                    .map_err(QueueError::from),
+                Err(e) => Err(e.into()),
+            }
+        };
+        if let Err(error) = stored {
+            warn!(job = %kind, %id, %error, "failed to store the failed job, dropping it");
+        }
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}{name}", self.prefix)
+    }
+}
+
+impl fmt::Debug for RedisQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut kinds: Vec<_> = self.handlers.keys().collect();
+        kinds.sort();
+        f.debug_struct("RedisQueue")
+            .field("addr", &self.client.addr())
+            .field("prefix", &self.prefix)
+            .field("handlers", &kinds)
+            .field("retry", &self.retry)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The workers started by [`RedisQueue::start`].
+///
+/// Dropping this stops them without waiting for the jobs they are running.
+#[derive(Debug)]
+pub struct RedisWorkers {
+    stop: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl RedisWorkers {
+    /// Stops taking jobs and waits for the ones already running to finish. Jobs left in
+    /// Redis wait for the next workers.
+    pub async fn shutdown(mut self) {
+        let _ = self.stop.send(true);
+        for task in std::mem::take(&mut self.tasks) {
+            let _ = task.await;
+        }
+    }
+}
+
+impl Drop for RedisWorkers {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+async fn work(queue: Arc<RedisQueue>, mut stop: watch::Receiver<bool>) {
+    while !*stop.borrow() {
+        // Taken before polling, so a job that comes due during the poll is not missed.
+        let wake = queue.clock.now() + queue.poll_interval;
+        match queue.next().await {
+            Ok(Some(job)) => {
+                queue.run(&job).await;
+                continue;
+            }
+            Ok(None) => {}
+            Err(error) => warn!(queue = %queue.prefix, %error, "failed to poll the job queue"),
+        }
+        tokio::select! {
+            () = queue.clock.sleep_until(wake) => {}
+            changed = stop.changed() => if changed.is_err() {
+                return;
+            },
+        }
+    }
+}
+
+fn unix_millis(at: SystemTime) -> i64 {
+    at.duration_since(UNIX_EPOCH).map_or(0, |since| {
+        i64::try_from(since.as_millis()).unwrap_or(i64::MAX)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::{clock::MockClock, redis::testing::FakeRedis};
+
+    // A queue whose "record" jobs send their payload's label down the channel.
+    fn queue(
+        server: &FakeRedis,
+        clock: &MockClock,
+    ) -> (RedisQueue, mpsc::UnboundedReceiver<String>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let queue = RedisQueue::new(RedisClient::new(server.addr()), "test")
+            .clock(Arc::new(clock.clone()))
+            .handler("record", move |payload: Value| {
+                let _ = tx.send(payload["label"].as_str().unwrap_or_default().to_owned());
+                async { Ok(()) }
+            });
+        (queue, rx)
+    }
+
+    // Yields until `ready` holds for the queue's counts.
+    async fn settle(queue: &RedisQueue, ready: impl Fn(RedisQueueStats) -> bool) {
+        let wait = async {
+            while !ready(queue.stats().await.unwrap()) {
+                tokio::task::yield_now().await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), wait)
+            .await
+            .expect("queue never settled");
+    }
+
+    #[tokio::test]
+    async fn delayed_jobs_wait_in_redis_until_due() {
+        let server = FakeRedis::start().await;
+        let clock = MockClock::new();
+        let hour = Duration::from_secs(3600);
+        // Jobs pushed by a process without workers are run by another.
+        let producer =
+            RedisQueue::new(RedisClient::new(server.addr()), "test").clock(Arc::new(clock.clone()));
+        producer
+            .push_after(hour, "record", &json!({ "label": "in an hour" }))
+            .await
+            .unwrap();
+        producer
+            .push_at(
+                clock.system_time() + 2 * hour,
+                "record",
+                &json!({ "label": "in two" }),
+            )
+            .await
+            .unwrap();
+        let first = producer
+            .push("record", &json!({ "label": "now" }))
+            .await
+            .unwrap();
+        let overdue = producer
+            .push_at(UNIX_EPOCH, "record", &json!({ "label": "overdue" }))
+            .await
+            .unwrap();
+        assert_eq!(overdue, JobId(first.0 + 1));
+        assert_eq!(
+            producer.stats().await.unwrap(),
+            RedisQueueStats {
+                ready: 1,
+                scheduled: 3,
+                dead: 0
+            }
+        );
+
+        let (consumer, mut ran) = queue(&server, &clock);
+        let workers = consumer.start(1);
+        assert_eq!(ran.recv().await.unwrap(), "now");
+        assert_eq!(ran.recv().await.unwrap(), "overdue");
+        settle(&consumer, |stats| stats.scheduled == 2).await;
+
+        clock.advance(hour);
+        assert_eq!(ran.recv().await.unwrap(), "in an hour");
+        clock.advance(hour);
+        assert_eq!(ran.recv().await.unwrap(), "in two");
+        workers.shutdown().await;
+        assert_eq!(consumer.stats().await.unwrap(), RedisQueueStats::default());
+    }
+
+    #[tokio::test]
+    async fn failed_jobs_retry_from_the_schedule_then_dead_letter() {
+        let server = FakeRedis::start().await;
+        let clock = MockClock::new();
+        let (tx, mut attempts) = mpsc::unbounded_channel();
+        let queue = RedisQueue::new(RedisClient::new(server.addr()), "test")
+            .clock(Arc::new(clock.clone()))
+            .retry(
+                RetryPolicy::exponential(2)
+                    .initial_backoff(Duration::from_secs(60))
+                    .jitter(false),
+            )
+            .handler("flaky", move |_| {
+                let _ = tx.send(());
+                async { Err(JobError::failed("SMTP timeout")) }
+            });
+        queue.push("flaky", &json!({})).await.unwrap();
+        queue.push("unknown", &json!({})).await.unwrap();
+
+        let workers = queue.start(1);
+        attempts.recv().await.unwrap();
+        settle(&queue, |stats| stats.scheduled == 1 && stats.dead == 1).await;
+
+        clock.advance(Duration::from_secs(60));
+        attempts.recv().await.unwrap();
+        settle(&queue, |stats| stats.dead == 2).await;
+        workers.shutdown().await;
+        assert_eq!(
+            queue.stats().await.unwrap(),
+            RedisQueueStats {
+                ready: 0,
+                scheduled: 0,
+                dead: 2
+            }
+        );
+
+        let dead = queue
+            .client
+            .lpop(&queue.key("dead"))
+            .await
+            .unwrap()
+            .unwrap();
+        let record: Record = serde_json::from_slice(&dead).unwrap();
+        assert_eq!(record.kind, "unknown");
+        assert_eq!(
+            record.error.as_deref(),
+            Some("job failed permanently: no handler for jobs of kind \"unknown\"")
+        );
+    }
+}
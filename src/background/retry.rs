@@ -0,0 +1,177 @@
+//! Retry policies for failed jobs.
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use super::JobError;
+use crate::security::crypto::random_bytes;
+
+/// Decides whether a failed job runs again, and after how long.
+///
+/// The wait before attempt `n + 1` is `initial_backoff * 2^(n - 1)`, capped at
+/// `max_backoff`. With jitter enabled (the default) a random half of that wait is
+/// dropped, so jobs that failed together do not all retry at the same instant.
+///
+/// Only errors accepted by the [`retry_if`](Self::retry_if) predicate are retried; by
+/// default that is [`JobError::is_retryable`].
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use rttp::background::{JobError, RetryPolicy};
+///
+/// let policy = RetryPolicy::exponential(5)
+///     .initial_backoff(Duration::from_millis(200))
+///     .max_backoff(Duration::from_secs(10))
+///     .jitter(false);
+/// assert_eq!(policy.backoff(3), Duration::from_millis(800));
+/// assert!(policy.should_retry(1, &JobError::failed("timeout talking to SMTP")));
+/// assert!(!policy.should_retry(5, &JobError::failed("still failing")));
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+    retry_if: Arc<dyn Fn(&JobError) -> bool + Send + Sync>,
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("jitter", &self.jitter)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl RetryPolicy {
+    /// Runs each job once; failures go straight to the dead-letter queue.
+    pub fn none() -> Self {
+        Self::exponential(1)
+    }
+
+    /// Runs each job up to `max_attempts` times in total, starting from a 1-second
+    /// backoff capped at 5 minutes, with jitter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_attempts` is zero.
+    pub fn exponential(max_attempts: u32) -> Self {
+        assert!(max_attempts > 0, "a job needs at least one attempt");
+        Self {
+            max_attempts,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5 * 60),
+            jitter: true,
+            retry_if: Arc::new(JobError::is_retryable),
+        }
+    }
+
+    /// Sets the wait before the first retry.
+    #[must_use]
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Sets the longest wait between attempts.
+    #[must_use]
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Whether waits are randomized (default `true`).
+    #[must_use]
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets which errors are worth retrying.
+    #[must_use]
+    pub fn retry_if(
+        mut self,
+        predicate: impl Fn(&JobError) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry_if = Arc::new(predicate);
+        self
+    }
+
+    /// The most times a job runs.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns `true` if a job that has failed `attempts` times with `error` should run
+    /// again.
+    pub fn should_retry(&self, attempts: u32, error: &JobError) -> bool {
+        attempts < self.max_attempts && (self.retry_if)(error)
+    }
+
+    /// The wait after the `attempts`-th failure, before the next attempt.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let exponent = attempts.saturating_sub(1).min(31);
+        let delay = self
+            .initial_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff);
+        if !self.jitter {
+            return delay;
+        }
+        let half = delay / 2;
+        let random = u64::from_le_bytes(random_bytes(8).try_into().expect("8 random bytes"));
+        let nanos = u64::try_from(half.as_nanos()).unwrap_or(u64::MAX);
+        half + Duration::from_nanos(random % nanos.saturating_add(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy::exponential(10)
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_secs(1))
+            .jitter(false);
+        let waits: Vec<_> = (1..=6).map(|n| policy.backoff(n).as_millis()).collect();
+        assert_eq!(waits, [100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(1));
+
+        let jittered = policy.jitter(true);
+        for n in 1..=6 {
+            let wait = jittered.backoff(n);
+            let full = RetryPolicy::backoff(&jittered.clone().jitter(false), n);
+            assert!(
+                wait >= full / 2 && wait <= full,
+                "{wait:?} outside {full:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn classifies_errors() {
+        let policy = RetryPolicy::exponential(3);
+        assert!(policy.should_retry(2, &JobError::Timeout(Duration::from_secs(1))));
+        assert!(!policy.should_retry(3, &JobError::failed("x")));
+        assert!(!policy.should_retry(1, &JobError::permanent("bad input")));
+        assert!(!policy.should_retry(1, &JobError::Panicked("boom".into())));
+        assert!(!RetryPolicy::none().should_retry(1, &JobError::failed("x")));
+
+        let custom = policy.retry_if(|e| matches!(e, JobError::Panicked(_)));
+        assert!(custom.should_retry(1, &JobError::Panicked("boom".into())));
+        assert!(!custom.should_retry(1, &JobError::failed("x")));
+    }
+}
@@ -0,0 +1,90 @@
+//! Job status tracking.
+
+use std::collections::{HashMap, VecDeque};
+
+use super::JobId;
+
+/// Where a job accepted by a [`TaskQueue`](super::TaskQueue) is in its lifecycle, as
+/// returned by [`TaskQueue::status`](super::TaskQueue::status).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobStatus {
+    /// Waiting for a worker.
+    Queued,
+    /// Waiting for its start time: a delayed job or a retry in backoff.
+    Scheduled,
+    /// Running on a worker.
+    Running,
+    /// Finished successfully.
+    Completed,
+    /// Failed for good and moved to the dead-letter queue.
+    Failed {
+        /// The error from the last attempt.
+        error: String,
+    },
+}
+
+impl JobStatus {
+    /// The lowercase name used in JSON responses, e.g. `"running"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Scheduled => "scheduled",
+            Self::Running => "running",
+            Self::Completed => "completed",
+            Self::Failed { .. } => "failed",
+        }
+    }
+
+    /// Returns `true` for [`Completed`](Self::Completed) and [`Failed`](Self::Failed).
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Completed | Self::Failed { .. })
+    }
+}
+
+// Statuses of live jobs plus the most recent `capacity` finished ones.
+pub(super) struct Statuses {
+    capacity: usize,
+    statuses: HashMap<JobId, JobStatus>,
+    // Finished jobs, oldest first; forgotten once more than `capacity` have finished.
+    finished: VecDeque<JobId>,
+}
+
+impl Statuses {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            statuses: HashMap::new(),
+            finished: VecDeque::new(),
+        }
+    }
+
+    pub(super) fn get(&self, id: JobId) -> Option<JobStatus> {
+        self.statuses.get(&id).cloned()
+    }
+
+    pub(super) fn set(&mut self, id: JobId, status: JobStatus) {
+        let finished = status.is_finished();
+        self.statuses.insert(id, status);
+        if !finished {
+            return;
+        }
+        self.finished.push_back(id);
+        while self.finished.len() > self.capacity {
+            let Some(oldest) = self.finished.pop_front() else {
+                break;
+            };
+            // A requeued job is live again and stays tracked.
+            if self
+                .statuses
+                .get(&oldest)
+                .is_some_and(JobStatus::is_finished)
+            {
+                self.statuses.remove(&oldest);
+            }
+        }
+    }
+
+    pub(super) fn remove(&mut self, id: JobId) {
+        self.statuses.remove(&id);
+    }
+}
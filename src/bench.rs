@@ -0,0 +1,553 @@
+//! Load generation for measuring server performance.
+//!
+//! [`Bench`] opens a number of keep-alive connections to a server, sends the same
+//! request on each as fast as responses come back for a fixed duration, and returns a
+//! [`BenchReport`] with throughput, latency percentiles, and status counts. Run it in
+//! CI against a server started in the same test to catch performance regressions:
+//! compare [`BenchReport::percentile`] against a budget, or keep
+//! [`BenchReport::to_json`] output as an artifact.
+//!
+//! Numbers from a load generator sharing a machine with the server are only
+//! comparable with each other; treat them as a trend, not a capacity figure.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use rttp::bench::Bench;
+//!
+//! # async fn example() -> Result<(), rttp::bench::BenchError> {
+//! let report = Bench::new("127.0.0.1:8080")
+//!     .get("/users/1")
+//!     .concurrency(32)
+//!     .duration(Duration::from_secs(10))
+//!     .run()
+//!     .await?;
+//! println!("{report}");
+//! assert!(report.percentile(99.0) < Duration::from_millis(50));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use serde_json::{Value, json};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream},
+    net::TcpStream,
+    task::JoinSet,
+    time::Instant,
+};
+
+use crate::Method;
+
+/// Errors that stop a benchmark.
+#[derive(Debug, Error)]
+pub enum BenchError {
+    /// No connection to the server could be opened.
+    #[error("cannot connect to {addr}: {source}")]
+    Connect {
+        /// The address being benchmarked.
+        addr: String,
+        /// The underlying error.
+        source: io::Error,
+    },
+}
+
+/// A load test against one server and request.
+///
+/// Defaults: `GET /`, 16 connections, 10 seconds, a 1-second warm-up whose requests
+/// are not measured, and a 5-second timeout per request.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Bench {
+    addr: String,
+    method: Method,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    concurrency: usize,
+    duration: Duration,
+    warmup: Duration,
+    max_requests: Option<u64>,
+    timeout: Duration,
+}
+
+impl Bench {
+    /// Benchmarks the server at `addr`, a `host:port` address.
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            method: Method::Get,
+            path: "/".to_owned(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            concurrency: 16,
+            duration: Duration::from_secs(10),
+            warmup: Duration::from_secs(1),
+            max_requests: None,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Sends `GET path`.
+    pub fn get(self, path: impl Into<String>) -> Self {
+        self.request(Method::Get, path)
+    }
+
+    /// Sends `method path`.
+    pub fn request(mut self, method: Method, path: impl Into<String>) -> Self {
+        self.method = method;
+        self.path = path.into();
+        self
+    }
+
+    /// Adds a request header.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the request body.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Sets the number of connections, each with one request in flight.
+    pub fn concurrency(mut self, connections: usize) -> Self {
+        self.concurrency = connections.max(1);
+        self
+    }
+
+    /// Sets how long requests are measured for.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Sets how long requests are sent before measuring starts.
+    pub fn warmup(mut self, warmup: Duration) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Stops after `requests` measured requests, even before the duration is up.
+    pub fn max_requests(mut self, requests: u64) -> Self {
+        self.max_requests = Some(requests);
+        self
+    }
+
+    /// Sets how long one request may take before it counts as an error and its
+    /// connection is replaced.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Runs the benchmark.
+    ///
+    /// # Errors
+    ///
+    /// [`BenchError::Connect`] if the first connection fails; later connection
+    /// failures are counted as errors in the report.
+    pub async fn run(self) -> Result<BenchReport, BenchError> {
+        let connect = |e| BenchError::Connect {
+            addr: self.addr.clone(),
+            source: e,
+        };
+        let first = TcpStream::connect(&self.addr).await.map_err(connect)?;
+
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\n",
+            self.method, self.path, self.addr
+        );
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        if !self.body.is_empty() || !matches!(self.method, Method::Get | Method::Head) {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        head.push_str("\r\n");
+        let mut request = head.into_bytes();
+        request.extend_from_slice(&self.body);
+        let request: Arc<[u8]> = request.into();
+
+        let measure_from = Instant::now() + self.warmup;
+        let deadline = measure_from + self.duration;
+        let budget = self.max_requests.map(|n| Arc::new(AtomicU64::new(n)));
+        let mut workers = JoinSet::new();
+        let mut first = Some(first);
+        for _ in 0..self.concurrency {
+            let worker = Worker {
+                addr: self.addr.clone(),
+                request: Arc::clone(&request),
+                head_only: self.method == Method::Head,
+                timeout: self.timeout,
+                measure_from,
+                deadline,
+                budget: budget.clone(),
+            };
+            workers.spawn(worker.run(first.take()));
+        }
+
+        let mut samples = Samples::default();
+        while let Some(joined) = workers.join_next().await {
+            if let Ok(worker) = joined {
+                samples.merge(worker);
+            }
+        }
+        let elapsed = Instant::now()
+            .min(deadline)
+            .saturating_duration_since(measure_from);
+        samples.latencies.sort_unstable();
+        Ok(BenchReport {
+            requests: samples.latencies.len() as u64,
+            errors: samples.errors,
+            bytes: samples.bytes,
+            elapsed,
+            statuses: samples.statuses,
+            latencies: samples.latencies,
+        })
+    }
+}
+
+// What one connection measured.
+#[derive(Debug, Default)]
+struct Samples {
+    // Microseconds, one per measured response.
+    latencies: Vec<u64>,
+    statuses: BTreeMap<u16, u64>,
+    errors: u64,
+    bytes: u64,
+}
+
+impl Samples {
+    fn merge(&mut self, other: Samples) {
+        self.latencies.extend(other.latencies);
+        for (status, count) in other.statuses {
+            *self.statuses.entry(status).or_default() += count;
+        }
+        self.errors += other.errors;
+        self.bytes += other.bytes;
+    }
+}
+
+struct Worker {
+    addr: String,
+    request: Arc<[u8]>,
+    head_only: bool,
+    timeout: Duration,
+    measure_from: Instant,
+    deadline: Instant,
+    budget: Option<Arc<AtomicU64>>,
+}
+
+impl Worker {
+    async fn run(self, first: Option<TcpStream>) -> Samples {
+        let mut samples = Samples::default();
+        let mut conn = first.map(BufStream::new);
+        loop {
+            let now = Instant::now();
+            if now >= self.deadline {
+                return samples;
+            }
+            let measured = now >= self.measure_from;
+            if measured && !self.take_budget() {
+                return samples;
+            }
+            let stream = match conn.take() {
+                Some(stream) => stream,
+                None => match TcpStream::connect(&self.addr).await {
+                    Ok(stream) => {
+                        let _ = stream.set_nodelay(true);
+                        BufStream::new(stream)
+                    }
+                    Err(_) => {
+                        if measured {
+                            samples.errors += 1;
+                        }
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                        continue;
+                    }
+                },
+            };
+            let started = Instant::now();
+            let outcome = tokio::time::timeout(self.timeout, self.exchange(stream)).await;
+            let latency = started.elapsed();
+            match outcome {
+                Ok(Ok((status, bytes, stream))) => {
+                    conn = stream;
+                    if measured {
+                        samples.latencies.push(latency.as_micros() as u64);
+                        *samples.statuses.entry(status).or_default() += 1;
+                        samples.bytes += bytes;
+                    }
+                }
+                _ if measured => samples.errors += 1,
+                _ => {}
+            }
+        }
+    }
+
+    fn take_budget(&self) -> bool {
+        let Some(budget) = &self.budget else {
+            return true;
+        };
+        budget
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                left.checked_sub(1)
+            })
+            .is_ok()
+    }
+
+    // Sends the request and reads the whole response, returning the connection if it
+    // can be reused.
+    async fn exchange(
+        &self,
+        mut stream: BufStream<TcpStream>,
+    ) -> io::Result<(u16, u64, Option<BufStream<TcpStream>>)> {
+        stream.write_all(&self.request).await?;
+        stream.flush().await?;
+
+        let mut line = String::new();
+        stream.read_line(&mut line).await?;
+        let status: u16 = line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed status line"))?;
+        let mut length = None;
+        let mut chunked = false;
+        let mut close = false;
+        loop {
+            line.clear();
+            if stream.read_line(&mut line).await? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            let Some((name, value)) = header.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.parse::<u64>().ok();
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.to_ascii_lowercase().contains("chunked");
+            } else if name.eq_ignore_ascii_case("connection") {
+                close = value.eq_ignore_ascii_case("close");
+            }
+        }
+
+        let bodiless = self.head_only || status == 204 || status == 304 || status < 200;
+        let mut sink = tokio::io::sink();
+        let bytes = if bodiless {
+            0
+        } else if chunked {
+            let mut total = 0;
+            loop {
+                line.clear();
+                stream.read_line(&mut line).await?;
+                let size = line.trim().split(';').next().unwrap_or("");
+                let size = u64::from_str_radix(size, 16)
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad chunk size"))?;
+                if size == 0 {
+                    // Trailer fields, up to a blank line.
+                    loop {
+                        line.clear();
+                        if stream.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+                            break;
+                        }
+                    }
+                    break total;
+                }
+                // The chunk and its trailing CRLF.
+                tokio::io::copy(&mut (&mut stream).take(size + 2), &mut sink).await?;
+                total += size;
+            }
+        } else if let Some(length) = length {
+            tokio::io::copy(&mut (&mut stream).take(length), &mut sink).await?
+        } else {
+            close = true;
+            tokio::io::copy(&mut stream, &mut sink).await?
+        };
+        Ok((status, bytes, (!close).then_some(stream)))
+    }
+}
+
+/// The results of a [`Bench`] run.
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    /// Responses received while measuring.
+    pub requests: u64,
+    /// Requests that failed or timed out while measuring.
+    pub errors: u64,
+    /// Response body bytes received while measuring.
+    pub bytes: u64,
+    /// How long measuring lasted.
+    pub elapsed: Duration,
+    /// Response counts by status code.
+    pub statuses: BTreeMap<u16, u64>,
+    // Sorted, in microseconds.
+    latencies: Vec<u64>,
+}
+
+impl BenchReport {
+    /// Responses per second.
+    pub fn throughput(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            0.0
+        } else {
+            self.requests as f64 / secs
+        }
+    }
+
+    /// The latency below which `p` percent of responses arrived, e.g. `99.0`.
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * self.latencies.len() as f64).ceil() as usize;
+        let index = rank.clamp(1, self.latencies.len()) - 1;
+        Duration::from_micros(self.latencies[index])
+    }
+
+    /// The mean latency.
+    pub fn mean(&self) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        Duration::from_micros(self.latencies.iter().sum::<u64>() / self.latencies.len() as u64)
+    }
+
+    /// The slowest response.
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.latencies.last().copied().unwrap_or(0))
+    }
+
+    /// Returns the report as JSON, with latencies in milliseconds.
+    pub fn to_json(&self) -> Value {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        json!({
+            "requests": self.requests,
+            "errors": self.errors,
+            "bytes": self.bytes,
+            "elapsed_secs": self.elapsed.as_secs_f64(),
+            "throughput": self.throughput(),
+            "latency_ms": {
+                "mean": ms(self.mean()),
+                "p50": ms(self.percentile(50.0)),
+                "p90": ms(self.percentile(90.0)),
+                "p99": ms(self.percentile(99.0)),
+                "p999": ms(self.percentile(99.9)),
+                "max": ms(self.max()),
+            },
+            "statuses": self
+                .statuses
+                .iter()
+                .map(|(status, count)| (status.to_string(), json!(count)))
+                .collect::<serde_json::Map<_, _>>(),
+        })
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} requests in {:.2?}, {} errors, {:.1} req/s",
+            self.requests,
+            self.elapsed,
+            self.errors,
+            self.throughput()
+        )?;
+        writeln!(
+            f,
+            "latency: mean {:.2?}, p50 {:.2?}, p90 {:.2?}, p99 {:.2?}, max {:.2?}",
+            self.mean(),
+            self.percentile(50.0),
+            self.percentile(90.0),
+            self.percentile(99.0),
+            self.max()
+        )?;
+        let statuses: Vec<String> = self
+            .statuses
+            .iter()
+            .map(|(status, count)| format!("{status}: {count}"))
+            .collect();
+        write!(f, "statuses: {}", statuses.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Arc;
+
+    use super::*;
+    use crate::{
+        Response, Router, Server, StatusCode,
+        context::Context,
+        middleware::{Next, from_middleware},
+    };
+
+    #[tokio::test]
+    async fn measures_a_running_server() {
+        let mut router = Router::new();
+        router.get("/", |_ctx| async {
+            Response::new(StatusCode::Ok).body("hello")
+        });
+        let server = Server::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr();
+        let pipeline = vec![from_middleware(Arc::new(router))];
+        tokio::spawn(server.run(move |req| Next::new(pipeline.clone()).run(Context::new(req))));
+
+        let report = Bench::new(addr.to_string())
+            .concurrency(4)
+            .warmup(Duration::ZERO)
+            .duration(Duration::from_secs(5))
+            .max_requests(200)
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(report.requests, 200, "{report}");
+        assert_eq!(report.errors, 0);
+        assert_eq!(report.statuses.get(&200), Some(&200));
+        assert_eq!(report.bytes, 1000);
+        assert!(report.percentile(50.0) <= report.percentile(99.0));
+        assert!(report.percentile(100.0) == report.max());
+        assert_eq!(report.to_json()["statuses"]["200"], 200);
+
+        let unreachable = Bench::new("127.0.0.1:1").run().await;
+        assert!(matches!(unreachable, Err(BenchError::Connect { .. })));
+    }
+
+    #[test]
+    fn computes_percentiles() {
+        let report = BenchReport {
+            requests: 4,
+            errors: 0,
+            bytes: 0,
+            elapsed: Duration::from_secs(2),
+            statuses: BTreeMap::new(),
+            latencies: vec![1000, 2000, 3000, 10_000],
+        };
+        assert_eq!(report.throughput(), 2.0);
+        assert_eq!(report.percentile(50.0), Duration::from_millis(2));
+        assert_eq!(report.percentile(75.0), Duration::from_millis(3));
+        assert_eq!(report.percentile(99.0), Duration::from_millis(10));
+        assert_eq!(report.mean(), Duration::from_millis(4));
+    }
+}
@@ -0,0 +1,304 @@
+//! Admin routes for purging responses stored by [`CacheMiddleware`].
+//!
+//! [`CacheAdmin`] mounts three `POST` routes under a base path (`/admin/cache` by
+//! default), each answering `{"purged": n}` with the number of entries removed:
+//!
+//! - `…/purge/key` with `{"key": "GET /posts?page=2"}` — one entry, by the key
+//!   [`CacheKeyBuilder`](super::CacheKeyBuilder) derived for it. A bare path such as
+//!   `{"key": "/posts"}` purges both its `GET` and `HEAD` entries.
+//! - `…/purge/tag` with `{"tag": "posts"}` — every entry labelled with
+//!   [`CacheTagsExt::cache_tags`](super::CacheTagsExt::cache_tags).
+//! - `…/purge/all` — every entry the middleware stored, when it was built with
+//!   [`tag_all`](super::CacheMiddleware::tag_all); otherwise nothing.
+//!
+//! The routes sit behind an [`ApiKeyMiddleware`](crate::security::ApiKeyMiddleware):
+//! requests must carry one of the configured API keys, as `X-API-Key` or as a bearer
+//! token, and without any key configured every request is refused.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use rttp::Router;
+//! use rttp::cache::{Cache, CacheAdmin, CacheMiddleware, MemoryCache};
+//!
+//! let cache: Arc<dyn Cache> = Arc::new(MemoryCache::new(10_000));
+//! let caching = CacheMiddleware::new(Arc::clone(&cache)).tag_all(true);
+//!
+//! let mut router = Router::new();
+//! CacheAdmin::new(cache).api_key("ops-secret").mount(&mut router);
+//! ```
+//!
+//! [`CacheMiddleware`]: super::CacheMiddleware
+
+use std::sync::Arc;
+
+use serde_json::{Value, json};
+use tracing::{info, warn};
+
+use super::{Cache, CacheError, middleware::all_tag};
+use crate::{
+    Response, Router, StatusCode,
+    context::Context,
+    middleware::{MiddlewareHandler, Next, from_middleware},
+    security::api_key_auth,
+};
+
+/// Purge endpoints for a [`CacheMiddleware`](super::CacheMiddleware)'s responses. See the
+/// [module docs](self) for the routes.
+#[derive(Clone)]
+pub struct CacheAdmin {
+    cache: Arc<dyn Cache>,
+    prefix: String,
+    path: String,
+    keys: Vec<String>,
+}
+
+impl CacheAdmin {
+    /// Creates admin routes for responses stored in `cache` under the default `http:`
+    /// prefix.
+    pub fn new(cache: Arc<dyn Cache>) -> Self {
+        Self {
+            cache,
+            prefix: "http:".to_owned(),
+            path: "/admin/cache".to_owned(),
+            keys: Vec::new(),
+        }
+    }
+
+    /// Sets the key prefix, matching [`CacheMiddleware::prefix`](super::CacheMiddleware::prefix).
+    #[must_use]
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the base path [`mount`](Self::mount) registers under; `/admin/cache` by
+    /// default.
+    #[must_use]
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into().trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// Accepts `key` from callers; other requests get `401 Unauthorized`.
+    #[must_use]
+    pub fn api_key(mut self, key: impl Into<String>) -> Self {
+        self.keys.push(key.into());
+        self
+    }
+
+    /// Registers `POST {path}/purge/:scope` on `router`, behind the API key check.
+    pub fn mount(self, router: &mut Router) {
+        let path = format!("{}/purge/:scope", self.path);
+        let auth = api_key_auth(self.keys.clone()).on_reject(|| {
+            error(StatusCode::Unauthorized, "invalid API key").header("WWW-Authenticate", "Bearer")
+        });
+        let admin = Arc::new(self);
+        let purge: MiddlewareHandler = Arc::new(move |ctx: Context, _next: Next| {
+            let admin = Arc::clone(&admin);
+            Box::pin(async move { admin.handle(ctx).await })
+        });
+        let chain: Arc<[MiddlewareHandler]> = vec![from_middleware(Arc::new(auth)), purge].into();
+        router.post(&path, move |ctx: Context| {
+            Next::new(Arc::clone(&chain)).run(ctx)
+        });
+    }
+
+    // Answers one purge request; `scope` is the `:scope` route parameter.
+    async fn handle(&self, ctx: Context) -> Response {
+        let request = ctx.request();
+        let body: Value = if request.body().is_empty() {
+            Value::Null
+        } else {
+            match serde_json::from_slice(request.body()) {
+                Ok(body) => body,
+                Err(_) => return error(StatusCode::BadRequest, "body must be JSON"),
+            }
+        };
+        let field = |name: &str| body.get(name).and_then(Value::as_str).map(str::to_owned);
+
+        let scope = ctx.params().get("scope").unwrap_or_default();
+        let result = match scope {
+            "key" => match field("key") {
+                Some(key) => self.purge_key(&key).await,
+                None => return error(StatusCode::BadRequest, "missing \"key\""),
+            },
+            "tag" => match field("tag") {
+                Some(tag) => self.cache.invalidate_tag(&tag).await,
+                None => return error(StatusCode::BadRequest, "missing \"tag\""),
+            },
+            "all" => self.cache.invalidate_tag(&all_tag(&self.prefix)).await,
+            _ => return error(StatusCode::NotFound, "unknown purge scope"),
+        };
+        match result {
+            Ok(purged) => {
+                info!(scope, purged, "purged cached responses");
+                Response::new(StatusCode::Ok)
+                    .header("Content-Type", "application/json")
+                    .body(json!({ "purged": purged }).to_string())
+            }
+            Err(e) => {
+                warn!(error = %e, scope, "cache purge failed");
+                error(StatusCode::ServiceUnavailable, "cache unavailable")
+            }
+        }
+    }
+
+    // Deletes the entry for `key`, or the `GET` and `HEAD` entries for a bare path.
+    async fn purge_key(&self, key: &str) -> Result<usize, CacheError> {
+        let keys = if key.starts_with('/') {
+            vec![
+                format!("{}GET {key}", self.prefix),
+                format!("{}HEAD {key}", self.prefix),
+            ]
+        } else {
+            vec![format!("{}{key}", self.prefix)]
+        };
+        let mut purged = 0;
+        for key in keys {
+            if self.cache.delete(&key).await? {
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+}
+
+impl std::fmt::Debug for CacheAdmin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CacheAdmin")
+            .field("prefix", &self.prefix)
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    Response::new(status)
+        .header("Content-Type", "application/json")
+        .body(json!({ "error": message }).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{
+        cache::{CacheMiddleware, CacheTagsExt, MemoryCache},
+        middleware::from_middleware,
+        testing::TestClient,
+    };
+
+    // A cached app with `/posts` tagged `posts`, `/about` untagged, and the admin routes.
+    fn client(tag_all: bool) -> (TestClient, Arc<AtomicUsize>) {
+        let cache: Arc<dyn Cache> = Arc::new(MemoryCache::new(100));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut router = Router::new();
+        for path in ["/posts", "/about"] {
+            let calls = Arc::clone(&calls);
+            router.get(path, move |ctx: Context| {
+                let n = calls.fetch_add(1, Ordering::SeqCst);
+                if ctx.request().path() == "/posts" {
+                    ctx.cache_tags(["posts"]);
+                }
+                async move { Response::new(StatusCode::Ok).body(n.to_string()) }
+            });
+        }
+        CacheAdmin::new(Arc::clone(&cache))
+            .api_key("secret")
+            .mount(&mut router);
+        let client = TestClient::pipeline(vec![
+            from_middleware(Arc::new(CacheMiddleware::new(cache).tag_all(tag_all))),
+            from_middleware(Arc::new(router)),
+        ]);
+        (client, calls)
+    }
+
+    async fn purge(client: &mut TestClient, scope: &str, body: Value) -> Value {
+        client
+            .post(&format!("/admin/cache/purge/{scope}"))
+            .header("X-API-Key", "secret")
+            .json(&body)
+            .send()
+            .await
+            .assert_status(StatusCode::Ok)
+            .json()
+    }
+
+    #[tokio::test]
+    async fn requires_api_key() {
+        let (mut client, _) = client(true);
+        let path = "/admin/cache/purge/all";
+        client
+            .post(path)
+            .send()
+            .await
+            .assert_status(StatusCode::Unauthorized);
+        client
+            .post(path)
+            .header("X-API-Key", "wrong")
+            .send()
+            .await
+            .assert_status(StatusCode::Unauthorized);
+        client
+            .post(path)
+            .bearer("secret")
+            .send()
+            .await
+            .assert_status(StatusCode::Ok);
+    }
+
+    #[tokio::test]
+    async fn purges_by_key_tag_and_all() {
+        let (mut client, calls) = client(true);
+        client.get("/posts").send().await;
+        client.get("/about").send().await;
+        client.get("/posts").send().await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let purged = purge(&mut client, "key", json!({ "key": "/about" })).await;
+        assert_eq!(purged, json!({ "purged": 1 }));
+        client.get("/about").send().await;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let purged = purge(&mut client, "tag", json!({ "tag": "posts" })).await;
+        assert_eq!(purged, json!({ "purged": 1 }));
+        client.get("/posts").send().await;
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        let purged = purge(&mut client, "all", Value::Null).await;
+        assert_eq!(purged, json!({ "purged": 2 }));
+        client.get("/posts").send().await;
+        client.get("/about").send().await;
+        assert_eq!(calls.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn purge_all_needs_the_catch_all_tag() {
+        let (mut client, calls) = client(false);
+        client.get("/posts").send().await;
+        let purged = purge(&mut client, "all", Value::Null).await;
+        assert_eq!(purged, json!({ "purged": 0 }));
+        client.get("/posts").send().await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_requests() {
+        let (mut client, _) = client(true);
+        for (scope, status) in [
+            ("key", StatusCode::BadRequest),
+            ("nope", StatusCode::NotFound),
+        ] {
+            client
+                .post(&format!("/admin/cache/purge/{scope}"))
+                .header("X-API-Key", "secret")
+                .json(&json!({}))
+                .send()
+                .await
+                .assert_status(status);
+        }
+    }
+}
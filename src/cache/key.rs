@@ -0,0 +1,271 @@
+//! Cache key derivation from request attributes.
+//!
+//! The default key — method, path, and the full query string — is wrong for many APIs:
+//! tracking parameters (`utm_source`, `fbclid`) split one page into thousands of
+//! entries, `?a=1&b=2` and `?b=2&a=1` miss each other, and per-user responses must not
+//! be shared at all. [`CacheKeyBuilder`] selects exactly which attributes form the key
+//! and how they are normalized.
+//!
+//! # Examples
+//!
+//! ```
+//! use rttp::{Request, cache::CacheKeyBuilder, context::Context};
+//!
+//! let key = CacheKeyBuilder::new()
+//!     .ignore_query_params(["utm_source", "utm_medium"])
+//!     .header("Accept-Language");
+//!
+//! let raw = b"GET /posts?utm_source=x&page=2&sort=new HTTP/1.1\r\nAccept-Language: fr\r\n\r\n";
+//! let ctx = Context::new(Request::parse(raw).unwrap().0);
+//! assert_eq!(key.build(&ctx), "GET /posts?page=2&sort=new\nh:accept-language=fr");
+//! ```
+
+use crate::{context::Context, security::Principal};
+
+/// Which query parameters take part in the key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum QueryKey {
+    All,
+    None,
+    Only(Vec<String>),
+    Except(Vec<String>),
+}
+
+/// Chooses the request attributes that make up a cache key.
+///
+/// The key always starts with the method and path, followed by the selected query
+/// parameters and then one line per selected header, cookie, and the principal. Absent
+/// headers and cookies still contribute an empty value, so "no header" and "header set"
+/// never share an entry.
+///
+/// Defaults: every query parameter, sorted by name; no headers, cookies, or principal;
+/// path compared case-sensitively.
+#[derive(Debug, Clone)]
+pub struct CacheKeyBuilder {
+    query: QueryKey,
+    sort_query: bool,
+    lowercase_path: bool,
+    headers: Vec<String>,
+    cookies: Vec<String>,
+    principal: bool,
+}
+
+impl Default for CacheKeyBuilder {
+    fn default() -> Self {
+        Self {
+            query: QueryKey::All,
+            sort_query: true,
+            lowercase_path: false,
+            headers: Vec::new(),
+            cookies: Vec::new(),
+            principal: false,
+        }
+    }
+}
+
+impl CacheKeyBuilder {
+    /// Creates a builder with the default key (method, path, sorted query).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keys only on the named query parameters, ignoring all others.
+    #[must_use]
+    pub fn query_params<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.query = QueryKey::Only(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Keys on every query parameter except the named ones.
+    #[must_use]
+    pub fn ignore_query_params<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.query = QueryKey::Except(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Leaves the query string out of the key entirely.
+    #[must_use]
+    pub fn without_query(mut self) -> Self {
+        self.query = QueryKey::None;
+        self
+    }
+
+    /// Whether query parameters are sorted by name (default `true`). Repeated
+    /// parameters keep their relative order either way.
+    #[must_use]
+    pub fn sort_query(mut self, sort: bool) -> Self {
+        self.sort_query = sort;
+        self
+    }
+
+    /// Whether the path is lowercased, for applications with case-insensitive routes
+    /// (default `false`).
+    #[must_use]
+    pub fn lowercase_path(mut self, lowercase: bool) -> Self {
+        self.lowercase_path = lowercase;
+        self
+    }
+
+    /// Adds a request header's value to the key. Names are case-insensitive.
+    #[must_use]
+    pub fn header(mut self, name: impl Into<String>) -> Self {
+        let name = name.into().to_ascii_lowercase();
+        if !self.headers.contains(&name) {
+            self.headers.push(name);
+        }
+        self
+    }
+
+    /// Adds a cookie's value to the key.
+    #[must_use]
+    pub fn cookie(mut self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        if !self.cookies.contains(&name) {
+            self.cookies.push(name);
+        }
+        self
+    }
+
+    /// Adds the authenticated [`Principal`]'s id to the key, giving each user their own
+    /// entries. Requests without a principal share the anonymous entry.
+    ///
+    /// Place the caching middleware after the authentication middleware so the principal
+    /// is already in the request extensions.
+    #[must_use]
+    pub fn principal(mut self) -> Self {
+        self.principal = true;
+        self
+    }
+
+    /// Returns `true` if keys are partitioned by [`principal`](Self::principal).
+    pub fn is_per_principal(&self) -> bool {
+        self.principal
+    }
+
+    /// Derives the key for `ctx`.
+    pub fn build(&self, ctx: &Context) -> String {
+        let request = ctx.request();
+        let mut key = format!("{} ", request.method());
+        if self.lowercase_path {
+            key.push_str(&request.path().to_lowercase());
+        } else {
+            key.push_str(request.path());
+        }
+
+        let query = self.query_pairs(request.query_string().unwrap_or(""));
+        if !query.is_empty() {
+            key.push('?');
+            key.push_str(&query.join("&"));
+        }
+
+        for name in &self.headers {
+            let values: Vec<&str> = request.headers().get_all(name).map(str::trim).collect();
+            key.push_str(&format!("\nh:{name}={}", values.join(",")));
+        }
+        for name in &self.cookies {
+            let value = request.cookie(name).unwrap_or("");
+            key.push_str(&format!("\nc:{name}={value}"));
+        }
+        if self.principal {
+            let id = ctx
+                .extensions()
+                .get::<Principal>()
+                .map_or("", Principal::id);
+            key.push_str(&format!("\nu:{id}"));
+        }
+        key
+    }
+
+    // The selected `name=value` pairs, still encoded, in key order.
+    fn query_pairs<'a>(&self, query: &'a str) -> Vec<&'a str> {
+        let name = |pair: &str| pair.split_once('=').map_or(pair, |(n, _)| n).to_owned();
+        let mut pairs: Vec<&str> = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .filter(|pair| match &self.query {
+                QueryKey::All => true,
+                QueryKey::None => false,
+                QueryKey::Only(names) => names.contains(&name(pair)),
+                QueryKey::Except(names) => !names.contains(&name(pair)),
+            })
+            .collect();
+        if self.sort_query {
+            pairs.sort_by_key(|pair| name(pair));
+        }
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Request;
+
+    fn ctx(target: &str, headers: &str) -> Context {
+        let raw = format!("GET {target} HTTP/1.1\r\nHost: x\r\n{headers}\r\n");
+        Context::new(Request::parse(raw.as_bytes()).unwrap().0)
+    }
+
+    #[test]
+    fn default_key_sorts_query() {
+        let key = CacheKeyBuilder::new();
+        assert_eq!(key.build(&ctx("/a?b=2&a=1&b=1", "")), "GET /a?a=1&b=2&b=1");
+        assert_eq!(key.build(&ctx("/a", "")), "GET /a");
+        assert_eq!(
+            CacheKeyBuilder::new()
+                .sort_query(false)
+                .build(&ctx("/a?b=2&a=1", "")),
+            "GET /a?b=2&a=1"
+        );
+    }
+
+    #[test]
+    fn query_selection() {
+        let c = ctx("/s?q=rust&utm_source=x&page=2&flag", "");
+        assert_eq!(
+            CacheKeyBuilder::new().query_params(["q", "page"]).build(&c),
+            "GET /s?page=2&q=rust"
+        );
+        assert_eq!(
+            CacheKeyBuilder::new()
+                .ignore_query_params(["utm_source"])
+                .build(&c),
+            "GET /s?flag&page=2&q=rust"
+        );
+        assert_eq!(CacheKeyBuilder::new().without_query().build(&c), "GET /s");
+    }
+
+    #[test]
+    fn headers_cookies_and_case() {
+        let key = CacheKeyBuilder::new()
+            .header("Accept")
+            .header("accept")
+            .header("X-Missing")
+            .cookie("theme")
+            .lowercase_path(true);
+        assert_eq!(
+            key.build(&ctx(
+                "/Docs",
+                "Accept: text/html\r\nCookie: theme=dark; sid=1\r\n"
+            )),
+            "GET /docs\nh:accept=text/html\nh:x-missing=\nc:theme=dark"
+        );
+    }
+
+    #[test]
+    fn principal_partitions_keys() {
+        let key = CacheKeyBuilder::new().principal();
+        let mut c = ctx("/me", "");
+        assert_eq!(key.build(&c), "GET /me\nu:");
+        c.extensions_mut().insert(Principal::new("alice"));
+        assert_eq!(key.build(&c), "GET /me\nu:alice");
+    }
+}
@@ -0,0 +1,744 @@
+//! Memcached-backed [`Cache`] speaking the binary protocol.
+//!
+//! Available with the `memcached` feature.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, BufStream},
+    net::TcpStream,
+    sync::Mutex,
+};
+
+use super::{Cache, CacheError, CacheFuture, CacheStats, stats::StatsRecorder};
+use crate::security::crypto::{sha256, to_hex};
+
+/// Virtual points each node owns on the hash ring.
+const POINTS_PER_NODE: usize = 160;
+
+/// Longest key memcached accepts.
+const MAX_KEY_LEN: usize = 250;
+
+/// Expirations above this many seconds are read by memcached as Unix timestamps.
+const MAX_RELATIVE_EXPIRY: u64 = 30 * 24 * 60 * 60;
+
+const OP_GET: u8 = 0x00;
+const OP_SET: u8 = 0x01;
+const OP_ADD: u8 = 0x02;
+const OP_DELETE: u8 = 0x04;
+const OP_INCREMENT: u8 = 0x05;
+const OP_APPEND: u8 = 0x0e;
+const OP_TOUCH: u8 = 0x1c;
+
+const STATUS_OK: u16 = 0x0000;
+const STATUS_NOT_FOUND: u16 = 0x0001;
+const STATUS_EXISTS: u16 = 0x0002;
+const STATUS_NOT_STORED: u16 = 0x0005;
+
+/// A [`Cache`] spread over one or more memcached servers.
+///
+/// - **Binary protocol** — one lazily opened connection per node, re-opened after any
+///   I/O error, like [`RedisClient`](crate::redis::RedisClient).
+/// - **Consistent hashing** — keys are placed on a hash ring with 160 virtual points per
+///   node, so adding or removing a node only moves the keys that node owned.
+/// - **Keys** — live under `<prefix><key>`; keys longer than memcached's 250-byte limit
+///   are replaced by `<prefix>sha256:<hex digest>`. TTLs are rounded up to whole
+///   seconds.
+/// - **Tags** — each tag is a list of member keys appended to `<tag prefix><tag>`.
+///   Invalidation deletes the listed entries and then the list, using CAS so members
+///   appended meanwhile are not lost. Tag lists carry no expiry, but memcached may still
+///   evict them under memory pressure, after which their entries are only removed by
+///   their TTL.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::{sync::Arc, time::Duration};
+/// use rttp::cache::{Cache, MemcachedCache};
+///
+/// let cache: Arc<dyn Cache> = Arc::new(
+///     MemcachedCache::new(["10.0.0.1:11211", "10.0.0.2:11211"])
+///         .default_ttl(Duration::from_secs(300)),
+/// );
+/// ```
+pub struct MemcachedCache {
+    nodes: Vec<Node>,
+    // Ring point → index into `nodes`.
+    ring: BTreeMap<u32, usize>,
+    prefix: String,
+    tag_prefix: String,
+    default_ttl: Option<Duration>,
+    stats: StatsRecorder,
+}
+
+struct Node {
+    addr: String,
+    conn: Mutex<Option<BufStream<TcpStream>>>,
+}
+
+/// A decoded response packet.
+struct Reply {
+    status: u16,
+    cas: u64,
+    value: Vec<u8>,
+}
+
+impl MemcachedCache {
+    /// Creates a cache over the servers at `addrs` (e.g. `"127.0.0.1:11211"`), with
+    /// keys under `cache:` and tags under `cache-tag:`.
+    ///
+    /// No connection is made until the first operation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `addrs` is empty.
+    pub fn new<I, S>(addrs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let nodes: Vec<Node> = addrs
+            .into_iter()
+            .map(|addr| Node {
+                addr: addr.into(),
+                conn: Mutex::new(None),
+            })
+            .collect();
+        assert!(
+            !nodes.is_empty(),
+            "MemcachedCache needs at least one server"
+        );
+        let mut ring = BTreeMap::new();
+        for (index, node) in nodes.iter().enumerate() {
+            for point in 0..POINTS_PER_NODE {
+                ring.insert(
+                    ring_hash(format!("{}-{point}", node.addr).as_bytes()),
+                    index,
+                );
+            }
+        }
+        Self {
+            nodes,
+            ring,
+            prefix: "cache:".to_owned(),
+            tag_prefix: "cache-tag:".to_owned(),
+            default_ttl: None,
+            stats: StatsRecorder::default(),
+        }
+    }
+
+    /// Sets the prefix namespacing value keys.
+    #[must_use]
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the prefix namespacing tag lists.
+    #[must_use]
+    pub fn tag_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.tag_prefix = prefix.into();
+        self
+    }
+
+    /// Sets the expiry for entries stored without an explicit TTL. Without one, such
+    /// entries never expire.
+    #[must_use]
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Returns the address of the server owning `key`.
+    pub fn server_for(&self, key: &str) -> &str {
+        &self.node(&self.key(&self.prefix, key)).addr
+    }
+
+    fn key(&self, prefix: &str, key: &str) -> Vec<u8> {
+        if prefix.len() + key.len() <= MAX_KEY_LEN {
+            return format!("{prefix}{key}").into_bytes();
+        }
+        format!("{prefix}sha256:{}", to_hex(&sha256(key.as_bytes()))).into_bytes()
+    }
+
+    fn node(&self, key: &[u8]) -> &Node {
+        let hash = ring_hash(key);
+        let (_, &index) = self
+            .ring
+            .range(hash..)
+            .next()
+            .or_else(|| self.ring.iter().next())
+            .expect("ring has points for every node");
+        &self.nodes[index]
+    }
+
+    async fn call(
+        &self,
+        opcode: u8,
+        key: &[u8],
+        extras: &[u8],
+        value: &[u8],
+        cas: u64,
+    ) -> Result<Reply, CacheError> {
+        let node = self.node(key);
+        node.request(opcode, key, extras, value, cas)
+            .await
+            .map_err(|e| CacheError::Backend(format!("memcached {}: {e}", node.addr)))
+    }
+
+    // Appends `member` to the list at `tag_key`, creating the list if needed.
+    async fn add_member(&self, tag_key: &[u8], member: &[u8]) -> Result<(), CacheError> {
+        let mut record = (member.len() as u32).to_be_bytes().to_vec();
+        record.extend_from_slice(member);
+        loop {
+            match self.call(OP_APPEND, tag_key, &[], &record, 0).await?.status {
+                STATUS_OK => return Ok(()),
+                STATUS_NOT_STORED => {}
+                status => return Err(status_error(status)),
+            }
+            // No list yet: create it, unless another writer just did.
+            match self
+                .call(OP_ADD, tag_key, &store_extras(0), &record, 0)
+                .await?
+                .status
+            {
+                STATUS_OK => return Ok(()),
+                STATUS_EXISTS | STATUS_NOT_STORED => continue,
+                status => return Err(status_error(status)),
+            }
+        }
+    }
+}
+
+impl Node {
+    async fn request(
+        &self,
+        opcode: u8,
+        key: &[u8],
+        extras: &[u8],
+        value: &[u8],
+        cas: u64,
+    ) -> std::io::Result<Reply> {
+        let mut guard = self.conn.lock().await;
+        if guard.is_none() {
+            *guard = Some(BufStream::new(TcpStream::connect(&self.addr).await?));
+        }
+        let conn = guard.as_mut().expect("connection was just established");
+
+        let result = async {
+            conn.write_all(&encode_request(opcode, key, extras, value, cas))
+                .await?;
+            conn.flush().await?;
+            read_reply(conn).await
+        }
+        .await;
+        if result.is_err() {
+            *guard = None;
+        }
+        result
+    }
+}
+
+impl Cache for MemcachedCache {
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Option<Arc<[u8]>>> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let reply = self
+                .call(OP_GET, &self.key(&self.prefix, key), &[], &[], 0)
+                .await;
+            self.stats.operation(start.elapsed());
+            let value = match reply?.status_or_missing()? {
+                Some(reply) => Some(Arc::from(reply.value)),
+                None => None,
+            };
+            self.stats.lookup(value.is_some());
+            Ok(value)
+        })
+    }
+
+    fn set<'a>(
+        &'a self,
+        key: &'a str,
+        value: Arc<[u8]>,
+        ttl: Option<Duration>,
+    ) -> CacheFuture<'a, ()> {
+        self.set_tagged(key, value, ttl, &[])
+    }
+
+    fn set_tagged<'a>(
+        &'a self,
+        key: &'a str,
+        value: Arc<[u8]>,
+        ttl: Option<Duration>,
+        tags: &'a [String],
+    ) -> CacheFuture<'a, ()> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let key = self.key(&self.prefix, key);
+            // As with Redis: list the key under its tags before storing it.
+            for tag in tags {
+                self.add_member(&self.key(&self.tag_prefix, tag), &key)
+                    .await?;
+            }
+            let extras = store_extras(expiration(ttl.or(self.default_ttl)));
+            let reply = self.call(OP_SET, &key, &extras, &value, 0).await;
+            self.stats.operation(start.elapsed());
+            match reply?.status {
+                STATUS_OK => Ok(()),
+                status => Err(status_error(status)),
+            }
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> CacheFuture<'a, bool> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let reply = self
+                .call(OP_DELETE, &self.key(&self.prefix, key), &[], &[], 0)
+                .await;
+            self.stats.operation(start.elapsed());
+            Ok(reply?.status_or_missing()?.is_some())
+        })
+    }
+
+    fn increment<'a>(
+        &'a self,
+        key: &'a str,
+        delta: u64,
+        ttl: Option<Duration>,
+    ) -> CacheFuture<'a, u64> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let key = self.key(&self.prefix, key);
+            // A missing counter is created holding `delta`, expiring per the default TTL.
+            let mut extras = [0u8; 20];
+            extras[..8].copy_from_slice(&delta.to_be_bytes());
+            extras[8..16].copy_from_slice(&delta.to_be_bytes());
+            let created = expiration(ttl.or(self.default_ttl));
+            extras[16..].copy_from_slice(&created.to_be_bytes());
+            let reply = self.call(OP_INCREMENT, &key, &extras, &[], 0).await;
+            let value = match reply {
+                Ok(reply) if reply.status == STATUS_OK => reply
+                    .value
+                    .first_chunk::<8>()
+                    .map(|value| u64::from_be_bytes(*value))
+                    .ok_or_else(|| CacheError::Backend("short memcached counter".into())),
+                Ok(reply) => Err(status_error(reply.status)),
+                Err(e) => Err(e),
+            };
+            // The increment leaves an existing counter's expiry alone.
+            if value.is_ok() && ttl.is_some() {
+                let extras = expiration(ttl).to_be_bytes();
+                self.call(OP_TOUCH, &key, &extras, &[], 0)
+                    .await?
+                    .status_or_missing()?;
+            }
+            self.stats.operation(start.elapsed());
+            value
+        })
+    }
+
+    fn invalidate_tag<'a>(&'a self, tag: &'a str) -> CacheFuture<'a, usize> {
+        Box::pin(async move {
+            let tag_key = self.key(&self.tag_prefix, tag);
+            let mut deleted = BTreeSet::new();
+            let mut removed = 0;
+            loop {
+                let Some(list) = self
+                    .call(OP_GET, &tag_key, &[], &[], 0)
+                    .await?
+                    .status_or_missing()?
+                else {
+                    return Ok(removed);
+                };
+                for member in decode_members(&list.value) {
+                    if deleted.insert(member.to_vec()) {
+                        let reply = self.call(OP_DELETE, member, &[], &[], 0).await?;
+                        removed += usize::from(reply.status_or_missing()?.is_some());
+                    }
+                }
+                // Drop the list only if nothing was appended since we read it.
+                match self
+                    .call(OP_DELETE, &tag_key, &[], &[], list.cas)
+                    .await?
+                    .status
+                {
+                    STATUS_OK | STATUS_NOT_FOUND => return Ok(removed),
+                    STATUS_EXISTS => continue,
+                    status => return Err(status_error(status)),
+                }
+            }
+        })
+    }
+
+    fn stats(&self) -> Option<CacheStats> {
+        Some(self.stats.snapshot())
+    }
+}
+
+impl Reply {
+    // `Some(self)` on success, `None` for a missing key, an error otherwise.
+    fn status_or_missing(self) -> Result<Option<Self>, CacheError> {
+        match self.status {
+            STATUS_OK => Ok(Some(self)),
+            STATUS_NOT_FOUND => Ok(None),
+            status => Err(status_error(status)),
+        }
+    }
+}
+
+fn status_error(status: u16) -> CacheError {
+    CacheError::Backend(format!("memcached error status {status:#06x}"))
+}
+
+fn ring_hash(bytes: &[u8]) -> u32 {
+    let digest = sha256(bytes);
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]])
+}
+
+// The memcached expiration field for `ttl`: 0 for none, whole seconds (at least one)
+// up to 30 days, and an absolute Unix time beyond that.
+fn expiration(ttl: Option<Duration>) -> u32 {
+    let Some(ttl) = ttl else {
+        return 0;
+    };
+    let secs = ttl.as_secs() + u64::from(ttl.subsec_nanos() > 0);
+    let secs = secs.max(1);
+    let value = if secs <= MAX_RELATIVE_EXPIRY {
+        secs
+    } else {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        now + secs
+    };
+    u32::try_from(value).unwrap_or(u32::MAX)
+}
+
+// SET/ADD extras: flags (unused) and expiration.
+fn store_extras(expiration: u32) -> [u8; 8] {
+    let mut extras = [0u8; 8];
+    extras[4..].copy_from_slice(&expiration.to_be_bytes());
+    extras
+}
+
+fn decode_members(mut list: &[u8]) -> impl Iterator<Item = &[u8]> {
+    std::iter::from_fn(move || {
+        let (len, rest) = list.split_first_chunk::<4>()?;
+        let len = u32::from_be_bytes(*len) as usize;
+        let member = rest.get(..len)?;
+        list = &rest[len..];
+        Some(member)
+    })
+}
+
+/// Encodes a request packet: 24-byte header, then extras, key, and value.
+fn encode_request(opcode: u8, key: &[u8], extras: &[u8], value: &[u8], cas: u64) -> Vec<u8> {
+    let body = extras.len() + key.len() + value.len();
+    let mut packet = Vec::with_capacity(24 + body);
+    packet.push(0x80);
+    packet.push(opcode);
+    packet.extend_from_slice(&(key.len() as u16).to_be_bytes());
+    packet.push(extras.len() as u8);
+    packet.push(0); // data type
+    packet.extend_from_slice(&[0, 0]); // vbucket
+    packet.extend_from_slice(&(body as u32).to_be_bytes());
+    packet.extend_from_slice(&[0; 4]); // opaque
+    packet.extend_from_slice(&cas.to_be_bytes());
+    packet.extend_from_slice(extras);
+    packet.extend_from_slice(key);
+    packet.extend_from_slice(value);
+    packet
+}
+
+async fn read_reply(conn: &mut BufStream<TcpStream>) -> std::io::Result<Reply> {
+    let mut header = [0u8; 24];
+    conn.read_exact(&mut header).await?;
+    if header[0] != 0x81 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "bad response magic",
+        ));
+    }
+    let key_len = usize::from(u16::from_be_bytes([header[2], header[3]]));
+    let extras_len = usize::from(header[4]);
+    let status = u16::from_be_bytes([header[6], header[7]]);
+    let body_len = u32::from_be_bytes([header[8], header[9], header[10], header[11]]) as usize;
+    let cas = u64::from_be_bytes(header[16..24].try_into().expect("8-byte slice"));
+    if extras_len + key_len > body_len {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "response body shorter than its extras and key",
+        ));
+    }
+    let mut body = vec![0u8; body_len];
+    conn.read_exact(&mut body).await?;
+    Ok(Reply {
+        status,
+        cas,
+        value: body.split_off(extras_len + key_len),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{
+            Mutex as StdMutex,
+            atomic::{AtomicU64, Ordering},
+        },
+    };
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::cache::CacheExt;
+
+    // Key → (value, CAS).
+    type Store = StdMutex<HashMap<Vec<u8>, (Vec<u8>, u64)>>;
+
+    /// In-process memcached answering GET, SET, ADD, DELETE, INCREMENT, APPEND, and
+    /// TOUCH, with CAS; expirations are ignored.
+    struct FakeMemcached {
+        addr: String,
+        store: Arc<Store>,
+    }
+
+    impl FakeMemcached {
+        async fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap().to_string();
+            let store: Arc<Store> = Arc::default();
+            let shared = Arc::clone(&store);
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let store = Arc::clone(&shared);
+                    tokio::spawn(async move {
+                        let mut conn = BufStream::new(stream);
+                        while let Ok(reply) = serve(&mut conn, &store).await {
+                            if conn.write_all(&reply).await.is_err() || conn.flush().await.is_err()
+                            {
+                                break;
+                            }
+                        }
+                    });
+                }
+            });
+            Self { addr, store }
+        }
+
+        fn len(&self) -> usize {
+            self.store.lock().unwrap().len()
+        }
+    }
+
+    async fn serve(conn: &mut BufStream<TcpStream>, store: &Store) -> std::io::Result<Vec<u8>> {
+        static NEXT_CAS: AtomicU64 = AtomicU64::new(1);
+
+        let mut header = [0u8; 24];
+        conn.read_exact(&mut header).await?;
+        let key_len = usize::from(u16::from_be_bytes([header[2], header[3]]));
+        let extras_len = usize::from(header[4]);
+        let body_len = u32::from_be_bytes(header[8..12].try_into().unwrap()) as usize;
+        let cas = u64::from_be_bytes(header[16..24].try_into().unwrap());
+        let mut body = vec![0u8; body_len];
+        conn.read_exact(&mut body).await?;
+        let key = body[extras_len..extras_len + key_len].to_vec();
+        let value = body[extras_len + key_len..].to_vec();
+
+        let mut store = store.lock().unwrap();
+        let new_cas = NEXT_CAS.fetch_add(1, Ordering::Relaxed);
+        let (status, cas, value) = match header[1] {
+            OP_GET => match store.get(&key) {
+                Some((value, cas)) => (STATUS_OK, *cas, value.clone()),
+                None => (STATUS_NOT_FOUND, 0, Vec::new()),
+            },
+            OP_SET => {
+                store.insert(key, (value, new_cas));
+                (STATUS_OK, new_cas, Vec::new())
+            }
+            OP_ADD if store.contains_key(&key) => (STATUS_EXISTS, 0, Vec::new()),
+            OP_ADD => {
+                store.insert(key, (value, new_cas));
+                (STATUS_OK, new_cas, Vec::new())
+            }
+            OP_APPEND => match store.get_mut(&key) {
+                Some(entry) => {
+                    entry.0.extend_from_slice(&value);
+                    entry.1 = new_cas;
+                    (STATUS_OK, new_cas, Vec::new())
+                }
+                None => (STATUS_NOT_STORED, 0, Vec::new()),
+            },
+            OP_INCREMENT => {
+                let delta = u64::from_be_bytes(body[..8].try_into().unwrap());
+                let initial = u64::from_be_bytes(body[8..16].try_into().unwrap());
+                let next = match store.get(&key) {
+                    Some((value, _)) => String::from_utf8_lossy(value)
+                        .parse::<u64>()
+                        .map(|n| n.wrapping_add(delta)),
+                    None => Ok(initial),
+                };
+                match next {
+                    Ok(next) => {
+                        store.insert(key, (next.to_string().into_bytes(), new_cas));
+                        (STATUS_OK, new_cas, next.to_be_bytes().to_vec())
+                    }
+                    Err(_) => (0x0006, 0, b"Non-numeric value".to_vec()),
+                }
+            }
+            OP_TOUCH if store.contains_key(&key) => (STATUS_OK, 0, Vec::new()),
+            OP_TOUCH => (STATUS_NOT_FOUND, 0, Vec::new()),
+            OP_DELETE => match store.get(&key) {
+                None => (STATUS_NOT_FOUND, 0, Vec::new()),
+                Some((_, current)) if cas != 0 && cas != *current => (STATUS_EXISTS, 0, Vec::new()),
+                Some(_) => {
+                    store.remove(&key);
+                    (STATUS_OK, 0, Vec::new())
+                }
+            },
+            _ => (0x0081, 0, b"Unknown command".to_vec()),
+        };
+
+        let extras: &[u8] = if header[1] == OP_GET && status == STATUS_OK {
+            &[0; 4]
+        } else {
+            &[]
+        };
+        let mut reply = encode_request(header[1], &[], extras, &value, cas);
+        reply[0] = 0x81;
+        reply[6..8].copy_from_slice(&status.to_be_bytes());
+        Ok(reply)
+    }
+
+    #[test]
+    fn request_encoding() {
+        let packet = encode_request(OP_SET, b"k", &store_extras(60), b"v", 0);
+        assert_eq!(packet.len(), 24 + 8 + 1 + 1);
+        assert_eq!(&packet[..8], &[0x80, OP_SET, 0, 1, 8, 0, 0, 0]);
+        assert_eq!(&packet[8..12], &10u32.to_be_bytes());
+        assert_eq!(&packet[28..32], &60u32.to_be_bytes());
+        assert_eq!(&packet[32..], b"kv");
+    }
+
+    #[test]
+    fn expirations() {
+        assert_eq!(expiration(None), 0);
+        assert_eq!(expiration(Some(Duration::from_millis(1))), 1);
+        assert_eq!(expiration(Some(Duration::from_millis(1500))), 2);
+        assert_eq!(expiration(Some(Duration::from_secs(60))), 60);
+        let long = expiration(Some(Duration::from_secs(MAX_RELATIVE_EXPIRY + 1)));
+        assert!(
+            u64::from(long) > MAX_RELATIVE_EXPIRY * 10,
+            "absolute time expected"
+        );
+    }
+
+    #[test]
+    fn long_keys_are_hashed() {
+        let cache = MemcachedCache::new(["127.0.0.1:1"]);
+        assert_eq!(cache.key("cache:", "short"), b"cache:short");
+        let long = cache.key("cache:", &"x".repeat(300));
+        assert_eq!(long.len(), "cache:sha256:".len() + 64);
+    }
+
+    #[test]
+    fn consistent_hashing_moves_few_keys() {
+        let three = MemcachedCache::new(["a:1", "b:1", "c:1"]);
+        let four = MemcachedCache::new(["a:1", "b:1", "c:1", "d:1"]);
+        let keys: Vec<String> = (0..1000).map(|i| format!("key-{i}")).collect();
+
+        let moved = keys
+            .iter()
+            .filter(|k| three.server_for(k) != four.server_for(k))
+            .count();
+        // Ideally a quarter of the keys move, all of them to the new node.
+        assert!((150..350).contains(&moved), "moved {moved}");
+        assert!(
+            keys.iter()
+                .filter(|k| three.server_for(k) != four.server_for(k))
+                .all(|k| four.server_for(k) == "d:1")
+        );
+    }
+
+    #[tokio::test]
+    async fn round_trip_across_nodes() {
+        let (a, b) = (FakeMemcached::start().await, FakeMemcached::start().await);
+        let cache = MemcachedCache::new([a.addr.clone(), b.addr.clone()]);
+
+        for i in 0..20 {
+            cache.set_json(&format!("k{i}"), &i, None).await.unwrap();
+        }
+        for i in 0..20 {
+            assert_eq!(
+                cache.get_json::<i32>(&format!("k{i}")).await.unwrap(),
+                Some(i)
+            );
+        }
+        assert_eq!(a.len() + b.len(), 20);
+        assert!(a.len() > 0 && b.len() > 0);
+
+        assert!(cache.delete("k0").await.unwrap());
+        assert!(!cache.delete("k0").await.unwrap());
+        assert_eq!(cache.get("k0").await.unwrap(), None);
+
+        let stats = cache.stats().unwrap();
+        assert_eq!((stats.hits, stats.misses), (20, 1));
+    }
+
+    #[tokio::test]
+    async fn invalidate_tag() {
+        let (a, b) = (FakeMemcached::start().await, FakeMemcached::start().await);
+        let cache = MemcachedCache::new([a.addr.clone(), b.addr.clone()]);
+        let value: Arc<[u8]> = Arc::from(&b"v"[..]);
+        let tags = |t: &[&str]| t.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        for key in ["GET /a\nh:accept=x", "b", "c"] {
+            cache
+                .set_tagged(key, value.clone(), None, &tags(&["posts"]))
+                .await
+                .unwrap();
+        }
+        cache
+            .set_tagged("d", value.clone(), None, &tags(&["other"]))
+            .await
+            .unwrap();
+
+        assert_eq!(cache.invalidate_tag("posts").await.unwrap(), 3);
+        assert_eq!(cache.get("GET /a\nh:accept=x").await.unwrap(), None);
+        assert_eq!(cache.get("b").await.unwrap(), None);
+        assert!(cache.get("d").await.unwrap().is_some());
+        assert_eq!(cache.invalidate_tag("posts").await.unwrap(), 0);
+        // Only "d" and its tag list remain.
+        assert_eq!(a.len() + b.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn increments_counters() {
+        let server = FakeMemcached::start().await;
+        let cache = MemcachedCache::new([server.addr.clone()]);
+
+        assert_eq!(cache.increment("n", 2, None).await.unwrap(), 2);
+        let ttl = Some(Duration::from_secs(60));
+        assert_eq!(cache.increment("n", 3, ttl).await.unwrap(), 5);
+        assert_eq!(cache.get("n").await.unwrap().as_deref(), Some(&b"5"[..]));
+
+        cache.set_json("json", &"text", None).await.unwrap();
+        assert!(cache.increment("json", 1, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn reports_unreachable_server() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        let cache = MemcachedCache::new([addr]);
+        let err = cache.get("k").await.unwrap_err();
+        assert!(err.to_string().contains("memcached"), "{err}");
+    }
+}
@@ -0,0 +1,675 @@
+//! In-process [`Cache`] backend with TTL expiry and LRU eviction.
+
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use super::{Cache, CacheError, CacheFuture, CacheStats, stats::StatsRecorder};
+use crate::clock::{self, Clock};
+
+/// A bounded, in-process cache.
+///
+/// - **TTL** — every entry may carry its own expiry; entries without one fall back to
+///   [`default_ttl`](Self::default_ttl), or never expire if none is set. Expired entries
+///   are dropped lazily on access or in bulk via [`purge_expired`](Self::purge_expired).
+/// - **LRU** — once `capacity` entries are stored, inserting a new key evicts the least
+///   recently read or written one.
+/// - **Typed values** — besides the byte values of the [`Cache`] trait, arbitrary
+///   `Send + Sync` values can be stored with [`insert_typed`](Self::insert_typed) and
+///   read back as `Arc<T>` without serialization. Typed and byte entries share one key
+///   space and one capacity; each API only sees its own kind of value.
+/// - **Tags** — entries stored with [`set_tagged`](Cache::set_tagged) are indexed by
+///   tag, so [`invalidate_tag`](Cache::invalidate_tag) removes them without a scan.
+/// - **Single flight** — concurrent [`get_or_insert_with`](Cache::get_or_insert_with)
+///   calls that miss on the same key run `init` once; the other callers wait for it and
+///   receive the stored result.
+/// - **Statistics** — hits, misses, LRU evictions, and operation latency are counted
+///   and reported by [`stats`](Cache::stats).
+///
+/// The cache is safe to share across Tokio tasks behind an `Arc`. Operations take a
+/// short, non-async lock and never hold it across an `.await`.
+pub struct MemoryCache {
+    inner: Mutex<Inner>,
+    capacity: usize,
+    default_ttl: Option<Duration>,
+    // Per-key gates for in-flight `get_or_insert_with` calls.
+    inflight: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    stats: StatsRecorder,
+    clock: Arc<dyn Clock>,
+}
+
+#[derive(Clone)]
+enum Value {
+    Bytes(Arc<[u8]>),
+    Typed(Arc<dyn Any + Send + Sync>),
+}
+
+struct Entry {
+    value: Value,
+    expires: Option<Instant>,
+    tags: Vec<String>,
+    // Position in `Inner::recency`.
+    tick: u64,
+}
+
+impl Entry {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires.is_some_and(|at| at <= now)
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<String, Entry>,
+    // Access order: the smallest tick is the least recently used key.
+    recency: BTreeMap<u64, String>,
+    next_tick: u64,
+    // Tag → keys of the live entries carrying it.
+    tags: HashMap<String, HashSet<String>>,
+}
+
+impl Inner {
+    // Returns the live value for `key`, marking it most recently used.
+    fn get(&mut self, key: &str, now: Instant) -> Option<Value> {
+        let entry = self.entries.get_mut(key)?;
+        if entry.is_expired(now) {
+            self.remove(key);
+            return None;
+        }
+        self.recency.remove(&entry.tick);
+        entry.tick = self.next_tick;
+        self.next_tick += 1;
+        self.recency.insert(entry.tick, key.to_owned());
+        Some(entry.value.clone())
+    }
+
+    // Stores `entry`, returning how many entries were evicted to make room.
+    fn insert(&mut self, key: &str, entry: Entry, capacity: usize) -> usize {
+        self.remove(key);
+        let mut evicted = 0;
+        while self.entries.len() >= capacity {
+            let Some((_, oldest)) = self.recency.first_key_value() else {
+                break;
+            };
+            let oldest = oldest.clone();
+            self.remove(&oldest);
+            evicted += 1;
+        }
+        for tag in &entry.tags {
+            self.tags
+                .entry(tag.clone())
+                .or_default()
+                .insert(key.to_owned());
+        }
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        self.recency.insert(tick, key.to_owned());
+        self.entries.insert(key.to_owned(), Entry { tick, ..entry });
+        evicted
+    }
+
+    fn remove(&mut self, key: &str) -> bool {
+        let Some(entry) = self.entries.remove(key) else {
+            return false;
+        };
+        self.recency.remove(&entry.tick);
+        for tag in &entry.tags {
+            if let Some(keys) = self.tags.get_mut(tag) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.tags.remove(tag);
+                }
+            }
+        }
+        true
+    }
+
+    fn invalidate_tag(&mut self, tag: &str) -> usize {
+        let keys = self.tags.remove(tag).unwrap_or_default();
+        keys.iter().filter(|key| self.remove(key)).count()
+    }
+
+    // Adds `delta` to the live counter at `key` in place, returning the new value, or
+    // `Ok(None)` if there is no live entry.
+    fn increment(
+        &mut self,
+        key: &str,
+        delta: u64,
+        expires: Option<Instant>,
+        now: Instant,
+    ) -> Result<Option<u64>, CacheError> {
+        let current = match self.get(key, now) {
+            Some(Value::Bytes(current)) => current,
+            Some(Value::Typed(_)) => return Err(not_a_counter(key)),
+            None => return Ok(None),
+        };
+        let value = std::str::from_utf8(&current)
+            .ok()
+            .and_then(|text| text.parse::<u64>().ok())
+            .ok_or_else(|| not_a_counter(key))?
+            .wrapping_add(delta);
+        let entry = self.entries.get_mut(key).expect("entry was just read");
+        entry.value = Value::Bytes(Arc::from(value.to_string().as_bytes()));
+        if expires.is_some() {
+            entry.expires = expires;
+        }
+        Ok(Some(value))
+    }
+}
+
+fn not_a_counter(key: &str) -> CacheError {
+    CacheError::Backend(format!("value at {key:?} is not a counter"))
+}
+
+impl MemoryCache {
+    /// Creates a cache holding at most `capacity` entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "MemoryCache capacity must be non-zero");
+        Self {
+            inner: Mutex::new(Inner::default()),
+            capacity,
+            default_ttl: None,
+            inflight: Mutex::new(HashMap::new()),
+            stats: StatsRecorder::default(),
+            clock: clock::system(),
+        }
+    }
+
+    /// Sets the expiry for entries stored without an explicit TTL.
+    #[must_use]
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Reads expiry times from `clock` instead of the system clock.
+    #[must_use]
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the maximum number of entries.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of stored entries, including expired ones not yet purged.
+    pub fn len(&self) -> usize {
+        self.inner().entries.len()
+    }
+
+    /// Returns `true` if the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes every entry.
+    pub fn clear(&self) {
+        *self.inner() = Inner::default();
+    }
+
+    /// Removes all expired entries, returning how many were dropped.
+    pub fn purge_expired(&self) -> usize {
+        let now = self.clock.now();
+        let mut inner = self.inner();
+        let expired: Vec<String> = inner
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.is_expired(now))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &expired {
+            inner.remove(key);
+        }
+        expired.len()
+    }
+
+    /// Returns the typed value stored under `key` by [`insert_typed`](Self::insert_typed).
+    ///
+    /// Returns `None` if the key is absent, expired, a byte value, or holds a different
+    /// type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::cache::MemoryCache;
+    ///
+    /// let cache = MemoryCache::new(100);
+    /// cache.insert_typed("primes", vec![2, 3, 5], None);
+    /// assert_eq!(cache.get_typed::<Vec<i32>>("primes").as_deref(), Some(&vec![2, 3, 5]));
+    /// assert!(cache.get_typed::<String>("primes").is_none());
+    /// ```
+    pub fn get_typed<T: Any + Send + Sync>(&self, key: &str) -> Option<Arc<T>> {
+        let value = match self.inner().get(key, self.clock.now()) {
+            Some(Value::Typed(value)) => value.downcast().ok(),
+            _ => None,
+        };
+        self.stats.lookup(value.is_some());
+        value
+    }
+
+    /// Stores `value` under `key` without serializing it.
+    pub fn insert_typed<T: Any + Send + Sync>(&self, key: &str, value: T, ttl: Option<Duration>) {
+        self.insert(key, Value::Typed(Arc::new(value)), ttl, Vec::new());
+    }
+
+    fn get_bytes(&self, key: &str) -> Option<Arc<[u8]>> {
+        match self.inner().get(key, self.clock.now())? {
+            Value::Bytes(bytes) => Some(bytes),
+            Value::Typed(_) => None,
+        }
+    }
+
+    fn insert(&self, key: &str, value: Value, ttl: Option<Duration>, tags: Vec<String>) {
+        let entry = Entry {
+            value,
+            expires: ttl.or(self.default_ttl).map(|ttl| self.clock.now() + ttl),
+            tags,
+            tick: 0,
+        };
+        let evicted = self.inner().insert(key, entry, self.capacity);
+        self.stats.evicted(evicted);
+    }
+
+    fn inner(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn inflight(&self) -> MutexGuard<'_, HashMap<String, Arc<tokio::sync::Mutex<()>>>> {
+        self.inflight.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Cache for MemoryCache {
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Option<Arc<[u8]>>> {
+        let start = Instant::now();
+        let value = self.get_bytes(key);
+        self.stats.lookup(value.is_some());
+        self.stats.operation(start.elapsed());
+        Box::pin(async move { Ok(value) })
+    }
+
+    fn set<'a>(
+        &'a self,
+        key: &'a str,
+        value: Arc<[u8]>,
+        ttl: Option<Duration>,
+    ) -> CacheFuture<'a, ()> {
+        let start = Instant::now();
+        self.insert(key, Value::Bytes(value), ttl, Vec::new());
+        self.stats.operation(start.elapsed());
+        Box::pin(async { Ok(()) })
+    }
+
+    fn set_tagged<'a>(
+        &'a self,
+        key: &'a str,
+        value: Arc<[u8]>,
+        ttl: Option<Duration>,
+        tags: &'a [String],
+    ) -> CacheFuture<'a, ()> {
+        let start = Instant::now();
+        self.insert(key, Value::Bytes(value), ttl, tags.to_vec());
+        self.stats.operation(start.elapsed());
+        Box::pin(async { Ok(()) })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> CacheFuture<'a, bool> {
+        let start = Instant::now();
+        let removed = self.inner().remove(key);
+        self.stats.operation(start.elapsed());
+        Box::pin(async move { Ok(removed) })
+    }
+
+    fn invalidate_tag<'a>(&'a self, tag: &'a str) -> CacheFuture<'a, usize> {
+        let removed = self.inner().invalidate_tag(tag);
+        Box::pin(async move { Ok(removed) })
+    }
+
+    fn increment<'a>(
+        &'a self,
+        key: &'a str,
+        delta: u64,
+        ttl: Option<Duration>,
+    ) -> CacheFuture<'a, u64> {
+        let start = Instant::now();
+        let now = self.clock.now();
+        let mut inner = self.inner();
+        let result = match inner.increment(key, delta, ttl.map(|ttl| now + ttl), now) {
+            Ok(Some(value)) => Ok(value),
+            Ok(None) => {
+                let entry = Entry {
+                    value: Value::Bytes(Arc::from(delta.to_string().as_bytes())),
+                    expires: ttl.or(self.default_ttl).map(|ttl| now + ttl),
+                    tags: Vec::new(),
+                    tick: 0,
+                };
+                let evicted = inner.insert(key, entry, self.capacity);
+                self.stats.evicted(evicted);
+                Ok(delta)
+            }
+            Err(e) => Err(e),
+        };
+        drop(inner);
+        self.stats.operation(start.elapsed());
+        Box::pin(async move { result })
+    }
+
+    fn get_or_insert_with<'a>(
+        &'a self,
+        key: &'a str,
+        ttl: Option<Duration>,
+        init: CacheFuture<'a, Arc<[u8]>>,
+    ) -> CacheFuture<'a, Arc<[u8]>> {
+        Box::pin(async move {
+            if let Some(value) = self.get_bytes(key) {
+                self.stats.lookup(true);
+                return Ok(value);
+            }
+
+            let gate = Arc::clone(self.inflight().entry(key.to_owned()).or_default());
+            let guard = gate.lock().await;
+            // Another caller may have filled the entry while we waited.
+            let cached = self.get_bytes(key);
+            self.stats.lookup(cached.is_some());
+            let result = match cached {
+                Some(value) => Ok(value),
+                None => init.await.inspect(|value| {
+                    self.insert(key, Value::Bytes(Arc::clone(value)), ttl, Vec::new());
+                }),
+            };
+            drop(guard);
+
+            // The map and this task hold the only references: nobody else is waiting.
+            let mut inflight = self.inflight();
+            if Arc::strong_count(&gate) == 2 {
+                inflight.remove(key);
+            }
+            result
+        })
+    }
+
+    fn stats(&self) -> Option<CacheStats> {
+        Some(self.stats.snapshot())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::cache::{CacheError, CacheExt};
+
+    fn bytes(s: &str) -> Arc<[u8]> {
+        Arc::from(s.as_bytes())
+    }
+
+    // ── Byte API ──────────────────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn set_get_delete() {
+        let cache = MemoryCache::new(10);
+        assert_eq!(cache.get("a").await.unwrap(), None);
+
+        cache.set("a", bytes("1"), None).await.unwrap();
+        assert_eq!(cache.get("a").await.unwrap(), Some(bytes("1")));
+        cache.set("a", bytes("2"), None).await.unwrap();
+        assert_eq!(cache.get("a").await.unwrap(), Some(bytes("2")));
+        assert_eq!(cache.len(), 1);
+
+        assert!(cache.delete("a").await.unwrap());
+        assert!(!cache.delete("a").await.unwrap());
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn entries_expire() {
+        let cache = MemoryCache::new(10);
+        cache
+            .set("gone", bytes("x"), Some(Duration::ZERO))
+            .await
+            .unwrap();
+        cache
+            .set("kept", bytes("y"), Some(Duration::from_secs(60)))
+            .await
+            .unwrap();
+        assert_eq!(cache.get("gone").await.unwrap(), None);
+        assert_eq!(cache.get("kept").await.unwrap(), Some(bytes("y")));
+
+        let cache = MemoryCache::new(10).default_ttl(Duration::ZERO);
+        cache.set("a", bytes("x"), None).await.unwrap();
+        cache.set("b", bytes("x"), None).await.unwrap();
+        assert_eq!(cache.purge_expired(), 2);
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn expiry_follows_the_clock() {
+        let clock = crate::clock::MockClock::new();
+        let cache = MemoryCache::new(10).clock(Arc::new(clock.clone()));
+        cache
+            .set("k", bytes("v"), Some(Duration::from_secs(60)))
+            .await
+            .unwrap();
+
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(cache.get("k").await.unwrap(), Some(bytes("v")));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.get("k").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn increments_counters() {
+        let clock = crate::clock::MockClock::new();
+        let cache = MemoryCache::new(10).clock(Arc::new(clock.clone()));
+        let minute = Some(Duration::from_secs(60));
+
+        assert_eq!(cache.increment("n", 2, minute).await.unwrap(), 2);
+        assert_eq!(cache.increment("n", 3, None).await.unwrap(), 5);
+        assert_eq!(cache.get("n").await.unwrap(), Some(bytes("5")));
+
+        // Without a TTL the counter keeps its expiry; with one it is pushed back.
+        clock.advance(Duration::from_secs(30));
+        cache.increment("n", 1, None).await.unwrap();
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(cache.get("n").await.unwrap(), None);
+        cache.increment("m", 1, minute).await.unwrap();
+        clock.advance(Duration::from_secs(30));
+        cache.increment("m", 1, minute).await.unwrap();
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(cache.get("m").await.unwrap(), Some(bytes("2")));
+
+        cache.set("text", bytes("x"), None).await.unwrap();
+        assert!(cache.increment("text", 1, None).await.is_err());
+        cache.insert_typed("typed", 1u64, None);
+        assert!(cache.increment("typed", 1, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn concurrent_increments_are_not_lost() {
+        let cache = Arc::new(MemoryCache::new(10));
+        let tasks: Vec<_> = (0..50)
+            .map(|_| {
+                let cache = Arc::clone(&cache);
+                tokio::spawn(async move { cache.increment("n", 1, None).await.unwrap() })
+            })
+            .collect();
+        let mut seen = Vec::new();
+        for task in tasks {
+            seen.push(task.await.unwrap());
+        }
+        seen.sort_unstable();
+        assert_eq!(seen, (1..=50).collect::<Vec<u64>>());
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used() {
+        let cache = MemoryCache::new(2);
+        cache.set("a", bytes("1"), None).await.unwrap();
+        cache.set("b", bytes("2"), None).await.unwrap();
+        // Reading `a` makes `b` the eviction candidate.
+        cache.get("a").await.unwrap();
+        cache.set("c", bytes("3"), None).await.unwrap();
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("b").await.unwrap(), None);
+        assert!(cache.get("a").await.unwrap().is_some());
+        assert!(cache.get("c").await.unwrap().is_some());
+    }
+
+    // ── Tags ──────────────────────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn invalidate_tag_removes_tagged_entries() {
+        let cache = MemoryCache::new(10);
+        let tags = |t: &[&str]| t.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        cache
+            .set_tagged("u1", bytes("1"), None, &tags(&["user:1", "users"]))
+            .await
+            .unwrap();
+        cache
+            .set_tagged("u2", bytes("2"), None, &tags(&["user:2", "users"]))
+            .await
+            .unwrap();
+        cache.set("other", bytes("x"), None).await.unwrap();
+
+        assert_eq!(cache.invalidate_tag("user:1").await.unwrap(), 1);
+        assert_eq!(cache.get("u1").await.unwrap(), None);
+        assert!(cache.get("u2").await.unwrap().is_some());
+
+        assert_eq!(cache.invalidate_tag("users").await.unwrap(), 1);
+        assert_eq!(cache.invalidate_tag("users").await.unwrap(), 0);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.inner().tags.is_empty());
+    }
+
+    #[tokio::test]
+    async fn overwrite_and_eviction_drop_stale_tags() {
+        let cache = MemoryCache::new(1);
+        let tag = vec!["t".to_owned()];
+        cache.set_tagged("a", bytes("1"), None, &tag).await.unwrap();
+        // Re-set without tags: the old association must not survive.
+        cache.set("a", bytes("2"), None).await.unwrap();
+        assert_eq!(cache.invalidate_tag("t").await.unwrap(), 0);
+
+        cache.set_tagged("a", bytes("3"), None, &tag).await.unwrap();
+        cache.set("b", bytes("4"), None).await.unwrap(); // evicts `a`
+        assert!(cache.inner().tags.is_empty());
+    }
+
+    // ── Typed values ──────────────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn typed_and_byte_values_do_not_mix() {
+        let cache = MemoryCache::new(10);
+        cache.insert_typed("n", 42u64, None);
+        assert_eq!(cache.get_typed::<u64>("n").as_deref(), Some(&42));
+        assert_eq!(cache.get("n").await.unwrap(), None);
+
+        cache.set("b", bytes("raw"), None).await.unwrap();
+        assert!(cache.get_typed::<u64>("b").is_none());
+    }
+
+    #[tokio::test]
+    async fn json_round_trip() {
+        let cache: Arc<dyn Cache> = Arc::new(MemoryCache::new(10));
+        cache.set_json("v", &vec![1, 2, 3], None).await.unwrap();
+        assert_eq!(
+            cache.get_json::<Vec<u8>>("v").await.unwrap(),
+            Some(vec![1, 2, 3])
+        );
+        assert!(matches!(
+            cache.get_json::<String>("v").await,
+            Err(CacheError::Serialization(_))
+        ));
+    }
+
+    // ── get_or_insert_with ────────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn get_or_insert_runs_init_once_under_contention() {
+        let cache = Arc::new(MemoryCache::new(10));
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let cache = Arc::clone(&cache);
+                let calls = Arc::clone(&calls);
+                tokio::spawn(async move {
+                    cache
+                        .get_or_insert_json_with("k", None, || async move {
+                            calls.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(20)).await;
+                            Ok("computed".to_owned())
+                        })
+                        .await
+                        .unwrap()
+                })
+            })
+            .collect();
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), "computed");
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(cache.inflight().is_empty());
+        let stats = cache.stats().unwrap();
+        assert_eq!((stats.hits, stats.misses), (7, 1));
+    }
+
+    #[tokio::test]
+    async fn get_or_insert_does_not_cache_errors() {
+        let cache = MemoryCache::new(10);
+        let result = cache
+            .get_or_insert_with(
+                "k",
+                None,
+                Box::pin(async { Err(CacheError::Backend("db down".into())) }),
+            )
+            .await;
+        assert!(result.is_err());
+        assert!(cache.is_empty());
+
+        let value = cache
+            .get_or_insert_with("k", None, Box::pin(async { Ok(bytes("ok")) }))
+            .await
+            .unwrap();
+        assert_eq!(value, bytes("ok"));
+        assert_eq!(cache.get("k").await.unwrap(), Some(bytes("ok")));
+    }
+
+    // ── Statistics ────────────────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn stats_count_hits_misses_and_evictions() {
+        let cache = MemoryCache::new(2);
+        cache.set("a", bytes("1"), None).await.unwrap();
+        cache.set("b", bytes("2"), None).await.unwrap();
+        cache.get("a").await.unwrap();
+        cache.get("missing").await.unwrap();
+        cache.set("c", bytes("3"), None).await.unwrap(); // evicts "b"
+        cache.set("c", bytes("4"), None).await.unwrap(); // overwrite, no eviction
+        cache.insert_typed("n", 1_u8, None); // evicts "a"
+        assert!(cache.get_typed::<u8>("n").is_some());
+
+        let stats = cache.stats().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (2, 1, 2));
+        // Typed access is not a timed trait call.
+        assert_eq!(stats.operations, 6);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity must be non-zero")]
+    fn zero_capacity_panics() {
+        let _ = MemoryCache::new(0);
+    }
+}
@@ -0,0 +1,732 @@
+//! HTTP response caching middleware.
+//!
+//! [`CacheMiddleware`] stores successful `GET`/`HEAD` responses in any [`Cache`] backend
+//! and answers repeat requests from it without running the rest of the chain.
+//!
+//! ## What gets cached
+//!
+//! A response is stored only when all of these hold:
+//!
+//! - the request method is `GET` or `HEAD`, and the request did not send
+//!   `Cache-Control: no-store`;
+//! - the status is `2xx` (other than `206 Partial Content`);
+//! - the response has no `Set-Cookie` header and no `Vary: *`;
+//! - the response `Cache-Control` contains neither `no-store` nor `no-cache` nor (unless
+//!   keys are per principal) `private`, and its lifetime is non-zero;
+//! - if the request carried `Authorization` and keys are not per principal, the response
+//!   is explicitly `public` or has `s-maxage`.
+//!
+//! The lifetime is `s-maxage`, else `max-age`, else the middleware's default TTL.
+//!
+//! ## Keys and `Vary`
+//!
+//! By default the key is the method, path, and sorted query string; use
+//! [`CacheMiddleware::key`] with a [`CacheKeyBuilder`] to choose other attributes. When a
+//! response carries `Vary`, the varying header names are remembered under that key and
+//! each combination of their request values is stored as a separate variant.
+//!
+//! With a per-principal key ([`CacheKeyBuilder::principal`]) every user gets their own
+//! entries, so responses to authorized requests and `private` responses are cacheable
+//! too.
+//!
+//! Hits carry `Age` and `X-Cache: HIT`; responses produced by the handler carry
+//! `X-Cache: MISS`. A request with `Cache-Control: no-cache` skips the lookup but may
+//! refresh the stored entry.
+//!
+//! ## Tags
+//!
+//! Handlers label the response being produced with [`CacheTagsExt::cache_tags`]; the
+//! stored entry is then removed by [`Cache::invalidate_tag`] for any of those tags, so a
+//! write can purge every affected page without knowing their keys.
+//!
+//! With [`CacheMiddleware::tag_all`], every stored entry is also tagged with the
+//! middleware's prefix followed by `*` (`http:*` by default), so all of its responses can
+//! be purged at once — see [`CacheAdmin`](super::CacheAdmin). It is off by default: the
+//! catch-all tag lists every stored response, which a backend such as
+//! [`RedisCache`](super::RedisCache) keeps for as long as the longest-lived of them.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::{sync::Arc, time::Duration};
+//! use rttp::cache::{CacheKeyBuilder, CacheMiddleware, MemoryCache};
+//! use rttp::middleware::from_middleware;
+//!
+//! let cache = Arc::new(MemoryCache::new(10_000));
+//! let caching = CacheMiddleware::new(cache)
+//!     .default_ttl(Duration::from_secs(30))
+//!     .key(CacheKeyBuilder::new().ignore_query_params(["utm_source"]).header("Accept"));
+//! let middleware = from_middleware(Arc::new(caching));
+//! ```
+
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tracing::warn;
+
+use super::{Cache, CacheKeyBuilder};
+use crate::{
+    Headers, Method, Response, StatusCode,
+    context::Context,
+    middleware::{Middleware, Next},
+};
+
+/// Caches successful `GET`/`HEAD` responses. See the [module docs](self) for the rules.
+#[derive(Clone)]
+pub struct CacheMiddleware {
+    cache: Arc<dyn Cache>,
+    default_ttl: Duration,
+    key: CacheKeyBuilder,
+    prefix: String,
+    tag_all: bool,
+}
+
+impl CacheMiddleware {
+    /// Creates a middleware storing responses in `cache`, with a 60-second default TTL.
+    pub fn new(cache: Arc<dyn Cache>) -> Self {
+        Self {
+            cache,
+            default_ttl: Duration::from_secs(60),
+            key: CacheKeyBuilder::new(),
+            prefix: "http:".to_owned(),
+            tag_all: false,
+        }
+    }
+
+    /// Sets the lifetime of responses whose `Cache-Control` gives none.
+    #[must_use]
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = ttl;
+        self
+    }
+
+    /// Sets how cache keys are derived from requests.
+    #[must_use]
+    pub fn key(mut self, key: CacheKeyBuilder) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Adds a request header whose value is part of every cache key, whether or not
+    /// responses list it in `Vary`. Shorthand for [`CacheKeyBuilder::header`].
+    #[must_use]
+    pub fn key_header(mut self, name: impl Into<String>) -> Self {
+        self.key = self.key.header(name);
+        self
+    }
+
+    /// Sets the prefix namespacing this middleware's keys in a shared cache
+    /// (default `http:`).
+    #[must_use]
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Tags every stored response with `<prefix>*`, so
+    /// [`CacheAdmin`](super::CacheAdmin)'s purge-all route can remove them. Off by
+    /// default.
+    #[must_use]
+    pub fn tag_all(mut self, enabled: bool) -> Self {
+        self.tag_all = enabled;
+        self
+    }
+
+    fn primary_key(&self, ctx: &Context) -> String {
+        format!("{}{}", self.prefix, self.key.build(ctx))
+    }
+
+    // Returns the stored response for a request, following a `Vary` indirection.
+    async fn lookup(&self, key: &str, request: &Headers) -> Option<Response> {
+        let entry = self.cache_get(key).await?;
+        match decode(&entry)? {
+            Stored::Response(response) => Some(response),
+            Stored::Vary(names) => {
+                let entry = self.cache_get(&variant_key(key, request, &names)).await?;
+                match decode(&entry)? {
+                    Stored::Response(response) => Some(response),
+                    Stored::Vary(_) => None,
+                }
+            }
+        }
+    }
+
+    async fn store(
+        &self,
+        key: &str,
+        request: &Headers,
+        response: &Response,
+        ttl: Duration,
+        tags: &[String],
+    ) {
+        let vary: Vec<String> = response
+            .headers()
+            .get_all("vary")
+            .flat_map(|v| v.split(','))
+            .map(|name| name.trim().to_ascii_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect();
+        let result = if vary.is_empty() {
+            self.cache
+                .set_tagged(key, encode_response(response), Some(ttl), tags)
+                .await
+        } else {
+            let variant = variant_key(key, request, &vary);
+            let vary = encode_vary(&vary);
+            match self.cache.set_tagged(key, vary, Some(ttl), tags).await {
+                Ok(()) => {
+                    self.cache
+                        .set_tagged(&variant, encode_response(response), Some(ttl), tags)
+                        .await
+                }
+                Err(e) => Err(e),
+            }
+        };
+        if let Err(e) = result {
+            warn!(error = %e, key, "failed to store cached response");
+        }
+    }
+
+    // Cache errors degrade to a miss rather than failing the request.
+    async fn cache_get(&self, key: &str) -> Option<Arc<[u8]>> {
+        self.cache
+            .get(key)
+            .await
+            .inspect_err(|e| warn!(error = %e, key, "cache lookup failed"))
+            .ok()
+            .flatten()
+    }
+}
+
+impl Middleware for CacheMiddleware {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let request = ctx.request();
+        let request_cc = CacheControl::parse(request.headers().get_all("cache-control"));
+        if !matches!(request.method(), Method::Get | Method::Head) || request_cc.no_store {
+            return next.run(ctx);
+        }
+
+        let this = self.clone();
+        Box::pin(async move {
+            let key = this.primary_key(&ctx);
+            // Only the headers are needed after the request moves down the chain.
+            let request = ctx.request().headers().clone();
+            if !request_cc.no_cache {
+                if let Some(hit) = this.lookup(&key, &request).await {
+                    return hit.header("X-Cache", "HIT");
+                }
+            }
+
+            let tags = CacheTags::default();
+            ctx.extensions_mut().insert(tags.clone());
+            let response = next.run(ctx).await;
+            if let Some(ttl) = this.cacheable_ttl(&request, &response) {
+                let mut tags = tags.0.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                if this.tag_all {
+                    tags.push(all_tag(&this.prefix));
+                }
+                this.store(&key, &request, &response, ttl, &tags).await;
+            }
+            response.header("X-Cache", "MISS")
+        })
+    }
+}
+
+// The tag carried by every response stored under `prefix`.
+pub(super) fn all_tag(prefix: &str) -> String {
+    format!("{prefix}*")
+}
+
+/// Tags collected for the response being produced, shared between the middleware and
+/// the handler through the request extensions.
+#[derive(Clone, Default)]
+struct CacheTags(Arc<Mutex<Vec<String>>>);
+
+/// Tags the cached response from inside a handler.
+pub trait CacheTagsExt {
+    /// Associates the response being produced with `tags`, so
+    /// [`Cache::invalidate_tag`] for any of them removes it. Calls accumulate.
+    ///
+    /// Has no effect outside a [`CacheMiddleware`], or when the response is not stored.
+    fn cache_tags<I, S>(&self, tags: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>;
+}
+
+impl CacheTagsExt for Context {
+    fn cache_tags<I, S>(&self, tags: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        if let Some(handle) = self.extensions().get::<CacheTags>() {
+            let mut collected = handle.0.lock().unwrap_or_else(|e| e.into_inner());
+            for tag in tags {
+                let tag = tag.into();
+                if !collected.contains(&tag) {
+                    collected.push(tag);
+                }
+            }
+        }
+    }
+}
+
+impl CacheMiddleware {
+    // The lifetime to store `response` for, or `None` if it must not be stored.
+    fn cacheable_ttl(&self, request: &Headers, response: &Response) -> Option<Duration> {
+        let status = response.status();
+        if !status.is_success() || status == StatusCode::PartialContent {
+            return None;
+        }
+        // A streamed body is written to the connection later and never held here.
+        if response.is_streamed() {
+            return None;
+        }
+        let headers = response.headers();
+        if headers.contains("set-cookie") || headers.get_all("vary").any(|v| v.trim() == "*") {
+            return None;
+        }
+
+        let cc = CacheControl::parse(headers.get_all("cache-control"));
+        if cc.no_store || cc.no_cache {
+            return None;
+        }
+        // Shared entries must not hold one user's private or authorized responses.
+        let shared = !self.key.is_per_principal();
+        let authorized = request.contains("authorization");
+        let explicitly_shareable = cc.public || cc.s_maxage.is_some();
+        if shared && (cc.private || authorized && !explicitly_shareable) {
+            return None;
+        }
+        let ttl = cc.s_maxage.or(cc.max_age).unwrap_or(self.default_ttl);
+        (!ttl.is_zero()).then_some(ttl)
+    }
+}
+
+/// The `Cache-Control` directives this middleware acts on.
+#[derive(Debug, Default)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    private: bool,
+    public: bool,
+    max_age: Option<Duration>,
+    s_maxage: Option<Duration>,
+}
+
+impl CacheControl {
+    fn parse<'a>(values: impl Iterator<Item = &'a str>) -> Self {
+        let mut cc = Self::default();
+        for directive in values.flat_map(|v| v.split(',')) {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            let seconds = || value.and_then(|v| v.parse().ok()).map(Duration::from_secs);
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => cc.no_store = true,
+                "no-cache" => cc.no_cache = true,
+                "private" => cc.private = true,
+                "public" => cc.public = true,
+                "max-age" => cc.max_age = seconds(),
+                "s-maxage" => cc.s_maxage = seconds(),
+                _ => {}
+            }
+        }
+        cc
+    }
+}
+
+fn variant_key(primary: &str, request: &Headers, vary: &[String]) -> String {
+    let mut key = format!("{primary}\nvary");
+    for name in vary {
+        let values: Vec<&str> = request.get_all(name).map(str::trim).collect();
+        key.push_str(&format!("\n{name}:{}", values.join(",")));
+    }
+    key
+}
+
+// ── Entry encoding ────────────────────────────────────────────────────────────
+//
+// Response:  0x01 | stored_at: u64 | status: u16 | count: u16 | (name_len: u16, name,
+//            value_len: u32, value)* | body
+// Vary list: 0x02 | header names joined by '\n'
+//
+// All integers are big-endian.
+
+enum Stored {
+    Response(Response),
+    Vary(Vec<String>),
+}
+
+const TAG_RESPONSE: u8 = 1;
+const TAG_VARY: u8 = 2;
+
+fn encode_response(response: &Response) -> Arc<[u8]> {
+    let mut buf = vec![TAG_RESPONSE];
+    buf.extend_from_slice(&unix_now().to_be_bytes());
+    buf.extend_from_slice(&response.status().as_u16().to_be_bytes());
+    let headers: Vec<_> = response
+        .headers()
+        .iter()
+        .filter(|(name, _)| !name.eq_ignore_ascii_case("x-cache"))
+        .collect();
+    buf.extend_from_slice(&(headers.len() as u16).to_be_bytes());
+    for (name, value) in headers {
+        buf.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        buf.extend_from_slice(name.as_bytes());
+        buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        buf.extend_from_slice(value.as_bytes());
+    }
+    buf.extend_from_slice(response.body_ref());
+    buf.into()
+}
+
+fn encode_vary(names: &[String]) -> Arc<[u8]> {
+    let mut buf = vec![TAG_VARY];
+    buf.extend_from_slice(names.join("\n").as_bytes());
+    buf.into()
+}
+
+fn decode(entry: &[u8]) -> Option<Stored> {
+    let (&tag, mut rest) = entry.split_first()?;
+    match tag {
+        TAG_VARY => {
+            let names = std::str::from_utf8(rest).ok()?;
+            Some(Stored::Vary(names.split('\n').map(str::to_owned).collect()))
+        }
+        TAG_RESPONSE => {
+            let stored_at = u64::from_be_bytes(take(&mut rest, 8)?.try_into().ok()?);
+            let status = u16::from_be_bytes(take(&mut rest, 2)?.try_into().ok()?);
+            let mut response = Response::new(StatusCode::from_u16(status)?);
+            let count = u16::from_be_bytes(take(&mut rest, 2)?.try_into().ok()?);
+            for _ in 0..count {
+                let len = u16::from_be_bytes(take(&mut rest, 2)?.try_into().ok()?);
+                let name = std::str::from_utf8(take(&mut rest, len.into())?).ok()?;
+                let len = u32::from_be_bytes(take(&mut rest, 4)?.try_into().ok()?);
+                let value = std::str::from_utf8(take(&mut rest, len as usize)?).ok()?;
+                response.add_header(name, value);
+            }
+            let age = unix_now().saturating_sub(stored_at);
+            Some(Stored::Response(
+                response.header("Age", age.to_string()).body_bytes(rest),
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn take<'a>(buf: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+    if buf.len() < n {
+        return None;
+    }
+    let (head, tail) = buf.split_at(n);
+    *buf = tail;
+    Some(head)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{Request, cache::MemoryCache, middleware::MiddlewareHandler};
+
+    // Terminal handler that counts invocations and returns a response built by `f`.
+    fn counting(
+        calls: &Arc<AtomicUsize>,
+        f: impl Fn(&Context, usize) -> Response + Send + Sync + 'static,
+    ) -> MiddlewareHandler {
+        let calls = Arc::clone(calls);
+        let f = Arc::new(f);
+        Arc::new(move |ctx: Context, _next: Next| {
+            let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            let response = f(&ctx, n);
+            Box::pin(async move { response })
+        })
+    }
+
+    fn request(method: &str, path: &str, headers: &[(&str, &str)]) -> Context {
+        let headers: String = headers
+            .iter()
+            .map(|(n, v)| format!("{n}: {v}\r\n"))
+            .collect();
+        let raw = format!("{method} {path} HTTP/1.1\r\nHost: localhost\r\n{headers}\r\n");
+        Context::new(Request::parse(raw.as_bytes()).unwrap().0)
+    }
+
+    struct Harness {
+        middleware: CacheMiddleware,
+        handler: MiddlewareHandler,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl Harness {
+        fn new(f: impl Fn(&Context, usize) -> Response + Send + Sync + 'static) -> Self {
+            let calls = Arc::new(AtomicUsize::new(0));
+            Self {
+                middleware: CacheMiddleware::new(Arc::new(MemoryCache::new(100))),
+                handler: counting(&calls, f),
+                calls,
+            }
+        }
+
+        async fn send(&self, ctx: Context) -> Response {
+            let chain = vec![
+                crate::middleware::from_middleware(Arc::new(self.middleware.clone())),
+                Arc::clone(&self.handler),
+            ];
+            Next::new(chain).run(ctx).await
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    fn numbered(_: &Context, n: usize) -> Response {
+        Response::new(StatusCode::Ok)
+            .header("Content-Type", "text/plain")
+            .body(format!("response {n}"))
+    }
+
+    // ── Hits and misses ───────────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn hit_skips_handler() {
+        let h = Harness::new(numbered);
+        let first = h.send(request("GET", "/a?x=1", &[])).await;
+        assert_eq!(first.headers().get("x-cache"), Some("MISS"));
+
+        let second = h.send(request("GET", "/a?x=1", &[])).await;
+        assert_eq!(second.headers().get("x-cache"), Some("HIT"));
+        assert_eq!(second.headers().get("age"), Some("0"));
+        assert_eq!(second.headers().get("content-type"), Some("text/plain"));
+        assert_eq!(second.body_ref(), b"response 1");
+        assert_eq!(h.calls(), 1);
+
+        // Different query string, different entry.
+        h.send(request("GET", "/a?x=2", &[])).await;
+        assert_eq!(h.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn unsafe_methods_and_failures_are_not_cached() {
+        let h = Harness::new(|ctx, n| match ctx.request().path() {
+            "/missing" => Response::new(StatusCode::NotFound).body(n.to_string()),
+            "/stream" => Response::new(StatusCode::Ok)
+                .stream(crate::http::upgrade::OnUpgrade::new(|_io| async {})),
+            _ => Response::new(StatusCode::Ok).body(n.to_string()),
+        });
+        for _ in 0..2 {
+            h.send(request("POST", "/a", &[])).await;
+            h.send(request("GET", "/missing", &[])).await;
+            h.send(request("GET", "/stream", &[])).await;
+        }
+        assert_eq!(h.calls(), 6);
+    }
+
+    #[tokio::test]
+    async fn head_is_cached_separately() {
+        let h = Harness::new(numbered);
+        h.send(request("GET", "/a", &[])).await;
+        let head = h.send(request("HEAD", "/a", &[])).await;
+        assert_eq!(head.headers().get("x-cache"), Some("MISS"));
+        let head = h.send(request("HEAD", "/a", &[])).await;
+        assert_eq!(head.headers().get("x-cache"), Some("HIT"));
+        assert_eq!(h.calls(), 2);
+    }
+
+    // ── Cache-Control ─────────────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn response_directives_prevent_storage() {
+        for cc in ["no-store", "private, max-age=60", "no-cache", "max-age=0"] {
+            let h = Harness::new(move |_, n| {
+                Response::new(StatusCode::Ok)
+                    .header("Cache-Control", cc)
+                    .body(n.to_string())
+            });
+            h.send(request("GET", "/a", &[])).await;
+            h.send(request("GET", "/a", &[])).await;
+            assert_eq!(h.calls(), 2, "{cc}");
+        }
+    }
+
+    #[tokio::test]
+    async fn set_cookie_prevents_storage() {
+        let h = Harness::new(|_, n| {
+            Response::new(StatusCode::Ok)
+                .header("Set-Cookie", "a=b")
+                .body(n.to_string())
+        });
+        h.send(request("GET", "/a", &[])).await;
+        h.send(request("GET", "/a", &[])).await;
+        assert_eq!(h.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn request_no_cache_refreshes_entry() {
+        let h = Harness::new(numbered);
+        h.send(request("GET", "/a", &[])).await;
+        let fresh = h
+            .send(request("GET", "/a", &[("Cache-Control", "no-cache")]))
+            .await;
+        assert_eq!(fresh.body_ref(), b"response 2");
+        let cached = h.send(request("GET", "/a", &[])).await;
+        assert_eq!(cached.body_ref(), b"response 2");
+        assert_eq!(h.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn authorized_requests_need_public_responses() {
+        let h = Harness::new(numbered);
+        let auth = [("Authorization", "Bearer t")];
+        h.send(request("GET", "/me", &auth)).await;
+        h.send(request("GET", "/me", &auth)).await;
+        assert_eq!(h.calls(), 2);
+
+        let h = Harness::new(|_, n| {
+            Response::new(StatusCode::Ok)
+                .header("Cache-Control", "public, max-age=60")
+                .body(n.to_string())
+        });
+        h.send(request("GET", "/doc", &auth)).await;
+        h.send(request("GET", "/doc", &auth)).await;
+        assert_eq!(h.calls(), 1);
+    }
+
+    #[test]
+    fn cache_control_parsing() {
+        let cc = CacheControl::parse(["public, max-age=30", "S-MaxAge=\"120\""].into_iter());
+        assert!(cc.public && !cc.private && !cc.no_store);
+        assert_eq!(cc.max_age, Some(Duration::from_secs(30)));
+        assert_eq!(cc.s_maxage, Some(Duration::from_secs(120)));
+    }
+
+    // ── Vary and key headers ──────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn vary_stores_variants() {
+        let h = Harness::new(|ctx, _| {
+            let lang = ctx
+                .request()
+                .headers()
+                .get("accept-language")
+                .unwrap_or("en");
+            Response::new(StatusCode::Ok)
+                .header("Vary", "Accept-Language")
+                .body(lang.to_owned())
+        });
+        let en = [("Accept-Language", "en")];
+        let fr = [("Accept-Language", "fr")];
+        h.send(request("GET", "/", &en)).await;
+        h.send(request("GET", "/", &fr)).await;
+        assert_eq!(h.calls(), 2);
+
+        assert_eq!(h.send(request("GET", "/", &en)).await.body_ref(), b"en");
+        assert_eq!(h.send(request("GET", "/", &fr)).await.body_ref(), b"fr");
+        assert_eq!(h.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn vary_star_is_not_cached() {
+        let h = Harness::new(|_, n| {
+            Response::new(StatusCode::Ok)
+                .header("Vary", "*")
+                .body(n.to_string())
+        });
+        h.send(request("GET", "/", &[])).await;
+        h.send(request("GET", "/", &[])).await;
+        assert_eq!(h.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn key_headers_split_entries() {
+        let mut h = Harness::new(numbered);
+        h.middleware = h.middleware.key_header("X-Tenant");
+        h.send(request("GET", "/", &[("X-Tenant", "a")])).await;
+        h.send(request("GET", "/", &[("X-Tenant", "b")])).await;
+        h.send(request("GET", "/", &[("X-Tenant", "a")])).await;
+        assert_eq!(h.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn per_principal_keys_cache_private_responses() {
+        let mut h = Harness::new(|ctx, _| {
+            let user = ctx
+                .extensions()
+                .get::<crate::security::Principal>()
+                .unwrap();
+            Response::new(StatusCode::Ok)
+                .header("Cache-Control", "private, max-age=60")
+                .body(user.id().to_owned())
+        });
+        h.middleware = h.middleware.key(CacheKeyBuilder::new().principal());
+        let as_user = |id: &str| {
+            let mut ctx = request("GET", "/me", &[("Authorization", "Bearer t")]);
+            ctx.extensions_mut()
+                .insert(crate::security::Principal::new(id));
+            ctx
+        };
+
+        assert_eq!(h.send(as_user("alice")).await.body_ref(), b"alice");
+        assert_eq!(h.send(as_user("bob")).await.body_ref(), b"bob");
+        let hit = h.send(as_user("alice")).await;
+        assert_eq!(hit.headers().get("x-cache"), Some("HIT"));
+        assert_eq!(hit.body_ref(), b"alice");
+        assert_eq!(h.calls(), 2);
+    }
+
+    // ── Tags ──────────────────────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn tagged_responses_are_invalidated() {
+        let cache = Arc::new(MemoryCache::new(100));
+        let mut h = Harness::new(|ctx, n| {
+            ctx.cache_tags(["posts"]);
+            if ctx.request().path() == "/users/42" {
+                ctx.cache_tags(["user:42".to_owned()]);
+            }
+            numbered(ctx, n).header("Vary", "Accept")
+        });
+        h.middleware = CacheMiddleware::new(cache.clone());
+
+        h.send(request("GET", "/users/42", &[])).await;
+        h.send(request("GET", "/posts", &[])).await;
+        assert_eq!(h.calls(), 2);
+
+        cache.invalidate_tag("user:42").await.unwrap();
+        let miss = h.send(request("GET", "/users/42", &[])).await;
+        assert_eq!(miss.headers().get("x-cache"), Some("MISS"));
+        let hit = h.send(request("GET", "/posts", &[])).await;
+        assert_eq!(hit.headers().get("x-cache"), Some("HIT"));
+        assert_eq!(h.calls(), 3);
+
+        cache.invalidate_tag("posts").await.unwrap();
+        h.send(request("GET", "/posts", &[])).await;
+        h.send(request("GET", "/users/42", &[])).await;
+        assert_eq!(h.calls(), 5);
+    }
+
+    #[test]
+    fn cache_tags_outside_middleware_is_a_no_op() {
+        let ctx = request("GET", "/", &[]);
+        ctx.cache_tags(["anything"]);
+        assert!(ctx.extensions().get::<CacheTags>().is_none());
+    }
+}
@@ -1,16 +1,233 @@
 //! Caching layer — in-memory and external caching backends.
 //!
+//! ## Implemented
+//!
+//! - [`Cache`] — object-safe async byte cache trait (`get`/`set`/`delete`/
+//!   `get_or_insert_with`), shareable as `Arc<dyn Cache>`.
+//! - [`CacheExt`] — typed JSON values on top of any [`Cache`].
+//! - [`MemoryCache`] — in-process backend with per-entry TTL, LRU eviction at a fixed
+//!   capacity, single-flight `get_or_insert_with`, and typed (non-serialized) values.
+//! - [`CacheMiddleware`] — HTTP response caching honoring `Cache-Control` and `Vary`.
+//! - [`CacheKeyBuilder`] — configurable cache key derivation from request attributes.
+//! - [`RedisCache`] — shared backend on top of [`RedisClient`](crate::redis::RedisClient).
+//! - Tag-based invalidation — entries stored with tags ([`Cache::set_tagged`], or
+//!   [`CacheTagsExt::cache_tags`] from handlers behind [`CacheMiddleware`]) are purged
+//!   together by [`Cache::invalidate_tag`].
+//! - [`TieredCache`] — in-process LRU in front of a shared backend, kept coherent across
+//!   instances by Redis pub/sub invalidation broadcasts.
+//! - `MemcachedCache` (feature `memcached`) — binary-protocol backend with consistent
+//!   hashing over several servers.
+//! - [`CacheStats`] — hit/miss/eviction/latency counters from [`Cache::stats`], exported
+//!   to [`Metrics`](crate::middleware::Metrics) by [`CacheMetrics`].
+//! - [`CacheAdmin`] — API-key protected routes purging [`CacheMiddleware`] responses by
+//!   key, by tag, or all at once.
+//!
 //! ## Planned Features
 //!
-//! - In-memory LRU cache (via `moka` or `lru`)
-//! - Redis backend via `fred` or `deadpool-redis`
 //! - HTTP cache-control header generation
-//! - Response caching middleware
-//! - Cache key derivation from request attributes
 //!
-//! ## Status: PLANNED
+//! ## Status: IN PROGRESS
+//!
+//! # Examples
+//!
+//! ```
+//! use std::{sync::Arc, time::Duration};
+//! use rttp::cache::{Cache, CacheExt, MemoryCache};
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), rttp::cache::CacheError> {
+//! let cache: Arc<dyn Cache> = Arc::new(MemoryCache::new(10_000));
+//!
+//! cache.set("greeting", Arc::from(&b"hello"[..]), Some(Duration::from_secs(60))).await?;
+//! assert_eq!(cache.get("greeting").await?.as_deref(), Some(&b"hello"[..]));
+//!
+//! let user: Vec<String> = cache
+//!     .get_or_insert_json_with("user:1:roles", None, || async {
+//!         Ok(vec!["admin".to_owned()]) // e.g. a database query
+//!     })
+//!     .await?;
+//! assert_eq!(user, ["admin"]);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod admin;
+pub mod key;
+#[cfg(feature = "memcached")]
+pub mod memcached;
+pub mod memory;
+pub mod middleware;
+pub mod redis;
+pub mod stats;
+pub mod tiered;
+
+pub use admin::CacheAdmin;
+pub use key::CacheKeyBuilder;
+#[cfg(feature = "memcached")]
+pub use memcached::MemcachedCache;
+pub use memory::MemoryCache;
+pub use middleware::{CacheMiddleware, CacheTagsExt};
+pub use redis::RedisCache;
+pub use stats::{CacheMetrics, CacheStats};
+pub use tiered::TieredCache;
+
+use std::{pin::Pin, sync::Arc, time::Duration};
+
+use serde::{Serialize, de::DeserializeOwned};
+use thiserror::Error;
+
+use crate::redis::RedisError;
+
+/// Errors produced by cache backends.
+#[derive(Debug, Error)]
+pub enum CacheError {
+    #[error("cache backend error: {0}")]
+    Backend(String),
+
+    #[error("cached value could not be (de)serialized: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+impl From<RedisError> for CacheError {
+    fn from(err: RedisError) -> Self {
+        Self::Backend(err.to_string())
+    }
+}
+
+/// Boxed future returned by [`Cache`] methods.
+pub type CacheFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, CacheError>> + Send + 'a>>;
+
+/// An async key/value cache of byte strings.
+///
+/// Methods return boxed futures so the trait stays object-safe, mirroring
+/// [`SessionStore`](crate::security::session::SessionStore). Values are `Arc<[u8]>` so a
+/// hit can be handed out without copying.
+pub trait Cache: Send + Sync {
+    /// Returns the value for `key`, or `None` if it is absent or has expired.
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Option<Arc<[u8]>>>;
+
+    /// Stores `value` under `key`. `ttl` of `None` uses the backend's default expiry,
+    /// which may be "never".
+    fn set<'a>(
+        &'a self,
+        key: &'a str,
+        value: Arc<[u8]>,
+        ttl: Option<Duration>,
+    ) -> CacheFuture<'a, ()>;
+
+    /// Like [`set`](Self::set), additionally associating the entry with `tags` so it is
+    /// removed by [`invalidate_tag`](Self::invalidate_tag) for any of them.
+    fn set_tagged<'a>(
+        &'a self,
+        key: &'a str,
+        value: Arc<[u8]>,
+        ttl: Option<Duration>,
+        tags: &'a [String],
+    ) -> CacheFuture<'a, ()>;
+
+    /// Removes `key`, returning whether it was present.
+    fn delete<'a>(&'a self, key: &'a str) -> CacheFuture<'a, bool>;
+
+    /// Removes every entry stored with `tag`, returning how many were removed.
+    fn invalidate_tag<'a>(&'a self, tag: &'a str) -> CacheFuture<'a, usize>;
+
+    /// Atomically adds `delta` to the counter under `key`, starting from zero if it is
+    /// absent, and returns the new value.
+    ///
+    /// Counters are stored as decimal text, so [`get`](Self::get) reads them back as
+    /// such. With `ttl` the counter expires that long after this call; without it, an
+    /// existing counter keeps its expiry and a new one gets the backend's default.
+    /// Incrementing a value that is not a counter is an error.
+    fn increment<'a>(
+        &'a self,
+        key: &'a str,
+        delta: u64,
+        ttl: Option<Duration>,
+    ) -> CacheFuture<'a, u64>;
+
+    /// Returns a snapshot of the backend's counters, or `None` if it keeps none.
+    fn stats(&self) -> Option<CacheStats> {
+        None
+    }
+
+    /// Returns the value for `key`, computing and storing it with `init` on a miss.
+    ///
+    /// `init` is only polled on a miss. Errors from `init` are returned as-is and nothing
+    /// is stored. The default implementation is a plain get-then-set; backends may
+    /// override it to collapse concurrent misses for the same key into one `init`.
+    fn get_or_insert_with<'a>(
+        &'a self,
+        key: &'a str,
+        ttl: Option<Duration>,
+        init: CacheFuture<'a, Arc<[u8]>>,
+    ) -> CacheFuture<'a, Arc<[u8]>> {
+        Box::pin(async move {
+            if let Some(value) = self.get(key).await? {
+                return Ok(value);
+            }
+            let value = init.await?;
+            self.set(key, Arc::clone(&value), ttl).await?;
+            Ok(value)
+        })
+    }
+}
+
+/// Typed access to any [`Cache`], storing values as JSON.
+///
+/// Implemented for every `Cache`, including `dyn Cache`.
+pub trait CacheExt: Cache {
+    /// Returns the value for `key` deserialized as `T`.
+    ///
+    /// A value that does not deserialize as `T` is reported as
+    /// [`CacheError::Serialization`].
+    fn get_json<'a, T>(
+        &'a self,
+        key: &'a str,
+    ) -> impl Future<Output = Result<Option<T>, CacheError>> + Send + 'a
+    where
+        T: DeserializeOwned,
+    {
+        async move {
+            match self.get(key).await? {
+                Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+                None => Ok(None),
+            }
+        }
+    }
+
+    /// Stores `value` under `key` as JSON.
+    fn set_json<'a, T>(
+        &'a self,
+        key: &'a str,
+        value: &T,
+        ttl: Option<Duration>,
+    ) -> impl Future<Output = Result<(), CacheError>> + Send + 'a
+    where
+        T: Serialize + ?Sized,
+    {
+        let bytes = serde_json::to_vec(value).map(Arc::from);
+        async move { self.set(key, bytes?, ttl).await }
+    }
 
-// TODO: Implement caching layer
+    /// Typed [`Cache::get_or_insert_with`]: returns the cached `T`, or awaits `init()`
+    /// and caches its result.
+    fn get_or_insert_json_with<'a, T, F, Fut>(
+        &'a self,
+        key: &'a str,
+        ttl: Option<Duration>,
+        init: F,
+    ) -> impl Future<Output = Result<T, CacheError>> + Send + 'a
+    where
+        T: Serialize + DeserializeOwned + Send + 'a,
+        F: FnOnce() -> Fut + Send + 'a,
+        Fut: Future<Output = Result<T, CacheError>> + Send + 'a,
+    {
+        async move {
+            let init = Box::pin(async move { Ok(Arc::from(serde_json::to_vec(&init().await?)?)) });
+            let bytes = self.get_or_insert_with(key, ttl, init).await?;
+            Ok(serde_json::from_slice(&bytes)?)
+        }
+    }
+}
 
-/// Placeholder — will become the `Cache` trait and implementations.
-pub struct Cache;
+impl<C: Cache + ?Sized> CacheExt for C {}
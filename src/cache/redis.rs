@@ -0,0 +1,400 @@
+//! Redis-backed [`Cache`], shared by every instance of the application.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use tracing::warn;
+
+use super::{Cache, CacheFuture, CacheStats, stats::StatsRecorder};
+use crate::redis::{ExpireIf, RedisClient, RedisError};
+
+/// How many tagged stores pass between sweeps of the stored entry's tag sets.
+const TRIM_INTERVAL: u64 = 64;
+
+/// A [`Cache`] stored in Redis.
+///
+/// Values live under `<prefix><key>` with a native Redis expiry. Each tag is a Redis set
+/// under `<tag prefix><tag>` listing the keys stored with it. A tag set expires with the
+/// longest-lived entry added to it, or never while it lists an entry without an expiry.
+/// A tag that keeps receiving entries outlives the older ones, so every
+/// 64th tagged store also drops the names of expired entries from its tag sets.
+/// Extending tag-set expiries uses `PEXPIRE … NX|GT`, which needs Redis 7.
+///
+/// [`stats`](Cache::stats) counts hits, misses, and round-trip latency as seen by this
+/// instance; evictions happen inside Redis and are not observed.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::{sync::Arc, time::Duration};
+/// use rttp::{cache::{Cache, RedisCache}, redis::RedisClient};
+///
+/// let cache: Arc<dyn Cache> = Arc::new(
+///     RedisCache::new(RedisClient::new("127.0.0.1:6379"))
+///         .prefix("myapp:cache:")
+///         .default_ttl(Duration::from_secs(300)),
+/// );
+/// ```
+pub struct RedisCache {
+    client: RedisClient,
+    prefix: String,
+    tag_prefix: String,
+    default_ttl: Option<Duration>,
+    stats: StatsRecorder,
+    tagged_stores: AtomicU64,
+}
+
+impl RedisCache {
+    /// Creates a cache using `client`, with keys under `cache:` and tags under
+    /// `cache-tag:`.
+    pub fn new(client: RedisClient) -> Self {
+        Self {
+            client,
+            prefix: "cache:".to_owned(),
+            tag_prefix: "cache-tag:".to_owned(),
+            default_ttl: None,
+            stats: StatsRecorder::default(),
+            tagged_stores: AtomicU64::new(0),
+        }
+    }
+
+    /// Sets the prefix namespacing value keys.
+    #[must_use]
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the prefix namespacing tag sets.
+    #[must_use]
+    pub fn tag_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.tag_prefix = prefix.into();
+        self
+    }
+
+    /// Sets the expiry for entries stored without an explicit TTL. Without one, such
+    /// entries never expire.
+    #[must_use]
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+
+    fn tag_key(&self, tag: &str) -> String {
+        format!("{}{tag}", self.tag_prefix)
+    }
+
+    // Adds `key` to a tag set and makes the set live at least as long as the entry.
+    async fn track(
+        &self,
+        tag_key: &str,
+        key: &str,
+        ttl: Option<Duration>,
+    ) -> Result<(), RedisError> {
+        let Some(ttl) = ttl else {
+            self.client.sadd(tag_key, &[key]).await?;
+            self.client.persist(tag_key).await?;
+            return Ok(());
+        };
+        // `-1`: the set already lists an entry that never expires, so it must not either.
+        let current = self.client.pttl(tag_key).await?;
+        self.client.sadd(tag_key, &[key]).await?;
+        if current != -1 {
+            // `NX` covers a set that was just created, `GT` one that expires sooner.
+            self.client
+                .pexpire_if(tag_key, ttl, ExpireIf::Unset)
+                .await?;
+            self.client
+                .pexpire_if(tag_key, ttl, ExpireIf::Later)
+                .await?;
+        }
+        Ok(())
+    }
+
+    // Removes the names of entries that no longer exist from a tag set.
+    async fn trim(&self, tag_key: &str) -> Result<(), RedisError> {
+        let mut gone = Vec::new();
+        for member in self.client.smembers(tag_key).await? {
+            let Ok(key) = String::from_utf8(member) else {
+                continue;
+            };
+            if !self.client.exists(&key).await? {
+                gone.push(key);
+            }
+        }
+        if !gone.is_empty() {
+            let gone: Vec<&str> = gone.iter().map(String::as_str).collect();
+            self.client.srem(tag_key, &gone).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Cache for RedisCache {
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Option<Arc<[u8]>>> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let value = self.client.get(&self.key(key)).await;
+            self.stats.operation(start.elapsed());
+            let value = value?;
+            self.stats.lookup(value.is_some());
+            Ok(value.map(Arc::from))
+        })
+    }
+
+    fn set<'a>(
+        &'a self,
+        key: &'a str,
+        value: Arc<[u8]>,
+        ttl: Option<Duration>,
+    ) -> CacheFuture<'a, ()> {
+        self.set_tagged(key, value, ttl, &[])
+    }
+
+    fn set_tagged<'a>(
+        &'a self,
+        key: &'a str,
+        value: Arc<[u8]>,
+        ttl: Option<Duration>,
+        tags: &'a [String],
+    ) -> CacheFuture<'a, ()> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let key = self.key(key);
+            let ttl = ttl.or(self.default_ttl);
+            // Register the tags first: a crash in between leaves a dangling tag
+            // membership, never an entry that invalidation cannot reach.
+            for tag in tags {
+                self.track(&self.tag_key(tag), &key, ttl).await?;
+            }
+            let result = self.client.set(&key, &value, ttl).await;
+            self.stats.operation(start.elapsed());
+            result?;
+
+            let sweep = !tags.is_empty()
+                && self.tagged_stores.fetch_add(1, Ordering::Relaxed) % TRIM_INTERVAL
+                    == TRIM_INTERVAL - 1;
+            if sweep {
+                for tag in tags {
+                    if let Err(e) = self.trim(&self.tag_key(tag)).await {
+                        warn!(error = %e, tag, "failed to trim cache tag set");
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> CacheFuture<'a, bool> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let removed = self.client.del(&[&self.key(key)]).await;
+            self.stats.operation(start.elapsed());
+            Ok(removed? > 0)
+        })
+    }
+
+    fn increment<'a>(
+        &'a self,
+        key: &'a str,
+        delta: u64,
+        ttl: Option<Duration>,
+    ) -> CacheFuture<'a, u64> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let key = self.key(key);
+            let delta = i64::try_from(delta).unwrap_or(i64::MAX);
+            let value = self.client.incr_by(&key, delta).await;
+            // `INCRBY` keeps an existing expiry; a new counter gets the default one.
+            let expiry = match &value {
+                Ok(value) if ttl.is_none() && *value == delta => self.default_ttl,
+                Ok(_) => ttl,
+                Err(_) => None,
+            };
+            if let Some(expiry) = expiry {
+                self.client.pexpire(&key, expiry).await?;
+            }
+            self.stats.operation(start.elapsed());
+            Ok(u64::try_from(value?).unwrap_or(0))
+        })
+    }
+
+    fn invalidate_tag<'a>(&'a self, tag: &'a str) -> CacheFuture<'a, usize> {
+        Box::pin(async move {
+            let tag_key = self.tag_key(tag);
+            let members = self.client.smembers(&tag_key).await?;
+            let keys: Vec<String> = members
+                .into_iter()
+                .filter_map(|m| String::from_utf8(m).ok())
+                .collect();
+            if keys.is_empty() {
+                return Ok(0);
+            }
+            let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+            let removed = self.client.del(&keys).await?;
+            // Remove only the members we saw, so keys tagged concurrently stay tracked.
+            self.client.srem(&tag_key, &keys).await?;
+            Ok(usize::try_from(removed).unwrap_or(0))
+        })
+    }
+
+    fn stats(&self) -> Option<CacheStats> {
+        Some(self.stats.snapshot())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cache::CacheExt, redis::testing::FakeRedis};
+
+    #[tokio::test]
+    async fn round_trip_and_delete() {
+        let server = FakeRedis::start().await;
+        let cache = RedisCache::new(RedisClient::new(server.addr())).prefix("c:");
+
+        cache.set_json("k", &[1, 2], None).await.unwrap();
+        assert_eq!(
+            cache.get_json::<Vec<u8>>("k").await.unwrap(),
+            Some(vec![1, 2])
+        );
+        assert_eq!(
+            cache.client.get("c:k").await.unwrap().as_deref(),
+            Some(&b"[1,2]"[..])
+        );
+        assert!(cache.delete("k").await.unwrap());
+        assert!(!cache.delete("k").await.unwrap());
+        assert_eq!(cache.get("k").await.unwrap(), None);
+
+        let stats = cache.stats().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.operations), (1, 1, 5));
+    }
+
+    #[tokio::test]
+    async fn increments_counters() {
+        let server = FakeRedis::start().await;
+        let cache = RedisCache::new(RedisClient::new(server.addr())).prefix("c:");
+
+        assert_eq!(cache.increment("n", 2, None).await.unwrap(), 2);
+        let ttl = Some(Duration::from_secs(60));
+        assert_eq!(cache.increment("n", 3, ttl).await.unwrap(), 5);
+        assert_eq!(
+            cache.client.get("c:n").await.unwrap().as_deref(),
+            Some(&b"5"[..])
+        );
+
+        cache.set_json("json", &"text", None).await.unwrap();
+        assert!(cache.increment("json", 1, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn invalidate_tag() {
+        let server = FakeRedis::start().await;
+        let cache = RedisCache::new(RedisClient::new(server.addr()));
+        let value: Arc<[u8]> = Arc::from(&b"v"[..]);
+        let tags = |t: &[&str]| t.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        cache
+            .set_tagged("a", value.clone(), None, &tags(&["user:1", "users"]))
+            .await
+            .unwrap();
+        cache
+            .set_tagged("b", value.clone(), None, &tags(&["users"]))
+            .await
+            .unwrap();
+        cache.set("c", value, None).await.unwrap();
+
+        assert_eq!(cache.invalidate_tag("user:1").await.unwrap(), 1);
+        assert_eq!(cache.get("a").await.unwrap(), None);
+        assert!(cache.get("b").await.unwrap().is_some());
+
+        // `a` is already gone; only `b` is removed now.
+        assert_eq!(cache.invalidate_tag("users").await.unwrap(), 1);
+        assert_eq!(cache.invalidate_tag("users").await.unwrap(), 0);
+        assert!(cache.get("c").await.unwrap().is_some());
+        assert!(
+            cache
+                .client
+                .smembers("cache-tag:users")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn tag_sets_expire_with_their_entries() {
+        let server = FakeRedis::start().await;
+        let cache = RedisCache::new(RedisClient::new(server.addr()));
+        let value: Arc<[u8]> = Arc::from(&b"v"[..]);
+        let tags = ["posts".to_owned()];
+        let pttl = || cache.client.pttl("cache-tag:posts");
+
+        let minute = Duration::from_secs(60);
+        cache
+            .set_tagged("a", value.clone(), Some(minute), &tags)
+            .await
+            .unwrap();
+        assert!((1..=60_000).contains(&pttl().await.unwrap()));
+        // A longer-lived entry extends the set; a shorter one does not shorten it.
+        cache
+            .set_tagged("b", value.clone(), Some(10 * minute), &tags)
+            .await
+            .unwrap();
+        cache
+            .set_tagged("c", value.clone(), Some(Duration::from_secs(1)), &tags)
+            .await
+            .unwrap();
+        assert!(pttl().await.unwrap() > 60_000);
+
+        // An entry without an expiry keeps the set forever, whatever follows.
+        cache
+            .set_tagged("d", value.clone(), None, &tags)
+            .await
+            .unwrap();
+        cache
+            .set_tagged("e", value, Some(minute), &tags)
+            .await
+            .unwrap();
+        assert_eq!(pttl().await.unwrap(), -1);
+    }
+
+    #[tokio::test]
+    async fn trims_expired_entries_from_busy_tags() {
+        let server = FakeRedis::start().await;
+        let cache = RedisCache::new(RedisClient::new(server.addr()));
+        let value: Arc<[u8]> = Arc::from(&b"v"[..]);
+        let tags = ["feed".to_owned()];
+
+        // A live entry keeps the set; expired ones linger in it until a sweep.
+        let minute = Some(Duration::from_secs(60));
+        cache
+            .set_tagged("live", value.clone(), minute, &tags)
+            .await
+            .unwrap();
+        let gone = Some(Duration::from_millis(1));
+        for i in 0..TRIM_INTERVAL - 2 {
+            let key = format!("old{i}");
+            cache
+                .set_tagged(&key, value.clone(), gone, &tags)
+                .await
+                .unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let members = cache.client.smembers("cache-tag:feed").await.unwrap();
+        assert_eq!(members.len() as u64, TRIM_INTERVAL - 1);
+
+        cache.set_tagged("new", value, minute, &tags).await.unwrap();
+        let members = cache.client.smembers("cache-tag:feed").await.unwrap();
+        assert_eq!(members, [b"cache:live".to_vec(), b"cache:new".to_vec()]);
+    }
+}
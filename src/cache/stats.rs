@@ -0,0 +1,176 @@
+//! Cache effectiveness counters.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use super::Cache;
+use crate::middleware::metrics::{MetricsSource, MetricsWriter};
+
+/// A point-in-time snapshot of a cache's counters, from [`Cache::stats`].
+///
+/// Counters start at zero when the cache is created and only grow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups answered from the cache.
+    pub hits: u64,
+    /// Lookups that found nothing (absent or expired).
+    pub misses: u64,
+    /// Entries dropped to make room for new ones. Backends that evict on their own
+    /// (such as Redis under `maxmemory`) cannot observe this and report zero.
+    pub evictions: u64,
+    /// Timed `get`/`set`/`delete` calls.
+    pub operations: u64,
+    /// Total time spent in the timed calls.
+    pub total_latency: Duration,
+}
+
+impl CacheStats {
+    /// The fraction of lookups that were hits, or `None` before the first lookup.
+    pub fn hit_ratio(&self) -> Option<f64> {
+        let lookups = self.hits + self.misses;
+        (lookups > 0).then(|| self.hits as f64 / lookups as f64)
+    }
+
+    /// The mean latency of the timed calls, or `None` before the first one.
+    pub fn mean_latency(&self) -> Option<Duration> {
+        let operations = u32::try_from(self.operations).ok().filter(|&n| n > 0)?;
+        Some(self.total_latency / operations)
+    }
+}
+
+/// Lock-free counters backing [`CacheStats`].
+#[derive(Debug, Default)]
+pub(crate) struct StatsRecorder {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    operations: AtomicU64,
+    latency_nanos: AtomicU64,
+}
+
+impl StatsRecorder {
+    pub(crate) fn lookup(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn evicted(&self, n: usize) {
+        if n > 0 {
+            self.evictions.fetch_add(n as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn operation(&self, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.operations.fetch_add(1, Ordering::Relaxed);
+        self.latency_nanos.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            operations: self.operations.load(Ordering::Relaxed),
+            total_latency: Duration::from_nanos(self.latency_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Exports a cache's [`CacheStats`] through [`Metrics`](crate::middleware::Metrics),
+/// labelled `cache="<name>"`.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use rttp::{cache::{Cache, CacheMetrics, MemoryCache}, middleware::Metrics};
+///
+/// let pages: Arc<dyn Cache> = Arc::new(MemoryCache::new(1_000));
+/// let metrics = Metrics::new();
+/// metrics.register(CacheMetrics::new("pages", pages));
+/// assert!(metrics.render().contains("rttp_cache_hits_total{cache=\"pages\"} 0"));
+/// ```
+pub struct CacheMetrics {
+    name: String,
+    cache: Arc<dyn Cache>,
+}
+
+impl CacheMetrics {
+    /// Exports the statistics of `cache` under `name`.
+    pub fn new(name: impl Into<String>, cache: Arc<dyn Cache>) -> Self {
+        Self {
+            name: name.into(),
+            cache,
+        }
+    }
+}
+
+impl MetricsSource for CacheMetrics {
+    fn collect(&self, out: &mut MetricsWriter) {
+        let Some(stats) = self.cache.stats() else {
+            return;
+        };
+        let labels = [("cache", self.name.as_str())];
+        out.counter(
+            "rttp_cache_hits_total",
+            "Cache lookups answered from the cache.",
+            &labels,
+            stats.hits as f64,
+        );
+        out.counter(
+            "rttp_cache_misses_total",
+            "Cache lookups that found nothing.",
+            &labels,
+            stats.misses as f64,
+        );
+        out.counter(
+            "rttp_cache_evictions_total",
+            "Cache entries evicted to make room.",
+            &labels,
+            stats.evictions as f64,
+        );
+        out.counter(
+            "rttp_cache_operations_total",
+            "Timed cache get/set/delete calls.",
+            &labels,
+            stats.operations as f64,
+        );
+        out.counter(
+            "rttp_cache_operation_seconds_total",
+            "Total time spent in timed cache calls.",
+            &labels,
+            stats.total_latency.as_secs_f64(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derived_ratios() {
+        assert_eq!(CacheStats::default().hit_ratio(), None);
+        assert_eq!(CacheStats::default().mean_latency(), None);
+
+        let recorder = StatsRecorder::default();
+        for hit in [true, true, true, false] {
+            recorder.lookup(hit);
+        }
+        recorder.evicted(0);
+        recorder.evicted(2);
+        recorder.operation(Duration::from_millis(1));
+        recorder.operation(Duration::from_millis(3));
+
+        let stats = recorder.snapshot();
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (3, 1, 2));
+        assert_eq!(stats.hit_ratio(), Some(0.75));
+        assert_eq!(stats.mean_latency(), Some(Duration::from_millis(2)));
+    }
+}
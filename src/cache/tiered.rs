@@ -0,0 +1,501 @@
+//! Two-level cache: a small in-process LRU in front of a shared backend.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use super::{Cache, CacheError, CacheFuture, CacheStats, MemoryCache, stats::StatsRecorder};
+use crate::redis::{Message, RedisClient};
+
+/// A [`MemoryCache`] in front of a shared [`Cache`] (usually a
+/// [`RedisCache`](super::RedisCache)).
+///
+/// - **Read-through** — reads are answered from the local tier when possible; a local
+///   miss reads the remote tier and keeps a local copy.
+/// - **Write-through** — writes and deletes go to the remote tier first, then the local
+///   one. Concurrent local misses for one key share a single remote
+///   [`get_or_insert_with`](Cache::get_or_insert_with).
+/// - **Coherence** — local copies live at most [`local_ttl`](Self::local_ttl), which
+///   bounds how stale another instance's write can look. With
+///   [`broadcast_invalidations`](Self::broadcast_invalidations), every write also
+///   publishes an invalidation over Redis pub/sub so the other instances drop their
+///   copies immediately.
+///
+/// Entries read through from the remote tier do not know their tags, so invalidating a
+/// tag clears the whole local tier (on every instance, when broadcasting).
+///
+/// [`stats`](Cache::stats) reports the combined view: a hit is a value found in either
+/// tier, and evictions are the local tier's. [`local_stats`](Self::local_stats) shows how
+/// often the local tier alone answered.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::{sync::Arc, time::Duration};
+/// use rttp::{cache::{Cache, RedisCache, TieredCache}, redis::RedisClient};
+///
+/// # async fn example() -> Result<(), rttp::cache::CacheError> {
+/// let remote = Arc::new(RedisCache::new(RedisClient::new("127.0.0.1:6379")));
+/// let cache: Arc<dyn Cache> = Arc::new(
+///     TieredCache::new(remote, 1_000)
+///         .local_ttl(Duration::from_secs(5))
+///         .broadcast_invalidations(RedisClient::new("127.0.0.1:6379"), "cache-invalidate")
+///         .await?,
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub struct TieredCache {
+    local: Arc<MemoryCache>,
+    remote: Arc<dyn Cache>,
+    local_ttl: Duration,
+    broadcast: Option<Broadcast>,
+    stats: StatsRecorder,
+}
+
+struct Broadcast {
+    client: RedisClient,
+    channel: String,
+    // Identifies this instance so it ignores its own invalidations.
+    origin: String,
+    listener: JoinHandle<()>,
+}
+
+impl TieredCache {
+    /// Creates a cache keeping up to `local_capacity` entries in process, for at most
+    /// 10 seconds each, in front of `remote`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `local_capacity` is zero.
+    pub fn new(remote: Arc<dyn Cache>, local_capacity: usize) -> Self {
+        Self {
+            local: Arc::new(MemoryCache::new(local_capacity)),
+            remote,
+            local_ttl: Duration::from_secs(10),
+            broadcast: None,
+            stats: StatsRecorder::default(),
+        }
+    }
+
+    /// Sets the longest time an entry is served from the local tier. Shorter TTLs given
+    /// on writes still apply.
+    #[must_use]
+    pub fn local_ttl(mut self, ttl: Duration) -> Self {
+        self.local_ttl = ttl;
+        self
+    }
+
+    /// Publishes invalidations for every write on `channel` and applies those published
+    /// by other instances to the local tier.
+    ///
+    /// Resolves once the subscription is established. The listener runs in a background
+    /// task until the cache is dropped; if the subscription is lost it clears the local
+    /// tier (invalidations may have been missed) and resubscribes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CacheError::Backend`] if subscribing fails.
+    pub async fn broadcast_invalidations(
+        mut self,
+        client: RedisClient,
+        channel: impl Into<String>,
+    ) -> Result<Self, CacheError> {
+        let channel = channel.into();
+        let origin = crate::security::crypto::random_token(12);
+        let subscription = client.subscribe(&[&channel]).await?;
+        let listener = tokio::spawn(listen(
+            Arc::clone(&self.local),
+            RedisClient::new(client.addr()),
+            channel.clone(),
+            origin.clone(),
+            subscription,
+        ));
+        if let Some(previous) = self.broadcast.replace(Broadcast {
+            client,
+            channel,
+            origin,
+            listener,
+        }) {
+            previous.listener.abort();
+        }
+        Ok(self)
+    }
+
+    /// Returns the local tier's counters.
+    pub fn local_stats(&self) -> CacheStats {
+        self.local.stats().unwrap_or_default()
+    }
+
+    fn local_ttl_for(&self, ttl: Option<Duration>) -> Option<Duration> {
+        Some(ttl.map_or(self.local_ttl, |ttl| ttl.min(self.local_ttl)))
+    }
+
+    async fn publish(&self, invalidation: Invalidation<'_>) {
+        let Some(broadcast) = &self.broadcast else {
+            return;
+        };
+        let payload = invalidation.encode(&broadcast.origin);
+        let channel = &broadcast.channel;
+        if let Err(e) = broadcast.client.publish(channel, &payload).await {
+            warn!(error = %e, %channel, "failed to publish cache invalidation");
+        }
+    }
+}
+
+impl TieredCache {
+    async fn read_through(&self, key: &str) -> Result<Option<Arc<[u8]>>, CacheError> {
+        if let Some(value) = self.local.get(key).await? {
+            return Ok(Some(value));
+        }
+        let value = self.remote.get(key).await?;
+        if let Some(value) = &value {
+            self.local
+                .set(key, Arc::clone(value), self.local_ttl_for(None))
+                .await?;
+        }
+        Ok(value)
+    }
+}
+
+impl Drop for TieredCache {
+    fn drop(&mut self) {
+        if let Some(broadcast) = &self.broadcast {
+            broadcast.listener.abort();
+        }
+    }
+}
+
+impl Cache for TieredCache {
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Option<Arc<[u8]>>> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let value = self.read_through(key).await;
+            self.stats.operation(start.elapsed());
+            if let Ok(value) = &value {
+                self.stats.lookup(value.is_some());
+            }
+            value
+        })
+    }
+
+    fn set<'a>(
+        &'a self,
+        key: &'a str,
+        value: Arc<[u8]>,
+        ttl: Option<Duration>,
+    ) -> CacheFuture<'a, ()> {
+        self.set_tagged(key, value, ttl, &[])
+    }
+
+    fn set_tagged<'a>(
+        &'a self,
+        key: &'a str,
+        value: Arc<[u8]>,
+        ttl: Option<Duration>,
+        tags: &'a [String],
+    ) -> CacheFuture<'a, ()> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let result = self
+                .remote
+                .set_tagged(key, Arc::clone(&value), ttl, tags)
+                .await;
+            self.stats.operation(start.elapsed());
+            result?;
+            self.local
+                .set_tagged(key, value, self.local_ttl_for(ttl), tags)
+                .await?;
+            self.publish(Invalidation::Key(key)).await;
+            Ok(())
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> CacheFuture<'a, bool> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let removed = self.remote.delete(key).await;
+            self.stats.operation(start.elapsed());
+            let removed = removed?;
+            let removed_locally = self.local.delete(key).await?;
+            self.publish(Invalidation::Key(key)).await;
+            Ok(removed || removed_locally)
+        })
+    }
+
+    fn increment<'a>(
+        &'a self,
+        key: &'a str,
+        delta: u64,
+        ttl: Option<Duration>,
+    ) -> CacheFuture<'a, u64> {
+        Box::pin(async move {
+            // Counters live only in the shared tier; drop any stale local copy.
+            let start = Instant::now();
+            let value = self.remote.increment(key, delta, ttl).await;
+            self.stats.operation(start.elapsed());
+            let value = value?;
+            self.local.delete(key).await?;
+            self.publish(Invalidation::Key(key)).await;
+            Ok(value)
+        })
+    }
+
+    fn invalidate_tag<'a>(&'a self, tag: &'a str) -> CacheFuture<'a, usize> {
+        Box::pin(async move {
+            let removed = self.remote.invalidate_tag(tag).await?;
+            self.local.clear();
+            self.publish(Invalidation::Tag(tag)).await;
+            Ok(removed)
+        })
+    }
+
+    fn get_or_insert_with<'a>(
+        &'a self,
+        key: &'a str,
+        ttl: Option<Duration>,
+        init: CacheFuture<'a, Arc<[u8]>>,
+    ) -> CacheFuture<'a, Arc<[u8]>> {
+        Box::pin(async move {
+            // A hit in either tier never polls `init`.
+            let computed = AtomicBool::new(false);
+            let init = Box::pin(async {
+                computed.store(true, Ordering::Relaxed);
+                init.await
+            });
+            let remote = self.remote.get_or_insert_with(key, ttl, init);
+            let value = self
+                .local
+                .get_or_insert_with(key, self.local_ttl_for(ttl), remote)
+                .await;
+            if value.is_ok() {
+                self.stats.lookup(!computed.load(Ordering::Relaxed));
+            }
+            value
+        })
+    }
+
+    fn stats(&self) -> Option<CacheStats> {
+        Some(CacheStats {
+            evictions: self.local_stats().evictions,
+            ..self.stats.snapshot()
+        })
+    }
+}
+
+/// A message on the invalidation channel: `<origin> key <key>` or `<origin> tag <tag>`.
+#[derive(Debug, PartialEq, Eq)]
+enum Invalidation<'a> {
+    Key(&'a str),
+    Tag(&'a str),
+}
+
+impl<'a> Invalidation<'a> {
+    fn encode(&self, origin: &str) -> Vec<u8> {
+        match self {
+            Self::Key(key) => format!("{origin} key {key}"),
+            Self::Tag(tag) => format!("{origin} tag {tag}"),
+        }
+        .into_bytes()
+    }
+
+    // Returns the sender's origin and the invalidation.
+    fn decode(payload: &'a [u8]) -> Option<(&'a str, Self)> {
+        let payload = std::str::from_utf8(payload).ok()?;
+        let (origin, rest) = payload.split_once(' ')?;
+        let (kind, name) = rest.split_once(' ')?;
+        match kind {
+            "key" => Some((origin, Self::Key(name))),
+            "tag" => Some((origin, Self::Tag(name))),
+            _ => None,
+        }
+    }
+}
+
+async fn listen(
+    local: Arc<MemoryCache>,
+    client: RedisClient,
+    channel: String,
+    origin: String,
+    mut subscription: crate::redis::Subscription,
+) {
+    loop {
+        match subscription.next_message().await {
+            Ok(Message { payload, .. }) => match Invalidation::decode(&payload) {
+                Some((from, _)) if from == origin => {}
+                Some((_, Invalidation::Key(key))) => {
+                    let _ = local.delete(key).await;
+                }
+                Some((_, Invalidation::Tag(_))) => local.clear(),
+                None => warn!(%channel, "ignoring malformed cache invalidation"),
+            },
+            Err(e) => {
+                warn!(error = %e, %channel, "cache invalidation subscription lost");
+                local.clear();
+                subscription = loop {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    match client.subscribe(&[&channel]).await {
+                        Ok(subscription) => break subscription,
+                        Err(e) => {
+                            warn!(error = %e, %channel, "cache invalidation resubscribe failed")
+                        }
+                    }
+                };
+                // Writes made while disconnected were never announced.
+                local.clear();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redis::testing::FakeRedis;
+
+    fn bytes(s: &str) -> Arc<[u8]> {
+        Arc::from(s.as_bytes())
+    }
+
+    fn tiers() -> (Arc<MemoryCache>, TieredCache) {
+        let remote = Arc::new(MemoryCache::new(100));
+        let cache = TieredCache::new(remote.clone(), 10);
+        (remote, cache)
+    }
+
+    // Polls `cache` until `key` reads as `expected`, failing after a second.
+    async fn eventually(cache: &TieredCache, key: &str, expected: Option<&str>) {
+        for _ in 0..100 {
+            if cache.get(key).await.unwrap() == expected.map(bytes) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("{key} never became {expected:?}");
+    }
+
+    // ── Read- and write-through ───────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn reads_through_and_keeps_local_copy() {
+        let (remote, cache) = tiers();
+        let cache = cache.local_ttl(Duration::from_millis(30));
+
+        remote.set("k", bytes("v1"), None).await.unwrap();
+        assert_eq!(cache.get("k").await.unwrap(), Some(bytes("v1")));
+        assert_eq!(cache.local.len(), 1);
+
+        // A write that bypasses this instance is seen once the local copy expires.
+        remote.set("k", bytes("v2"), None).await.unwrap();
+        assert_eq!(cache.get("k").await.unwrap(), Some(bytes("v1")));
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(cache.get("k").await.unwrap(), Some(bytes("v2")));
+        assert_eq!(cache.get("missing").await.unwrap(), None);
+
+        let (stats, local) = (cache.stats().unwrap(), cache.local_stats());
+        assert_eq!((stats.hits, stats.misses), (3, 1));
+        assert_eq!((local.hits, local.misses), (1, 3));
+    }
+
+    #[tokio::test]
+    async fn writes_through_to_both_tiers() {
+        let (remote, cache) = tiers();
+
+        cache.set("k", bytes("v"), None).await.unwrap();
+        assert_eq!(remote.get("k").await.unwrap(), Some(bytes("v")));
+        assert_eq!(cache.local.len(), 1);
+
+        assert!(cache.delete("k").await.unwrap());
+        assert_eq!(remote.get("k").await.unwrap(), None);
+        assert!(cache.local.is_empty());
+    }
+
+    #[tokio::test]
+    async fn invalidate_tag_clears_local_tier() {
+        let (remote, cache) = tiers();
+        let tags = ["posts".to_owned()];
+
+        cache
+            .set_tagged("a", bytes("1"), None, &tags)
+            .await
+            .unwrap();
+        remote.set("b", bytes("2"), None).await.unwrap();
+        cache.get("b").await.unwrap();
+
+        assert_eq!(cache.invalidate_tag("posts").await.unwrap(), 1);
+        assert!(cache.local.is_empty());
+        assert_eq!(cache.get("a").await.unwrap(), None);
+        assert_eq!(cache.get("b").await.unwrap(), Some(bytes("2")));
+    }
+
+    #[tokio::test]
+    async fn get_or_insert_with_fills_both_tiers() {
+        let (remote, cache) = tiers();
+        let value = cache
+            .get_or_insert_with("k", None, Box::pin(async { Ok(bytes("computed")) }))
+            .await
+            .unwrap();
+        assert_eq!(value, bytes("computed"));
+        assert_eq!(remote.get("k").await.unwrap(), Some(bytes("computed")));
+        assert_eq!(cache.local.len(), 1);
+
+        cache
+            .get_or_insert_with("k", None, Box::pin(async { unreachable!() }))
+            .await
+            .unwrap();
+        let stats = cache.stats().unwrap();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+    }
+
+    // ── Broadcast invalidation ────────────────────────────────────────────────
+
+    #[test]
+    fn invalidation_round_trip() {
+        let encoded = Invalidation::Key("user:1 profile").encode("abc");
+        assert_eq!(
+            Invalidation::decode(&encoded),
+            Some(("abc", Invalidation::Key("user:1 profile")))
+        );
+        assert_eq!(
+            Invalidation::decode(&Invalidation::Tag("t").encode("abc")),
+            Some(("abc", Invalidation::Tag("t")))
+        );
+        assert_eq!(Invalidation::decode(b"abc nope x"), None);
+    }
+
+    #[tokio::test]
+    async fn writes_invalidate_other_instances() {
+        let server = FakeRedis::start().await;
+        let remote: Arc<dyn Cache> = Arc::new(MemoryCache::new(100));
+        let instance = || async {
+            TieredCache::new(Arc::clone(&remote), 10)
+                .broadcast_invalidations(RedisClient::new(server.addr()), "inval")
+                .await
+                .unwrap()
+        };
+        let a = instance().await;
+        let b = instance().await;
+
+        b.set("k", bytes("v1"), None).await.unwrap();
+        assert_eq!(a.get("k").await.unwrap(), Some(bytes("v1")));
+
+        b.set("k", bytes("v2"), None).await.unwrap();
+        eventually(&a, "k", Some("v2")).await;
+
+        b.delete("k").await.unwrap();
+        eventually(&a, "k", None).await;
+
+        remote.set("t", bytes("x"), None).await.unwrap();
+        assert_eq!(a.get("t").await.unwrap(), Some(bytes("x")));
+        remote.delete("t").await.unwrap();
+        b.invalidate_tag("anything").await.unwrap();
+        eventually(&a, "t", None).await;
+    }
+}
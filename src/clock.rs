@@ -0,0 +1,163 @@
+//! Time sources for time-based components.
+//!
+//! Cache TTLs, session expiry, rate limits, and the task queue's scheduler read the
+//! time through a [`Clock`] instead of calling `Instant::now` directly. They use
+//! [`SystemClock`] unless given another clock with their `clock` builder; tests pass a
+//! [`MockClock`] and [`advance`](MockClock::advance) it by hand instead of sleeping.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::{sync::Arc, time::Duration};
+//! use rttp::cache::{Cache, MemoryCache};
+//! use rttp::clock::MockClock;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let clock = MockClock::new();
+//! let cache = MemoryCache::new(100).clock(Arc::new(clock.clone()));
+//! cache.set("k", Arc::from(&b"v"[..]), Some(Duration::from_secs(60))).await.unwrap();
+//!
+//! clock.advance(Duration::from_secs(61));
+//! assert_eq!(cache.get("k").await.unwrap(), None);
+//! # }
+//! ```
+
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use tokio::sync::watch;
+
+/// A source of the current time.
+pub trait Clock: Send + Sync + fmt::Debug {
+    /// The current monotonic time, for measuring intervals and deadlines.
+    fn now(&self) -> Instant;
+
+    /// The current wall-clock time, for timestamps.
+    fn system_time(&self) -> SystemTime;
+
+    /// Completes once [`now`](Self::now) reaches `deadline`.
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+}
+
+/// The real time, as reported by the operating system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(tokio::time::sleep_until(deadline.into()))
+    }
+}
+
+/// Returns the [`SystemClock`] as a shared clock, the default for every component.
+pub fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// A clock that only moves when told to.
+///
+/// Clones share the same time, so a test keeps one handle and gives another to the
+/// component under test. Sleeps wake when [`advance`](Self::advance) moves the clock
+/// past their deadline.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use rttp::clock::{Clock, MockClock};
+///
+/// let clock = MockClock::new();
+/// let start = clock.now();
+/// clock.advance(Duration::from_secs(5));
+/// assert_eq!(clock.now() - start, Duration::from_secs(5));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<watch::Sender<Instant>>,
+    system: Arc<Mutex<SystemTime>>,
+}
+
+impl MockClock {
+    /// Creates a clock stopped at the current time.
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(watch::Sender::new(Instant::now())),
+            system: Arc::new(Mutex::new(SystemTime::now())),
+        }
+    }
+
+    /// Moves both the monotonic and the wall-clock time forward by `by`, waking any
+    /// sleeps whose deadline has passed.
+    pub fn advance(&self, by: Duration) {
+        self.now.send_modify(|now| *now += by);
+        let mut system = self.system.lock().unwrap_or_else(|e| e.into_inner());
+        *system += by;
+    }
+
+    /// Sets the wall-clock time, leaving the monotonic time alone.
+    pub fn set_system_time(&self, at: SystemTime) {
+        *self.system.lock().unwrap_or_else(|e| e.into_inner()) = at;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.now.borrow()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        *self.system.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let mut now = self.now.subscribe();
+        Box::pin(async move {
+            // The sender lives as long as any clone of the clock; if it is gone no one
+            // can advance it, so the sleep never ends.
+            if now.wait_for(|now| *now >= deadline).await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mock_sleeps_wake_on_advance() {
+        let clock = MockClock::new();
+        let deadline = clock.now() + Duration::from_secs(10);
+        let sleep = tokio::spawn(clock.sleep_until(deadline));
+
+        clock.advance(Duration::from_secs(9));
+        tokio::task::yield_now().await;
+        assert!(!sleep.is_finished());
+
+        clock.advance(Duration::from_secs(1));
+        tokio::time::timeout(Duration::from_secs(1), sleep)
+            .await
+            .expect("sleep should wake")
+            .unwrap();
+    }
+}
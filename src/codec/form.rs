@@ -0,0 +1,135 @@
+//! `application/x-www-form-urlencoded` codec.
+
+use serde_json::{Map, Value};
+
+use super::{Codec, CodecError};
+use crate::http::uri::{Space, decode_component, encode_component};
+
+/// The HTML form codec (`application/x-www-form-urlencoded`).
+///
+/// Decoding produces a flat JSON object of string values; a key that repeats, or that
+/// ends in `[]`, collects its values into an array. [`Body`](super::Body) converts the
+/// strings to the numbers and booleans the target type expects, as
+/// [`Query`](super::Query) does. Encoding accepts a flat object whose values are
+/// strings, numbers, booleans, or `null` (written as an empty value); nested arrays and
+/// objects are rejected.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FormCodec;
+
+impl Codec for FormCodec {
+    fn media_type(&self) -> &str {
+        "application/x-www-form-urlencoded"
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Value, CodecError> {
+        let text = std::str::from_utf8(bytes).map_err(|e| CodecError::Decode(e.to_string()))?;
+
+        let mut map = Map::new();
+        for pair in text.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let key = decode(key)?;
+            let value = Value::String(decode(value)?);
+            let (key, array) = match key.strip_suffix("[]") {
+                Some(key) => (key.to_owned(), true),
+                None => (key, false),
+            };
+            match map.get_mut(&key) {
+                Some(Value::Array(values)) => values.push(value),
+                Some(first) => *first = Value::Array(vec![first.take(), value]),
+                None if array => {
+                    map.insert(key, Value::Array(vec![value]));
+                }
+                None => {
+                    map.insert(key, value);
+                }
+            }
+        }
+        Ok(Value::Object(map))
+    }
+
+    fn values_are_strings(&self) -> bool {
+        true
+    }
+
+    fn encode(&self, value: &Value) -> Result<Vec<u8>, CodecError> {
+        let Value::Object(map) = value else {
+            return Err(CodecError::Encode(
+                "form bodies must be encoded from an object".to_owned(),
+            ));
+        };
+
+        let mut out = String::new();
+        for (key, value) in map {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) => b.to_string(),
+                Value::Null => String::new(),
+                Value::Array(_) | Value::Object(_) => {
+                    return Err(CodecError::Encode(format!(
+                        "field `{key}` is not a scalar value"
+                    )));
+                }
+            };
+            if !out.is_empty() {
+                out.push('&');
+            }
+            encode_component(key, Space::Plus, &mut out);
+            out.push('=');
+            encode_component(&value, Space::Plus, &mut out);
+        }
+        Ok(out.into_bytes())
+    }
+}
+
+fn decode(component: &str) -> Result<String, CodecError> {
+    decode_component(component)
+        .map_err(|_| CodecError::Decode(format!("invalid percent-encoding in `{component}`")))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn decode_plus_and_percent() {
+        let value = FormCodec
+            .decode(b"name=ada+lovelace&city=Z%C3%BCrich")
+            .unwrap();
+        assert_eq!(value, json!({"name": "ada lovelace", "city": "Zürich"}));
+    }
+
+    #[test]
+    fn decode_key_without_value() {
+        let value = FormCodec.decode(b"flag&x=1").unwrap();
+        assert_eq!(value, json!({"flag": "", "x": "1"}));
+    }
+
+    #[test]
+    fn decode_collects_repeated_keys() {
+        let value = FormCodec.decode(b"tag=a&tag=b&ids[]=7&x=1").unwrap();
+        assert_eq!(value, json!({"tag": ["a", "b"], "ids": ["7"], "x": "1"}));
+    }
+
+    #[test]
+    fn decode_invalid_escape() {
+        assert!(FormCodec.decode(b"a=%zz").is_err());
+        assert!(FormCodec.decode(b"a=%FF").is_err());
+    }
+
+    #[test]
+    fn encode_scalars() {
+        let bytes = FormCodec
+            .encode(&json!({"a": "x y", "b": 2, "c": true, "d": null}))
+            .unwrap();
+        assert_eq!(bytes, b"a=x+y&b=2&c=true&d=");
+    }
+
+    #[test]
+    fn encode_rejects_nested() {
+        assert!(FormCodec.encode(&json!({"a": [1]})).is_err());
+        assert!(FormCodec.encode(&json!([1])).is_err());
+    }
+}
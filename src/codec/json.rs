@@ -0,0 +1,48 @@
+//! `application/json` codec backed by `serde_json`.
+
+use serde_json::Value;
+
+use super::{Codec, CodecError};
+
+/// The JSON codec (`application/json`).
+///
+/// Registered first in [`CodecRegistry::global`](super::CodecRegistry::global), which makes
+/// it the default format for requests without `Content-Type` or `Accept` headers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl Codec for JsonCodec {
+    fn media_type(&self) -> &str {
+        "application/json"
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Value, CodecError> {
+        serde_json::from_slice(bytes).map_err(|e| CodecError::Decode(e.to_string()))
+    }
+
+    fn encode(&self, value: &Value) -> Result<Vec<u8>, CodecError> {
+        serde_json::to_vec(value).map_err(|e| CodecError::Encode(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let value = json!({"name": "ada", "tags": [1, 2]});
+        let bytes = JsonCodec.encode(&value).unwrap();
+        assert_eq!(JsonCodec.decode(&bytes).unwrap(), value);
+    }
+
+    #[test]
+    fn decode_error() {
+        assert!(matches!(
+            JsonCodec.decode(b"{nope"),
+            Err(CodecError::Decode(_))
+        ));
+    }
+}
@@ -0,0 +1,610 @@
+//! Serialization formats — content-type driven encoding and decoding of bodies.
+//!
+//! This module maps media types to [`Codec`] implementations so that request bodies are
+//! decoded according to their `Content-Type` and responses are encoded according to the
+//! client's `Accept` header.
+//!
+//! ## Core types
+//!
+//! - [`Codec`] — trait implemented by every serialization format.
+//! - [`CodecRegistry`] — ordered map of media types to codecs, with negotiation helpers.
+//! - [`Body`] — typed body wrapper that decodes from a [`Context`] and encodes into a
+//!   [`Response`] using the registry.
+//! - [`Query`] — typed query string, deserialized from a [`Context`].
+//! - [`CodecMiddleware`] — installs a custom registry into each request's extensions.
+//!
+//! Codecs translate between raw bytes and a [`serde_json::Value`] tree. This keeps the
+//! trait object-safe while still letting [`Body`] work with any `serde` type, and lets
+//! applications plug in additional formats (Avro, MessagePack, CBOR, …) by implementing a
+//! single trait instead of forking the extractor code.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use rttp::codec::{CodecRegistry, JsonCodec};
+//!
+//! let registry = CodecRegistry::new().register(JsonCodec);
+//! assert!(registry.for_content_type("application/json; charset=utf-8").is_some());
+//! assert!(registry.for_content_type("text/csv").is_none());
+//! ```
+
+use std::{
+    pin::Pin,
+    sync::{Arc, LazyLock},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{
+    Response, StatusCode,
+    context::Context,
+    http::{Mime, QualityItem, headers::parse_quality_list},
+    middleware::{Middleware, Next},
+};
+
+mod form;
+mod json;
+mod query;
+
+pub use form::FormCodec;
+pub use json::JsonCodec;
+pub use query::Query;
+
+/// Errors produced while selecting a codec or converting a body.
+#[derive(Debug, Error)]
+pub enum CodecError {
+    #[error("unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+
+    #[error("no registered format satisfies Accept: {0}")]
+    NotAcceptable(String),
+
+    #[error("failed to decode body: {0}")]
+    Decode(String),
+
+    #[error("failed to encode body: {0}")]
+    Encode(String),
+}
+
+impl CodecError {
+    /// Returns the HTTP status code that best describes this error.
+    ///
+    /// | Variant                  | Status                       |
+    /// |--------------------------|------------------------------|
+    /// | `UnsupportedMediaType`   | `415 Unsupported Media Type` |
+    /// | `NotAcceptable`          | `406 Not Acceptable`         |
+    /// | `Decode`                 | `400 Bad Request`            |
+    /// | `Encode`                 | `500 Internal Server Error`  |
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::UnsupportedMediaType(_) => StatusCode::UnsupportedMediaType,
+            Self::NotAcceptable(_) => StatusCode::NotAcceptable,
+            Self::Decode(_) => StatusCode::BadRequest,
+            Self::Encode(_) => StatusCode::InternalServerError,
+        }
+    }
+
+    /// Converts the error into a plain-text error response.
+    pub fn into_response(self) -> Response {
+        Response::new(self.status()).body(self.to_string())
+    }
+}
+
+/// A serialization format that converts between raw bytes and a [`Value`] tree.
+///
+/// Implementations must be `Send + Sync` because a single registry is shared by every
+/// request on every worker thread.
+///
+/// # Examples
+///
+/// ```rust
+/// use rttp::codec::{Codec, CodecError};
+/// use serde_json::Value;
+///
+/// /// Treats the whole body as a single UTF-8 string.
+/// struct PlainText;
+///
+/// impl Codec for PlainText {
+///     fn media_type(&self) -> &str {
+///         "text/plain"
+///     }
+///
+///     fn decode(&self, bytes: &[u8]) -> Result<Value, CodecError> {
+///         std::str::from_utf8(bytes)
+///             .map(|s| Value::String(s.to_owned()))
+///             .map_err(|e| CodecError::Decode(e.to_string()))
+///     }
+///
+///     fn encode(&self, value: &Value) -> Result<Vec<u8>, CodecError> {
+///         match value {
+///             Value::String(s) => Ok(s.clone().into_bytes()),
+///             other => Ok(other.to_string().into_bytes()),
+///         }
+///     }
+/// }
+/// ```
+pub trait Codec: Send + Sync {
+    /// The canonical media type handled by this codec, e.g. `"application/json"`.
+    ///
+    /// Used as the registry key and written as the response `Content-Type`.
+    fn media_type(&self) -> &str;
+
+    /// Decode a raw body into a [`Value`].
+    fn decode(&self, bytes: &[u8]) -> Result<Value, CodecError>;
+
+    /// Encode a [`Value`] into raw body bytes.
+    fn encode(&self, value: &Value) -> Result<Vec<u8>, CodecError>;
+
+    /// The `Content-Type` header value written on encoded responses.
+    ///
+    /// Defaults to [`media_type`](Self::media_type); override to add parameters such as
+    /// `charset`.
+    fn content_type(&self) -> String {
+        self.media_type().to_owned()
+    }
+
+    /// Whether every decoded value is text, as in HTML forms.
+    ///
+    /// [`Body`] then converts strings to the numbers and booleans `T` expects, the way
+    /// [`Query`] does. Defaults to `false`.
+    fn values_are_strings(&self) -> bool {
+        false
+    }
+}
+
+/// An ordered mapping from media types to [`Codec`] implementations.
+///
+/// The first registered codec is the default: it is used when the client sends
+/// `Accept: */*` or no `Accept` header at all.
+///
+/// Lookups ignore media type parameters (`; charset=utf-8`) and case. Structured syntax
+/// suffixes are honored as a fallback, so `application/problem+json` resolves to the codec
+/// registered for `application/json`.
+///
+/// # Examples
+///
+/// ```rust
+/// use rttp::codec::{CodecRegistry, FormCodec, JsonCodec};
+///
+/// let registry = CodecRegistry::new().register(JsonCodec).register(FormCodec);
+///
+/// let codec = registry.negotiate(Some("application/x-www-form-urlencoded;q=0.5, application/json")).unwrap();
+/// assert_eq!(codec.media_type(), "application/json");
+/// ```
+#[derive(Clone, Default)]
+pub struct CodecRegistry {
+    codecs: Vec<Arc<dyn Codec>>,
+}
+
+/// Registry used when no [`CodecMiddleware`] has installed one: JSON, then form-urlencoded.
+static DEFAULT_REGISTRY: LazyLock<Arc<CodecRegistry>> =
+    LazyLock::new(|| Arc::new(CodecRegistry::new().register(JsonCodec).register(FormCodec)));
+
+impl CodecRegistry {
+    /// Creates an empty registry.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { codecs: Vec::new() }
+    }
+
+    /// Returns the built-in registry (JSON, then `application/x-www-form-urlencoded`).
+    pub fn global() -> Arc<CodecRegistry> {
+        Arc::clone(&DEFAULT_REGISTRY)
+    }
+
+    /// Registers a codec.
+    ///
+    /// If a codec for the same media type already exists it is replaced in place, keeping
+    /// its position (and therefore its default status).
+    #[must_use]
+    pub fn register(mut self, codec: impl Codec + 'static) -> Self {
+        let codec: Arc<dyn Codec> = Arc::new(codec);
+        match self
+            .codecs
+            .iter()
+            .position(|c| c.media_type().eq_ignore_ascii_case(codec.media_type()))
+        {
+            Some(pos) => self.codecs[pos] = codec,
+            None => self.codecs.push(codec),
+        }
+        self
+    }
+
+    /// Returns the number of registered codecs.
+    pub fn len(&self) -> usize {
+        self.codecs.len()
+    }
+
+    /// Returns `true` if no codecs are registered.
+    pub fn is_empty(&self) -> bool {
+        self.codecs.is_empty()
+    }
+
+    /// Returns the default codec (the first one registered), if any.
+    pub fn default_codec(&self) -> Option<&Arc<dyn Codec>> {
+        self.codecs.first()
+    }
+
+    /// Finds the codec for a `Content-Type` header value.
+    ///
+    /// Parameters are ignored. If no exact match exists and the subtype carries a
+    /// structured suffix (`+json`, `+xml`, …), the suffix is tried as
+    /// `<type>/<suffix>`.
+    pub fn for_content_type(&self, content_type: &str) -> Option<&Arc<dyn Codec>> {
+        let mime = Mime::parse(content_type)?;
+        if let Some(codec) = self.find(mime.essence()) {
+            return Some(codec);
+        }
+        self.find(&format!("{}/{}", mime.type_(), mime.suffix()?))
+    }
+
+    /// Picks the best codec for an `Accept` header value.
+    ///
+    /// Media ranges are ordered by their `q` weight (ties keep header order); the first
+    /// range that matches a registered codec wins. Ranges with `q=0` are never selected.
+    /// A missing or empty header selects the default codec.
+    pub fn negotiate(&self, accept: Option<&str>) -> Option<&Arc<dyn Codec>> {
+        let accept = match accept.map(str::trim) {
+            None | Some("") => return self.default_codec(),
+            Some(accept) => accept,
+        };
+
+        let mut ranges = parse_quality_list(accept);
+        ranges.retain(QualityItem::is_acceptable);
+        ranges.sort_by(|a, b| b.quality.total_cmp(&a.quality));
+
+        ranges
+            .into_iter()
+            .find_map(|QualityItem { value: media, .. }| {
+                if media == "*/*" {
+                    return self.default_codec();
+                }
+                if media.ends_with("/*") {
+                    return self.codecs.iter().find(|c| {
+                        Mime::parse(c.media_type()).is_some_and(|mime| mime.matches(media))
+                    });
+                }
+                self.for_content_type(media)
+            })
+    }
+
+    // Exact (case-insensitive) media type lookup.
+    fn find(&self, media_type: &str) -> Option<&Arc<dyn Codec>> {
+        self.codecs
+            .iter()
+            .find(|c| c.media_type().eq_ignore_ascii_case(media_type))
+    }
+
+    // Returns the registry installed by `CodecMiddleware`, or the global default.
+    fn from_context(ctx: &Context) -> Arc<CodecRegistry> {
+        ctx.extensions()
+            .get::<Arc<CodecRegistry>>()
+            .cloned()
+            .unwrap_or_else(CodecRegistry::global)
+    }
+}
+
+/// A typed request or response body, converted through the [`CodecRegistry`].
+///
+/// Decoding picks the codec from the request's `Content-Type`; encoding picks it from the
+/// request's `Accept` header. The registry comes from [`CodecMiddleware`] when installed,
+/// otherwise from [`CodecRegistry::global`].
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rttp::{Response, StatusCode, codec::Body, context::Context};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Deserialize, Serialize)]
+/// struct User {
+///     name: String,
+/// }
+///
+/// async fn create_user(ctx: Context) -> Response {
+///     let Body(user) = match Body::<User>::from_context(&ctx) {
+///         Ok(body) => body,
+///         Err(e) => return e.into_response(),
+///     };
+///     Body(user).respond(&ctx, StatusCode::Created)
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Body<T>(pub T);
+
+impl<T: DeserializeOwned> Body<T> {
+    /// Decodes the request body using the codec selected by its `Content-Type`.
+    ///
+    /// A missing `Content-Type` falls back to the registry's default codec.
+    ///
+    /// # Errors
+    ///
+    /// - [`CodecError::UnsupportedMediaType`] — no codec is registered for the content type.
+    /// - [`CodecError::Decode`] — the body is malformed or does not fit `T`.
+    pub fn from_context(ctx: &Context) -> Result<Self, CodecError> {
+        let registry = CodecRegistry::from_context(ctx);
+        let content_type = ctx.request().headers().get("content-type");
+
+        let codec = match content_type {
+            Some(ct) => registry.for_content_type(ct),
+            None => registry.default_codec(),
+        }
+        .ok_or_else(|| {
+            CodecError::UnsupportedMediaType(content_type.unwrap_or("<none>").to_owned())
+        })?;
+
+        let value = codec.decode(ctx.request().body())?;
+        if codec.values_are_strings() {
+            return query::from_strings(value).map(Body);
+        }
+        serde_json::from_value(value)
+            .map(Body)
+            .map_err(|e| CodecError::Decode(e.to_string()))
+    }
+}
+
+impl<T: Serialize> Body<T> {
+    /// Encodes the body using the codec negotiated from the request's `Accept` header.
+    ///
+    /// # Errors
+    ///
+    /// - [`CodecError::NotAcceptable`] — no registered codec satisfies `Accept`.
+    /// - [`CodecError::Encode`] — `T` could not be serialized.
+    pub fn encode(&self, ctx: &Context) -> Result<Response, CodecError> {
+        let registry = CodecRegistry::from_context(ctx);
+        let accept = ctx.request().headers().get("accept");
+
+        let codec = registry
+            .negotiate(accept)
+            .ok_or_else(|| CodecError::NotAcceptable(accept.unwrap_or("<none>").to_owned()))?;
+
+        let value = serde_json::to_value(&self.0).map_err(|e| CodecError::Encode(e.to_string()))?;
+        let bytes = codec.encode(&value)?;
+
+        Ok(Response::new(StatusCode::Ok)
+            .header("Content-Type", codec.content_type())
+            .body_bytes(bytes))
+    }
+
+    /// Encodes the body with the given status, converting any [`CodecError`] into its
+    /// error response.
+    pub fn respond(&self, ctx: &Context, status: StatusCode) -> Response {
+        match self.encode(ctx) {
+            Ok(mut response) => {
+                response.set_status(status);
+                response
+            }
+            Err(e) => e.into_response(),
+        }
+    }
+}
+
+/// Middleware that makes a custom [`CodecRegistry`] available to [`Body`] for every request.
+///
+/// Without this middleware, [`Body`] uses [`CodecRegistry::global`].
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::sync::Arc;
+/// use rttp::codec::{CodecMiddleware, CodecRegistry, JsonCodec};
+/// use rttp::middleware::from_middleware;
+///
+/// let registry = CodecRegistry::new().register(JsonCodec);
+/// let handler = from_middleware(Arc::new(CodecMiddleware::new(registry)));
+/// ```
+pub struct CodecMiddleware {
+    registry: Arc<CodecRegistry>,
+}
+
+impl CodecMiddleware {
+    /// Creates a middleware that installs `registry` for downstream handlers.
+    pub fn new(registry: CodecRegistry) -> Self {
+        Self {
+            registry: Arc::new(registry),
+        }
+    }
+}
+
+impl Middleware for CodecMiddleware {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        ctx.extensions_mut().insert(Arc::clone(&self.registry));
+        Box::pin(async move { next.run(ctx).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::http::request::Request;
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct User {
+        name: String,
+    }
+
+    fn context(raw: &str) -> Context {
+        let (req, _) = Request::parse(raw.as_bytes()).unwrap();
+        Context::new(req)
+    }
+
+    fn registry() -> CodecRegistry {
+        CodecRegistry::new().register(JsonCodec).register(FormCodec)
+    }
+
+    // ── CodecRegistry ─────────────────────────────────────────────────────────
+
+    #[test]
+    fn content_type_ignores_parameters_and_case() {
+        let r = registry();
+        let codec = r
+            .for_content_type("Application/JSON; charset=utf-8")
+            .unwrap();
+        assert_eq!(codec.media_type(), "application/json");
+    }
+
+    #[test]
+    fn content_type_structured_suffix_fallback() {
+        let r = registry();
+        let codec = r.for_content_type("application/problem+json").unwrap();
+        assert_eq!(codec.media_type(), "application/json");
+    }
+
+    #[test]
+    fn content_type_unknown_returns_none() {
+        assert!(registry().for_content_type("text/csv").is_none());
+    }
+
+    #[test]
+    fn register_replaces_existing_media_type() {
+        let r = registry().register(JsonCodec);
+        assert_eq!(r.len(), 2);
+        assert_eq!(r.default_codec().unwrap().media_type(), "application/json");
+    }
+
+    #[test]
+    fn negotiate_missing_accept_uses_default() {
+        let r = registry();
+        assert_eq!(r.negotiate(None).unwrap().media_type(), "application/json");
+        assert_eq!(
+            r.negotiate(Some("")).unwrap().media_type(),
+            "application/json"
+        );
+    }
+
+    #[test]
+    fn negotiate_respects_q_values() {
+        let r = registry();
+        let codec = r
+            .negotiate(Some(
+                "application/json;q=0.2, application/x-www-form-urlencoded",
+            ))
+            .unwrap();
+        assert_eq!(codec.media_type(), "application/x-www-form-urlencoded");
+    }
+
+    #[test]
+    fn negotiate_skips_q_zero() {
+        let r = registry();
+        assert!(r.negotiate(Some("application/json;q=0")).is_none());
+    }
+
+    #[test]
+    fn negotiate_type_wildcard() {
+        let r = registry();
+        let codec = r.negotiate(Some("text/html, application/*;q=0.9")).unwrap();
+        assert_eq!(codec.media_type(), "application/json");
+    }
+
+    #[test]
+    fn negotiate_unsatisfiable_returns_none() {
+        assert!(registry().negotiate(Some("text/html")).is_none());
+    }
+
+    // ── Body ──────────────────────────────────────────────────────────────────
+
+    #[test]
+    fn body_decodes_json() {
+        let ctx = context(
+            "POST /users HTTP/1.1\r\nContent-Type: application/json\r\n\r\n{\"name\":\"ada\"}",
+        );
+        let Body(user) = Body::<User>::from_context(&ctx).unwrap();
+        assert_eq!(user.name, "ada");
+    }
+
+    #[test]
+    fn body_decodes_form() {
+        let ctx = context(
+            "POST /users HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\n\r\nname=ada+lovelace",
+        );
+        let Body(user) = Body::<User>::from_context(&ctx).unwrap();
+        assert_eq!(user.name, "ada lovelace");
+    }
+
+    #[test]
+    fn body_decodes_typed_form_fields() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Signup {
+            name: String,
+            age: u32,
+            admin: bool,
+            team: Vec<u64>,
+            invite: Option<String>,
+        }
+
+        let ctx = context(
+            "POST /users HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\n\r\nname=ada&age=36&admin=true&team=1&team=2",
+        );
+        let Body(signup) = Body::<Signup>::from_context(&ctx).unwrap();
+        assert_eq!(
+            signup,
+            Signup {
+                name: "ada".to_owned(),
+                age: 36,
+                admin: true,
+                team: vec![1, 2],
+                invite: None,
+            }
+        );
+
+        let ctx = context(
+            "POST /users HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\n\r\nname=ada&age=old&admin=true&team=1",
+        );
+        let err = Body::<Signup>::from_context(&ctx).unwrap_err();
+        assert_eq!(err.status(), StatusCode::BadRequest);
+    }
+
+    #[test]
+    fn body_unknown_content_type_is_415() {
+        let ctx = context("POST /users HTTP/1.1\r\nContent-Type: text/csv\r\n\r\nname\nada");
+        let err = Body::<User>::from_context(&ctx).unwrap_err();
+        assert_eq!(err.status(), StatusCode::UnsupportedMediaType);
+    }
+
+    #[test]
+    fn body_malformed_is_400() {
+        let ctx = context("POST /users HTTP/1.1\r\nContent-Type: application/json\r\n\r\n{");
+        let err = Body::<User>::from_context(&ctx).unwrap_err();
+        assert_eq!(err.status(), StatusCode::BadRequest);
+    }
+
+    #[test]
+    fn body_encodes_negotiated_format() {
+        let ctx =
+            context("GET /users HTTP/1.1\r\nAccept: application/x-www-form-urlencoded\r\n\r\n");
+        let res = Body(User { name: "ada".into() }).respond(&ctx, StatusCode::Created);
+        assert_eq!(res.status(), StatusCode::Created);
+        assert_eq!(
+            res.headers().get("content-type"),
+            Some("application/x-www-form-urlencoded")
+        );
+        assert_eq!(res.body_ref(), b"name=ada");
+    }
+
+    #[test]
+    fn body_not_acceptable_is_406() {
+        let ctx = context("GET /users HTTP/1.1\r\nAccept: text/html\r\n\r\n");
+        let res = Body(User { name: "ada".into() }).respond(&ctx, StatusCode::Ok);
+        assert_eq!(res.status(), StatusCode::NotAcceptable);
+    }
+
+    #[test]
+    fn body_uses_registry_from_extensions() {
+        let mut ctx = context("GET /users HTTP/1.1\r\n\r\n");
+        ctx.extensions_mut()
+            .insert(Arc::new(CodecRegistry::new().register(FormCodec)));
+        let res = Body(User { name: "ada".into() }).respond(&ctx, StatusCode::Ok);
+        assert_eq!(
+            res.headers().get("content-type"),
+            Some("application/x-www-form-urlencoded")
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Typed query strings.
+
+use serde::{
+    de::{
+        self, DeserializeOwned, Deserializer, IntoDeserializer, Visitor,
+        value::{Error, MapDeserializer, SeqDeserializer},
+    },
+    forward_to_deserialize_any,
+};
+use serde_json::Value;
+
+use super::CodecError;
+use crate::{context::Context, http::uri::decode_component};
+
+/// A request's query string, deserialized into `T`.
+///
+/// Values are percent-decoded, and strings convert to numbers and booleans as the
+/// fields require. A key that repeats, or that ends in `[]` as in `tag[]=a&tag[]=b`,
+/// fills a sequence field; a single value fills a sequence of one. For a scalar field
+/// the last value wins. Absent keys suit `Option` and `#[serde(default)]` fields.
+///
+/// # Examples
+///
+/// ```rust
+/// use rttp::codec::Query;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Search {
+///     q: String,
+///     page: Option<u32>,
+///     #[serde(default)]
+///     tag: Vec<String>,
+/// }
+///
+/// let Query(search) = Query::<Search>::parse("q=rust%20http&tag[]=web&tag[]=async").unwrap();
+/// assert_eq!(search.q, "rust http");
+/// assert_eq!(search.page, None);
+/// assert_eq!(search.tag, ["web", "async"]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query<T>(pub T);
+
+impl<T: DeserializeOwned> Query<T> {
+    /// Deserializes the request's query string; a request without one is treated as
+    /// having an empty one.
+    ///
+    /// # Errors
+    ///
+    /// [`CodecError::Decode`] if the query does not fit `T`.
+    pub fn from_context(ctx: &Context) -> Result<Self, CodecError> {
+        Self::parse(ctx.request().query_string().unwrap_or(""))
+    }
+
+    /// Deserializes a query string, without the leading `?`.
+    ///
+    /// # Errors
+    ///
+    /// [`CodecError::Decode`] if the query does not fit `T`.
+    pub fn parse(query: &str) -> Result<Self, CodecError> {
+        let mut fields: Vec<(String, Values)> = Vec::new();
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let key = decode_component(key).unwrap_or_else(|lossy| lossy);
+            let (key, array) = match key.strip_suffix("[]") {
+                Some(key) => (key.to_owned(), true),
+                None => (key, false),
+            };
+            let value = decode_component(value).unwrap_or_else(|lossy| lossy);
+            match fields.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, values)) => values.push(value),
+                None if array => fields.push((key, Values::Many(vec![value]))),
+                None => fields.push((key, Values::One(value))),
+            }
+        }
+        deserialize(fields).map(Query)
+    }
+}
+
+// Deserializes `T` from a flat object of strings and arrays of strings, such as a decoded
+// form body. Numbers and booleans are read back as their text.
+pub(super) fn from_strings<T: DeserializeOwned>(value: Value) -> Result<T, CodecError> {
+    let Value::Object(map) = value else {
+        return Err(CodecError::Decode(
+            "expected an object of fields".to_owned(),
+        ));
+    };
+    let fields = map
+        .into_iter()
+        .map(|(key, value)| {
+            let values = match value {
+                Value::Array(items) => Values::Many(
+                    items
+                        .into_iter()
+                        .map(|v| text(&key, v))
+                        .collect::<Result<_, _>>()?,
+                ),
+                other => Values::One(text(&key, other)?),
+            };
+            Ok((key, values))
+        })
+        .collect::<Result<Vec<_>, CodecError>>()?;
+    deserialize(fields)
+}
+
+fn text(key: &str, value: Value) -> Result<String, CodecError> {
+    match value {
+        Value::String(s) => Ok(s),
+        Value::Null => Ok(String::new()),
+        Value::Bool(_) | Value::Number(_) => Ok(value.to_string()),
+        Value::Array(_) | Value::Object(_) => Err(CodecError::Decode(format!(
+            "field `{key}` is not a scalar value"
+        ))),
+    }
+}
+
+fn deserialize<T: DeserializeOwned>(fields: Vec<(String, Values)>) -> Result<T, CodecError> {
+    T::deserialize(MapDeserializer::<_, Error>::new(fields.into_iter()))
+        .map_err(|e| CodecError::Decode(e.to_string()))
+}
+
+// The values given for one key.
+enum Values {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Values {
+    fn push(&mut self, value: String) {
+        match self {
+            Self::One(first) => *self = Self::Many(vec![std::mem::take(first), value]),
+            Self::Many(values) => values.push(value),
+        }
+    }
+
+    fn last(self) -> String {
+        match self {
+            Self::One(value) => value,
+            Self::Many(mut values) => values.pop().unwrap_or_default(),
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Values {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! parse_scalar {
+    ($($method:ident => $visit:ident,)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            let value = self.last();
+            match value.parse() {
+                Ok(parsed) => visitor.$visit(parsed),
+                Err(_) => Err(de::Error::invalid_value(de::Unexpected::Str(&value), &visitor)),
+            }
+        }
+    )*};
+}
+
+impl<'de> Deserializer<'de> for Values {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Self::One(value) => visitor.visit_string(value),
+            Self::Many(_) => self.deserialize_seq(visitor),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let values = match self {
+            Self::One(value) => vec![value],
+            Self::Many(values) => values,
+        };
+        visitor.visit_seq(SeqDeserializer::new(values.into_iter().map(Values::One)))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let value: de::value::StringDeserializer<Error> = self.last().into_deserializer();
+        value.deserialize_enum(name, variants, visitor)
+    }
+
+    parse_scalar! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.last())
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.last())
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 bytes byte_buf unit unit_struct tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Sort {
+        Newest,
+        Oldest,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Filters {
+        page: u32,
+        draft: bool,
+        sort: Sort,
+        #[serde(default)]
+        tag: Vec<String>,
+        ids: Vec<u64>,
+        author: Option<String>,
+    }
+
+    #[test]
+    fn collects_repeated_and_bracketed_keys() {
+        let Query(filters) =
+            Query::<Filters>::parse("page=1&page=3&draft=true&sort=oldest&tag=a&tag=b+c&ids[]=7")
+                .unwrap();
+        assert_eq!(
+            filters,
+            Filters {
+                page: 3,
+                draft: true,
+                sort: Sort::Oldest,
+                tag: vec!["a".to_owned(), "b c".to_owned()],
+                ids: vec![7],
+                author: None,
+            }
+        );
+        let Query(filters) = Query::<Filters>::parse(
+            "page=1&draft=false&sort=newest&ids=1&ids%5B%5D=2&author=%C3%A9mile",
+        )
+        .unwrap();
+        assert_eq!(filters.ids, [1, 2]);
+        assert_eq!(filters.tag, Vec::<String>::new());
+        assert_eq!(filters.author.as_deref(), Some("émile"));
+    }
+
+    #[test]
+    fn rejects_values_that_do_not_fit() {
+        let err = Query::<Filters>::parse("page=x&draft=true&sort=newest&ids=1").unwrap_err();
+        assert!(matches!(err, CodecError::Decode(_)));
+        assert!(err.to_string().contains("invalid value"), "{err}");
+        assert!(Query::<Filters>::parse("draft=true&sort=newest&ids=1").is_err());
+    }
+
+    #[test]
+    fn reads_the_request_query() {
+        let request = crate::Request::builder().path("/?q=x").build();
+        let ctx = Context::new(request);
+        let Query(map) =
+            Query::<std::collections::BTreeMap<String, String>>::from_context(&ctx).unwrap();
+        assert_eq!(map["q"], "x");
+    }
+}
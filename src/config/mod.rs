@@ -0,0 +1,928 @@
+//! Layered configuration loaded into application structs.
+//!
+//! A [`ConfigLoader`] merges layers in the order they are added — typically defaults,
+//! then a file, then environment variables — and deserializes the result into any
+//! `serde` type. Tables merge key by key, so a layer only needs the values it changes.
+//! Loading checks everything up front: a missing required file, a syntax error, a
+//! value of the wrong type, or a failed [`Validate`] check stops the application at
+//! startup instead of surfacing on the first request that reads the setting.
+//!
+//! The loaded struct is shared through [`Config`], which is also a middleware that
+//! makes it available to handlers as `ctx.config::<T>()` through [`ConfigExt`]. A
+//! config built with [`ConfigLoader::reloadable`] can be loaded again while the
+//! application runs — on demand, when its files change, or on `SIGHUP` — and handlers
+//! see the new values from their next request.
+//!
+//! # File formats
+//!
+//! The format follows the file's extension: `.toml` ([`Toml`]) and `.json` ([`Json`])
+//! are built in, and `.yaml`/`.yml` (`Yaml`) are read with the `yaml` feature. Others
+//! are added by implementing [`Format`] and registering it with
+//! [`ConfigLoader::format`].
+//!
+//! # Environment variables
+//!
+//! [`env`](ConfigLoader::env) reads variables starting with the prefix and an
+//! underscore. The rest of the name, lowercased, is the key, with `__` separating
+//! nested keys: `APP_SERVER__PORT=8080` sets `server.port`. Values that read as
+//! booleans or numbers become booleans or numbers; values starting with `[`, `{`, or
+//! `"` are parsed as JSON, so `APP_NAME='"42"'` sets a string of digits.
+//!
+//! # Examples
+//!
+//! ```
+//! use rttp::config::{ConfigLoader, Validate};
+//! use serde::Deserialize;
+//! use serde_json::json;
+//!
+//! #[derive(Debug, Deserialize)]
+//! struct Settings {
+//!     server: Server,
+//!     log_level: String,
+//! }
+//!
+//! #[derive(Debug, Deserialize)]
+//! struct Server {
+//!     host: String,
+//!     port: u16,
+//! }
+//!
+//! impl Validate for Settings {
+//!     fn validate(&self) -> Result<(), Vec<String>> {
+//!         if self.server.port == 0 {
+//!             return Err(vec!["server.port must not be 0".into()]);
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let settings: Settings = ConfigLoader::new()
+//!     .defaults(&json!({
+//!         "server": { "host": "127.0.0.1", "port": 8080 },
+//!         "log_level": "info",
+//!     }))
+//!     .optional_file("settings.toml")
+//!     .env_vars("APP", [("APP_SERVER__PORT", "9000"), ("HOME", "/root")])
+//!     .load_validated()
+//!     .unwrap();
+//!
+//! assert_eq!(settings.server.port, 9000);
+//! assert_eq!(settings.server.host, "127.0.0.1");
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Weak},
+    time::{Duration, SystemTime},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+use thiserror::Error;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::{
+    Response,
+    context::Context,
+    middleware::{Middleware, Next},
+};
+
+mod toml;
+#[cfg(feature = "yaml")]
+mod yaml;
+
+pub use self::toml::Toml;
+#[cfg(feature = "yaml")]
+pub use yaml::Yaml;
+
+/// Errors produced while loading configuration.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("failed to read config file {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("no config format is registered for {}", .0.display())]
+    UnknownFormat(PathBuf),
+
+    #[error("failed to parse config file {}: {message}", path.display())]
+    Parse { path: PathBuf, message: String },
+
+    #[error("invalid config: {0}")]
+    Deserialize(String),
+
+    #[error("invalid config: {}", .0.join("; "))]
+    Invalid(Vec<String>),
+}
+
+/// A configuration file format, parsed into a JSON tree.
+pub trait Format: Send + Sync {
+    /// Parses `source`, describing the first error (with its line, where known).
+    ///
+    /// # Errors
+    ///
+    /// A message describing why `source` is malformed.
+    fn parse(&self, source: &str) -> Result<Value, String>;
+}
+
+/// JSON, as a [`Format`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+impl Format for Json {
+    fn parse(&self, source: &str) -> Result<Value, String> {
+        serde_json::from_str(source).map_err(|e| e.to_string())
+    }
+}
+
+/// Checks a loaded configuration for problems its types cannot express.
+pub trait Validate {
+    /// Returns every problem found, each naming the setting it concerns.
+    ///
+    /// # Errors
+    ///
+    /// One message per invalid setting.
+    fn validate(&self) -> Result<(), Vec<String>>;
+}
+
+#[derive(Clone)]
+enum Source {
+    Value(Value),
+    // A value given to `defaults` that could not be serialized.
+    Invalid(String),
+    File {
+        path: PathBuf,
+        required: bool,
+    },
+    Env {
+        prefix: String,
+        // Explicit variables; `None` reads the process environment at load time.
+        vars: Option<Vec<(String, String)>>,
+    },
+}
+
+/// Builds a configuration from layers, later layers overriding earlier ones.
+///
+/// The layers are read each time the configuration is loaded, so one loader can load
+/// it again after a file changes.
+#[derive(Clone)]
+pub struct ConfigLoader {
+    sources: Vec<Source>,
+    formats: HashMap<String, Arc<dyn Format>>,
+}
+
+impl ConfigLoader {
+    /// Creates a loader with no layers that reads `.toml` and `.json` files, and
+    /// `.yaml` and `.yml` files with the `yaml` feature.
+    pub fn new() -> Self {
+        let loader = Self {
+            sources: Vec::new(),
+            formats: HashMap::new(),
+        }
+        .format("toml", Toml)
+        .format("json", Json);
+        #[cfg(feature = "yaml")]
+        let loader = loader.format("yaml", Yaml).format("yml", Yaml);
+        loader
+    }
+
+    /// Adds a layer from a serializable value, typically the defaults.
+    #[must_use]
+    pub fn defaults<T: Serialize + ?Sized>(mut self, values: &T) -> Self {
+        self.sources.push(match serde_json::to_value(values) {
+            Ok(value) => Source::Value(value),
+            Err(e) => Source::Invalid(e.to_string()),
+        });
+        self
+    }
+
+    /// Adds a layer from the file at `path`, which must exist.
+    #[must_use]
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.sources.push(Source::File {
+            path: path.into(),
+            required: true,
+        });
+        self
+    }
+
+    /// Adds a layer from the file at `path`, skipped if there is no such file.
+    #[must_use]
+    pub fn optional_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.sources.push(Source::File {
+            path: path.into(),
+            required: false,
+        });
+        self
+    }
+
+    /// Adds a layer from the process environment variables starting with `prefix_`.
+    #[must_use]
+    pub fn env(mut self, prefix: impl Into<String>) -> Self {
+        self.sources.push(Source::Env {
+            prefix: prefix.into(),
+            vars: None,
+        });
+        self
+    }
+
+    /// Adds a layer from `vars` as if they were the environment, for tests and for
+    /// variables read from elsewhere, such as a `.env` file.
+    #[must_use]
+    pub fn env_vars<K, V>(
+        mut self,
+        prefix: impl Into<String>,
+        vars: impl IntoIterator<Item = (K, V)>,
+    ) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.sources.push(Source::Env {
+            prefix: prefix.into(),
+            vars: Some(
+                vars.into_iter()
+                    .map(|(k, v)| (k.into(), v.into()))
+                    .collect(),
+            ),
+        });
+        self
+    }
+
+    /// Reads files ending in `.{extension}` with `format`.
+    #[must_use]
+    pub fn format(mut self, extension: &str, format: impl Format + 'static) -> Self {
+        self.formats
+            .insert(extension.to_ascii_lowercase(), Arc::new(format));
+        self
+    }
+
+    /// The files this loader reads, in layer order.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.sources.iter().filter_map(|source| match source {
+            Source::File { path, .. } => Some(path.as_path()),
+            _ => None,
+        })
+    }
+
+    /// Merges the layers into one JSON tree.
+    ///
+    /// # Errors
+    ///
+    /// [`ConfigError::Io`] or [`ConfigError::Parse`] for a file that cannot be read or
+    /// parsed, and [`ConfigError::UnknownFormat`] for one with an unknown extension.
+    pub fn load_value(&self) -> Result<Value, ConfigError> {
+        let mut merged = Value::Object(Map::new());
+        for source in &self.sources {
+            match source {
+                Source::Value(value) => merge(&mut merged, value.clone()),
+                Source::Invalid(message) => {
+                    return Err(ConfigError::Deserialize(message.clone()));
+                }
+                Source::File { path, required } => {
+                    if let Some(value) = self.read_file(path, *required)? {
+                        merge(&mut merged, value);
+                    }
+                }
+                Source::Env { prefix, vars } => {
+                    let vars = match vars {
+                        Some(vars) => vars.clone(),
+                        None => std::env::vars().collect(),
+                    };
+                    merge(&mut merged, env_layer(prefix, &vars));
+                }
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Loads the configuration into `T`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`load_value`](Self::load_value), and [`ConfigError::Deserialize`] if the
+    /// merged values do not fit `T`.
+    pub fn load<T: DeserializeOwned>(&self) -> Result<T, ConfigError> {
+        serde_json::from_value(self.load_value()?)
+            .map_err(|e| ConfigError::Deserialize(e.to_string()))
+    }
+
+    /// Loads the configuration into `T` and [validates](Validate) it.
+    ///
+    /// # Errors
+    ///
+    /// As for [`load`](Self::load), and [`ConfigError::Invalid`] with every problem
+    /// validation found.
+    pub fn load_validated<T: DeserializeOwned + Validate>(&self) -> Result<T, ConfigError> {
+        let config: T = self.load()?;
+        config.validate().map_err(ConfigError::Invalid)?;
+        Ok(config)
+    }
+
+    /// Loads the configuration into a [`Config`] that [reloads](Config::reload) from
+    /// these same layers.
+    ///
+    /// # Errors
+    ///
+    /// As for [`load`](Self::load).
+    pub fn reloadable<T: DeserializeOwned>(self) -> Result<Config<T>, ConfigError> {
+        let value = self.load()?;
+        Ok(Config::with_source(value, Some((self, Self::load::<T>))))
+    }
+
+    /// As [`reloadable`](Self::reloadable), [validating](Validate) the configuration
+    /// on every load; a reload that fails validation keeps the current snapshot.
+    ///
+    /// # Errors
+    ///
+    /// As for [`load_validated`](Self::load_validated).
+    pub fn reloadable_validated<T: DeserializeOwned + Validate>(
+        self,
+    ) -> Result<Config<T>, ConfigError> {
+        let value = self.load_validated()?;
+        Ok(Config::with_source(
+            value,
+            Some((self, Self::load_validated::<T>)),
+        ))
+    }
+
+    fn read_file(&self, path: &Path, required: bool) -> Result<Option<Value>, ConfigError> {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) if !required && e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => {
+                return Err(ConfigError::Io {
+                    path: path.to_owned(),
+                    source,
+                });
+            }
+        };
+        let format = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.formats.get(&ext.to_ascii_lowercase()))
+            .ok_or_else(|| ConfigError::UnknownFormat(path.to_owned()))?;
+        format
+            .parse(&source)
+            .map(Some)
+            .map_err(|message| ConfigError::Parse {
+                path: path.to_owned(),
+                message,
+            })
+    }
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ConfigLoader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfigLoader")
+            .field("files", &self.files().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+// Overlays `layer` onto `base`: tables merge key by key, anything else replaces.
+fn merge(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+// Builds the layer for the variables starting with `prefix_`.
+fn env_layer(prefix: &str, vars: &[(String, String)]) -> Value {
+    let prefix = format!("{}_", prefix.trim_end_matches('_'));
+    let mut layer = Value::Object(Map::new());
+    for (name, value) in vars {
+        let Some(key) = name.strip_prefix(&prefix) else {
+            continue;
+        };
+        let path: Vec<String> = key.split("__").map(str::to_ascii_lowercase).collect();
+        if path.iter().any(String::is_empty) {
+            continue;
+        }
+        let mut nested = env_value(value);
+        for segment in path.into_iter().rev() {
+            nested = Value::Object(Map::from_iter([(segment, nested)]));
+        }
+        merge(&mut layer, nested);
+    }
+    layer
+}
+
+fn env_value(value: &str) -> Value {
+    let trimmed = value.trim();
+    if trimmed.starts_with(['[', '{', '"']) {
+        if let Ok(json) = serde_json::from_str(trimmed) {
+            return json;
+        }
+    }
+    match trimmed {
+        "true" => return Value::Bool(true),
+        "false" => return Value::Bool(false),
+        _ => {}
+    }
+    if let Ok(integer) = trimmed.parse::<i64>() {
+        return Value::Number(integer.into());
+    }
+    if let Some(float) = trimmed
+        .parse::<f64>()
+        .ok()
+        .filter(|f| f.is_finite())
+        .and_then(serde_json::Number::from_f64)
+    {
+        return Value::Number(float);
+    }
+    Value::String(value.to_owned())
+}
+
+/// A loaded configuration, shared by every handler.
+///
+/// Handlers read the current snapshot with [`get`](Self::get); a reloadable config
+/// (see [`ConfigLoader::reloadable`]) swaps in a new snapshot atomically when it is
+/// [reloaded](Self::reload), so settings such as log levels, rate limits, or
+/// allowlists change without a restart. A request keeps the snapshot it started with.
+///
+/// Cloning is cheap; clones share the snapshot. As middleware it installs itself for
+/// [`ConfigExt::config`].
+pub struct Config<T> {
+    shared: Arc<Shared<T>>,
+}
+
+struct Shared<T> {
+    current: watch::Sender<Arc<T>>,
+    // How to load a new snapshot; `None` for a fixed value.
+    source: Option<(ConfigLoader, LoadFn<T>)>,
+}
+
+type LoadFn<T> = fn(&ConfigLoader) -> Result<T, ConfigError>;
+
+impl<T> Config<T> {
+    /// Shares a fixed `value`; [`reload`](Self::reload) leaves it unchanged.
+    pub fn new(value: T) -> Self {
+        Self::with_source(value, None)
+    }
+
+    fn with_source(value: T, source: Option<(ConfigLoader, LoadFn<T>)>) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                current: watch::Sender::new(Arc::new(value)),
+                source,
+            }),
+        }
+    }
+
+    /// Returns the current snapshot.
+    pub fn get(&self) -> Arc<T> {
+        Arc::clone(&self.shared.current.borrow())
+    }
+
+    /// Returns a receiver notified each time a new snapshot is swapped in, for
+    /// components that apply settings as they change.
+    pub fn subscribe(&self) -> watch::Receiver<Arc<T>> {
+        self.shared.current.subscribe()
+    }
+
+    /// Loads the configuration again and swaps in the new snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Whatever loading reports. The current snapshot is kept, so a bad edit to the
+    /// file leaves the application running on the last good configuration.
+    pub fn reload(&self) -> Result<(), ConfigError> {
+        if let Some((loader, load)) = &self.shared.source {
+            let value = load(loader)?;
+            self.shared.current.send_replace(Arc::new(value));
+            info!("configuration reloaded");
+        }
+        Ok(())
+    }
+}
+
+impl<T: Send + Sync + 'static> Config<T> {
+    /// Checks the configuration files every `interval` and reloads when one is
+    /// created, changed, or removed. Failed reloads are logged and the last good
+    /// snapshot kept. Checking stops once every clone of this config is dropped.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn watch_files(&self, interval: Duration) {
+        let Some((loader, _)) = &self.shared.source else {
+            return;
+        };
+        let files: Vec<PathBuf> = loader.files().map(Path::to_owned).collect();
+        let seen = modified(&files);
+        tokio::spawn(watch_files(
+            Arc::downgrade(&self.shared),
+            files,
+            seen,
+            interval,
+        ));
+    }
+
+    /// Reloads whenever the process receives `SIGHUP`. Failed reloads are logged and
+    /// the last good snapshot kept.
+    ///
+    /// Must be called within a Tokio runtime.
+    ///
+    /// # Errors
+    ///
+    /// If the signal handler cannot be installed.
+    #[cfg(unix)]
+    pub fn reload_on_sighup(&self) -> io::Result<()> {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut hangups = signal(SignalKind::hangup())?;
+        let shared = Arc::downgrade(&self.shared);
+        tokio::spawn(async move {
+            while hangups.recv().await.is_some() {
+                let Some(shared) = shared.upgrade() else {
+                    return;
+                };
+                if let Err(e) = (Config { shared }).reload() {
+                    warn!(error = %e, "configuration reload failed; keeping the previous one");
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+// The modification time of each file, or `None` for a missing one.
+fn modified(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| fs::metadata(file).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+async fn watch_files<T>(
+    shared: Weak<Shared<T>>,
+    files: Vec<PathBuf>,
+    mut seen: Vec<Option<SystemTime>>,
+    interval: Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        let now = modified(&files);
+        if now == seen {
+            continue;
+        }
+        seen = now;
+        if let Err(e) = (Config { shared }).reload() {
+            warn!(error = %e, "configuration reload failed; keeping the previous one");
+        }
+    }
+}
+
+impl<T> Clone for Config<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Config<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("current", &self.get())
+            .field("reloadable", &self.shared.source.is_some())
+            .finish()
+    }
+}
+
+impl<T: Send + Sync + 'static> Middleware for Config<T> {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        ctx.extensions_mut().insert(self.get());
+        next.run(ctx)
+    }
+}
+
+/// Adds configuration access to [`Context`].
+pub trait ConfigExt {
+    /// Returns the snapshot of the configuration of type `T` taken when the request
+    /// reached the [`Config`] middleware, if it is installed.
+    fn config<T: Send + Sync + 'static>(&self) -> Option<Arc<T>>;
+}
+
+impl ConfigExt for Context {
+    fn config<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.extensions().get::<Arc<T>>().cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Router, StatusCode, middleware::from_middleware, testing::TestClient};
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Settings {
+        name: String,
+        port: u16,
+        debug: bool,
+        allow: Vec<String>,
+        db: Db,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Db {
+        url: String,
+        pool: u32,
+    }
+
+    impl Validate for Settings {
+        fn validate(&self) -> Result<(), Vec<String>> {
+            let mut problems = Vec::new();
+            if self.port < 1024 {
+                problems.push("port must be at least 1024".to_owned());
+            }
+            if self.db.pool == 0 {
+                problems.push("db.pool must not be 0".to_owned());
+            }
+            if problems.is_empty() {
+                Ok(())
+            } else {
+                Err(problems)
+            }
+        }
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "rttp-config-{}",
+            crate::security::crypto::random_token(8)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn layers_override_in_order() {
+        let dir = temp_dir();
+        let file = dir.join("app.toml");
+        fs::write(
+            &file,
+            "name = \"from file\"\nallow = [\"a\", \"b\"]\n\n[db]\nurl = \"postgres://db\"\n",
+        )
+        .unwrap();
+
+        let loader = ConfigLoader::new()
+            .defaults(&json!({
+                "name": "default",
+                "port": 8080,
+                "debug": false,
+                "allow": [],
+                "db": { "url": "sqlite::memory:", "pool": 4 },
+            }))
+            .file(&file)
+            .optional_file(dir.join("local.toml"))
+            .env_vars(
+                "APP",
+                [
+                    ("APP_DEBUG", "true"),
+                    ("APP_DB__POOL", "16"),
+                    ("APP_NAME", "\"42\""),
+                    ("OTHER_PORT", "1"),
+                ],
+            );
+        let settings: Settings = loader.load_validated().unwrap();
+        assert_eq!(
+            settings,
+            Settings {
+                name: "42".into(),
+                port: 8080,
+                debug: true,
+                allow: vec!["a".into(), "b".into()],
+                db: Db {
+                    url: "postgres://db".into(),
+                    pool: 16,
+                },
+            }
+        );
+        assert_eq!(loader.files().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn problems_surface_at_load() {
+        let dir = temp_dir();
+        let defaults = json!({
+            "name": "x", "port": 80, "debug": false, "allow": [],
+            "db": { "url": "u", "pool": 0 },
+        });
+        let loader = || ConfigLoader::new().defaults(&defaults);
+
+        match loader().load_validated::<Settings>() {
+            Err(ConfigError::Invalid(problems)) => assert_eq!(problems.len(), 2),
+            other => panic!("{other:?}"),
+        }
+        assert!(matches!(
+            loader()
+                .env_vars("APP", [("APP_PORT", "http")])
+                .load::<Settings>(),
+            Err(ConfigError::Deserialize(_))
+        ));
+        assert!(matches!(
+            loader().file(dir.join("missing.toml")).load_value(),
+            Err(ConfigError::Io { .. })
+        ));
+
+        let broken = dir.join("broken.toml");
+        fs::write(&broken, "name = \"x\"\nport = \n").unwrap();
+        match loader().file(&broken).load_value() {
+            Err(ConfigError::Parse { message, .. }) => assert!(message.starts_with("line 2")),
+            other => panic!("{other:?}"),
+        }
+        let ini = dir.join("app.ini");
+        fs::write(&ini, "port = 1").unwrap();
+        assert!(matches!(
+            loader().file(&ini).load_value(),
+            Err(ConfigError::UnknownFormat(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parses_toml() {
+        let value = Toml
+            .parse(
+                r#"
+# A comment.
+title = "TOML \"quoted\" \u00e9"   # trailing comment
+literal = 'C:\path'
+"dotted.key" = 1
+owner.name = "Tom"
+ints = [1_000, 0x1F, -3,
+        0o17, 0b101]
+floats = [1.5, -2e3]
+mixed = [{ a = 1, b.c = true }, []]
+date = 1979-05-27T07:32:00Z
+text = """
+Roses
+are red"""
+
+[server]
+host = "0.0.0.0"
+
+[[plugins]]
+name = "a"
+
+[[plugins]]
+name = "b"
+[plugins.options]
+fast = true
+"#,
+            )
+            .unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "title": "TOML \"quoted\" é",
+                "literal": "C:\\path",
+                "dotted.key": 1,
+                "owner": { "name": "Tom" },
+                "ints": [1000, 31, -3, 15, 5],
+                "floats": [1.5, -2000.0],
+                "mixed": [{ "a": 1, "b": { "c": true } }, []],
+                "date": "1979-05-27T07:32:00Z",
+                "text": "Roses\nare red",
+                "server": { "host": "0.0.0.0" },
+                "plugins": [
+                    { "name": "a" },
+                    { "name": "b", "options": { "fast": true } },
+                ],
+            })
+        );
+
+        for bad in [
+            "a = 1\na = 2",
+            "a = ",
+            "a = \"open",
+            "a = [1 2]",
+            "[t]\n[t.x]\nt = 1 2",
+            "[a]\nx = 1\n[a]\ny = 2",
+            "x = 007",
+        ] {
+            assert!(Toml.parse(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn loads_yaml() {
+        let dir = temp_dir();
+        let file = dir.join("app.yml");
+        fs::write(&file, "server:\n  port: 8080\n  hosts: [a, b]\n").unwrap();
+        assert_eq!(
+            ConfigLoader::new().file(&file).load_value().unwrap(),
+            json!({ "server": { "port": 8080, "hosts": ["a", "b"] } })
+        );
+        match Yaml.parse("a: 1\nb: [\n") {
+            Err(message) => assert!(message.starts_with("line "), "{message}"),
+            other => panic!("{other:?}"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reloads_when_the_file_changes() {
+        #[derive(Debug, Deserialize)]
+        struct Limits {
+            rate: u32,
+        }
+
+        impl Validate for Limits {
+            fn validate(&self) -> Result<(), Vec<String>> {
+                match self.rate {
+                    0 => Err(vec!["rate must not be 0".into()]),
+                    _ => Ok(()),
+                }
+            }
+        }
+
+        let dir = temp_dir();
+        let file = dir.join("limits.toml");
+        fs::write(&file, "rate = 10").unwrap();
+        let config = ConfigLoader::new()
+            .file(&file)
+            .reloadable_validated::<Limits>()
+            .unwrap();
+        let before = config.get();
+        let mut changes = config.subscribe();
+        config.watch_files(Duration::from_millis(10));
+
+        // Bump the modification time so the change is seen on coarse filesystems.
+        let touch = |contents: &str, secs: u64| {
+            fs::write(&file, contents).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&file)
+                .unwrap()
+                .set_modified(SystemTime::now() + Duration::from_secs(secs))
+                .unwrap();
+        };
+        touch("rate = 20", 5);
+        tokio::time::timeout(Duration::from_secs(5), changes.changed())
+            .await
+            .expect("the change should be picked up")
+            .unwrap();
+        assert_eq!(config.get().rate, 20);
+        assert_eq!(before.rate, 10, "old snapshots are unaffected");
+
+        // Invalid edits keep the last good configuration.
+        touch("rate = 0", 10);
+        assert!(matches!(config.reload(), Err(ConfigError::Invalid(_))));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(config.get().rate, 20);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn handlers_read_the_shared_config() {
+        let mut router = Router::new();
+        router.get("/", |ctx: Context| async move {
+            let pool = ctx.config::<Db>().map(|db| db.pool).unwrap_or_default();
+            Response::new(StatusCode::Ok).body(pool.to_string())
+        });
+        let config = Config::new(Db {
+            url: "u".into(),
+            pool: 7,
+        });
+        let mut client = TestClient::pipeline(vec![
+            from_middleware(Arc::new(config)),
+            from_middleware(Arc::new(router)),
+        ]);
+        client.get("/").await.assert_text("7");
+    }
+}
@@ -0,0 +1,45 @@
+//! TOML configuration files, parsed with the `toml` crate.
+
+use serde_json::{Map, Number, Value};
+
+use super::Format;
+
+/// [TOML](https://toml.io), parsed into a JSON tree.
+///
+/// The whole TOML 1.0 grammar is accepted and validated, so redefined tables and keys,
+/// or malformed numbers such as `007`, are reported instead of silently accepted.
+/// Dates and times are kept as strings, in their TOML form.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Toml;
+
+impl Format for Toml {
+    fn parse(&self, source: &str) -> Result<Value, String> {
+        let table: toml::Table = toml::from_str(source).map_err(|e| {
+            let line = e.span().map_or(1, |span| line_of(source, span.start));
+            format!("line {line}: {}", e.message().trim_end())
+        })?;
+        Ok(to_json(toml::Value::Table(table)))
+    }
+}
+
+fn line_of(source: &str, pos: usize) -> usize {
+    source[..pos.min(source.len())].matches('\n').count() + 1
+}
+
+fn to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(n) => Value::from(n),
+        // JSON has no NaN or infinity; those become `null`.
+        toml::Value::Float(f) => Number::from_f64(f).map_or(Value::Null, Value::Number),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(dt) => Value::String(dt.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, to_json(value)))
+                .collect::<Map<_, _>>(),
+        ),
+    }
+}
@@ -0,0 +1,20 @@
+//! YAML configuration files, behind the `yaml` feature.
+
+use serde_json::Value;
+
+use super::Format;
+
+/// [YAML](https://yaml.org), parsed into a JSON tree.
+///
+/// Mapping keys must be strings, since the tree is merged with the other layers by key.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Yaml;
+
+impl Format for Yaml {
+    fn parse(&self, source: &str) -> Result<Value, String> {
+        serde_yaml::from_str(source).map_err(|e| match e.location() {
+            Some(location) => format!("line {}: {e}", location.line()),
+            None => e.to_string(),
+        })
+    }
+}
@@ -9,7 +9,7 @@ use std::{
     collections::HashMap,
 };
 
-use crate::Request;
+use crate::{Request, http::Interim};
 
 /// Type-erased request extensions map — used to inject per-request state
 /// into handlers without requiring handlers to know about each other's types.
@@ -151,6 +151,12 @@ impl Context {
         &self.request
     }
 
+    /// Returns the handle for sending informational responses, such as `103 Early
+    /// Hints`, before the final response.
+    pub fn interim(&self) -> &Interim {
+        self.request.interim()
+    }
+
     /// Returns a shared reference to the path parameters.
     pub fn params(&self) -> &PathParams {
         &self.params
@@ -170,6 +176,11 @@ impl Context {
     pub fn extensions_mut(&mut self) -> &mut Extensions {
         &mut self.extensions
     }
+
+    /// Consumes the context, returning its extensions map.
+    pub fn into_extensions(self) -> Extensions {
+        self.extensions
+    }
 }
 
 #[cfg(test)]
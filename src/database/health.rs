@@ -0,0 +1,266 @@
+//! Pool health checks and metrics.
+
+use std::time::Duration;
+
+use super::{Connection, DbError, Pool};
+use crate::middleware::{
+    health::{HealthCheck, HealthFuture},
+    metrics::{MetricsSource, MetricsWriter},
+};
+
+impl<C: Connection> Pool<C> {
+    /// Checks out a connection and [`ping`](Connection::ping)s it, giving up after
+    /// `timeout`.
+    ///
+    /// A connection that fails the ping is detached rather than returned to the pool.
+    ///
+    /// # Errors
+    ///
+    /// [`DbError::Timeout`] when acquiring and pinging take longer than `timeout`, or the
+    /// error from [`acquire`](Self::acquire) or the ping.
+    pub async fn check(&self, timeout: Duration) -> Result<(), DbError> {
+        let probe = async {
+            let mut conn = self.acquire().await?;
+            let result = conn.ping().await;
+            if result.is_err() {
+                drop(conn.detach());
+            }
+            result
+        };
+        tokio::time::timeout(timeout, probe)
+            .await
+            .unwrap_or(Err(DbError::Timeout))
+    }
+}
+
+/// A [`HealthCheck`] that runs [`Pool::check`].
+///
+/// The timeout defaults to 2 seconds, short enough for load-balancer probes.
+///
+/// # Examples
+///
+/// ```
+/// # use rttp::database::{Connection, DbFuture, Pool, Row, Value};
+/// # struct Conn;
+/// # impl Connection for Conn {
+/// #     fn ping(&mut self) -> DbFuture<'_, ()> { Box::pin(async { Ok(()) }) }
+/// #     fn query<'a>(&'a mut self, _: &'a str, _: &'a [Value]) -> DbFuture<'a, Vec<Row>> {
+/// #         Box::pin(async { Ok(Vec::new()) })
+/// #     }
+/// # }
+/// use rttp::database::{PoolHealthCheck, PoolMetrics};
+/// use rttp::middleware::{Health, Metrics};
+///
+/// fn instrument(pool: &Pool<Conn>, health: &Health, metrics: &Metrics) {
+///     health.register("database", PoolHealthCheck::new(pool.clone()));
+///     metrics.register(PoolMetrics::new("primary", pool.clone()));
+/// }
+/// ```
+pub struct PoolHealthCheck<C: Connection> {
+    pool: Pool<C>,
+    timeout: Duration,
+}
+
+impl<C: Connection> PoolHealthCheck<C> {
+    /// Checks `pool` with the default timeout.
+    pub fn new(pool: Pool<C>) -> Self {
+        Self {
+            pool,
+            timeout: Duration::from_secs(2),
+        }
+    }
+
+    /// Sets how long acquiring and pinging a connection may take.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl<C: Connection> HealthCheck for PoolHealthCheck<C> {
+    fn check(&self) -> HealthFuture<'_> {
+        Box::pin(async move {
+            self.pool
+                .check(self.timeout)
+                .await
+                .map_err(|e| e.to_string())
+        })
+    }
+}
+
+/// Exports a pool's [`PoolStatus`](super::PoolStatus) through
+/// [`Metrics`](crate::middleware::Metrics), labelled `pool="<name>"`.
+pub struct PoolMetrics<C: Connection> {
+    name: String,
+    pool: Pool<C>,
+}
+
+impl<C: Connection> PoolMetrics<C> {
+    /// Exports the status of `pool` under `name`.
+    pub fn new(name: impl Into<String>, pool: Pool<C>) -> Self {
+        Self {
+            name: name.into(),
+            pool,
+        }
+    }
+}
+
+impl<C: Connection> MetricsSource for PoolMetrics<C> {
+    fn collect(&self, out: &mut MetricsWriter) {
+        let status = self.pool.status();
+        let labels = [("pool", self.name.as_str())];
+        let gauges = [
+            (
+                "rttp_db_pool_connections",
+                "Open database connections.",
+                status.size,
+            ),
+            (
+                "rttp_db_pool_idle_connections",
+                "Database connections idle in the pool.",
+                status.idle,
+            ),
+            (
+                "rttp_db_pool_in_use_connections",
+                "Database connections checked out.",
+                status.in_use,
+            ),
+            (
+                "rttp_db_pool_waiters",
+                "Tasks waiting for a database connection.",
+                status.waiters,
+            ),
+            (
+                "rttp_db_pool_max_connections",
+                "Configured maximum database connections.",
+                status.max_size,
+            ),
+        ];
+        for (name, help, value) in gauges {
+            out.gauge(name, help, &labels, value as f64);
+        }
+        let counters = [
+            (
+                "rttp_db_pool_acquires_total",
+                "Successful database connection checkouts.",
+                status.acquires,
+            ),
+            (
+                "rttp_db_pool_acquire_timeouts_total",
+                "Database connection checkouts that timed out.",
+                status.timeouts,
+            ),
+            (
+                "rttp_db_pool_connections_opened_total",
+                "Database connections opened.",
+                status.connections_opened,
+            ),
+            (
+                "rttp_db_pool_connections_closed_total",
+                "Database connections closed.",
+                status.connections_closed,
+            ),
+        ];
+        for (name, help, value) in counters {
+            out.counter(name, help, &labels, value as f64);
+        }
+        out.counter(
+            "rttp_db_pool_acquire_seconds_total",
+            "Total time spent waiting for database connections.",
+            &labels,
+            status.total_acquire_time.as_secs_f64(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    };
+
+    use super::*;
+    use crate::{
+        database::{DbFuture, PoolOptions, Row, Value},
+        middleware::{Health, Metrics},
+    };
+
+    struct FlakyConn {
+        healthy: Arc<AtomicBool>,
+    }
+
+    impl Connection for FlakyConn {
+        fn ping(&mut self) -> DbFuture<'_, ()> {
+            let healthy = self.healthy.load(Ordering::SeqCst);
+            Box::pin(async move {
+                if healthy {
+                    Ok(())
+                } else {
+                    Err(DbError::Backend("connection reset".into()))
+                }
+            })
+        }
+
+        fn query<'a>(&'a mut self, _: &'a str, _: &'a [Value]) -> DbFuture<'a, Vec<Row>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+    }
+
+    async fn pool(healthy: &Arc<AtomicBool>) -> Pool<FlakyConn> {
+        let healthy = Arc::clone(healthy);
+        PoolOptions::new()
+            .max_size(1)
+            .test_on_checkout(false)
+            .connect(move || {
+                let healthy = Arc::clone(&healthy);
+                async move { Ok(FlakyConn { healthy }) }
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn health_check_pings_with_timeout() {
+        let healthy = Arc::new(AtomicBool::new(true));
+        let pool = pool(&healthy).await;
+        let health = Health::new();
+        health.register(
+            "db",
+            PoolHealthCheck::new(pool.clone()).timeout(Duration::from_millis(20)),
+        );
+        assert!(health.check().await.is_healthy());
+
+        healthy.store(false, Ordering::SeqCst);
+        let report = health.check().await;
+        assert_eq!(
+            report.checks[0].error.as_deref(),
+            Some("database error: connection reset")
+        );
+        assert_eq!(pool.status().size, 0, "failed connection detached");
+
+        healthy.store(true, Ordering::SeqCst);
+        let _held = pool.acquire().await.unwrap();
+        assert!(matches!(
+            pool.check(Duration::from_millis(20)).await,
+            Err(DbError::Timeout)
+        ));
+    }
+
+    #[tokio::test]
+    async fn metrics_export_pool_status() {
+        let healthy = Arc::new(AtomicBool::new(true));
+        let pool = pool(&healthy).await;
+        let metrics = Metrics::new();
+        metrics.register(PoolMetrics::new("primary", pool.clone()));
+
+        let _conn = pool.acquire().await.unwrap();
+        let text = metrics.render();
+        assert!(text.contains("rttp_db_pool_connections{pool=\"primary\"} 1\n"));
+        assert!(text.contains("rttp_db_pool_in_use_connections{pool=\"primary\"} 1\n"));
+        assert!(text.contains("rttp_db_pool_idle_connections{pool=\"primary\"} 0\n"));
+        assert!(text.contains("rttp_db_pool_acquires_total{pool=\"primary\"} 1\n"));
+        assert!(text.contains("# TYPE rttp_db_pool_waiters gauge\n"));
+    }
+}
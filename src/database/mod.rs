@@ -1,16 +1,161 @@
 //! Database layer — connection pooling and query building.
 //!
+//! ## Implemented
+//!
+//! - [`Pool`] — generic async connection pool over any [`Connection`] type, configured
+//!   with [`PoolOptions`]: min/max size, acquire timeout, idle reaping, connection
+//!   lifetime, and health checks on checkout. [`Pool::status`] exposes the gauges and
+//!   counters metrics exporters need.
+//! - Typed queries — [`query_as`] decodes result [`Row`]s into any [`FromRow`] type,
+//!   usually via `#[derive(FromRow)]`, with [`DbError::ColumnNotFound`] and
+//!   [`DbError::Decode`] naming the offending column.
+//! - [`Transaction`] and [`TransactionMiddleware`] — one transaction per request,
+//!   committed on `2xx`/`3xx` responses and rolled back otherwise.
+//! - [`PoolHealthCheck`] and [`PoolMetrics`] — plug a pool into the
+//!   [`Health`](crate::middleware::Health) endpoint and the
+//!   [`Metrics`](crate::middleware::Metrics) registry.
+//! - [`ReplicaSet`] — a primary plus read replicas, with `read()`/`write()` routing,
+//!   optional routing of safe-method requests to replicas, and failover away from
+//!   replicas that stop answering.
+//! - [`StatementCache`] — wraps a [`Prepare`] connection and reuses its prepared
+//!   statements by SQL text, within an LRU bound.
+//!
 //! ## Planned Features
 //!
-//! - Async connection pool (backed by `deadpool` or `bb8`)
 //! - PostgreSQL support via `tokio-postgres`
 //! - SQLite support via `rusqlite` with async wrapper
 //! - Migration runner
 //! - Query builder DSL
 //!
-//! ## Status: PLANNED
+//! ## Status: IN PROGRESS
+//!
+//! # Examples
+//!
+//! ```
+//! use std::{sync::Arc, time::Duration};
+//! use rttp::database::{Connection, DbFuture, FromRow, PoolOptions, Row, Value, query_as};
+//!
+//! struct Conn; // a driver connection
+//!
+//! impl Connection for Conn {
+//!     fn ping(&mut self) -> DbFuture<'_, ()> {
+//!         Box::pin(async { Ok(()) }) // e.g. `SELECT 1`
+//!     }
+//!
+//!     fn query<'a>(&'a mut self, sql: &'a str, params: &'a [Value]) -> DbFuture<'a, Vec<Row>> {
+//!         Box::pin(async move {
+//!             let columns = Arc::from(["id".to_owned(), "name".to_owned()]);
+//!             Ok(vec![Row::new(columns, vec![params[0].clone(), "ada".into()])])
+//!         })
+//!     }
+//! }
+//!
+//! #[derive(FromRow)]
+//! struct User {
+//!     id: i64,
+//!     name: String,
+//! }
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), rttp::database::DbError> {
+//! let pool = PoolOptions::new()
+//!     .min_size(1)
+//!     .max_size(8)
+//!     .acquire_timeout(Duration::from_secs(5))
+//!     .connect(|| async { Ok(Conn) })
+//!     .await?;
+//!
+//! let mut conn = pool.acquire().await?;
+//! assert_eq!(pool.status().in_use, 1);
+//! let user = query_as::<User>("SELECT id, name FROM users WHERE id = $1")
+//!     .bind(7)
+//!     .fetch_one(&mut conn)
+//!     .await?;
+//! assert_eq!((user.id, user.name.as_str()), (7, "ada"));
+//! drop(conn); // returned to the pool
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod health;
+pub mod pool;
+pub mod query;
+pub mod replica;
+pub mod row;
+pub mod statement;
+pub mod transaction;
+
+pub use health::{PoolHealthCheck, PoolMetrics};
+pub use pool::{Connect, Pool, PoolOptions, PoolStatus, PooledConnection};
+pub use query::{QueryAs, query, query_as};
+pub use replica::ReplicaSet;
+pub use row::{ColumnIndex, FromRow, FromValue, Row, Value};
+/// Derives [`FromRow`] for a struct; see [`row`] for the column mapping.
+pub use rttp_macros::FromRow;
+pub use statement::{Prepare, StatementCache, StatementCacheStats};
+pub use transaction::{Transaction, TransactionExt, TransactionGuard, TransactionMiddleware};
+
+use std::{future::Future, pin::Pin};
+
+use thiserror::Error;
+
+/// Errors produced by the database layer.
+#[derive(Debug, Error)]
+pub enum DbError {
+    #[error("failed to connect to the database: {0}")]
+    Connect(String),
+
+    #[error("timed out waiting for a database connection")]
+    Timeout,
+
+    #[error("the connection pool is closed")]
+    Closed,
+
+    #[error("database error: {0}")]
+    Backend(String),
+
+    #[error("query returned no rows")]
+    RowNotFound,
+
+    #[error("no column `{0}` in the result row")]
+    ColumnNotFound(String),
+
+    #[error("failed to decode column `{column}`: {message}")]
+    Decode { column: String, message: String },
+
+    #[error("the transaction has already been committed or rolled back")]
+    TransactionFinished,
+}
+
+/// Boxed future returned by database traits.
+pub type DbFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, DbError>> + Send + 'a>>;
+
+/// A driver connection that can be pooled.
+///
+/// Methods return boxed futures so the trait stays object-safe, like
+/// [`Cache`](crate::cache::Cache).
+pub trait Connection: Send + 'static {
+    /// Checks that the connection is still usable, typically with a trivial query.
+    ///
+    /// Called on checkout when [`PoolOptions::test_on_checkout`] is enabled; a failing
+    /// connection is closed and another one is tried.
+    fn ping(&mut self) -> DbFuture<'_, ()>;
+
+    /// Runs `sql` with positional `params` and returns every result row.
+    fn query<'a>(&'a mut self, sql: &'a str, params: &'a [Value]) -> DbFuture<'a, Vec<Row>>;
+
+    /// Starts a transaction. Defaults to running `BEGIN`.
+    fn begin(&mut self) -> DbFuture<'_, ()> {
+        Box::pin(async move { self.query("BEGIN", &[]).await.map(drop) })
+    }
 
-// TODO: Implement database layer
+    /// Commits the current transaction. Defaults to running `COMMIT`.
+    fn commit(&mut self) -> DbFuture<'_, ()> {
+        Box::pin(async move { self.query("COMMIT", &[]).await.map(drop) })
+    }
 
-/// Placeholder — will become the `Database` connection pool type.
-pub struct Database;
+    /// Rolls back the current transaction. Defaults to running `ROLLBACK`.
+    fn rollback(&mut self) -> DbFuture<'_, ()> {
+        Box::pin(async move { self.query("ROLLBACK", &[]).await.map(drop) })
+    }
+}
@@ -0,0 +1,826 @@
+//! Generic async connection pool.
+//!
+//! A [`Pool`] hands out at most `max_size` connections at a time. Checkout order is
+//! last-in first-out so a quiet pool keeps reusing a few warm connections and lets the
+//! rest go idle long enough to be reaped.
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{
+        Arc, Mutex, MutexGuard, Weak,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::Instant,
+};
+use tracing::warn;
+
+use super::{Connection, DbError, DbFuture, Row, Value};
+
+/// Opens new connections for a [`Pool`].
+///
+/// Implemented for any `Fn() -> impl Future<Output = Result<C, DbError>>` closure.
+pub trait Connect<C>: Send + Sync + 'static {
+    /// Opens one connection.
+    fn connect(&self) -> DbFuture<'static, C>;
+}
+
+impl<C, F, Fut> Connect<C> for F
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<C, DbError>> + Send + 'static,
+{
+    fn connect(&self) -> DbFuture<'static, C> {
+        Box::pin(self())
+    }
+}
+
+/// Configuration for a [`Pool`].
+///
+/// Defaults: no minimum, at most 10 connections, a 30-second acquire timeout, idle
+/// connections closed after 10 minutes, connections replaced after 30 minutes, and a
+/// [`ping`](Connection::ping) on every checkout.
+#[derive(Debug, Clone)]
+pub struct PoolOptions {
+    min_size: usize,
+    max_size: usize,
+    acquire_timeout: Duration,
+    idle_timeout: Option<Duration>,
+    max_lifetime: Option<Duration>,
+    test_on_checkout: bool,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            min_size: 0,
+            max_size: 10,
+            acquire_timeout: Duration::from_secs(30),
+            idle_timeout: Some(Duration::from_secs(10 * 60)),
+            max_lifetime: Some(Duration::from_secs(30 * 60)),
+            test_on_checkout: true,
+        }
+    }
+}
+
+impl PoolOptions {
+    /// Creates the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how many connections are kept open even when idle. They are opened by
+    /// [`connect`](Self::connect) and replaced in the background when closed.
+    #[must_use]
+    pub fn min_size(mut self, size: usize) -> Self {
+        self.min_size = size;
+        self
+    }
+
+    /// Sets the most connections open at once.
+    #[must_use]
+    pub fn max_size(mut self, size: usize) -> Self {
+        self.max_size = size;
+        self
+    }
+
+    /// Sets how long [`Pool::acquire`] waits, including opening a new connection,
+    /// before failing with [`DbError::Timeout`].
+    #[must_use]
+    pub fn acquire_timeout(mut self, timeout: Duration) -> Self {
+        self.acquire_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a connection may sit idle before it is closed (never below
+    /// `min_size`). `None` keeps idle connections open.
+    #[must_use]
+    pub fn idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a connection is used before it is closed and replaced. `None`
+    /// lets connections live forever.
+    #[must_use]
+    pub fn max_lifetime(mut self, lifetime: Option<Duration>) -> Self {
+        self.max_lifetime = lifetime;
+        self
+    }
+
+    /// Whether idle connections are [`ping`](Connection::ping)ed before being handed
+    /// out (default `true`).
+    #[must_use]
+    pub fn test_on_checkout(mut self, test: bool) -> Self {
+        self.test_on_checkout = test;
+        self
+    }
+
+    /// Builds the pool, opening `min_size` connections before returning.
+    ///
+    /// Spawns the background task that reaps idle and expired connections, so this must
+    /// be called within a Tokio runtime.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error from opening the initial connections.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_size` is zero or smaller than `min_size`.
+    pub async fn connect<C, F>(self, connect: F) -> Result<Pool<C>, DbError>
+    where
+        C: Connection,
+        F: Connect<C>,
+    {
+        assert!(self.max_size > 0, "pool max_size must be non-zero");
+        assert!(
+            self.min_size <= self.max_size,
+            "pool min_size must not exceed max_size"
+        );
+        let shared = Arc::new(Shared {
+            permits: Arc::new(Semaphore::new(self.max_size)),
+            options: self,
+            connect: Box::new(connect),
+            state: Mutex::new(State {
+                idle: Vec::new(),
+                size: 0,
+                closed: false,
+            }),
+            counters: Counters::default(),
+        });
+        shared.replenish().await?;
+        if let Some(interval) = shared.options.reap_interval() {
+            tokio::spawn(reap(Arc::downgrade(&shared), interval));
+        }
+        Ok(Pool { shared })
+    }
+
+    // How often the reaper runs: often enough to honor the shortest configured limit.
+    fn reap_interval(&self) -> Option<Duration> {
+        let limits = [self.idle_timeout, self.max_lifetime];
+        let shortest = limits.into_iter().flatten().min();
+        match shortest {
+            Some(limit) => {
+                Some((limit / 4).clamp(Duration::from_millis(5), Duration::from_secs(30)))
+            }
+            None if self.min_size > 0 => Some(Duration::from_secs(30)),
+            None => None,
+        }
+    }
+}
+
+/// A point-in-time view of a [`Pool`], for health checks and metrics.
+///
+/// `size`, `idle`, `in_use`, and `waiters` are gauges; the remaining fields are counters
+/// since the pool was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStatus {
+    /// Open connections, including ones being opened.
+    pub size: usize,
+    /// Open connections waiting in the pool.
+    pub idle: usize,
+    /// Connections currently checked out.
+    pub in_use: usize,
+    /// Tasks waiting in [`Pool::acquire`] for a free slot.
+    pub waiters: usize,
+    /// The configured maximum size.
+    pub max_size: usize,
+    /// Successful [`Pool::acquire`] calls.
+    pub acquires: u64,
+    /// [`Pool::acquire`] calls that failed with [`DbError::Timeout`].
+    pub timeouts: u64,
+    /// Total time successful acquires spent waiting.
+    pub total_acquire_time: Duration,
+    /// Connections opened.
+    pub connections_opened: u64,
+    /// Connections closed: failed health checks, expired, reaped, or detached.
+    pub connections_closed: u64,
+}
+
+/// An async pool of `C` connections. Cloning is cheap and clones share the pool.
+///
+/// Build one with [`PoolOptions::connect`].
+pub struct Pool<C: Connection> {
+    shared: Arc<Shared<C>>,
+}
+
+impl<C: Connection> Clone for Pool<C> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+struct Shared<C> {
+    options: PoolOptions,
+    connect: Box<dyn Connect<C>>,
+    // One permit per connection that may be checked out.
+    permits: Arc<Semaphore>,
+    state: Mutex<State<C>>,
+    counters: Counters,
+}
+
+struct State<C> {
+    // Most recently returned last.
+    idle: Vec<Idle<C>>,
+    size: usize,
+    closed: bool,
+}
+
+struct Idle<C> {
+    conn: C,
+    created: Instant,
+    since: Instant,
+}
+
+#[derive(Default)]
+struct Counters {
+    waiters: AtomicUsize,
+    acquires: AtomicU64,
+    timeouts: AtomicU64,
+    acquire_nanos: AtomicU64,
+    opened: AtomicU64,
+    closed: AtomicU64,
+}
+
+impl<C: Connection> Pool<C> {
+    /// Checks out a connection, waiting up to the acquire timeout for one to free up.
+    ///
+    /// Idle connections past their lifetime or failing the checkout
+    /// [`ping`](Connection::ping) are closed and skipped. When none is idle and the pool
+    /// is below `max_size`, a new connection is opened.
+    ///
+    /// # Errors
+    ///
+    /// [`DbError::Timeout`] when no connection is available in time,
+    /// [`DbError::Closed`] after [`close`](Self::close), or the error from opening a
+    /// connection.
+    pub async fn acquire(&self) -> Result<PooledConnection<C>, DbError> {
+        let start = Instant::now();
+        let counters = &self.shared.counters;
+        let result =
+            match tokio::time::timeout(self.shared.options.acquire_timeout, self.shared.acquire())
+                .await
+            {
+                Ok(result) => result,
+                Err(_) => {
+                    counters.timeouts.fetch_add(1, Ordering::Relaxed);
+                    Err(DbError::Timeout)
+                }
+            };
+        if result.is_ok() {
+            let nanos = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+            counters.acquires.fetch_add(1, Ordering::Relaxed);
+            counters.acquire_nanos.fetch_add(nanos, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Returns the current gauges and counters.
+    pub fn status(&self) -> PoolStatus {
+        let (size, idle) = {
+            let state = self.shared.state();
+            (state.size, state.idle.len())
+        };
+        let counters = &self.shared.counters;
+        PoolStatus {
+            size,
+            idle,
+            in_use: size - idle,
+            waiters: counters.waiters.load(Ordering::Relaxed),
+            max_size: self.shared.options.max_size,
+            acquires: counters.acquires.load(Ordering::Relaxed),
+            timeouts: counters.timeouts.load(Ordering::Relaxed),
+            total_acquire_time: Duration::from_nanos(
+                counters.acquire_nanos.load(Ordering::Relaxed),
+            ),
+            connections_opened: counters.opened.load(Ordering::Relaxed),
+            connections_closed: counters.closed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the pool's configuration.
+    pub fn options(&self) -> &PoolOptions {
+        &self.shared.options
+    }
+
+    /// Closes the pool: idle connections are closed now, checked-out ones when they are
+    /// returned, and every pending and future [`acquire`](Self::acquire) fails with
+    /// [`DbError::Closed`].
+    pub fn close(&self) {
+        let idle = {
+            let mut state = self.shared.state();
+            state.closed = true;
+            let idle = std::mem::take(&mut state.idle);
+            state.size -= idle.len();
+            idle
+        };
+        self.shared
+            .counters
+            .closed
+            .fetch_add(idle.len() as u64, Ordering::Relaxed);
+        self.shared.permits.close();
+    }
+
+    /// Returns `true` once [`close`](Self::close) has been called.
+    pub fn is_closed(&self) -> bool {
+        self.shared.state().closed
+    }
+}
+
+impl<C: Connection> Shared<C> {
+    async fn acquire(self: &Arc<Self>) -> Result<PooledConnection<C>, DbError> {
+        let permit = {
+            let _waiting = Waiting::new(&self.counters.waiters);
+            Arc::clone(&self.permits)
+                .acquire_owned()
+                .await
+                .map_err(|_| DbError::Closed)?
+        };
+
+        loop {
+            let idle = {
+                let mut state = self.state();
+                if state.closed {
+                    return Err(DbError::Closed);
+                }
+                state.idle.pop()
+            };
+            let Some(idle) = idle else {
+                break;
+            };
+            // Closes the connection if this future is dropped mid-ping.
+            let slot = Slot::new(self);
+            let mut conn = idle.conn;
+            if self.expired(idle.created, Instant::now()) {
+                continue;
+            }
+            if self.options.test_on_checkout {
+                if let Err(e) = conn.ping().await {
+                    warn!(error = %e, "closing pooled connection that failed its health check");
+                    continue;
+                }
+            }
+            slot.keep();
+            return Ok(PooledConnection::new(conn, idle.created, self, permit));
+        }
+
+        let slot = {
+            self.state().size += 1;
+            Slot::new(self)
+        };
+        let conn = self.connect.connect().await?;
+        slot.keep();
+        self.counters.opened.fetch_add(1, Ordering::Relaxed);
+        Ok(PooledConnection::new(conn, Instant::now(), self, permit))
+    }
+
+    fn release(&self, conn: C, created: Instant) {
+        let now = Instant::now();
+        let mut state = self.state();
+        if state.closed || self.expired(created, now) {
+            drop(state);
+            self.discard();
+            drop(conn);
+            return;
+        }
+        state.idle.push(Idle {
+            conn,
+            created,
+            since: now,
+        });
+    }
+
+    fn expired(&self, created: Instant, now: Instant) -> bool {
+        self.options
+            .max_lifetime
+            .is_some_and(|lifetime| now.duration_since(created) >= lifetime)
+    }
+
+    // Closes idle connections past their idle timeout or lifetime, keeping `min_size`
+    // open for the idle timeout (expired ones are always closed).
+    fn reap(&self) {
+        let now = Instant::now();
+        let removed = {
+            let mut state = self.state();
+            let mut removed = Vec::new();
+            let mut index = 0;
+            while index < state.idle.len() {
+                let idle = &state.idle[index];
+                let stale = self
+                    .options
+                    .idle_timeout
+                    .is_some_and(|timeout| now.duration_since(idle.since) >= timeout)
+                    && state.size > self.options.min_size;
+                if stale || self.expired(idle.created, now) {
+                    removed.push(state.idle.remove(index));
+                    state.size -= 1;
+                } else {
+                    index += 1;
+                }
+            }
+            removed
+        };
+        self.counters
+            .closed
+            .fetch_add(removed.len() as u64, Ordering::Relaxed);
+    }
+
+    // Opens idle connections until the pool holds `min_size`.
+    async fn replenish(&self) -> Result<(), DbError> {
+        loop {
+            {
+                let mut state = self.state();
+                if state.closed || state.size >= self.options.min_size {
+                    return Ok(());
+                }
+                state.size += 1;
+            }
+            match self.connect.connect().await {
+                Ok(conn) => {
+                    self.counters.opened.fetch_add(1, Ordering::Relaxed);
+                    let now = Instant::now();
+                    self.state().idle.push(Idle {
+                        conn,
+                        created: now,
+                        since: now,
+                    });
+                }
+                Err(e) => {
+                    self.state().size -= 1;
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+impl<C> Shared<C> {
+    // Forgets one counted connection.
+    fn discard(&self) {
+        self.state().size -= 1;
+        self.counters.closed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn state(&self) -> MutexGuard<'_, State<C>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+async fn reap<C: Connection>(shared: Weak<Shared<C>>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        if shared.state().closed {
+            return;
+        }
+        shared.reap();
+        if let Err(e) = shared.replenish().await {
+            warn!(error = %e, "failed to restore the pool's minimum connections");
+        }
+    }
+}
+
+// Counts a task as waiting for a permit until dropped.
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl<'a> Waiting<'a> {
+    fn new(waiters: &'a AtomicUsize) -> Self {
+        waiters.fetch_add(1, Ordering::Relaxed);
+        Self(waiters)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// A counted connection slot that is given back (size decremented, counted as closed)
+// unless `keep` is called, so errors and cancellation never leak pool capacity.
+struct Slot<'a, C> {
+    shared: Option<&'a Shared<C>>,
+}
+
+impl<'a, C> Slot<'a, C> {
+    fn new(shared: &'a Shared<C>) -> Self {
+        Self {
+            shared: Some(shared),
+        }
+    }
+
+    fn keep(mut self) {
+        self.shared = None;
+    }
+}
+
+impl<C> Drop for Slot<'_, C> {
+    fn drop(&mut self) {
+        if let Some(shared) = self.shared {
+            shared.discard();
+        }
+    }
+}
+
+/// A connection checked out of a [`Pool`]; derefs to `C`.
+///
+/// Dropping it returns the connection to the pool. Call [`detach`](Self::detach)
+/// instead for a connection left in an unknown state, so it is not reused.
+pub struct PooledConnection<C: Connection> {
+    conn: Option<C>,
+    created: Instant,
+    shared: Arc<Shared<C>>,
+    // Released after the connection is back in the pool (fields drop after `drop`).
+    _permit: OwnedSemaphorePermit,
+}
+
+impl<C: Connection> PooledConnection<C> {
+    fn new(
+        conn: C,
+        created: Instant,
+        shared: &Arc<Shared<C>>,
+        permit: OwnedSemaphorePermit,
+    ) -> Self {
+        Self {
+            conn: Some(conn),
+            created,
+            shared: Arc::clone(shared),
+            _permit: permit,
+        }
+    }
+
+    /// Removes the connection from the pool, freeing its slot for a new one.
+    pub fn detach(mut self) -> C {
+        let conn = self.conn.take().expect("connection present until drop");
+        self.shared.discard();
+        conn
+    }
+}
+
+impl<C: Connection> Deref for PooledConnection<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        self.conn.as_ref().expect("connection present until drop")
+    }
+}
+
+impl<C: Connection> DerefMut for PooledConnection<C> {
+    fn deref_mut(&mut self) -> &mut C {
+        self.conn.as_mut().expect("connection present until drop")
+    }
+}
+
+impl<C: Connection> Connection for PooledConnection<C> {
+    fn ping(&mut self) -> DbFuture<'_, ()> {
+        (**self).ping()
+    }
+
+    fn query<'a>(&'a mut self, sql: &'a str, params: &'a [Value]) -> DbFuture<'a, Vec<Row>> {
+        (**self).query(sql, params)
+    }
+
+    fn begin(&mut self) -> DbFuture<'_, ()> {
+        (**self).begin()
+    }
+
+    fn commit(&mut self) -> DbFuture<'_, ()> {
+        (**self).commit()
+    }
+
+    fn rollback(&mut self) -> DbFuture<'_, ()> {
+        (**self).rollback()
+    }
+}
+
+impl<C: Connection> Drop for PooledConnection<C> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.shared.release(conn, self.created);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use super::*;
+
+    struct TestConn {
+        id: usize,
+        healthy: Arc<AtomicBool>,
+    }
+
+    impl Connection for TestConn {
+        fn ping(&mut self) -> DbFuture<'_, ()> {
+            let healthy = self.healthy.load(Ordering::SeqCst);
+            Box::pin(async move {
+                if healthy {
+                    Ok(())
+                } else {
+                    Err(DbError::Backend("connection reset".into()))
+                }
+            })
+        }
+
+        fn query<'a>(&'a mut self, _: &'a str, _: &'a [Value]) -> DbFuture<'a, Vec<Row>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+    }
+
+    /// A connector handing out numbered connections that share one health flag.
+    #[derive(Clone, Default)]
+    struct Server {
+        opened: Arc<AtomicUsize>,
+        healthy: Arc<AtomicBool>,
+        down: Arc<AtomicBool>,
+    }
+
+    impl Server {
+        fn new() -> Self {
+            let server = Self::default();
+            server.healthy.store(true, Ordering::SeqCst);
+            server
+        }
+
+        fn connector(&self) -> impl Connect<TestConn> {
+            let server = self.clone();
+            move || {
+                let server = server.clone();
+                async move {
+                    if server.down.load(Ordering::SeqCst) {
+                        return Err(DbError::Connect("refused".into()));
+                    }
+                    Ok(TestConn {
+                        id: server.opened.fetch_add(1, Ordering::SeqCst) + 1,
+                        healthy: Arc::clone(&server.healthy),
+                    })
+                }
+            }
+        }
+    }
+
+    async fn pool(server: &Server, options: PoolOptions) -> Pool<TestConn> {
+        options.connect(server.connector()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn reuses_returned_connections() {
+        let server = Server::new();
+        let pool = pool(&server, PoolOptions::new()).await;
+
+        let a = pool.acquire().await.unwrap();
+        let b = pool.acquire().await.unwrap();
+        assert_eq!((a.id, b.id), (1, 2));
+        assert_eq!(pool.status().in_use, 2);
+        drop(b);
+        drop(a);
+
+        // LIFO: the most recently returned connection comes back first.
+        assert_eq!(pool.acquire().await.unwrap().id, 1);
+        let status = pool.status();
+        assert_eq!((status.size, status.idle, status.in_use), (2, 2, 0));
+        assert_eq!((status.acquires, status.connections_opened), (3, 2));
+    }
+
+    #[tokio::test]
+    async fn acquire_waits_then_times_out() {
+        let server = Server::new();
+        let options = PoolOptions::new()
+            .max_size(1)
+            .acquire_timeout(Duration::from_millis(50));
+        let pool = pool(&server, options).await;
+
+        let held = pool.acquire().await.unwrap();
+        let waiter = {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.acquire().await.map(|c| c.id) })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(pool.status().waiters, 1);
+        drop(held);
+        assert_eq!(waiter.await.unwrap().unwrap(), 1);
+
+        let _held = pool.acquire().await.unwrap();
+        assert!(matches!(pool.acquire().await, Err(DbError::Timeout)));
+        let status = pool.status();
+        assert_eq!((status.timeouts, status.waiters, status.size), (1, 0, 1));
+    }
+
+    #[tokio::test]
+    async fn unhealthy_connections_are_replaced_on_checkout() {
+        let server = Server::new();
+        let pool = pool(&server, PoolOptions::new()).await;
+        drop(pool.acquire().await.unwrap());
+
+        server.healthy.store(false, Ordering::SeqCst);
+        let conn = pool.acquire().await.unwrap();
+        assert_eq!(conn.id, 2);
+        let status = pool.status();
+        assert_eq!((status.size, status.connections_closed), (1, 1));
+
+        drop(conn);
+        let pool = PoolOptions::new()
+            .test_on_checkout(false)
+            .connect(server.connector())
+            .await
+            .unwrap();
+        drop(pool.acquire().await.unwrap());
+        assert_eq!(pool.acquire().await.unwrap().id, 3, "not pinged, so reused");
+    }
+
+    #[tokio::test]
+    async fn connect_errors_do_not_leak_capacity() {
+        let server = Server::new();
+        server.down.store(true, Ordering::SeqCst);
+        let pool = pool(&server, PoolOptions::new().max_size(1)).await;
+
+        assert!(matches!(pool.acquire().await, Err(DbError::Connect(_))));
+        assert_eq!(pool.status().size, 0);
+        server.down.store(false, Ordering::SeqCst);
+        assert!(pool.acquire().await.is_ok());
+
+        server.down.store(true, Ordering::SeqCst);
+        let result = PoolOptions::new()
+            .min_size(1)
+            .connect(server.connector())
+            .await;
+        assert!(matches!(result, Err(DbError::Connect(_))));
+    }
+
+    #[tokio::test]
+    async fn min_size_is_opened_and_idle_excess_reaped() {
+        let server = Server::new();
+        let options = PoolOptions::new()
+            .min_size(1)
+            .idle_timeout(Some(Duration::from_millis(40)));
+        let pool = pool(&server, options).await;
+        assert_eq!(pool.status().idle, 1);
+
+        let conns: Vec<_> = acquire_many(&pool, 3).await;
+        assert_eq!(pool.status().size, 3);
+        drop(conns);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let status = pool.status();
+        assert_eq!((status.size, status.idle), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn expired_connections_are_replaced() {
+        let server = Server::new();
+        let options = PoolOptions::new().max_lifetime(Some(Duration::from_millis(30)));
+        let pool = pool(&server, options).await;
+
+        let conn = pool.acquire().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        drop(conn); // past its lifetime: closed instead of returned
+        assert_eq!(pool.status().size, 0);
+        assert_eq!(pool.acquire().await.unwrap().id, 2);
+    }
+
+    #[tokio::test]
+    async fn detach_frees_the_slot() {
+        let server = Server::new();
+        let pool = pool(&server, PoolOptions::new().max_size(1)).await;
+        let conn = pool.acquire().await.unwrap().detach();
+        assert_eq!(conn.id, 1);
+        assert_eq!(pool.status().size, 0);
+        assert_eq!(pool.acquire().await.unwrap().id, 2);
+    }
+
+    #[tokio::test]
+    async fn close_rejects_acquires() {
+        let server = Server::new();
+        let pool = pool(&server, PoolOptions::new().min_size(2)).await;
+        let held = pool.acquire().await.unwrap();
+
+        pool.close();
+        assert!(pool.is_closed());
+        assert!(matches!(pool.acquire().await, Err(DbError::Closed)));
+        assert_eq!(pool.status().size, 1);
+        drop(held);
+        assert_eq!(pool.status().size, 0);
+    }
+
+    async fn acquire_many(pool: &Pool<TestConn>, n: usize) -> Vec<PooledConnection<TestConn>> {
+        let mut conns = Vec::new();
+        for _ in 0..n {
+            conns.push(pool.acquire().await.unwrap());
+        }
+        conns
+    }
+}
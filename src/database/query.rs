@@ -0,0 +1,171 @@
+//! Typed queries.
+//!
+//! [`query_as`] runs a statement on any [`Connection`] and decodes each result row with
+//! [`FromRow`]; [`query`] returns the raw [`Row`]s.
+
+use std::marker::PhantomData;
+
+use super::{Connection, DbError, FromRow, Row, Value};
+
+/// Starts a query whose rows are returned as raw [`Row`]s.
+pub fn query(sql: impl Into<String>) -> QueryAs<Row> {
+    query_as(sql)
+}
+
+/// Starts a query whose rows are decoded into `T`.
+///
+/// ```
+/// # use rttp::database::{FromRow, query_as};
+/// #[derive(FromRow)]
+/// struct User {
+///     id: i64,
+///     name: String,
+/// }
+///
+/// let users = query_as::<User>("SELECT id, name FROM users WHERE id = $1").bind(7);
+/// ```
+pub fn query_as<T: FromRow>(sql: impl Into<String>) -> QueryAs<T> {
+    QueryAs {
+        sql: sql.into(),
+        params: Vec::new(),
+        _row: PhantomData,
+    }
+}
+
+/// A statement and its bound parameters, decoding rows into `T`.
+///
+/// Built with [`query_as`] or [`query`]; run with one of the `fetch_*` methods.
+#[must_use = "a query does nothing until fetched"]
+#[derive(Debug, Clone)]
+pub struct QueryAs<T> {
+    sql: String,
+    params: Vec<Value>,
+    _row: PhantomData<fn() -> T>,
+}
+
+impl<T: FromRow> QueryAs<T> {
+    /// Binds the next positional parameter.
+    pub fn bind(mut self, value: impl Into<Value>) -> Self {
+        self.params.push(value.into());
+        self
+    }
+
+    /// The statement text.
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// The parameters bound so far, in order.
+    pub fn params(&self) -> &[Value] {
+        &self.params
+    }
+
+    /// Runs the query and decodes every row.
+    ///
+    /// # Errors
+    ///
+    /// The driver's error, or the first row that fails to decode.
+    pub async fn fetch_all<C: Connection>(self, conn: &mut C) -> Result<Vec<T>, DbError> {
+        let rows = conn.query(&self.sql, &self.params).await?;
+        rows.iter().map(T::from_row).collect()
+    }
+
+    /// Runs the query and decodes the first row, if any.
+    ///
+    /// # Errors
+    ///
+    /// The driver's error, or the first row's decode error.
+    pub async fn fetch_optional<C: Connection>(self, conn: &mut C) -> Result<Option<T>, DbError> {
+        let rows = conn.query(&self.sql, &self.params).await?;
+        rows.first().map(T::from_row).transpose()
+    }
+
+    /// Runs the query and decodes the first row.
+    ///
+    /// # Errors
+    ///
+    /// [`DbError::RowNotFound`] when the query returned no rows, the driver's error, or
+    /// the row's decode error.
+    pub async fn fetch_one<C: Connection>(self, conn: &mut C) -> Result<T, DbError> {
+        self.fetch_optional(conn).await?.ok_or(DbError::RowNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::database::{DbFuture, PoolOptions};
+
+    #[derive(Debug, PartialEq, FromRow)]
+    struct User {
+        id: i64,
+        name: String,
+    }
+
+    /// Answers every query with the users whose id is at least the first parameter.
+    struct UsersConn;
+
+    impl Connection for UsersConn {
+        fn ping(&mut self) -> DbFuture<'_, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn query<'a>(&'a mut self, _sql: &'a str, params: &'a [Value]) -> DbFuture<'a, Vec<Row>> {
+            Box::pin(async move {
+                let min = match params.first() {
+                    Some(Value::Int(n)) => *n,
+                    _ => return Err(DbError::Backend("expected an id parameter".into())),
+                };
+                let columns: Arc<[String]> = Arc::from(["id".to_owned(), "name".to_owned()]);
+                Ok([(1, "ada"), (2, "grace")]
+                    .into_iter()
+                    .filter(|(id, _)| *id >= min)
+                    .map(|(id, name)| Row::new(Arc::clone(&columns), vec![id.into(), name.into()]))
+                    .collect())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn fetches_typed_rows() {
+        let mut conn = UsersConn;
+        let sql = "SELECT id, name FROM users WHERE id >= $1";
+
+        let users = query_as::<User>(sql).bind(1).fetch_all(&mut conn).await;
+        assert_eq!(users.unwrap().len(), 2);
+        let user = query_as::<User>(sql).bind(2).fetch_one(&mut conn).await;
+        assert_eq!(
+            user.unwrap(),
+            User {
+                id: 2,
+                name: "grace".into(),
+            }
+        );
+        let none = query_as::<User>(sql)
+            .bind(3)
+            .fetch_optional(&mut conn)
+            .await;
+        assert_eq!(none.unwrap(), None);
+        let err = query_as::<User>(sql).bind(3).fetch_one(&mut conn).await;
+        assert!(matches!(err, Err(DbError::RowNotFound)));
+        let err = query(sql).fetch_all(&mut conn).await;
+        assert!(matches!(err, Err(DbError::Backend(_))));
+    }
+
+    #[tokio::test]
+    async fn pooled_connections_run_queries() {
+        let pool = PoolOptions::new()
+            .connect(|| async { Ok(UsersConn) })
+            .await
+            .unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        let (id, name) = query_as::<(i64, String)>("SELECT id, name FROM users")
+            .bind(2)
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!((id, name.as_str()), (2, "grace"));
+    }
+}
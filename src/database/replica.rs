@@ -0,0 +1,297 @@
+//! Read-replica routing.
+//!
+//! A [`ReplicaSet`] pairs a primary [`Pool`] with any number of read-replica pools.
+//! Writes always go to the primary; reads are spread round-robin over the replicas that
+//! are up, falling back to the primary when none is.
+//!
+//! Replica health is tracked passively: a replica whose [`acquire`](Pool::acquire) fails
+//! is skipped for a cooldown period and then tried again. Give replica pools a short
+//! [`acquire_timeout`](super::PoolOptions::acquire_timeout) so an unreachable replica
+//! fails fast instead of stalling reads.
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use tokio::time::Instant;
+use tracing::warn;
+
+use super::{Connection, DbError, Pool, PooledConnection};
+use crate::Method;
+
+/// A primary pool plus read replicas. Clones share the replicas' health state.
+///
+/// # Examples
+///
+/// ```
+/// # use rttp::database::{Connection, DbFuture, Row, Value};
+/// # struct Conn;
+/// # impl Connection for Conn {
+/// #     fn ping(&mut self) -> DbFuture<'_, ()> { Box::pin(async { Ok(()) }) }
+/// #     fn query<'a>(&'a mut self, _: &'a str, _: &'a [Value]) -> DbFuture<'a, Vec<Row>> {
+/// #         Box::pin(async { Ok(Vec::new()) })
+/// #     }
+/// # }
+/// use rttp::{Method, database::{PoolOptions, ReplicaSet}};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), rttp::database::DbError> {
+/// let primary = PoolOptions::new().connect(|| async { Ok(Conn) }).await?;
+/// let replica = PoolOptions::new().connect(|| async { Ok(Conn) }).await?;
+/// let db = ReplicaSet::new(primary).replica(replica).route_reads(true);
+///
+/// let reader = db.read().await?; // a replica
+/// let writer = db.write().await?; // the primary
+/// let conn = db.acquire_for(&Method::Get).await?; // a replica, since reads are routed
+/// # Ok(())
+/// # }
+/// ```
+pub struct ReplicaSet<C: Connection> {
+    primary: Pool<C>,
+    replicas: Vec<Arc<Replica<C>>>,
+    next: Arc<AtomicUsize>,
+    cooldown: Duration,
+    route_reads: bool,
+}
+
+impl<C: Connection> Clone for ReplicaSet<C> {
+    fn clone(&self) -> Self {
+        Self {
+            primary: self.primary.clone(),
+            replicas: self.replicas.clone(),
+            next: Arc::clone(&self.next),
+            cooldown: self.cooldown,
+            route_reads: self.route_reads,
+        }
+    }
+}
+
+struct Replica<C: Connection> {
+    pool: Pool<C>,
+    // Set while the replica is considered down.
+    down_until: Mutex<Option<Instant>>,
+}
+
+impl<C: Connection> Replica<C> {
+    fn is_up(&self, now: Instant) -> bool {
+        let down_until = self.down_until.lock().unwrap_or_else(|e| e.into_inner());
+        down_until.is_none_or(|until| now >= until)
+    }
+
+    fn mark(&self, down_until: Option<Instant>) {
+        *self.down_until.lock().unwrap_or_else(|e| e.into_inner()) = down_until;
+    }
+}
+
+impl<C: Connection> ReplicaSet<C> {
+    /// Creates a set with no replicas: every connection comes from `primary`.
+    ///
+    /// Defaults: a 30-second replica cooldown and no method-based routing.
+    pub fn new(primary: Pool<C>) -> Self {
+        Self {
+            primary,
+            replicas: Vec::new(),
+            next: Arc::new(AtomicUsize::new(0)),
+            cooldown: Duration::from_secs(30),
+            route_reads: false,
+        }
+    }
+
+    /// Adds a read replica.
+    #[must_use]
+    pub fn replica(mut self, pool: Pool<C>) -> Self {
+        self.replicas.push(Arc::new(Replica {
+            pool,
+            down_until: Mutex::new(None),
+        }));
+        self
+    }
+
+    /// Sets how long a failing replica is skipped before it is tried again.
+    #[must_use]
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Whether [`acquire_for`](Self::acquire_for) sends safe methods (`GET`, `HEAD`,
+    /// `OPTIONS`, `TRACE`) to a replica (default `false`).
+    ///
+    /// Replicas lag the primary, so a handler that must read its own writes should use
+    /// [`write`](Self::write) explicitly.
+    #[must_use]
+    pub fn route_reads(mut self, route: bool) -> Self {
+        self.route_reads = route;
+        self
+    }
+
+    /// The primary pool.
+    pub fn primary(&self) -> &Pool<C> {
+        &self.primary
+    }
+
+    /// Checks out a connection to the primary.
+    ///
+    /// # Errors
+    ///
+    /// The error from the primary's [`Pool::acquire`].
+    pub async fn write(&self) -> Result<PooledConnection<C>, DbError> {
+        self.primary.acquire().await
+    }
+
+    /// Checks out a connection to the next available replica, or to the primary when
+    /// there are none or every replica is down.
+    ///
+    /// # Errors
+    ///
+    /// The error from the primary's [`Pool::acquire`]; replica failures only trigger
+    /// failover.
+    pub async fn read(&self) -> Result<PooledConnection<C>, DbError> {
+        let count = self.replicas.len();
+        if count > 0 {
+            let start = self.next.fetch_add(1, Ordering::Relaxed);
+            for offset in 0..count {
+                let index = (start + offset) % count;
+                let replica = &self.replicas[index];
+                if !replica.is_up(Instant::now()) {
+                    continue;
+                }
+                match replica.pool.acquire().await {
+                    Ok(conn) => {
+                        replica.mark(None);
+                        return Ok(conn);
+                    }
+                    Err(e) => {
+                        warn!(error = %e, replica = index, "read replica unavailable, failing over");
+                        replica.mark(Some(Instant::now() + self.cooldown));
+                    }
+                }
+            }
+        }
+        self.primary.acquire().await
+    }
+
+    /// Checks out a connection for a request with `method`: [`read`](Self::read) for
+    /// safe methods when [`route_reads`](Self::route_reads) is enabled,
+    /// [`write`](Self::write) otherwise.
+    ///
+    /// # Errors
+    ///
+    /// The error from [`read`](Self::read) or [`write`](Self::write).
+    pub async fn acquire_for(&self, method: &Method) -> Result<PooledConnection<C>, DbError> {
+        if self.route_reads && method.is_safe() {
+            self.read().await
+        } else {
+            self.write().await
+        }
+    }
+
+    /// Returns whether each replica, in the order added, is currently considered up.
+    pub fn replica_health(&self) -> Vec<bool> {
+        let now = Instant::now();
+        self.replicas.iter().map(|r| r.is_up(now)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use super::*;
+    use crate::database::{DbFuture, PoolOptions, Row, Value};
+
+    struct NamedConn(&'static str);
+
+    impl Connection for NamedConn {
+        fn ping(&mut self) -> DbFuture<'_, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn query<'a>(&'a mut self, _: &'a str, _: &'a [Value]) -> DbFuture<'a, Vec<Row>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+    }
+
+    async fn pool(name: &'static str, down: &Arc<AtomicBool>) -> Pool<NamedConn> {
+        let down = Arc::clone(down);
+        PoolOptions::new()
+            .connect(move || {
+                let down = down.load(Ordering::SeqCst);
+                async move {
+                    if down {
+                        Err(DbError::Connect("refused".into()))
+                    } else {
+                        Ok(NamedConn(name))
+                    }
+                }
+            })
+            .await
+            .unwrap()
+    }
+
+    async fn set(cooldown: Duration) -> (ReplicaSet<NamedConn>, Arc<AtomicBool>) {
+        let stable = Arc::new(AtomicBool::new(false));
+        let flaky = Arc::new(AtomicBool::new(false));
+        let db = ReplicaSet::new(pool("primary", &stable).await)
+            .replica(pool("a", &flaky).await)
+            .replica(pool("b", &stable).await)
+            .cooldown(cooldown);
+        (db, flaky)
+    }
+
+    async fn read(db: &ReplicaSet<NamedConn>) -> &'static str {
+        db.read().await.unwrap().0
+    }
+
+    #[tokio::test]
+    async fn reads_round_robin_and_writes_use_primary() {
+        let (db, _) = set(Duration::from_secs(30)).await;
+        assert_eq!(
+            [read(&db).await, read(&db).await, read(&db).await],
+            ["a", "b", "a"]
+        );
+        assert_eq!(db.write().await.unwrap().0, "primary");
+
+        let db = ReplicaSet::new(db.primary().clone());
+        assert_eq!(read(&db).await, "primary", "no replicas");
+    }
+
+    #[tokio::test]
+    async fn failing_replicas_are_skipped_until_cooldown() {
+        let (db, flaky) = set(Duration::from_millis(40)).await;
+        flaky.store(true, Ordering::SeqCst);
+
+        assert_eq!(read(&db).await, "b", "fails over to the next replica");
+        assert_eq!(db.replica_health(), [false, true]);
+        assert_eq!(read(&db).await, "b");
+        assert_eq!(read(&db).await, "b");
+
+        flaky.store(false, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(db.replica_health(), [true, true]);
+        let reads = [read(&db).await, read(&db).await];
+        assert!(reads.contains(&"a"), "retried after cooldown");
+    }
+
+    #[tokio::test]
+    async fn reads_fall_back_to_primary() {
+        let down = Arc::new(AtomicBool::new(true));
+        let stable = Arc::new(AtomicBool::new(false));
+        let db = ReplicaSet::new(pool("primary", &stable).await).replica(pool("a", &down).await);
+        assert_eq!(read(&db).await, "primary");
+    }
+
+    #[tokio::test]
+    async fn acquire_for_routes_safe_methods_when_enabled() {
+        let (db, _) = set(Duration::from_secs(30)).await;
+        assert_eq!(db.acquire_for(&Method::Get).await.unwrap().0, "primary");
+
+        let db = db.route_reads(true);
+        assert_eq!(db.acquire_for(&Method::Get).await.unwrap().0, "a");
+        assert_eq!(db.acquire_for(&Method::Post).await.unwrap().0, "primary");
+    }
+}
@@ -0,0 +1,377 @@
+//! Result rows and typed decoding.
+//!
+//! A driver returns each result row as a [`Row`] of [`Value`]s. [`FromValue`] decodes a
+//! single column into a Rust type and [`FromRow`] decodes a whole row, usually through
+//! `#[derive(FromRow)]`.
+
+use std::{fmt, sync::Arc};
+
+use super::DbError;
+
+/// A single column value as returned by a driver.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Value {
+    /// The value's SQL-ish type name, as used in decode errors.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Null => "NULL",
+            Self::Bool(_) => "bool",
+            Self::Int(_) => "integer",
+            Self::Float(_) => "float",
+            Self::Text(_) => "text",
+            Self::Bytes(_) => "bytes",
+        }
+    }
+
+    /// Returns `true` for [`Value::Null`].
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+macro_rules! value_from_int {
+    ($($ty:ty),*) => {$(
+        impl From<$ty> for Value {
+            fn from(value: $ty) -> Self {
+                Self::Int(i64::from(value))
+            }
+        }
+    )*};
+}
+
+value_from_int!(i8, i16, i32, i64, u8, u16, u32);
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Self::Float(f64::from(value))
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::Text(value.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(value: &[u8]) -> Self {
+        Self::Bytes(value.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Bytes(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+/// One result row: column names shared across the result set, plus this row's values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    columns: Arc<[String]>,
+    values: Vec<Value>,
+}
+
+impl Row {
+    /// Creates a row. Drivers share one `columns` slice across every row of a result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `columns` and `values` differ in length.
+    pub fn new(columns: Arc<[String]>, values: Vec<Value>) -> Self {
+        assert_eq!(
+            columns.len(),
+            values.len(),
+            "row must have one value per column"
+        );
+        Self { columns, values }
+    }
+
+    /// The column names, in order.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// The raw values, in column order.
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// Number of columns.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` for a row without columns.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the raw value of a column, looked up by name or position.
+    pub fn value<I: ColumnIndex>(&self, index: I) -> Option<&Value> {
+        index.position(self).map(|i| &self.values[i])
+    }
+
+    /// Decodes a column, looked up by name or position.
+    ///
+    /// # Errors
+    ///
+    /// [`DbError::ColumnNotFound`] when there is no such column, or
+    /// [`DbError::Decode`] when its value does not fit `T`.
+    pub fn try_get<T: FromValue, I: ColumnIndex>(&self, index: I) -> Result<T, DbError> {
+        let position = index
+            .position(self)
+            .ok_or_else(|| DbError::ColumnNotFound(index.to_string()))?;
+        T::from_value(&self.values[position]).map_err(|message| DbError::Decode {
+            column: self.columns[position].clone(),
+            message,
+        })
+    }
+}
+
+/// Looks up a column in a [`Row`]: by name with `&str`, by position with `usize`.
+pub trait ColumnIndex: fmt::Display {
+    /// The column's position in `row`, if present.
+    fn position(&self, row: &Row) -> Option<usize>;
+}
+
+impl ColumnIndex for &str {
+    fn position(&self, row: &Row) -> Option<usize> {
+        row.columns.iter().position(|column| column == self)
+    }
+}
+
+impl ColumnIndex for usize {
+    fn position(&self, row: &Row) -> Option<usize> {
+        (*self < row.len()).then_some(*self)
+    }
+}
+
+/// Decodes one column [`Value`] into a Rust type.
+///
+/// Integers are range-checked and `NULL` only decodes into `Option<T>`. The error is a
+/// short description; [`Row::try_get`] wraps it in [`DbError::Decode`] with the column
+/// name.
+pub trait FromValue: Sized {
+    /// Decodes `value`, describing the mismatch on failure.
+    fn from_value(value: &Value) -> Result<Self, String>;
+}
+
+fn mismatch(expected: &str, found: &Value) -> String {
+    format!("expected {expected}, found {}", found.type_name())
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        Ok(value.clone())
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Bool(b) => Ok(*b),
+            // Drivers without a native boolean (SQLite) store 0 and 1.
+            Value::Int(0) => Ok(false),
+            Value::Int(1) => Ok(true),
+            other => Err(mismatch("bool", other)),
+        }
+    }
+}
+
+macro_rules! int_from_value {
+    ($($ty:ty),*) => {$(
+        impl FromValue for $ty {
+            fn from_value(value: &Value) -> Result<Self, String> {
+                match value {
+                    Value::Int(n) => <$ty>::try_from(*n)
+                        .map_err(|_| format!("{n} is out of range for {}", stringify!($ty))),
+                    other => Err(mismatch(stringify!($ty), other)),
+                }
+            }
+        }
+    )*};
+}
+
+int_from_value!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Float(f) => Ok(*f),
+            Value::Int(n) => Ok(*n as f64),
+            other => Err(mismatch("f64", other)),
+        }
+    }
+}
+
+impl FromValue for f32 {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        f64::from_value(value)
+            .map(|f| f as f32)
+            .map_err(|_| mismatch("f32", value))
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Text(s) => Ok(s.clone()),
+            other => Err(mismatch("text", other)),
+        }
+    }
+}
+
+impl FromValue for Vec<u8> {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Bytes(b) => Ok(b.clone()),
+            Value::Text(s) => Ok(s.as_bytes().to_vec()),
+            other => Err(mismatch("bytes", other)),
+        }
+    }
+}
+
+/// Decodes a whole [`Row`] into a Rust type.
+///
+/// Usually derived with `#[derive(FromRow)]`, which reads each named field from the
+/// column of the same name (or `#[row(rename = "...")]`) and tuple-struct fields by
+/// position. Tuples decode by position too, and [`Row`] decodes into itself.
+pub trait FromRow: Sized {
+    /// Decodes `row`.
+    fn from_row(row: &Row) -> Result<Self, DbError>;
+}
+
+impl FromRow for Row {
+    fn from_row(row: &Row) -> Result<Self, DbError> {
+        Ok(row.clone())
+    }
+}
+
+macro_rules! tuple_from_row {
+    ($($ty:ident $index:tt),+) => {
+        impl<$($ty: FromValue),+> FromRow for ($($ty,)+) {
+            fn from_row(row: &Row) -> Result<Self, DbError> {
+                Ok(($(row.try_get::<$ty, _>($index)?,)+))
+            }
+        }
+    };
+}
+
+tuple_from_row!(A 0);
+tuple_from_row!(A 0, B 1);
+tuple_from_row!(A 0, B 1, C 2);
+tuple_from_row!(A 0, B 1, C 2, D 3);
+tuple_from_row!(A 0, B 1, C 2, D 3, E 4);
+tuple_from_row!(A 0, B 1, C 2, D 3, E 4, F 5);
+tuple_from_row!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+tuple_from_row!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::FromRow;
+
+    fn row(columns: &[&str], values: Vec<Value>) -> Row {
+        let columns: Arc<[String]> = columns.iter().map(|c| c.to_string()).collect();
+        Row::new(columns, values)
+    }
+
+    #[derive(Debug, PartialEq, FromRow)]
+    struct User {
+        id: i64,
+        #[row(rename = "user_name")]
+        name: String,
+        email: Option<String>,
+        active: bool,
+    }
+
+    #[derive(Debug, PartialEq, FromRow)]
+    struct Pair(u8, String);
+
+    fn user_row(id: Value) -> Row {
+        row(
+            &["id", "user_name", "email", "active"],
+            vec![id, "ada".into(), Value::Null, Value::Int(1)],
+        )
+    }
+
+    #[test]
+    fn derive_decodes_named_and_tuple_structs() {
+        let user = User::from_row(&user_row(Value::Int(7))).unwrap();
+        assert_eq!(
+            user,
+            User {
+                id: 7,
+                name: "ada".into(),
+                email: None,
+                active: true,
+            }
+        );
+
+        let pair = Pair::from_row(&row(&["a", "b"], vec![3.into(), "x".into()])).unwrap();
+        assert_eq!(pair, Pair(3, "x".into()));
+        let tuple = <(i32, Option<f64>)>::from_row(&row(&["a", "b"], vec![1.into(), 2.into()]));
+        assert_eq!(tuple.unwrap(), (1, Some(2.0)));
+    }
+
+    #[test]
+    fn missing_columns_and_type_mismatches_are_reported() {
+        let err = User::from_row(&row(&["id"], vec![1.into()])).unwrap_err();
+        assert!(matches!(&err, DbError::ColumnNotFound(c) if c == "user_name"));
+
+        let err = User::from_row(&user_row("7".into())).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to decode column `id`: expected i64, found text"
+        );
+
+        let err = Pair::from_row(&row(&["a", "b"], vec![300.into(), "x".into()])).unwrap_err();
+        assert!(err.to_string().contains("300 is out of range for u8"));
+
+        let err = row(&["n"], vec![Value::Null]).try_get::<i64, _>("n");
+        assert!(err.unwrap_err().to_string().contains("found NULL"));
+    }
+}
@@ -0,0 +1,310 @@
+//! Per-connection prepared-statement caching.
+//!
+//! Drivers that support server-side prepared statements implement [`Prepare`]. Wrapping
+//! such a connection in a [`StatementCache`] makes every [`query`](Connection::query)
+//! reuse the statement prepared for the same SQL text, so hot queries are parsed and
+//! planned once per connection instead of on every call.
+
+use std::collections::{BTreeMap, HashMap};
+
+use tracing::warn;
+
+use super::{Connection, DbError, DbFuture, Row, Value};
+
+/// A connection that can prepare statements ahead of running them.
+pub trait Prepare: Connection {
+    /// The driver's handle to a prepared statement.
+    type Statement: Send + Sync + 'static;
+
+    /// Parses and plans `sql` on the server.
+    fn prepare<'a>(&'a mut self, sql: &'a str) -> DbFuture<'a, Self::Statement>;
+
+    /// Runs a statement returned by [`prepare`](Self::prepare) with positional `params`.
+    fn query_prepared<'a>(
+        &'a mut self,
+        statement: &'a Self::Statement,
+        params: &'a [Value],
+    ) -> DbFuture<'a, Vec<Row>>;
+
+    /// Releases a prepared statement on the server.
+    fn deallocate(&mut self, statement: Self::Statement) -> DbFuture<'_, ()>;
+}
+
+/// Counters for one [`StatementCache`], from [`StatementCache::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatementCacheStats {
+    /// Queries that reused a cached statement.
+    pub hits: u64,
+    /// Queries that had to prepare their statement.
+    pub misses: u64,
+    /// Statements deallocated to make room for new ones.
+    pub evictions: u64,
+}
+
+/// A connection wrapper that caches prepared statements by SQL text.
+///
+/// Holds at most `capacity` statements; preparing one more deallocates the least
+/// recently used. A statement whose execution fails is dropped from the cache, so a
+/// plan invalidated by a schema change is re-prepared on the next call.
+///
+/// Open pooled connections through it to give each one its own cache:
+///
+/// ```rust,ignore
+/// let pool = PoolOptions::new()
+///     .connect(|| async { Ok(StatementCache::new(PgConn::connect(url).await?, 256)) })
+///     .await?;
+/// ```
+pub struct StatementCache<C: Prepare> {
+    conn: C,
+    capacity: usize,
+    statements: HashMap<String, Cached<C::Statement>>,
+    // Access order: the smallest tick is the least recently used SQL.
+    recency: BTreeMap<u64, String>,
+    next_tick: u64,
+    stats: StatementCacheStats,
+}
+
+struct Cached<S> {
+    statement: S,
+    // Position in `StatementCache::recency`.
+    tick: u64,
+}
+
+impl<C: Prepare> StatementCache<C> {
+    /// Wraps `conn`, caching at most `capacity` statements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(conn: C, capacity: usize) -> Self {
+        assert!(capacity > 0, "statement cache capacity must be non-zero");
+        Self {
+            conn,
+            capacity,
+            statements: HashMap::new(),
+            recency: BTreeMap::new(),
+            next_tick: 0,
+            stats: StatementCacheStats::default(),
+        }
+    }
+
+    /// Returns the cache's counters.
+    pub fn stats(&self) -> StatementCacheStats {
+        self.stats
+    }
+
+    /// Number of cached statements.
+    pub fn len(&self) -> usize {
+        self.statements.len()
+    }
+
+    /// Returns `true` when no statement is cached.
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty()
+    }
+
+    /// Returns `true` if a statement for `sql` is cached.
+    pub fn contains(&self, sql: &str) -> bool {
+        self.statements.contains_key(sql)
+    }
+
+    /// Returns the wrapped connection.
+    pub fn get_ref(&self) -> &C {
+        &self.conn
+    }
+
+    /// Returns the wrapped connection mutably.
+    ///
+    /// Statements deallocated directly through it must not be cached here.
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.conn
+    }
+
+    /// Unwraps the connection. Cached statements stay allocated on the server until the
+    /// connection closes.
+    pub fn into_inner(self) -> C {
+        self.conn
+    }
+
+    // Marks `sql` as most recently used.
+    fn touch(&mut self, sql: &str) {
+        if let Some(cached) = self.statements.get_mut(sql) {
+            self.recency.remove(&cached.tick);
+            cached.tick = self.next_tick;
+            self.next_tick += 1;
+            self.recency.insert(cached.tick, sql.to_owned());
+        }
+    }
+
+    fn remove(&mut self, sql: &str) -> Option<C::Statement> {
+        let cached = self.statements.remove(sql)?;
+        self.recency.remove(&cached.tick);
+        Some(cached.statement)
+    }
+
+    // Deallocates least recently used statements until one more fits.
+    async fn make_room(&mut self) {
+        while self.statements.len() >= self.capacity {
+            let Some((_, oldest)) = self.recency.first_key_value() else {
+                break;
+            };
+            let oldest = oldest.clone();
+            if let Some(statement) = self.remove(&oldest) {
+                self.stats.evictions += 1;
+                self.deallocate(statement).await;
+            }
+        }
+    }
+
+    async fn deallocate(&mut self, statement: C::Statement) {
+        if let Err(e) = self.conn.deallocate(statement).await {
+            warn!(error = %e, "failed to deallocate prepared statement");
+        }
+    }
+
+    async fn query_cached(&mut self, sql: &str, params: &[Value]) -> Result<Vec<Row>, DbError> {
+        if self.statements.contains_key(sql) {
+            self.stats.hits += 1;
+            self.touch(sql);
+        } else {
+            self.stats.misses += 1;
+            let statement = self.conn.prepare(sql).await?;
+            self.make_room().await;
+            let tick = self.next_tick;
+            self.next_tick += 1;
+            self.recency.insert(tick, sql.to_owned());
+            self.statements
+                .insert(sql.to_owned(), Cached { statement, tick });
+        }
+
+        let cached = &self.statements[sql];
+        let result = self.conn.query_prepared(&cached.statement, params).await;
+        if result.is_err() {
+            if let Some(statement) = self.remove(sql) {
+                self.deallocate(statement).await;
+            }
+        }
+        result
+    }
+}
+
+impl<C: Prepare> Connection for StatementCache<C> {
+    fn ping(&mut self) -> DbFuture<'_, ()> {
+        self.conn.ping()
+    }
+
+    fn query<'a>(&'a mut self, sql: &'a str, params: &'a [Value]) -> DbFuture<'a, Vec<Row>> {
+        Box::pin(self.query_cached(sql, params))
+    }
+
+    fn begin(&mut self) -> DbFuture<'_, ()> {
+        self.conn.begin()
+    }
+
+    fn commit(&mut self) -> DbFuture<'_, ()> {
+        self.conn.commit()
+    }
+
+    fn rollback(&mut self) -> DbFuture<'_, ()> {
+        self.conn.rollback()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::query;
+
+    /// Hands out numbered statements and records prepare/deallocate calls.
+    #[derive(Default)]
+    struct PrepConn {
+        next_id: u32,
+        log: Vec<String>,
+    }
+
+    impl Connection for PrepConn {
+        fn ping(&mut self) -> DbFuture<'_, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn query<'a>(&'a mut self, _: &'a str, _: &'a [Value]) -> DbFuture<'a, Vec<Row>> {
+            Box::pin(async { Err(DbError::Backend("unprepared query".into())) })
+        }
+    }
+
+    impl Prepare for PrepConn {
+        type Statement = (u32, String);
+
+        fn prepare<'a>(&'a mut self, sql: &'a str) -> DbFuture<'a, Self::Statement> {
+            self.next_id += 1;
+            self.log.push(format!("prepare {sql}"));
+            let statement = (self.next_id, sql.to_owned());
+            Box::pin(async move { Ok(statement) })
+        }
+
+        fn query_prepared<'a>(
+            &'a mut self,
+            statement: &'a Self::Statement,
+            _: &'a [Value],
+        ) -> DbFuture<'a, Vec<Row>> {
+            let fail = statement.1 == "BROKEN";
+            Box::pin(async move {
+                if fail {
+                    Err(DbError::Backend(
+                        "cached plan must not change result type".into(),
+                    ))
+                } else {
+                    Ok(Vec::new())
+                }
+            })
+        }
+
+        fn deallocate(&mut self, statement: Self::Statement) -> DbFuture<'_, ()> {
+            self.log.push(format!("deallocate {}", statement.1));
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    async fn run(conn: &mut StatementCache<PrepConn>, sql: &str) -> Result<Vec<Row>, DbError> {
+        query(sql).fetch_all(conn).await
+    }
+
+    #[tokio::test]
+    async fn reuses_statements_and_evicts_least_recently_used() {
+        let mut conn = StatementCache::new(PrepConn::default(), 2);
+        for sql in ["A", "B", "A", "C", "A"] {
+            run(&mut conn, sql).await.unwrap();
+        }
+
+        assert_eq!(
+            conn.get_ref().log,
+            ["prepare A", "prepare B", "prepare C", "deallocate B"]
+        );
+        assert!(conn.contains("A") && conn.contains("C") && !conn.contains("B"));
+        assert_eq!(
+            conn.stats(),
+            StatementCacheStats {
+                hits: 2,
+                misses: 3,
+                evictions: 1,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn failing_statements_are_dropped() {
+        let mut conn = StatementCache::new(PrepConn::default(), 4);
+        assert!(run(&mut conn, "BROKEN").await.is_err());
+        assert!(conn.is_empty());
+        assert!(run(&mut conn, "BROKEN").await.is_err());
+        assert_eq!(
+            conn.into_inner().log,
+            [
+                "prepare BROKEN",
+                "deallocate BROKEN",
+                "prepare BROKEN",
+                "deallocate BROKEN"
+            ]
+        );
+    }
+}
@@ -0,0 +1,378 @@
+//! Transactions and per-request transaction middleware.
+//!
+//! - [`Transaction`] — cheap-to-clone handle to a pooled connection inside `BEGIN`.
+//! - [`TransactionMiddleware`] — begins a transaction per request and commits or rolls
+//!   it back depending on the response status.
+//! - [`TransactionExt`] — adds `ctx.transaction()` to [`Context`].
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! # use rttp::database::{Connection, DbFuture, Row, Value};
+//! # struct Conn;
+//! # impl Connection for Conn {
+//! #     fn ping(&mut self) -> DbFuture<'_, ()> { Box::pin(async { Ok(()) }) }
+//! #     fn query<'a>(&'a mut self, _: &'a str, _: &'a [Value]) -> DbFuture<'a, Vec<Row>> {
+//! #         Box::pin(async { Ok(Vec::new()) })
+//! #     }
+//! # }
+//! use rttp::{Response, StatusCode, context::Context};
+//! use rttp::database::{TransactionExt, query};
+//!
+//! async fn transfer(ctx: Context) -> Response {
+//!     let tx = ctx.transaction::<Conn>().expect("TransactionMiddleware installed");
+//!     let mut conn = tx.lock().await.unwrap();
+//!     let debit = query("UPDATE accounts SET balance = balance - 10 WHERE id = 1");
+//!     if debit.fetch_all(&mut *conn).await.is_err() {
+//!         return Response::new(StatusCode::InternalServerError); // rolled back
+//!     }
+//!     Response::new(StatusCode::Ok) // committed
+//! }
+//! ```
+
+use std::{
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    sync::Arc,
+};
+
+use tokio::sync::{Mutex, MutexGuard};
+
+use super::{Connection, DbError, Pool, PooledConnection};
+use crate::{
+    Response, StatusCode,
+    context::Context,
+    middleware::{Middleware, Next},
+};
+
+/// A handle to an open transaction on a pooled connection.
+///
+/// Clones share the same transaction. It ends with [`commit`](Self::commit) or
+/// [`rollback`](Self::rollback), which return the connection to the pool. If every
+/// handle is dropped while the transaction is still open, the connection is
+/// [detached](PooledConnection::detach) rather than reused, and the database rolls the
+/// transaction back when it closes.
+pub struct Transaction<C: Connection> {
+    conn: Arc<Mutex<TxConn<C>>>,
+}
+
+impl<C: Connection> Clone for Transaction<C> {
+    fn clone(&self) -> Self {
+        Self {
+            conn: Arc::clone(&self.conn),
+        }
+    }
+}
+
+// The transaction's connection; `None` once committed or rolled back.
+struct TxConn<C: Connection>(Option<PooledConnection<C>>);
+
+impl<C: Connection> Drop for TxConn<C> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.0.take() {
+            drop(conn.detach());
+        }
+    }
+}
+
+impl<C: Connection> Transaction<C> {
+    /// Checks out a connection from `pool` and begins a transaction on it.
+    ///
+    /// # Errors
+    ///
+    /// The error from [`Pool::acquire`] or [`Connection::begin`].
+    pub async fn begin(pool: &Pool<C>) -> Result<Self, DbError> {
+        let mut conn = pool.acquire().await?;
+        if let Err(e) = conn.begin().await {
+            drop(conn.detach());
+            return Err(e);
+        }
+        Ok(Self {
+            conn: Arc::new(Mutex::new(TxConn(Some(conn)))),
+        })
+    }
+
+    /// Locks the transaction's connection for running queries.
+    ///
+    /// # Errors
+    ///
+    /// [`DbError::TransactionFinished`] after the transaction was committed or rolled
+    /// back.
+    pub async fn lock(&self) -> Result<TransactionGuard<'_, C>, DbError> {
+        let guard = self.conn.lock().await;
+        if guard.0.is_none() {
+            return Err(DbError::TransactionFinished);
+        }
+        Ok(TransactionGuard { guard })
+    }
+
+    /// Returns `true` until the transaction is committed or rolled back.
+    pub async fn is_active(&self) -> bool {
+        self.conn.lock().await.0.is_some()
+    }
+
+    /// Commits the transaction and returns the connection to the pool.
+    ///
+    /// # Errors
+    ///
+    /// [`DbError::TransactionFinished`] if it already ended, or the driver's error, in
+    /// which case the connection is detached from the pool.
+    pub async fn commit(&self) -> Result<(), DbError> {
+        self.finish(true).await
+    }
+
+    /// Rolls the transaction back and returns the connection to the pool.
+    ///
+    /// # Errors
+    ///
+    /// [`DbError::TransactionFinished`] if it already ended, or the driver's error, in
+    /// which case the connection is detached from the pool.
+    pub async fn rollback(&self) -> Result<(), DbError> {
+        self.finish(false).await
+    }
+
+    async fn finish(&self, commit: bool) -> Result<(), DbError> {
+        let mut guard = self.conn.lock().await;
+        let mut conn = guard.0.take().ok_or(DbError::TransactionFinished)?;
+        let result = if commit {
+            conn.commit().await
+        } else {
+            conn.rollback().await
+        };
+        if result.is_err() {
+            drop(conn.detach());
+        }
+        result
+    }
+}
+
+/// Exclusive access to a [`Transaction`]'s connection; derefs to `C`.
+pub struct TransactionGuard<'a, C: Connection> {
+    guard: MutexGuard<'a, TxConn<C>>,
+}
+
+impl<C: Connection> Deref for TransactionGuard<'_, C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        self.guard.0.as_ref().expect("checked by Transaction::lock")
+    }
+}
+
+impl<C: Connection> DerefMut for TransactionGuard<'_, C> {
+    fn deref_mut(&mut self) -> &mut C {
+        self.guard.0.as_mut().expect("checked by Transaction::lock")
+    }
+}
+
+/// Adds transaction access to [`Context`].
+pub trait TransactionExt {
+    /// Returns the transaction installed by a [`TransactionMiddleware<C>`], if any.
+    fn transaction<C: Connection>(&self) -> Option<&Transaction<C>>;
+}
+
+impl TransactionExt for Context {
+    fn transaction<C: Connection>(&self) -> Option<&Transaction<C>> {
+        self.extensions().get::<Transaction<C>>()
+    }
+}
+
+/// Middleware that wraps each request in a database transaction.
+///
+/// # Behavior
+///
+/// - Before the handler, a [`Transaction`] is begun on a connection from the pool and
+///   stored in the request extensions. Failing to begin one produces
+///   `503 Service Unavailable` when the pool timed out and `500 Internal Server Error`
+///   otherwise.
+/// - After the handler, a `2xx` or `3xx` response commits the transaction and anything
+///   else rolls it back. A failed commit replaces the response with
+///   `500 Internal Server Error`.
+/// - A transaction the handler already committed or rolled back is left alone.
+pub struct TransactionMiddleware<C: Connection> {
+    pool: Pool<C>,
+}
+
+impl<C: Connection> TransactionMiddleware<C> {
+    /// Creates a middleware that draws connections from `pool`.
+    pub fn new(pool: Pool<C>) -> Self {
+        Self { pool }
+    }
+}
+
+impl<C: Connection> Middleware for TransactionMiddleware<C> {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            let tx = match Transaction::begin(&pool).await {
+                Ok(tx) => tx,
+                Err(e) => {
+                    tracing::error!(error = %e, "failed to begin request transaction");
+                    return Response::new(match e {
+                        DbError::Timeout => StatusCode::ServiceUnavailable,
+                        _ => StatusCode::InternalServerError,
+                    });
+                }
+            };
+
+            ctx.extensions_mut().insert(tx.clone());
+            let response = next.run(ctx).await;
+
+            let status = response.status();
+            if status.is_success() || status.is_redirection() {
+                match tx.commit().await {
+                    Ok(()) | Err(DbError::TransactionFinished) => response,
+                    Err(e) => {
+                        tracing::error!(error = %e, "failed to commit request transaction");
+                        Response::new(StatusCode::InternalServerError)
+                    }
+                }
+            } else {
+                match tx.rollback().await {
+                    Ok(()) | Err(DbError::TransactionFinished) => {}
+                    Err(e) => {
+                        tracing::error!(error = %e, "failed to roll back request transaction");
+                    }
+                }
+                response
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex as StdMutex;
+
+    use super::*;
+    use crate::{
+        database::{DbFuture, PoolOptions, Row, Value, query},
+        http::request::Request,
+        middleware::{MiddlewareHandler, from_middleware},
+    };
+
+    /// Records every statement it runs; fails `COMMIT` when `fail_commit` is set.
+    struct LogConn {
+        log: Arc<StdMutex<Vec<String>>>,
+        fail_commit: bool,
+    }
+
+    impl Connection for LogConn {
+        fn ping(&mut self) -> DbFuture<'_, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn query<'a>(&'a mut self, sql: &'a str, _: &'a [Value]) -> DbFuture<'a, Vec<Row>> {
+            self.log.lock().unwrap().push(sql.to_owned());
+            let fail = self.fail_commit && sql == "COMMIT";
+            Box::pin(async move {
+                if fail {
+                    Err(DbError::Backend("serialization failure".into()))
+                } else {
+                    Ok(Vec::new())
+                }
+            })
+        }
+    }
+
+    async fn pool(fail_commit: bool) -> (Pool<LogConn>, Arc<StdMutex<Vec<String>>>) {
+        let log = Arc::new(StdMutex::new(Vec::new()));
+        let pool = PoolOptions::new()
+            .connect({
+                let log = Arc::clone(&log);
+                move || {
+                    let log = Arc::clone(&log);
+                    async move { Ok(LogConn { log, fail_commit }) }
+                }
+            })
+            .await
+            .unwrap();
+        (pool, log)
+    }
+
+    // Runs an insert in the request transaction, then responds with `status`.
+    fn handler(status: StatusCode) -> MiddlewareHandler {
+        Arc::new(move |ctx: Context, _next: Next| {
+            Box::pin(async move {
+                let tx = ctx.transaction::<LogConn>().unwrap();
+                let mut conn = tx.lock().await.unwrap();
+                query("INSERT").fetch_all(&mut *conn).await.unwrap();
+                Response::new(status)
+            })
+        })
+    }
+
+    async fn run(pool: &Pool<LogConn>, handler: MiddlewareHandler) -> Response {
+        let (req, _) = Request::parse(b"POST / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mw = Arc::new(TransactionMiddleware::new(pool.clone()));
+        Next::new(vec![from_middleware(mw), handler])
+            .run(Context::new(req))
+            .await
+    }
+
+    fn take(log: &StdMutex<Vec<String>>) -> Vec<String> {
+        std::mem::take(&mut *log.lock().unwrap())
+    }
+
+    #[tokio::test]
+    async fn commits_success_and_rolls_back_errors() {
+        let (pool, log) = pool(false).await;
+
+        let res = run(&pool, handler(StatusCode::Created)).await;
+        assert_eq!(res.status(), StatusCode::Created);
+        assert_eq!(take(&log), ["BEGIN", "INSERT", "COMMIT"]);
+
+        for status in [StatusCode::NotFound, StatusCode::InternalServerError] {
+            let res = run(&pool, handler(status)).await;
+            assert_eq!(res.status(), status);
+            assert_eq!(take(&log), ["BEGIN", "INSERT", "ROLLBACK"]);
+        }
+        let status = pool.status();
+        assert_eq!((status.in_use, status.connections_closed), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn handler_can_end_the_transaction_early() {
+        let (pool, log) = pool(false).await;
+        let early: MiddlewareHandler = Arc::new(|ctx: Context, _next: Next| {
+            Box::pin(async move {
+                let tx = ctx.transaction::<LogConn>().unwrap();
+                tx.rollback().await.unwrap();
+                assert!(!tx.is_active().await);
+                assert!(matches!(
+                    tx.lock().await.err(),
+                    Some(DbError::TransactionFinished)
+                ));
+                Response::new(StatusCode::Ok)
+            })
+        });
+
+        assert_eq!(run(&pool, early).await.status(), StatusCode::Ok);
+        assert_eq!(take(&log), ["BEGIN", "ROLLBACK"]);
+    }
+
+    #[tokio::test]
+    async fn failed_commit_is_a_server_error() {
+        let (pool, log) = pool(true).await;
+        let res = run(&pool, handler(StatusCode::Ok)).await;
+        assert_eq!(res.status(), StatusCode::InternalServerError);
+        assert_eq!(take(&log), ["BEGIN", "INSERT", "COMMIT"]);
+        assert_eq!(pool.status().size, 0, "connection detached");
+    }
+
+    #[tokio::test]
+    async fn dropped_transaction_is_not_reused() {
+        let (pool, log) = pool(false).await;
+        let tx = Transaction::begin(&pool).await.unwrap();
+        drop(tx.clone());
+        assert_eq!(pool.status().in_use, 1);
+        drop(tx);
+        assert_eq!(pool.status().size, 0);
+        assert_eq!(take(&log), ["BEGIN"]);
+    }
+}
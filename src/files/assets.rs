@@ -0,0 +1,398 @@
+//! Subresource integrity hashes and fingerprinted asset URLs.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    pin::Pin,
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+use super::{ServeDir, is_hidden};
+use crate::{
+    Method, Response, StatusCode,
+    context::Context,
+    http::html::escape,
+    middleware::{Middleware, Next},
+    security::{
+        crypto::{base64_encode, sha256, to_hex},
+        path::{self, PathError},
+    },
+};
+
+// Hex digits of the content hash embedded in fingerprinted file names.
+const FINGERPRINT_LEN: usize = 8;
+
+/// Content hashes for the files under a [`ServeDir`], for cache-busting and
+/// [subresource integrity](https://www.w3.org/TR/SRI/).
+///
+/// [`url`](Self::url) turns `js/app.js` into `/static/js/app.1a2b3c4d.js`, where the
+/// fingerprint is taken from the file's SHA-256, and [`integrity`](Self::integrity)
+/// gives the matching `sha256-…` value. [`script_tag`](Self::script_tag) and
+/// [`stylesheet_tag`](Self::stylesheet_tag) combine both into ready-made HTML for
+/// templates.
+///
+/// Installed as middleware, it serves fingerprinted URLs under its prefix with
+/// `Cache-Control: public, max-age=31536000, immutable`, since a changed file gets a new
+/// URL; plain paths under the prefix are served as the wrapped [`ServeDir`] would. It
+/// also makes itself available to handlers through [`AssetsExt`].
+///
+/// Hashes are cached per file. With hot reload — on by default in debug builds — a
+/// file is re-hashed whenever its modification time changes. Hashing reads the file on
+/// the calling thread.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rttp::{context::Context, files::{Assets, AssetsExt, ServeDir}, templates::TemplateExt};
+/// use serde_json::json;
+///
+/// let assets = Assets::new(ServeDir::new("public"), "/static");
+///
+/// async fn page(ctx: Context) -> rttp::Response {
+///     let assets = ctx.assets().expect("Assets middleware is installed");
+///     let script = assets.script_tag("js/app.js").unwrap_or_default();
+///     // In the template: {{{script}}}
+///     ctx.render("page.html", &json!({ "script": script }))
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Assets {
+    dir: ServeDir,
+    prefix: String,
+    hot_reload: bool,
+    cache: Arc<RwLock<HashMap<String, Hashed>>>,
+}
+
+// A file's SHA-256 and the modification time it was computed at.
+#[derive(Debug, Clone, Copy)]
+struct Hashed {
+    digest: [u8; 32],
+    modified: Option<SystemTime>,
+}
+
+impl Assets {
+    /// Hashes files served by `dir`, whose URLs start with `prefix` (such as `/static`).
+    pub fn new(dir: ServeDir, prefix: impl Into<String>) -> Self {
+        Self {
+            dir,
+            prefix: prefix.into().trim_end_matches('/').to_owned(),
+            hot_reload: cfg!(debug_assertions),
+            cache: Arc::default(),
+        }
+    }
+
+    /// Turns re-hashing changed files on or off.
+    #[must_use]
+    pub fn hot_reload(mut self, enabled: bool) -> Self {
+        self.hot_reload = enabled;
+        self
+    }
+
+    /// Returns the `integrity` attribute value for `path`, e.g. `sha256-…`.
+    ///
+    /// # Errors
+    ///
+    /// [`PathError`] if `path` is rejected or the file cannot be read; hidden files
+    /// report [`io::ErrorKind::NotFound`] unless the [`ServeDir`] allows them.
+    pub fn integrity(&self, path: &str) -> Result<String, PathError> {
+        let digest = self.digest(path)?;
+        Ok(format!("sha256-{}", base64_encode(&digest)))
+    }
+
+    /// Returns the fingerprinted URL for `path`: the prefix followed by the path with the
+    /// first eight hex digits of the file's SHA-256 before its extension.
+    ///
+    /// # Errors
+    ///
+    /// As for [`integrity`](Self::integrity).
+    pub fn url(&self, path: &str) -> Result<String, PathError> {
+        let digest = self.digest(path)?;
+        let path = path.trim_start_matches('/');
+        let fingerprint = &to_hex(&digest)[..FINGERPRINT_LEN];
+        let (dir, name) = match path.rsplit_once('/') {
+            Some((dir, name)) => (format!("{dir}/"), name),
+            None => (String::new(), path),
+        };
+        let name = match name.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => format!("{stem}.{fingerprint}.{ext}"),
+            _ => format!("{name}.{fingerprint}"),
+        };
+        Ok(format!("{}/{dir}{name}", self.prefix))
+    }
+
+    /// Returns a `<script>` tag loading `path` by its fingerprinted URL, with `integrity`
+    /// and `crossorigin="anonymous"` set.
+    ///
+    /// # Errors
+    ///
+    /// As for [`integrity`](Self::integrity).
+    pub fn script_tag(&self, path: &str) -> Result<String, PathError> {
+        Ok(format!(
+            r#"<script src="{}" integrity="{}" crossorigin="anonymous"></script>"#,
+            escape(&self.url(path)?),
+            self.integrity(path)?
+        ))
+    }
+
+    /// Returns a `<link rel="stylesheet">` tag for `path`, like
+    /// [`script_tag`](Self::script_tag).
+    ///
+    /// # Errors
+    ///
+    /// As for [`integrity`](Self::integrity).
+    pub fn stylesheet_tag(&self, path: &str) -> Result<String, PathError> {
+        Ok(format!(
+            r#"<link rel="stylesheet" href="{}" integrity="{}" crossorigin="anonymous">"#,
+            escape(&self.url(path)?),
+            self.integrity(path)?
+        ))
+    }
+
+    // Returns the SHA-256 of `path`, hashing the file if it is new or changed.
+    fn digest(&self, path: &str) -> Result<[u8; 32], PathError> {
+        let relative = path::sanitize(path)?;
+        if !self.dir.allow_hidden && is_hidden(&relative) {
+            return Err(io::Error::from(io::ErrorKind::NotFound).into());
+        }
+        let key = relative.to_string_lossy().into_owned();
+        let cached = self
+            .cache
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+            .copied();
+
+        let file = match cached {
+            Some(hashed) if !self.hot_reload => return Ok(hashed.digest),
+            _ => path::resolve(self.dir.root(), &relative)?,
+        };
+        let modified = fs::metadata(&file)?.modified().ok();
+        if let Some(hashed) =
+            cached.filter(|hashed| modified.is_some() && hashed.modified == modified)
+        {
+            return Ok(hashed.digest);
+        }
+        let hashed = Hashed {
+            digest: sha256(&fs::read(&file)?),
+            modified,
+        };
+        self.cache
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, hashed);
+        Ok(hashed.digest)
+    }
+
+    // Maps a fingerprinted request path back to the file it names, if the fingerprint
+    // matches the file's current contents.
+    fn unfingerprint(&self, request_path: &str) -> Option<String> {
+        let (dir, name) = request_path.rsplit_once('/')?;
+        let mut parts: Vec<&str> = name.split('.').collect();
+        let index = match parts.len() {
+            0 | 1 => return None,
+            2 => 1,
+            n => n - 2,
+        };
+        let fingerprint = parts.remove(index);
+        let is_fingerprint = fingerprint.len() == FINGERPRINT_LEN
+            && fingerprint
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+        if !is_fingerprint || parts[0].is_empty() {
+            return None;
+        }
+        let original = format!("{dir}/{}", parts.join("."));
+        let digest = self.digest(&original).ok()?;
+        (to_hex(&digest)[..FINGERPRINT_LEN] == *fingerprint).then_some(original)
+    }
+}
+
+impl Middleware for Assets {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        ctx.extensions_mut().insert(self.clone());
+        let method = ctx.request().method().clone();
+        let path = ctx.request().path();
+        let rest = match path.strip_prefix(self.prefix.as_str()) {
+            Some(rest) if rest.starts_with('/') && matches!(method, Method::Get | Method::Head) => {
+                rest.to_owned()
+            }
+            _ => return next.run(ctx),
+        };
+
+        let this = self.clone();
+        Box::pin(async move {
+            let accept_encoding = ctx
+                .request()
+                .headers()
+                .get("accept-encoding")
+                .map(str::to_owned);
+            let lookup = this.clone();
+            let original =
+                tokio::task::spawn_blocking(move || lookup.unfingerprint(&rest).ok_or(rest))
+                    .await
+                    .ok();
+            let mut response = match original {
+                Some(Ok(original)) => this
+                    .dir
+                    .serve_encoded(&original, accept_encoding.as_deref())
+                    .await
+                    .header("Cache-Control", "public, max-age=31536000, immutable"),
+                Some(Err(rest)) => {
+                    let response = this
+                        .dir
+                        .serve_encoded(&rest, accept_encoding.as_deref())
+                        .await;
+                    if response.status() == StatusCode::NotFound {
+                        return next.run(ctx).await;
+                    }
+                    response
+                }
+                None => return next.run(ctx).await,
+            };
+            if method == Method::Head {
+                response = response.body_bytes(Vec::new());
+            }
+            response
+        })
+    }
+}
+
+/// Access to the [`Assets`] installed as middleware.
+pub trait AssetsExt {
+    /// Returns the assets installed by the [`Assets`] middleware, if any.
+    fn assets(&self) -> Option<&Assets>;
+}
+
+impl AssetsExt for Context {
+    fn assets(&self) -> Option<&Assets> {
+        self.extensions().get::<Assets>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{
+        Request,
+        middleware::{MiddlewareHandler, from_middleware},
+        security::crypto::random_token,
+    };
+
+    // A directory holding one script and one stylesheet, removed on drop.
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new() -> Self {
+            let root = std::env::temp_dir().join(format!("rttp-assets-{}", random_token(8)));
+            fs::create_dir_all(root.join("js")).unwrap();
+            fs::write(root.join("js/app.min.js"), "alert(1)").unwrap();
+            fs::write(root.join("site.css"), "body{}").unwrap();
+            fs::write(root.join(".env"), "SECRET=1").unwrap();
+            Self(root)
+        }
+
+        fn assets(&self) -> Assets {
+            Assets::new(ServeDir::new(&self.0), "/static/")
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    async fn get(assets: Assets, path: &str) -> Response {
+        let raw = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let (req, _) = Request::parse(raw.as_bytes()).unwrap();
+        let fallback: MiddlewareHandler = Arc::new(|_ctx: Context, _next: Next| {
+            Box::pin(async { Response::new(StatusCode::Ok).body("next") })
+        });
+        Next::new(vec![from_middleware(Arc::new(assets)), fallback])
+            .run(Context::new(req))
+            .await
+    }
+
+    #[test]
+    fn integrity_and_url() {
+        let fixture = Fixture::new();
+        let assets = fixture.assets();
+        let digest = sha256(b"alert(1)");
+        let hex = &to_hex(&digest)[..8];
+
+        assert_eq!(
+            assets.integrity("js/app.min.js").unwrap(),
+            format!("sha256-{}", base64_encode(&digest))
+        );
+        assert_eq!(
+            assets.url("/js/app.min.js").unwrap(),
+            format!("/static/js/app.min.{hex}.js")
+        );
+        assert_eq!(
+            assets.script_tag("js/app.min.js").unwrap(),
+            format!(
+                r#"<script src="/static/js/app.min.{hex}.js" integrity="sha256-{}" crossorigin="anonymous"></script>"#,
+                base64_encode(&digest)
+            )
+        );
+        assert!(
+            assets
+                .stylesheet_tag("site.css")
+                .unwrap()
+                .starts_with(r#"<link rel="stylesheet" href="/static/site."#)
+        );
+        assert!(assets.url("missing.js").unwrap_err().is_not_found());
+        assert!(assets.url(".env").unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn changed_files_are_rehashed_with_hot_reload() {
+        let fixture = Fixture::new();
+        let assets = fixture.assets().hot_reload(true);
+        let before = assets.url("site.css").unwrap();
+
+        fs::write(fixture.0.join("site.css"), "body{color:red}").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(fixture.0.join("site.css"))
+            .unwrap()
+            .set_modified(SystemTime::now() + std::time::Duration::from_secs(5))
+            .unwrap();
+        assert_ne!(assets.url("site.css").unwrap(), before);
+
+        let frozen = fixture.assets().hot_reload(false);
+        let url = frozen.url("site.css").unwrap();
+        fs::write(fixture.0.join("site.css"), "body{}").unwrap();
+        assert_eq!(frozen.url("site.css").unwrap(), url);
+    }
+
+    #[tokio::test]
+    async fn middleware_serves_fingerprinted_urls_as_immutable() {
+        let fixture = Fixture::new();
+        let assets = fixture.assets();
+        let url = assets.url("js/app.min.js").unwrap();
+
+        let response = get(assets.clone(), &url).await;
+        assert_eq!(response.body_ref(), b"alert(1)");
+        assert_eq!(
+            response.headers().get("cache-control"),
+            Some("public, max-age=31536000, immutable")
+        );
+
+        let response = get(assets.clone(), "/static/site.css").await;
+        assert_eq!(response.body_ref(), b"body{}");
+        assert!(!response.headers().contains("cache-control"));
+
+        // A stale fingerprint does not name a file, so the request falls through.
+        let stale = get(assets.clone(), "/static/js/app.min.00000000.js").await;
+        assert_eq!(stale.body_ref(), b"next");
+        assert_eq!(get(assets, "/other").await.body_ref(), b"next");
+    }
+}
@@ -0,0 +1,599 @@
+//! Static file serving.
+//!
+//! [`ServeDir`] maps request paths onto a directory on disk. Every path goes through
+//! [`security::path`](crate::security::path) first, so percent-encoded `..` segments,
+//! double encoding, and symlinks pointing outside the root cannot be used to read
+//! arbitrary files.
+//!
+//! It can be used two ways:
+//!
+//! - **As middleware**, in front of the router: `GET`/`HEAD` requests whose path names an
+//!   existing file are answered directly; everything else falls through to the next layer.
+//! - **As a route handler** for a wildcard route such as `/files/*`, via
+//!   [`ServeDir::serve`]: the wildcard suffix is resolved under the root and misses
+//!   become `404 Not Found`.
+//!
+//! With [`precompressed`](ServeDir::precompressed), a file's `.br`, `.zst`, or `.gz`
+//! sibling — built ahead of time alongside the assets — is sent instead when the
+//! request's `Accept-Encoding` allows it, so nothing is compressed per request.
+//!
+//! With [`spa_fallback`](ServeDir::spa_fallback), paths that match neither a file nor a
+//! route are answered with a single-page app's shell, so client-side routes such as
+//! `/settings/profile` survive a reload. Paths that look like assets (`/logo.png`) and
+//! API paths (`/api/…`) still get `404 Not Found`.
+//!
+//! [`Assets`] adds cache-busting on top: it fingerprints file names with a content hash,
+//! computes [subresource integrity](https://www.w3.org/TR/SRI/) values, and renders
+//! `<script>` and `<link>` tags for templates.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rttp::{Router, context::Context, files::ServeDir};
+//!
+//! let assets = ServeDir::new("public");
+//! let mut router = Router::new();
+//! router.get("/files/*", move |ctx: Context| {
+//!     let assets = assets.clone();
+//!     async move { assets.serve(ctx.params().get("wildcard").unwrap_or("/")).await }
+//! });
+//! ```
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    pin::Pin,
+};
+
+use tracing::{error, warn};
+
+use crate::{
+    Method, Response, StatusCode,
+    context::Context,
+    http::{Mime, headers::parse_quality_list},
+    middleware::{Middleware, Next},
+    security::path::{self, PathError},
+};
+
+mod assets;
+
+pub use assets::{Assets, AssetsExt};
+
+/// Serves files from a directory.
+///
+/// Directory requests are answered with the directory's index file (`index.html` by
+/// default); there are no directory listings. Hidden files and directories (any segment
+/// starting with `.`, such as `.env` or `.git/`) are not served unless
+/// [`allow_hidden`](Self::allow_hidden) is set.
+#[derive(Debug, Clone)]
+pub struct ServeDir {
+    root: PathBuf,
+    index: Option<String>,
+    allow_hidden: bool,
+    precompressed: bool,
+    spa: Option<String>,
+    spa_excludes: Vec<String>,
+}
+
+// Content codings with precompressed siblings, in order of preference at equal weight.
+const ENCODINGS: [(&str, &str); 3] = [("br", "br"), ("zstd", "zst"), ("gzip", "gz")];
+
+// A file to send, and the content coding of its body if it is a compressed sibling.
+struct Loaded {
+    file: PathBuf,
+    body: Vec<u8>,
+    encoding: Option<&'static str>,
+}
+
+impl ServeDir {
+    /// Serves files under `root`. The directory is resolved per request, so it need not
+    /// exist yet.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            index: Some("index.html".to_owned()),
+            allow_hidden: false,
+            precompressed: false,
+            spa: None,
+            spa_excludes: vec!["/api".to_owned()],
+        }
+    }
+
+    /// Sets the file served for directory requests (default `index.html`).
+    #[must_use]
+    pub fn index_file(mut self, name: impl Into<String>) -> Self {
+        self.index = Some(name.into());
+        self
+    }
+
+    /// Answers directory requests with `404 Not Found` instead of an index file.
+    #[must_use]
+    pub fn without_index(mut self) -> Self {
+        self.index = None;
+        self
+    }
+
+    /// Allows serving dotfiles and files inside dot-directories.
+    #[must_use]
+    pub fn allow_hidden(mut self, allow: bool) -> Self {
+        self.allow_hidden = allow;
+        self
+    }
+
+    /// Serves a file's precompressed sibling — `app.js.br`, `app.js.zst`, or
+    /// `app.js.gz` for `app.js` — when the request's `Accept-Encoding` allows it.
+    ///
+    /// The response keeps the original file's `Content-Type`, adds `Content-Encoding`,
+    /// and carries `Vary: Accept-Encoding` either way so caches keep the variants apart.
+    /// The uncompressed file must exist; siblings alone are never served.
+    #[must_use]
+    pub fn precompressed(mut self, enabled: bool) -> Self {
+        self.precompressed = enabled;
+        self
+    }
+
+    /// Answers unmatched requests for non-asset paths with `shell`, a file under the
+    /// root such as `index.html`, with `200 OK` and `Cache-Control: no-cache`.
+    ///
+    /// As middleware, a request falls back only after the rest of the chain answered
+    /// `404 Not Found`, so routes registered on the router keep working. A path falls
+    /// back when its last segment has no extension and it is not under an excluded
+    /// prefix — `/api` by default; see [`spa_exclude`](Self::spa_exclude).
+    #[must_use]
+    pub fn spa_fallback(mut self, shell: impl Into<String>) -> Self {
+        self.spa = Some(shell.into());
+        self
+    }
+
+    /// Keeps paths under `prefix` (such as `/auth`) from falling back to the SPA shell,
+    /// in addition to `/api`.
+    #[must_use]
+    pub fn spa_exclude(mut self, prefix: impl Into<String>) -> Self {
+        self.spa_excludes
+            .push(prefix.into().trim_end_matches('/').to_owned());
+        self
+    }
+
+    /// Returns the configured root directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Serves the file at `request_path` (still percent-encoded) with a `GET` response.
+    ///
+    /// Malformed or traversing paths get `400 Bad Request`; missing files, hidden files,
+    /// and paths escaping the root get `404 Not Found`.
+    pub async fn serve(&self, request_path: &str) -> Response {
+        self.serve_encoded(request_path, None).await
+    }
+
+    /// Like [`serve`](Self::serve), choosing a [precompressed](Self::precompressed)
+    /// sibling by the request's `Accept-Encoding` header value.
+    pub async fn serve_encoded(
+        &self,
+        request_path: &str,
+        accept_encoding: Option<&str>,
+    ) -> Response {
+        match self.load(request_path, accept_encoding).await {
+            Ok(loaded) => self.file_response(loaded),
+            Err(e) if e.is_not_found() && self.falls_back(request_path) => {
+                self.shell(accept_encoding).await
+            }
+            Err(e) => error_response(&e),
+        }
+    }
+
+    // Whether an unmatched `request_path` is answered with the SPA shell.
+    fn falls_back(&self, request_path: &str) -> bool {
+        let excluded = self.spa_excludes.iter().any(|prefix| {
+            request_path
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        });
+        let last = request_path.rsplit('/').next().unwrap_or_default();
+        self.spa.is_some() && !excluded && !last.contains('.')
+    }
+
+    async fn shell(&self, accept_encoding: Option<&str>) -> Response {
+        let Some(shell) = &self.spa else {
+            return Response::new(StatusCode::NotFound).body("Not Found");
+        };
+        match self.load(shell, accept_encoding).await {
+            Ok(loaded) => self
+                .file_response(loaded)
+                .header("Cache-Control", "no-cache"),
+            Err(e) => error_response(&e),
+        }
+    }
+
+    // Resolves and reads the file on a blocking thread.
+    async fn load(
+        &self,
+        request_path: &str,
+        accept_encoding: Option<&str>,
+    ) -> Result<Loaded, PathError> {
+        let relative = path::sanitize(request_path)?;
+        if !self.allow_hidden && is_hidden(&relative) {
+            return Err(io::Error::from(io::ErrorKind::NotFound).into());
+        }
+
+        let encodings = if self.precompressed {
+            accepted_encodings(accept_encoding)
+        } else {
+            Vec::new()
+        };
+        let root = self.root.clone();
+        let index = self.index.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut relative = relative;
+            let mut file = path::resolve(&root, &relative)?;
+            if file.is_dir() {
+                let index = index.ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+                relative = relative.join(index);
+                file = path::resolve(&root, &relative)?;
+            }
+            if !file.is_file() {
+                return Err(io::Error::from(io::ErrorKind::NotFound).into());
+            }
+            for (encoding, extension) in encodings {
+                let mut sibling = relative.clone().into_os_string();
+                sibling.push(".");
+                sibling.push(extension);
+                // Resolved like any request path, so a sibling cannot be a way out.
+                if let Ok(sibling) = path::resolve(&root, Path::new(&sibling)) {
+                    if let Ok(body) = fs::read(&sibling) {
+                        return Ok(Loaded {
+                            file,
+                            body,
+                            encoding: Some(encoding),
+                        });
+                    }
+                }
+            }
+            let body = fs::read(&file)?;
+            Ok(Loaded {
+                file,
+                body,
+                encoding: None,
+            })
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+
+    fn file_response(&self, loaded: Loaded) -> Response {
+        let mut response = Response::new(StatusCode::Ok)
+            .header("Content-Type", Mime::from_path(&loaded.file).to_string())
+            .body_bytes(loaded.body);
+        if let Some(encoding) = loaded.encoding {
+            response.add_header("Content-Encoding", encoding);
+        }
+        if self.precompressed {
+            response.add_header("Vary", "Accept-Encoding");
+        }
+        response
+    }
+}
+
+impl Middleware for ServeDir {
+    fn handle(&self, ctx: Context, next: Next) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let method = ctx.request().method().clone();
+        if !matches!(method, Method::Get | Method::Head) {
+            return next.run(ctx);
+        }
+
+        let this = self.clone();
+        Box::pin(async move {
+            let request = ctx.request();
+            let path = request.path().to_owned();
+            let accept_encoding = request.headers().get("accept-encoding").map(str::to_owned);
+            let mut response = match this.load(&path, accept_encoding.as_deref()).await {
+                Ok(loaded) => this.file_response(loaded),
+                // Not ours: let the router (or whatever comes next) handle it.
+                Err(PathError::Traversal | PathError::Encoding | PathError::InvalidCharacter) => {
+                    return next.run(ctx).await;
+                }
+                Err(e) if e.is_not_found() => {
+                    let response = next.run(ctx).await;
+                    if response.status() != StatusCode::NotFound || !this.falls_back(&path) {
+                        return response;
+                    }
+                    this.shell(accept_encoding.as_deref()).await
+                }
+                Err(e) => error_response(&e),
+            };
+            if method == Method::Head {
+                response = response.body_bytes(Vec::new());
+            }
+            response
+        })
+    }
+}
+
+fn is_hidden(relative: &Path) -> bool {
+    relative
+        .components()
+        .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+}
+
+// The content codings with a precompressed sibling that `accept_encoding` allows, best
+// first: by `q` weight, then by `ENCODINGS` order. `*` stands for any coding not named.
+fn accepted_encodings(accept_encoding: Option<&str>) -> Vec<(&'static str, &'static str)> {
+    let weights = parse_quality_list(accept_encoding.unwrap_or_default());
+    let weight = |name: &str| {
+        weights
+            .iter()
+            .find(|coding| coding.value.eq_ignore_ascii_case(name))
+            .or_else(|| weights.iter().find(|coding| coding.value == "*"))
+            .map_or(0.0, |coding| coding.quality)
+    };
+
+    let mut accepted: Vec<(f32, (&str, &str))> = ENCODINGS
+        .iter()
+        .map(|&encoding| (weight(encoding.0), encoding))
+        .filter(|(q, _)| *q > 0.0)
+        .collect();
+    // Stable, so equal weights keep the preference order.
+    accepted.sort_by(|a, b| b.0.total_cmp(&a.0));
+    accepted.into_iter().map(|(_, encoding)| encoding).collect()
+}
+
+fn error_response(e: &PathError) -> Response {
+    match e {
+        PathError::Traversal | PathError::Encoding | PathError::InvalidCharacter => {
+            Response::new(StatusCode::BadRequest).body("Bad Request")
+        }
+        PathError::Escape => {
+            warn!("static file request resolved outside the root directory");
+            Response::new(StatusCode::NotFound).body("Not Found")
+        }
+        PathError::Io(e) if e.kind() == io::ErrorKind::NotFound => {
+            Response::new(StatusCode::NotFound).body("Not Found")
+        }
+        PathError::Io(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            Response::new(StatusCode::Forbidden).body("Forbidden")
+        }
+        PathError::Io(e) => {
+            error!(error = %e, "failed to read static file");
+            Response::new(StatusCode::InternalServerError).body("Internal Server Error")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{Request, Router, middleware::from_middleware, security::crypto::random_token};
+
+    // A populated directory under the system temp dir, removed on drop.
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new() -> Self {
+            let root = std::env::temp_dir().join(format!("rttp-files-{}", random_token(8)));
+            fs::create_dir_all(root.join("docs")).unwrap();
+            fs::create_dir_all(root.join(".git")).unwrap();
+            fs::write(root.join("index.html"), "<h1>home</h1>").unwrap();
+            fs::write(root.join("docs/readme.txt"), "read me").unwrap();
+            fs::write(root.join("docs/app.JS"), "run()").unwrap();
+            fs::write(root.join(".env"), "SECRET=1").unwrap();
+            fs::write(root.join(".git/config"), "[core]").unwrap();
+            Self(root)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    async fn through_middleware(dir: ServeDir, method: &str, path: &str) -> Response {
+        let raw = format!("{method} {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let (req, _) = Request::parse(raw.as_bytes()).unwrap();
+        let mut router = Router::new();
+        router.post("/docs/readme.txt", |_ctx| async {
+            Response::new(StatusCode::Created)
+        });
+        let chain = vec![
+            from_middleware(Arc::new(dir)),
+            from_middleware(Arc::new(router)),
+        ];
+        Next::new(chain).run(Context::new(req)).await
+    }
+
+    // ── serve ─────────────────────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn serves_files_with_content_type() {
+        let fixture = Fixture::new();
+        let dir = ServeDir::new(&fixture.0);
+
+        let response = dir.serve("/docs/readme.txt").await;
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert_eq!(response.body_ref(), b"read me");
+        assert_eq!(
+            response.headers().get("content-type"),
+            Some("text/plain; charset=utf-8")
+        );
+
+        let response = dir.serve("/docs/app.JS").await;
+        assert_eq!(
+            response.headers().get("content-type"),
+            Some("text/javascript; charset=utf-8")
+        );
+    }
+
+    #[tokio::test]
+    async fn directories_use_index_file() {
+        let fixture = Fixture::new();
+        let response = ServeDir::new(&fixture.0).serve("/").await;
+        assert_eq!(response.body_ref(), b"<h1>home</h1>");
+
+        let response = ServeDir::new(&fixture.0).without_index().serve("/").await;
+        assert_eq!(response.status(), StatusCode::NotFound);
+        let response = ServeDir::new(&fixture.0).serve("/docs").await;
+        assert_eq!(response.status(), StatusCode::NotFound);
+    }
+
+    #[tokio::test]
+    async fn traversal_is_rejected() {
+        let fixture = Fixture::new();
+        let dir = ServeDir::new(fixture.0.join("docs"));
+        for path in [
+            "/../index.html",
+            "/%2e%2e/index.html",
+            "/%252e%252e/index.html",
+        ] {
+            assert_eq!(
+                dir.serve(path).await.status(),
+                StatusCode::BadRequest,
+                "{path}"
+            );
+        }
+        assert_eq!(dir.serve("/nope.txt").await.status(), StatusCode::NotFound);
+    }
+
+    #[tokio::test]
+    async fn hidden_files_are_not_served_by_default() {
+        let fixture = Fixture::new();
+        let dir = ServeDir::new(&fixture.0);
+        assert_eq!(dir.serve("/.env").await.status(), StatusCode::NotFound);
+        assert_eq!(
+            dir.serve("/.git/config").await.status(),
+            StatusCode::NotFound
+        );
+
+        let dir = dir.allow_hidden(true);
+        assert_eq!(dir.serve("/.env").await.body_ref(), b"SECRET=1");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlink_escape_is_not_found() {
+        let fixture = Fixture::new();
+        let outside = Fixture::new();
+        std::os::unix::fs::symlink(outside.0.join(".env"), fixture.0.join("docs/leak")).unwrap();
+        let dir = ServeDir::new(&fixture.0);
+        assert_eq!(dir.serve("/docs/leak").await.status(), StatusCode::NotFound);
+    }
+
+    // ── Middleware ────────────────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn middleware_serves_hits_and_falls_through_on_misses() {
+        let fixture = Fixture::new();
+        let dir = ServeDir::new(&fixture.0);
+
+        let response = through_middleware(dir.clone(), "GET", "/docs/readme.txt").await;
+        assert_eq!(response.body_ref(), b"read me");
+
+        let response = through_middleware(dir.clone(), "POST", "/docs/readme.txt").await;
+        assert_eq!(response.status(), StatusCode::Created);
+
+        let response = through_middleware(dir.clone(), "GET", "/%2e%2e/etc/passwd").await;
+        assert_eq!(response.status(), StatusCode::NotFound);
+
+        let response = through_middleware(dir, "GET", "/missing").await;
+        assert_eq!(response.status(), StatusCode::NotFound);
+    }
+
+    #[tokio::test]
+    async fn middleware_head_has_no_body() {
+        let fixture = Fixture::new();
+        let response =
+            through_middleware(ServeDir::new(&fixture.0), "HEAD", "/docs/readme.txt").await;
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert!(response.body_ref().is_empty());
+    }
+
+    #[tokio::test]
+    async fn wildcard_route_handler() {
+        let fixture = Fixture::new();
+        let dir = ServeDir::new(fixture.0.join("docs"));
+        let mut router = Router::new();
+        router.get("/files/*", move |ctx: Context| {
+            let dir = dir.clone();
+            async move { dir.serve(ctx.params().get("wildcard").unwrap_or("/")).await }
+        });
+
+        let get = |path: &str| {
+            let raw = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+            Request::parse(raw.as_bytes()).unwrap().0
+        };
+        let response = router.route(get("/files/readme.txt")).await;
+        assert_eq!(response.body_ref(), b"read me");
+        let response = router.route(get("/files/..%2findex.html")).await;
+        assert_eq!(response.status(), StatusCode::BadRequest);
+    }
+
+    // ── spa_fallback ──────────────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn spa_fallback_serves_the_shell_for_unrouted_pages() {
+        let fixture = Fixture::new();
+        let dir = || ServeDir::new(&fixture.0).spa_fallback("index.html");
+
+        let response = through_middleware(dir(), "GET", "/settings/profile").await;
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert_eq!(response.body_ref(), b"<h1>home</h1>");
+        assert_eq!(response.headers().get("cache-control"), Some("no-cache"));
+        let response = through_middleware(dir(), "HEAD", "/settings").await;
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert!(response.body_ref().is_empty());
+
+        for path in ["/logo.png", "/api/users", "/api", "/auth/callback"] {
+            let dir = dir().spa_exclude("/auth/");
+            let response = through_middleware(dir, "GET", path).await;
+            assert_eq!(response.status(), StatusCode::NotFound, "{path}");
+        }
+        // Non-GET requests and routed paths still reach the router.
+        let response = through_middleware(dir(), "POST", "/docs/readme.txt").await;
+        assert_eq!(response.status(), StatusCode::Created);
+        let response = through_middleware(dir(), "GET", "/apiary").await;
+        assert_eq!(response.status(), StatusCode::Ok);
+
+        let response = dir().serve("/dashboard").await;
+        assert_eq!(response.body_ref(), b"<h1>home</h1>");
+    }
+
+    // ── precompressed ─────────────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn precompressed_siblings_follow_accept_encoding() {
+        let fixture = Fixture::new();
+        fs::write(fixture.0.join("docs/app.JS.br"), "brotli").unwrap();
+        fs::write(fixture.0.join("docs/app.JS.gz"), "gzip").unwrap();
+        fs::write(fixture.0.join("docs/orphan.js.gz"), "gzip").unwrap();
+        let dir = ServeDir::new(&fixture.0).precompressed(true);
+
+        let response = dir.serve_encoded("/docs/app.JS", Some("gzip, br")).await;
+        assert_eq!(response.body_ref(), b"brotli");
+        assert_eq!(response.headers().get("content-encoding"), Some("br"));
+        assert_eq!(
+            response.headers().get("content-type"),
+            Some("text/javascript; charset=utf-8")
+        );
+        assert_eq!(response.headers().get("vary"), Some("Accept-Encoding"));
+
+        let response = dir
+            .serve_encoded("/docs/app.JS", Some("br;q=0.5, gzip"))
+            .await;
+        assert_eq!(response.headers().get("content-encoding"), Some("gzip"));
+        let response = dir.serve_encoded("/docs/app.JS", Some("zstd")).await;
+        assert_eq!(response.body_ref(), b"run()");
+        assert_eq!(response.headers().get("content-encoding"), None);
+        let response = dir.serve_encoded("/docs/app.JS", Some("*, br;q=0")).await;
+        assert_eq!(response.headers().get("content-encoding"), Some("gzip"));
+
+        let response = dir.serve_encoded("/docs/orphan.js", Some("gzip")).await;
+        assert_eq!(response.status(), StatusCode::NotFound);
+        let plain = ServeDir::new(&fixture.0)
+            .serve_encoded("/docs/app.JS", Some("br"))
+            .await;
+        assert_eq!(plain.body_ref(), b"run()");
+        assert_eq!(plain.headers().get("vary"), None);
+    }
+}
@@ -0,0 +1,396 @@
+//! X.509 client certificates as seen by a request.
+//!
+//! A TLS listener that verifies client certificates parses the chain into
+//! [`PeerCertificate`]s and records it on the request's
+//! [`ConnectionInfo`](super::ConnectionInfo).
+//!
+//! Parsing is deliberately shallow: it extracts the subject, issuer, serial number, and
+//! subject alternative names from an already-verified DER certificate. Chain validation
+//! and signature checks are done by the TLS stack.
+
+use std::{fmt, sync::Arc};
+
+use crate::security::crypto::{sha256, to_hex};
+
+/// A parsed X.509 distinguished name (subject or issuer).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DistinguishedName {
+    // `(short name, value)` pairs in certificate order, e.g. `("CN", "alice")`.
+    attributes: Vec<(String, String)>,
+}
+
+impl DistinguishedName {
+    /// Returns the first value of the attribute with the given short name (`"CN"`, `"O"`,
+    /// `"OU"`, `"C"`, …) or dotted OID for attributes without a short name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the common name (`CN`).
+    pub fn common_name(&self) -> Option<&str> {
+        self.get("CN")
+    }
+
+    /// Returns the organization (`O`).
+    pub fn organization(&self) -> Option<&str> {
+        self.get("O")
+    }
+
+    /// Returns all attributes in certificate order.
+    pub fn attributes(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.attributes
+            .iter()
+            .map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+}
+
+/// Renders the name in RFC 4514 form (most specific attribute first, e.g.
+/// `CN=alice,O=Example Corp,C=US`).
+impl fmt::Display for DistinguishedName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, value)) in self.attributes.iter().rev().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{name}=")?;
+            for (j, c) in value.chars().enumerate() {
+                let leading = j == 0 && matches!(c, ' ' | '#');
+                let trailing = j + 1 == value.chars().count() && c == ' ';
+                if leading || trailing || matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';') {
+                    f.write_str("\\")?;
+                }
+                write!(f, "{c}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A subject alternative name entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubjectAltName {
+    /// `dNSName`.
+    Dns(String),
+    /// `rfc822Name` (email address).
+    Email(String),
+    /// `uniformResourceIdentifier`, e.g. a SPIFFE id `spiffe://example.org/ns/web`.
+    Uri(String),
+    /// `iPAddress`.
+    Ip(std::net::IpAddr),
+}
+
+/// Identity information extracted from a verified client certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerCertificate {
+    der: Arc<[u8]>,
+    serial: Vec<u8>,
+    subject: DistinguishedName,
+    issuer: DistinguishedName,
+    subject_alt_names: Vec<SubjectAltName>,
+}
+
+impl PeerCertificate {
+    /// Parses a DER-encoded X.509 certificate.
+    ///
+    /// Returns `None` if the structure is not a well-formed certificate.
+    pub fn from_der(der: &[u8]) -> Option<Self> {
+        let (cert, _) = Der::new(der).expect(TAG_SEQUENCE)?;
+        let (mut tbs, _) = cert.expect(TAG_SEQUENCE)?;
+
+        // version [0] EXPLICIT, optional
+        if tbs.peek() == Some(0xA0) {
+            tbs = tbs.skip()?;
+        }
+        let (serial, rest) = tbs.expect(TAG_INTEGER)?;
+        let rest = rest.skip()?; // signature algorithm
+        let (issuer, rest) = rest.expect(TAG_SEQUENCE)?;
+        let rest = rest.skip()?; // validity
+        let (subject, mut rest) = rest.expect(TAG_SEQUENCE)?;
+        rest = rest.skip()?; // subjectPublicKeyInfo
+
+        let mut subject_alt_names = Vec::new();
+        while let Some(tag) = rest.peek() {
+            let (body, next) = rest.any()?;
+            if tag == 0xA3 {
+                subject_alt_names = parse_extensions(body.expect(TAG_SEQUENCE)?.0)?;
+            }
+            rest = next;
+        }
+
+        Some(Self {
+            der: Arc::from(der),
+            serial: serial.bytes.to_vec(),
+            subject: parse_name(subject)?,
+            issuer: parse_name(issuer)?,
+            subject_alt_names,
+        })
+    }
+
+    /// Returns the certificate in DER form.
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// Returns the serial number as big-endian bytes.
+    pub fn serial(&self) -> &[u8] {
+        &self.serial
+    }
+
+    /// Returns the subject name.
+    pub fn subject(&self) -> &DistinguishedName {
+        &self.subject
+    }
+
+    /// Returns the issuer name.
+    pub fn issuer(&self) -> &DistinguishedName {
+        &self.issuer
+    }
+
+    /// Returns the subject alternative names.
+    pub fn subject_alt_names(&self) -> &[SubjectAltName] {
+        &self.subject_alt_names
+    }
+
+    /// Returns the lowercase hex SHA-256 fingerprint of the DER encoding, suitable for
+    /// certificate pinning.
+    pub fn fingerprint_sha256(&self) -> String {
+        to_hex(&sha256(&self.der))
+    }
+}
+
+// ── Minimal DER reader ────────────────────────────────────────────────────────
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+
+// A cursor over a run of DER TLV elements.
+#[derive(Clone, Copy)]
+struct Der<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.first().copied()
+    }
+
+    // Reads the next element, returning its tag, contents, and the remaining input.
+    fn read(self) -> Option<(u8, Der<'a>, Der<'a>)> {
+        let (&tag, rest) = self.bytes.split_first()?;
+        let (&first, rest) = rest.split_first()?;
+        let (len, rest) = if first & 0x80 == 0 {
+            (usize::from(first), rest)
+        } else {
+            let n = usize::from(first & 0x7F);
+            if n == 0 || n > 4 || rest.len() < n {
+                return None;
+            }
+            let len = rest[..n]
+                .iter()
+                .fold(0usize, |acc, &b| (acc << 8) | usize::from(b));
+            (len, &rest[n..])
+        };
+        if rest.len() < len {
+            return None;
+        }
+        Some((tag, Der::new(&rest[..len]), Der::new(&rest[len..])))
+    }
+
+    fn any(self) -> Option<(Der<'a>, Der<'a>)> {
+        self.read().map(|(_, body, rest)| (body, rest))
+    }
+
+    fn expect(self, tag: u8) -> Option<(Der<'a>, Der<'a>)> {
+        let (actual, body, rest) = self.read()?;
+        (actual == tag).then_some((body, rest))
+    }
+
+    fn skip(self) -> Option<Der<'a>> {
+        self.read().map(|(_, _, rest)| rest)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+// Name ::= SEQUENCE OF SET OF SEQUENCE { type OID, value ANY }
+fn parse_name(mut rdns: Der<'_>) -> Option<DistinguishedName> {
+    let mut attributes = Vec::new();
+    while !rdns.is_empty() {
+        let (mut set, rest) = rdns.expect(TAG_SET)?;
+        while !set.is_empty() {
+            let (attr, next) = set.expect(TAG_SEQUENCE)?;
+            let (oid, value) = attr.expect(TAG_OID)?;
+            let (_, value, _) = value.read()?;
+            let name = attribute_name(oid.bytes);
+            attributes.push((name, String::from_utf8_lossy(value.bytes).into_owned()));
+            set = next;
+        }
+        rdns = rest;
+    }
+    Some(DistinguishedName { attributes })
+}
+
+// Short names for the common attribute types under 2.5.4 (id-at); others use dotted OIDs.
+fn attribute_name(oid: &[u8]) -> String {
+    let short = match oid {
+        [0x55, 0x04, 0x03] => "CN",
+        [0x55, 0x04, 0x05] => "serialNumber",
+        [0x55, 0x04, 0x06] => "C",
+        [0x55, 0x04, 0x07] => "L",
+        [0x55, 0x04, 0x08] => "ST",
+        [0x55, 0x04, 0x09] => "street",
+        [0x55, 0x04, 0x0A] => "O",
+        [0x55, 0x04, 0x0B] => "OU",
+        [0x09, 0x92, 0x26, 0x89, 0x93, 0xF2, 0x2C, 0x64, 0x01, 0x19] => "DC",
+        [0x09, 0x92, 0x26, 0x89, 0x93, 0xF2, 0x2C, 0x64, 0x01, 0x01] => "UID",
+        _ => return dotted_oid(oid),
+    };
+    short.to_owned()
+}
+
+fn dotted_oid(oid: &[u8]) -> String {
+    let Some((&first, rest)) = oid.split_first() else {
+        return String::new();
+    };
+    // The first byte packs the first two arcs as `40 * a + b` (with `a` at most 2).
+    let (a, b) = if first < 80 {
+        (first / 40, first % 40)
+    } else {
+        (2, first - 80)
+    };
+    let mut parts = vec![a.to_string(), b.to_string()];
+    let mut value = 0u64;
+    for &b in rest {
+        value = (value << 7) | u64::from(b & 0x7F);
+        if b & 0x80 == 0 {
+            parts.push(value.to_string());
+            value = 0;
+        }
+    }
+    parts.join(".")
+}
+
+// Extensions ::= SEQUENCE OF Extension; only subjectAltName (2.5.29.17) is read.
+fn parse_extensions(mut extensions: Der<'_>) -> Option<Vec<SubjectAltName>> {
+    let mut names = Vec::new();
+    while !extensions.is_empty() {
+        let (ext, rest) = extensions.expect(TAG_SEQUENCE)?;
+        let (oid, mut body) = ext.expect(TAG_OID)?;
+        if oid.bytes == [0x55, 0x1D, 0x11] {
+            if body.peek() == Some(0x01) {
+                body = body.skip()?; // critical flag
+            }
+            let (value, _) = body.expect(TAG_OCTET_STRING)?;
+            let (mut general_names, _) = value.expect(TAG_SEQUENCE)?;
+            while !general_names.is_empty() {
+                let (tag, name, next) = general_names.read()?;
+                let text = || String::from_utf8_lossy(name.bytes).into_owned();
+                match tag {
+                    0x81 => names.push(SubjectAltName::Email(text())),
+                    0x82 => names.push(SubjectAltName::Dns(text())),
+                    0x86 => names.push(SubjectAltName::Uri(text())),
+                    0x87 => match name.bytes.len() {
+                        4 => {
+                            let octets: [u8; 4] = name.bytes.try_into().ok()?;
+                            names.push(SubjectAltName::Ip(octets.into()));
+                        }
+                        16 => {
+                            let octets: [u8; 16] = name.bytes.try_into().ok()?;
+                            names.push(SubjectAltName::Ip(octets.into()));
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
+                general_names = next;
+            }
+        }
+        extensions = rest;
+    }
+    Some(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use rcgen::{CertificateParams, DnType, KeyPair, SanType};
+
+    use super::*;
+
+    fn certificate() -> PeerCertificate {
+        let mut params = CertificateParams::new(vec!["svc.internal".to_owned()]).unwrap();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "billing");
+        params
+            .distinguished_name
+            .push(DnType::OrganizationName, "Example, Inc.");
+        params
+            .subject_alt_names
+            .push(SanType::Rfc822Name("ops@example.com".try_into().unwrap()));
+        params.subject_alt_names.push(SanType::URI(
+            "spiffe://example.org/billing".try_into().unwrap(),
+        ));
+        params
+            .subject_alt_names
+            .push(SanType::IpAddress([10, 0, 0, 1].into()));
+        let key = KeyPair::generate().unwrap();
+        let cert = params.self_signed(&key).unwrap();
+        PeerCertificate::from_der(cert.der()).unwrap()
+    }
+
+    #[test]
+    fn parses_subject_and_sans() {
+        let cert = certificate();
+        assert_eq!(cert.subject().common_name(), Some("billing"));
+        assert_eq!(cert.subject().organization(), Some("Example, Inc."));
+        assert_eq!(cert.issuer(), cert.subject());
+        assert_eq!(
+            cert.subject_alt_names(),
+            [
+                SubjectAltName::Dns("svc.internal".into()),
+                SubjectAltName::Email("ops@example.com".into()),
+                SubjectAltName::Uri("spiffe://example.org/billing".into()),
+                SubjectAltName::Ip([10, 0, 0, 1].into()),
+            ]
+        );
+        assert!(!cert.serial().is_empty());
+        assert_eq!(cert.fingerprint_sha256().len(), 64);
+    }
+
+    #[test]
+    fn display_is_rfc4514() {
+        let cert = certificate();
+        assert_eq!(cert.subject().to_string(), "O=Example\\, Inc.,CN=billing");
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(PeerCertificate::from_der(b""), None);
+        assert_eq!(PeerCertificate::from_der(&[0x30, 0x82, 0xFF]), None);
+        assert_eq!(
+            PeerCertificate::from_der(&[0x30, 0x03, 0x02, 0x01, 0x01]),
+            None
+        );
+    }
+
+    #[test]
+    fn dotted_oid_rendering() {
+        // 1.2.840.113549.1.9.1 (emailAddress)
+        assert_eq!(
+            dotted_oid(&[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x01]),
+            "1.2.840.113549.1.9.1"
+        );
+    }
+}
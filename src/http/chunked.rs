@@ -0,0 +1,221 @@
+//! Chunked response bodies and trailers.
+//!
+//! [`Response::chunked`](super::Response::chunked) streams a body with
+//! `Transfer-Encoding: chunked`, so the client can tell a complete body from a cut-off
+//! one, and lets the writer finish with trailer fields computed from what it sent —
+//! a checksum of a large download, say, or how long producing it took. Declare the
+//! trailer names up front with [`Response::trailer`](super::Response::trailer); clients
+//! may ignore trailers they were not told about.
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use super::{Headers, upgrade::Upgraded};
+use crate::security::crypto::{Sha256, base64_encode};
+
+/// Writes a chunked body; each write becomes one chunk.
+///
+/// Handed to the callback of [`Response::chunked`](super::Response::chunked), which
+/// returns it when the body is complete so the final chunk and trailers can be sent.
+pub struct ChunkedWriter {
+    io: Upgraded,
+    // A chunk's size line is written with its data, so a short write would split them;
+    // the whole framed chunk is kept here until it is out.
+    pending: Vec<u8>,
+    written: u64,
+    started: Instant,
+    digest: Option<Sha256>,
+    trailers: Vec<(String, String)>,
+}
+
+impl ChunkedWriter {
+    pub(crate) fn new(io: Upgraded) -> Self {
+        Self {
+            io,
+            pending: Vec::new(),
+            written: 0,
+            started: Instant::now(),
+            digest: None,
+            trailers: Vec::new(),
+        }
+    }
+
+    /// Adds a `Content-Digest: sha-256=:<base64>:` trailer (RFC 9530) covering every
+    /// byte written after this call. Call it before writing anything.
+    pub fn digest_trailer(&mut self) {
+        self.digest = Some(Sha256::new());
+    }
+
+    /// Adds a trailer field, sent after the last chunk.
+    pub fn trailer(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.trailers.push((name.into(), value.into()));
+    }
+
+    /// Returns how many body bytes have been written.
+    pub fn bytes_written(&self) -> u64 {
+        self.written
+    }
+
+    /// Returns the time since the body started.
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.started.elapsed()
+    }
+
+    // Writes the last chunk and the trailers.
+    pub(crate) async fn finish(mut self) -> io::Result<()> {
+        self.flush().await?;
+        if let Some(digest) = self.digest.take() {
+            let value = format!("sha-256=:{}:", base64_encode(&digest.finalize()));
+            self.trailers.push(("Content-Digest".to_owned(), value));
+        }
+        let mut end = b"0\r\n".to_vec();
+        for (name, value) in &self.trailers {
+            let value: String = value.chars().filter(|c| !c.is_control()).collect();
+            end.extend_from_slice(format!("{name}: {value}\r\n").as_bytes());
+        }
+        end.extend_from_slice(b"\r\n");
+        self.io.write_all(&end).await?;
+        self.io.flush().await
+    }
+
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let n = match Pin::new(&mut self.io).poll_write(cx, &self.pending) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(n)) => n,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+            self.pending.drain(..n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for ChunkedWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if !this.pending.is_empty() {
+            // The previous chunk was accepted but not yet sent.
+            match this.poll_pending(cx) {
+                Poll::Ready(Ok(())) => {}
+                other => return other.map_ok(|()| 0),
+            }
+        }
+        if buf.is_empty() {
+            // An empty chunk would end the body.
+            return Poll::Ready(Ok(0));
+        }
+        this.pending = format!("{:x}\r\n", buf.len()).into_bytes();
+        this.pending.extend_from_slice(buf);
+        this.pending.extend_from_slice(b"\r\n");
+        this.written += buf.len() as u64;
+        if let Some(digest) = &mut this.digest {
+            digest.update(buf);
+        }
+        // The chunk is ours now; errors sending it surface on the next write or flush.
+        let _ = this.poll_pending(cx);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match this.poll_pending(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.io).poll_flush(cx),
+            other => other,
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // The body ends with `finish`, not by closing the connection.
+        self.poll_flush(cx)
+    }
+}
+
+impl std::fmt::Debug for ChunkedWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChunkedWriter")
+            .field("written", &self.written)
+            .field("trailers", &self.trailers)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Decodes a complete chunked body, returning the payload and any trailer fields.
+///
+/// Returns `None` if `body` is not well-formed chunked encoding.
+pub(crate) fn decode(mut body: &[u8]) -> Option<(Vec<u8>, Headers)> {
+    let mut payload = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|w| w == b"\r\n")?;
+        let size = std::str::from_utf8(&body[..line_end]).ok()?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            break;
+        }
+        let chunk = body.get(..size)?;
+        payload.extend_from_slice(chunk);
+        body = body.get(size..)?.strip_prefix(b"\r\n")?;
+    }
+    let mut trailers = Headers::new();
+    let text = std::str::from_utf8(body).ok()?;
+    for line in text.split("\r\n").take_while(|line| !line.is_empty()) {
+        let (name, value) = line.split_once(':')?;
+        trailers.insert(name.trim(), value.trim());
+    }
+    Some((payload, trailers))
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use tokio::io::{AsyncReadExt, duplex};
+
+    use super::*;
+    use crate::security::crypto::sha256;
+
+    #[tokio::test]
+    async fn frames_chunks_and_trailers() {
+        let (server, mut client) = duplex(16);
+        let writing = async move {
+            let mut writer = ChunkedWriter::new(Upgraded::new(server, BytesMut::new()));
+            writer.digest_trailer();
+            writer.write_all(b"hello, ").await.unwrap();
+            writer.write_all(b"chunked world").await.unwrap();
+            writer.trailer("X-Rows", writer.bytes_written().to_string());
+            writer.finish().await.unwrap();
+        };
+        let mut raw = Vec::new();
+        let (_, read) = tokio::join!(writing, client.read_to_end(&mut raw));
+        read.unwrap();
+
+        let (payload, trailers) = decode(&raw).unwrap();
+        assert_eq!(payload, b"hello, chunked world");
+        let digest = format!("sha-256=:{}:", base64_encode(&sha256(&payload)));
+        let trailers: Vec<_> = trailers.iter().collect();
+        assert_eq!(
+            trailers,
+            [("X-Rows", "20"), ("Content-Digest", digest.as_str())]
+        );
+        assert!(raw.starts_with(b"7\r\nhello, \r\nd\r\nchunked world\r\n0\r\n"));
+    }
+
+    #[test]
+    fn rejects_malformed_bodies() {
+        assert!(decode(b"5\r\nhi\r\n0\r\n\r\n").is_none());
+        assert!(decode(b"zz\r\n").is_none());
+        let (payload, trailers) = decode(b"0\r\n\r\n").unwrap();
+        assert!(payload.is_empty() && trailers.is_empty());
+    }
+}
@@ -0,0 +1,597 @@
+//! A minimal outbound HTTP/1.1 client, shared by the OAuth, LLM, and webhook
+//! transports.
+//!
+//! [`Client`] opens one connection per request, sends it with `Connection: close`, and
+//! returns the response once its head arrives; the body is then read piece by piece,
+//! with chunked and length-delimited framing removed. `https://` URLs are served over
+//! rustls with the `tls` feature (on by default), verifying the server against the
+//! system's CA bundle or the roots given to [`Client::root_certificates`]. Redirects are
+//! not followed.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rttp::http::{Method, client::Client};
+//!
+//! # async fn example() -> Result<(), rttp::http::client::ClientError> {
+//! let response = Client::new()
+//!     .request(Method::Get, "https://example.com/", &[], b"")
+//!     .await?;
+//! let status = response.status();
+//! let body = response.bytes().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{io, time::Duration};
+
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufStream},
+    net::TcpStream,
+};
+
+use super::{Headers, Method};
+
+/// Errors produced by [`Client`].
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("unsupported URL {0:?}")]
+    InvalidUrl(String),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("TLS error: {0}")]
+    Tls(String),
+
+    #[error("malformed response: {0}")]
+    Malformed(String),
+
+    #[error("request timed out after {0:?}")]
+    Timeout(Duration),
+}
+
+// The connection's transport, type-erased so plain and TLS connections read alike.
+trait Io: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Io for T {}
+
+/// An HTTP/1.1 client opening one connection per request. See the
+/// [module docs](self).
+#[derive(Clone)]
+pub struct Client {
+    timeout: Duration,
+    #[cfg(feature = "tls")]
+    tls: Option<tokio_rustls::TlsConnector>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Client {
+    /// Creates a client with a 30-second timeout that trusts the system's CA bundle.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how long to wait for the response head, and then for each piece of the
+    /// body.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Trusts only the CA certificates in `pem` for `https://` URLs, instead of the
+    /// system's CA bundle.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClientError::Tls`] if `pem` cannot be decoded or holds no
+    /// certificates.
+    #[cfg(feature = "tls")]
+    pub fn root_certificates(mut self, pem: &[u8]) -> Result<Self, ClientError> {
+        self.tls = Some(tls::connector(pem)?);
+        Ok(self)
+    }
+
+    /// Sends `body` to `url` with `method` and `headers`, resolving once the response
+    /// head arrives. `Host`, `Content-Length`, and `Connection` are set by the client.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClientError::InvalidUrl`] for anything but an `http://` or `https://`
+    /// URL, [`ClientError::Tls`] for `https://` without the `tls` feature or when the
+    /// handshake fails, [`ClientError::Timeout`] if the head takes too long, and other
+    /// variants if connecting or parsing the head fails.
+    pub async fn request(
+        &self,
+        method: Method,
+        url: &str,
+        headers: &[(String, String)],
+        body: &[u8],
+    ) -> Result<ClientResponse, ClientError> {
+        let target = parse_url(url).ok_or_else(|| ClientError::InvalidUrl(url.to_owned()))?;
+        tokio::time::timeout(self.timeout, self.send(&method, &target, headers, body))
+            .await
+            .map_err(|_| ClientError::Timeout(self.timeout))?
+    }
+
+    async fn send(
+        &self,
+        method: &Method,
+        target: &Target<'_>,
+        headers: &[(String, String)],
+        body: &[u8],
+    ) -> Result<ClientResponse, ClientError> {
+        let tcp = TcpStream::connect((target.host, target.port)).await?;
+        let io: Box<dyn Io> = if target.tls {
+            self.handshake(target.host, tcp).await?
+        } else {
+            Box::new(tcp)
+        };
+        let mut stream = BufStream::new(io);
+
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            method.as_str(),
+            target.path,
+            target.authority,
+            body.len()
+        );
+        for (name, value) in headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(body).await?;
+        stream.flush().await?;
+
+        // Interim `1xx` heads are skipped.
+        let (status, headers) = loop {
+            let head = read_head(&mut stream).await?;
+            if !(100..200).contains(&head.0) || head.0 == 101 {
+                break head;
+            }
+        };
+
+        let chunked = headers
+            .get_all("transfer-encoding")
+            .any(|value| value.to_ascii_lowercase().contains("chunked"));
+        let framing = if *method == Method::Head || matches!(status, 101 | 204 | 304) {
+            Framing::Done
+        } else if chunked {
+            Framing::Chunked
+        } else if let Some(length) = headers.get("content-length") {
+            let length = length
+                .trim()
+                .parse()
+                .map_err(|_| ClientError::Malformed(format!("bad Content-Length {length:?}")))?;
+            Framing::Length(length)
+        } else {
+            Framing::UntilClose
+        };
+        Ok(ClientResponse {
+            status,
+            headers,
+            stream,
+            framing,
+            timeout: self.timeout,
+        })
+    }
+
+    #[cfg(feature = "tls")]
+    async fn handshake(&self, host: &str, tcp: TcpStream) -> Result<Box<dyn Io>, ClientError> {
+        let connector = match &self.tls {
+            Some(connector) => connector.clone(),
+            None => tls::system()?,
+        };
+        let name = rustls::pki_types::ServerName::try_from(host.to_owned())
+            .map_err(|e| ClientError::Tls(e.to_string()))?;
+        let stream = connector
+            .connect(name, tcp)
+            .await
+            .map_err(|e| ClientError::Tls(e.to_string()))?;
+        Ok(Box::new(stream))
+    }
+
+    #[cfg(not(feature = "tls"))]
+    async fn handshake(&self, _host: &str, _tcp: TcpStream) -> Result<Box<dyn Io>, ClientError> {
+        Err(ClientError::Tls(
+            "https:// needs the `tls` feature".to_owned(),
+        ))
+    }
+}
+
+// Reads a status line and header block.
+async fn read_head(stream: &mut BufStream<Box<dyn Io>>) -> Result<(u16, Headers), ClientError> {
+    let mut status_line = String::new();
+    stream.read_line(&mut status_line).await?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| ClientError::Malformed(format!("bad status line {status_line:?}")))?;
+
+    let mut headers = Headers::new();
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            return Err(ClientError::Malformed(
+                "connection closed in headers".into(),
+            ));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            return Ok((status, headers));
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim(), value.trim());
+        }
+    }
+}
+
+/// A response whose body is read as it arrives.
+pub struct ClientResponse {
+    status: u16,
+    headers: Headers,
+    stream: BufStream<Box<dyn Io>>,
+    framing: Framing,
+    timeout: Duration,
+}
+
+// How the end of the body is marked.
+enum Framing {
+    Chunked,
+    // Bytes remaining.
+    Length(u64),
+    UntilClose,
+    Done,
+}
+
+impl ClientResponse {
+    /// Returns the status code.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the response headers.
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Returns the next piece of the body, with any transfer encoding removed, or
+    /// `None` at its end.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClientError::Timeout`] if no data arrives within the client's timeout,
+    /// and other variants if the connection fails or the framing is malformed.
+    pub async fn chunk(&mut self) -> Result<Option<Vec<u8>>, ClientError> {
+        let timeout = self.timeout;
+        tokio::time::timeout(timeout, self.read_chunk())
+            .await
+            .map_err(|_| ClientError::Timeout(timeout))?
+    }
+
+    /// Reads the rest of the body.
+    ///
+    /// # Errors
+    ///
+    /// See [`chunk`](Self::chunk).
+    pub async fn bytes(mut self) -> Result<Vec<u8>, ClientError> {
+        let mut body = Vec::new();
+        while let Some(chunk) = self.chunk().await? {
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+
+    async fn read_chunk(&mut self) -> Result<Option<Vec<u8>>, ClientError> {
+        match self.framing {
+            Framing::Done => Ok(None),
+            Framing::Chunked => {
+                let mut line = String::new();
+                self.stream.read_line(&mut line).await?;
+                let size = line.trim().split(';').next().unwrap_or_default();
+                let size = usize::from_str_radix(size, 16)
+                    .map_err(|_| ClientError::Malformed(format!("bad chunk size {line:?}")))?;
+                if size == 0 {
+                    // Skip any trailers.
+                    loop {
+                        line.clear();
+                        if self.stream.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+                            break;
+                        }
+                    }
+                    self.framing = Framing::Done;
+                    return Ok(None);
+                }
+                let mut chunk = vec![0; size + 2];
+                self.stream.read_exact(&mut chunk).await?;
+                chunk.truncate(size);
+                Ok(Some(chunk))
+            }
+            Framing::Length(0) => {
+                self.framing = Framing::Done;
+                Ok(None)
+            }
+            Framing::Length(remaining) => {
+                let mut chunk = vec![0; remaining.min(8192) as usize];
+                let n = self.stream.read(&mut chunk).await?;
+                if n == 0 {
+                    return Err(ClientError::Malformed("connection closed mid-body".into()));
+                }
+                chunk.truncate(n);
+                self.framing = Framing::Length(remaining - n as u64);
+                Ok(Some(chunk))
+            }
+            Framing::UntilClose => {
+                let mut chunk = vec![0; 8192];
+                let n = self.stream.read(&mut chunk).await?;
+                if n == 0 {
+                    self.framing = Framing::Done;
+                    return Ok(None);
+                }
+                chunk.truncate(n);
+                Ok(Some(chunk))
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for ClientResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientResponse")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The parts of an absolute `http://` or `https://` URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Target<'a> {
+    pub(crate) tls: bool,
+    /// `host[:port]`, as written.
+    pub(crate) authority: &'a str,
+    /// The host, without brackets around an IPv6 address.
+    pub(crate) host: &'a str,
+    pub(crate) port: u16,
+    /// The request target: the path and query, `/` if the URL has none.
+    pub(crate) path: &'a str,
+}
+
+/// Splits an `http://` or `https://` URL, rejecting user info, fragments, and
+/// whitespace.
+pub(crate) fn parse_url(url: &str) -> Option<Target<'_>> {
+    let (tls, rest) = match url.strip_prefix("https://") {
+        Some(rest) => (true, rest),
+        None => (false, url.strip_prefix("http://")?),
+    };
+    let (authority, path) = match rest.find(['/', '?', '#']) {
+        Some(at) if rest[at..].starts_with('/') => rest.split_at(at),
+        Some(_) => return None,
+        None => (rest, "/"),
+    };
+    if authority.is_empty()
+        || authority.contains(['@', ' '])
+        || path.contains([' ', '#', '\r', '\n'])
+    {
+        return None;
+    }
+    let default_port = if tls { 443 } else { 80 };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, port.parse().ok()?),
+        _ => (authority, default_port),
+    };
+    let host = match host.strip_prefix('[') {
+        Some(v6) => v6.strip_suffix(']')?,
+        None => host,
+    };
+    if host.is_empty() {
+        return None;
+    }
+    Some(Target {
+        tls,
+        authority,
+        host,
+        port,
+        path,
+    })
+}
+
+#[cfg(feature = "tls")]
+mod tls {
+    use std::sync::{Arc, OnceLock};
+
+    use rustls::{
+        ClientConfig, RootCertStore,
+        crypto::ring,
+        pki_types::{CertificateDer, pem::PemObject},
+    };
+    use tokio_rustls::TlsConnector;
+
+    use super::ClientError;
+
+    // Where distributions keep their CA bundle; `SSL_CERT_FILE` takes precedence.
+    const BUNDLES: [&str; 4] = [
+        "/etc/ssl/certs/ca-certificates.crt",
+        "/etc/pki/tls/certs/ca-bundle.crt",
+        "/etc/ssl/ca-bundle.pem",
+        "/etc/ssl/cert.pem",
+    ];
+
+    // A connector trusting the certificates in `pem`.
+    pub(super) fn connector(pem: &[u8]) -> Result<TlsConnector, ClientError> {
+        let mut roots = RootCertStore::empty();
+        for cert in CertificateDer::pem_slice_iter(pem) {
+            let cert = cert.map_err(|e| ClientError::Tls(e.to_string()))?;
+            roots
+                .add(cert)
+                .map_err(|e| ClientError::Tls(e.to_string()))?;
+        }
+        if roots.is_empty() {
+            return Err(ClientError::Tls("no root certificates".to_owned()));
+        }
+        let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| ClientError::Tls(e.to_string()))?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Ok(TlsConnector::from(Arc::new(config)))
+    }
+
+    // A connector trusting the system's CA bundle, loaded once.
+    pub(super) fn system() -> Result<TlsConnector, ClientError> {
+        static SYSTEM: OnceLock<Result<TlsConnector, String>> = OnceLock::new();
+        SYSTEM
+            .get_or_init(|| {
+                let paths = std::env::var("SSL_CERT_FILE").ok();
+                let pem = paths
+                    .iter()
+                    .map(String::as_str)
+                    .chain(BUNDLES)
+                    .find_map(|path| std::fs::read(path).ok())
+                    .ok_or_else(|| "no system CA bundle found; set SSL_CERT_FILE".to_owned())?;
+                connector(&pem).map_err(|e| e.to_string())
+            })
+            .clone()
+            .map_err(ClientError::Tls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    // Serves one canned response, returning the URL and the request it received.
+    async fn serve(response: &'static [u8]) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1/chat?x=1", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"{}") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream.write_all(response).await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn sends_the_request_and_decodes_chunked_bodies() {
+        let (url, server) = serve(
+            b"HTTP/1.1 100 Continue\r\n\r\n\
+              HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nX-Id: 7\r\n\r\n\
+              5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\n\r\n",
+        )
+        .await;
+        let headers = [("X-Test".to_owned(), "1".to_owned())];
+        let response = Client::new()
+            .request(Method::Post, &url, &headers, b"{}")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers().get("x-id"), Some("7"));
+        assert_eq!(response.bytes().await.unwrap(), b"hello, world");
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /v1/chat?x=1 HTTP/1.1\r\n"));
+        assert!(request.contains("Content-Length: 2\r\n"));
+        assert!(request.contains("X-Test: 1\r\n"));
+    }
+
+    #[tokio::test]
+    async fn reads_length_delimited_and_close_delimited_bodies() {
+        let (url, _) =
+            serve(b"HTTP/1.1 429 Too Many Requests\r\nContent-Length: 4\r\n\r\nslowXX").await;
+        let response = Client::new()
+            .request(Method::Post, &url, &[], b"{}")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 429);
+        assert_eq!(response.bytes().await.unwrap(), b"slow");
+
+        let (url, _) = serve(b"HTTP/1.0 200 OK\r\n\r\nuntil close").await;
+        let response = Client::new()
+            .request(Method::Post, &url, &[], b"{}")
+            .await
+            .unwrap();
+        assert_eq!(response.bytes().await.unwrap(), b"until close");
+    }
+
+    #[tokio::test]
+    async fn times_out_waiting_for_the_head() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let client = Client::new().timeout(Duration::from_millis(20));
+        let result = client.request(Method::Get, &url, &[], b"").await;
+        assert!(matches!(result, Err(ClientError::Timeout(_))));
+    }
+
+    #[test]
+    fn parses_urls() {
+        let target = parse_url("http://hooks.example:8080/in?x=1").unwrap();
+        assert_eq!(
+            (
+                target.tls,
+                target.authority,
+                target.host,
+                target.port,
+                target.path
+            ),
+            (
+                false,
+                "hooks.example:8080",
+                "hooks.example",
+                8080,
+                "/in?x=1"
+            )
+        );
+        let target = parse_url("https://[::1]").unwrap();
+        assert_eq!(
+            (
+                target.tls,
+                target.authority,
+                target.host,
+                target.port,
+                target.path
+            ),
+            (true, "[::1]", "::1", 443, "/")
+        );
+        for bad in [
+            "ftp://a",
+            "http://",
+            "http://a?x",
+            "http://u@a/",
+            "http://a:port/",
+            "http://a/#frag",
+            "http://a/b c",
+        ] {
+            assert!(parse_url(bad).is_none(), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Per-connection metadata shared by every request on a connection.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use super::certificate::PeerCertificate;
+
+/// Facts about the transport connection a request arrived on.
+///
+/// The server attaches one `ConnectionInfo` to each connection and hands the same
+/// [`Arc`] to every request read from it; retrieve it with
+/// [`Request::connection`](crate::http::request::Request::connection).
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    peer_addr: SocketAddr,
+    local_addr: SocketAddr,
+    tls: bool,
+    peer_certificates: Arc<[PeerCertificate]>,
+}
+
+impl ConnectionInfo {
+    /// Describes a plain-TCP connection.
+    pub fn new(peer_addr: SocketAddr, local_addr: SocketAddr) -> Self {
+        Self {
+            peer_addr,
+            local_addr,
+            tls: false,
+            peer_certificates: Arc::from([]),
+        }
+    }
+
+    /// Marks the connection as TLS, recording the client's verified certificate chain
+    /// (leaf first; empty if the client presented none).
+    #[must_use]
+    pub fn with_tls(mut self, peer_certificates: Vec<PeerCertificate>) -> Self {
+        self.tls = true;
+        self.peer_certificates = peer_certificates.into();
+        self
+    }
+
+    /// Returns the remote address.
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    /// Returns the local address the connection was accepted on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns `true` if the connection is TLS-encrypted.
+    pub fn is_tls(&self) -> bool {
+        self.tls
+    }
+
+    /// Returns the client certificate chain verified during the TLS handshake, leaf first.
+    ///
+    /// Empty for plain connections and for TLS clients that presented no certificate.
+    pub fn peer_certificates(&self) -> &[PeerCertificate] {
+        &self.peer_certificates
+    }
+
+    /// Returns the verified client (leaf) certificate, if any.
+    pub fn peer_certificate(&self) -> Option<&PeerCertificate> {
+        self.peer_certificates.first()
+    }
+}
@@ -0,0 +1,192 @@
+//! `Set-Cookie` header construction (RFC 6265 §4.1).
+//!
+//! Request cookies are parsed by [`Request::cookie`](super::Request::cookie); this module
+//! covers the response side.
+
+use std::{fmt, time::Duration};
+
+/// The `SameSite` cookie attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    /// Sent only with same-site requests.
+    Strict,
+    /// Sent with same-site requests and top-level cross-site navigations.
+    Lax,
+    /// Sent with all requests; browsers require `Secure` alongside it.
+    None,
+}
+
+impl SameSite {
+    /// Returns the attribute value as written in the header.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Strict => "Strict",
+            Self::Lax => "Lax",
+            Self::None => "None",
+        }
+    }
+}
+
+/// A cookie to be sent in a `Set-Cookie` response header.
+///
+/// The [`Display`](fmt::Display) implementation renders the full header value.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use rttp::http::cookie::{Cookie, SameSite};
+///
+/// let cookie = Cookie::new("sid", "abc123")
+///     .path("/")
+///     .max_age(Duration::from_secs(3600))
+///     .http_only(true)
+///     .secure(true)
+///     .same_site(SameSite::Lax);
+///
+/// assert_eq!(
+///     cookie.to_string(),
+///     "sid=abc123; Path=/; Max-Age=3600; HttpOnly; Secure; SameSite=Lax"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    path: Option<String>,
+    domain: Option<String>,
+    max_age: Option<Duration>,
+    http_only: bool,
+    secure: bool,
+    same_site: Option<SameSite>,
+}
+
+impl Cookie {
+    /// Creates a session cookie (no `Max-Age`) with no attributes set.
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+            path: None,
+            domain: None,
+            max_age: None,
+            http_only: false,
+            secure: false,
+            same_site: None,
+        }
+    }
+
+    /// Creates a cookie that instructs the browser to delete `name` immediately.
+    ///
+    /// The `path` and `domain` must match the original cookie for browsers to remove it.
+    pub fn removal(name: impl Into<String>) -> Self {
+        Self::new(name, "").max_age(Duration::ZERO)
+    }
+
+    /// Sets the `Path` attribute.
+    #[must_use]
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Sets the `Domain` attribute.
+    #[must_use]
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    /// Sets the `Max-Age` attribute (whole seconds).
+    #[must_use]
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Sets or clears the `HttpOnly` flag.
+    #[must_use]
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    /// Sets or clears the `Secure` flag.
+    #[must_use]
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Sets the `SameSite` attribute.
+    #[must_use]
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    /// Returns the cookie name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the cookie value.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl fmt::Display for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(path) = &self.path {
+            write!(f, "; Path={path}")?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={domain}")?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age.as_secs())?;
+        }
+        if self.http_only {
+            f.write_str("; HttpOnly")?;
+        }
+        if self.secure {
+            f.write_str("; Secure")?;
+        }
+        if let Some(same_site) = self.same_site {
+            write!(f, "; SameSite={}", same_site.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_cookie() {
+        assert_eq!(Cookie::new("a", "1").to_string(), "a=1");
+    }
+
+    #[test]
+    fn removal_cookie() {
+        assert_eq!(
+            Cookie::removal("sid").path("/").to_string(),
+            "sid=; Path=/; Max-Age=0"
+        );
+    }
+
+    #[test]
+    fn domain_and_same_site_none() {
+        let c = Cookie::new("a", "1")
+            .domain("example.com")
+            .same_site(SameSite::None)
+            .secure(true);
+        assert_eq!(
+            c.to_string(),
+            "a=1; Domain=example.com; Secure; SameSite=None"
+        );
+    }
+}
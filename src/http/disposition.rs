@@ -0,0 +1,288 @@
+//! `Content-Disposition` construction and parsing (RFC 6266).
+//!
+//! Filenames outside printable ASCII are sent twice: as an ASCII `filename` fallback for
+//! old clients and as an RFC 5987 `filename*` carrying the exact UTF-8 name, which
+//! every current browser prefers.
+
+use std::fmt;
+
+/// Whether the body should be displayed or downloaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispositionType {
+    /// Displayed in the browser when it can.
+    Inline,
+    /// Downloaded and saved.
+    Attachment,
+}
+
+impl DispositionType {
+    /// Returns the type as written in the header.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Inline => "inline",
+            Self::Attachment => "attachment",
+        }
+    }
+}
+
+/// A `Content-Disposition` header value.
+///
+/// The [`Display`](fmt::Display) implementation renders the header value; send it with
+/// [`Response::disposition`](super::Response::disposition).
+///
+/// # Examples
+///
+/// ```
+/// use rttp::http::ContentDisposition;
+///
+/// let disposition = ContentDisposition::attachment().filename("rapport annuel.pdf");
+/// assert_eq!(
+///     disposition.to_string(),
+///     r#"attachment; filename="rapport annuel.pdf""#
+/// );
+///
+/// let disposition = ContentDisposition::attachment().filename("résumé.pdf");
+/// assert_eq!(
+///     disposition.to_string(),
+///     r#"attachment; filename="r_sum_.pdf"; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf"#
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct ContentDisposition {
+    kind: DispositionType,
+    filename: Option<String>,
+}
+
+impl ContentDisposition {
+    /// A disposition of type `kind` with no filename.
+    pub fn new(kind: DispositionType) -> Self {
+        Self {
+            kind,
+            filename: None,
+        }
+    }
+
+    /// `inline`: display the body in the browser.
+    pub fn inline() -> Self {
+        Self::new(DispositionType::Inline)
+    }
+
+    /// `attachment`: ask the browser to download the body.
+    pub fn attachment() -> Self {
+        Self::new(DispositionType::Attachment)
+    }
+
+    /// Suggests `filename` for saving the body.
+    ///
+    /// Only the last path component is kept, so a name can never point the client at a
+    /// directory; control characters are dropped.
+    pub fn filename(mut self, filename: impl AsRef<str>) -> Self {
+        let filename = filename.as_ref();
+        let base = filename.rsplit(['/', '\\']).next().unwrap_or(filename);
+        self.filename = Some(base.chars().filter(|c| !c.is_control()).collect());
+        self
+    }
+
+    /// The disposition type.
+    pub fn kind(&self) -> DispositionType {
+        self.kind
+    }
+
+    /// The suggested filename.
+    pub fn filename_value(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    /// Parses a `Content-Disposition` header value, or returns `None` if it does not
+    /// start with a disposition type.
+    ///
+    /// `filename*` is preferred over `filename` when both are present and it decodes;
+    /// UTF-8 and ISO-8859-1 encodings are supported. Unknown disposition types are
+    /// treated as `attachment`, as RFC 6266 §4.2 requires.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::http::{ContentDisposition, DispositionType};
+    ///
+    /// let parsed = ContentDisposition::parse(
+    ///     r#"attachment; filename="EURO rates"; filename*=utf-8''%e2%82%ac%20rates"#,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(parsed.kind(), DispositionType::Attachment);
+    /// assert_eq!(parsed.filename_value(), Some("€ rates"));
+    /// ```
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = split_params(value).into_iter();
+        let kind = parts.next()?.trim().to_ascii_lowercase();
+        if kind.is_empty() || kind.contains('=') {
+            return None;
+        }
+        let kind = if kind == "inline" {
+            DispositionType::Inline
+        } else {
+            DispositionType::Attachment
+        };
+        let mut plain = None;
+        let mut extended = None;
+        for part in parts {
+            let Some((name, value)) = part.split_once('=') else {
+                continue;
+            };
+            let name = name.trim().to_ascii_lowercase();
+            let value = value.trim();
+            if name == "filename" && plain.is_none() {
+                plain = Some(unquote(value));
+            } else if name == "filename*" && extended.is_none() {
+                extended = decode_ext_value(value);
+            }
+        }
+        let mut disposition = Self::new(kind);
+        disposition.filename = extended.or(plain);
+        Some(disposition)
+    }
+}
+
+impl fmt::Display for ContentDisposition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.kind.as_str())?;
+        let Some(filename) = &self.filename else {
+            return Ok(());
+        };
+        let fallback: String = filename
+            .chars()
+            .map(|c| match c {
+                ' '..='~' if c != '"' && c != '\\' => c,
+                _ => '_',
+            })
+            .collect();
+        write!(f, "; filename=\"{fallback}\"")?;
+        if fallback != *filename {
+            f.write_str("; filename*=UTF-8''")?;
+            for byte in filename.bytes() {
+                match byte {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => write!(f, "{}", byte as char)?,
+                    b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|'
+                    | b'~' => write!(f, "{}", byte as char)?,
+                    _ => write!(f, "%{byte:02X}")?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Splits on `;` outside quoted strings.
+fn split_params(value: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                parts.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+fn unquote(value: &str) -> String {
+    let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) else {
+        return value.to_owned();
+    };
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Decodes an RFC 5987 `charset'language'percent-encoded` value.
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut fields = value.splitn(3, '\'');
+    let charset = fields.next()?.to_ascii_lowercase();
+    let _language = fields.next()?;
+    let encoded = fields.next()?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        if encoded[i] == b'%' {
+            let hex = std::str::from_utf8(encoded.get(i + 1..i + 3)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            bytes.push(encoded[i]);
+            i += 1;
+        }
+    }
+    match charset.as_str() {
+        "utf-8" => String::from_utf8(bytes).ok(),
+        "iso-8859-1" => Some(bytes.into_iter().map(char::from).collect()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_non_ascii_filenames() {
+        assert_eq!(
+            ContentDisposition::attachment()
+                .filename("report.pdf")
+                .to_string(),
+            "attachment; filename=\"report.pdf\""
+        );
+        assert_eq!(
+            ContentDisposition::attachment()
+                .filename("résumé \"v2\".pdf")
+                .to_string(),
+            "attachment; filename=\"r_sum_ _v2_.pdf\"; \
+             filename*=UTF-8''r%C3%A9sum%C3%A9%20%22v2%22.pdf"
+        );
+        assert_eq!(ContentDisposition::inline().to_string(), "inline");
+        assert_eq!(
+            ContentDisposition::inline()
+                .filename("../../etc/pass\nwd")
+                .filename_value(),
+            Some("passwd")
+        );
+    }
+
+    #[test]
+    fn parses_its_own_output_and_common_variants() {
+        let original = ContentDisposition::attachment().filename("日本語; \"x\".txt");
+        assert_eq!(
+            ContentDisposition::parse(&original.to_string()),
+            Some(original)
+        );
+
+        let parsed = ContentDisposition::parse(r#"INLINE; FILENAME="a \"b\"; c.txt""#).unwrap();
+        assert_eq!(parsed.kind(), DispositionType::Inline);
+        assert_eq!(parsed.filename_value(), Some(r#"a "b"; c.txt"#));
+
+        let parsed =
+            ContentDisposition::parse("x-custom; filename*=iso-8859-1'en'%A3.txt").unwrap();
+        assert_eq!(parsed.kind(), DispositionType::Attachment);
+        assert_eq!(parsed.filename_value(), Some("£.txt"));
+
+        let parsed =
+            ContentDisposition::parse("attachment; filename=plain; filename*=bad").unwrap();
+        assert_eq!(parsed.filename_value(), Some("plain"));
+        assert_eq!(ContentDisposition::parse(""), None);
+    }
+}
@@ -0,0 +1,153 @@
+//! File bodies streamed from disk, with single byte ranges.
+
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use tokio::io::AsyncWriteExt;
+
+use super::upgrade::OnUpgrade;
+
+// Bytes read from disk per write to the connection.
+const CHUNK: usize = 64 * 1024;
+
+// The part of a file sent as a response body.
+#[derive(Debug, Clone)]
+pub(super) struct FileBody {
+    path: PathBuf,
+    pub(super) size: u64,
+    pub(super) offset: u64,
+    pub(super) len: u64,
+    // Cleared for `HEAD`: the headers describe the body, but none is written.
+    pub(super) send: bool,
+}
+
+impl FileBody {
+    // Looks up the regular file at `path`.
+    pub(super) async fn open(path: &Path) -> io::Result<Self> {
+        let path = path.to_owned();
+        tokio::task::spawn_blocking(move || {
+            let metadata = fs::metadata(&path)?;
+            if !metadata.is_file() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "not a regular file",
+                ));
+            }
+            Ok(Self {
+                path,
+                size: metadata.len(),
+                offset: 0,
+                len: metadata.len(),
+                send: true,
+            })
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+
+    // Copies the selected bytes to the connection.
+    pub(super) fn writer(&self) -> OnUpgrade {
+        let body = self.clone();
+        OnUpgrade::new(move |mut io| async move {
+            if !body.send {
+                return;
+            }
+            let file = fs::File::open(&body.path)
+                .and_then(|mut file| file.seek(SeekFrom::Start(body.offset)).map(|_| file));
+            let Ok(mut file) = file else {
+                return;
+            };
+            let mut remaining = body.len;
+            while remaining > 0 {
+                let want = remaining.min(CHUNK as u64) as usize;
+                let read = tokio::task::spawn_blocking(move || {
+                    let mut buf = vec![0; want];
+                    let n = file.read(&mut buf)?;
+                    buf.truncate(n);
+                    Ok::<_, io::Error>((file, buf))
+                })
+                .await;
+                let Ok(Ok((next, buf))) = read else {
+                    return;
+                };
+                // The file shrank after its length was sent; the client sees a short body.
+                if buf.is_empty() || io.write_all(&buf).await.is_err() {
+                    return;
+                }
+                file = next;
+                remaining -= buf.len() as u64;
+            }
+            let _ = io.flush().await;
+        })
+    }
+}
+
+// What a `Range` header selects from a body of a given size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ByteRange {
+    // The whole body: no range, or one this server does not honour (several ranges).
+    Full,
+    // The inclusive byte range `first..=last`.
+    Partial(u64, u64),
+    // A syntactically valid range lying wholly past the end.
+    Unsatisfiable,
+}
+
+// Parses a single `bytes=` range; multiple ranges are answered with the full body.
+pub(super) fn parse_range(header: &str, size: u64) -> ByteRange {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((first, last)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (first, last) = (first.trim(), last.trim());
+    if first.is_empty() {
+        // A suffix: the final `last` bytes.
+        return match last.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if size == 0 => ByteRange::Unsatisfiable,
+            Ok(n) => ByteRange::Partial(size.saturating_sub(n), size - 1),
+            Err(_) => ByteRange::Full,
+        };
+    }
+    let Ok(first) = first.parse::<u64>() else {
+        return ByteRange::Full;
+    };
+    let last = match last {
+        "" => u64::MAX,
+        last => match last.parse::<u64>() {
+            Ok(last) if last >= first => last,
+            _ => return ByteRange::Full,
+        },
+    };
+    if first >= size {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial(first, last.min(size - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_ranges() {
+        assert_eq!(parse_range("bytes=0-9", 100), ByteRange::Partial(0, 9));
+        assert_eq!(parse_range("bytes=90-", 100), ByteRange::Partial(90, 99));
+        assert_eq!(parse_range("bytes=90-500", 100), ByteRange::Partial(90, 99));
+        assert_eq!(parse_range("bytes=-10", 100), ByteRange::Partial(90, 99));
+        assert_eq!(parse_range("bytes=-500", 100), ByteRange::Partial(0, 99));
+        assert_eq!(parse_range("bytes=100-", 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-1, 5-6", 100), ByteRange::Full);
+        assert_eq!(parse_range("bytes=9-1", 100), ByteRange::Full);
+        assert_eq!(parse_range("items=0-1", 100), ByteRange::Full);
+    }
+}
@@ -0,0 +1,88 @@
+//! Entry points for fuzzing the HTTP parsers.
+//!
+//! Each function takes arbitrary bytes, runs them through the parsers a server
+//! exposes to the network, and panics only when an invariant between them breaks, so
+//! a fuzzer's crash is always a bug. They keep no state and allocate nothing that
+//! outlives the call, which suits corpus minimization and replaying saved inputs.
+//!
+//! # Examples
+//!
+//! A `cargo fuzz` target:
+//!
+//! ```rust,ignore
+//! #![no_main]
+//! libfuzzer_sys::fuzz_target!(|data: &[u8]| rttp::http::fuzz::request(data));
+//! ```
+
+use super::{ContentDisposition, Link, request::Request};
+
+/// Parses `data` as a request both leniently and strictly.
+///
+/// # Panics
+///
+/// If a request accepted by [`Request::parse_strict`] is rejected or read differently
+/// by [`Request::parse`], or either reports a body offset past the end of `data`.
+pub fn request(data: &[u8]) {
+    let lenient = Request::parse(data);
+    if let Ok((req, offset)) = &lenient {
+        assert!(*offset <= data.len(), "body offset past input");
+        let _ = (req.content_length(), req.is_keep_alive(), req.cookies());
+    }
+    let Ok((strict, strict_offset)) = Request::parse_strict(data) else {
+        return;
+    };
+    let (lenient, offset) = lenient.expect("strictly valid request rejected leniently");
+    assert_eq!(strict_offset, offset);
+    assert_eq!(strict.method(), lenient.method());
+    assert_eq!(strict.path(), lenient.path());
+    assert_eq!(strict.query_string(), lenient.query_string());
+    assert!(strict.headers().iter().eq(lenient.headers().iter()));
+}
+
+/// Parses `data` as the value of each structured header the crate reads.
+///
+/// Input that is not UTF-8 is ignored, as header values are.
+///
+/// # Panics
+///
+/// If a parsed `Content-Disposition` does not survive formatting and parsing again.
+pub fn header_value(data: &[u8]) {
+    let Ok(value) = std::str::from_utf8(data) else {
+        return;
+    };
+    for link in Link::parse(value) {
+        let _ = link.to_string();
+    }
+    if let Some(disposition) = ContentDisposition::parse(value) {
+        let again = ContentDisposition::parse(&disposition.to_string())
+            .expect("formatted Content-Disposition does not parse");
+        assert_eq!(again.kind(), disposition.kind());
+    }
+    let raw = format!("GET / HTTP/1.1\r\nCookie: {value}\r\n\r\n");
+    let _ = Request::parse(raw.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn survives_a_small_corpus() {
+        let corpus: &[&[u8]] = &[
+            b"",
+            b"GET / HTTP/1.1\r\nHost: x\r\n\r\n",
+            b"GET / HTTP/1.1\nHost: x\n\n",
+            b"POST /a?b=1&&=&c HTTP/1.0\r\nContent-Length: 99999999999999999999\r\n\r\n",
+            b"OPTIONS * HTTP/1.1\r\nCookie: ;;=;a=\r\n\r\n",
+            b"CONNECT [::1]:443 HTTP/1.1\r\n\r\n",
+            b"GET /\xff HTTP/1.1\r\nX: \xfe\r\n\r\n",
+            b"\r\n\r\n",
+        ];
+        for input in corpus {
+            request(input);
+            header_value(input);
+        }
+        header_value(br#"attachment; filename*=UTF-8''%E2%82%AC%20rates.pdf"#);
+        header_value(br#"<a>; rel="next", <b; rel=, "#);
+    }
+}
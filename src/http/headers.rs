@@ -1,13 +1,291 @@
 //! HTTP header map with case-insensitive name lookup.
 //!
 //! HTTP headers are order-preserving and case-insensitive per [RFC 9110 §5].
+//!
+//! [`parse_quality_list`] reads the weighted lists of `Accept`, `Accept-Encoding`,
+//! `Accept-Language`, and `TE`.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+// Maps at least this long keep an index from name to positions; shorter ones are
+// faster to scan.
+const INDEX_AT: usize = 24;
+
+macro_rules! standard_headers {
+    ($($konst:ident, $variant:ident => $name:literal;)*) => {
+        // The well-known header names, sorted case-insensitively.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        enum Standard {
+            $($variant,)*
+        }
+
+        impl Standard {
+            const ALL: &[Standard] = &[$(Standard::$variant,)*];
+
+            fn as_str(self) -> &'static str {
+                match self {
+                    $(Standard::$variant => $name,)*
+                }
+            }
+        }
+
+        $(
+            #[doc = concat!("The `", $name, "` header.")]
+            pub const $konst: HeaderName = HeaderName(Repr::Standard(Standard::$variant));
+        )*
+    };
+}
+
+standard_headers! {
+    ACCEPT, Accept => "Accept";
+    ACCEPT_ENCODING, AcceptEncoding => "Accept-Encoding";
+    ACCEPT_LANGUAGE, AcceptLanguage => "Accept-Language";
+    ACCEPT_RANGES, AcceptRanges => "Accept-Ranges";
+    ACCESS_CONTROL_ALLOW_CREDENTIALS, AccessControlAllowCredentials => "Access-Control-Allow-Credentials";
+    ACCESS_CONTROL_ALLOW_HEADERS, AccessControlAllowHeaders => "Access-Control-Allow-Headers";
+    ACCESS_CONTROL_ALLOW_METHODS, AccessControlAllowMethods => "Access-Control-Allow-Methods";
+    ACCESS_CONTROL_ALLOW_ORIGIN, AccessControlAllowOrigin => "Access-Control-Allow-Origin";
+    ACCESS_CONTROL_EXPOSE_HEADERS, AccessControlExposeHeaders => "Access-Control-Expose-Headers";
+    ACCESS_CONTROL_MAX_AGE, AccessControlMaxAge => "Access-Control-Max-Age";
+    ACCESS_CONTROL_REQUEST_HEADERS, AccessControlRequestHeaders => "Access-Control-Request-Headers";
+    ACCESS_CONTROL_REQUEST_METHOD, AccessControlRequestMethod => "Access-Control-Request-Method";
+    AGE, Age => "Age";
+    ALLOW, Allow => "Allow";
+    AUTHORIZATION, Authorization => "Authorization";
+    CACHE_CONTROL, CacheControl => "Cache-Control";
+    CONNECTION, Connection => "Connection";
+    CONTENT_DISPOSITION, ContentDisposition => "Content-Disposition";
+    CONTENT_ENCODING, ContentEncoding => "Content-Encoding";
+    CONTENT_LANGUAGE, ContentLanguage => "Content-Language";
+    CONTENT_LENGTH, ContentLength => "Content-Length";
+    CONTENT_LOCATION, ContentLocation => "Content-Location";
+    CONTENT_RANGE, ContentRange => "Content-Range";
+    CONTENT_SECURITY_POLICY, ContentSecurityPolicy => "Content-Security-Policy";
+    CONTENT_TYPE, ContentType => "Content-Type";
+    COOKIE, Cookie => "Cookie";
+    DATE, Date => "Date";
+    ETAG, ETag => "ETag";
+    EXPECT, Expect => "Expect";
+    EXPIRES, Expires => "Expires";
+    FORWARDED, Forwarded => "Forwarded";
+    HOST, Host => "Host";
+    IF_MATCH, IfMatch => "If-Match";
+    IF_MODIFIED_SINCE, IfModifiedSince => "If-Modified-Since";
+    IF_NONE_MATCH, IfNoneMatch => "If-None-Match";
+    IF_RANGE, IfRange => "If-Range";
+    IF_UNMODIFIED_SINCE, IfUnmodifiedSince => "If-Unmodified-Since";
+    LAST_MODIFIED, LastModified => "Last-Modified";
+    LINK, Link => "Link";
+    LOCATION, Location => "Location";
+    ORIGIN, Origin => "Origin";
+    PRAGMA, Pragma => "Pragma";
+    RANGE, Range => "Range";
+    REFERER, Referer => "Referer";
+    REFERRER_POLICY, ReferrerPolicy => "Referrer-Policy";
+    RETRY_AFTER, RetryAfter => "Retry-After";
+    SEC_WEBSOCKET_ACCEPT, SecWebSocketAccept => "Sec-WebSocket-Accept";
+    SEC_WEBSOCKET_KEY, SecWebSocketKey => "Sec-WebSocket-Key";
+    SEC_WEBSOCKET_PROTOCOL, SecWebSocketProtocol => "Sec-WebSocket-Protocol";
+    SEC_WEBSOCKET_VERSION, SecWebSocketVersion => "Sec-WebSocket-Version";
+    SERVER, Server => "Server";
+    SET_COOKIE, SetCookie => "Set-Cookie";
+    STRICT_TRANSPORT_SECURITY, StrictTransportSecurity => "Strict-Transport-Security";
+    TE, Te => "TE";
+    TRAILER, Trailer => "Trailer";
+    TRANSFER_ENCODING, TransferEncoding => "Transfer-Encoding";
+    UPGRADE, Upgrade => "Upgrade";
+    USER_AGENT, UserAgent => "User-Agent";
+    VARY, Vary => "Vary";
+    VIA, Via => "Via";
+    WWW_AUTHENTICATE, WwwAuthenticate => "WWW-Authenticate";
+    X_CONTENT_TYPE_OPTIONS, XContentTypeOptions => "X-Content-Type-Options";
+    X_FORWARDED_FOR, XForwardedFor => "X-Forwarded-For";
+    X_FORWARDED_HOST, XForwardedHost => "X-Forwarded-Host";
+    X_FORWARDED_PROTO, XForwardedProto => "X-Forwarded-Proto";
+    X_FRAME_OPTIONS, XFrameOptions => "X-Frame-Options";
+    X_REQUEST_ID, XRequestId => "X-Request-Id";
+}
 
-use std::fmt;
+impl Standard {
+    // Finds the well-known name equal to `name`, ignoring case, without allocating.
+    fn find(name: &str) -> Option<Self> {
+        Self::ALL
+            .binary_search_by(|standard| {
+                let lower = |b: &u8| b.to_ascii_lowercase();
+                let known = standard.as_str().as_bytes().iter().map(lower);
+                known.cmp(name.as_bytes().iter().map(lower))
+            })
+            .ok()
+            .map(|i| Self::ALL[i])
+    }
+}
+
+/// A header name, compared case-insensitively.
+///
+/// Well-known names such as `Content-Type` are interned: converting one from a string
+/// does not allocate, and it is written in its canonical case. Other names keep the
+/// case they were given.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::http::headers::{self, HeaderName};
+///
+/// assert_eq!(HeaderName::from("content-type"), headers::CONTENT_TYPE);
+/// assert_eq!(HeaderName::from("content-type").as_str(), "Content-Type");
+/// assert_eq!(HeaderName::from("x-Trace").as_str(), "x-Trace");
+/// assert_eq!(HeaderName::from("x-Trace"), HeaderName::from("X-TRACE"));
+/// ```
+#[derive(Clone)]
+pub struct HeaderName(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Standard(Standard),
+    Custom(Box<str>),
+}
+
+impl HeaderName {
+    /// Returns the name as written on the wire.
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            Repr::Standard(standard) => standard.as_str(),
+            Repr::Custom(name) => name,
+        }
+    }
+
+    // The name as a key that can be matched against entries without allocating.
+    fn key(&self) -> Key<'_> {
+        match &self.0 {
+            Repr::Standard(standard) => Key::Standard(*standard),
+            Repr::Custom(name) => Key::Custom(name),
+        }
+    }
+}
+
+impl From<&str> for HeaderName {
+    fn from(name: &str) -> Self {
+        match Standard::find(name) {
+            Some(standard) => Self(Repr::Standard(standard)),
+            None => Self(Repr::Custom(name.into())),
+        }
+    }
+}
+
+impl From<String> for HeaderName {
+    fn from(name: String) -> Self {
+        match Standard::find(&name) {
+            Some(standard) => Self(Repr::Standard(standard)),
+            None => Self(Repr::Custom(name.into_boxed_str())),
+        }
+    }
+}
+
+impl From<&String> for HeaderName {
+    fn from(name: &String) -> Self {
+        Self::from(name.as_str())
+    }
+}
+
+impl From<&HeaderName> for HeaderName {
+    fn from(name: &HeaderName) -> Self {
+        name.clone()
+    }
+}
+
+impl PartialEq for HeaderName {
+    fn eq(&self, other: &Self) -> bool {
+        self.key().matches(other)
+    }
+}
+
+impl Eq for HeaderName {}
+
+impl PartialEq<str> for HeaderName {
+    fn eq(&self, other: &str) -> bool {
+        Key::new(other).matches(self)
+    }
+}
+
+impl PartialEq<&str> for HeaderName {
+    fn eq(&self, other: &&str) -> bool {
+        Key::new(other).matches(self)
+    }
+}
+
+impl Hash for HeaderName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match &self.0 {
+            Repr::Standard(standard) => standard.hash(state),
+            Repr::Custom(name) => {
+                for b in name.bytes() {
+                    state.write_u8(b.to_ascii_lowercase());
+                }
+            }
+        }
+    }
+}
+
+impl AsRef<str> for HeaderName {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for HeaderName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for HeaderName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// A name being looked up. Resolving it once makes each entry comparison a tag check
+// for well-known names. Interning guarantees a custom name never spells a standard one.
+#[derive(Clone, Copy)]
+enum Key<'a> {
+    Standard(Standard),
+    Custom(&'a str),
+}
+
+impl<'a> Key<'a> {
+    fn new(name: &'a str) -> Self {
+        match Standard::find(name) {
+            Some(standard) => Self::Standard(standard),
+            None => Self::Custom(name),
+        }
+    }
+
+    fn matches(self, name: &HeaderName) -> bool {
+        match (self, &name.0) {
+            (Self::Standard(a), Repr::Standard(b)) => a == *b,
+            (Self::Custom(a), Repr::Custom(b)) => a.eq_ignore_ascii_case(b),
+            _ => false,
+        }
+    }
+
+    fn to_name(self) -> HeaderName {
+        match self {
+            Self::Standard(standard) => HeaderName(Repr::Standard(standard)),
+            Self::Custom(name) => HeaderName(Repr::Custom(name.into())),
+        }
+    }
+}
 
 /// A case-insensitive, multi-value HTTP header map.
 ///
 /// Preserves insertion order and allows multiple values per header name,
-/// matching the semantics of HTTP/1.1 header fields (RFC 9110 §5.3).
+/// matching the semantics of HTTP/1.1 header fields (RFC 9110 §5.3). Names are
+/// [`HeaderName`]s, so well-known ones cost no allocation, and large maps keep an
+/// index so lookups do not scan every entry.
 ///
 /// # Examples
 ///
@@ -25,7 +303,10 @@ use std::fmt;
 /// ```
 #[derive(Debug, Clone, Default)]
 pub struct Headers {
-    inner: Vec<(String, String)>,
+    inner: Vec<(HeaderName, String)>,
+    // Positions of each name's entries, once the map reaches `INDEX_AT` entries. Boxed
+    // so that small maps, the usual case, stay small.
+    index: Option<Box<Index>>,
 }
 
 impl Headers {
@@ -38,42 +319,55 @@ impl Headers {
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
             inner: Vec::with_capacity(capacity),
+            index: None,
         }
     }
 
     /// Appends a header entry. Multiple values for the same name are preserved.
-    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
-        self.inner.push((name.into(), value.into()));
+    pub fn insert(&mut self, name: impl Into<HeaderName>, value: impl Into<String>) {
+        let name = name.into();
+        if let Some(index) = &mut self.index {
+            index
+                .0
+                .entry(name.clone())
+                .or_default()
+                .push(self.inner.len());
+        }
+        self.inner.push((name, value.into()));
+        if self.index.is_none() && self.inner.len() >= INDEX_AT {
+            self.reindex();
+        }
     }
 
     /// Returns the first value for the given header name (case-insensitive), or `None`.
     pub fn get(&self, name: &str) -> Option<&str> {
-        self.inner
-            .iter()
-            .find(|(k, _)| k.eq_ignore_ascii_case(name))
-            .map(|(_, v)| v.as_str())
+        self.positions(name)
+            .next()
+            .map(|i| self.inner[i].1.as_str())
     }
 
     /// Returns an iterator over all values for the given header name (case-insensitive).
     pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
-        self.inner
-            .iter()
-            .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
-            .map(|(_, v)| v.as_str())
+        self.positions(name).map(|i| self.inner[i].1.as_str())
     }
 
     /// Removes all entries with the given header name (case-insensitive).
     ///
     /// Returns `true` if any entries were removed.
     pub fn remove(&mut self, name: &str) -> bool {
+        let key = Key::new(name);
         let before = self.inner.len();
-        self.inner.retain(|(k, _)| !k.eq_ignore_ascii_case(name));
-        self.inner.len() < before
+        self.inner.retain(|(k, _)| !key.matches(k));
+        let removed = self.inner.len() < before;
+        if removed && self.index.is_some() {
+            self.reindex();
+        }
+        removed
     }
 
     /// Returns `true` if the map contains at least one entry with the given name.
     pub fn contains(&self, name: &str) -> bool {
-        self.inner.iter().any(|(k, _)| k.eq_ignore_ascii_case(name))
+        self.positions(name).next().is_some()
     }
 
     /// Returns the total number of header entries (not unique names).
@@ -90,8 +384,48 @@ impl Headers {
     pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
         self.inner.iter().map(|(k, v)| (k.as_str(), v.as_str()))
     }
+
+    /// Parses every value of a weighted list header, such as `Accept`, with
+    /// [`parse_quality_list`]. A list split across several header lines is read as one.
+    pub fn quality_list(&self, name: &str) -> Vec<QualityItem<'_>> {
+        self.positions(name)
+            .flat_map(|i| parse_quality_list(&self.inner[i].1))
+            .collect()
+    }
+
+    // The positions of the entries named `name`, in order.
+    fn positions<'a>(&'a self, name: &'a str) -> impl Iterator<Item = usize> + 'a {
+        let key = Key::new(name);
+        let indexed = self.index.as_ref().map(|index| {
+            let positions = index.0.get(&key.to_name()).map_or(&[][..], Vec::as_slice);
+            positions.iter().copied()
+        });
+        let scanned = indexed.is_none().then(|| {
+            self.inner
+                .iter()
+                .enumerate()
+                .filter(move |(_, (k, _))| key.matches(k))
+                .map(|(i, _)| i)
+        });
+        indexed
+            .into_iter()
+            .flatten()
+            .chain(scanned.into_iter().flatten())
+    }
+
+    fn reindex(&mut self) {
+        let mut index = Index::default();
+        for (i, (name, _)) in self.inner.iter().enumerate() {
+            index.0.entry(name.clone()).or_default().push(i);
+        }
+        self.index = (self.inner.len() >= INDEX_AT).then(|| Box::new(index));
+    }
 }
 
+// The positions of each name's entries in a large map.
+#[derive(Debug, Clone, Default)]
+struct Index(HashMap<HeaderName, Vec<usize>>);
+
 impl fmt::Display for Headers {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for (name, value) in &self.inner {
@@ -101,6 +435,119 @@ impl fmt::Display for Headers {
     }
 }
 
+/// One element of a weighted header list, such as `text/html;level=1;q=0.8`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityItem<'a> {
+    /// The element without its parameters, such as `text/html`, `gzip`, or `*`.
+    pub value: &'a str,
+    /// The `q` weight, from `0.0` (not acceptable) to `1.0`, the default.
+    pub quality: f32,
+    /// The other parameters, in order, with quotes removed from quoted values.
+    pub params: Vec<(&'a str, &'a str)>,
+}
+
+impl QualityItem<'_> {
+    /// Returns the value of parameter `name` (case-insensitive).
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+
+    /// Returns `true` unless the element is weighted `q=0`.
+    pub fn is_acceptable(&self) -> bool {
+        self.quality > 0.0
+    }
+}
+
+/// Parses a comma-separated list of elements with optional `q` weights and parameters
+/// (RFC 9110 §12.4.2), in header order.
+///
+/// Commas and semicolons inside quoted strings do not split, and empty elements are
+/// skipped. An element whose weight is malformed or outside `0`–`1` is dropped rather
+/// than guessed at, so a bad `q` never makes something acceptable. Sort with a stable
+/// sort to rank by weight while keeping header order for ties.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::http::headers::parse_quality_list;
+///
+/// let items = parse_quality_list(r#"text/html;q=0.8, application/json, text/*;x="a,b";q=0"#);
+/// assert_eq!(items.len(), 3);
+/// assert_eq!((items[0].value, items[0].quality), ("text/html", 0.8));
+/// assert_eq!((items[1].value, items[1].quality), ("application/json", 1.0));
+/// assert_eq!(items[2].param("x"), Some("a,b"));
+/// assert!(!items[2].is_acceptable());
+/// ```
+pub fn parse_quality_list(value: &str) -> Vec<QualityItem<'_>> {
+    split_unquoted(value, ',')
+        .into_iter()
+        .filter_map(|element| {
+            let mut parts = split_unquoted(element, ';').into_iter();
+            let value = parts.next()?.trim();
+            if value.is_empty() {
+                return None;
+            }
+            let mut quality = 1.0;
+            let mut params = Vec::new();
+            for part in parts {
+                let Some((key, param)) = part.split_once('=') else {
+                    continue;
+                };
+                let (key, param) = (key.trim(), param.trim());
+                if key.eq_ignore_ascii_case("q") {
+                    quality = parse_weight(param)?;
+                } else {
+                    let unquoted = param
+                        .strip_prefix('"')
+                        .and_then(|p| p.strip_suffix('"'))
+                        .unwrap_or(param);
+                    params.push((key, unquoted));
+                }
+            }
+            Some(QualityItem {
+                value,
+                quality,
+                params,
+            })
+        })
+        .collect()
+}
+
+// A weight is `0` or `1` with up to three decimals, no more than `1`.
+fn parse_weight(weight: &str) -> Option<f32> {
+    let (int, frac) = weight.split_once('.').unwrap_or((weight, ""));
+    let valid = matches!(int, "0" | "1")
+        && frac.len() <= 3
+        && frac.bytes().all(|b| b.is_ascii_digit())
+        && (int == "0" || frac.bytes().all(|b| b == b'0'));
+    if valid { weight.parse().ok() } else { None }
+}
+
+// Splits at `separator`, except inside quoted strings.
+pub(super) fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            c if c == separator && !in_quotes => {
+                parts.push(&value[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -140,4 +587,81 @@ mod tests {
         assert!(h.contains("authorization"));
         assert!(!h.contains("x-missing"));
     }
+
+    #[test]
+    fn quality_lists() {
+        let items = parse_quality_list(
+            "gzip;q=1.0, , identity; q=0.5, *;q=0, br;Q=0.25, x;q=1.5, y;q=0.1234, z;q=abc",
+        );
+        let weights: Vec<_> = items.iter().map(|i| (i.value, i.quality)).collect();
+        assert_eq!(
+            weights,
+            [("gzip", 1.0), ("identity", 0.5), ("*", 0.0), ("br", 0.25)]
+        );
+
+        let items = parse_quality_list(r#"text/html; level="1;2"; charset=utf-8"#);
+        assert_eq!(items[0].params, [("level", "1;2"), ("charset", "utf-8")]);
+        assert_eq!(items[0].param("CHARSET"), Some("utf-8"));
+        assert!(parse_quality_list("").is_empty());
+
+        let mut h = Headers::new();
+        h.insert("Accept-Encoding", "gzip");
+        h.insert("accept-encoding", "br;q=0.9");
+        let values: Vec<_> = h
+            .quality_list("Accept-Encoding")
+            .into_iter()
+            .map(|i| i.value)
+            .collect();
+        assert_eq!(values, ["gzip", "br"]);
+    }
+
+    #[test]
+    fn interns_standard_names() {
+        assert!(
+            Standard::ALL
+                .windows(2)
+                .all(|w| w[0].as_str().to_ascii_lowercase() < w[1].as_str().to_ascii_lowercase()),
+            "standard names must stay sorted"
+        );
+        for standard in Standard::ALL {
+            let lower = standard.as_str().to_ascii_lowercase();
+            assert_eq!(Standard::find(&lower), Some(*standard));
+        }
+        assert!(matches!(
+            HeaderName::from("etag").0,
+            Repr::Standard(Standard::ETag)
+        ));
+        assert!(matches!(HeaderName::from("X-Custom").0, Repr::Custom(_)));
+        assert_eq!(HeaderName::from("set-COOKIE"), SET_COOKIE);
+        assert_eq!(HeaderName::from("X-Custom"), "x-custom");
+
+        let mut h = Headers::new();
+        h.insert("content-length", "5");
+        h.insert(CACHE_CONTROL, "no-store");
+        assert_eq!(
+            h.to_string(),
+            "Content-Length: 5\r\nCache-Control: no-store\r\n"
+        );
+    }
+
+    #[test]
+    fn large_maps_are_indexed() {
+        let mut h = Headers::new();
+        for i in 0..INDEX_AT {
+            h.insert(format!("X-Field-{}", i % 10), i.to_string());
+        }
+        h.insert("Vary", "Accept");
+        h.insert("vary", "Origin");
+        assert!(h.index.is_some());
+        let values: Vec<_> = h.get_all("x-field-3").collect();
+        assert_eq!(values, ["3", "13", "23"]);
+        assert_eq!(h.get_all("VARY").collect::<Vec<_>>(), ["Accept", "Origin"]);
+        assert_eq!(h.get("x-missing"), None);
+
+        assert!(h.remove("x-field-3"));
+        assert_eq!(h.get("X-Field-4"), Some("4"));
+        assert_eq!(h.get("vary"), Some("Accept"));
+        assert!(h.index.is_none(), "dropped below the threshold");
+        assert_eq!(h.get_all("x-field-5").count(), 2);
+    }
 }
@@ -0,0 +1,100 @@
+//! HTML responses and escaping.
+//!
+//! [`Html`] marks a body as HTML so it is served as `text/html; charset=utf-8` rather
+//! than the `text/plain` default. [`escape`] makes untrusted text safe to place in an
+//! HTML document, both between tags and inside quoted attribute values.
+//!
+//! # Examples
+//!
+//! ```
+//! use rttp::http::{Response, StatusCode, html::{Html, escape}};
+//!
+//! let name = "<script>alert(1)</script>";
+//! let response: Response = Html(format!("<p>Hello, {}!</p>", escape(name))).into();
+//!
+//! assert_eq!(response.status(), StatusCode::Ok);
+//! assert_eq!(response.headers().get("Content-Type"), Some("text/html; charset=utf-8"));
+//! assert_eq!(
+//!     response.body_text(),
+//!     "<p>Hello, &lt;script&gt;alert(1)&lt;/script&gt;!</p>"
+//! );
+//! ```
+
+use std::borrow::Cow;
+
+use super::{Response, StatusCode};
+
+/// An HTML body, converted into a `200 OK` response with
+/// `Content-Type: text/html; charset=utf-8`.
+///
+/// The body is sent as given: interpolate untrusted values through [`escape`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Html<T>(pub T);
+
+impl<T: Into<String>> Html<T> {
+    /// Converts into a response, as the [`From`] impl does.
+    pub fn into_response(self) -> Response {
+        self.into()
+    }
+}
+
+impl<T: Into<String>> From<Html<T>> for Response {
+    fn from(html: Html<T>) -> Self {
+        Response::new(StatusCode::Ok)
+            .header("Content-Type", "text/html; charset=utf-8")
+            .body(html.0)
+    }
+}
+
+/// Escapes `&`, `<`, `>`, `"`, and `'` so `text` is inert in HTML content and in quoted
+/// attribute values.
+///
+/// Borrows `text` when there is nothing to escape. Escaping does not make a value safe
+/// in every position: URLs in `href` still need their scheme checked, and text inside
+/// `<script>` or `<style>` follows other rules.
+pub fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len() + 16);
+    escape_into(text, &mut out);
+    Cow::Owned(out)
+}
+
+/// Appends `text` to `out`, escaped as by [`escape`].
+pub fn escape_into(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#x27;"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_markup_and_quotes() {
+        assert!(matches!(escape("plain text"), Cow::Borrowed("plain text")));
+        assert_eq!(
+            escape(r#"<a href="x" title='y'>Tom & Jerry</a>"#),
+            "&lt;a href=&quot;x&quot; title=&#x27;y&#x27;&gt;Tom &amp; Jerry&lt;/a&gt;"
+        );
+        assert_eq!(escape("déjà <vu>"), "déjà &lt;vu&gt;");
+    }
+
+    #[test]
+    fn html_sets_the_content_type() {
+        let bytes = Html("<p>hi</p>").into_response().into_bytes();
+        let text = std::str::from_utf8(&bytes).unwrap();
+        assert!(text.contains("Content-Type: text/html; charset=utf-8\r\n"));
+        assert!(!text.contains("text/plain"));
+        assert!(text.ends_with("\r\n\r\n<p>hi</p>"));
+    }
+}
@@ -0,0 +1,206 @@
+//! Informational (`1xx`) responses sent ahead of the final one.
+//!
+//! A handler can send any number of informational responses before it returns — `103
+//! Early Hints` so the browser starts fetching stylesheets while the page renders, or
+//! `102`-style progress for a slow operation — through the [`Interim`] handle from
+//! [`Context::interim`](crate::context::Context::interim). The server writes each one
+//! as soon as it is sent and always before the final response; once the final response
+//! is out, sending fails with [`InterimError::Closed`].
+//!
+//! The server answers `Expect: 100-continue` itself with `100 Continue` when it starts
+//! waiting for the body. HTTP/1.0 clients do not understand informational responses,
+//! so for them the handle is disabled.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rttp::{Response, Router, StatusCode, context::Context, http::Link};
+//!
+//! let mut router = Router::new();
+//! router.get("/", |ctx: Context| async move {
+//!     let _ = ctx
+//!         .interim()
+//!         .early_hints(&[Link::preload("/app.css", "style")]);
+//!     // ... render the page ...
+//!     Response::new(StatusCode::Ok)
+//! });
+//! ```
+
+use bytes::{BufMut, BytesMut};
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+use super::{Headers, Link, StatusCode};
+
+/// Why an informational response was not sent.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum InterimError {
+    /// The status is not `1xx`, or is `101`, which only upgrades send.
+    #[error("{0:?} cannot be sent as an informational response")]
+    NotInformational(StatusCode),
+
+    /// The request came over HTTP/1.0, or not from the server.
+    #[error("informational responses are not supported on this request")]
+    Unsupported,
+
+    /// The final response has already been sent.
+    #[error("the final response has already been sent")]
+    Closed,
+}
+
+/// Sends informational responses for one request.
+///
+/// Cheap to clone; all clones feed the same connection.
+#[derive(Debug, Clone, Default)]
+pub struct Interim {
+    sender: Option<mpsc::UnboundedSender<BytesMut>>,
+}
+
+impl Interim {
+    /// Creates a handle and the receiver the connection drains.
+    pub(crate) fn channel() -> (Self, mpsc::UnboundedReceiver<BytesMut>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let interim = Self {
+            sender: Some(sender),
+        };
+        (interim, receiver)
+    }
+
+    /// Returns `true` if informational responses can be sent for this request.
+    pub fn is_supported(&self) -> bool {
+        self.sender.as_ref().is_some_and(|s| !s.is_closed())
+    }
+
+    /// Queues an informational response with `status` and `headers`.
+    ///
+    /// # Errors
+    ///
+    /// See [`InterimError`].
+    pub fn send(&self, status: StatusCode, headers: &Headers) -> Result<(), InterimError> {
+        if !status.is_informational() || status == StatusCode::SwitchingProtocols {
+            return Err(InterimError::NotInformational(status));
+        }
+        let sender = self.sender.as_ref().ok_or(InterimError::Unsupported)?;
+        let mut head = BytesMut::with_capacity(64 + headers.len() * 64);
+        head.put(
+            format!(
+                "HTTP/1.1 {} {}\r\n",
+                status.as_u16(),
+                status.canonical_reason()
+            )
+            .as_bytes(),
+        );
+        for (name, value) in headers.iter() {
+            head.put(format!("{name}: {value}\r\n").as_bytes());
+        }
+        head.put(&b"\r\n"[..]);
+        sender.send(head).map_err(|_| InterimError::Closed)
+    }
+
+    /// Sends `103 Early Hints` with a `Link` header for each of `links`.
+    ///
+    /// # Errors
+    ///
+    /// See [`InterimError`].
+    pub fn early_hints(&self, links: &[Link]) -> Result<(), InterimError> {
+        let mut headers = Headers::new();
+        for link in links {
+            headers.insert("Link", link.to_string());
+        }
+        self.send(StatusCode::EarlyHints, &headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    use super::*;
+    use crate::{Response, Server};
+
+    #[test]
+    fn serializes_and_closes() {
+        let (interim, mut receiver) = Interim::channel();
+        assert!(interim.is_supported());
+        interim
+            .early_hints(&[Link::preload("/app.css", "style")])
+            .unwrap();
+        assert_eq!(
+            &receiver.try_recv().unwrap()[..],
+            b"HTTP/1.1 103 Early Hints\r\nLink: </app.css>; rel=\"preload\"; as=\"style\"\r\n\r\n"
+        );
+        assert_eq!(
+            interim.send(StatusCode::Ok, &Headers::new()),
+            Err(InterimError::NotInformational(StatusCode::Ok))
+        );
+        assert_eq!(
+            interim.send(StatusCode::SwitchingProtocols, &Headers::new()),
+            Err(InterimError::NotInformational(
+                StatusCode::SwitchingProtocols
+            ))
+        );
+
+        receiver.close();
+        assert!(!interim.is_supported());
+        assert_eq!(
+            interim.send(StatusCode::Continue, &Headers::new()),
+            Err(InterimError::Closed)
+        );
+        assert_eq!(
+            Interim::default().send(StatusCode::Continue, &Headers::new()),
+            Err(InterimError::Unsupported)
+        );
+    }
+
+    #[tokio::test]
+    async fn server_writes_interim_responses_first() {
+        let server = Server::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr();
+        tokio::spawn(server.run(|request| async move {
+            let interim = request.interim().clone();
+            let hints = interim.early_hints(&[Link::new("/a.js").rel("preload")]);
+            tokio::task::yield_now().await;
+            let second = interim.send(StatusCode::Continue, &Headers::new());
+            tokio::spawn(async move {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                let _ = interim.send(StatusCode::EarlyHints, &Headers::new());
+            });
+            let body = format!("{hints:?} {second:?} {}", request.body().len());
+            Response::new(StatusCode::Ok).body(body)
+        }));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"POST / HTTP/1.1\r\nConnection: close\r\nExpect: 100-continue\r\nContent-Length: 4\r\n\r\n")
+            .await
+            .unwrap();
+        let mut continue_line = [0; 25];
+        stream.read_exact(&mut continue_line).await.unwrap();
+        assert_eq!(&continue_line, b"HTTP/1.1 100 Continue\r\n\r\n");
+        stream.write_all(b"body").await.unwrap();
+        let mut rest = String::new();
+        stream.read_to_string(&mut rest).await.unwrap();
+
+        let early = "HTTP/1.1 103 Early Hints\r\nLink: </a.js>; rel=\"preload\"\r\n\r\n";
+        let cont = "HTTP/1.1 100 Continue\r\n\r\n";
+        assert!(
+            rest.starts_with(&format!("{early}{cont}HTTP/1.1 200 OK\r\n")),
+            "{rest}"
+        );
+        assert!(rest.ends_with("Ok(()) Ok(()) 4"), "{rest}");
+        assert_eq!(rest.matches("103 Early Hints").count(), 1);
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET / HTTP/1.0\r\n\r\n").await.unwrap();
+        let mut rest = String::new();
+        stream.read_to_string(&mut rest).await.unwrap();
+        assert!(rest.starts_with("HTTP/1.1 200 OK"), "{rest}");
+        assert!(
+            rest.ends_with("Err(Unsupported) Err(Unsupported) 0"),
+            "{rest}"
+        );
+    }
+}
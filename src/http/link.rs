@@ -0,0 +1,369 @@
+//! `Link` header construction and parsing (RFC 8288).
+//!
+//! A [`Link`] is one link-value: a target URI and its parameters, the most important of
+//! which is `rel`. Several links can share one header, separated by commas; [`Link::parse`]
+//! reads them all and [`Response::link`](super::Response::link) appends one.
+
+use std::fmt;
+
+/// One link in a `Link` header: a target and parameters such as `rel` and `title`.
+///
+/// The [`Display`](fmt::Display) implementation renders the header value. Parameter
+/// names are lowercased; values are always written as quoted strings.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::http::Link;
+///
+/// let link = Link::new("/articles?page=3").rel("next").title("Next page");
+/// assert_eq!(link.to_string(), r#"</articles?page=3>; rel="next"; title="Next page""#);
+///
+/// let links = Link::parse(r#"</style.css>; rel=preload; as=style, </>; rel="home""#);
+/// assert_eq!(links.len(), 2);
+/// assert_eq!(links[0].param_value("as"), Some("style"));
+/// assert!(links[1].has_rel("home"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    target: String,
+    // In order; a parameter without a value has an empty one.
+    params: Vec<(String, String)>,
+}
+
+impl Link {
+    /// Creates a link to `target`, a URI reference, with no parameters.
+    pub fn new(target: impl Into<String>) -> Self {
+        Self {
+            target: target.into(),
+            params: Vec::new(),
+        }
+    }
+
+    /// A `rel="preload"` hint for `target`, fetched as the destination `as` (`style`,
+    /// `script`, `font`, `image`, ...).
+    pub fn preload(target: impl Into<String>, as_: impl Into<String>) -> Self {
+        Self::new(target).rel("preload").param("as", as_)
+    }
+
+    /// The `first`, `prev`, `next`, and `last` links for page `page` of `last` pages,
+    /// numbered from 1.
+    ///
+    /// Each target is `url` with its `page` query parameter set, keeping the other
+    /// parameters. `prev` and `next` are left out on the first and last pages.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::http::Link;
+    ///
+    /// let links = Link::pages("/users?sort=name&page=2", 2, 5);
+    /// let rendered: Vec<String> = links.iter().map(ToString::to_string).collect();
+    /// assert_eq!(rendered, [
+    ///     r#"</users?sort=name&page=1>; rel="first""#,
+    ///     r#"</users?sort=name&page=1>; rel="prev""#,
+    ///     r#"</users?sort=name&page=3>; rel="next""#,
+    ///     r#"</users?sort=name&page=5>; rel="last""#,
+    /// ]);
+    /// ```
+    pub fn pages(url: &str, page: u64, last: u64) -> Vec<Self> {
+        let last = last.max(1);
+        let at = |page: u64, rel: &str| Self::new(with_query_param(url, "page", page)).rel(rel);
+        let mut links = vec![at(1, "first")];
+        if page > 1 {
+            links.push(at((page - 1).min(last), "prev"));
+        }
+        if page < last {
+            links.push(at(page + 1, "next"));
+        }
+        links.push(at(last, "last"));
+        links
+    }
+
+    /// Sets the relation type; several types may be given separated by spaces.
+    #[must_use]
+    pub fn rel(self, rel: impl Into<String>) -> Self {
+        self.param("rel", rel)
+    }
+
+    /// Sets the human-readable `title`.
+    #[must_use]
+    pub fn title(self, title: impl Into<String>) -> Self {
+        self.param("title", title)
+    }
+
+    /// Sets the parameter `name`, replacing any earlier value. An empty value writes
+    /// the parameter without one, as in `crossorigin`.
+    #[must_use]
+    pub fn param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let name = name.into().to_ascii_lowercase();
+        let value = value.into();
+        match self.params.iter_mut().find(|(n, _)| *n == name) {
+            Some(entry) => entry.1 = value,
+            None => self.params.push((name, value)),
+        }
+        self
+    }
+
+    /// The target URI reference.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// The `rel` parameter as written.
+    pub fn rel_value(&self) -> Option<&str> {
+        self.param_value("rel")
+    }
+
+    /// Whether `rel` contains the relation type `rel`, compared case-insensitively.
+    pub fn has_rel(&self, rel: &str) -> bool {
+        self.param_value("rel").is_some_and(|rels| {
+            rels.split_ascii_whitespace()
+                .any(|r| r.eq_ignore_ascii_case(rel))
+        })
+    }
+
+    /// The `title` parameter.
+    pub fn title_value(&self) -> Option<&str> {
+        self.param_value("title")
+    }
+
+    /// The value of parameter `name` (case-insensitive).
+    pub fn param_value(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Iterates over the parameters in order.
+    pub fn params(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    /// Parses every link in a `Link` header value, skipping malformed ones.
+    ///
+    /// Quoted parameter values are unescaped. For a parameter given twice, the first
+    /// occurrence wins, as RFC 8288 requires for `rel`.
+    pub fn parse(value: &str) -> Vec<Self> {
+        let mut parser = Parser { src: value, pos: 0 };
+        let mut links = Vec::new();
+        loop {
+            parser.skip_whitespace();
+            if parser.rest().is_empty() {
+                return links;
+            }
+            match parser.link() {
+                Some(link) => links.push(link),
+                None => parser.skip_to_next(),
+            }
+        }
+    }
+}
+
+impl fmt::Display for Link {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{}>", self.target)?;
+        for (name, value) in &self.params {
+            write!(f, "; {name}")?;
+            if !value.is_empty() {
+                f.write_str("=\"")?;
+                for c in value.chars() {
+                    if c == '"' || c == '\\' {
+                        f.write_str("\\")?;
+                    }
+                    write!(f, "{c}")?;
+                }
+                f.write_str("\"")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Returns `url` with query parameter `name` set to `value`.
+fn with_query_param(url: &str, name: &str, value: impl fmt::Display) -> String {
+    let (url, fragment) = match url.find('#') {
+        Some(i) => url.split_at(i),
+        None => (url, ""),
+    };
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let mut pairs: Vec<String> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty() && pair.split('=').next() != Some(name))
+        .map(str::to_owned)
+        .collect();
+    pairs.push(format!("{name}={value}"));
+    format!("{path}?{}{fragment}", pairs.join("&"))
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.src[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t', ',']).len();
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let matched = self.rest().starts_with(c);
+        if matched {
+            self.pos += c.len_utf8();
+        }
+        matched
+    }
+
+    // One `<target>; name=value; ...` link, leaving the position before the next comma.
+    fn link(&mut self) -> Option<Link> {
+        if !self.eat('<') {
+            return None;
+        }
+        let end = self.rest().find('>')?;
+        let mut link = Link::new(&self.rest()[..end]);
+        self.pos += end + 1;
+        loop {
+            self.skip_spaces();
+            if self.rest().is_empty() || self.rest().starts_with(',') {
+                return Some(link);
+            }
+            if !self.eat(';') {
+                return None;
+            }
+            self.skip_spaces();
+            let name = self.token().to_ascii_lowercase();
+            if name.is_empty() {
+                // Tolerate a trailing or doubled `;`.
+                continue;
+            }
+            self.skip_spaces();
+            let value = if self.eat('=') {
+                self.skip_spaces();
+                if self.rest().starts_with('"') {
+                    self.quoted()?
+                } else {
+                    self.token().to_owned()
+                }
+            } else {
+                String::new()
+            };
+            // Extended parameters such as `title*` keep their encoded value.
+            if link.param_value(&name).is_none() {
+                link.params.push((name, value));
+            }
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t']).len();
+    }
+
+    fn token(&mut self) -> &str {
+        let rest = &self.src[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~/:".contains(c)))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn quoted(&mut self) -> Option<String> {
+        self.pos += 1;
+        let mut out = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Some(out);
+                }
+                '\\' => out.push(chars.next()?.1),
+                c => out.push(c),
+            }
+        }
+        None
+    }
+
+    // Recovers from a malformed link by skipping past the next comma outside `<>` and
+    // quotes.
+    fn skip_to_next(&mut self) {
+        let mut in_target = false;
+        let mut in_quotes = false;
+        let mut escaped = false;
+        for (i, c) in self.rest().char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if in_quotes => escaped = true,
+                '"' if !in_target => in_quotes = !in_quotes,
+                '<' if !in_quotes => in_target = true,
+                '>' if !in_quotes => in_target = false,
+                ',' if !in_target && !in_quotes => {
+                    self.pos += i + 1;
+                    return;
+                }
+                _ => {}
+            }
+        }
+        self.pos = self.src.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_display() {
+        let link = Link::new("https://example.com/a,b")
+            .rel("alternate")
+            .title(r#"The "A" page"#)
+            .param("Type", "text/html")
+            .param("crossorigin", "");
+        let rendered = link.to_string();
+        assert_eq!(
+            rendered,
+            r#"<https://example.com/a,b>; rel="alternate"; title="The \"A\" page"; type="text/html"; crossorigin"#
+        );
+        assert_eq!(Link::parse(&rendered), [link]);
+    }
+
+    #[test]
+    fn parses_multiple_links_and_skips_malformed_ones() {
+        let links = Link::parse(
+            r##"<a>; rel="next prev"; rel=ignored, garbage; rel=x, <b>;REL=Last;anchor="#top""##,
+        );
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].target(), "a");
+        assert_eq!(links[0].rel_value(), Some("next prev"));
+        assert!(links[0].has_rel("PREV"));
+        assert!(links[1].has_rel("last"));
+        assert_eq!(links[1].param_value("anchor"), Some("#top"));
+        assert!(Link::parse("").is_empty());
+        assert!(Link::parse(r#"<unterminated; rel="x""#).is_empty());
+    }
+
+    #[test]
+    fn page_links_replace_the_page_parameter() {
+        let links = Link::pages("/items?page=1#list", 1, 1);
+        let targets: Vec<_> = links.iter().map(|l| (l.target(), l.rel_value())).collect();
+        assert_eq!(
+            targets,
+            [
+                ("/items?page=1#list", Some("first")),
+                ("/items?page=1#list", Some("last")),
+            ]
+        );
+        let preload = Link::preload("/app.js", "script");
+        assert_eq!(
+            preload.to_string(),
+            r#"</app.js>; rel="preload"; as="script""#
+        );
+    }
+}
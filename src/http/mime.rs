@@ -0,0 +1,284 @@
+//! Media types and the file extensions that imply them.
+
+use std::{borrow::Cow, fmt, path::Path};
+
+use super::headers::split_unquoted;
+
+// Media types by lowercase file extension. Text types carry `charset=utf-8`, which is
+// what the files of a web project almost always are.
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("avif", "image/avif"),
+    ("css", "text/css; charset=utf-8"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("gif", "image/gif"),
+    ("gz", "application/gzip"),
+    ("htm", "text/html; charset=utf-8"),
+    ("html", "text/html; charset=utf-8"),
+    ("ico", "image/x-icon"),
+    ("jpeg", "image/jpeg"),
+    ("jpg", "image/jpeg"),
+    ("js", "text/javascript; charset=utf-8"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("md", "text/markdown; charset=utf-8"),
+    ("mjs", "text/javascript; charset=utf-8"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("otf", "font/otf"),
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("svg", "image/svg+xml"),
+    ("ttf", "font/ttf"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("wasm", "application/wasm"),
+    ("webm", "video/webm"),
+    ("webmanifest", "application/manifest+json"),
+    ("webp", "image/webp"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("xml", "application/xml"),
+    ("zip", "application/zip"),
+];
+
+/// A media type such as `text/html; charset=utf-8`.
+///
+/// The type, subtype, and parameter names are lowercased when parsed, so comparisons
+/// ignore their case; parameter values keep theirs.
+///
+/// # Examples
+///
+/// ```rust
+/// use rttp::http::Mime;
+///
+/// let mime = Mime::parse("Application/Problem+JSON; Charset=\"UTF-8\"").unwrap();
+/// assert_eq!(mime.essence(), "application/problem+json");
+/// assert_eq!(mime.suffix(), Some("json"));
+/// assert_eq!(mime.charset(), Some("UTF-8"));
+/// assert!(mime.matches("application/*"));
+///
+/// assert_eq!(Mime::from_path("site/app.css").to_string(), "text/css; charset=utf-8");
+/// assert_eq!(Mime::from_path("data.bin"), Mime::OCTET_STREAM);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Mime {
+    essence: Cow<'static, str>,
+    params: Vec<(String, String)>,
+}
+
+impl Mime {
+    /// `application/octet-stream`, for bytes of unknown type.
+    pub const OCTET_STREAM: Mime = Mime {
+        essence: Cow::Borrowed("application/octet-stream"),
+        params: Vec::new(),
+    };
+
+    /// Parses a media type, with optional parameters.
+    ///
+    /// Returns `None` unless the type and subtype are both non-empty tokens. Malformed
+    /// parameters are skipped.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = split_unquoted(value, ';').into_iter();
+        let essence = parts.next()?.trim().to_ascii_lowercase();
+        let (ty, subtype) = essence.split_once('/')?;
+        if !is_token(ty) || !is_token(subtype) {
+            return None;
+        }
+        let params = parts
+            .filter_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                let name = name.trim().to_ascii_lowercase();
+                let value = value.trim();
+                let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                    Some(quoted) => unescape(quoted),
+                    None => value.to_owned(),
+                };
+                is_token(&name).then_some((name, value))
+            })
+            .collect();
+        Some(Self {
+            essence: Cow::Owned(essence),
+            params,
+        })
+    }
+
+    /// Returns the media type for a file extension (case-insensitive, without the dot),
+    /// or `None` if the extension is not known.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        let extension = extension.to_ascii_lowercase();
+        EXTENSIONS
+            .binary_search_by_key(&extension.as_str(), |(ext, _)| ext)
+            .ok()
+            .and_then(|i| Self::parse(EXTENSIONS[i].1))
+    }
+
+    /// Returns the media type for a file name by its extension, falling back to
+    /// [`OCTET_STREAM`](Self::OCTET_STREAM).
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        path.as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Self::from_extension)
+            .unwrap_or(Self::OCTET_STREAM)
+    }
+
+    /// The type and subtype without parameters, such as `text/html`.
+    pub fn essence(&self) -> &str {
+        &self.essence
+    }
+
+    /// The top-level type, such as `text`.
+    pub fn type_(&self) -> &str {
+        self.essence().split_once('/').map_or("", |(ty, _)| ty)
+    }
+
+    /// The subtype, such as `html` or `problem+json`.
+    pub fn subtype(&self) -> &str {
+        self.essence().split_once('/').map_or("", |(_, sub)| sub)
+    }
+
+    /// The structured syntax suffix, such as `json` for `application/problem+json`.
+    pub fn suffix(&self) -> Option<&str> {
+        self.subtype().rsplit_once('+').map(|(_, suffix)| suffix)
+    }
+
+    /// Returns the value of parameter `name` (case-insensitive).
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the parameters, in order.
+    pub fn params(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// The `charset` parameter.
+    pub fn charset(&self) -> Option<&str> {
+        self.param("charset")
+    }
+
+    /// Returns this media type with parameter `name` set to `value`.
+    #[must_use]
+    pub fn with_param(mut self, name: &str, value: impl Into<String>) -> Self {
+        let name = name.to_ascii_lowercase();
+        let value = value.into();
+        match self.params.iter_mut().find(|(key, _)| *key == name) {
+            Some(param) => param.1 = value,
+            None => self.params.push((name, value)),
+        }
+        self
+    }
+
+    /// Returns this media type without parameters.
+    #[must_use]
+    pub fn without_params(mut self) -> Self {
+        self.params.clear();
+        self
+    }
+
+    /// Returns `true` if this media type falls within `range`, such as `*/*`, `text/*`,
+    /// or `text/html`. Parameters are ignored.
+    pub fn matches(&self, range: &str) -> bool {
+        let range = range.split(';').next().unwrap_or_default().trim();
+        match range.split_once('/') {
+            Some(("*", "*")) => true,
+            Some((ty, "*")) => ty.eq_ignore_ascii_case(self.type_()),
+            Some(_) => range.eq_ignore_ascii_case(self.essence()),
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for Mime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.essence())?;
+        for (name, value) in &self.params {
+            if is_token(value) {
+                write!(f, "; {name}={value}")?;
+            } else {
+                let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, "; {name}=\"{escaped}\"")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// RFC 9110 `token`.
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+fn unescape(quoted: &str) -> String {
+    let mut out = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_formats() {
+        let mime = Mime::parse(r#"multipart/form-data; boundary="a b;c"; X=1"#).unwrap();
+        assert_eq!((mime.type_(), mime.subtype()), ("multipart", "form-data"));
+        assert_eq!(mime.param("boundary"), Some("a b;c"));
+        assert_eq!(mime.param("x"), Some("1"));
+        assert_eq!(
+            mime.to_string(),
+            r#"multipart/form-data; boundary="a b;c"; x=1"#
+        );
+        assert_eq!(mime.suffix(), None);
+
+        for invalid in ["", "text", "text/", "/html", "te xt/html"] {
+            assert!(Mime::parse(invalid).is_none(), "{invalid}");
+        }
+        let plain = Mime::parse("text/plain")
+            .unwrap()
+            .with_param("charset", "utf-8");
+        assert_eq!(plain.to_string(), "text/plain; charset=utf-8");
+        assert_eq!(plain.clone().without_params().to_string(), "text/plain");
+        assert_eq!(Mime::parse("TEXT/Plain; charset=utf-8"), Some(plain));
+    }
+
+    #[test]
+    fn matches_ranges() {
+        let html = Mime::parse("text/html; charset=utf-8").unwrap();
+        assert!(html.matches("*/*"));
+        assert!(html.matches("text/*"));
+        assert!(html.matches("TEXT/HTML;level=1"));
+        assert!(!html.matches("text/plain"));
+        assert!(!html.matches("image/*"));
+        assert!(Mime::OCTET_STREAM.matches("application/octet-stream"));
+    }
+
+    #[test]
+    fn guesses_by_extension() {
+        assert!(EXTENSIONS.windows(2).all(|w| w[0].0 < w[1].0), "sorted");
+        assert!(
+            EXTENSIONS
+                .iter()
+                .all(|(_, mime)| Mime::parse(mime).is_some())
+        );
+        assert_eq!(Mime::from_path("app.JS").essence(), "text/javascript");
+        assert_eq!(Mime::from_path("a/b/logo.svg").suffix(), Some("xml"));
+        assert_eq!(Mime::from_path("README"), Mime::OCTET_STREAM);
+        assert_eq!(
+            Mime::parse("application/octet-stream").unwrap(),
+            Mime::OCTET_STREAM
+        );
+        assert_eq!(Mime::OCTET_STREAM.to_string(), "application/octet-stream");
+        assert_eq!(Mime::from_extension("exe"), None);
+    }
+}
@@ -5,13 +5,38 @@
 
 use std::fmt;
 
+pub mod certificate;
+pub mod chunked;
+pub mod client;
+pub mod connection;
+pub mod cookie;
+pub mod disposition;
+mod file;
+pub mod fuzz;
 pub mod headers;
+pub mod html;
+pub mod interim;
+pub mod link;
+pub mod mime;
 pub mod request;
 pub mod response;
+pub mod upgrade;
+pub mod uri;
 
-pub use headers::Headers;
-pub use request::Request;
+pub use certificate::PeerCertificate;
+pub use chunked::ChunkedWriter;
+pub use connection::ConnectionInfo;
+pub use cookie::{Cookie, SameSite};
+pub use disposition::{ContentDisposition, DispositionType};
+pub use headers::{HeaderName, Headers, QualityItem};
+pub use html::Html;
+pub use interim::{Interim, InterimError};
+pub use link::Link;
+pub use mime::Mime;
+pub use request::{Request, RequestBuilder};
 pub use response::Response;
+pub use upgrade::{OnUpgrade, Upgraded};
+pub use uri::{TargetForm, Uri, UriBuilder};
 
 /// An HTTP response status code.
 ///
@@ -31,6 +56,7 @@ pub enum StatusCode {
     // 1xx Informational
     Continue = 100,
     SwitchingProtocols = 101,
+    EarlyHints = 103,
 
     // 2xx Success
     Ok = 200,
@@ -53,13 +79,16 @@ pub enum StatusCode {
     Forbidden = 403,
     NotFound = 404,
     MethodNotAllowed = 405,
+    NotAcceptable = 406,
     Conflict = 409,
     Gone = 410,
     LengthRequired = 411,
     PayloadTooLarge = 413,
     UriTooLong = 414,
     UnsupportedMediaType = 415,
+    RangeNotSatisfiable = 416,
     UnprocessableEntity = 422,
+    UpgradeRequired = 426,
     TooManyRequests = 429,
 
     // 5xx Server Error
@@ -77,11 +106,65 @@ impl StatusCode {
         self as u16
     }
 
+    /// Returns the status code for `code`, or `None` if it is not one of the codes
+    /// this enum models.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::http::StatusCode;
+    ///
+    /// assert_eq!(StatusCode::from_u16(404), Some(StatusCode::NotFound));
+    /// assert_eq!(StatusCode::from_u16(299), None);
+    /// ```
+    pub fn from_u16(code: u16) -> Option<Self> {
+        const ALL: [StatusCode; 36] = [
+            StatusCode::Continue,
+            StatusCode::SwitchingProtocols,
+            StatusCode::EarlyHints,
+            StatusCode::Ok,
+            StatusCode::Created,
+            StatusCode::Accepted,
+            StatusCode::NoContent,
+            StatusCode::PartialContent,
+            StatusCode::MovedPermanently,
+            StatusCode::Found,
+            StatusCode::SeeOther,
+            StatusCode::NotModified,
+            StatusCode::TemporaryRedirect,
+            StatusCode::PermanentRedirect,
+            StatusCode::BadRequest,
+            StatusCode::Unauthorized,
+            StatusCode::Forbidden,
+            StatusCode::NotFound,
+            StatusCode::MethodNotAllowed,
+            StatusCode::NotAcceptable,
+            StatusCode::Conflict,
+            StatusCode::Gone,
+            StatusCode::LengthRequired,
+            StatusCode::PayloadTooLarge,
+            StatusCode::UriTooLong,
+            StatusCode::UnsupportedMediaType,
+            StatusCode::RangeNotSatisfiable,
+            StatusCode::UnprocessableEntity,
+            StatusCode::UpgradeRequired,
+            StatusCode::TooManyRequests,
+            StatusCode::InternalServerError,
+            StatusCode::NotImplemented,
+            StatusCode::BadGateway,
+            StatusCode::ServiceUnavailable,
+            StatusCode::GatewayTimeout,
+            StatusCode::HttpVersionNotSupported,
+        ];
+        ALL.into_iter().find(|status| status.as_u16() == code)
+    }
+
     /// Returns the canonical reason phrase for this status code.
     pub fn canonical_reason(self) -> &'static str {
         match self {
             Self::Continue => "Continue",
             Self::SwitchingProtocols => "Switching Protocols",
+            Self::EarlyHints => "Early Hints",
             Self::Ok => "OK",
             Self::Created => "Created",
             Self::Accepted => "Accepted",
@@ -98,13 +181,16 @@ impl StatusCode {
             Self::Forbidden => "Forbidden",
             Self::NotFound => "Not Found",
             Self::MethodNotAllowed => "Method Not Allowed",
+            Self::NotAcceptable => "Not Acceptable",
             Self::Conflict => "Conflict",
             Self::Gone => "Gone",
             Self::LengthRequired => "Length Required",
             Self::PayloadTooLarge => "Payload Too Large",
             Self::UriTooLong => "URI Too Long",
             Self::UnsupportedMediaType => "Unsupported Media Type",
+            Self::RangeNotSatisfiable => "Range Not Satisfiable",
             Self::UnprocessableEntity => "Unprocessable Entity",
+            Self::UpgradeRequired => "Upgrade Required",
             Self::TooManyRequests => "Too Many Requests",
             Self::InternalServerError => "Internal Server Error",
             Self::NotImplemented => "Not Implemented",
@@ -114,6 +200,21 @@ impl StatusCode {
             Self::HttpVersionNotSupported => "HTTP Version Not Supported",
         }
     }
+
+    /// Returns `true` for `1xx` informational status codes.
+    pub fn is_informational(self) -> bool {
+        (100..200).contains(&self.as_u16())
+    }
+
+    /// Returns `true` for `2xx` success status codes.
+    pub fn is_success(self) -> bool {
+        (200..300).contains(&self.as_u16())
+    }
+
+    /// Returns `true` for `3xx` redirection status codes.
+    pub fn is_redirection(self) -> bool {
+        (300..400).contains(&self.as_u16())
+    }
 }
 
 impl fmt::Display for StatusCode {
@@ -3,11 +3,14 @@
 use std::collections::HashMap;
 use std::fmt;
 use std::str;
+use std::sync::Arc;
+use std::time::Duration;
 
 use bytes::Bytes;
 use thiserror::Error;
 
-use super::{Headers, Method};
+use super::uri::decode_component;
+use super::{ConnectionInfo, HeaderName, Headers, Interim, Method, Uri};
 
 /// HTTP parsing errors
 #[derive(Debug)]
@@ -103,12 +106,86 @@ pub enum RequestError {
 
     #[error("request body exceeds maximum allowed size of {max_bytes} bytes")]
     BodyTooLarge { max_bytes: usize },
+
+    #[error("query string has more than {max} parameters")]
+    TooManyQueryParams { max: usize },
+
+    #[error("Cookie header has more than {max} cookies")]
+    TooManyCookies { max: usize },
+
+    #[error("line ends in a bare LF instead of CRLF")]
+    BareLineFeed,
+
+    #[error("whitespace between header name and colon")]
+    WhitespaceBeforeColon,
+
+    #[error("header name is not a token")]
+    InvalidHeaderName,
+
+    #[error("request target is not valid for {method}")]
+    InvalidTarget { method: Method },
+}
+
+/// Map type used for parsed query parameters and cookies.
+///
+/// Keys come from the client; [`ParseLimits`] caps how many entries a request may carry.
+pub type ParamMap = HashMap<String, String>;
+
+// Query parameters keep every value of a repeated key, in order.
+type QueryMap = HashMap<String, Vec<String>>;
+
+/// Per-request caps and strictness applied while parsing.
+///
+/// Inputs that exceed a cap are rejected with [`RequestError::TooManyQueryParams`] or
+/// [`RequestError::TooManyCookies`] before any per-entry allocation happens; the server
+/// answers these with `400 Bad Request`.
+///
+/// With [`strict`](Self::strict) on, framing that RFC 9112 lets recipients tolerate is
+/// rejected instead: bare LF line endings, whitespace before a header colon, header
+/// names that are not tokens, and request targets in a form the method does not allow.
+/// Lenient parsing is what lets a proxy in front and the server behind it disagree
+/// about where a request ends, so turn this on where request smuggling matters.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::http::request::{ParseLimits, Request, RequestError};
+///
+/// let limits = ParseLimits {
+///     max_query_params: 2,
+///     ..ParseLimits::default()
+/// };
+/// let raw = b"GET /?a=1&b=2&c=3 HTTP/1.1\r\nHost: localhost\r\n\r\n";
+/// assert!(matches!(
+///     Request::parse_with_limits(raw, &limits),
+///     Err(RequestError::TooManyQueryParams { max: 2 })
+/// ));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// Maximum number of `key=value` pairs accepted in the query string.
+    pub max_query_params: usize,
+    /// Maximum number of `name=value` pairs accepted across all `Cookie` headers.
+    pub max_cookies: usize,
+    /// Rejects requests that are only parseable leniently.
+    pub strict: bool,
+}
+
+impl Default for ParseLimits {
+    /// 100 query parameters, 50 cookies, and lenient parsing.
+    fn default() -> Self {
+        Self {
+            max_query_params: 100,
+            max_cookies: 50,
+            strict: false,
+        }
+    }
 }
 
 /// A fully parsed HTTP/1.1 request.
 ///
-/// Created by [`Request::parse`] from a raw byte buffer. The body is stored
-/// as a [`Bytes`] buffer.
+/// Created by [`Request::parse`] from a raw byte buffer, or in tests with
+/// [`Request::builder`]. The body is stored as a [`Bytes`] buffer.
 ///
 /// # Examples
 ///
@@ -126,13 +203,16 @@ pub enum RequestError {
 #[derive(Debug)]
 pub struct Request {
     method: Method,
-    path: String,
+    uri: Uri,
     /// HTTP minor version: 0 for HTTP/1.0, 1 for HTTP/1.1.
     version: u8,
     headers: Headers,
-    query: Option<String>,
     body: Bytes,
-    params: HashMap<String, String>,
+    params: QueryMap,
+    cookies: ParamMap,
+    connection: Option<Arc<ConnectionInfo>>,
+    interim: Interim,
+    read_duration: Option<Duration>,
 }
 
 impl Request {
@@ -149,7 +229,52 @@ impl Request {
     /// - [`RequestError::Incomplete`] — more data is needed to complete the request headers.
     /// - [`RequestError::Parse`] — the data is malformed and cannot be parsed.
     /// - [`RequestError::MissingField`] — a required field (method, path, version) is absent.
+    /// - [`RequestError::TooManyQueryParams`] / [`RequestError::TooManyCookies`] — the
+    ///   request exceeds the default [`ParseLimits`].
     pub fn parse(buf: &[u8]) -> Result<(Self, usize), RequestError> {
+        Self::parse_with_limits(buf, &ParseLimits::default())
+    }
+
+    /// Parse a raw HTTP/1.1 request, rejecting anything that is only parseable
+    /// leniently.
+    ///
+    /// # Errors
+    ///
+    /// Those of [`parse`](Self::parse), plus [`RequestError::BareLineFeed`],
+    /// [`RequestError::WhitespaceBeforeColon`], [`RequestError::InvalidHeaderName`],
+    /// and [`RequestError::InvalidTarget`]; see [`ParseLimits::strict`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::http::request::{Request, RequestError};
+    ///
+    /// let raw = b"GET / HTTP/1.1\nHost: localhost\n\n";
+    /// assert!(Request::parse(raw).is_ok());
+    /// assert!(matches!(Request::parse_strict(raw), Err(RequestError::BareLineFeed)));
+    /// ```
+    pub fn parse_strict(buf: &[u8]) -> Result<(Self, usize), RequestError> {
+        let limits = ParseLimits {
+            strict: true,
+            ..ParseLimits::default()
+        };
+        Self::parse_with_limits(buf, &limits)
+    }
+
+    /// Parse a raw HTTP/1.1 request, enforcing the given [`ParseLimits`].
+    ///
+    /// Behaves like [`parse`](Self::parse) otherwise.
+    ///
+    /// # Errors
+    ///
+    /// See [`parse`](Self::parse).
+    pub fn parse_with_limits(
+        buf: &[u8],
+        limits: &ParseLimits,
+    ) -> Result<(Self, usize), RequestError> {
+        if limits.strict {
+            check_strict(buf)?;
+        }
         let mut headers = [httparse::EMPTY_HEADER; Self::MAX_HEADERS];
         let mut raw_req = httparse::Request::new(&mut headers);
 
@@ -168,13 +293,7 @@ impl Request {
             .path
             .ok_or(RequestError::MissingField { field: "path" })?;
 
-        let (path, query) = match raw_path.find('?') {
-            Some(pos) => (
-                raw_path[..pos].to_owned(),
-                Some(raw_path[pos + 1..].to_owned()),
-            ),
-            None => (raw_path.to_owned(), None),
-        };
+        let uri = Uri::parse(raw_path);
 
         let version = raw_req
             .version
@@ -187,18 +306,25 @@ impl Request {
             }
         }
 
-        let params = query.as_deref().map(parse_query_string).unwrap_or_default();
+        let params = match uri.query() {
+            Some(q) => parse_query_string(q, limits.max_query_params)?,
+            None => QueryMap::default(),
+        };
+        let cookies = parse_cookies(&header_map, limits.max_cookies)?;
         let body = Bytes::copy_from_slice(&buf[body_offset..]);
 
         Ok((
             Self {
                 method,
-                path,
+                uri,
                 version,
                 headers: header_map,
-                query,
                 body,
                 params,
+                cookies,
+                connection: None,
+                interim: Interim::default(),
+                read_duration: None,
             },
             body_offset,
         ))
@@ -210,8 +336,28 @@ impl Request {
     }
 
     /// Returns the request path (without the query string).
+    ///
+    /// For an absolute-form target such as `http://example.com/users` this is the path
+    /// part, `/users`; for `CONNECT`'s authority-form it is empty. See [`uri`](Self::uri).
     pub fn path(&self) -> &str {
-        &self.path
+        self.uri.path()
+    }
+
+    /// Returns the request target, with its form and any scheme and authority.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::http::{Request, TargetForm};
+    ///
+    /// let raw = b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n";
+    /// let (request, _) = Request::parse(raw).unwrap();
+    /// assert_eq!(request.uri().form(), TargetForm::Authority);
+    /// assert_eq!(request.uri().host(), Some("example.com"));
+    /// assert_eq!(request.uri().port(), Some(443));
+    /// ```
+    pub fn uri(&self) -> &Uri {
+        &self.uri
     }
 
     /// Returns the HTTP minor version number (0 = HTTP/1.0, 1 = HTTP/1.1).
@@ -226,12 +372,44 @@ impl Request {
 
     /// Returns the raw query string (without the leading `?`), if any.
     pub fn query_string(&self) -> Option<&str> {
-        self.query.as_deref()
+        self.uri.query()
     }
 
-    /// Returns a parsed query parameter value by key.
+    /// Returns a query parameter's decoded value by key.
+    ///
+    /// When the key repeats, the last value wins; see
+    /// [`query_params_all`](Self::query_params_all) for the others.
     pub fn query_param(&self, key: &str) -> Option<&str> {
-        self.params.get(key).map(String::as_str)
+        self.params.get(key)?.last().map(String::as_str)
+    }
+
+    /// Returns every value of a repeated query parameter, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::Request;
+    ///
+    /// let request = Request::builder().path("/posts?tag=rust&tag=http&page=2").build();
+    /// assert_eq!(request.query_params_all("tag").collect::<Vec<_>>(), ["rust", "http"]);
+    /// assert_eq!(request.query_params_all("missing").count(), 0);
+    /// ```
+    pub fn query_params_all<'a>(&'a self, key: &str) -> impl Iterator<Item = &'a str> + 'a {
+        self.params
+            .get(key)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// Returns a cookie value by name, parsed from the `Cookie` header(s).
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies.get(name).map(String::as_str)
+    }
+
+    /// Returns all parsed cookies.
+    pub fn cookies(&self) -> &ParamMap {
+        &self.cookies
     }
 
     /// Returns the request body bytes.
@@ -239,6 +417,46 @@ impl Request {
         &self.body
     }
 
+    /// Returns metadata about the connection this request arrived on (peer address, TLS
+    /// state, client certificates).
+    ///
+    /// Always set for requests read by [`Server`](crate::server::Server); `None` for
+    /// requests parsed directly from bytes.
+    pub fn connection(&self) -> Option<&ConnectionInfo> {
+        self.connection.as_deref()
+    }
+
+    /// Attaches connection metadata. Called by the server for every request; useful in
+    /// tests that exercise connection-aware middleware.
+    pub fn set_connection(&mut self, connection: Arc<ConnectionInfo>) {
+        self.connection = Some(connection);
+    }
+
+    /// Returns the handle for sending informational responses before the final one.
+    ///
+    /// Disabled unless the server attached it, which it does for HTTP/1.1 requests.
+    pub fn interim(&self) -> &Interim {
+        &self.interim
+    }
+
+    /// Attaches the informational response handle. Called by the server.
+    pub fn set_interim(&mut self, interim: Interim) {
+        self.interim = interim;
+    }
+
+    /// Returns how long the server took to receive this request, from its first bytes
+    /// to the end of its body.
+    ///
+    /// `None` for requests not read by [`Server`](crate::server::Server).
+    pub fn read_duration(&self) -> Option<Duration> {
+        self.read_duration
+    }
+
+    /// Records how long the request took to receive. Called by the server.
+    pub fn set_read_duration(&mut self, duration: Duration) {
+        self.read_duration = Some(duration);
+    }
+
     /// Returns `true` if the connection should be kept alive after this request.
     ///
     /// HTTP/1.1 defaults to keep-alive. HTTP/1.0 defaults to close unless
@@ -254,28 +472,283 @@ impl Request {
     pub fn content_length(&self) -> Option<usize> {
         self.headers.get("content-length")?.parse().ok()
     }
+
+    /// Starts building a request in code, for tests and fixtures.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::{Method, Request};
+    ///
+    /// let request = Request::builder()
+    ///     .method(Method::Post)
+    ///     .path("/users?notify=true")
+    ///     .header("Content-Type", "application/json")
+    ///     .body(r#"{"name":"Ada"}"#)
+    ///     .build();
+    ///
+    /// assert_eq!(request.path(), "/users");
+    /// assert_eq!(request.query_param("notify"), Some("true"));
+    /// assert_eq!(request.content_length(), Some(14));
+    /// ```
+    pub fn builder() -> RequestBuilder {
+        RequestBuilder::default()
+    }
 }
 
-/// Parses a URL query string (`key=value&key2=value2`) into a `HashMap`.
+/// Builds a [`Request`] without writing raw HTTP.
 ///
-/// Keys and values have `+` decoded as a space. Full percent-decoding is
-/// intentionally omitted here; it will be added with the `percent-encoding`
-/// crate when the `context` module is implemented.
-fn parse_query_string(query: &str) -> HashMap<String, String> {
-    query
-        .split('&')
-        .filter_map(|pair| {
-            let mut parts = pair.splitn(2, '=');
-            let key = parts.next()?.replace('+', " ");
-            let value = parts.next().unwrap_or("").replace('+', " ");
-            Some((key, value))
-        })
-        .collect()
+/// Defaults to `GET /` over HTTP/1.1 with no headers. Query parameters and cookies are
+/// parsed from the path and `Cookie` headers as [`Request::parse`] would, without its
+/// limits.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct RequestBuilder {
+    method: Method,
+    target: String,
+    version: u8,
+    headers: Headers,
+    body: Bytes,
+    connection: Option<Arc<ConnectionInfo>>,
+}
+
+impl Default for RequestBuilder {
+    fn default() -> Self {
+        Self {
+            method: Method::Get,
+            target: "/".to_owned(),
+            version: 1,
+            headers: Headers::new(),
+            body: Bytes::new(),
+            connection: None,
+        }
+    }
+}
+
+impl RequestBuilder {
+    /// Sets the method.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Sets the request target: usually a path, optionally followed by `?` and a query
+    /// string, but any form [`Uri::parse`] accepts.
+    pub fn path(mut self, target: impl Into<String>) -> Self {
+        self.target = target.into();
+        self
+    }
+
+    /// Sets the HTTP minor version: 0 for HTTP/1.0, 1 for HTTP/1.1.
+    pub fn version(mut self, version: u8) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Appends a header.
+    pub fn header(mut self, name: impl Into<HeaderName>, value: impl Into<String>) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Sets the body. `Content-Length` is added on [`build`](Self::build) unless set.
+    pub fn body(mut self, body: impl Into<Bytes>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Attaches connection metadata, as the server would.
+    pub fn connection(mut self, connection: Arc<ConnectionInfo>) -> Self {
+        self.connection = Some(connection);
+        self
+    }
+
+    /// Builds the request.
+    pub fn build(self) -> Request {
+        let uri = Uri::parse(&self.target);
+        let mut headers = self.headers;
+        if !self.body.is_empty() && !headers.contains("content-length") {
+            headers.insert("Content-Length", self.body.len().to_string());
+        }
+        // Without limits neither parser can fail.
+        let params = uri
+            .query()
+            .map(|q| parse_query_string(q, usize::MAX).unwrap_or_default())
+            .unwrap_or_default();
+        let cookies = parse_cookies(&headers, usize::MAX).unwrap_or_default();
+        Request {
+            method: self.method,
+            uri,
+            version: self.version,
+            headers,
+            body: self.body,
+            params,
+            cookies,
+            connection: self.connection,
+            interim: Interim::default(),
+            read_duration: None,
+        }
+    }
+}
+
+/// Checks the complete lines of the request head for framing that only lenient parsing
+/// accepts, leaving everything else — including incompleteness — to `httparse`.
+fn check_strict(buf: &[u8]) -> Result<(), RequestError> {
+    let mut lines = buf.split_inclusive(|&b| b == b'\n');
+    // Ignore the partial line at the end of an incomplete head.
+    let mut next_line = || lines.next().filter(|line| line.ends_with(b"\n"));
+
+    let Some(request_line) = next_line() else {
+        return Ok(());
+    };
+    let request_line = strip_crlf(request_line)?;
+    let mut parts = request_line.split(|&b| b == b' ');
+    if let (Some(method), Some(target)) = (parts.next(), parts.next()) {
+        let method: Method = String::from_utf8_lossy(method).parse().unwrap(); // Infallible
+        if !valid_target(&method, target) {
+            return Err(RequestError::InvalidTarget { method });
+        }
+    }
+
+    while let Some(line) = next_line() {
+        let line = strip_crlf(line)?;
+        if line.is_empty() {
+            break;
+        }
+        let name_end = line.iter().position(|&b| b == b':').unwrap_or(line.len());
+        let name = &line[..name_end];
+        if name.last().is_some_and(|&b| b == b' ' || b == b'\t') {
+            return Err(RequestError::WhitespaceBeforeColon);
+        }
+        if name.is_empty() || !name.iter().all(|&b| is_tchar(b)) {
+            return Err(RequestError::InvalidHeaderName);
+        }
+    }
+    Ok(())
+}
+
+fn strip_crlf(line: &[u8]) -> Result<&[u8], RequestError> {
+    line.strip_suffix(b"\r\n").ok_or(RequestError::BareLineFeed)
+}
+
+fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+// RFC 9112 §3.2: origin-form for ordinary requests, absolute-form for proxies,
+// authority-form only for CONNECT, and asterisk-form only for OPTIONS.
+fn valid_target(method: &Method, target: &[u8]) -> bool {
+    let visible = |b: &u8| (0x21..=0x7e).contains(b) && *b != b'#';
+    if target.is_empty() || !target.iter().all(visible) {
+        return false;
+    }
+    if *method == Method::Connect {
+        let Some(colon) = target.iter().rposition(|&b| b == b':') else {
+            return false;
+        };
+        let (host, port) = (&target[..colon], &target[colon + 1..]);
+        return !host.is_empty()
+            && !host.contains(&b'/')
+            && !host.contains(&b'@')
+            && !port.is_empty()
+            && port.len() <= 5
+            && port.iter().all(u8::is_ascii_digit);
+    }
+    match target[0] {
+        b'/' => true,
+        b'*' => target.len() == 1 && *method == Method::Options,
+        _ => {
+            let scheme_end = target.iter().position(|&b| b == b':');
+            scheme_end.is_some_and(|end| {
+                end > 0
+                    && target[0].is_ascii_alphabetic()
+                    && target[..end]
+                        .iter()
+                        .all(|&b| b.is_ascii_alphanumeric() || b"+-.".contains(&b))
+                    && target[end..].starts_with(b"://")
+                    && target.len() > end + 3
+            })
+        }
+    }
+}
+
+/// Parses a URL query string (`key=value&key2=value2`), keeping every value of a
+/// repeated key.
+///
+/// Keys and values are decoded like [`Uri::query_pairs`]: `+` becomes a space,
+/// percent-escapes are decoded, and malformed escapes are kept as they are.
+///
+/// Pairs are counted before anything is allocated so that oversized inputs are
+/// rejected cheaply.
+fn parse_query_string(query: &str, max: usize) -> Result<QueryMap, RequestError> {
+    let pairs = || query.split('&').filter(|pair| !pair.is_empty());
+    if pairs().count() > max {
+        return Err(RequestError::TooManyQueryParams { max });
+    }
+
+    let mut params = QueryMap::default();
+    for pair in pairs() {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let decode = |s| decode_component(s).unwrap_or_else(|lossy| lossy);
+        params.entry(decode(key)).or_default().push(decode(value));
+    }
+    Ok(params)
+}
+
+/// Parses every `Cookie` header (`name=value; name2=value2`) into a [`ParamMap`].
+///
+/// Pairs without `=` are ignored. When a name repeats, the first occurrence wins, matching
+/// how browsers order more specific cookies first. The total pair count is checked against
+/// `max` before any allocation.
+fn parse_cookies(headers: &Headers, max: usize) -> Result<ParamMap, RequestError> {
+    let pairs = || {
+        headers
+            .get_all("cookie")
+            .flat_map(|h| h.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+    };
+    if pairs().count() > max {
+        return Err(RequestError::TooManyCookies { max });
+    }
+
+    let mut cookies = ParamMap::default();
+    for (name, value) in pairs() {
+        let value = value.trim().trim_matches('"');
+        cookies
+            .entry(name.trim().to_owned())
+            .or_insert_with(|| value.to_owned());
+    }
+    Ok(cookies)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::http::TargetForm;
+
+    #[test]
+    fn builder_matches_parse() {
+        let built = Request::builder()
+            .method(Method::Post)
+            .path("/search?q=rust+lang&page=2")
+            .header("Cookie", "session=abc; theme=dark")
+            .body("hello")
+            .build();
+        let raw =
+            b"POST /search?q=rust+lang&page=2 HTTP/1.1\r\nCookie: session=abc; theme=dark\r\n\
+            Content-Length: 5\r\n\r\nhello";
+        let (parsed, _) = Request::parse(raw).unwrap();
+
+        assert_eq!(built.method(), parsed.method());
+        assert_eq!(built.path(), parsed.path());
+        assert_eq!(built.query_string(), parsed.query_string());
+        assert_eq!(built.query_param("q"), Some("rust lang"));
+        assert_eq!(built.cookie("theme"), parsed.cookie("theme"));
+        assert_eq!(built.body(), parsed.body());
+        assert_eq!(built.content_length(), Some(5));
+        assert!(built.is_keep_alive());
+        assert!(!Request::builder().version(0).build().is_keep_alive());
+    }
 
     #[test]
     fn parse_simple_get() {
@@ -298,6 +771,18 @@ mod tests {
         assert_eq!(req.query_param("page"), Some("2"));
     }
 
+    #[test]
+    fn query_params_are_percent_decoded() {
+        let raw = b"GET /search?q=a%20b&tag=x+y&tag=%E2%82%AC&bad=%zz HTTP/1.1\r\n\r\n";
+        let (req, _) = Request::parse(raw).unwrap();
+        assert_eq!(req.query_param("q"), Some("a b"));
+        assert_eq!(
+            req.query_params_all("tag").collect::<Vec<_>>(),
+            ["x y", "€"]
+        );
+        assert_eq!(req.query_param("bad"), Some("%zz"));
+    }
+
     #[test]
     fn incomplete_request() {
         let raw = b"GET / HTTP/1.1\r\nHost:";
@@ -318,6 +803,62 @@ mod tests {
         assert!(!req.is_keep_alive());
     }
 
+    #[test]
+    fn query_param_limit() {
+        let limits = ParseLimits {
+            max_query_params: 2,
+            ..ParseLimits::default()
+        };
+        let ok = b"GET /?a=1&b=2 HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        assert!(Request::parse_with_limits(ok, &limits).is_ok());
+
+        let flood = b"GET /?a=1&a=1&a=1 HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        assert!(matches!(
+            Request::parse_with_limits(flood, &limits),
+            Err(RequestError::TooManyQueryParams { max: 2 })
+        ));
+    }
+
+    #[test]
+    fn query_param_limit_ignores_empty_pairs() {
+        let limits = ParseLimits {
+            max_query_params: 1,
+            ..ParseLimits::default()
+        };
+        let raw = b"GET /?a=1&&& HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        assert!(Request::parse_with_limits(raw, &limits).is_ok());
+    }
+
+    #[test]
+    fn cookies_parsed() {
+        let raw = b"GET / HTTP/1.1\r\nCookie: sid=abc; theme=\"dark\"\r\nCookie: lang=en\r\n\r\n";
+        let (req, _) = Request::parse(raw).unwrap();
+        assert_eq!(req.cookie("sid"), Some("abc"));
+        assert_eq!(req.cookie("theme"), Some("dark"));
+        assert_eq!(req.cookie("lang"), Some("en"));
+        assert_eq!(req.cookies().len(), 3);
+    }
+
+    #[test]
+    fn cookie_first_occurrence_wins() {
+        let raw = b"GET / HTTP/1.1\r\nCookie: sid=first; sid=second\r\n\r\n";
+        let (req, _) = Request::parse(raw).unwrap();
+        assert_eq!(req.cookie("sid"), Some("first"));
+    }
+
+    #[test]
+    fn cookie_limit() {
+        let limits = ParseLimits {
+            max_cookies: 2,
+            ..ParseLimits::default()
+        };
+        let raw = b"GET / HTTP/1.1\r\nCookie: a=1; b=2\r\nCookie: c=3\r\n\r\n";
+        assert!(matches!(
+            Request::parse_with_limits(raw, &limits),
+            Err(RequestError::TooManyCookies { max: 2 })
+        ));
+    }
+
     #[test]
     fn content_length() {
         let raw = b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello";
@@ -325,4 +866,80 @@ mod tests {
         assert_eq!(req.content_length(), Some(5));
         assert_eq!(&raw[body_offset..], b"hello");
     }
+
+    #[test]
+    fn strict_rejects_lenient_framing() {
+        let strict = |raw: &[u8]| Request::parse_strict(raw).map(|(req, _)| req);
+        assert!(strict(b"GET /a?b=1 HTTP/1.1\r\nHost: x\r\n\r\nbody\n").is_ok());
+        assert!(matches!(
+            strict(b"GET / HTTP/1.1\r\nHost: x\n\r\n"),
+            Err(RequestError::BareLineFeed)
+        ));
+        assert!(matches!(
+            strict(b"GET / HTTP/1.1\r\nHost : x\r\n\r\n"),
+            Err(RequestError::WhitespaceBeforeColon)
+        ));
+        assert!(matches!(
+            strict(b"GET / HTTP/1.1\r\nHo(st: x\r\n\r\n"),
+            Err(RequestError::InvalidHeaderName)
+        ));
+        assert!(matches!(
+            strict(b"GET / HTTP/1.1\r\n folded: x\r\n\r\n"),
+            Err(RequestError::InvalidHeaderName)
+        ));
+        // Problems are reported before the head is complete.
+        assert!(matches!(
+            strict(b"GET / HTTP/1.1\nHost"),
+            Err(RequestError::BareLineFeed)
+        ));
+        assert!(matches!(
+            strict(b"GET / HTTP/1.1\r\nHost: x\r\n"),
+            Err(RequestError::Incomplete)
+        ));
+    }
+
+    #[test]
+    fn strict_checks_target_forms() {
+        let target = |line: &str| {
+            let raw = format!("{line} HTTP/1.1\r\nHost: x\r\n\r\n");
+            match Request::parse_strict(raw.as_bytes()) {
+                Ok(_) => true,
+                Err(RequestError::InvalidTarget { .. }) => false,
+                Err(e) => panic!("{line}: {e}"),
+            }
+        };
+        assert!(target("GET /users/1?x=y"));
+        assert!(target("GET http://example.com/users"));
+        assert!(target("OPTIONS *"));
+        assert!(target("CONNECT example.com:443"));
+        assert!(!target("GET *"));
+        assert!(!target("GET users"));
+        assert!(!target("GET /users#top"));
+        assert!(!target("GET example.com:443"));
+        assert!(!target("CONNECT /"));
+        assert!(!target("CONNECT example.com"));
+        assert!(!target("CONNECT user@example.com:443"));
+
+        let raw = b"GET users HTTP/1.1\r\n\r\n";
+        assert!(Request::parse(raw).is_ok());
+    }
+
+    #[test]
+    fn absolute_and_authority_targets() {
+        let raw = b"GET http://example.com/users?page=2 HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        let (req, _) = Request::parse(raw).unwrap();
+        assert_eq!(req.uri().form(), TargetForm::Absolute);
+        assert_eq!(req.uri().authority(), Some("example.com"));
+        assert_eq!(req.path(), "/users");
+        assert_eq!(req.query_param("page"), Some("2"));
+
+        let raw = b"CONNECT example.com:443 HTTP/1.1\r\n\r\n";
+        let (req, _) = Request::parse_strict(raw).unwrap();
+        assert_eq!(req.uri().form(), TargetForm::Authority);
+        assert_eq!(req.path(), "");
+
+        let req = Request::builder().path("http://example.com").build();
+        assert_eq!(req.path(), "/");
+        assert_eq!(req.uri().scheme(), Some("http"));
+    }
 }
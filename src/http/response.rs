@@ -3,9 +3,17 @@
 //! Provides a fluent builder API for constructing HTTP responses and
 //! serializing them to a byte buffer for transmission over TCP.
 
+use std::{backtrace::Backtrace, error::Error, fmt, io, path::Path, time::Duration};
+
 use bytes::{BufMut, BytesMut};
 
-use super::{Headers, StatusCode};
+use super::{
+    ChunkedWriter, ContentDisposition, Cookie, HeaderName, Headers, Link, Method, Mime, Request,
+    StatusCode, Uri,
+    file::{ByteRange, FileBody, parse_range},
+    headers::{CONNECTION, CONTENT_TYPE},
+    upgrade::OnUpgrade,
+};
 
 /// An HTTP/1.1 response, ready to be serialized and sent.
 ///
@@ -29,6 +37,49 @@ pub struct Response {
     headers: Headers,
     body: Vec<u8>,
     keep_alive: bool,
+    upgrade: Option<OnUpgrade>,
+    streamed: bool,
+    // Boxed: most responses have no file, and `Response` is often returned in `Err`.
+    file: Option<Box<FileBody>>,
+    // The failure behind a `server_error` response, boxed for the same reason.
+    fault: Option<Box<Fault>>,
+    // Boxed likewise: few responses are watched.
+    sent: Option<Box<SentHooks>>,
+}
+
+// Callbacks waiting for the response to be written. Dropping them unwritten runs
+// them with `None`.
+#[derive(Default)]
+pub(crate) struct SentHooks(Vec<Box<dyn FnOnce(Option<Duration>) + Send + Sync>>);
+
+impl SentHooks {
+    // Runs the callbacks with how long writing the response took.
+    pub(crate) fn sent(mut self, write: Duration) {
+        for hook in self.0.drain(..) {
+            hook(Some(write));
+        }
+    }
+}
+
+impl Drop for SentHooks {
+    fn drop(&mut self) {
+        for hook in self.0.drain(..) {
+            hook(None);
+        }
+    }
+}
+
+impl fmt::Debug for SentHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SentHooks({})", self.0.len())
+    }
+}
+
+// An error attached to a response, with where it was raised.
+#[derive(Debug)]
+pub(crate) struct Fault {
+    pub(crate) error: Box<dyn Error + Send + Sync>,
+    pub(crate) backtrace: Backtrace,
 }
 
 impl Response {
@@ -39,22 +90,210 @@ impl Response {
             headers: Headers::new(),
             body: Vec::new(),
             keep_alive: true,
+            upgrade: None,
+            streamed: false,
+            file: None,
+            fault: None,
+            sent: None,
+        }
+    }
+
+    /// Creates a `500 Internal Server Error` response for `error`.
+    ///
+    /// The client sees only a generic message. The error and a backtrace (captured when
+    /// `RUST_BACKTRACE` is set) travel with the response, for
+    /// [`RecoveryMiddleware`](crate::middleware::RecoveryMiddleware) to log and, in
+    /// developer mode, to show.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::{Response, StatusCode};
+    ///
+    /// let err = std::fs::read("/definitely/missing").unwrap_err();
+    /// let response = Response::server_error(err);
+    /// assert_eq!(response.status(), StatusCode::InternalServerError);
+    /// assert_eq!(response.body_text(), "Internal Server Error");
+    /// assert!(response.error().is_some());
+    /// ```
+    pub fn server_error(error: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        let mut response = Self::new(StatusCode::InternalServerError).body("Internal Server Error");
+        response.fault = Some(Box::new(Fault {
+            error: error.into(),
+            backtrace: Backtrace::capture(),
+        }));
+        response
+    }
+
+    /// Creates a redirect to `location` with the given `3xx` status.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::{Response, StatusCode, http::Uri};
+    ///
+    /// let location = Uri::builder().segment("orders").segment("7").build();
+    /// let response = Response::redirect(StatusCode::SeeOther, &location);
+    /// assert_eq!(response.headers().get("Location"), Some("/orders/7"));
+    /// ```
+    pub fn redirect(status: StatusCode, location: &Uri) -> Self {
+        Self::new(status).header("Location", location.to_string())
+    }
+
+    /// Creates a `200 OK` response streaming the file at `path` from disk, shown inline.
+    ///
+    /// Sets `Content-Type` from the extension, `Content-Length`, `Content-Disposition`,
+    /// and `Accept-Ranges: bytes`. Like any [streamed](Self::stream) response it closes
+    /// the connection afterwards. Pass it through [`for_request`](Self::for_request) to
+    /// honour `Range` and `HEAD`.
+    ///
+    /// `path` is used as given; resolve untrusted paths with
+    /// [`safe_join`](crate::security::safe_join) first.
+    ///
+    /// # Errors
+    ///
+    /// Returns the I/O error from looking the file up, with kind `NotFound` if `path`
+    /// is not a regular file.
+    pub async fn file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self::from_file(path, ContentDisposition::inline().filename(name)).await
+    }
+
+    /// Like [`file`](Self::file), but asks the browser to download it as `filename`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`file`](Self::file).
+    pub async fn attachment(path: impl AsRef<Path>, filename: &str) -> io::Result<Self> {
+        Self::from_file(
+            path.as_ref(),
+            ContentDisposition::attachment().filename(filename),
+        )
+        .await
+    }
+
+    async fn from_file(path: &Path, disposition: ContentDisposition) -> io::Result<Self> {
+        let body = FileBody::open(path).await?;
+        let mut response = Self::new(StatusCode::Ok)
+            .header("Content-Type", Mime::from_path(path).to_string())
+            .disposition(&disposition)
+            .header("Accept-Ranges", "bytes");
+        response.streamed = true;
+        response.keep_alive = false;
+        response.file = Some(Box::new(body));
+        Ok(response)
+    }
+
+    /// Adapts a [file](Self::file) response to `request`: a single-range `Range` header
+    /// yields `206 Partial Content` with `Content-Range`, a range past the end `416 Range
+    /// Not Satisfiable`, and `HEAD` sends the headers alone. Requests with `If-Range`,
+    /// or several ranges, get the whole file. Other responses are returned unchanged.
+    #[must_use]
+    pub fn for_request(mut self, request: &Request) -> Self {
+        let Some(file) = &mut self.file else {
+            return self;
+        };
+        if *request.method() == Method::Head {
+            file.send = false;
         }
+        let range = match request.headers().get("range") {
+            Some(range) if !request.headers().contains("if-range") => parse_range(range, file.size),
+            _ => ByteRange::Full,
+        };
+        match range {
+            ByteRange::Full => {}
+            ByteRange::Partial(first, last) => {
+                file.offset = first;
+                file.len = last - first + 1;
+                let content_range = format!("bytes {first}-{last}/{}", file.size);
+                self.status = StatusCode::PartialContent;
+                self.headers.insert("Content-Range", content_range);
+            }
+            ByteRange::Unsatisfiable => {
+                let content_range = format!("bytes */{}", file.size);
+                self.status = StatusCode::RangeNotSatisfiable;
+                self.headers.insert("Content-Range", content_range);
+                self.file = None;
+                self.streamed = false;
+            }
+        }
+        self
     }
 
     /// Appends a response header. Multiple calls with the same name are additive.
     #[must_use]
-    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+    pub fn header(mut self, name: impl Into<HeaderName>, value: impl Into<String>) -> Self {
         self.headers.insert(name, value);
         self
     }
 
     /// Appends a header in-place. Intended for middleware pipelines that receive
     /// a `Response` from downstream and need to decorate it without consuming it.
-    pub fn add_header(&mut self, name: impl Into<String>, value: impl Into<String>) {
+    pub fn add_header(&mut self, name: impl Into<HeaderName>, value: impl Into<String>) {
         self.headers.insert(name, value);
     }
 
+    /// Appends a `Set-Cookie` header for `cookie`.
+    #[must_use]
+    pub fn cookie(self, cookie: &Cookie) -> Self {
+        self.header("Set-Cookie", cookie.to_string())
+    }
+
+    /// Appends a `Set-Cookie` header in-place (see [`add_header`](Self::add_header)).
+    pub fn add_cookie(&mut self, cookie: &Cookie) {
+        self.headers.insert("Set-Cookie", cookie.to_string());
+    }
+
+    /// Sets the `Content-Disposition` header, replacing any earlier one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::{Response, StatusCode, http::ContentDisposition};
+    ///
+    /// let response = Response::new(StatusCode::Ok)
+    ///     .body("id,total\n1,9.50\n")
+    ///     .disposition(&ContentDisposition::attachment().filename("commandes-été.csv"));
+    /// assert_eq!(
+    ///     response.headers().get("Content-Disposition"),
+    ///     Some("attachment; filename=\"commandes-_t_.csv\"; \
+    ///           filename*=UTF-8''commandes-%C3%A9t%C3%A9.csv"),
+    /// );
+    /// ```
+    #[must_use]
+    pub fn disposition(mut self, disposition: &ContentDisposition) -> Self {
+        self.headers.remove("Content-Disposition");
+        self.headers
+            .insert("Content-Disposition", disposition.to_string());
+        self
+    }
+
+    /// Appends a `Link` header for `link`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::{Response, StatusCode, http::Link};
+    ///
+    /// let response = Response::new(StatusCode::Ok)
+    ///     .link(&Link::preload("/app.css", "style"))
+    ///     .link(&Link::new("/docs").rel("help"));
+    /// assert_eq!(response.headers().get_all("Link").count(), 2);
+    /// ```
+    #[must_use]
+    pub fn link(self, link: &Link) -> Self {
+        self.header("Link", link.to_string())
+    }
+
+    /// Appends a `Link` header in-place (see [`add_header`](Self::add_header)).
+    pub fn add_link(&mut self, link: &Link) {
+        self.headers.insert("Link", link.to_string());
+    }
+
     /// Sets the response body from a string.
     ///
     /// The `Content-Length` header is written automatically by [`into_bytes`](Self::into_bytes).
@@ -65,6 +304,8 @@ impl Response {
     }
 
     /// Sets the response body from raw bytes.
+    ///
+    /// This is a builder; read the body back with [`body_ref`](Self::body_ref).
     #[must_use]
     pub fn body_bytes(mut self, body: impl Into<Vec<u8>>) -> Self {
         self.body = body.into();
@@ -78,11 +319,167 @@ impl Response {
         self
     }
 
+    /// Takes over the connection once this response is sent.
+    ///
+    /// Only honoured on `101 Switching Protocols` responses: after writing the response
+    /// the server stops parsing HTTP on the connection and runs `on_upgrade` with it.
+    /// The handler is responsible for the `Upgrade` and `Connection` headers.
+    #[must_use]
+    pub fn on_upgrade(mut self, on_upgrade: OnUpgrade) -> Self {
+        self.upgrade = Some(on_upgrade);
+        self
+    }
+
+    /// Streams the body instead of sending a fixed one.
+    ///
+    /// The response is sent without `Content-Length` and with `Connection: close`, after
+    /// which the server runs `writer` on the connection; the body is whatever it writes,
+    /// and it ends when `writer` returns. Used for
+    /// [Server-Sent Events](crate::realtime::sse).
+    #[must_use]
+    pub fn stream(mut self, writer: OnUpgrade) -> Self {
+        self.upgrade = Some(writer);
+        self.streamed = true;
+        self.keep_alive = false;
+        self
+    }
+
+    /// Streams the body with `Transfer-Encoding: chunked`.
+    ///
+    /// Like [`stream`](Self::stream), but every write `body` makes through the
+    /// [`ChunkedWriter`] is framed as a chunk, so the client can tell a finished body from
+    /// a broken connection. When `body` returns the writer, the last chunk is sent with
+    /// any trailers it added; if `body` fails, the body is left unterminated, which the
+    /// client sees as an error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use rttp::{Response, StatusCode};
+    /// use tokio::io::AsyncWriteExt;
+    ///
+    /// let response = Response::new(StatusCode::Ok)
+    ///     .header("Content-Type", "text/csv")
+    ///     .trailer("Content-Digest")
+    ///     .trailer("X-Rows")
+    ///     .chunked(|mut body| async move {
+    ///         body.digest_trailer();
+    ///         let mut rows = 0;
+    ///         for id in 0..1_000 {
+    ///             body.write_all(format!("{id},row {id}\n").as_bytes()).await?;
+    ///             rows += 1;
+    ///         }
+    ///         body.trailer("X-Rows", rows.to_string());
+    ///         Ok(body)
+    ///     });
+    /// ```
+    #[must_use]
+    pub fn chunked<F, Fut>(self, body: F) -> Self
+    where
+        F: FnOnce(ChunkedWriter) -> Fut + Send + 'static,
+        Fut: Future<Output = io::Result<ChunkedWriter>> + Send + 'static,
+    {
+        self.header("Transfer-Encoding", "chunked")
+            .stream(OnUpgrade::new(move |io| async move {
+                let finished = match body(ChunkedWriter::new(io)).await {
+                    Ok(writer) => writer.finish().await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = finished {
+                    tracing::debug!(error = %e, "chunked response body failed");
+                }
+            }))
+    }
+
+    /// Declares a trailer field the [chunked](Self::chunked) body will send, in the
+    /// `Trailer` header.
+    #[must_use]
+    pub fn trailer(mut self, name: &str) -> Self {
+        let value = match self.headers.get("Trailer") {
+            Some(existing) => format!("{existing}, {name}"),
+            None => name.to_owned(),
+        };
+        self.headers.remove("Trailer");
+        self.headers.insert("Trailer", value);
+        self
+    }
+
+    /// Returns `true` if the body is [streamed](Self::stream).
+    pub fn is_streamed(&self) -> bool {
+        self.streamed
+    }
+
+    /// Runs `hook` once the server has written this response, with how long writing it
+    /// took. If the response is dropped without being written — the connection failed,
+    /// or it was built in an in-process test — `hook` runs with `None` instead.
+    ///
+    /// For [streamed](Self::stream) and upgraded responses the write covers the head
+    /// only.
+    pub fn on_sent(&mut self, hook: impl FnOnce(Option<Duration>) + Send + Sync + 'static) {
+        self.sent.get_or_insert_default().0.push(Box::new(hook));
+    }
+
+    // Removes the callbacks registered with `on_sent`, for the server to run.
+    pub(crate) fn take_sent_hooks(&mut self) -> SentHooks {
+        self.sent.take().map(|hooks| *hooks).unwrap_or_default()
+    }
+
+    /// Removes and returns the upgrade or [stream](Self::stream) callback, if any.
+    pub fn take_upgrade(&mut self) -> Option<OnUpgrade> {
+        self.upgrade
+            .take()
+            .or_else(|| self.file.as_deref().map(FileBody::writer))
+    }
+
+    /// Replaces the status code in-place. Like [`add_header`](Self::add_header), this is
+    /// intended for code that decorates an already-built response.
+    pub fn set_status(&mut self, status: StatusCode) {
+        self.status = status;
+    }
+
     /// Returns the status code of this response.
     pub fn status(&self) -> StatusCode {
         self.status
     }
 
+    /// Returns the response headers.
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Returns the response body bytes, for inspecting a response in tests and middleware.
+    ///
+    /// This is the body accessor; [`body_bytes`](Self::body_bytes) is the builder that sets
+    /// it. See also [`body_text`](Self::body_text).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::{Response, StatusCode};
+    ///
+    /// let response = Response::new(StatusCode::Ok).body_bytes(vec![1, 2, 3]);
+    /// assert_eq!(response.body_ref(), [1, 2, 3]);
+    /// assert_eq!(response.headers().get("content-length"), None);
+    /// ```
+    pub fn body_ref(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns the error behind a [`server_error`](Self::server_error) response.
+    pub fn error(&self) -> Option<&(dyn Error + Send + Sync + 'static)> {
+        self.fault.as_ref().map(|fault| &*fault.error)
+    }
+
+    // Detaches the error and its backtrace.
+    pub(crate) fn take_fault(&mut self) -> Option<Fault> {
+        self.fault.take().map(|fault| *fault)
+    }
+
+    /// Returns the response body as text, replacing invalid UTF-8.
+    pub fn body_text(&self) -> std::borrow::Cow<'_, str> {
+        String::from_utf8_lossy(&self.body)
+    }
+
     /// Serializes the response into a `BytesMut` buffer using HTTP/1.1 wire format.
     ///
     /// Automatically adds:
@@ -90,52 +487,91 @@ impl Response {
     ///   `Content-Type` header was set.
     /// - `Content-Length: <n>` (always written).
     /// - `Connection: keep-alive` or `Connection: close`.
-    pub fn into_bytes(mut self) -> BytesMut {
+    ///
+    /// `101 Switching Protocols` responses get neither `Content-Length` nor an automatic
+    /// `Connection` header; the handler sets `Connection: Upgrade` itself. Streamed
+    /// responses get no `Content-Length`.
+    pub fn into_bytes(self) -> BytesMut {
+        let (mut head, body) = self.into_head_and_body();
+        head.put_slice(&body);
+        head
+    }
+
+    /// Serializes the status line and headers as [`into_bytes`](Self::into_bytes) does,
+    /// returning them apart from the body so the two can go out in one vectored write
+    /// without the body being copied.
+    pub fn into_head_and_body(self) -> (BytesMut, Vec<u8>) {
+        let mut head = BytesMut::with_capacity(128 + self.headers.len() * 64);
+        let body = self.encode_head(&mut head);
+        (head, body)
+    }
+
+    // Appends the status line and headers to `buf`, returning the body.
+    pub(crate) fn encode_head(mut self, buf: &mut BytesMut) -> Vec<u8> {
         let content_length = self.body.len();
+        let switching = self.status == StatusCode::SwitchingProtocols;
 
         if !self.body.is_empty() && !self.headers.contains("content-type") {
             self.headers
-                .insert("Content-Type", "text/plain; charset=utf-8");
+                .insert(CONTENT_TYPE, "text/plain; charset=utf-8");
         }
 
-        let connection = if self.keep_alive {
-            "keep-alive"
-        } else {
-            "close"
-        };
-        self.headers.insert("Connection", connection);
-
-        let estimated_size = 128 + self.headers.len() * 64 + content_length;
-        let mut buf = BytesMut::with_capacity(estimated_size);
+        if !switching {
+            let connection = if self.keep_alive {
+                "keep-alive"
+            } else {
+                "close"
+            };
+            self.headers.insert(CONNECTION, connection);
+        }
 
         // Status line
-        buf.put(
-            format!(
-                "HTTP/1.1 {} {}\r\n",
-                self.status.as_u16(),
-                self.status.canonical_reason()
-            )
-            .as_bytes(),
-        );
+        buf.put_slice(b"HTTP/1.1 ");
+        put_decimal(buf, u64::from(self.status.as_u16()));
+        buf.put_u8(b' ');
+        buf.put_slice(self.status.canonical_reason().as_bytes());
+        buf.put_slice(b"\r\n");
 
         // Headers
         for (name, value) in self.headers.iter() {
-            buf.put(format!("{name}: {value}\r\n").as_bytes());
+            buf.put_slice(name.as_bytes());
+            buf.put_slice(b": ");
+            buf.put_slice(value.as_bytes());
+            buf.put_slice(b"\r\n");
         }
 
         // Content-Length is always the last header before the blank line
-        buf.put(format!("Content-Length: {content_length}\r\n").as_bytes());
+        let length = match &self.file {
+            Some(file) => Some(file.len),
+            None if !switching && !self.streamed => Some(content_length as u64),
+            None => None,
+        };
+        if let Some(length) = length {
+            buf.put_slice(b"Content-Length: ");
+            put_decimal(buf, length);
+            buf.put_slice(b"\r\n");
+        }
 
         // Header/body separator
-        buf.put(&b"\r\n"[..]);
+        buf.put_slice(b"\r\n");
 
-        // Body
-        if !self.body.is_empty() {
-            buf.put(self.body.as_slice());
-        }
+        self.body
+    }
+}
 
-        buf
+// Appends `n` in decimal, without formatting through a temporary `String`.
+fn put_decimal(buf: &mut BytesMut, mut n: u64) {
+    let mut digits = [0; 20];
+    let mut start = digits.len();
+    loop {
+        start -= 1;
+        digits[start] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
     }
+    buf.put_slice(&digits[start..]);
 }
 
 impl Default for Response {
@@ -185,10 +621,107 @@ mod tests {
         assert!(s.contains("Connection: close\r\n"));
     }
 
+    #[test]
+    fn switching_protocols_has_no_length_or_default_connection() {
+        let r = Response::new(StatusCode::SwitchingProtocols)
+            .header("Upgrade", "websocket")
+            .header("Connection", "Upgrade");
+        let s = to_string(r.into_bytes());
+        assert_eq!(
+            s,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn streamed_responses_are_delimited_by_close() {
+        let r = Response::new(StatusCode::Ok).stream(OnUpgrade::new(|_| async {}));
+        assert!(r.is_streamed());
+        let s = to_string(r.into_bytes());
+        assert_eq!(s, "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n");
+    }
+
+    // Runs the response's body writer and returns everything it wrote.
+    async fn streamed_body(response: &mut Response) -> Vec<u8> {
+        use tokio::io::{AsyncReadExt, duplex};
+
+        let (server, mut client) = duplex(64 * 1024);
+        let writer = response.take_upgrade().unwrap();
+        writer
+            .run(crate::http::Upgraded::new(server, BytesMut::new()))
+            .await;
+        let mut body = Vec::new();
+        client.read_to_end(&mut body).await.unwrap();
+        body
+    }
+
+    #[tokio::test]
+    async fn files_stream_whole_or_by_range() {
+        let path = std::env::temp_dir().join(format!(
+            "rttp-download-{}.txt",
+            crate::security::crypto::random_token(8)
+        ));
+        std::fs::write(&path, "0123456789").unwrap();
+        let request = |range: &str| {
+            let raw = format!("GET /download HTTP/1.1\r\n{range}\r\n");
+            Request::parse(raw.as_bytes()).unwrap().0
+        };
+
+        let mut whole = Response::attachment(&path, "digits.txt").await.unwrap();
+        assert_eq!(
+            whole.headers().get("content-type"),
+            Some("text/plain; charset=utf-8")
+        );
+        assert_eq!(
+            whole.headers().get("content-disposition"),
+            Some("attachment; filename=\"digits.txt\"")
+        );
+        assert_eq!(streamed_body(&mut whole).await, b"0123456789");
+        assert!(to_string(whole.into_bytes()).contains("Content-Length: 10\r\n"));
+
+        let mut part = Response::file(&path)
+            .await
+            .unwrap()
+            .for_request(&request("Range: bytes=2-4\r\n"));
+        assert_eq!(part.status(), StatusCode::PartialContent);
+        assert_eq!(part.headers().get("content-range"), Some("bytes 2-4/10"));
+        assert_eq!(streamed_body(&mut part).await, b"234");
+        assert!(to_string(part.into_bytes()).contains("Content-Length: 3\r\n"));
+
+        let past = Response::file(&path)
+            .await
+            .unwrap()
+            .for_request(&request("Range: bytes=10-\r\n"));
+        assert_eq!(past.status(), StatusCode::RangeNotSatisfiable);
+        assert_eq!(past.headers().get("content-range"), Some("bytes */10"));
+        assert!(!past.is_streamed());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(Response::file(&path).await.is_err());
+    }
+
     #[test]
     fn not_found() {
         let r = Response::new(StatusCode::NotFound).body("Not Found");
         let s = to_string(r.into_bytes());
         assert!(s.starts_with("HTTP/1.1 404 Not Found\r\n"));
     }
+
+    #[test]
+    fn head_and_body_are_split() {
+        let r = || Response::new(StatusCode::NotFound).body("missing");
+        let (head, body) = r().into_head_and_body();
+        let head = to_string(head);
+        assert!(head.starts_with("HTTP/1.1 404 Not Found\r\n"), "{head}");
+        assert!(head.ends_with("Content-Length: 7\r\n\r\n"), "{head}");
+        assert_eq!(body, b"missing");
+        assert_eq!(to_string(r().into_bytes()), format!("{head}missing"));
+
+        let mut buf = BytesMut::new();
+        for n in [0, 7, 10, 1234567890, u64::MAX] {
+            put_decimal(&mut buf, n);
+            buf.put_u8(b' ');
+        }
+        assert_eq!(to_string(buf), format!("0 7 10 1234567890 {} ", u64::MAX));
+    }
 }
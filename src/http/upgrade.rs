@@ -0,0 +1,144 @@
+//! Connection upgrades (`101 Switching Protocols`).
+//!
+//! A handler that wants to take over the connection — for WebSocket, say — returns a
+//! `101` [`Response`](super::Response) carrying an [`OnUpgrade`] callback. Once the
+//! server has written that response it stops treating the connection as HTTP and hands
+//! it to the callback as an [`Upgraded`] stream.
+
+use std::{
+    fmt,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+// The connection's transport, type-erased so plain and TLS connections upgrade alike.
+trait Io: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Io for T {}
+
+/// A connection handed over by the server after a `101 Switching Protocols` response.
+///
+/// Reads first return any bytes the client sent after its upgrade request that the
+/// server had already buffered, then continue from the underlying transport.
+pub struct Upgraded {
+    io: Box<dyn Io>,
+    buffered: BytesMut,
+}
+
+impl Upgraded {
+    /// Wraps `io`, replaying `buffered` before reading from it.
+    pub fn new<S>(io: S, buffered: BytesMut) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        Self {
+            io: Box::new(io),
+            buffered,
+        }
+    }
+}
+
+impl fmt::Debug for Upgraded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Upgraded")
+            .field("buffered", &self.buffered.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl AsyncRead for Upgraded {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if !self.buffered.is_empty() {
+            let n = self.buffered.len().min(buf.remaining());
+            buf.put_slice(&self.buffered[..n]);
+            self.buffered.advance(n);
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Upgraded {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+/// The callback a `101` response runs on the [`Upgraded`] connection.
+///
+/// Attach it with [`Response::on_upgrade`](super::Response::on_upgrade). The server
+/// awaits it on the connection's task; the connection closes when it returns.
+// The mutex is never contended; it only makes `OnUpgrade`, and so `Response`, `Sync`.
+pub struct OnUpgrade(Mutex<Callback>);
+
+type Callback = Box<dyn FnOnce(Upgraded) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+impl OnUpgrade {
+    /// Wraps `callback`.
+    pub fn new<F, Fut>(callback: F) -> Self
+    where
+        F: FnOnce(Upgraded) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        Self(Mutex::new(Box::new(move |upgraded| {
+            Box::pin(callback(upgraded))
+        })))
+    }
+
+    /// Runs the callback on `upgraded`.
+    pub async fn run(self, upgraded: Upgraded) {
+        let callback = self.0.into_inner().unwrap_or_else(|e| e.into_inner());
+        callback(upgraded).await;
+    }
+}
+
+impl fmt::Debug for OnUpgrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnUpgrade")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, duplex};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn replays_buffered_bytes_first() {
+        let (server, mut client) = duplex(64);
+        let mut upgraded = Upgraded::new(server, BytesMut::from(&b"early"[..]));
+        client.write_all(b" late").await.unwrap();
+
+        let mut read = [0; 10];
+        upgraded.read_exact(&mut read).await.unwrap();
+        assert_eq!(&read, b"early late");
+
+        upgraded.write_all(b"reply").await.unwrap();
+        let mut reply = [0; 5];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"reply");
+    }
+}
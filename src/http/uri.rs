@@ -0,0 +1,543 @@
+//! Request targets.
+//!
+//! HTTP/1.1 has four forms of request target (RFC 9112 §3.2): origin-form
+//! (`/path?query`) for ordinary requests, absolute-form (`http://host/path`) sent to
+//! proxies, authority-form (`host:443`) for `CONNECT`, and asterisk-form (`*`) for
+//! server-wide `OPTIONS`. [`Uri`] records which one a request used and splits it into
+//! its parts; [`Request::uri`](super::Request::uri) returns it.
+//!
+//! [`UriBuilder`] goes the other way, percent-encoding path segments and query
+//! parameters to build targets for redirects and outgoing requests.
+//!
+//! # Examples
+//!
+//! ```
+//! use rttp::{Response, StatusCode, http::Uri};
+//!
+//! let next = Uri::parse("/search?q=rust+lang&page=2");
+//! let location = next
+//!     .to_builder()
+//!     .scheme("https")
+//!     .host("example.com")
+//!     .query_param("page", "3")
+//!     .build();
+//! assert_eq!(
+//!     location.to_string(),
+//!     "https://example.com/search?q=rust+lang&page=2&page=3"
+//! );
+//! let response = Response::redirect(StatusCode::SeeOther, &location);
+//! ```
+
+use std::fmt;
+
+/// The form of a request target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TargetForm {
+    /// `/path?query`.
+    Origin,
+    /// `scheme://authority/path?query`.
+    Absolute,
+    /// `host:port`, used by `CONNECT`.
+    Authority,
+    /// `*`, used by `OPTIONS`.
+    Asterisk,
+}
+
+/// A parsed request target.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::http::{TargetForm, Uri};
+///
+/// let uri = Uri::parse("http://example.com:8080/users?page=2");
+/// assert_eq!(uri.form(), TargetForm::Absolute);
+/// assert_eq!(uri.scheme(), Some("http"));
+/// assert_eq!(uri.host(), Some("example.com"));
+/// assert_eq!(uri.port(), Some(8080));
+/// assert_eq!(uri.path(), "/users");
+/// assert_eq!(uri.query(), Some("page=2"));
+///
+/// let uri = Uri::parse("example.com:443");
+/// assert_eq!(uri.form(), TargetForm::Authority);
+/// assert_eq!(uri.authority(), Some("example.com:443"));
+/// assert_eq!(uri.path(), "");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Uri {
+    form: TargetForm,
+    scheme: Option<String>,
+    authority: Option<String>,
+    path: String,
+    query: Option<String>,
+}
+
+impl Uri {
+    /// Parses a request target.
+    ///
+    /// Parsing is lenient: a target that fits none of the forms is kept whole as an
+    /// origin-form path, so the request can still be answered, typically with a 404.
+    /// [`Request::parse_strict`](super::Request::parse_strict) rejects those instead.
+    pub fn parse(target: &str) -> Self {
+        if target == "*" {
+            return Self {
+                form: TargetForm::Asterisk,
+                scheme: None,
+                authority: None,
+                path: "*".to_owned(),
+                query: None,
+            };
+        }
+        if !target.starts_with('/') {
+            if let Some((scheme, rest)) = target.split_once("://") {
+                if is_scheme(scheme) {
+                    let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+                    let (authority, rest) = rest.split_at(authority_end);
+                    let (path, query) = split_query(rest);
+                    return Self {
+                        form: TargetForm::Absolute,
+                        scheme: Some(scheme.to_ascii_lowercase()),
+                        authority: Some(authority.to_owned()),
+                        path: if path.is_empty() { "/" } else { path }.to_owned(),
+                        query,
+                    };
+                }
+            } else if !target.is_empty() && !target.contains(['/', '?']) {
+                return Self {
+                    form: TargetForm::Authority,
+                    scheme: None,
+                    authority: Some(target.to_owned()),
+                    path: String::new(),
+                    query: None,
+                };
+            }
+        }
+        let (path, query) = split_query(target);
+        Self {
+            form: TargetForm::Origin,
+            scheme: None,
+            authority: None,
+            path: path.to_owned(),
+            query,
+        }
+    }
+
+    /// Returns the target's form.
+    pub fn form(&self) -> TargetForm {
+        self.form
+    }
+
+    /// Returns the lowercased scheme of an absolute-form target.
+    pub fn scheme(&self) -> Option<&str> {
+        self.scheme.as_deref()
+    }
+
+    /// Returns the authority of an absolute- or authority-form target, such as
+    /// `example.com:8080`.
+    pub fn authority(&self) -> Option<&str> {
+        self.authority.as_deref()
+    }
+
+    /// Returns the `user:password` part of the authority, before any `@`.
+    pub fn userinfo(&self) -> Option<&str> {
+        self.authority
+            .as_deref()?
+            .rsplit_once('@')
+            .map(|(user, _)| user)
+    }
+
+    /// Returns the host from the authority, without brackets around IPv6 addresses or
+    /// any `user@` prefix.
+    pub fn host(&self) -> Option<&str> {
+        let authority = self.authority.as_deref()?;
+        let host = authority
+            .rsplit_once('@')
+            .map_or(authority, |(_, host)| host);
+        if let Some(rest) = host.strip_prefix('[') {
+            return rest.split_once(']').map(|(ip, _)| ip);
+        }
+        Some(host.split_once(':').map_or(host, |(host, _)| host))
+    }
+
+    /// Returns the port from the authority, if one is given.
+    pub fn port(&self) -> Option<u16> {
+        let authority = self.authority.as_deref()?;
+        let after_host = authority
+            .rsplit_once(']')
+            .map_or(authority, |(_, rest)| rest);
+        after_host.rsplit_once(':')?.1.parse().ok()
+    }
+
+    /// Returns the path: `/` at least for absolute-form, empty for authority-form, and
+    /// `*` for asterisk-form.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the path's `/`-separated segments, still percent-encoded.
+    ///
+    /// `/users/7/` has the segments `users`, `7`, and an empty one; `/` has one empty
+    /// segment.
+    pub fn path_segments(&self) -> impl Iterator<Item = &str> {
+        let path = self.path.strip_prefix('/').unwrap_or(&self.path);
+        path.split('/')
+    }
+
+    /// Returns the query string, without the `?`.
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    /// Returns the query's `key=value` pairs in order, with `+` and percent-escapes
+    /// decoded.
+    ///
+    /// Repeated keys are all kept. Escapes that are malformed or decode to invalid
+    /// UTF-8 are replaced rather than rejected.
+    pub fn query_pairs(&self) -> Vec<(String, String)> {
+        self.query
+            .as_deref()
+            .unwrap_or("")
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                let decode = |s| decode_component(s).unwrap_or_else(|lossy| lossy);
+                (decode(key), decode(value))
+            })
+            .collect()
+    }
+
+    /// Starts a [`UriBuilder`] from this URI's parts.
+    pub fn to_builder(&self) -> UriBuilder {
+        let mut builder = UriBuilder::new();
+        builder.scheme = self.scheme.clone();
+        builder.userinfo = self.userinfo().map(str::to_owned);
+        builder.host = self.host().map(str::to_owned);
+        builder.port = self.port();
+        builder.path = self.path.clone();
+        builder.query = self.query.clone();
+        builder
+    }
+
+    /// Starts building a URI.
+    pub fn builder() -> UriBuilder {
+        UriBuilder::new()
+    }
+}
+
+/// Builds a [`Uri`] from parts, percent-encoding as it goes.
+///
+/// With a scheme and host the result is absolute-form; with only a host it is
+/// authority-form; otherwise it is origin-form, with a path of `/` if none was set.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::http::Uri;
+///
+/// let uri = Uri::builder()
+///     .scheme("http")
+///     .host("::1")
+///     .port(8080)
+///     .segment("files")
+///     .segment("q3 report.pdf")
+///     .query_param("v", "2 & 3")
+///     .build();
+/// assert_eq!(uri.to_string(), "http://[::1]:8080/files/q3%20report.pdf?v=2+%26+3");
+/// ```
+#[derive(Debug, Clone, Default)]
+#[must_use]
+pub struct UriBuilder {
+    scheme: Option<String>,
+    userinfo: Option<String>,
+    host: Option<String>,
+    port: Option<u16>,
+    path: String,
+    query: Option<String>,
+}
+
+impl UriBuilder {
+    /// Creates an empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the scheme, such as `https`.
+    pub fn scheme(mut self, scheme: impl Into<String>) -> Self {
+        self.scheme = Some(scheme.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Sets the `user:password` part of the authority, used as given.
+    pub fn userinfo(mut self, userinfo: impl Into<String>) -> Self {
+        self.userinfo = Some(userinfo.into());
+        self
+    }
+
+    /// Sets the host. IPv6 addresses are bracketed when written.
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    /// Sets the port.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Replaces the path, used as given; it should already be percent-encoded.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Appends one path segment, percent-encoding `/` and anything else a segment
+    /// cannot hold.
+    pub fn segment(mut self, segment: &str) -> Self {
+        if !self.path.ends_with('/') {
+            self.path.push('/');
+        }
+        for b in segment.bytes() {
+            if b.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@".contains(&b) {
+                self.path.push(b as char);
+            } else {
+                self.path.push_str(&format!("%{b:02X}"));
+            }
+        }
+        self
+    }
+
+    /// Replaces the query string, used as given.
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.query = Some(query.into());
+        self
+    }
+
+    /// Appends a `key=value` query parameter, form-encoding both.
+    pub fn query_param(mut self, key: &str, value: &str) -> Self {
+        let query = self.query.get_or_insert_with(String::new);
+        if !query.is_empty() {
+            query.push('&');
+        }
+        encode_component(key, Space::Plus, query);
+        query.push('=');
+        encode_component(value, Space::Plus, query);
+        self
+    }
+
+    /// Builds the URI.
+    pub fn build(self) -> Uri {
+        let authority = self.host.map(|host| {
+            let mut authority = String::new();
+            if let Some(userinfo) = &self.userinfo {
+                authority.push_str(userinfo);
+                authority.push('@');
+            }
+            if host.contains(':') {
+                authority.push_str(&format!("[{host}]"));
+            } else {
+                authority.push_str(&host);
+            }
+            if let Some(port) = self.port {
+                authority.push_str(&format!(":{port}"));
+            }
+            authority
+        });
+        let form = match (&self.scheme, &authority) {
+            (Some(_), Some(_)) => TargetForm::Absolute,
+            (None, Some(_)) => TargetForm::Authority,
+            _ => TargetForm::Origin,
+        };
+        let path = match form {
+            TargetForm::Authority => String::new(),
+            _ if self.path.is_empty() => "/".to_owned(),
+            TargetForm::Absolute if !self.path.starts_with('/') => format!("/{}", self.path),
+            _ => self.path,
+        };
+        Uri {
+            form,
+            scheme: self.scheme.filter(|_| form == TargetForm::Absolute),
+            authority,
+            path,
+            query: self.query.filter(|_| form != TargetForm::Authority),
+        }
+    }
+}
+
+impl fmt::Display for Uri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let (Some(scheme), Some(authority)) = (&self.scheme, &self.authority) {
+            write!(f, "{scheme}://{authority}")?;
+        } else if let Some(authority) = &self.authority {
+            f.write_str(authority)?;
+        }
+        f.write_str(&self.path)?;
+        if let Some(query) = &self.query {
+            write!(f, "?{query}")?;
+        }
+        Ok(())
+    }
+}
+
+// Decodes `+` as a space and `%XX` escapes. If an escape is malformed or the bytes are not
+// UTF-8, returns the lossy decoding as the error: malformed escapes are kept as they are and
+// invalid UTF-8 is replaced.
+pub(crate) fn decode_component(input: &str) -> Result<String, String> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut malformed = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'+', _) => out.push(b' '),
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 2;
+            }
+            (b'%', None) => {
+                malformed = true;
+                out.push(b'%');
+            }
+            (b, _) => out.push(b),
+        }
+        i += 1;
+    }
+    match String::from_utf8(out) {
+        Ok(decoded) if !malformed => Ok(decoded),
+        Ok(decoded) => Err(decoded),
+        Err(e) => Err(String::from_utf8_lossy(e.as_bytes()).into_owned()),
+    }
+}
+
+// How `encode_component` writes a space: `+` in form-encoded queries, `%20` elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Space {
+    Plus,
+    Percent,
+}
+
+// Percent-encodes everything except unreserved characters.
+pub(crate) fn encode_component(input: &str, space: Space, out: &mut String) {
+    for b in input.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(b as char)
+            }
+            b' ' if space == Space::Plus => out.push('+'),
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+}
+
+fn split_query(target: &str) -> (&str, Option<String>) {
+    match target.split_once('?') {
+        Some((path, query)) => (path, Some(query.to_owned())),
+        None => (target, None),
+    }
+}
+
+fn is_scheme(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic())
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_form() {
+        let uri = Uri::parse("/search?q=a?b");
+        assert_eq!(uri.form(), TargetForm::Origin);
+        assert_eq!((uri.path(), uri.query()), ("/search", Some("q=a?b")));
+        assert_eq!(uri.authority(), None);
+
+        let uri = Uri::parse("HTTPS://user@[::1]:8443?x");
+        assert_eq!(uri.form(), TargetForm::Absolute);
+        assert_eq!(uri.scheme(), Some("https"));
+        assert_eq!(uri.host(), Some("::1"));
+        assert_eq!(uri.port(), Some(8443));
+        assert_eq!((uri.path(), uri.query()), ("/", Some("x")));
+
+        let uri = Uri::parse("[::1]:443");
+        assert_eq!(uri.form(), TargetForm::Authority);
+        assert_eq!((uri.host(), uri.port()), (Some("::1"), Some(443)));
+        assert_eq!(Uri::parse("example.com").port(), None);
+
+        assert_eq!(Uri::parse("*").form(), TargetForm::Asterisk);
+        assert_eq!(Uri::parse("*").path(), "*");
+    }
+
+    #[test]
+    fn keeps_unrecognised_targets_as_paths() {
+        for target in ["users/1", "1http://x/", "", "/a://b"] {
+            let uri = Uri::parse(target);
+            assert_eq!(uri.form(), TargetForm::Origin, "{target}");
+            assert_eq!(uri.path(), target);
+        }
+    }
+
+    #[test]
+    fn displays_the_target() {
+        for target in ["/a?b", "http://example.com/a?b", "example.com:443", "*"] {
+            assert_eq!(Uri::parse(target).to_string(), target);
+        }
+        assert_eq!(
+            Uri::parse("http://example.com").to_string(),
+            "http://example.com/"
+        );
+    }
+
+    #[test]
+    fn splits_segments_and_query_pairs() {
+        let uri = Uri::parse("http://ada:pw@example.com/a%20b/c/?q=x+y&q=%E2%82%AC&flag&bad=%zz");
+        assert_eq!(uri.userinfo(), Some("ada:pw"));
+        assert_eq!(uri.host(), Some("example.com"));
+        assert_eq!(uri.path_segments().collect::<Vec<_>>(), ["a%20b", "c", ""]);
+        assert_eq!(
+            uri.query_pairs(),
+            [
+                ("q".to_owned(), "x y".to_owned()),
+                ("q".to_owned(), "€".to_owned()),
+                ("flag".to_owned(), String::new()),
+                ("bad".to_owned(), "%zz".to_owned()),
+            ]
+        );
+        assert_eq!(Uri::parse("/").path_segments().collect::<Vec<_>>(), [""]);
+    }
+
+    #[test]
+    fn builds_each_form() {
+        let uri = Uri::builder()
+            .scheme("HTTPS")
+            .userinfo("ada")
+            .host("example.com")
+            .path("a/b")
+            .query_param("x", "1/2")
+            .build();
+        assert_eq!(uri.form(), TargetForm::Absolute);
+        assert_eq!(uri.to_string(), "https://ada@example.com/a/b?x=1%2F2");
+
+        let uri = Uri::builder()
+            .host("example.com")
+            .port(443)
+            .segment("x")
+            .build();
+        assert_eq!(uri.form(), TargetForm::Authority);
+        assert_eq!(uri.to_string(), "example.com:443");
+
+        let uri = Uri::builder().segment("a/b").segment("").build();
+        assert_eq!(uri.form(), TargetForm::Origin);
+        assert_eq!(uri.to_string(), "/a%2Fb/");
+        assert_eq!(Uri::builder().build().to_string(), "/");
+
+        let original = Uri::parse("http://[::1]:8080/x?y=1");
+        assert_eq!(original.to_builder().build(), original);
+    }
+}
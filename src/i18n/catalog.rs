@@ -0,0 +1,444 @@
+//! Message catalogs in a subset of the Fluent syntax.
+
+use std::{collections::HashMap, fmt};
+
+use super::I18nError;
+
+/// The messages of one locale.
+///
+/// Catalogs are written in a subset of [Fluent](https://projectfluent.org/fluent/guide/)
+/// syntax:
+///
+/// ```text
+/// # Comments start with a hash.
+/// hello = Hello, { $name }!
+/// cart =
+///     { $count ->
+///         [0] Your cart is empty.
+///         [one] You have one item.
+///        *[other] You have { $count } items.
+///     }
+/// braces = Literal braces are written { "{" } and { "}" }.
+/// ```
+///
+/// A message is `key = value`; indented lines continue the value. `{ $name }` inserts
+/// an argument, and a select expression picks a variant by the argument's exact value
+/// or, for numbers, by the locale's plural category (`one`, `few`, `many`, or
+/// `other`), falling back to the variant marked `*`. Terms, attributes, and functions
+/// are not supported.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    locale: String,
+    messages: HashMap<String, Vec<Element>>,
+}
+
+#[derive(Debug, Clone)]
+enum Element {
+    Text(String),
+    Variable(String),
+    Select {
+        variable: String,
+        variants: Vec<(String, Vec<Element>)>,
+        default: usize,
+    },
+}
+
+impl Catalog {
+    /// Parses the catalog for `locale` from `source`.
+    ///
+    /// # Errors
+    ///
+    /// [`I18nError::Syntax`] with the line of the first malformed message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::i18n::Catalog;
+    ///
+    /// let catalog = Catalog::parse("en", "hello = Hello, { $name }!").unwrap();
+    /// assert_eq!(catalog.format("hello", &[("name", &"Ada")]).unwrap(), "Hello, Ada!");
+    /// ```
+    pub fn parse(locale: &str, source: &str) -> Result<Self, I18nError> {
+        let mut catalog = Self {
+            locale: locale.to_owned(),
+            messages: HashMap::new(),
+        };
+        catalog.extend(source)?;
+        Ok(catalog)
+    }
+
+    // Adds the messages in `source`, replacing any with the same key.
+    pub(super) fn extend(&mut self, source: &str) -> Result<(), I18nError> {
+        let syntax = |line: usize, message: String| I18nError::Syntax {
+            locale: self.locale.clone(),
+            line,
+            message,
+        };
+        // The message being read: its key, first line, and value lines.
+        let mut current: Option<(String, usize, Vec<&str>)> = None;
+        let mut entries = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let number = index + 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // As in Fluent, lines of a select expression may start unindented.
+            let continues = current.is_some() && line.starts_with(['}', '[', '*']);
+            if continues || line.starts_with([' ', '\t']) {
+                match current.as_mut() {
+                    Some((_, _, lines)) => lines.push(line.trim()),
+                    None => return Err(syntax(number, "indented line outside a message".into())),
+                }
+                continue;
+            }
+            entries.extend(current.take());
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(syntax(
+                    number,
+                    format!("expected `key = value`, got {line:?}"),
+                ));
+            };
+            let key = key.trim();
+            if !is_identifier(key) {
+                return Err(syntax(number, format!("invalid message key {key:?}")));
+            }
+            let value = value.trim();
+            let lines = if value.is_empty() {
+                vec![]
+            } else {
+                vec![value]
+            };
+            current = Some((key.to_owned(), number, lines));
+        }
+        entries.extend(current);
+
+        for (key, line, lines) in entries {
+            if lines.is_empty() {
+                return Err(syntax(line, format!("message {key:?} has no value")));
+            }
+            let source = lines.join("\n");
+            let mut pos = 0;
+            let pattern = parse_pattern(&source, &mut pos, false)
+                .map_err(|message| syntax(line, format!("in message {key:?}: {message}")))?;
+            self.messages.insert(key, pattern);
+        }
+        Ok(())
+    }
+
+    /// The locale these messages are in.
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    /// Returns whether the catalog has a message for `key`.
+    pub fn contains(&self, key: &str) -> bool {
+        self.messages.contains_key(key)
+    }
+
+    /// Returns the number of messages.
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Returns whether the catalog has no messages.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Formats message `key` with `args`, or returns `None` if there is no such message.
+    ///
+    /// Arguments missing from `args` appear as `{$name}`.
+    pub fn format(&self, key: &str, args: &[(&str, &dyn fmt::Display)]) -> Option<String> {
+        let pattern = self.messages.get(key)?;
+        let mut out = String::new();
+        self.write(pattern, args, &mut out);
+        Some(out)
+    }
+
+    fn write(&self, pattern: &[Element], args: &[(&str, &dyn fmt::Display)], out: &mut String) {
+        for element in pattern {
+            match element {
+                Element::Text(text) => out.push_str(text),
+                Element::Variable(name) => match argument(args, name) {
+                    Some(value) => out.push_str(&value),
+                    None => {
+                        out.push_str("{$");
+                        out.push_str(name);
+                        out.push('}');
+                    }
+                },
+                Element::Select {
+                    variable,
+                    variants,
+                    default,
+                } => {
+                    let value = argument(args, variable);
+                    let exact = value
+                        .as_deref()
+                        .and_then(|value| variants.iter().position(|(key, _)| key == value));
+                    let plural = || {
+                        let n = value.as_deref()?.parse::<f64>().ok()?;
+                        let category = plural_category(&self.locale, n);
+                        variants.iter().position(|(key, _)| key == category)
+                    };
+                    let chosen = exact.or_else(plural).unwrap_or(*default);
+                    self.write(&variants[chosen].1, args, out);
+                }
+            }
+        }
+    }
+}
+
+fn argument(args: &[(&str, &dyn fmt::Display)], name: &str) -> Option<String> {
+    args.iter()
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+}
+
+// The CLDR plural category of `n` for the common rule families; other languages use
+// the English rule.
+fn plural_category(locale: &str, n: f64) -> &'static str {
+    let language = locale.split(['-', '_']).next().unwrap_or("");
+    let language = language.to_ascii_lowercase();
+    if n.fract() != 0.0 || n < 0.0 {
+        return "other";
+    }
+    let i = n as u64;
+    let (mod10, mod100) = (i % 10, i % 100);
+    match language.as_str() {
+        "ja" | "zh" | "ko" | "vi" | "th" | "id" | "ms" | "tr" => "other",
+        "fr" | "pt" if i <= 1 => "one",
+        "ru" | "uk" | "be" => {
+            if mod10 == 1 && mod100 != 11 {
+                "one"
+            } else if (2..=4).contains(&mod10) && !(12..=14).contains(&mod100) {
+                "few"
+            } else {
+                "many"
+            }
+        }
+        "pl" => {
+            if i == 1 {
+                "one"
+            } else if (2..=4).contains(&mod10) && !(12..=14).contains(&mod100) {
+                "few"
+            } else {
+                "many"
+            }
+        }
+        "cs" | "sk" => match i {
+            1 => "one",
+            2..=4 => "few",
+            _ => "other",
+        },
+        _ if i == 1 => "one",
+        _ => "other",
+    }
+}
+
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// Parses text and placeables from `pos`. Inside a select variant the pattern ends at
+// the end of its line or at the `}` closing the select.
+fn parse_pattern(src: &str, pos: &mut usize, variant: bool) -> Result<Vec<Element>, String> {
+    let mut elements = Vec::new();
+    let mut text = String::new();
+    while let Some(c) = src[*pos..].chars().next() {
+        match c {
+            '{' => {
+                *pos += 1;
+                match parse_placeable(src, pos)? {
+                    Element::Text(literal) => text.push_str(&literal),
+                    element => {
+                        if !text.is_empty() {
+                            elements.push(Element::Text(std::mem::take(&mut text)));
+                        }
+                        elements.push(element);
+                    }
+                }
+            }
+            '}' if variant => break,
+            '}' => return Err("unmatched `}`".into()),
+            '\n' if variant => break,
+            c => {
+                text.push(c);
+                *pos += c.len_utf8();
+            }
+        }
+    }
+    if !text.is_empty() {
+        elements.push(Element::Text(text));
+    }
+    Ok(elements)
+}
+
+// Parses a placeable after its opening `{`, up to and including the closing `}`.
+fn parse_placeable(src: &str, pos: &mut usize) -> Result<Element, String> {
+    skip_whitespace(src, pos);
+    let rest = &src[*pos..];
+    if let Some(literal) = rest.strip_prefix('"') {
+        let end = literal.find('"').ok_or("unterminated string literal")?;
+        let value = literal[..end].to_owned();
+        *pos += end + 2;
+        expect_close(src, pos)?;
+        return Ok(Element::Text(value));
+    }
+    let Some(name) = rest.strip_prefix('$') else {
+        return Err("expected `$variable` or a string literal in `{ }`".into());
+    };
+    let len = name
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .unwrap_or(name.len());
+    let variable = name[..len].to_owned();
+    if !is_identifier(&variable) {
+        return Err("expected a variable name after `$`".into());
+    }
+    *pos += 1 + len;
+    skip_whitespace(src, pos);
+    if !src[*pos..].starts_with("->") {
+        expect_close(src, pos)?;
+        return Ok(Element::Variable(variable));
+    }
+    *pos += 2;
+
+    let mut variants = Vec::new();
+    let mut default = None;
+    loop {
+        skip_whitespace(src, pos);
+        let rest = &src[*pos..];
+        if rest.starts_with('}') {
+            *pos += 1;
+            break;
+        }
+        let starred = rest.starts_with('*');
+        let rest = rest.strip_prefix('*').unwrap_or(rest);
+        let key = rest
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once(']'))
+            .map(|(key, _)| key.trim().to_owned())
+            .ok_or("expected a `[key]` variant or `}`")?;
+        *pos += usize::from(starred) + rest.find(']').unwrap_or(0) + 1;
+        if starred {
+            if default.is_some() {
+                return Err("more than one default `*` variant".into());
+            }
+            default = Some(variants.len());
+        }
+        let mut value = parse_pattern(src, pos, true)?;
+        trim(&mut value);
+        variants.push((key, value));
+    }
+    let default = default.ok_or("select expression has no default `*` variant")?;
+    Ok(Element::Select {
+        variable,
+        variants,
+        default,
+    })
+}
+
+fn expect_close(src: &str, pos: &mut usize) -> Result<(), String> {
+    skip_whitespace(src, pos);
+    if src[*pos..].starts_with('}') {
+        *pos += 1;
+        Ok(())
+    } else {
+        Err("expected `}`".into())
+    }
+}
+
+fn skip_whitespace(src: &str, pos: &mut usize) {
+    let rest = &src[*pos..];
+    *pos += rest.len() - rest.trim_start().len();
+}
+
+// Strips whitespace around a variant's value.
+fn trim(pattern: &mut Vec<Element>) {
+    if let Some(Element::Text(text)) = pattern.first_mut() {
+        *text = text.trim_start().to_owned();
+    }
+    if let Some(Element::Text(text)) = pattern.last_mut() {
+        *text = text.trim_end().to_owned();
+    }
+    pattern.retain(|element| !matches!(element, Element::Text(text) if text.is_empty()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CART: &str = "\
+# The shopping cart.
+cart =
+    { $count ->
+        [0] Your cart is empty.
+        [one] You have one item.
+       *[other] You have { $count } items.
+    }
+greeting = Hello, { $name }!
+    Welcome back.
+braces = Use { \"{\" } and { \"}\" }.
+";
+
+    #[test]
+    fn formats_messages_and_selects_variants() {
+        let catalog = Catalog::parse("en", CART).unwrap();
+        assert_eq!(catalog.len(), 3);
+        let cart = |count: u32| catalog.format("cart", &[("count", &count)]).unwrap();
+        assert_eq!(cart(0), "Your cart is empty.");
+        assert_eq!(cart(1), "You have one item.");
+        assert_eq!(cart(7), "You have 7 items.");
+        assert_eq!(
+            catalog.format("greeting", &[("name", &"Ada")]).unwrap(),
+            "Hello, Ada!\nWelcome back."
+        );
+        assert_eq!(
+            catalog.format("greeting", &[]).unwrap(),
+            "Hello, {$name}!\nWelcome back."
+        );
+        assert_eq!(catalog.format("braces", &[]).unwrap(), "Use { and }.");
+        assert_eq!(catalog.format("missing", &[]), None);
+    }
+
+    #[test]
+    fn plural_categories_follow_the_locale() {
+        let source = "n = { $n ->\n [one] one\n [few] few\n [many] many\n*[other] other\n}";
+        let format = |locale: &str, n: f64| {
+            Catalog::parse(locale, source)
+                .unwrap()
+                .format("n", &[("n", &n)])
+                .unwrap()
+        };
+        assert_eq!(format("fr", 0.0), "one");
+        assert_eq!(format("en", 0.0), "other");
+        assert_eq!(format("ru", 21.0), "one");
+        assert_eq!(format("ru", 23.0), "few");
+        assert_eq!(format("ru", 12.0), "many");
+        assert_eq!(format("ja", 1.0), "other");
+        assert_eq!(format("en", 1.5), "other");
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_lines() {
+        for (source, line) in [
+            ("ok = fine\n\nbad line", 3),
+            ("  indented = x", 1),
+            ("empty =", 1),
+            ("x = { $a ->\n [one] a\n}", 1),
+            ("x = unmatched }", 1),
+            ("x = { name }", 1),
+            ("1x = y", 1),
+        ] {
+            match Catalog::parse("en", source) {
+                Err(I18nError::Syntax { line: got, .. }) => assert_eq!(got, line, "{source}"),
+                other => panic!("{source}: {other:?}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! Language tags and `Accept-Language` negotiation.
+
+use std::fmt;
+
+use crate::{Request, http::headers::parse_quality_list};
+
+/// A BCP 47 language tag such as `en`, `pt-BR`, or `zh-Hant-TW`.
+///
+/// Parsing normalizes `_` to `-` and the case of each subtag — language lowercase,
+/// script titlecase, region uppercase — so tags compare equal however they were
+/// written.
+///
+/// # Examples
+///
+/// ```rust
+/// use rttp::i18n::LanguageTag;
+///
+/// let tag = LanguageTag::parse("zh_hant_tw").unwrap();
+/// assert_eq!(tag.as_str(), "zh-Hant-TW");
+/// assert_eq!(tag.language(), "zh");
+/// assert!(LanguageTag::parse("not a tag").is_none());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LanguageTag(String);
+
+impl LanguageTag {
+    /// Parses a tag: subtags of one to eight ASCII letters or digits, the first all
+    /// letters. Returns `None` for anything else, including `*`.
+    pub fn parse(tag: &str) -> Option<Self> {
+        let mut canonical = String::with_capacity(tag.len());
+        for (i, subtag) in tag.trim().split(['-', '_']).enumerate() {
+            let valid = (1..=8).contains(&subtag.len())
+                && subtag.bytes().all(|b| b.is_ascii_alphanumeric())
+                && (i > 0 || subtag.bytes().all(|b| b.is_ascii_alphabetic()));
+            if !valid {
+                return None;
+            }
+            if i > 0 {
+                canonical.push('-');
+            }
+            let letters = subtag.bytes().all(|b| b.is_ascii_alphabetic());
+            match subtag.len() {
+                // A script, such as `Hant`.
+                4 if i > 0 && letters => {
+                    canonical.push_str(&subtag[..1].to_ascii_uppercase());
+                    canonical.push_str(&subtag[1..].to_ascii_lowercase());
+                }
+                // A region, such as `BR`.
+                2 if i > 0 => canonical.push_str(&subtag.to_ascii_uppercase()),
+                _ => canonical.push_str(&subtag.to_ascii_lowercase()),
+            }
+        }
+        Some(Self(canonical))
+    }
+
+    /// The tag, in canonical case.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The primary language subtag, such as `pt` for `pt-BR`.
+    pub fn language(&self) -> &str {
+        self.subtags().next().unwrap_or_default()
+    }
+
+    /// The subtags, in order.
+    pub fn subtags(&self) -> impl Iterator<Item = &str> {
+        self.0.split('-')
+    }
+}
+
+impl fmt::Display for LanguageTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Picks the language in `supported` that best suits the request's `Accept-Language`
+/// header, or `None` if the client accepts none of them.
+///
+/// Each supported tag takes the `q` weight of the most specific range that covers it:
+/// the tag itself, a prefix of it (`fr` covers `fr-CA`), a longer tag it is a prefix
+/// of (`fr-BE` falls back to `fr`), another region of the same language (`en-AU` takes
+/// `en-GB`), and finally `*`. The tag with the highest weight wins; ties go to the
+/// range listed first in the header, then to the closer match, then to the order of
+/// `supported`. Tags weighted `q=0` are never chosen, so `fr;q=0, *` accepts anything
+/// but French. A request without the header accepts the first supported tag.
+///
+/// # Examples
+///
+/// ```rust
+/// use rttp::{Request, i18n::{LanguageTag, negotiate_language}};
+///
+/// let supported: Vec<_> = ["en", "fr", "pt-BR"]
+///     .into_iter()
+///     .filter_map(LanguageTag::parse)
+///     .collect();
+/// let request = Request::builder()
+///     .header("Accept-Language", "de;q=0.9, pt;q=0.8, en;q=0.5")
+///     .build();
+/// let chosen = negotiate_language(&request, &supported);
+/// assert_eq!(chosen.unwrap().as_str(), "pt-BR");
+/// ```
+pub fn negotiate_language(request: &Request, supported: &[LanguageTag]) -> Option<LanguageTag> {
+    let index = match request.headers().get("Accept-Language") {
+        Some(accept_language) => best_match(accept_language, supported)?,
+        None if supported.is_empty() => return None,
+        None => 0,
+    };
+    Some(supported[index].clone())
+}
+
+// Returns the index in `supported` chosen by the rules of `negotiate_language`.
+pub(crate) fn best_match(accept_language: &str, supported: &[LanguageTag]) -> Option<usize> {
+    let ranges: Vec<(Option<LanguageTag>, f32)> = parse_quality_list(accept_language)
+        .into_iter()
+        .filter_map(|range| {
+            let tag = match range.value {
+                "*" => None,
+                tag => Some(LanguageTag::parse(tag)?),
+            };
+            Some((tag, range.quality))
+        })
+        .collect();
+
+    // For each supported tag: its weight, the position of the range that gave it, and
+    // how closely that range matched.
+    let mut candidates: Vec<(f32, usize, usize, usize)> = supported
+        .iter()
+        .enumerate()
+        .filter_map(|(index, tag)| {
+            let (closeness, position, q) = ranges
+                .iter()
+                .enumerate()
+                .filter_map(|(position, (range, q))| {
+                    let closeness = match range {
+                        Some(range) => closeness(range, tag)?,
+                        None => 0,
+                    };
+                    Some((closeness, position, *q))
+                })
+                .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)))?;
+            Some((q, position, closeness, index))
+        })
+        .filter(|(q, ..)| *q > 0.0)
+        .collect();
+    candidates.sort_by(|a, b| {
+        b.0.total_cmp(&a.0)
+            .then(a.1.cmp(&b.1))
+            .then(b.2.cmp(&a.2))
+            .then(a.3.cmp(&b.3))
+    });
+    candidates.first().map(|candidate| candidate.3)
+}
+
+// How closely a language range matches a tag, or `None` if it does not: twice the
+// number of shared leading subtags when one is a prefix of the other, one more when
+// they are equal, one less when they only share some.
+fn closeness(range: &LanguageTag, tag: &LanguageTag) -> Option<usize> {
+    if range == tag {
+        return Some(2 * tag.subtags().count() + 1);
+    }
+    let shared = range
+        .subtags()
+        .zip(tag.subtags())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if shared == 0 {
+        return None;
+    }
+    let prefix = shared == range.subtags().count() || shared == tag.subtags().count();
+    Some(if prefix { 2 * shared } else { 2 * shared - 1 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<LanguageTag> {
+        tags.iter()
+            .filter_map(|tag| LanguageTag::parse(tag))
+            .collect()
+    }
+
+    fn pick<'a>(accept_language: &str, supported: &'a [&str]) -> Option<&'a str> {
+        best_match(accept_language, &tags(supported)).map(|index| supported[index])
+    }
+
+    #[test]
+    fn parses_and_normalizes_tags() {
+        assert_eq!(LanguageTag::parse("EN").unwrap().as_str(), "en");
+        assert_eq!(LanguageTag::parse("pt_br").unwrap().as_str(), "pt-BR");
+        assert_eq!(
+            LanguageTag::parse("sr-latn-rs").unwrap().as_str(),
+            "sr-Latn-RS"
+        );
+        assert_eq!(LanguageTag::parse("es-419").unwrap().as_str(), "es-419");
+        for invalid in ["", "*", "en-", "1en", "en-toolongsubtag", "e n"] {
+            assert!(LanguageTag::parse(invalid).is_none(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn weighs_ranges_by_quality_and_specificity() {
+        let supported = ["en", "fr", "fr-CA", "pt-BR"];
+        assert_eq!(pick("de, fr;q=0.5, en;q=0.7", &supported), Some("en"));
+        assert_eq!(pick("fr-ca;q=0.5, de", &supported), Some("fr-CA"));
+        assert_eq!(pick("fr-BE", &supported), Some("fr"));
+        assert_eq!(pick("pt", &supported), Some("pt-BR"));
+        assert_eq!(pick("pt-PT", &supported), Some("pt-BR"));
+        assert_eq!(pick("fr-CA;q=0.2, fr;q=0.9", &supported), Some("fr"));
+        assert_eq!(pick("de", &supported), None);
+        assert_eq!(pick("", &supported), None);
+
+        // Wildcards and exclusions.
+        assert_eq!(pick("*", &supported), Some("en"));
+        assert_eq!(pick("en;q=0, fr;q=0, *;q=0.1", &supported), Some("pt-BR"));
+        assert_eq!(pick("fr;q=0, fr-CA", &supported), Some("fr-CA"));
+        assert_eq!(pick("*;q=0", &supported), None);
+    }
+
+    #[test]
+    fn reads_the_request_header() {
+        let supported = tags(&["en", "fr"]);
+        let request = Request::builder().build();
+        assert_eq!(
+            negotiate_language(&request, &supported).unwrap().as_str(),
+            "en"
+        );
+        let request = Request::builder()
+            .header("Accept-Language", "fr-FR, en;q=0.5")
+            .build();
+        assert_eq!(
+            negotiate_language(&request, &supported).unwrap().as_str(),
+            "fr"
+        );
+        assert_eq!(negotiate_language(&request, &[]), None);
+    }
+}
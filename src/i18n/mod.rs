@@ -0,0 +1,476 @@
+//! Internationalization — message catalogs and locale negotiation.
+//!
+//! - [`Catalog`] — one locale's messages, in a subset of the Fluent syntax.
+//! - [`I18n`] — the catalogs of every supported locale, with `Accept-Language`
+//!   negotiation and fallback from `fr-CA` to `fr` to the default locale.
+//! - [`negotiate_language`] — the same negotiation over any list of [`LanguageTag`]s,
+//!   for handlers that localize without catalogs.
+//! - [`LocaleMiddleware`] — picks each request's locale, optionally pinned by a query
+//!   parameter or cookie.
+//! - [`I18nExt`] — adds `ctx.locale()` and `ctx.t(key, args)` to [`Context`].
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::Arc;
+//! use rttp::{Response, Router, StatusCode, context::Context, middleware::from_middleware};
+//! use rttp::i18n::{Catalog, I18n, I18nExt, LocaleMiddleware};
+//! use rttp::testing::TestClient;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let i18n = I18n::new("en")
+//!     .catalog(Catalog::parse("en", "hello = Hello, { $name }!").unwrap())
+//!     .catalog(Catalog::parse("fr", "hello = Bonjour, { $name } !").unwrap());
+//!
+//! let mut router = Router::new();
+//! router.get("/", |ctx: Context| async move {
+//!     Response::new(StatusCode::Ok).body(ctx.t("hello", &[("name", &"Ada")]))
+//! });
+//!
+//! let mut client = TestClient::pipeline(vec![
+//!     from_middleware(Arc::new(LocaleMiddleware::new(i18n))),
+//!     from_middleware(Arc::new(router)),
+//! ]);
+//! client
+//!     .get("/")
+//!     .header("Accept-Language", "de;q=0.9, fr-CA;q=0.8, en;q=0.5")
+//!     .await
+//!     .assert_header("Content-Language", "fr")
+//!     .assert_text("Bonjour, Ada !");
+//! # }
+//! ```
+
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    fmt, fs, io,
+    path::Path,
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
+
+use thiserror::Error;
+
+use crate::{
+    Response,
+    context::Context,
+    http::{Cookie, SameSite},
+    middleware::{Middleware, Next},
+};
+
+mod catalog;
+mod language;
+
+pub use catalog::Catalog;
+pub use language::{LanguageTag, negotiate_language};
+
+// How long a locale chosen through the query parameter stays pinned by the cookie.
+const PIN_FOR: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Errors produced while loading catalogs.
+#[derive(Debug, Error)]
+pub enum I18nError {
+    #[error("syntax error in {locale} catalog at line {line}: {message}")]
+    Syntax {
+        locale: String,
+        line: usize,
+        message: String,
+    },
+
+    #[error("catalog io error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// The message catalogs of every supported locale.
+///
+/// Locale tags are matched case-insensitively. A message missing from a locale's
+/// catalog is looked up in its parent (`fr` for `fr-CA`) and then in the default
+/// locale; a message missing everywhere is shown as its key.
+#[derive(Debug, Clone)]
+pub struct I18n {
+    default: String,
+    catalogs: HashMap<String, Catalog>,
+}
+
+impl I18n {
+    /// Creates an empty set of catalogs whose fallback locale is `default`.
+    pub fn new(default: &str) -> Self {
+        Self {
+            default: default.to_owned(),
+            catalogs: HashMap::new(),
+        }
+    }
+
+    /// Loads the catalogs in `dir`: each `<locale>.ftl` file, and the `.ftl` files in
+    /// each `<locale>/` directory, merged.
+    ///
+    /// # Errors
+    ///
+    /// [`I18nError::Io`] if a file cannot be read, or [`I18nError::Syntax`] for the
+    /// first malformed message.
+    pub fn from_dir(dir: impl AsRef<Path>, default: &str) -> Result<Self, I18nError> {
+        let mut i18n = Self::new(default);
+        let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.path());
+        for entry in entries {
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                let locale = entry.file_name().to_string_lossy().into_owned();
+                let mut files = fs::read_dir(&path)?.collect::<Result<Vec<_>, _>>()?;
+                files.sort_by_key(|file| file.path());
+                for file in files {
+                    if is_ftl(&file.path()) {
+                        i18n.load(&locale, &fs::read_to_string(file.path())?)?;
+                    }
+                }
+            } else if is_ftl(&path) {
+                let locale = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                i18n.load(&locale, &fs::read_to_string(&path)?)?;
+            }
+        }
+        Ok(i18n)
+    }
+
+    // Adds the messages in `source` to `locale`'s catalog.
+    fn load(&mut self, locale: &str, source: &str) -> Result<(), I18nError> {
+        match self.catalogs.entry(locale.to_ascii_lowercase()) {
+            Entry::Occupied(entry) => entry.into_mut().extend(source),
+            Entry::Vacant(entry) => {
+                entry.insert(Catalog::parse(locale, source)?);
+                Ok(())
+            }
+        }
+    }
+
+    /// Adds `catalog`, replacing any for the same locale.
+    #[must_use]
+    pub fn catalog(mut self, catalog: Catalog) -> Self {
+        self.catalogs
+            .insert(catalog.locale().to_ascii_lowercase(), catalog);
+        self
+    }
+
+    /// The fallback locale.
+    pub fn default_locale(&self) -> &str {
+        &self.default
+    }
+
+    /// The locales that have a catalog, in no particular order.
+    pub fn locales(&self) -> impl Iterator<Item = &str> {
+        self.catalogs.values().map(Catalog::locale)
+    }
+
+    /// Returns the supported locale that best serves `tag`: the locale itself, the
+    /// nearest parent (`zh` for `zh-Hant-TW`), or another region of the same language
+    /// (`pt-BR` for `pt`).
+    pub fn resolve(&self, tag: &str) -> Option<&str> {
+        let tag = tag.trim().replace('_', "-").to_ascii_lowercase();
+        let mut prefix = tag.as_str();
+        loop {
+            if let Some(catalog) = self.catalogs.get(prefix) {
+                return Some(catalog.locale());
+            }
+            match prefix.rsplit_once('-') {
+                Some((parent, _)) => prefix = parent,
+                None => break,
+            }
+        }
+        let mut siblings: Vec<_> = self
+            .catalogs
+            .iter()
+            .filter(|(key, _)| key.split('-').next() == Some(prefix))
+            .collect();
+        siblings.sort_by_key(|(key, _)| key.as_str());
+        siblings.first().map(|(_, catalog)| catalog.locale())
+    }
+
+    /// Picks the locale for an `Accept-Language` header value.
+    ///
+    /// Chooses among the default locale and those with a catalog by the rules of
+    /// [`negotiate_language`], with `*` favoring the default. Falls back to the default
+    /// locale.
+    pub fn negotiate(&self, accept_language: Option<&str>) -> &str {
+        let default_key = self.default.to_ascii_lowercase();
+        let mut locales: Vec<&str> = self
+            .catalogs
+            .iter()
+            .filter(|(key, _)| **key != default_key)
+            .map(|(_, catalog)| catalog.locale())
+            .collect();
+        locales.sort_unstable();
+        locales.insert(0, &self.default);
+        let (locales, tags): (Vec<&str>, Vec<LanguageTag>) = locales
+            .into_iter()
+            .filter_map(|locale| Some((locale, LanguageTag::parse(locale)?)))
+            .unzip();
+
+        accept_language
+            .and_then(|accept_language| language::best_match(accept_language, &tags))
+            .map_or(&self.default, |index| locales[index])
+    }
+
+    /// Formats message `key` in `locale`, falling back as described on [`I18n`].
+    pub fn translate(&self, locale: &str, key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+        let locale = locale.to_ascii_lowercase();
+        let mut prefix = locale.as_str();
+        loop {
+            let found = self
+                .catalogs
+                .get(prefix)
+                .and_then(|catalog| catalog.format(key, args));
+            if let Some(message) = found {
+                return message;
+            }
+            match prefix.rsplit_once('-') {
+                Some((parent, _)) => prefix = parent,
+                None => break,
+            }
+        }
+        self.catalogs
+            .get(&self.default.to_ascii_lowercase())
+            .and_then(|catalog| catalog.format(key, args))
+            .unwrap_or_else(|| key.to_owned())
+    }
+}
+
+fn is_ftl(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "ftl")
+}
+
+/// The locale chosen for a request, installed by [`LocaleMiddleware`].
+#[derive(Debug, Clone)]
+pub struct Locale {
+    i18n: Arc<I18n>,
+    locale: String,
+}
+
+impl Locale {
+    /// The locale tag, such as `fr` or `pt-BR`.
+    pub fn as_str(&self) -> &str {
+        &self.locale
+    }
+
+    /// Formats message `key` in this locale.
+    pub fn t(&self, key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+        self.i18n.translate(&self.locale, key, args)
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.locale)
+    }
+}
+
+/// Chooses a locale for each request and installs it as a [`Locale`] extension.
+///
+/// The locale comes from, in order: the query parameter set with
+/// [`query`](Self::query), the cookie set with [`cookie`](Self::cookie), and the
+/// `Accept-Language` header. Overrides naming an unsupported locale are ignored. When
+/// both are configured, a query override is pinned by setting the cookie, so
+/// `?lang=fr` switches the language for later requests too.
+///
+/// Responses get a `Content-Language` header unless the handler set one, and
+/// `Vary: Accept-Language` so caches keep the translations apart.
+#[derive(Debug, Clone)]
+pub struct LocaleMiddleware {
+    i18n: Arc<I18n>,
+    query: Option<String>,
+    cookie: Option<String>,
+}
+
+impl LocaleMiddleware {
+    /// Creates a middleware that negotiates among the locales of `i18n`.
+    pub fn new(i18n: I18n) -> Self {
+        Self {
+            i18n: Arc::new(i18n),
+            query: None,
+            cookie: None,
+        }
+    }
+
+    /// Lets the query parameter `name` (such as `lang`) override the locale.
+    #[must_use]
+    pub fn query(mut self, name: impl Into<String>) -> Self {
+        self.query = Some(name.into());
+        self
+    }
+
+    /// Lets the cookie `name` override the locale, and pins query overrides in it.
+    #[must_use]
+    pub fn cookie(mut self, name: impl Into<String>) -> Self {
+        self.cookie = Some(name.into());
+        self
+    }
+}
+
+impl Middleware for LocaleMiddleware {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let request = ctx.request();
+        let from_query = self
+            .query
+            .as_deref()
+            .and_then(|name| request.query_param(name))
+            .and_then(|tag| self.i18n.resolve(tag));
+        let from_cookie = self
+            .cookie
+            .as_deref()
+            .and_then(|name| request.cookie(name))
+            .and_then(|tag| self.i18n.resolve(tag));
+        let locale = from_query
+            .or(from_cookie)
+            .unwrap_or_else(|| {
+                self.i18n
+                    .negotiate(request.headers().get("Accept-Language"))
+            })
+            .to_owned();
+        let pin = match (&self.cookie, from_query) {
+            (Some(name), Some(_)) if from_cookie != from_query => Some(
+                Cookie::new(name.clone(), locale.clone())
+                    .path("/")
+                    .max_age(PIN_FOR)
+                    .same_site(SameSite::Lax),
+            ),
+            _ => None,
+        };
+
+        ctx.extensions_mut().insert(Locale {
+            i18n: Arc::clone(&self.i18n),
+            locale: locale.clone(),
+        });
+        Box::pin(async move {
+            let mut response = next.run(ctx).await;
+            if response.headers().get("Content-Language").is_none() {
+                response.add_header("Content-Language", locale);
+            }
+            response.add_header("Vary", "Accept-Language");
+            if let Some(cookie) = pin {
+                response.add_cookie(&cookie);
+            }
+            response
+        })
+    }
+}
+
+/// Adds the request's locale and translations to [`Context`].
+pub trait I18nExt {
+    /// Returns the locale chosen by [`LocaleMiddleware`], if it is installed.
+    fn locale(&self) -> Option<&Locale>;
+
+    /// Formats message `key` in the request's locale. Without [`LocaleMiddleware`] the
+    /// key itself is returned.
+    fn t(&self, key: &str, args: &[(&str, &dyn fmt::Display)]) -> String;
+}
+
+impl I18nExt for Context {
+    fn locale(&self) -> Option<&Locale> {
+        self.extensions().get::<Locale>()
+    }
+
+    fn t(&self, key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+        match self.locale() {
+            Some(locale) => locale.t(key, args),
+            None => key.to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Router, StatusCode, middleware::from_middleware, testing::TestClient};
+
+    fn i18n() -> I18n {
+        I18n::new("en")
+            .catalog(Catalog::parse("en", "hello = Hello\nbye = Goodbye").unwrap())
+            .catalog(Catalog::parse("fr", "hello = Bonjour\nbye = Au revoir").unwrap())
+            .catalog(Catalog::parse("fr-CA", "hello = Allô").unwrap())
+            .catalog(Catalog::parse("pt-BR", "hello = Olá").unwrap())
+    }
+
+    #[test]
+    fn negotiates_by_quality_and_falls_back() {
+        let i18n = i18n();
+        assert_eq!(i18n.negotiate(None), "en");
+        assert_eq!(i18n.negotiate(Some("de, fr;q=0.5, en;q=0.7")), "en");
+        assert_eq!(i18n.negotiate(Some("de, FR-ca;q=0.5")), "fr-CA");
+        assert_eq!(i18n.negotiate(Some("fr-BE")), "fr");
+        assert_eq!(i18n.negotiate(Some("pt")), "pt-BR");
+        assert_eq!(i18n.negotiate(Some("fr;q=0, *;q=0.1")), "en");
+        assert_eq!(i18n.negotiate(Some("de")), "en");
+
+        assert_eq!(i18n.translate("fr-CA", "hello", &[]), "Allô");
+        assert_eq!(i18n.translate("fr-CA", "bye", &[]), "Au revoir");
+        assert_eq!(i18n.translate("pt-BR", "bye", &[]), "Goodbye");
+        assert_eq!(i18n.translate("fr", "missing", &[]), "missing");
+    }
+
+    #[test]
+    fn loads_catalogs_from_a_directory() {
+        let dir = std::env::temp_dir().join(format!(
+            "rttp-i18n-{}",
+            crate::security::crypto::random_token(8)
+        ));
+        fs::create_dir_all(dir.join("fr")).unwrap();
+        fs::write(dir.join("en.ftl"), "hello = Hello").unwrap();
+        fs::write(dir.join("fr/main.ftl"), "hello = Bonjour").unwrap();
+        fs::write(dir.join("fr/shop.ftl"), "cart = Panier").unwrap();
+        fs::write(dir.join("README.md"), "not a catalog").unwrap();
+
+        let i18n = I18n::from_dir(&dir, "en").unwrap();
+        let mut locales: Vec<_> = i18n.locales().collect();
+        locales.sort_unstable();
+        assert_eq!(locales, ["en", "fr"]);
+        assert_eq!(i18n.translate("fr", "cart", &[]), "Panier");
+
+        fs::write(dir.join("de.ftl"), "broken").unwrap();
+        assert!(matches!(
+            I18n::from_dir(&dir, "en"),
+            Err(I18nError::Syntax { line: 1, .. })
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn overrides_pin_the_locale() {
+        let mut router = Router::new();
+        router.get("/", |ctx: Context| async move {
+            let locale = ctx.locale().map(Locale::to_string).unwrap_or_default();
+            Response::new(StatusCode::Ok).body(format!("{locale}: {}", ctx.t("hello", &[])))
+        });
+        let middleware = LocaleMiddleware::new(i18n()).query("lang").cookie("lang");
+        let mut client = TestClient::pipeline(vec![
+            from_middleware(Arc::new(middleware)),
+            from_middleware(Arc::new(router)),
+        ]);
+
+        client
+            .get("/")
+            .header("Accept-Language", "fr")
+            .await
+            .assert_header("Content-Language", "fr")
+            .assert_header("Vary", "Accept-Language")
+            .assert_text("fr: Bonjour");
+        assert_eq!(client.cookie("lang"), None);
+
+        // An unsupported override is ignored.
+        client.get("/?lang=xx").await.assert_text("en: Hello");
+        assert_eq!(client.cookie("lang"), None);
+
+        client.get("/?lang=fr-ca").await.assert_text("fr-CA: Allô");
+        assert_eq!(client.cookie("lang"), Some("fr-CA"));
+        client
+            .get("/")
+            .header("Accept-Language", "en")
+            .await
+            .assert_text("fr-CA: Allô");
+    }
+}
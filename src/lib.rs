@@ -19,21 +19,36 @@
 //! }
 //! ```
 
-// ── Active modules with real implementations ──────────────────────────────────
-pub mod http;
-pub mod server;
+// Lets derive macros refer to `::rttp` from inside this crate too.
+extern crate self as rttp;
 
-// ── Planned modules — stubs for future implementation ────────────────────────
+// ── Modules ───────────────────────────────────────────────────────────────────
+pub mod app;
 pub mod background;
+pub mod bench;
 pub mod cache;
+pub mod clock;
+pub mod codec;
+pub mod config;
 pub mod context;
 pub mod database;
+pub mod files;
+pub mod http;
+pub mod i18n;
 pub mod llm;
 pub mod middleware;
+pub mod proxy;
 pub mod realtime;
+pub mod redis;
 pub mod router;
 pub mod security;
+pub mod server;
+pub mod templates;
+pub mod testing;
+pub mod webhooks;
 
 // ── Convenience re-exports ────────────────────────────────────────────────────
+pub use app::App;
 pub use http::{Headers, Method, Request, Response, StatusCode};
+pub use router::Router;
 pub use server::{Server, ServerError};
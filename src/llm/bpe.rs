@@ -0,0 +1,227 @@
+//! Byte-pair encoding with tiktoken vocabularies.
+//!
+//! OpenAI publishes its encodings (`cl100k_base`, `o200k_base`, …) as `.tiktoken` files:
+//! one base64 token and its merge rank per line. [`Bpe::from_tiktoken`] loads one, and
+//! [`Bpe`] then encodes exactly as the model does for ordinary text. Text is first split
+//! into words, numbers, punctuation, and whitespace the way `cl100k_base` splits it;
+//! `o200k_base` splits slightly differently around case changes, so counts for it may be
+//! off by a token here and there. Special tokens such as `<|endoftext|>` are not
+//! recognized.
+//!
+//! Vocabularies are several megabytes and not bundled; ship the file with the
+//! application or fetch it at startup.
+
+use std::collections::HashMap;
+
+use super::{LlmError, tokens::Tokenizer};
+use crate::security::crypto::base64_decode;
+
+/// A byte-pair encoder for one tiktoken vocabulary.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::llm::Bpe;
+///
+/// // A toy vocabulary: the bytes `a` and `b`, then the merges `ab` and `abab`.
+/// let bpe = Bpe::from_tiktoken("YQ== 0\nYg== 1\nYWI= 2\nYWJhYg== 3\n").unwrap();
+/// assert_eq!(bpe.encode("ababa"), [3, 0]);
+/// assert_eq!(bpe.decode(&[3, 0]), b"ababa");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Bpe {
+    ranks: HashMap<Vec<u8>, u32>,
+    tokens: HashMap<u32, Vec<u8>>,
+}
+
+impl Bpe {
+    /// Loads a vocabulary in the `.tiktoken` format.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LlmError::Malformed`] naming the first line that is not a base64 token
+    /// followed by a rank.
+    pub fn from_tiktoken(vocabulary: &str) -> Result<Self, LlmError> {
+        let mut ranks = HashMap::new();
+        let mut tokens = HashMap::new();
+        for (number, line) in vocabulary.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let parsed = line.split_once(' ').and_then(|(token, rank)| {
+                Some((base64_decode(token)?, rank.trim().parse::<u32>().ok()?))
+            });
+            let Some((token, rank)) = parsed else {
+                return Err(LlmError::Malformed(format!(
+                    "tiktoken vocabulary line {}: {line:?}",
+                    number + 1
+                )));
+            };
+            tokens.insert(rank, token.clone());
+            ranks.insert(token, rank);
+        }
+        Ok(Self { ranks, tokens })
+    }
+
+    /// Returns the number of tokens in the vocabulary.
+    pub fn len(&self) -> usize {
+        self.ranks.len()
+    }
+
+    /// Returns `true` if the vocabulary is empty.
+    pub fn is_empty(&self) -> bool {
+        self.ranks.is_empty()
+    }
+
+    /// Encodes `text` as token IDs.
+    ///
+    /// Bytes missing from the vocabulary are skipped; every real vocabulary has all 256.
+    pub fn encode(&self, text: &str) -> Vec<u32> {
+        let mut ids = Vec::new();
+        for piece in pieces(text) {
+            self.encode_piece(piece.as_bytes(), &mut ids);
+        }
+        ids
+    }
+
+    /// Decodes token IDs back into bytes, skipping unknown IDs.
+    ///
+    /// The bytes are UTF-8 unless the IDs split a character.
+    pub fn decode(&self, ids: &[u32]) -> Vec<u8> {
+        ids.iter()
+            .filter_map(|id| self.tokens.get(id))
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    // Merges adjacent parts of `piece`, lowest rank first, until no pair is a token.
+    fn encode_piece(&self, piece: &[u8], ids: &mut Vec<u32>) {
+        if let Some(&rank) = self.ranks.get(piece) {
+            ids.push(rank);
+            return;
+        }
+        // Part boundaries as byte offsets, starting with one part per byte.
+        let mut bounds: Vec<usize> = (0..=piece.len()).collect();
+        loop {
+            let best = bounds
+                .windows(3)
+                .enumerate()
+                .filter_map(|(i, w)| Some((*self.ranks.get(&piece[w[0]..w[2]])?, i)))
+                .min();
+            let Some((_, i)) = best else { break };
+            bounds.remove(i + 1);
+        }
+        ids.extend(
+            bounds
+                .windows(2)
+                .filter_map(|w| self.ranks.get(&piece[w[0]..w[1]]).copied()),
+        );
+    }
+}
+
+impl Tokenizer for Bpe {
+    fn count(&self, text: &str) -> usize {
+        self.encode(text).len()
+    }
+}
+
+// Splits text the way `cl100k_base`'s pattern does:
+// contractions, words with one leading non-letter, numbers of up to three digits,
+// punctuation runs with an optional leading space, and whitespace — where a run of
+// spaces before a word leaves its last space to the word.
+fn pieces(text: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let at = |i: usize| chars.get(i).map(|&(_, c)| c);
+    let offset = |i: usize| chars.get(i).map_or(text.len(), |&(o, _)| o);
+    let is_punct = |c: char| !c.is_whitespace() && !c.is_alphabetic() && !c.is_numeric();
+    let run = |mut i: usize, f: &dyn Fn(char) -> bool| {
+        while at(i).is_some_and(f) {
+            i += 1;
+        }
+        i
+    };
+
+    let mut pieces = Vec::new();
+    let mut i = 0;
+    while let Some(c) = at(i) {
+        let end = if let Some(len) = contraction(&text[offset(i)..]) {
+            // Contractions are ASCII, so bytes are characters.
+            i + len
+        } else if c.is_alphabetic() {
+            run(i, &char::is_alphabetic)
+        } else if !matches!(c, '\r' | '\n')
+            && !c.is_numeric()
+            && at(i + 1).is_some_and(char::is_alphabetic)
+        {
+            run(i + 1, &char::is_alphabetic)
+        } else if c.is_numeric() {
+            (i + 1..i + 3)
+                .take_while(|&j| at(j).is_some_and(char::is_numeric))
+                .last()
+                .map_or(i + 1, |j| j + 1)
+        } else if is_punct(c) || (c == ' ' && at(i + 1).is_some_and(is_punct)) {
+            let end = run(i + 1, &is_punct);
+            run(end, &|c| matches!(c, '\r' | '\n'))
+        } else {
+            // Whitespace.
+            let end = run(i, &char::is_whitespace);
+            let last_newline = (i..end).rev().find(|&j| matches!(at(j), Some('\r' | '\n')));
+            match last_newline {
+                Some(j) => j + 1,
+                None if end == chars.len() || end - i == 1 => end,
+                None => end - 1,
+            }
+        };
+        pieces.push(&text[offset(i)..offset(end)]);
+        i = end;
+    }
+    pieces
+}
+
+// The length in bytes of an English contraction suffix at the start of `s`.
+fn contraction(s: &str) -> Option<usize> {
+    let rest = s.strip_prefix('\'')?;
+    ["s", "t", "re", "ve", "m", "ll", "d"]
+        .iter()
+        .find(|suffix| {
+            rest.get(..suffix.len())
+                .is_some_and(|r| r.eq_ignore_ascii_case(suffix))
+        })
+        .map(|suffix| suffix.len() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::crypto::base64_encode;
+
+    #[test]
+    fn splits_like_cl100k() {
+        assert_eq!(
+            pieces("Hello, world's 12345\n\n  x (y)!"),
+            [
+                "Hello", ",", " world", "'s", " ", "123", "45", "\n\n", " ", " x", " (", "y", ")!"
+            ]
+        );
+        assert_eq!(pieces("a  "), ["a", "  "]);
+    }
+
+    #[test]
+    fn merges_lowest_ranks_first() {
+        // Single bytes, then merges; "cd" outranks "bc", so "bcd" is "b" + "cd".
+        let vocabulary: String = ["a", "b", "c", "d", " ", "cd", "bc", " b"]
+            .iter()
+            .enumerate()
+            .map(|(rank, token)| format!("{} {rank}\n", base64_encode(token.as_bytes())))
+            .collect();
+        let bpe = Bpe::from_tiktoken(&vocabulary).unwrap();
+        assert_eq!(bpe.len(), 8);
+        assert_eq!(bpe.encode("bcd"), [1, 5]);
+        assert_eq!(bpe.encode("a bcd"), [0, 7, 5]);
+        assert_eq!(bpe.decode(&bpe.encode("a bcd")), b"a bcd");
+        assert_eq!(bpe.count("a bcd"), 3);
+
+        assert!(Bpe::from_tiktoken("YQ== x\n").is_err());
+    }
+}
@@ -0,0 +1,320 @@
+//! Caching completions so repeated prompts skip the provider.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use serde_json::json;
+use tokio::sync::watch;
+use tracing::warn;
+
+use super::{ChatRequest, Completion, Delta, Provider, stream::Assembler};
+use crate::{
+    cache::{Cache, CacheExt},
+    security::crypto::{sha256, to_hex},
+};
+
+// Completions being generated, by key, so identical concurrent requests wait for the
+// first instead of each calling the provider.
+type InFlight = Arc<Mutex<HashMap<String, Leader>>>;
+
+// The ID of the recorder generating a completion, and where it will announce it.
+type Leader = (u64, watch::Receiver<Option<Completion>>);
+
+/// Caches completions in any [`Cache`] backend.
+///
+/// Requests are keyed by a hash of the endpoint, model, messages, tools, and sampling
+/// parameters, with surrounding whitespace in messages ignored. Only completions that
+/// finish are stored. A hit is replayed as a [`ChatStream`](super::ChatStream) of the
+/// same deltas, without usage, since it cost nothing. Identical requests made while the
+/// first is still streaming wait for it and are then answered from its completion.
+///
+/// Attach one with [`LlmClient::cache`](super::LlmClient::cache); skip it for a single
+/// request with [`ChatRequest::no_cache`].
+///
+/// # Examples
+///
+/// ```
+/// use std::{sync::Arc, time::Duration};
+/// use rttp::cache::MemoryCache;
+/// use rttp::llm::{LlmCache, LlmClient};
+///
+/// let client = LlmClient::openai("http://127.0.0.1:11434/v1").cache(
+///     LlmCache::new(Arc::new(MemoryCache::new(1_000))).ttl(Duration::from_secs(3600)),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct LlmCache {
+    cache: Arc<dyn Cache>,
+    ttl: Option<Duration>,
+    in_flight: InFlight,
+    next_id: Arc<AtomicU64>,
+}
+
+impl LlmCache {
+    /// Caches completions in `cache`, using the backend's default expiry.
+    pub fn new(cache: Arc<dyn Cache>) -> Self {
+        Self {
+            cache,
+            ttl: None,
+            in_flight: InFlight::default(),
+            next_id: Arc::default(),
+        }
+    }
+
+    /// Sets how long completions are kept.
+    #[must_use]
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    // The cache key for `request` sent to `base_url`.
+    pub(super) fn key(provider: Provider, base_url: &str, request: &ChatRequest) -> String {
+        let messages: Vec<_> = request
+            .messages
+            .iter()
+            .map(|m| {
+                json!({
+                    "role": m.role,
+                    "content": m.content.trim(),
+                    "tool_calls": m.tool_calls,
+                    "tool_call_id": m.tool_call_id,
+                })
+            })
+            .collect();
+        // `serde_json` maps keep their keys sorted, so equal requests encode equally.
+        let normalized = json!({
+            "provider": format!("{provider:?}"),
+            "endpoint": base_url,
+            "model": request.model,
+            "messages": messages,
+            "tools": request.tools,
+            "max_tokens": request.max_tokens,
+            "temperature": request.temperature,
+        });
+        format!("llm:{}", to_hex(&sha256(normalized.to_string().as_bytes())))
+    }
+
+    // Returns the cached completion for `key`, or a recorder for the caller to fill in.
+    pub(super) async fn lookup(&self, key: &str) -> Lookup {
+        loop {
+            match self.cache.get_json::<Completion>(key).await {
+                Ok(Some(completion)) => return Lookup::Hit(completion),
+                Ok(None) => {}
+                Err(e) => warn!(error = %e, "LLM cache read failed"),
+            }
+
+            let mut leader = {
+                let mut in_flight = self.lock();
+                match in_flight.get(key) {
+                    Some((_, rx)) => rx.clone(),
+                    None => {
+                        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                        let (tx, rx) = watch::channel(None);
+                        in_flight.insert(key.to_owned(), (id, rx));
+                        return Lookup::Miss(Recorder {
+                            cache: self.clone(),
+                            key: key.to_owned(),
+                            id,
+                            tx,
+                            assembler: Some(Assembler::default()),
+                        });
+                    }
+                }
+            };
+            // The leader failing drops its sender; then try again, perhaps as the leader.
+            if let Ok(completion) = leader.wait_for(Option::is_some).await {
+                if let Some(completion) = completion.clone() {
+                    return Lookup::Hit(completion);
+                }
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Leader>> {
+        self.in_flight.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub(super) enum Lookup {
+    Hit(Completion),
+    Miss(Recorder),
+}
+
+// Collects a streaming completion and caches it once it finishes.
+pub(super) struct Recorder {
+    cache: LlmCache,
+    key: String,
+    id: u64,
+    tx: watch::Sender<Option<Completion>>,
+    assembler: Option<Assembler>,
+}
+
+impl Recorder {
+    pub(super) fn push(&mut self, delta: &Delta) {
+        if let Some(assembler) = &mut self.assembler {
+            assembler.push(delta);
+        }
+    }
+
+    pub(super) async fn store(mut self) {
+        let Some(assembler) = self.assembler.take() else {
+            return;
+        };
+        let completion = assembler.finish();
+        let cache = &self.cache;
+        if let Err(e) = cache
+            .cache
+            .set_json(&self.key, &completion, cache.ttl)
+            .await
+        {
+            warn!(error = %e, "LLM cache write failed");
+        }
+        self.tx.send_replace(Some(completion));
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let mut in_flight = self.cache.lock();
+        if in_flight
+            .get(&self.key)
+            .is_some_and(|(id, _)| *id == self.id)
+        {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::{
+        cache::MemoryCache,
+        llm::{
+            ChatMessage, LlmClient,
+            transport::{StreamingResponse, Transport, TransportFuture},
+        },
+    };
+
+    // Counts requests and answers each with the same completion, after a pause.
+    #[derive(Default)]
+    struct Counting {
+        calls: AtomicUsize,
+    }
+
+    impl Transport for Counting {
+        fn post<'a>(
+            &'a self,
+            _url: &'a str,
+            _headers: &'a [(String, String)],
+            _body: &'a [u8],
+        ) -> TransportFuture<'a, StreamingResponse> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let body = b"data: {\"choices\":[{\"delta\":{\"content\":\"Paris\"},\"finish_reason\":\"stop\"}]}\n\n\
+                data: {\"choices\":[],\"usage\":{\"prompt_tokens\":9,\"completion_tokens\":1}}\n\n\
+                data: [DONE]\n\n"
+                .to_vec();
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok(StreamingResponse {
+                    status: 200,
+                    body: Box::new(vec![body].into_iter()),
+                })
+            })
+        }
+    }
+
+    fn request(question: &str) -> ChatRequest {
+        ChatRequest::new("model-x").message(ChatMessage::user(question))
+    }
+
+    #[test]
+    fn keys_ignore_surrounding_whitespace_only() {
+        let key = |r: &ChatRequest| LlmCache::key(Provider::OpenAi, "http://a/v1", r);
+        assert_eq!(
+            key(&request("Capital of France?")),
+            key(&request("  Capital of France?\n"))
+        );
+        assert_ne!(
+            key(&request("Capital of France?")),
+            key(&request("Capital of Spain?"))
+        );
+        assert_ne!(
+            key(&request("Capital of France?")),
+            key(&request("Capital of France?").temperature(0.5))
+        );
+        assert_ne!(
+            key(&request("Capital of France?")),
+            LlmCache::key(
+                Provider::OpenAi,
+                "http://b/v1",
+                &request("Capital of France?")
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn answers_repeats_from_the_cache() {
+        let transport = Arc::new(Counting::default());
+        let client = LlmClient::openai("http://llm.internal/v1")
+            .transport(transport.clone())
+            .cache(LlmCache::new(Arc::new(MemoryCache::new(100))));
+
+        let first = client
+            .stream_chat(&request("Capital of France?"))
+            .await
+            .unwrap();
+        let first = first.complete().await.unwrap();
+        assert_eq!(first.text, "Paris");
+        assert_eq!(first.usage.input_tokens, Some(9));
+
+        let again = client
+            .stream_chat(&request("Capital of France?"))
+            .await
+            .unwrap();
+        let again = again.complete().await.unwrap();
+        assert_eq!(again.text, "Paris");
+        assert_eq!(again.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(again.usage.input_tokens, None);
+        assert_eq!(transport.calls.load(Ordering::SeqCst), 1);
+
+        let uncached = request("Capital of France?").no_cache();
+        client
+            .stream_chat(&uncached)
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(transport.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn collapses_concurrent_identical_requests() {
+        let transport = Arc::new(Counting::default());
+        let client = LlmClient::openai("http://llm.internal/v1")
+            .transport(transport.clone())
+            .cache(LlmCache::new(Arc::new(MemoryCache::new(100))));
+
+        let ask = || {
+            let client = client.clone();
+            tokio::spawn(async move {
+                let stream = client.stream_chat(&request("Capital of France?")).await?;
+                stream.text().await
+            })
+        };
+        let (a, b) = (ask(), ask());
+        assert_eq!(a.await.unwrap().unwrap(), "Paris");
+        assert_eq!(b.await.unwrap().unwrap(), "Paris");
+        assert_eq!(transport.calls.load(Ordering::SeqCst), 1);
+    }
+}
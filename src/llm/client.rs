@@ -0,0 +1,513 @@
+//! The provider client: request encoding and error handling.
+
+use std::sync::Arc;
+
+use serde_json::{Value, json};
+
+use super::{
+    ChatMessage, ChatRequest, LlmError, Role, Usage,
+    cache::{LlmCache, Lookup},
+    context::ContextWindow,
+    meter::{Metering, UsageMeter},
+    stream::ChatStream,
+    transport::{HttpTransport, StreamingResponse, Transport, TransportFuture},
+};
+
+// The largest error body kept for `LlmError::Status`.
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+// Anthropic requires `max_tokens`; used when the request leaves it unset.
+const DEFAULT_ANTHROPIC_MAX_TOKENS: u32 = 1024;
+
+/// The API a [`LlmClient`] speaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    /// OpenAI's chat completions API, also served by most local model servers and
+    /// gateways.
+    OpenAi,
+    /// Anthropic's messages API.
+    Anthropic,
+}
+
+/// Anything that completes chat requests: a remote [`LlmClient`], or an in-process
+/// model.
+///
+/// Helpers such as [`Toolbox::run`](super::Toolbox::run) accept any provider.
+pub trait ChatProvider: Send + Sync {
+    /// Starts a streamed completion of `request`.
+    fn stream_chat<'a>(&'a self, request: &'a ChatRequest) -> TransportFuture<'a, ChatStream>;
+}
+
+/// A client for one provider endpoint.
+#[derive(Clone)]
+pub struct LlmClient {
+    provider: Provider,
+    base_url: String,
+    api_key: Option<String>,
+    transport: Arc<dyn Transport>,
+    window: Option<ContextWindow>,
+    cache: Option<LlmCache>,
+    meter: Option<UsageMeter>,
+}
+
+impl LlmClient {
+    /// Creates a client for an OpenAI-compatible API rooted at `base_url`, such as
+    /// `http://127.0.0.1:11434/v1`; requests go to `{base_url}/chat/completions`.
+    pub fn openai(base_url: impl Into<String>) -> Self {
+        Self::new(Provider::OpenAi, base_url)
+    }
+
+    /// Creates a client for Anthropic's API rooted at `base_url`; requests go to
+    /// `{base_url}/v1/messages`.
+    pub fn anthropic(base_url: impl Into<String>) -> Self {
+        Self::new(Provider::Anthropic, base_url)
+    }
+
+    fn new(provider: Provider, base_url: impl Into<String>) -> Self {
+        Self {
+            provider,
+            base_url: base_url.into().trim_end_matches('/').to_owned(),
+            api_key: None,
+            transport: Arc::new(HttpTransport::new()),
+            window: None,
+            cache: None,
+            meter: None,
+        }
+    }
+
+    /// Sets the API key sent with every request.
+    #[must_use]
+    pub fn api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    /// Replaces the default [`HttpTransport`].
+    #[must_use]
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Fits every request into `window` before sending it.
+    #[must_use]
+    pub fn context_window(mut self, window: ContextWindow) -> Self {
+        self.window = Some(window);
+        self
+    }
+
+    /// Answers repeated requests from `cache`.
+    #[must_use]
+    pub fn cache(mut self, cache: LlmCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Records usage into `meter` and enforces its budgets.
+    #[must_use]
+    pub fn meter(mut self, meter: UsageMeter) -> Self {
+        self.meter = Some(meter);
+        self
+    }
+
+    /// Returns the provider this client speaks to.
+    pub fn provider(&self) -> Provider {
+        self.provider
+    }
+
+    /// Starts a streamed completion of `request`.
+    ///
+    /// Resolves once the provider has accepted the request; the completion then arrives
+    /// through the returned [`ChatStream`].
+    ///
+    /// # Errors
+    ///
+    /// [`LlmError::Status`] with the provider's error body if it rejects the request,
+    /// [`LlmError::BudgetExceeded`] if the client's meter refuses it, or the transport's
+    /// error if the provider cannot be reached.
+    pub async fn stream_chat(&self, request: &ChatRequest) -> Result<ChatStream, LlmError> {
+        // Keyed before fitting, so a hit also skips any summary the window would request.
+        let recorder = match self.cache.as_ref().filter(|_| request.cache) {
+            Some(cache) => match cache
+                .lookup(&LlmCache::key(self.provider, &self.base_url, request))
+                .await
+            {
+                Lookup::Hit(completion) => {
+                    return Ok(ChatStream::replay(self.provider, completion));
+                }
+                Lookup::Miss(recorder) => Some(recorder),
+            },
+            None => None,
+        };
+        let stream = match &self.window {
+            Some(window) => self.send(&window.fit(self, request).await?).await?,
+            None => self.send(request).await?,
+        };
+        Ok(match recorder {
+            Some(recorder) => stream.record(recorder),
+            None => stream,
+        })
+    }
+
+    // Sends `request` as is; the context window's own summaries go through here.
+    pub(super) async fn send(&self, request: &ChatRequest) -> Result<ChatStream, LlmError> {
+        if let Some(meter) = &self.meter {
+            meter.check(&request.route)?;
+        }
+        let (url, body) = match self.provider {
+            Provider::OpenAi => (
+                format!("{}/chat/completions", self.base_url),
+                openai_body(request),
+            ),
+            Provider::Anthropic => (
+                format!("{}/v1/messages", self.base_url),
+                anthropic_body(request),
+            ),
+        };
+        let body = serde_json::to_vec(&body)?;
+        let headers = self.headers();
+        let response = self.transport.post(&url, &headers, &body).await?;
+
+        if !(200..300).contains(&response.status) {
+            return Err(rejection(response).await);
+        }
+        let stream = ChatStream::new(self.provider, response.body);
+        Ok(match &self.meter {
+            Some(meter) => stream.meter(Metering {
+                meter: meter.clone(),
+                provider: self.provider,
+                model: request.model.clone(),
+                route: request.route.clone(),
+                usage: Usage::default(),
+            }),
+            None => stream,
+        })
+    }
+
+    fn headers(&self) -> Vec<(String, String)> {
+        let mut headers = vec![
+            ("Content-Type".to_owned(), "application/json".to_owned()),
+            ("Accept".to_owned(), "text/event-stream".to_owned()),
+        ];
+        match (self.provider, &self.api_key) {
+            (Provider::OpenAi, Some(key)) => {
+                headers.push(("Authorization".to_owned(), format!("Bearer {key}")));
+            }
+            (Provider::Anthropic, key) => {
+                headers.push(("anthropic-version".to_owned(), "2023-06-01".to_owned()));
+                if let Some(key) = key {
+                    headers.push(("x-api-key".to_owned(), key.clone()));
+                }
+            }
+            (Provider::OpenAi, None) => {}
+        }
+        headers
+    }
+}
+
+impl ChatProvider for LlmClient {
+    fn stream_chat<'a>(&'a self, request: &'a ChatRequest) -> TransportFuture<'a, ChatStream> {
+        Box::pin(LlmClient::stream_chat(self, request))
+    }
+}
+
+// A `Status` error carrying the start of a non-2xx response's body.
+pub(super) async fn rejection(mut response: StreamingResponse) -> LlmError {
+    let mut error = Vec::new();
+    while error.len() < MAX_ERROR_BODY {
+        match response.body.chunk().await {
+            Ok(Some(chunk)) => error.extend(chunk),
+            _ => break,
+        }
+    }
+    error.truncate(MAX_ERROR_BODY);
+    LlmError::Status {
+        status: response.status,
+        body: String::from_utf8_lossy(&error).into_owned(),
+    }
+}
+
+fn openai_body(request: &ChatRequest) -> Value {
+    let messages: Vec<Value> = request.messages.iter().map(openai_message).collect();
+    let mut body = json!({
+        "model": request.model,
+        "messages": messages,
+        "stream": true,
+        "stream_options": { "include_usage": true },
+    });
+    if !request.tools.is_empty() {
+        let tools: Vec<Value> = request
+            .tools
+            .iter()
+            .map(|tool| json!({ "type": "function", "function": tool }))
+            .collect();
+        body["tools"] = json!(tools);
+    }
+    if let Some(max_tokens) = request.max_tokens {
+        body["max_tokens"] = json!(max_tokens);
+    }
+    if let Some(temperature) = request.temperature {
+        body["temperature"] = json!(temperature);
+    }
+    body
+}
+
+fn openai_message(message: &ChatMessage) -> Value {
+    let mut value = json!({ "role": message.role, "content": message.content });
+    if !message.tool_calls.is_empty() {
+        let calls: Vec<Value> = message
+            .tool_calls
+            .iter()
+            .map(|call| {
+                json!({
+                    "id": call.id,
+                    "type": "function",
+                    "function": { "name": call.name, "arguments": call.arguments },
+                })
+            })
+            .collect();
+        value["tool_calls"] = json!(calls);
+    }
+    if let Some(id) = &message.tool_call_id {
+        value["tool_call_id"] = json!(id);
+    }
+    value
+}
+
+// Anthropic takes system prompts as a top-level field rather than as messages.
+fn anthropic_body(request: &ChatRequest) -> Value {
+    let (system, messages): (Vec<_>, Vec<_>) = request
+        .messages
+        .iter()
+        .partition(|message| message.role == Role::System);
+    let mut body = json!({
+        "model": request.model,
+        "messages": anthropic_messages(&messages),
+        "max_tokens": request.max_tokens.unwrap_or(DEFAULT_ANTHROPIC_MAX_TOKENS),
+        "stream": true,
+    });
+    if !system.is_empty() {
+        let prompt: Vec<&str> = system.iter().map(|m| m.content.as_str()).collect();
+        body["system"] = json!(prompt.join("\n\n"));
+    }
+    if let Some(temperature) = request.temperature {
+        body["temperature"] = json!(temperature);
+    }
+    if !request.tools.is_empty() {
+        let tools: Vec<Value> = request
+            .tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "input_schema": tool.parameters,
+                })
+            })
+            .collect();
+        body["tools"] = json!(tools);
+    }
+    body
+}
+
+// Tool calls become `tool_use` content blocks, and tool results `tool_result` blocks in
+// a user message, one message for each run of results.
+fn anthropic_messages(messages: &[&ChatMessage]) -> Vec<Value> {
+    let mut encoded: Vec<Value> = Vec::with_capacity(messages.len());
+    let mut in_results = false;
+    for message in messages {
+        if message.role == Role::Tool {
+            let result = json!({
+                "type": "tool_result",
+                "tool_use_id": message.tool_call_id,
+                "content": message.content,
+            });
+            match encoded.last_mut() {
+                Some(last) if in_results => {
+                    if let Some(blocks) = last["content"].as_array_mut() {
+                        blocks.push(result);
+                    }
+                }
+                _ => encoded.push(json!({ "role": "user", "content": [result] })),
+            }
+            in_results = true;
+            continue;
+        }
+        in_results = false;
+        if message.tool_calls.is_empty() {
+            encoded.push(json!({ "role": message.role, "content": message.content }));
+            continue;
+        }
+        let mut blocks = Vec::new();
+        if !message.content.is_empty() {
+            blocks.push(json!({ "type": "text", "text": message.content }));
+        }
+        for call in &message.tool_calls {
+            let input: Value = serde_json::from_str(&call.arguments).unwrap_or_else(|_| json!({}));
+            blocks.push(json!({
+                "type": "tool_use",
+                "id": call.id,
+                "name": call.name,
+                "input": input,
+            }));
+        }
+        encoded.push(json!({ "role": "assistant", "content": blocks }));
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::llm::{
+        Delta, Tool, ToolCall,
+        transport::{StreamingResponse, TransportFuture},
+    };
+
+    // The URL, headers, and JSON body of a request.
+    type Seen = (String, Vec<(String, String)>, Value);
+
+    // Records the request and answers with a canned status and body.
+    struct Canned {
+        status: u16,
+        body: &'static str,
+        seen: Mutex<Option<Seen>>,
+    }
+
+    impl Transport for Canned {
+        fn post<'a>(
+            &'a self,
+            url: &'a str,
+            headers: &'a [(String, String)],
+            body: &'a [u8],
+        ) -> TransportFuture<'a, StreamingResponse> {
+            *self.seen.lock().unwrap() = Some((
+                url.to_owned(),
+                headers.to_vec(),
+                serde_json::from_slice(body).unwrap(),
+            ));
+            let body = vec![self.body.as_bytes().to_vec()];
+            Box::pin(async move {
+                Ok(StreamingResponse {
+                    status: self.status,
+                    body: Box::new(body.into_iter()),
+                })
+            })
+        }
+    }
+
+    fn canned(status: u16, body: &'static str) -> Arc<Canned> {
+        Arc::new(Canned {
+            status,
+            body,
+            seen: Mutex::default(),
+        })
+    }
+
+    fn request() -> ChatRequest {
+        ChatRequest::new("model-x")
+            .message(ChatMessage::system("Be brief."))
+            .message(ChatMessage::user("Hi"))
+    }
+
+    #[tokio::test]
+    async fn streams_openai_completions() {
+        let transport = canned(
+            200,
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Yo\"}}]}\n\ndata: [DONE]\n\n",
+        );
+        let client = LlmClient::openai("http://llm.internal/v1/")
+            .api_key("sk-test")
+            .transport(transport.clone());
+        let mut stream = client.stream_chat(&request()).await.unwrap();
+        assert_eq!(
+            stream.next().await.unwrap().unwrap(),
+            Delta::Text("Yo".into())
+        );
+
+        let (url, headers, body) = transport.seen.lock().unwrap().take().unwrap();
+        assert_eq!(url, "http://llm.internal/v1/chat/completions");
+        assert!(headers.contains(&("Authorization".into(), "Bearer sk-test".into())));
+        assert_eq!(body["stream"], true);
+        assert_eq!(
+            body["messages"][0],
+            json!({"role": "system", "content": "Be brief."})
+        );
+    }
+
+    #[tokio::test]
+    async fn moves_system_prompts_for_anthropic() {
+        let transport = canned(200, "event: message_stop\ndata: {}\n\n");
+        let client = LlmClient::anthropic("http://llm.internal")
+            .api_key("key")
+            .transport(transport.clone());
+        let text = client.stream_chat(&request()).await.unwrap().text().await;
+        assert_eq!(text.unwrap(), "");
+
+        let (url, headers, body) = transport.seen.lock().unwrap().take().unwrap();
+        assert_eq!(url, "http://llm.internal/v1/messages");
+        assert!(headers.contains(&("x-api-key".into(), "key".into())));
+        assert_eq!(body["system"], "Be brief.");
+        assert_eq!(body["messages"], json!([{"role": "user", "content": "Hi"}]));
+        assert_eq!(body["max_tokens"], 1024);
+    }
+
+    #[test]
+    fn encodes_tool_turns_for_each_provider() {
+        let call = ToolCall {
+            id: "call_1".into(),
+            name: "weather".into(),
+            arguments: "{\"city\":\"Oslo\"}".into(),
+        };
+        let request = request()
+            .tool(Tool::new(
+                "weather",
+                "Current weather.",
+                json!({"type": "object"}),
+            ))
+            .message(ChatMessage::assistant("").with_tool_calls([call]))
+            .message(ChatMessage::tool("call_1", "4°C"));
+
+        let openai = openai_body(&request);
+        assert_eq!(openai["tools"][0]["function"]["name"], "weather");
+        assert_eq!(
+            openai["messages"][2]["tool_calls"][0]["function"]["arguments"],
+            "{\"city\":\"Oslo\"}"
+        );
+        assert_eq!(
+            openai["messages"][3],
+            json!({"role": "tool", "content": "4°C", "tool_call_id": "call_1"})
+        );
+
+        let anthropic = anthropic_body(&request);
+        assert_eq!(
+            anthropic["tools"][0]["input_schema"],
+            json!({"type": "object"})
+        );
+        assert_eq!(
+            anthropic["messages"][1]["content"],
+            json!([{"type": "tool_use", "id": "call_1", "name": "weather", "input": {"city": "Oslo"}}])
+        );
+        assert_eq!(
+            anthropic["messages"][2],
+            json!({"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "call_1", "content": "4°C"}
+            ]})
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_rejections_with_the_body() {
+        let client = LlmClient::openai("http://llm.internal/v1")
+            .transport(canned(401, "{\"error\":{\"message\":\"bad key\"}}"));
+        match client.stream_chat(&request()).await {
+            Err(LlmError::Status { status, body }) => {
+                assert_eq!(status, 401);
+                assert!(body.contains("bad key"));
+            }
+            other => panic!("expected a status error, got {:?}", other.err()),
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! Fitting a conversation into a model's context window.
+
+use super::{ChatMessage, ChatRequest, LlmClient, LlmError, Role, tokens::TokenCounter};
+
+// Room left for the completion when neither the window nor the request says.
+const DEFAULT_RESERVE: usize = 1024;
+
+// The longest summary requested when summarizing dropped history; a quarter of the
+// prompt budget when that is smaller.
+const SUMMARY_TOKENS: usize = 512;
+
+const SUMMARY_PROMPT: &str = "Summarize the following conversation in a few sentences. \
+    Keep names, facts, and decisions that later messages may depend on.";
+
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:\n";
+
+/// What [`ContextWindow::fit`] does with history that no longer fits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Drop the oldest messages.
+    #[default]
+    Truncate,
+    /// Replace the oldest messages with a model-written summary, costing one extra
+    /// completion whenever the conversation overflows.
+    Summarize,
+}
+
+/// Keeps requests within a model's context window.
+///
+/// System messages are always kept, as is the newest message; the rest of the history
+/// is kept newest first for as long as it fits, leaving room for the completion. Attach
+/// a window to a client with [`LlmClient::context_window`] to fit every request before
+/// it is sent.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::llm::{ChatMessage, ChatRequest, ContextWindow};
+///
+/// let window = ContextWindow::new(16).reserve(0);
+/// let request = ChatRequest::new("any-model")
+///     .message(ChatMessage::user("an early question that no longer fits"))
+///     .message(ChatMessage::user("the latest question"));
+/// let fitted = window.truncate(&request);
+/// assert_eq!(fitted.conversation().len(), 1);
+/// assert!(window.fits(&fitted));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ContextWindow {
+    limit: usize,
+    reserve: usize,
+    overflow: Overflow,
+    counter: TokenCounter,
+}
+
+impl ContextWindow {
+    /// Creates a window of `limit` tokens that truncates, reserves 1024 tokens for the
+    /// completion, and estimates token counts.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            reserve: DEFAULT_RESERVE,
+            overflow: Overflow::Truncate,
+            counter: TokenCounter::new(),
+        }
+    }
+
+    /// Sets the tokens left free for the completion. A request's own `max_tokens` takes
+    /// precedence when it is larger.
+    #[must_use]
+    pub fn reserve(mut self, tokens: usize) -> Self {
+        self.reserve = tokens;
+        self
+    }
+
+    /// Sets what happens to history that does not fit.
+    #[must_use]
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Counts tokens with `counter` instead of estimating.
+    #[must_use]
+    pub fn counter(mut self, counter: TokenCounter) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    /// Returns the window size in tokens.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Returns `true` if `request` leaves enough of the window for its completion.
+    pub fn fits(&self, request: &ChatRequest) -> bool {
+        self.cost(request, &request.messages) <= self.budget(request)
+    }
+
+    /// Drops the oldest messages that do not fit.
+    pub fn truncate(&self, request: &ChatRequest) -> ChatRequest {
+        let cut = self.cut(request, self.budget(request));
+        self.rebuild(request, cut, None)
+    }
+
+    /// Fits `request` into the window, summarizing with `client` if the window is set to
+    /// [`Overflow::Summarize`].
+    ///
+    /// Requests that already fit are returned unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from the summary completion.
+    pub async fn fit(
+        &self,
+        client: &LlmClient,
+        request: &ChatRequest,
+    ) -> Result<ChatRequest, LlmError> {
+        if self.fits(request) || self.overflow == Overflow::Truncate {
+            return Ok(self.truncate(request));
+        }
+
+        // Leave room for the summary message itself.
+        let budget = self.budget(request);
+        let summary_tokens = SUMMARY_TOKENS.min(budget / 4);
+        let summary_cost = self
+            .counter
+            .count_message(&request.model, &ChatMessage::system(SUMMARY_PREFIX))
+            + summary_tokens;
+        let budget = budget.saturating_sub(summary_cost);
+        let cut = self.cut(request, budget);
+        let dropped: Vec<&ChatMessage> = request.messages[..cut]
+            .iter()
+            .filter(|m| m.role != Role::System)
+            .collect();
+        if dropped.is_empty() {
+            return Ok(self.rebuild(request, cut, None));
+        }
+
+        let transcript: Vec<String> = dropped
+            .iter()
+            .map(|m| format!("{}: {}", role_name(m.role), m.content))
+            .collect();
+        let summarize = ChatRequest::new(&request.model)
+            .message(ChatMessage::system(SUMMARY_PROMPT))
+            .message(ChatMessage::user(transcript.join("\n\n")))
+            .max_tokens(u32::try_from(summary_tokens).unwrap_or(u32::MAX));
+        let summary = client.send(&summarize).await?.text().await?;
+        let summary = ChatMessage::system(format!("{SUMMARY_PREFIX}{}", summary.trim()));
+        Ok(self.rebuild(request, cut, Some(summary)))
+    }
+
+    // Tokens available to the prompt.
+    fn budget(&self, request: &ChatRequest) -> usize {
+        let completion = request.max_tokens.map_or(0, |n| n as usize);
+        self.limit.saturating_sub(self.reserve.max(completion))
+    }
+
+    fn cost<'a>(
+        &self,
+        request: &ChatRequest,
+        messages: impl IntoIterator<Item = &'a ChatMessage>,
+    ) -> usize {
+        let messages: Vec<ChatMessage> = messages.into_iter().cloned().collect();
+        self.counter.count_messages(&request.model, &messages)
+    }
+
+    // The index of the oldest non-system message kept within `budget`. The newest message
+    // is kept regardless.
+    fn cut(&self, request: &ChatRequest, budget: usize) -> usize {
+        let messages = &request.messages;
+        let mut used = self.cost(request, messages.iter().filter(|m| m.role == Role::System));
+        let mut cut = messages.len();
+        for (i, message) in messages.iter().enumerate().rev() {
+            if message.role == Role::System {
+                continue;
+            }
+            used += self.counter.count_message(&request.model, message);
+            if used > budget && cut < messages.len() {
+                break;
+            }
+            cut = i;
+        }
+        cut
+    }
+
+    // Keeps system messages and those from `cut` on, inserting `summary` where the
+    // dropped history was.
+    fn rebuild(
+        &self,
+        request: &ChatRequest,
+        cut: usize,
+        summary: Option<ChatMessage>,
+    ) -> ChatRequest {
+        let mut summary = summary;
+        let mut messages = Vec::with_capacity(request.messages.len());
+        for (i, message) in request.messages.iter().enumerate() {
+            if i < cut && message.role != Role::System {
+                continue;
+            }
+            if message.role != Role::System {
+                messages.extend(summary.take());
+            }
+            messages.push(message.clone());
+        }
+        messages.extend(summary);
+        ChatRequest {
+            messages,
+            ..request.clone()
+        }
+    }
+}
+
+fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::System => "System",
+        Role::User => "User",
+        Role::Assistant => "Assistant",
+        Role::Tool => "Tool",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::llm::{
+        Tokenizer,
+        transport::{StreamingResponse, Transport, TransportFuture},
+    };
+
+    struct Words;
+
+    impl Tokenizer for Words {
+        fn count(&self, text: &str) -> usize {
+            text.split_whitespace().count()
+        }
+    }
+
+    // Answers every request with the same completion and records the bodies.
+    #[derive(Default)]
+    struct Recorder {
+        bodies: Mutex<Vec<Value>>,
+    }
+
+    impl Transport for Recorder {
+        fn post<'a>(
+            &'a self,
+            _url: &'a str,
+            _headers: &'a [(String, String)],
+            body: &'a [u8],
+        ) -> TransportFuture<'a, StreamingResponse> {
+            self.bodies
+                .lock()
+                .unwrap()
+                .push(serde_json::from_slice(body).unwrap());
+            let body = b"data: {\"choices\":[{\"delta\":{\"content\":\"They said hello.\"}}]}\n\n\
+                data: [DONE]\n\n"
+                .to_vec();
+            Box::pin(async move {
+                Ok(StreamingResponse {
+                    status: 200,
+                    body: Box::new(vec![body].into_iter()),
+                })
+            })
+        }
+    }
+
+    fn window(limit: usize) -> ContextWindow {
+        ContextWindow::new(limit)
+            .reserve(0)
+            .counter(TokenCounter::new().with("", Arc::new(Words)))
+    }
+
+    // 3 tokens of reply overhead, then 6 + 7 + 6 + 5 for the messages.
+    fn request() -> ChatRequest {
+        ChatRequest::new("model-x")
+            .message(ChatMessage::system("be brief"))
+            .message(ChatMessage::user("one two three"))
+            .message(ChatMessage::assistant("four five"))
+            .message(ChatMessage::user("six"))
+    }
+
+    fn contents(request: &ChatRequest) -> Vec<&str> {
+        request
+            .conversation()
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn truncates_the_oldest_history() {
+        assert_eq!(window(27).truncate(&request()), request());
+        assert_eq!(
+            contents(&window(20).truncate(&request())),
+            ["be brief", "four five", "six"]
+        );
+        // A completion reserve comes out of the window.
+        assert_eq!(
+            contents(&window(30).truncate(&request().max_tokens(10))),
+            ["be brief", "four five", "six"]
+        );
+        // The newest message stays even when nothing fits.
+        let fitted = window(5).truncate(&request());
+        assert_eq!(contents(&fitted), ["be brief", "six"]);
+        assert!(!window(5).fits(&fitted));
+    }
+
+    #[tokio::test]
+    async fn summarizes_dropped_history_before_sending() {
+        let transport = Arc::new(Recorder::default());
+        let client = LlmClient::openai("http://llm.internal/v1")
+            .transport(transport.clone())
+            .context_window(window(24).overflow(Overflow::Summarize));
+        let text = client.stream_chat(&request()).await.unwrap().text().await;
+        assert_eq!(text.unwrap(), "They said hello.");
+
+        let bodies = transport.bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 2);
+        // A quarter of the 24-token budget.
+        assert_eq!(bodies[0]["max_tokens"], 6);
+        assert_eq!(
+            bodies[0]["messages"][1]["content"],
+            "User: one two three\n\nAssistant: four five"
+        );
+        let sent: Vec<&str> = bodies[1]["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            sent,
+            [
+                "be brief",
+                "Summary of the earlier conversation:\nThey said hello.",
+                "six"
+            ]
+        );
+    }
+}
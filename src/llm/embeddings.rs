@@ -0,0 +1,409 @@
+//! Embeddings and in-memory vector search.
+//!
+//! [`EmbeddingClient`] turns text into vectors through an OpenAI-compatible
+//! `/embeddings` endpoint — OpenAI, Voyage, Ollama, vLLM, and most gateways speak it.
+//! [`VectorIndex`] keeps vectors in memory and finds the nearest by cosine similarity,
+//! which is enough for retrieval over a few hundred thousand passages without a separate
+//! vector database.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rttp::llm::embeddings::{EmbeddingClient, VectorIndex};
+//!
+//! # async fn run() -> Result<(), rttp::llm::LlmError> {
+//! let embedder = EmbeddingClient::openai("http://127.0.0.1:11434/v1", "nomic-embed-text");
+//! let passages = ["Refunds take 5 days.", "Shipping is free over $50."];
+//!
+//! let mut index = VectorIndex::new();
+//! for (passage, vector) in passages.iter().zip(embedder.embed(&passages).await?) {
+//!     index.insert(*passage, vector, passage.to_string())?;
+//! }
+//!
+//! let query = embedder.embed_one("how long do refunds take?").await?;
+//! for hit in index.search(&query, 1) {
+//!     println!("{:.2} {}", hit.score, hit.value);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{collections::HashMap, sync::Arc};
+
+use serde_json::{Value, json};
+
+use super::{
+    LlmError,
+    client::rejection,
+    transport::{HttpTransport, Transport, TransportFuture},
+};
+
+// The largest embeddings response read.
+const MAX_RESPONSE: usize = 64 * 1024 * 1024;
+
+/// Turns text into embedding vectors.
+pub trait Embedder: Send + Sync {
+    /// Embeds each of `texts`, returning one vector per text, in order.
+    fn embed_texts<'a>(&'a self, texts: &'a [String]) -> TransportFuture<'a, Vec<Vec<f32>>>;
+}
+
+/// A client for an OpenAI-compatible embeddings endpoint.
+#[derive(Clone)]
+pub struct EmbeddingClient {
+    url: String,
+    model: String,
+    api_key: Option<String>,
+    dimensions: Option<usize>,
+    transport: Arc<dyn Transport>,
+}
+
+impl EmbeddingClient {
+    /// Creates a client for `model` on the API rooted at `base_url`; requests go to
+    /// `{base_url}/embeddings`.
+    pub fn openai(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            url: format!("{}/embeddings", base_url.into().trim_end_matches('/')),
+            model: model.into(),
+            api_key: None,
+            dimensions: None,
+            transport: Arc::new(HttpTransport::new()),
+        }
+    }
+
+    /// Sets the API key sent with every request.
+    #[must_use]
+    pub fn api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    /// Asks for vectors shortened to `dimensions`, for models that support it.
+    #[must_use]
+    pub fn dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    /// Replaces the default [`HttpTransport`].
+    #[must_use]
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Embeds each of `texts` in one request.
+    ///
+    /// # Errors
+    ///
+    /// [`LlmError::Status`] if the provider rejects the request, [`LlmError::Malformed`]
+    /// if the response is not one vector per text, or the transport's error.
+    pub async fn embed<S: AsRef<str>>(&self, texts: &[S]) -> Result<Vec<Vec<f32>>, LlmError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let input: Vec<&str> = texts.iter().map(AsRef::as_ref).collect();
+        let mut body = json!({ "model": self.model, "input": input });
+        if let Some(dimensions) = self.dimensions {
+            body["dimensions"] = json!(dimensions);
+        }
+        let mut headers = vec![("Content-Type".to_owned(), "application/json".to_owned())];
+        if let Some(key) = &self.api_key {
+            headers.push(("Authorization".to_owned(), format!("Bearer {key}")));
+        }
+        let body = serde_json::to_vec(&body)?;
+        let mut response = self.transport.post(&self.url, &headers, &body).await?;
+        if !(200..300).contains(&response.status) {
+            return Err(rejection(response).await);
+        }
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.body.chunk().await? {
+            bytes.extend(chunk);
+            if bytes.len() > MAX_RESPONSE {
+                return Err(LlmError::Malformed("embeddings response too large".into()));
+            }
+        }
+        let response: Value = serde_json::from_slice(&bytes)?;
+        let malformed = || LlmError::Malformed("embeddings response has no data".into());
+        let mut vectors: Vec<(usize, Vec<f32>)> = Vec::with_capacity(texts.len());
+        for (position, item) in response["data"]
+            .as_array()
+            .ok_or_else(malformed)?
+            .iter()
+            .enumerate()
+        {
+            let index = item["index"].as_u64().map_or(position, |i| i as usize);
+            let vector = item["embedding"]
+                .as_array()
+                .ok_or_else(malformed)?
+                .iter()
+                .map(|x| x.as_f64().map(|x| x as f32).ok_or_else(malformed))
+                .collect::<Result<_, _>>()?;
+            vectors.push((index, vector));
+        }
+        if vectors.len() != texts.len() {
+            return Err(LlmError::Malformed(format!(
+                "{} embeddings for {} texts",
+                vectors.len(),
+                texts.len()
+            )));
+        }
+        vectors.sort_by_key(|(index, _)| *index);
+        Ok(vectors.into_iter().map(|(_, vector)| vector).collect())
+    }
+
+    /// Embeds a single text.
+    ///
+    /// # Errors
+    ///
+    /// As for [`embed`](Self::embed).
+    pub async fn embed_one(&self, text: &str) -> Result<Vec<f32>, LlmError> {
+        let mut vectors = self.embed(&[text]).await?;
+        Ok(vectors.remove(0))
+    }
+}
+
+impl Embedder for EmbeddingClient {
+    fn embed_texts<'a>(&'a self, texts: &'a [String]) -> TransportFuture<'a, Vec<Vec<f32>>> {
+        Box::pin(self.embed(texts))
+    }
+}
+
+/// A nearest-neighbour result from [`VectorIndex::search`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Match<'a, T> {
+    pub id: &'a str,
+    /// Cosine similarity with the query, from -1 to 1.
+    pub score: f32,
+    pub value: &'a T,
+}
+
+/// Vectors in memory, searched exhaustively by cosine similarity.
+///
+/// Every vector must have the same number of dimensions; the first one inserted sets it.
+/// Vectors are normalized on insert, so searching costs one dot product per entry.
+#[derive(Debug, Clone)]
+pub struct VectorIndex<T> {
+    dimensions: Option<usize>,
+    ids: Vec<String>,
+    vectors: Vec<Vec<f32>>,
+    values: Vec<T>,
+    positions: HashMap<String, usize>,
+}
+
+impl<T> Default for VectorIndex<T> {
+    fn default() -> Self {
+        Self {
+            dimensions: None,
+            ids: Vec::new(),
+            vectors: Vec::new(),
+            values: Vec::new(),
+            positions: HashMap::new(),
+        }
+    }
+}
+
+impl<T> VectorIndex<T> {
+    /// Creates an empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `vector` with `value` under `id`, replacing any entry with that ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LlmError::Dimensions`] if the vector's length differs from the index's.
+    pub fn insert(
+        &mut self,
+        id: impl Into<String>,
+        vector: Vec<f32>,
+        value: T,
+    ) -> Result<(), LlmError> {
+        self.check(&vector)?;
+        self.dimensions = Some(vector.len());
+        let id = id.into();
+        let vector = normalize(vector);
+        match self.positions.get(&id) {
+            Some(&at) => {
+                self.vectors[at] = vector;
+                self.values[at] = value;
+            }
+            None => {
+                self.positions.insert(id.clone(), self.ids.len());
+                self.ids.push(id);
+                self.vectors.push(vector);
+                self.values.push(value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the entry under `id`, returning its value.
+    pub fn remove(&mut self, id: &str) -> Option<T> {
+        let at = self.positions.remove(id)?;
+        self.ids.swap_remove(at);
+        self.vectors.swap_remove(at);
+        let value = self.values.swap_remove(at);
+        if let Some(moved) = self.ids.get(at) {
+            self.positions.insert(moved.clone(), at);
+        }
+        Some(value)
+    }
+
+    /// Returns the value under `id`.
+    pub fn get(&self, id: &str) -> Option<&T> {
+        self.positions.get(id).map(|&at| &self.values[at])
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns `true` if the index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Returns the `k` entries most similar to `query`, best first.
+    ///
+    /// A query with the wrong number of dimensions matches nothing.
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<Match<'_, T>> {
+        if self.check(query).is_err() || k == 0 {
+            return Vec::new();
+        }
+        let query = normalize(query.to_vec());
+        let mut scored: Vec<(f32, usize)> = self
+            .vectors
+            .iter()
+            .enumerate()
+            .map(|(at, vector)| (dot(&query, vector), at))
+            .collect();
+        let by_score = |a: &(f32, usize), b: &(f32, usize)| b.0.total_cmp(&a.0);
+        if scored.len() > k {
+            scored.select_nth_unstable_by(k - 1, by_score);
+            scored.truncate(k);
+        }
+        scored.sort_by(by_score);
+        scored
+            .into_iter()
+            .map(|(score, at)| Match {
+                id: &self.ids[at],
+                score,
+                value: &self.values[at],
+            })
+            .collect()
+    }
+
+    fn check(&self, vector: &[f32]) -> Result<(), LlmError> {
+        match self.dimensions {
+            Some(expected) if expected != vector.len() => Err(LlmError::Dimensions {
+                expected,
+                found: vector.len(),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns the cosine similarity of `a` and `b`, or 0 if either is all zeros.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    dot(&normalize(a.to_vec()), &normalize(b.to_vec()))
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = dot(&vector, &vector).sqrt();
+    if norm > 0.0 {
+        for x in &mut vector {
+            *x /= norm;
+        }
+    }
+    vector
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::llm::transport::StreamingResponse;
+
+    struct Canned {
+        seen: Mutex<Option<Value>>,
+    }
+
+    impl Transport for Canned {
+        fn post<'a>(
+            &'a self,
+            url: &'a str,
+            _headers: &'a [(String, String)],
+            body: &'a [u8],
+        ) -> TransportFuture<'a, StreamingResponse> {
+            assert_eq!(url, "http://llm.internal/v1/embeddings");
+            *self.seen.lock().unwrap() = Some(serde_json::from_slice(body).unwrap());
+            // Out of order, as the API allows.
+            let body =
+                br#"{"data":[{"index":1,"embedding":[0,1]},{"index":0,"embedding":[1,0.5]}]}"#;
+            Box::pin(async move {
+                Ok(StreamingResponse {
+                    status: 200,
+                    body: Box::new(vec![body.to_vec()].into_iter()),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn embeds_in_input_order() {
+        let transport = Arc::new(Canned {
+            seen: Mutex::default(),
+        });
+        let client = EmbeddingClient::openai("http://llm.internal/v1/", "embed-small")
+            .dimensions(2)
+            .transport(transport.clone());
+        let vectors = client.embed(&["first", "second"]).await.unwrap();
+        assert_eq!(vectors, [vec![1.0, 0.5], vec![0.0, 1.0]]);
+
+        let seen = transport.seen.lock().unwrap().take().unwrap();
+        assert_eq!(
+            seen,
+            json!({"model": "embed-small", "input": ["first", "second"], "dimensions": 2})
+        );
+        assert!(matches!(
+            client.embed(&["just one"]).await,
+            Err(LlmError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn finds_the_nearest_vectors() {
+        let mut index = VectorIndex::new();
+        index.insert("east", vec![1.0, 0.0], "E").unwrap();
+        index.insert("north", vec![0.0, 2.0], "N").unwrap();
+        index.insert("northeast", vec![3.0, 3.0], "NE").unwrap();
+        index.insert("west", vec![-1.0, 0.0], "W").unwrap();
+
+        let hits = index.search(&[0.9, 0.1], 2);
+        let ids: Vec<&str> = hits.iter().map(|hit| hit.id).collect();
+        assert_eq!(ids, ["east", "northeast"]);
+        assert!((hits[0].score - cosine_similarity(&[0.9, 0.1], &[1.0, 0.0])).abs() < 1e-6);
+        assert_eq!(index.search(&[0.9, 0.1], 10).last().unwrap().id, "west");
+
+        assert_eq!(index.remove("east"), Some("E"));
+        assert_eq!(index.get("west"), Some(&"W"));
+        assert_eq!(index.search(&[1.0, 0.0], 1)[0].id, "northeast");
+        assert!(matches!(
+            index.insert("bad", vec![1.0, 2.0, 3.0], "?"),
+            Err(LlmError::Dimensions {
+                expected: 2,
+                found: 3
+            })
+        ));
+        assert!(index.search(&[1.0], 1).is_empty());
+    }
+}
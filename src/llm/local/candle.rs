@@ -0,0 +1,388 @@
+//! An [`Engine`] running quantized GGUF models on candle's CPU kernels.
+
+use std::{
+    fs::File,
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+
+use candle_core::{Device, Tensor, quantized::gguf_file};
+use candle_transformers::{
+    generation::LogitsProcessor,
+    models::{quantized_llama, quantized_qwen2},
+};
+use tokenizers::Tokenizer;
+
+use super::{Engine, Generation};
+use crate::llm::{LlmError, Usage};
+
+/// Runs a quantized GGUF model in-process on the CPU, with [candle].
+///
+/// Loads Llama-architecture models (Llama 2 and 3, Mistral, and their fine-tunes) and
+/// Qwen2 models, tokenizing with the model's Hugging Face `tokenizer.json`. The weights
+/// are loaded once; completions take turns on them.
+///
+/// [candle]: https://github.com/huggingface/candle
+pub struct CandleEngine {
+    state: Mutex<State>,
+    tokenizer: Tokenizer,
+    eos: Option<u32>,
+    seed: u64,
+    device: Device,
+}
+
+struct State {
+    weights: Weights,
+    completions: u64,
+}
+
+enum Weights {
+    Llama(quantized_llama::ModelWeights),
+    Qwen2(quantized_qwen2::ModelWeights),
+}
+
+impl Weights {
+    fn forward(&mut self, input: &Tensor, position: usize) -> candle_core::Result<Tensor> {
+        match self {
+            Self::Llama(weights) => weights.forward(input, position),
+            Self::Qwen2(weights) => weights.forward(input, position),
+        }
+    }
+}
+
+impl CandleEngine {
+    /// Loads the GGUF model at `model` and the tokenizer at `tokenizer`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LlmError::Io`] if the model cannot be opened, [`LlmError::Malformed`] if
+    /// it is not a GGUF file, or [`LlmError::Provider`] if its architecture is not
+    /// supported or the weights or tokenizer fail to load.
+    pub fn load(model: impl AsRef<Path>, tokenizer: impl AsRef<Path>) -> Result<Self, LlmError> {
+        let device = Device::Cpu;
+        let mut file = File::open(model)?;
+        let content =
+            gguf_file::Content::read(&mut file).map_err(|e| LlmError::Malformed(e.to_string()))?;
+        let architecture = content
+            .metadata
+            .get("general.architecture")
+            .and_then(|value| value.to_string().ok())
+            .cloned()
+            .unwrap_or_default();
+        let eos = content
+            .metadata
+            .get("tokenizer.ggml.eos_token_id")
+            .and_then(|value| value.to_u32().ok());
+        let weights = match architecture.as_str() {
+            "llama" => Weights::Llama(
+                quantized_llama::ModelWeights::from_gguf(content, &mut file, &device)
+                    .map_err(runtime)?,
+            ),
+            "qwen2" => Weights::Qwen2(
+                quantized_qwen2::ModelWeights::from_gguf(content, &mut file, &device)
+                    .map_err(runtime)?,
+            ),
+            other => {
+                return Err(LlmError::Provider(format!(
+                    "unsupported model architecture {other:?}"
+                )));
+            }
+        };
+        let tokenizer = Tokenizer::from_file(tokenizer)
+            .map_err(|e| LlmError::Provider(format!("failed to load tokenizer: {e}")))?;
+        Ok(Self {
+            state: Mutex::new(State {
+                weights,
+                completions: 0,
+            }),
+            tokenizer,
+            eos,
+            seed: 299_792_458,
+            device,
+        })
+    }
+
+    /// Sets the seed sampling starts from, for reproducible completions.
+    #[must_use]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    fn encode(&self, text: &str) -> Result<Vec<u32>, LlmError> {
+        self.tokenizer
+            .encode(text, false)
+            .map(|encoding| encoding.get_ids().to_vec())
+            .map_err(|e| LlmError::Provider(format!("failed to tokenize: {e}")))
+    }
+
+    fn decode(&self, tokens: &[u32]) -> Result<String, LlmError> {
+        self.tokenizer
+            .decode(tokens, false)
+            .map_err(|e| LlmError::Provider(format!("failed to detokenize: {e}")))
+    }
+}
+
+impl Engine for CandleEngine {
+    fn count_tokens(&self, text: &str) -> usize {
+        self.encode(text).map_or(0, |tokens| tokens.len())
+    }
+
+    fn generate(
+        &self,
+        prompt: &str,
+        options: &Generation,
+        emit: &mut dyn FnMut(&str) -> bool,
+    ) -> Result<Usage, LlmError> {
+        let prompt_tokens = self.encode(prompt)?;
+        // Stop sequences that are special tokens end generation without being decoded.
+        let stop_tokens: Vec<u32> = options
+            .stop
+            .iter()
+            .filter_map(|stop| self.tokenizer.token_to_id(stop))
+            .chain(self.eos)
+            .collect();
+
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.completions += 1;
+        let temperature = (options.temperature > 0.0).then_some(f64::from(options.temperature));
+        let mut sampler =
+            LogitsProcessor::new(self.seed.wrapping_add(state.completions), temperature, None);
+
+        let mut generated = Vec::new();
+        let mut input = prompt_tokens.clone();
+        let mut position = 0;
+        let mut text = String::new();
+        let mut emitted = 0;
+        // Whether held-back text is still owed to the reader when generation ends.
+        let mut flush = true;
+        while generated.len() < options.max_tokens as usize {
+            let tensor = Tensor::new(input.as_slice(), &self.device)
+                .and_then(|t| t.unsqueeze(0))
+                .map_err(runtime)?;
+            let logits = state
+                .weights
+                .forward(&tensor, position)
+                .and_then(|logits| logits.squeeze(0))
+                .map_err(runtime)?;
+            position += input.len();
+            let token = sampler.sample(&logits).map_err(runtime)?;
+            if stop_tokens.contains(&token) {
+                break;
+            }
+            generated.push(token);
+            input = vec![token];
+
+            text = self.decode(&generated)?;
+            let (ready, stopped) = releasable(&text, &options.stop);
+            if let Some(piece) = text.get(emitted..ready).filter(|piece| !piece.is_empty()) {
+                if !emit(piece) {
+                    flush = false;
+                    break;
+                }
+                emitted = ready;
+            }
+            if stopped {
+                flush = false;
+                break;
+            }
+        }
+        if let Some(rest) = text.get(emitted..).filter(|rest| flush && !rest.is_empty()) {
+            emit(rest);
+        }
+
+        Ok(Usage {
+            input_tokens: Some(prompt_tokens.len() as u64),
+            output_tokens: Some(generated.len() as u64),
+        })
+    }
+}
+
+// Returns how much of the decoded `text` can be passed on, and whether a stop sequence
+// ends it. Holds back a trailing partial character and any tail that may begin a stop.
+fn releasable(text: &str, stops: &[String]) -> (usize, bool) {
+    if let Some(end) = stops
+        .iter()
+        .filter(|stop| !stop.is_empty())
+        .filter_map(|stop| text.find(stop.as_str()))
+        .min()
+    {
+        return (end, true);
+    }
+    let complete = text.trim_end_matches(char::REPLACEMENT_CHARACTER);
+    let held = stops
+        .iter()
+        .filter_map(|stop| {
+            (1..stop.len())
+                .rev()
+                .filter(|&n| stop.is_char_boundary(n))
+                .find(|&n| complete.ends_with(&stop[..n]))
+        })
+        .max()
+        .unwrap_or(0);
+    (complete.len() - held, false)
+}
+
+fn runtime(err: candle_core::Error) -> LlmError {
+    LlmError::Provider(format!("inference failed: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use candle_core::quantized::{GgmlDType, QTensor, gguf_file::Value};
+    use serde_json::json;
+
+    use super::*;
+
+    const WORDS: [&str; 6] = ["[UNK]", "hi", "yes", "no", "maybe", "</s>"];
+
+    fn string(out: &mut Vec<u8>, s: &str) {
+        out.extend((s.len() as u64).to_le_bytes());
+        out.extend(s.as_bytes());
+    }
+
+    fn temp_path(extension: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "rttp-gguf-{}.{extension}",
+            crate::security::crypto::random_token(8)
+        ))
+    }
+
+    // Writes a one-layer Llama with random weights over `WORDS`, and its tokenizer.
+    fn tiny_llama() -> (PathBuf, PathBuf) {
+        let (embedding, feed_forward) = (8, 16);
+        let tensor = |shape: &[usize]| {
+            let weights = Tensor::randn(0f32, 1.0, shape, &Device::Cpu).unwrap();
+            QTensor::quantize(&weights, GgmlDType::F32).unwrap()
+        };
+        let tensors = [
+            ("token_embd.weight", tensor(&[WORDS.len(), embedding])),
+            ("output_norm.weight", tensor(&[embedding])),
+            ("output.weight", tensor(&[WORDS.len(), embedding])),
+            ("blk.0.attn_q.weight", tensor(&[embedding, embedding])),
+            ("blk.0.attn_k.weight", tensor(&[embedding, embedding])),
+            ("blk.0.attn_v.weight", tensor(&[embedding, embedding])),
+            ("blk.0.attn_output.weight", tensor(&[embedding, embedding])),
+            ("blk.0.attn_norm.weight", tensor(&[embedding])),
+            ("blk.0.ffn_gate.weight", tensor(&[feed_forward, embedding])),
+            ("blk.0.ffn_down.weight", tensor(&[embedding, feed_forward])),
+            ("blk.0.ffn_up.weight", tensor(&[feed_forward, embedding])),
+            ("blk.0.ffn_norm.weight", tensor(&[embedding])),
+        ];
+        let metadata = [
+            ("general.architecture", Value::String("llama".into())),
+            ("llama.attention.head_count", Value::U32(2)),
+            ("llama.attention.head_count_kv", Value::U32(2)),
+            ("llama.block_count", Value::U32(1)),
+            ("llama.embedding_length", Value::U32(embedding as u32)),
+            ("llama.rope.dimension_count", Value::U32(4)),
+            ("llama.attention.layer_norm_rms_epsilon", Value::F32(1e-5)),
+        ];
+        let model = temp_path("gguf");
+        let mut file = File::create(&model).unwrap();
+        gguf_file::write(
+            &mut file,
+            &metadata.iter().map(|(k, v)| (*k, v)).collect::<Vec<_>>(),
+            &tensors.iter().map(|(k, t)| (*k, t)).collect::<Vec<_>>(),
+        )
+        .unwrap();
+
+        let vocab: serde_json::Map<_, _> = WORDS
+            .iter()
+            .enumerate()
+            .map(|(id, word)| (word.to_string(), json!(id)))
+            .collect();
+        let tokenizer = temp_path("json");
+        let config = json!({
+            "version": "1.0",
+            "truncation": null,
+            "padding": null,
+            "added_tokens": [],
+            "normalizer": null,
+            "pre_tokenizer": { "type": "Whitespace" },
+            "post_processor": null,
+            "decoder": null,
+            "model": { "type": "WordLevel", "vocab": vocab, "unk_token": "[UNK]" },
+        });
+        fs::write(&tokenizer, config.to_string()).unwrap();
+        (model, tokenizer)
+    }
+
+    #[test]
+    fn generates_with_a_gguf_model() {
+        let (model, tokenizer) = tiny_llama();
+        let engine = CandleEngine::load(&model, &tokenizer).unwrap();
+        assert_eq!(engine.count_tokens("hi yes no"), 3);
+
+        let options = Generation {
+            max_tokens: 4,
+            temperature: 0.0,
+            stop: Vec::new(),
+        };
+        let complete = |engine: &CandleEngine| {
+            let mut text = String::new();
+            let usage = engine
+                .generate("hi yes", &options, &mut |piece| {
+                    text.push_str(piece);
+                    true
+                })
+                .unwrap();
+            (text, usage)
+        };
+        let (text, usage) = complete(&engine);
+        assert_eq!(usage.input_tokens, Some(2));
+        assert_eq!(usage.output_tokens, Some(4));
+        assert_eq!(text.split_whitespace().count(), 4);
+        assert!(text.split_whitespace().all(|word| WORDS.contains(&word)));
+        // Greedy decoding starts over for each completion.
+        assert_eq!(complete(&engine).0, text);
+
+        let mut pieces = 0;
+        let usage = engine
+            .generate("hi", &options, &mut |_| {
+                pieces += 1;
+                false
+            })
+            .unwrap();
+        assert_eq!((pieces, usage.output_tokens), (1, Some(1)));
+
+        fs::remove_file(model).unwrap();
+        fs::remove_file(tokenizer).unwrap();
+    }
+
+    #[test]
+    fn holds_back_stop_sequences() {
+        let stops = ["<|im_end|>".to_owned()];
+        assert_eq!(releasable("Hello", &stops), (5, false));
+        assert_eq!(releasable("Hello<|im", &stops), (5, false));
+        assert_eq!(releasable("Hello<|im_end|> more", &stops), (5, true));
+        assert_eq!(releasable("caf\u{FFFD}", &stops), (3, false));
+        assert_eq!(releasable("a < b", &stops), (5, false));
+    }
+
+    #[test]
+    fn refuses_unsupported_architectures() {
+        let mut file = b"GGUF".to_vec();
+        file.extend(3u32.to_le_bytes());
+        file.extend(0u64.to_le_bytes());
+        file.extend(1u64.to_le_bytes());
+        string(&mut file, "general.architecture");
+        file.extend(8u32.to_le_bytes());
+        string(&mut file, "gpt2");
+        let path = temp_path("gguf");
+        fs::write(&path, file).unwrap();
+
+        let err = CandleEngine::load(&path, "tokenizer.json").err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "LLM provider error: unsupported model architecture \"gpt2\""
+        );
+        assert!(matches!(
+            CandleEngine::load(path.with_extension("missing"), "tokenizer.json"),
+            Err(LlmError::Io(_))
+        ));
+        fs::remove_file(path).unwrap();
+    }
+}
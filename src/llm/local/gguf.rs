@@ -0,0 +1,221 @@
+//! Reading the metadata of GGUF model files.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+use crate::llm::LlmError;
+
+// The longest string value kept; longer ones (none that matter) are an error.
+const MAX_STRING: u64 = 1 << 20;
+
+/// One metadata value. Arrays, such as the tokenizer's vocabulary, are skipped and only
+/// their length kept.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GgufValue {
+    Uint(u64),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+    Array(u64),
+}
+
+/// The key/value metadata at the start of a GGUF file: architecture, context length,
+/// chat template, and the like.
+#[derive(Debug, Clone, Default)]
+pub struct GgufInfo {
+    metadata: BTreeMap<String, GgufValue>,
+}
+
+impl GgufInfo {
+    /// Reads the metadata of the GGUF file at `path`, leaving the tensors unread.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LlmError::Io`] if the file cannot be read, or [`LlmError::Malformed`]
+    /// if it is not GGUF version 2 or later.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, LlmError> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads GGUF metadata from `reader`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`read`](Self::read).
+    pub fn read_from(mut reader: impl Read) -> Result<Self, LlmError> {
+        let r = &mut reader;
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != b"GGUF" {
+            return Err(LlmError::Malformed("not a GGUF file".into()));
+        }
+        let version = u32::from_le_bytes(read_array(r)?);
+        if version < 2 {
+            return Err(LlmError::Malformed(format!(
+                "unsupported GGUF version {version}"
+            )));
+        }
+        let _tensors = read_u64(r)?;
+        let count = read_u64(r)?;
+        let mut metadata = BTreeMap::new();
+        for _ in 0..count {
+            let key = read_string(r)?;
+            let kind = u32::from_le_bytes(read_array(r)?);
+            metadata.insert(key, read_value(r, kind)?);
+        }
+        Ok(Self { metadata })
+    }
+
+    /// Returns the value stored under `key`.
+    pub fn get(&self, key: &str) -> Option<&GgufValue> {
+        self.metadata.get(key)
+    }
+
+    /// Returns the model architecture, such as `llama`.
+    pub fn architecture(&self) -> Option<&str> {
+        self.string("general.architecture")
+    }
+
+    /// Returns the model's name.
+    pub fn name(&self) -> Option<&str> {
+        self.string("general.name")
+    }
+
+    /// Returns the context length the model was trained for.
+    pub fn context_length(&self) -> Option<u64> {
+        let key = format!("{}.context_length", self.architecture()?);
+        match self.get(&key)? {
+            GgufValue::Uint(n) => Some(*n),
+            GgufValue::Int(n) => u64::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the model's Jinja chat template.
+    pub fn chat_template(&self) -> Option<&str> {
+        self.string("tokenizer.chat_template")
+    }
+
+    fn string(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            GgufValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+fn read_array<const N: usize>(r: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    r.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    read_array(r).map(u64::from_le_bytes)
+}
+
+fn read_string(r: &mut impl Read) -> Result<String, LlmError> {
+    let len = read_u64(r)?;
+    if len > MAX_STRING {
+        return Err(LlmError::Malformed(format!("GGUF string of {len} bytes")));
+    }
+    let mut bytes = vec![0; len as usize];
+    r.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| LlmError::Malformed("GGUF string is not UTF-8".into()))
+}
+
+fn read_value(r: &mut impl Read, kind: u32) -> Result<GgufValue, LlmError> {
+    Ok(match kind {
+        0 => GgufValue::Uint(read_array::<1>(r)?[0].into()),
+        1 => GgufValue::Int(i8::from_le_bytes(read_array(r)?).into()),
+        2 => GgufValue::Uint(u16::from_le_bytes(read_array(r)?).into()),
+        3 => GgufValue::Int(i16::from_le_bytes(read_array(r)?).into()),
+        4 => GgufValue::Uint(u32::from_le_bytes(read_array(r)?).into()),
+        5 => GgufValue::Int(i32::from_le_bytes(read_array(r)?).into()),
+        6 => GgufValue::Float(f32::from_le_bytes(read_array(r)?).into()),
+        7 => GgufValue::Bool(read_array::<1>(r)?[0] != 0),
+        8 => GgufValue::String(read_string(r)?),
+        9 => {
+            let kind = u32::from_le_bytes(read_array(r)?);
+            let len = read_u64(r)?;
+            for _ in 0..len {
+                skip_value(r, kind)?;
+            }
+            GgufValue::Array(len)
+        }
+        10 => GgufValue::Uint(read_u64(r)?),
+        11 => GgufValue::Int(i64::from_le_bytes(read_array(r)?)),
+        12 => GgufValue::Float(f64::from_le_bytes(read_array(r)?)),
+        other => {
+            return Err(LlmError::Malformed(format!(
+                "unknown GGUF value type {other}"
+            )));
+        }
+    })
+}
+
+fn skip_value(r: &mut impl Read, kind: u32) -> Result<(), LlmError> {
+    let size = match kind {
+        0 | 1 | 7 => 1,
+        2 | 3 => 2,
+        4..=6 => 4,
+        10..=12 => 8,
+        8 => read_u64(r)?,
+        _ => return read_value(r, kind).map(drop),
+    };
+    let skipped = io::copy(&mut r.take(size), &mut io::sink())?;
+    if skipped < size {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(out: &mut Vec<u8>, s: &str) {
+        out.extend((s.len() as u64).to_le_bytes());
+        out.extend(s.as_bytes());
+    }
+
+    #[test]
+    fn reads_metadata_and_skips_arrays() {
+        let mut file = b"GGUF".to_vec();
+        file.extend(3u32.to_le_bytes());
+        file.extend(0u64.to_le_bytes());
+        file.extend(4u64.to_le_bytes());
+        string(&mut file, "general.architecture");
+        file.extend(8u32.to_le_bytes());
+        string(&mut file, "llama");
+        string(&mut file, "tokenizer.ggml.tokens");
+        file.extend(9u32.to_le_bytes());
+        file.extend(8u32.to_le_bytes());
+        file.extend(2u64.to_le_bytes());
+        string(&mut file, "<s>");
+        string(&mut file, "</s>");
+        string(&mut file, "llama.context_length");
+        file.extend(4u32.to_le_bytes());
+        file.extend(8192u32.to_le_bytes());
+        string(&mut file, "tokenizer.chat_template");
+        file.extend(8u32.to_le_bytes());
+        string(&mut file, "{{ '<|im_start|>' }}");
+
+        let info = GgufInfo::read_from(&file[..]).unwrap();
+        assert_eq!(info.architecture(), Some("llama"));
+        assert_eq!(info.context_length(), Some(8192));
+        assert_eq!(
+            info.get("tokenizer.ggml.tokens"),
+            Some(&GgufValue::Array(2))
+        );
+        assert_eq!(info.chat_template(), Some("{{ '<|im_start|>' }}"));
+
+        assert!(GgufInfo::read_from(&b"GGML\x03\0\0\0"[..]).is_err());
+        assert!(GgufInfo::read_from(&file[..file.len() - 3]).is_err());
+    }
+}
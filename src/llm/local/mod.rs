@@ -0,0 +1,360 @@
+//! In-process inference with local models.
+//!
+//! [`LocalModel`] implements [`ChatProvider`] for a model running inside the
+//! application, so handlers, [`Toolbox`](super::Toolbox), and the relays work the same
+//! with it as with a remote [`LlmClient`](super::LlmClient). It renders the conversation
+//! with the model's chat template, trims it to the context window, and streams the
+//! generated text as [`Delta`]s while generation runs on the blocking thread pool.
+//!
+//! The inference runtime sits behind the [`Engine`] trait. [`CandleEngine`] runs
+//! quantized GGUF models (Llama, Mistral, Qwen2) on the CPU; implement [`Engine`] over
+//! llama.cpp bindings or another runtime for GPUs or other architectures.
+//! [`LocalModel::from_gguf`] reads the file's metadata to pick the context length and
+//! chat template.
+//!
+//! Tool definitions on a request are ignored.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use rttp::llm::{ChatMessage, ChatProvider, ChatRequest, LlmError};
+//! use rttp::llm::local::{CandleEngine, LocalModel};
+//!
+//! # async fn run() -> Result<(), LlmError> {
+//! let path = "models/qwen2.5-7b-instruct-q4_k_m.gguf";
+//! let engine = CandleEngine::load(path, "models/qwen2.5-tokenizer.json")?;
+//! let model = LocalModel::from_gguf(path, Arc::new(engine))?;
+//! let request = ChatRequest::new("local").message(ChatMessage::user("Hi"));
+//! let text = model.stream_chat(&request).await?.text().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod candle;
+mod gguf;
+
+use std::{path::Path, sync::Arc};
+
+pub use candle::CandleEngine;
+pub use gguf::{GgufInfo, GgufValue};
+
+use super::{
+    ChatMessage, ChatProvider, ChatRequest, ChatStream, ContextWindow, Delta, LlmError, Role,
+    TokenCounter, Tokenizer, TransportFuture, Usage,
+};
+
+// Deltas buffered between the generating thread and the reader.
+const STREAM_BUFFER: usize = 64;
+
+/// An inference runtime with a model loaded.
+///
+/// Calls run on tokio's blocking thread pool, one per completion in progress; an engine
+/// that cannot generate concurrently should serialize calls itself.
+pub trait Engine: Send + Sync + 'static {
+    /// Returns the number of tokens `text` encodes to in the model's vocabulary.
+    fn count_tokens(&self, text: &str) -> usize;
+
+    /// Generates a continuation of `prompt`, passing each piece of text to `emit` as it
+    /// is sampled, and returns the tokens read and generated.
+    ///
+    /// Generation ends at an end-of-generation token, at any of `options.stop`, after
+    /// `options.max_tokens`, or when `emit` returns `false` because the reader is gone.
+    fn generate(
+        &self,
+        prompt: &str,
+        options: &Generation,
+        emit: &mut dyn FnMut(&str) -> bool,
+    ) -> Result<Usage, LlmError>;
+}
+
+/// Sampling settings for one completion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generation {
+    pub max_tokens: u32,
+    pub temperature: f32,
+    /// Text that ends generation; not emitted.
+    pub stop: Vec<String>,
+}
+
+/// How a conversation is laid out as a prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChatTemplate {
+    /// `<|im_start|>role … <|im_end|>`, used by Qwen, Mistral fine-tunes, and many others.
+    #[default]
+    ChatMl,
+    /// Llama 3's `<|start_header_id|>role<|end_header_id|> … <|eot_id|>`.
+    Llama3,
+}
+
+impl ChatTemplate {
+    /// Picks the template a GGUF file's Jinja `chat_template` implements, if it is one of
+    /// these.
+    pub fn detect(jinja: &str) -> Option<Self> {
+        if jinja.contains("<|start_header_id|>") {
+            Some(Self::Llama3)
+        } else if jinja.contains("<|im_start|>") {
+            Some(Self::ChatMl)
+        } else {
+            None
+        }
+    }
+
+    /// Renders `messages` as a prompt ending where the assistant's reply begins.
+    pub fn render(&self, messages: &[ChatMessage]) -> String {
+        let mut prompt = String::new();
+        match self {
+            Self::ChatMl => {
+                for m in messages {
+                    prompt.push_str(&format!(
+                        "<|im_start|>{}\n{}<|im_end|>\n",
+                        role_name(m.role),
+                        m.content
+                    ));
+                }
+                prompt.push_str("<|im_start|>assistant\n");
+            }
+            Self::Llama3 => {
+                prompt.push_str("<|begin_of_text|>");
+                for m in messages {
+                    prompt.push_str(&format!(
+                        "<|start_header_id|>{}<|end_header_id|>\n\n{}<|eot_id|>",
+                        role_name(m.role),
+                        m.content
+                    ));
+                }
+                prompt.push_str("<|start_header_id|>assistant<|end_header_id|>\n\n");
+            }
+        }
+        prompt
+    }
+
+    /// Returns the text that ends the assistant's turn.
+    pub fn stop(&self) -> &'static str {
+        match self {
+            Self::ChatMl => "<|im_end|>",
+            Self::Llama3 => "<|eot_id|>",
+        }
+    }
+}
+
+/// A model running in-process, behind an [`Engine`].
+///
+/// The request's model name is ignored: every request goes to the loaded model.
+#[derive(Clone)]
+pub struct LocalModel {
+    engine: Arc<dyn Engine>,
+    context_length: usize,
+    max_tokens: u32,
+    temperature: f32,
+    template: ChatTemplate,
+}
+
+impl LocalModel {
+    /// Serves `engine` with a 4096-token context, the ChatML template, and completions of
+    /// up to 512 tokens at temperature 0.8.
+    pub fn new(engine: Arc<dyn Engine>) -> Self {
+        Self {
+            engine,
+            context_length: 4096,
+            max_tokens: 512,
+            temperature: 0.8,
+            template: ChatTemplate::ChatMl,
+        }
+    }
+
+    /// Serves `engine`, which has the GGUF model at `path` loaded, taking the context
+    /// length and chat template from the file's metadata where it has them.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from reading the file's metadata.
+    pub fn from_gguf(path: impl AsRef<Path>, engine: Arc<dyn Engine>) -> Result<Self, LlmError> {
+        let info = GgufInfo::read(path)?;
+        let mut model = Self::new(engine);
+        if let Some(length) = info.context_length() {
+            model.context_length = usize::try_from(length).unwrap_or(usize::MAX);
+        }
+        if let Some(template) = info.chat_template().and_then(ChatTemplate::detect) {
+            model.template = template;
+        }
+        Ok(model)
+    }
+
+    /// Sets the context window in tokens, shared by the prompt and the completion.
+    #[must_use]
+    pub fn context_length(mut self, tokens: usize) -> Self {
+        self.context_length = tokens;
+        self
+    }
+
+    /// Sets the completion length used when a request does not set one.
+    #[must_use]
+    pub fn max_tokens(mut self, tokens: u32) -> Self {
+        self.max_tokens = tokens;
+        self
+    }
+
+    /// Sets the temperature used when a request does not set one.
+    #[must_use]
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    /// Sets the chat template.
+    #[must_use]
+    pub fn template(mut self, template: ChatTemplate) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// Renders `request` as the prompt the engine receives, after dropping the oldest
+    /// history that does not fit the context window.
+    pub fn prompt(&self, request: &ChatRequest) -> String {
+        let counter = TokenCounter::new().with("", Arc::new(EngineTokens(self.engine.clone())));
+        let window = ContextWindow::new(self.context_length)
+            .reserve(self.max_tokens as usize)
+            .counter(counter);
+        self.template
+            .render(window.truncate(request).conversation())
+    }
+}
+
+impl ChatProvider for LocalModel {
+    fn stream_chat<'a>(&'a self, request: &'a ChatRequest) -> TransportFuture<'a, ChatStream> {
+        let prompt = self.prompt(request);
+        let options = Generation {
+            max_tokens: request.max_tokens.unwrap_or(self.max_tokens),
+            temperature: request.temperature.unwrap_or(self.temperature),
+            stop: vec![self.template.stop().to_owned()],
+        };
+        let engine = Arc::clone(&self.engine);
+        let (tx, stream) = ChatStream::channel(STREAM_BUFFER);
+        tokio::task::spawn_blocking(move || {
+            let result = {
+                let mut emit =
+                    |piece: &str| tx.blocking_send(Ok(Delta::Text(piece.to_owned()))).is_ok();
+                engine.generate(&prompt, &options, &mut emit)
+            };
+            let usage = match result {
+                Ok(usage) => usage,
+                Err(e) => {
+                    let _ = tx.blocking_send(Err(e));
+                    return;
+                }
+            };
+            let limit = u64::from(options.max_tokens);
+            let reason = if usage.output_tokens.is_some_and(|n| n >= limit) {
+                "length"
+            } else {
+                "stop"
+            };
+            let _ = tx.blocking_send(Ok(Delta::Usage(usage)));
+            let _ = tx.blocking_send(Ok(Delta::Done {
+                finish_reason: Some(reason.to_owned()),
+            }));
+        });
+        Box::pin(async move { Ok(stream) })
+    }
+}
+
+// Counts tokens with the engine's own vocabulary.
+struct EngineTokens(Arc<dyn Engine>);
+
+impl Tokenizer for EngineTokens {
+    fn count(&self, text: &str) -> usize {
+        self.0.count_tokens(text)
+    }
+}
+
+fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::System => "system",
+        Role::User => "user",
+        Role::Assistant => "assistant",
+        Role::Tool => "tool",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // Echoes the last line of the prompt's user turn, word by word.
+    #[derive(Default)]
+    struct Echo {
+        prompts: Mutex<Vec<String>>,
+    }
+
+    impl Engine for Echo {
+        fn count_tokens(&self, text: &str) -> usize {
+            text.split_whitespace().count()
+        }
+
+        fn generate(
+            &self,
+            prompt: &str,
+            options: &Generation,
+            emit: &mut dyn FnMut(&str) -> bool,
+        ) -> Result<Usage, LlmError> {
+            self.prompts.lock().unwrap().push(prompt.to_owned());
+            let words = ["one ", "two ", "three"];
+            let mut output = 0;
+            for word in words.iter().take(options.max_tokens as usize) {
+                if !emit(word) {
+                    break;
+                }
+                output += 1;
+            }
+            Ok(Usage {
+                input_tokens: Some(self.count_tokens(prompt) as u64),
+                output_tokens: Some(output),
+            })
+        }
+    }
+
+    #[test]
+    fn renders_chat_templates() {
+        let messages = [ChatMessage::system("Be brief."), ChatMessage::user("Hi")];
+        assert_eq!(
+            ChatTemplate::ChatMl.render(&messages),
+            "<|im_start|>system\nBe brief.<|im_end|>\n<|im_start|>user\nHi<|im_end|>\n\
+             <|im_start|>assistant\n"
+        );
+        assert_eq!(
+            ChatTemplate::Llama3.render(&messages[1..]),
+            "<|begin_of_text|><|start_header_id|>user<|end_header_id|>\n\nHi<|eot_id|>\
+             <|start_header_id|>assistant<|end_header_id|>\n\n"
+        );
+        assert_eq!(
+            ChatTemplate::detect("{{ '<|start_header_id|>' + role }}"),
+            Some(ChatTemplate::Llama3)
+        );
+    }
+
+    #[tokio::test]
+    async fn streams_generated_text() {
+        let engine = Arc::new(Echo::default());
+        let model = LocalModel::new(engine.clone())
+            .context_length(12)
+            .max_tokens(2);
+        let request = ChatRequest::new("ignored")
+            .message(ChatMessage::user("an old question that no longer fits"))
+            .message(ChatMessage::user("count"));
+
+        let completion = model.stream_chat(&request).await.unwrap().complete().await;
+        let completion = completion.unwrap();
+        assert_eq!(completion.text, "one two ");
+        assert_eq!(completion.finish_reason.as_deref(), Some("length"));
+        assert_eq!(completion.usage.output_tokens, Some(2));
+
+        let prompts = engine.prompts.lock().unwrap();
+        assert_eq!(
+            prompts[0],
+            "<|im_start|>user\ncount<|im_end|>\n<|im_start|>assistant\n"
+        );
+    }
+}
@@ -0,0 +1,398 @@
+//! Token usage, cost, and budgets.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use super::{LlmError, Provider, Usage};
+use crate::middleware::metrics::{MetricsSource, MetricsWriter};
+
+/// What a model charges, in US dollars per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Pricing {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl Pricing {
+    /// Prices input and output tokens per million.
+    pub fn per_million(input: f64, output: f64) -> Self {
+        Self { input, output }
+    }
+
+    /// The cost of `usage` in dollars; missing counts cost nothing.
+    pub fn cost(&self, usage: Usage) -> f64 {
+        let input = usage.input_tokens.unwrap_or_default() as f64;
+        let output = usage.output_tokens.unwrap_or_default() as f64;
+        (input * self.input + output * self.output) / 1_000_000.0
+    }
+}
+
+/// A spending limit. Calls fail with [`LlmError::BudgetExceeded`] once any set limit is
+/// reached.
+///
+/// The check runs before each call, so the call that crosses a limit completes and only
+/// later ones are refused.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Budget {
+    max_cost: Option<f64>,
+    max_tokens: Option<u64>,
+}
+
+impl Budget {
+    /// Creates a budget with no limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits spending to `usd` dollars.
+    #[must_use]
+    pub fn max_cost(mut self, usd: f64) -> Self {
+        self.max_cost = Some(usd);
+        self
+    }
+
+    /// Limits input and output tokens together to `tokens`.
+    #[must_use]
+    pub fn max_tokens(mut self, tokens: u64) -> Self {
+        self.max_tokens = Some(tokens);
+        self
+    }
+
+    // Describes the limit `spent` has reached, if any.
+    fn exceeded(&self, spent: &Totals) -> Option<String> {
+        if let Some(max) = self.max_cost.filter(|&max| spent.cost >= max) {
+            return Some(format!("spent ${:.4} of ${max}", spent.cost));
+        }
+        let tokens = spent.input_tokens + spent.output_tokens;
+        self.max_tokens
+            .filter(|&max| tokens >= max)
+            .map(|max| format!("used {tokens} of {max} tokens"))
+    }
+}
+
+/// Totals for one provider, model, and route.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Totals {
+    /// Calls made to the provider.
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Estimated cost in dollars, from the model's [`Pricing`].
+    pub cost: f64,
+}
+
+impl Totals {
+    fn add(&mut self, other: &Totals) {
+        self.requests += other.requests;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cost += other.cost;
+    }
+}
+
+// Provider, model, and route.
+type Key = (&'static str, String, String);
+
+#[derive(Default)]
+struct Ledger {
+    totals: HashMap<Key, Totals>,
+}
+
+impl Ledger {
+    fn sum(&self, route: Option<&str>) -> Totals {
+        let mut sum = Totals::default();
+        for ((_, _, r), totals) in &self.totals {
+            if route.is_none_or(|route| route == r) {
+                sum.add(totals);
+            }
+        }
+        sum
+    }
+}
+
+/// Meters the usage of every call a client makes, and enforces budgets.
+///
+/// Usage is recorded by provider, model, and route — the label set with
+/// [`ChatRequest::route`](super::ChatRequest::route) — when each stream ends, from the
+/// counts the provider reports. Cache hits are free and not recorded. Clones share the
+/// same totals; register one with [`Metrics`](crate::middleware::Metrics) to export
+/// them.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::llm::{Budget, LlmClient, Pricing, UsageMeter};
+/// use rttp::middleware::Metrics;
+///
+/// let meter = UsageMeter::new()
+///     .price("gpt-4o-mini", Pricing::per_million(0.15, 0.60))
+///     .budget(Budget::new().max_cost(50.0))
+///     .route_budget("/playground", Budget::new().max_tokens(2_000_000));
+///
+/// let metrics = Metrics::new();
+/// metrics.register(meter.clone());
+/// let client = LlmClient::openai("http://127.0.0.1:11434/v1").meter(meter);
+/// ```
+#[derive(Clone, Default)]
+pub struct UsageMeter {
+    prices: Vec<(String, Pricing)>,
+    budget: Option<Budget>,
+    route_budgets: HashMap<String, Budget>,
+    ledger: Arc<Mutex<Ledger>>,
+}
+
+impl UsageMeter {
+    /// Creates a meter with no prices or budgets.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prices models whose names start with `model_prefix`; the longest prefix wins.
+    /// Unpriced models cost nothing.
+    #[must_use]
+    pub fn price(mut self, model_prefix: impl Into<String>, pricing: Pricing) -> Self {
+        self.prices.push((model_prefix.into(), pricing));
+        self
+    }
+
+    /// Sets a budget for all calls together.
+    #[must_use]
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Sets a budget for the calls made for `route`.
+    #[must_use]
+    pub fn route_budget(mut self, route: impl Into<String>, budget: Budget) -> Self {
+        self.route_budgets.insert(route.into(), budget);
+        self
+    }
+
+    /// Checks the budgets that apply to a call for `route`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LlmError::BudgetExceeded`] if the overall or the route's budget is spent.
+    pub fn check(&self, route: &str) -> Result<(), LlmError> {
+        let ledger = self.lock();
+        if let Some(reason) = self.budget.and_then(|b| b.exceeded(&ledger.sum(None))) {
+            return Err(LlmError::BudgetExceeded(reason));
+        }
+        if let Some(budget) = self.route_budgets.get(route) {
+            if let Some(reason) = budget.exceeded(&ledger.sum(Some(route))) {
+                return Err(LlmError::BudgetExceeded(format!(
+                    "route {route:?} {reason}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Records one call and returns its cost.
+    pub fn record(&self, provider: Provider, model: &str, route: &str, usage: Usage) -> f64 {
+        let cost = self.pricing(model).map_or(0.0, |p| p.cost(usage));
+        let call = Totals {
+            requests: 1,
+            input_tokens: usage.input_tokens.unwrap_or_default(),
+            output_tokens: usage.output_tokens.unwrap_or_default(),
+            cost,
+        };
+        self.lock()
+            .totals
+            .entry((provider_name(provider), model.to_owned(), route.to_owned()))
+            .or_default()
+            .add(&call);
+        cost
+    }
+
+    /// Returns the totals for each provider, model, and route, sorted.
+    pub fn totals(&self) -> Vec<(String, String, String, Totals)> {
+        let ledger = self.lock();
+        let sorted: BTreeMap<&Key, &Totals> = ledger.totals.iter().collect();
+        sorted
+            .into_iter()
+            .map(|((p, m, r), t)| ((*p).to_owned(), m.clone(), r.clone(), *t))
+            .collect()
+    }
+
+    /// Returns the totals across every call.
+    pub fn total(&self) -> Totals {
+        self.lock().sum(None)
+    }
+
+    /// Clears all totals, starting a new budget period.
+    pub fn reset(&self) {
+        self.lock().totals.clear();
+    }
+
+    fn pricing(&self, model: &str) -> Option<Pricing> {
+        self.prices
+            .iter()
+            .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, pricing)| *pricing)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Ledger> {
+        self.ledger.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl MetricsSource for UsageMeter {
+    fn collect(&self, out: &mut MetricsWriter) {
+        for (provider, model, route, totals) in self.totals() {
+            let labels = [
+                ("provider", provider.as_str()),
+                ("model", model.as_str()),
+                ("route", route.as_str()),
+            ];
+            out.counter(
+                "rttp_llm_requests_total",
+                "LLM calls made to providers.",
+                &labels,
+                totals.requests as f64,
+            );
+            out.counter(
+                "rttp_llm_input_tokens_total",
+                "Prompt tokens reported by LLM providers.",
+                &labels,
+                totals.input_tokens as f64,
+            );
+            out.counter(
+                "rttp_llm_output_tokens_total",
+                "Completion tokens reported by LLM providers.",
+                &labels,
+                totals.output_tokens as f64,
+            );
+            out.counter(
+                "rttp_llm_cost_dollars_total",
+                "Estimated LLM spend in US dollars.",
+                &labels,
+                totals.cost,
+            );
+        }
+    }
+}
+
+fn provider_name(provider: Provider) -> &'static str {
+    match provider {
+        Provider::OpenAi => "openai",
+        Provider::Anthropic => "anthropic",
+    }
+}
+
+// Records a call's usage when its stream is dropped, however it ended.
+pub(super) struct Metering {
+    pub(super) meter: UsageMeter,
+    pub(super) provider: Provider,
+    pub(super) model: String,
+    pub(super) route: String,
+    pub(super) usage: Usage,
+}
+
+impl Drop for Metering {
+    fn drop(&mut self) {
+        self.meter
+            .record(self.provider, &self.model, &self.route, self.usage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{
+        ChatMessage, ChatRequest, LlmClient,
+        transport::{StreamingResponse, Transport, TransportFuture},
+    };
+
+    struct Reply;
+
+    impl Transport for Reply {
+        fn post<'a>(
+            &'a self,
+            _url: &'a str,
+            _headers: &'a [(String, String)],
+            _body: &'a [u8],
+        ) -> TransportFuture<'a, StreamingResponse> {
+            let body = b"data: {\"choices\":[{\"delta\":{\"content\":\"ok\"},\"finish_reason\":\"stop\"}]}\n\n\
+                data: {\"choices\":[],\"usage\":{\"prompt_tokens\":600000,\"completion_tokens\":100000}}\n\n\
+                data: [DONE]\n\n"
+                .to_vec();
+            Box::pin(async move {
+                Ok(StreamingResponse {
+                    status: 200,
+                    body: Box::new(vec![body].into_iter()),
+                })
+            })
+        }
+    }
+
+    #[test]
+    fn prices_by_longest_prefix() {
+        let meter = UsageMeter::new()
+            .price("gpt-4o", Pricing::per_million(2.5, 10.0))
+            .price("gpt-4o-mini", Pricing::per_million(0.15, 0.6));
+        let usage = Usage {
+            input_tokens: Some(1_000_000),
+            output_tokens: Some(1_000_000),
+        };
+        assert_eq!(
+            meter.record(Provider::OpenAi, "gpt-4o-mini", "/a", usage),
+            0.75
+        );
+        assert_eq!(meter.record(Provider::OpenAi, "gpt-4o", "/a", usage), 12.5);
+        assert_eq!(meter.record(Provider::OpenAi, "llama3", "/b", usage), 0.0);
+        assert_eq!(meter.total().requests, 3);
+        assert_eq!(meter.totals()[0].2, "/a");
+    }
+
+    #[tokio::test]
+    async fn meters_calls_and_fails_fast_over_budget() {
+        let meter = UsageMeter::new()
+            .price("model-x", Pricing::per_million(1.0, 4.0))
+            .budget(Budget::new().max_cost(1.5))
+            .route_budget("/chat", Budget::new().max_tokens(500_000));
+        let client = LlmClient::openai("http://llm.internal/v1")
+            .transport(Arc::new(Reply))
+            .meter(meter.clone());
+        let request = |route: &str| {
+            ChatRequest::new("model-x")
+                .message(ChatMessage::user("hi"))
+                .route(route)
+        };
+
+        // 0.6M input at $1 and 0.1M output at $4: $1 a call.
+        client
+            .stream_chat(&request("/chat"))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        let error = client.stream_chat(&request("/chat")).await.err().unwrap();
+        assert!(matches!(error, LlmError::BudgetExceeded(ref r) if r.contains("/chat")));
+
+        client
+            .stream_chat(&request("/other"))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        let error = client.stream_chat(&request("/other")).await.err().unwrap();
+        assert!(matches!(error, LlmError::BudgetExceeded(_)));
+        assert_eq!(meter.total().cost, 2.0);
+
+        let metrics = crate::middleware::Metrics::new();
+        metrics.register(meter.clone());
+        assert!(metrics.render().contains(
+            "rttp_llm_output_tokens_total{provider=\"openai\",model=\"model-x\",route=\"/chat\"} 100000"
+        ));
+
+        meter.reset();
+        assert!(meter.check("/chat").is_ok());
+    }
+}
@@ -1,17 +1,401 @@
-//! LLM / AI integration — streaming inference and prompt management.
+//! LLM integration — streaming chat completions.
 //!
-//! ## Planned Features
+//! [`LlmClient`] sends a [`ChatRequest`] to an OpenAI-compatible or Anthropic endpoint
+//! and returns a [`ChatStream`] of [`Delta`]s as the model produces them. Each
+//! provider's streaming format — OpenAI's `data:` chunks ending in `[DONE]`, Anthropic's
+//! typed `content_block_delta` / `message_stop` events — is decoded into the same
+//! deltas, and error frames sent mid-stream surface as [`LlmError::Provider`].
 //!
-//! - OpenAI-compatible chat completion client
-//! - Anthropic Claude API client
-//! - Streaming response proxying (SSE → SSE)
-//! - Prompt template engine
-//! - Token counting utilities
-//! - Local model support (via `llama.cpp` bindings)
+//! Requests can offer [`Tool`]s; the model's calls stream in as [`Delta::ToolCall`]
+//! pieces, which [`ChatStream::complete`] assembles. A [`Toolbox`] maps tool names to
+//! async Rust functions and loops — complete, run the calls, send back the results —
+//! until the model gives a final answer.
 //!
-//! ## Status: PLANNED
+//! [`sse_response`] answers a request by relaying a stream to the browser as
+//! Server-Sent Events; [`pipe_to_sse`] and [`pipe_to_websocket`] do the same on a
+//! connection the handler already holds.
+//!
+//! [`LlmProxy`] mounts an OpenAI-compatible `/v1/chat/completions` endpoint that
+//! forwards to a provider, holding the provider key and rate-limiting each client key.
+//!
+//! A [`ContextWindow`] keeps long conversations within a model's limit, dropping or
+//! summarizing the oldest history; attach one with [`LlmClient::context_window`]. It
+//! counts tokens with a [`TokenCounter`], which estimates unless given a [`Tokenizer`]
+//! for the model — such as a `Bpe` loaded from a tiktoken vocabulary, with the
+//! `tokenizer` feature.
+//!
+//! Anything implementing [`ChatProvider`] can stand in for a client; with the `local`
+//! feature, `local::LocalModel` serves a model running in-process, such as a quantized
+//! GGUF model loaded with `local::CandleEngine`.
+//!
+//! A [`UsageMeter`] totals tokens and estimated cost by provider, model, and route,
+//! exports them as metrics, and refuses calls once a [`Budget`] is spent; attach one
+//! with [`LlmClient::meter`].
+//!
+//! An [`LlmCache`] answers repeated prompts from any [`cache`](crate::cache) backend
+//! instead of the provider; attach one with [`LlmClient::cache`].
+//!
+//! [`embeddings`] turns text into vectors and searches them in memory, for retrieval
+//! without a separate vector database.
+//!
+//! The built-in [`HttpTransport`] sends through the shared
+//! [`Client`](crate::http::client::Client): plain `http://` for local model servers and
+//! internal gateways, and `https://` for hosted APIs with the `tls` feature.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use rttp::{Response, Router, StatusCode, context::Context};
+//! use rttp::llm::{self, ChatMessage, ChatRequest, LlmClient};
+//!
+//! let client = Arc::new(LlmClient::openai("http://127.0.0.1:11434/v1"));
+//! let mut router = Router::new();
+//! router.get("/ask", move |ctx: Context| {
+//!     let client = Arc::clone(&client);
+//!     async move {
+//!         let question = ctx.request().query_param("q").unwrap_or_default().to_owned();
+//!         let request = ChatRequest::new("llama3").message(ChatMessage::user(question));
+//!         match client.stream_chat(&request).await {
+//!             Ok(stream) => llm::sse_response(ctx, stream),
+//!             Err(_) => Response::new(StatusCode::BadGateway),
+//!         }
+//!     }
+//! });
+//! ```
+
+use std::{io, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::http::client::ClientError;
+
+#[cfg(feature = "tokenizer")]
+mod bpe;
+mod cache;
+mod client;
+mod context;
+pub mod embeddings;
+#[cfg(feature = "local")]
+pub mod local;
+mod meter;
+mod proxy;
+mod relay;
+mod stream;
+mod tokens;
+mod tools;
+mod transport;
+
+#[cfg(feature = "tokenizer")]
+pub use bpe::Bpe;
+pub use cache::LlmCache;
+pub use client::{ChatProvider, LlmClient, Provider};
+pub use context::{ContextWindow, Overflow};
+pub use meter::{Budget, Pricing, Totals, UsageMeter};
+pub use proxy::LlmProxy;
+pub use relay::{pipe_to_sse, pipe_to_websocket, sse_response};
+pub use stream::ChatStream;
+pub use tokens::{Estimate, TokenCounter, Tokenizer, count_tokens};
+pub use tools::Toolbox;
+pub use transport::{BodyReader, HttpTransport, StreamingResponse, Transport, TransportFuture};
+
+/// Errors produced while requesting or reading a completion.
+#[derive(Debug, Error)]
+pub enum LlmError {
+    #[error("LLM I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("invalid LLM endpoint URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("LLM transport error: {0}")]
+    Transport(String),
+
+    #[error("LLM request timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("LLM provider answered {status}: {body}")]
+    Status { status: u16, body: String },
+
+    #[error("LLM provider error: {0}")]
+    Provider(String),
+
+    #[error("malformed LLM stream: {0}")]
+    Malformed(String),
+
+    #[error("LLM serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("model was still calling tools after {0} rounds")]
+    ToolRounds(usize),
+
+    #[error("LLM budget exceeded: {0}")]
+    BudgetExceeded(String),
+
+    #[error("vector has {found} dimensions, expected {expected}")]
+    Dimensions { expected: usize, found: usize },
+}
+
+impl From<ClientError> for LlmError {
+    fn from(err: ClientError) -> Self {
+        match err {
+            ClientError::InvalidUrl(url) => Self::InvalidUrl(url),
+            ClientError::Io(err) => Self::Io(err),
+            ClientError::Timeout(timeout) => Self::Timeout(timeout),
+            err => Self::Transport(err.to_string()),
+        }
+    }
+}
+
+/// Who wrote a message in a conversation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    /// The result of a tool call, answering an assistant message's [`ToolCall`].
+    Tool,
+}
+
+/// One message in a conversation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+    /// The tools an assistant message calls.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// The call a [`Role::Tool`] message answers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    /// Creates a message from `role`.
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+
+    /// Creates a system message.
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(Role::System, content)
+    }
+
+    /// Creates a user message.
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(Role::User, content)
+    }
+
+    /// Creates an assistant message.
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(Role::Assistant, content)
+    }
+
+    /// Creates a message answering the tool call `call_id` with its result.
+    pub fn tool(call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: Some(call_id.into()),
+            ..Self::new(Role::Tool, content)
+        }
+    }
+
+    /// Attaches the tools an assistant message calls.
+    #[must_use]
+    pub fn with_tool_calls(mut self, calls: impl IntoIterator<Item = ToolCall>) -> Self {
+        self.tool_calls.extend(calls);
+        self
+    }
+}
+
+/// A tool the model may call, described by a JSON schema of its arguments.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Tool {
+    pub name: String,
+    pub description: String,
+    /// A JSON schema for the arguments object.
+    pub parameters: Value,
+}
+
+impl Tool {
+    /// Creates a tool definition.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::llm::Tool;
+    /// use serde_json::json;
+    ///
+    /// let weather = Tool::new(
+    ///     "get_weather",
+    ///     "Current weather for a city.",
+    ///     json!({
+    ///         "type": "object",
+    ///         "properties": { "city": { "type": "string" } },
+    ///         "required": ["city"],
+    ///     }),
+    /// );
+    /// assert_eq!(weather.name, "get_weather");
+    /// ```
+    pub fn new(name: impl Into<String>, description: impl Into<String>, parameters: Value) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            parameters,
+        }
+    }
+}
+
+/// A call the model made to a [`Tool`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// The provider's ID for the call, echoed back with its result.
+    pub id: String,
+    pub name: String,
+    /// The arguments as a JSON object, encoded as text.
+    pub arguments: String,
+}
+
+/// A piece of a tool call as it streams in.
+///
+/// The first piece for a call carries its `id` and `name`; later pieces with the same
+/// `index` carry more of its `arguments`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ToolCallDelta {
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub arguments: String,
+}
+
+/// A chat completion request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatRequest {
+    model: String,
+    messages: Vec<ChatMessage>,
+    max_tokens: Option<u32>,
+    temperature: Option<f32>,
+    tools: Vec<Tool>,
+    cache: bool,
+    route: String,
+}
+
+impl ChatRequest {
+    /// Creates a request for `model` with no messages.
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            messages: Vec::new(),
+            max_tokens: None,
+            temperature: None,
+            tools: Vec::new(),
+            cache: true,
+            route: String::new(),
+        }
+    }
+
+    /// Appends a message.
+    #[must_use]
+    pub fn message(mut self, message: ChatMessage) -> Self {
+        self.messages.push(message);
+        self
+    }
+
+    /// Appends several messages.
+    #[must_use]
+    pub fn messages(mut self, messages: impl IntoIterator<Item = ChatMessage>) -> Self {
+        self.messages.extend(messages);
+        self
+    }
+
+    /// Limits the length of the completion.
+    #[must_use]
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Sets the sampling temperature.
+    #[must_use]
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Offers the model a tool.
+    #[must_use]
+    pub fn tool(mut self, tool: Tool) -> Self {
+        self.tools.push(tool);
+        self
+    }
+
+    /// Bypasses the client's [`LlmCache`] for this request: it is neither answered from
+    /// the cache nor stored in it.
+    #[must_use]
+    pub fn no_cache(mut self) -> Self {
+        self.cache = false;
+        self
+    }
+
+    /// Labels the request with the route it serves, for a [`UsageMeter`]'s totals and
+    /// per-route budgets. Not sent to the provider.
+    #[must_use]
+    pub fn route(mut self, route: impl Into<String>) -> Self {
+        self.route = route.into();
+        self
+    }
+
+    /// Returns the model name.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Returns the conversation so far.
+    pub fn conversation(&self) -> &[ChatMessage] {
+        &self.messages
+    }
+
+    /// Returns the tools offered to the model.
+    pub fn tool_definitions(&self) -> &[Tool] {
+        &self.tools
+    }
+}
+
+/// Token counts reported by the provider. Either may be missing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub input_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+}
 
-// TODO: Implement LLM integration layer
+/// One step of a streamed completion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Delta {
+    /// The next piece of generated text.
+    Text(String),
+    /// Part of a call to a tool.
+    ToolCall(ToolCallDelta),
+    /// Token usage, when the provider reports it.
+    Usage(Usage),
+    /// The completion finished. Always the last delta.
+    Done { finish_reason: Option<String> },
+}
 
-/// Placeholder — will become the `LlmClient` type.
-pub struct LlmClient;
+/// A finished completion, assembled from its deltas by [`ChatStream::complete`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Completion {
+    pub text: String,
+    /// The tools the model called, in order. Empty for a final answer.
+    pub tool_calls: Vec<ToolCall>,
+    pub finish_reason: Option<String>,
+    pub usage: Usage,
+}
@@ -0,0 +1,537 @@
+//! An OpenAI-compatible chat completions proxy.
+//!
+//! [`LlmProxy`] answers `POST /v1/chat/completions` by forwarding the body unchanged to
+//! an upstream provider, so existing OpenAI SDKs can point at an rttp app. Clients
+//! authenticate with their own keys; the provider key never leaves the server. Each
+//! client key gets its own rate limit, and streamed completions are relayed chunk by
+//! chunk as they arrive.
+//!
+//! Every request is logged with the client key shortened to its last four characters.
+//! Bodies are logged only when asked, with message contents replaced by their length.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use rttp::Router;
+//! use rttp::llm::LlmProxy;
+//!
+//! let mut router = Router::new();
+//! LlmProxy::new("http://llm-gateway.internal/v1")
+//!     .api_key("sk-provider-key")
+//!     .allow_key("team-a-key")
+//!     .allow_key("team-b-key")
+//!     .rate_limit(60, Duration::from_secs(60))
+//!     .mount(&mut router);
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde_json::{Value, json};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, warn};
+
+use super::{
+    BodyReader, LlmError,
+    transport::{HttpTransport, Transport},
+};
+use crate::{
+    Response, Router, StatusCode,
+    clock::{self, Clock},
+    context::Context,
+    http::upgrade::OnUpgrade,
+    middleware::{MiddlewareHandler, Next, from_middleware},
+    security::{api_key_auth, auth::parse_bearer},
+};
+
+// The largest non-streamed upstream response relayed.
+const MAX_RESPONSE: usize = 16 * 1024 * 1024;
+
+// Rate-limit buckets kept before idle, full ones are dropped.
+const MAX_BUCKETS: usize = 10_000;
+
+/// Forwards OpenAI-style chat completion requests to an upstream provider.
+///
+/// Without [`allow_key`](Self::allow_key) any caller is served, rate-limited by its
+/// key or, lacking one, its address. Errors the proxy itself produces use
+/// OpenAI's `{"error": {"message", "type"}}` shape; upstream errors pass through as sent.
+#[derive(Clone)]
+pub struct LlmProxy {
+    upstream: String,
+    path: String,
+    api_key: Option<String>,
+    allowed: HashSet<String>,
+    limit: Option<(f64, Duration)>,
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+    log_bodies: bool,
+    transport: Arc<dyn Transport>,
+    clock: Arc<dyn Clock>,
+}
+
+// A token bucket: `tokens` requests may be made now, refilling over time.
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl LlmProxy {
+    /// Creates a proxy to the OpenAI-compatible API rooted at `upstream`; requests go to
+    /// `{upstream}/chat/completions`.
+    pub fn new(upstream: impl Into<String>) -> Self {
+        Self {
+            upstream: format!("{}/chat/completions", upstream.into().trim_end_matches('/')),
+            path: "/v1/chat/completions".to_owned(),
+            api_key: None,
+            allowed: HashSet::new(),
+            limit: None,
+            buckets: Arc::default(),
+            log_bodies: false,
+            transport: Arc::new(HttpTransport::new()),
+            clock: clock::system(),
+        }
+    }
+
+    /// Sets the provider key sent upstream in place of the client's.
+    #[must_use]
+    pub fn api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    /// Accepts `key` from clients, as a bearer token or `X-API-Key`; once any key is
+    /// allowed, others get `401`.
+    #[must_use]
+    pub fn allow_key(mut self, key: impl Into<String>) -> Self {
+        self.allowed.insert(key.into());
+        self
+    }
+
+    /// Allows each client key `requests` requests per `per`, in bursts of up to
+    /// `requests`; beyond that it gets `429` with `Retry-After`.
+    #[must_use]
+    pub fn rate_limit(mut self, requests: u32, per: Duration) -> Self {
+        self.limit = Some((f64::from(requests.max(1)), per));
+        self
+    }
+
+    /// Sets the route [`mount`](Self::mount) registers; `/v1/chat/completions` by default.
+    #[must_use]
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Logs request and response bodies at debug level, with message contents redacted.
+    #[must_use]
+    pub fn log_bodies(mut self, enabled: bool) -> Self {
+        self.log_bodies = enabled;
+        self
+    }
+
+    /// Replaces the default [`HttpTransport`].
+    #[must_use]
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Refills rate-limit buckets by `clock` instead of the system clock.
+    #[must_use]
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Registers the proxy on `router` as a `POST` route, behind an
+    /// [`ApiKeyMiddleware`](crate::security::ApiKeyMiddleware) once any key is allowed.
+    pub fn mount(self, router: &mut Router) {
+        let path = self.path.clone();
+        let auth = (!self.allowed.is_empty()).then(|| {
+            api_key_auth(self.allowed.iter().cloned()).on_reject(|| {
+                error(
+                    StatusCode::Unauthorized,
+                    "invalid_api_key",
+                    "invalid API key",
+                )
+            })
+        });
+        let proxy = Arc::new(self);
+        let forward: MiddlewareHandler = Arc::new(move |ctx: Context, _next: Next| {
+            let proxy = Arc::clone(&proxy);
+            Box::pin(async move { proxy.handle(ctx).await })
+        });
+        let chain: Arc<[MiddlewareHandler]> = match auth {
+            Some(auth) => vec![from_middleware(Arc::new(auth)), forward].into(),
+            None => vec![forward].into(),
+        };
+        router.post(&path, move |ctx: Context| {
+            Next::new(Arc::clone(&chain)).run(ctx)
+        });
+    }
+
+    // Answers one chat completions request that passed the key check.
+    async fn handle(&self, ctx: Context) -> Response {
+        let request = ctx.request();
+        let token = request
+            .headers()
+            .get("authorization")
+            .and_then(parse_bearer)
+            .or_else(|| request.headers().get("x-api-key"));
+        let client = match (token, request.connection()) {
+            (Some(token), _) => token.to_owned(),
+            (None, Some(connection)) => connection.peer_addr().ip().to_string(),
+            (None, None) => String::new(),
+        };
+        let shown = redact_key(&client);
+
+        if let Err(wait) = self.acquire(&client) {
+            info!(client = %shown, "LLM proxy rate limit exceeded");
+            return error(
+                StatusCode::TooManyRequests,
+                "rate_limit_exceeded",
+                "rate limit exceeded",
+            )
+            .header(
+                "Retry-After",
+                (wait.as_secs_f64().ceil() as u64).to_string(),
+            );
+        }
+
+        let body: Value = match serde_json::from_slice(request.body()) {
+            Ok(body @ Value::Object(_)) => body,
+            _ => {
+                return error(
+                    StatusCode::BadRequest,
+                    "invalid_request_error",
+                    "body must be a JSON object",
+                );
+            }
+        };
+        let model = body["model"].as_str().unwrap_or_default().to_owned();
+        let streamed = body["stream"].as_bool().unwrap_or(false);
+        if self.log_bodies {
+            debug!(client = %shown, body = %redact_body(&body), "LLM proxy request");
+        }
+
+        let started = Instant::now();
+        let upstream = match self.forward(request.body()).await {
+            Ok(upstream) => upstream,
+            Err(e) => {
+                warn!(client = %shown, %model, error = %e, "LLM proxy upstream failed");
+                return error(StatusCode::BadGateway, "upstream_error", &e.to_string());
+            }
+        };
+        let status = StatusCode::from_u16(upstream.status).unwrap_or(StatusCode::BadGateway);
+        info!(
+            client = %shown,
+            %model,
+            streamed,
+            status = upstream.status,
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "LLM proxy request"
+        );
+
+        let mut body = upstream.body;
+        if streamed && (200..300).contains(&upstream.status) {
+            return Response::new(status)
+                .header("Content-Type", "text/event-stream")
+                .header("Cache-Control", "no-cache")
+                .header("X-Accel-Buffering", "no")
+                .stream(OnUpgrade::new(move |mut io| async move {
+                    while let Ok(Some(chunk)) = body.chunk().await {
+                        if io.write_all(&chunk).await.is_err() || io.flush().await.is_err() {
+                            return;
+                        }
+                    }
+                }));
+        }
+
+        match read_all(body.as_mut()).await {
+            Ok(bytes) => {
+                if self.log_bodies {
+                    let shown_body = serde_json::from_slice(&bytes)
+                        .map(|v| redact_body(&v).to_string())
+                        .unwrap_or_else(|_| format!("[{} bytes]", bytes.len()));
+                    debug!(client = %shown, body = %shown_body, "LLM proxy response");
+                }
+                Response::new(status)
+                    .header("Content-Type", "application/json")
+                    .body_bytes(bytes)
+            }
+            Err(e) => error(StatusCode::BadGateway, "upstream_error", &e.to_string()),
+        }
+    }
+
+    async fn forward(&self, body: &[u8]) -> Result<super::StreamingResponse, LlmError> {
+        let mut headers = vec![("Content-Type".to_owned(), "application/json".to_owned())];
+        if let Some(key) = &self.api_key {
+            headers.push(("Authorization".to_owned(), format!("Bearer {key}")));
+        }
+        self.transport.post(&self.upstream, &headers, body).await
+    }
+
+    // Takes a request from `client`'s bucket, or returns how long until one is available.
+    fn acquire(&self, client: &str) -> Result<(), Duration> {
+        let Some((capacity, per)) = self.limit else {
+            return Ok(());
+        };
+        let rate = capacity / per.as_secs_f64().max(f64::EPSILON);
+        let now = self.clock.now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_BUCKETS {
+            buckets.retain(|_, b| {
+                b.tokens + now.duration_since(b.updated).as_secs_f64() * rate < capacity
+            });
+        }
+        let bucket = buckets.entry(client.to_owned()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        bucket.tokens =
+            (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate).max(Duration::from_secs(1)))
+        }
+    }
+}
+
+impl std::fmt::Debug for LlmProxy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LlmProxy")
+            .field("upstream", &self.upstream)
+            .field("path", &self.path)
+            .field("allowed_keys", &self.allowed.len())
+            .field("limit", &self.limit)
+            .finish_non_exhaustive()
+    }
+}
+
+async fn read_all(body: &mut dyn BodyReader) -> Result<Vec<u8>, LlmError> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.chunk().await? {
+        bytes.extend(chunk);
+        if bytes.len() > MAX_RESPONSE {
+            return Err(LlmError::Malformed("upstream response too large".into()));
+        }
+    }
+    Ok(bytes)
+}
+
+// An error in OpenAI's format.
+fn error(status: StatusCode, kind: &str, message: &str) -> Response {
+    Response::new(status)
+        .header("Content-Type", "application/json")
+        .body(json!({ "error": { "message": message, "type": kind } }).to_string())
+}
+
+// Shows only the last four characters of a key.
+fn redact_key(key: &str) -> String {
+    let tail: String = key
+        .chars()
+        .rev()
+        .take(4)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    if key.chars().count() > 8 {
+        format!("…{tail}")
+    } else {
+        "…".to_owned()
+    }
+}
+
+// A copy of a request or response body with message text replaced by its length.
+fn redact_body(body: &Value) -> Value {
+    let mut body = body.clone();
+    let mut redact = |message: &mut Value| {
+        if let Some(content) = message.get_mut("content") {
+            if let Some(text) = content.as_str() {
+                *content = json!(format!("[{} chars]", text.chars().count()));
+            } else if !content.is_null() {
+                *content = json!("[redacted]");
+            }
+        }
+    };
+    if let Some(messages) = body["messages"].as_array_mut() {
+        messages.iter_mut().for_each(&mut redact);
+    }
+    if let Some(choices) = body["choices"].as_array_mut() {
+        for choice in choices {
+            if let Some(message) = choice.get_mut("message") {
+                redact(message);
+            }
+        }
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use tokio::io::{AsyncReadExt, duplex};
+
+    use super::*;
+    use crate::{
+        Request,
+        http::upgrade::Upgraded,
+        llm::transport::{StreamingResponse, TransportFuture},
+        testing::TestClient,
+    };
+
+    // Answers every request with `body` and records the headers sent.
+    struct Upstream {
+        body: &'static str,
+        seen: Mutex<Vec<(String, String)>>,
+    }
+
+    impl Transport for Upstream {
+        fn post<'a>(
+            &'a self,
+            url: &'a str,
+            headers: &'a [(String, String)],
+            _body: &'a [u8],
+        ) -> TransportFuture<'a, StreamingResponse> {
+            assert_eq!(url, "http://llm.internal/v1/chat/completions");
+            *self.seen.lock().unwrap() = headers.to_vec();
+            let body = self.body.as_bytes().to_vec();
+            Box::pin(async move {
+                Ok(StreamingResponse {
+                    status: 200,
+                    body: Box::new(vec![body].into_iter()),
+                })
+            })
+        }
+    }
+
+    fn proxy(body: &'static str) -> (LlmProxy, Arc<Upstream>) {
+        let upstream = Arc::new(Upstream {
+            body,
+            seen: Mutex::default(),
+        });
+        let proxy = LlmProxy::new("http://llm.internal/v1/")
+            .api_key("sk-provider")
+            .allow_key("client-key-1234")
+            .transport(upstream.clone());
+        (proxy, upstream)
+    }
+
+    fn ctx(key: &str, body: &str) -> Context {
+        let raw = format!(
+            "POST /v1/chat/completions HTTP/1.1\r\nAuthorization: Bearer {key}\r\n\
+             Content-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        Context::new(Request::parse(raw.as_bytes()).unwrap().0)
+    }
+
+    const ASK: &str = r#"{"model":"gpt-x","messages":[{"role":"user","content":"Hi"}]}"#;
+
+    #[tokio::test]
+    async fn forwards_with_the_provider_key() {
+        let (proxy, upstream) = proxy(r#"{"choices":[{"message":{"content":"Hello"}}]}"#);
+        let response = proxy.handle(ctx("client-key-1234", ASK)).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert!(String::from_utf8_lossy(response.body_ref()).contains("Hello"));
+        let injected = ("Authorization".into(), "Bearer sk-provider".into());
+        assert!(upstream.seen.lock().unwrap().contains(&injected));
+
+        let response = proxy.handle(ctx("client-key-1234", "[]")).await;
+        assert_eq!(response.status(), StatusCode::BadRequest);
+    }
+
+    #[tokio::test]
+    async fn mounted_proxy_rejects_unknown_keys() {
+        let (proxy, _) = proxy("{}");
+        let mut router = Router::new();
+        proxy.mount(&mut router);
+        let mut client = TestClient::new(router);
+        let body: Value = client
+            .post("/v1/chat/completions")
+            .bearer("stolen")
+            .body(ASK)
+            .send()
+            .await
+            .assert_status(StatusCode::Unauthorized)
+            .json();
+        assert_eq!(body["error"]["type"], "invalid_api_key");
+        client
+            .post("/v1/chat/completions")
+            .bearer("client-key-1234")
+            .body(ASK)
+            .send()
+            .await
+            .assert_status(StatusCode::Ok);
+    }
+
+    #[tokio::test]
+    async fn limits_each_key() {
+        let (proxy, _) = proxy("{}");
+        let proxy = proxy
+            .allow_key("other-key")
+            .rate_limit(2, Duration::from_secs(60));
+        for _ in 0..2 {
+            let response = proxy.handle(ctx("client-key-1234", ASK)).await;
+            assert_eq!(response.status(), StatusCode::Ok);
+        }
+        let limited = proxy.handle(ctx("client-key-1234", ASK)).await;
+        assert_eq!(limited.status(), StatusCode::TooManyRequests);
+        assert_eq!(limited.headers().get("retry-after"), Some("30"));
+        let other = proxy.handle(ctx("other-key", ASK)).await;
+        assert_eq!(other.status(), StatusCode::Ok);
+    }
+
+    #[tokio::test]
+    async fn buckets_refill_with_the_clock() {
+        let clock = crate::clock::MockClock::new();
+        let (proxy, _) = proxy("{}");
+        let proxy = proxy
+            .rate_limit(2, Duration::from_secs(60))
+            .clock(Arc::new(clock.clone()));
+        for _ in 0..2 {
+            let response = proxy.handle(ctx("client-key-1234", ASK)).await;
+            assert_eq!(response.status(), StatusCode::Ok);
+        }
+        clock.advance(Duration::from_secs(29));
+        let limited = proxy.handle(ctx("client-key-1234", ASK)).await;
+        assert_eq!(limited.status(), StatusCode::TooManyRequests);
+        assert_eq!(limited.headers().get("retry-after"), Some("1"));
+
+        clock.advance(Duration::from_secs(1));
+        let response = proxy.handle(ctx("client-key-1234", ASK)).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+    }
+
+    #[tokio::test]
+    async fn passes_streams_through() {
+        let events = "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\ndata: [DONE]\n\n";
+        let (proxy, _) = proxy(events);
+        let streamed = r#"{"model":"gpt-x","stream":true,"messages":[]}"#;
+        let mut response = proxy.handle(ctx("client-key-1234", streamed)).await;
+        assert!(response.is_streamed());
+
+        let (server, mut client) = duplex(4096);
+        let writer = response.take_upgrade().unwrap();
+        writer.run(Upgraded::new(server, BytesMut::new())).await;
+        let mut body = String::new();
+        client.read_to_string(&mut body).await.unwrap();
+        assert_eq!(body, events);
+    }
+
+    #[test]
+    fn redacts_keys_and_content() {
+        assert_eq!(redact_key("client-key-1234"), "…1234");
+        assert_eq!(redact_key("short"), "…");
+        let body: Value = serde_json::from_str(ASK).unwrap();
+        assert_eq!(redact_body(&body)["messages"][0]["content"], "[2 chars]");
+    }
+}
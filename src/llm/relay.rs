@@ -0,0 +1,162 @@
+//! Relaying a [`ChatStream`] to a client over SSE or a WebSocket.
+//!
+//! Both relays send the same frames, named by `type` (the SSE event name):
+//!
+//! | Frame   | Payload                                        |
+//! |---------|------------------------------------------------|
+//! | `token` | `{"text": "…"}`                                |
+//! | `tool_call` | `{"index": 0, "id": "…", "name": "…", "arguments": "…"}` |
+//! | `usage` | `{"input_tokens": 12, "output_tokens": 40}`    |
+//! | `done`  | `{"finish_reason": "stop"}`                    |
+//! | `error` | `{"error": {"code": "llm_error", "message": "…"}}` |
+//!
+//! On a WebSocket the payload fields sit beside `type` in one JSON object, and `error`
+//! follows the [`WsRouter`](crate::realtime::WsRouter) error convention.
+
+use std::io;
+
+use serde_json::{Value, json};
+
+use super::{ChatStream, Delta, LlmError};
+use crate::{
+    Response,
+    context::Context,
+    realtime::{
+        WebSocket, WsError,
+        sse::{self, Event, Sse},
+    },
+};
+
+/// Answers a request with an SSE stream relaying `stream`.
+///
+/// Start the completion first, so a provider that rejects it can still be answered with
+/// an ordinary error response.
+pub fn sse_response(ctx: Context, stream: ChatStream) -> Response {
+    sse::stream(ctx, move |_, mut sse| async move {
+        let _ = pipe_to_sse(stream, &mut sse).await;
+    })
+}
+
+/// Relays `stream` to `sse`, sending keep-alives while the model is quiet.
+///
+/// Returns the generated text.
+///
+/// # Errors
+///
+/// Returns the error that ended the relay: the stream's, after sending it to the client
+/// as an `error` event, or [`LlmError::Io`] if the client disconnected.
+pub async fn pipe_to_sse(mut stream: ChatStream, sse: &mut Sse) -> Result<String, LlmError> {
+    let mut text = String::new();
+    loop {
+        let Some(next) = sse.wait_for(stream.next()).await? else {
+            return Ok(text);
+        };
+        let (kind, payload) = frame(next.as_ref(), &mut text);
+        sse.send(&Event::new().event(kind).data(payload.to_string()))
+            .await?;
+        next?;
+    }
+}
+
+/// Relays `stream` to `ws` as JSON text messages.
+///
+/// Returns the generated text.
+///
+/// # Errors
+///
+/// Returns the error that ended the relay: the stream's, after sending it to the client
+/// as an `error` message, or [`LlmError::Io`] if the WebSocket failed.
+pub async fn pipe_to_websocket(
+    mut stream: ChatStream,
+    ws: &mut WebSocket,
+) -> Result<String, LlmError> {
+    let mut text = String::new();
+    while let Some(next) = stream.next().await {
+        let (kind, mut payload) = frame(next.as_ref(), &mut text);
+        payload["type"] = json!(kind);
+        ws.send(payload.to_string()).await.map_err(|e| match e {
+            WsError::Io(e) => LlmError::Io(e),
+            other => LlmError::Io(io::Error::new(io::ErrorKind::BrokenPipe, other)),
+        })?;
+        next?;
+    }
+    Ok(text)
+}
+
+// The frame type and payload for one step of the stream, accumulating text.
+fn frame(next: Result<&Delta, &LlmError>, text: &mut String) -> (&'static str, Value) {
+    match next {
+        Ok(Delta::Text(piece)) => {
+            text.push_str(piece);
+            ("token", json!({ "text": piece }))
+        }
+        Ok(Delta::ToolCall(call)) => ("tool_call", json!(call)),
+        Ok(Delta::Usage(usage)) => ("usage", json!(usage)),
+        Ok(Delta::Done { finish_reason }) => ("done", json!({ "finish_reason": finish_reason })),
+        Err(e) => (
+            "error",
+            json!({ "error": { "code": "llm_error", "message": e.to_string() } }),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use tokio::io::{AsyncReadExt, duplex};
+
+    use super::*;
+    use crate::{Request, http::upgrade::Upgraded, llm::Provider, realtime::WebSocketConfig};
+
+    fn stream(body: &str) -> ChatStream {
+        ChatStream::new(
+            Provider::OpenAi,
+            Box::new(vec![body.as_bytes().to_vec()].into_iter()),
+        )
+    }
+
+    const BODY: &str = "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}\n\n\
+        data: [DONE]\n\n";
+
+    #[tokio::test]
+    async fn relays_to_sse() {
+        let ctx = Context::new(Request::parse(b"GET /ask HTTP/1.1\r\n\r\n").unwrap().0);
+        let mut response = sse_response(ctx, stream(BODY));
+        let (server, mut client) = duplex(4096);
+        let writer = response.take_upgrade().unwrap();
+        writer.run(Upgraded::new(server, BytesMut::new())).await;
+
+        let mut body = String::new();
+        client.read_to_string(&mut body).await.unwrap();
+        assert_eq!(
+            body,
+            "event: token\ndata: {\"text\":\"Hi\"}\n\n\
+             event: done\ndata: {\"finish_reason\":\"stop\"}\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn relays_errors_to_websockets() {
+        let (server, client) = duplex(4096);
+        let mut ws = WebSocket::from_stream(server, WebSocketConfig::new());
+        let truncated = "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n";
+        let result = pipe_to_websocket(stream(truncated), &mut ws).await;
+        assert!(matches!(result, Err(LlmError::Malformed(_))));
+        drop(ws);
+
+        // Unmasked server frames: 0x81, a one-byte length, then the text.
+        let mut raw = Vec::new();
+        let mut client = client;
+        client.read_to_end(&mut raw).await.unwrap();
+        let mut messages = Vec::new();
+        while raw.len() >= 2 {
+            let len = usize::from(raw[1]);
+            let message: Value = serde_json::from_slice(&raw[2..2 + len]).unwrap();
+            messages.push(message);
+            raw.drain(..2 + len);
+        }
+        assert_eq!(messages[0], json!({"type": "token", "text": "Hi"}));
+        assert_eq!(messages[1]["type"], "error");
+        assert_eq!(messages[1]["error"]["code"], "llm_error");
+    }
+}
@@ -0,0 +1,558 @@
+//! Decoding providers' streaming responses into [`Delta`]s.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use super::{
+    Completion, Delta, LlmError, Provider, ToolCall, ToolCallDelta, Usage,
+    cache::Recorder,
+    meter::Metering,
+    transport::{BodyReader, TransportFuture},
+};
+
+/// A completion being streamed from a provider.
+///
+/// Call [`next`](Self::next) until it returns `None`. A successful stream ends with
+/// [`Delta::Done`]; an error frame from the provider, or a body that ends before the
+/// completion does, is returned as an error and ends the stream.
+pub struct ChatStream {
+    source: Source,
+    pending: VecDeque<Delta>,
+    finished: bool,
+    recorder: Option<Recorder>,
+    metering: Option<Metering>,
+}
+
+// Where deltas come from.
+enum Source {
+    // A provider's streaming HTTP response.
+    Provider {
+        body: Box<dyn BodyReader>,
+        events: EventParser,
+        decoder: Decoder,
+    },
+    // Deltas produced in-process.
+    Channel(mpsc::Receiver<Result<Delta, LlmError>>),
+}
+
+impl ChatStream {
+    pub(super) fn new(provider: Provider, body: Box<dyn BodyReader>) -> Self {
+        Self::from_source(Source::Provider {
+            body,
+            events: EventParser::default(),
+            decoder: Decoder {
+                provider,
+                finish_reason: None,
+                usage: Usage::default(),
+            },
+        })
+    }
+
+    /// Creates a stream fed by the returned sender, for completions produced in-process,
+    /// as by a custom [`ChatProvider`](super::ChatProvider).
+    ///
+    /// Send the deltas and finish with [`Delta::Done`]; dropping the sender before that
+    /// ends the stream with an error.
+    pub fn channel(capacity: usize) -> (mpsc::Sender<Result<Delta, LlmError>>, Self) {
+        let (tx, rx) = mpsc::channel(capacity);
+        (tx, Self::from_source(Source::Channel(rx)))
+    }
+
+    fn from_source(source: Source) -> Self {
+        Self {
+            source,
+            pending: VecDeque::new(),
+            finished: false,
+            recorder: None,
+            metering: None,
+        }
+    }
+
+    // A stream that plays back a cached completion. Usage is left out: a cache hit cost
+    // nothing.
+    pub(super) fn replay(provider: Provider, completion: Completion) -> Self {
+        let mut stream = Self::new(provider, Box::new(Finished));
+        if !completion.text.is_empty() {
+            stream.pending.push_back(Delta::Text(completion.text));
+        }
+        for (index, call) in completion.tool_calls.into_iter().enumerate() {
+            stream.pending.push_back(Delta::ToolCall(ToolCallDelta {
+                index,
+                id: Some(call.id),
+                name: Some(call.name),
+                arguments: call.arguments,
+            }));
+        }
+        stream.pending.push_back(Delta::Done {
+            finish_reason: completion.finish_reason,
+        });
+        stream.finished = true;
+        stream
+    }
+
+    // Reports the stream's usage to a meter when it is dropped.
+    pub(super) fn meter(mut self, metering: Metering) -> Self {
+        self.metering = Some(metering);
+        self
+    }
+
+    // Hands every delta to `recorder`, which caches the completion once it is done.
+    pub(super) fn record(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Waits for the next delta.
+    pub async fn next(&mut self) -> Option<Result<Delta, LlmError>> {
+        loop {
+            if let Some(delta) = self.pending.pop_front() {
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.push(&delta);
+                }
+                if let (Some(metering), Delta::Usage(usage)) = (&mut self.metering, &delta) {
+                    metering.usage = *usage;
+                }
+                if matches!(delta, Delta::Done { .. }) {
+                    if let Some(recorder) = self.recorder.take() {
+                        recorder.store().await;
+                    }
+                }
+                return Some(Ok(delta));
+            }
+            if self.finished {
+                return None;
+            }
+            let ended = || LlmError::Malformed("stream ended before the completion".into());
+            let step = match &mut self.source {
+                Source::Provider {
+                    body,
+                    events,
+                    decoder,
+                } => match events.next_event() {
+                    Some(event) => decoder.decode(&event),
+                    None => match body.chunk().await {
+                        Ok(Some(chunk)) => {
+                            events.push(&chunk);
+                            Ok(Vec::new())
+                        }
+                        Ok(None) => Err(ended()),
+                        Err(e) => Err(e),
+                    },
+                },
+                Source::Channel(rx) => match rx.recv().await {
+                    Some(delta) => delta.map(|delta| vec![delta]),
+                    None => Err(ended()),
+                },
+            };
+            match step {
+                Ok(deltas) => {
+                    self.finished = deltas.iter().any(|d| matches!(d, Delta::Done { .. }));
+                    self.pending.extend(deltas);
+                }
+                Err(e) => return Some(Err(self.fail(e))),
+            }
+        }
+    }
+
+    /// Reads the rest of the stream and returns the generated text.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error the stream produces.
+    pub async fn text(mut self) -> Result<String, LlmError> {
+        let mut text = String::new();
+        while let Some(delta) = self.next().await {
+            if let Delta::Text(piece) = delta? {
+                text.push_str(&piece);
+            }
+        }
+        Ok(text)
+    }
+
+    /// Reads the rest of the stream and assembles the completion, including any tool
+    /// calls.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error the stream produces.
+    pub async fn complete(mut self) -> Result<Completion, LlmError> {
+        let mut assembler = Assembler::default();
+        while let Some(delta) = self.next().await {
+            assembler.push(&delta?);
+        }
+        Ok(assembler.finish())
+    }
+
+    fn fail(&mut self, e: LlmError) -> LlmError {
+        self.finished = true;
+        e
+    }
+}
+
+// Builds a `Completion` from deltas.
+#[derive(Default)]
+pub(super) struct Assembler {
+    completion: Completion,
+    calls: BTreeMap<usize, ToolCall>,
+}
+
+impl Assembler {
+    pub(super) fn push(&mut self, delta: &Delta) {
+        match delta {
+            Delta::Text(piece) => self.completion.text.push_str(piece),
+            Delta::ToolCall(piece) => {
+                let call = self.calls.entry(piece.index).or_default();
+                if let Some(id) = &piece.id {
+                    call.id.clone_from(id);
+                }
+                if let Some(name) = &piece.name {
+                    call.name.clone_from(name);
+                }
+                call.arguments.push_str(&piece.arguments);
+            }
+            Delta::Usage(usage) => self.completion.usage = *usage,
+            Delta::Done { finish_reason } => {
+                self.completion.finish_reason.clone_from(finish_reason);
+            }
+        }
+    }
+
+    pub(super) fn finish(mut self) -> Completion {
+        self.completion.tool_calls = self
+            .calls
+            .into_values()
+            .map(|mut call| {
+                // Anthropic streams no arguments at all for a call that takes none.
+                if call.arguments.trim().is_empty() {
+                    call.arguments = "{}".to_owned();
+                }
+                call
+            })
+            .collect();
+        self.completion
+    }
+}
+
+// The body of a replayed stream, which has nothing left to read.
+struct Finished;
+
+impl BodyReader for Finished {
+    fn chunk(&mut self) -> TransportFuture<'_, Option<Vec<u8>>> {
+        Box::pin(async { Ok(None) })
+    }
+}
+
+// One server-sent event from the provider.
+#[derive(Debug, Default, PartialEq)]
+struct SseEvent {
+    event: Option<String>,
+    data: String,
+}
+
+// Incremental `text/event-stream` parser; bytes may arrive split anywhere.
+#[derive(Default)]
+struct EventParser {
+    buf: Vec<u8>,
+    current: SseEvent,
+    has_data: bool,
+}
+
+impl EventParser {
+    fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn next_event(&mut self) -> Option<SseEvent> {
+        while let Some(end) = self.buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                let event = std::mem::take(&mut self.current);
+                if std::mem::take(&mut self.has_data) || event.event.is_some() {
+                    return Some(event);
+                }
+                continue;
+            }
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => self.current.event = Some(value.to_owned()),
+                "data" => {
+                    if self.has_data {
+                        self.current.data.push('\n');
+                    }
+                    self.current.data.push_str(value);
+                    self.has_data = true;
+                }
+                // Comments, `id`, and `retry` carry nothing we need.
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+// Turns provider events into deltas, carrying what arrives ahead of the end.
+struct Decoder {
+    provider: Provider,
+    finish_reason: Option<String>,
+    usage: Usage,
+}
+
+impl Decoder {
+    fn decode(&mut self, event: &SseEvent) -> Result<Vec<Delta>, LlmError> {
+        match self.provider {
+            Provider::OpenAi => self.openai(event),
+            Provider::Anthropic => self.anthropic(event),
+        }
+    }
+
+    // `data: {"choices":[{"delta":{"content":"…"}}]}` chunks, then `data: [DONE]`.
+    fn openai(&mut self, event: &SseEvent) -> Result<Vec<Delta>, LlmError> {
+        if event.data.trim() == "[DONE]" {
+            return Ok(vec![Delta::Done {
+                finish_reason: self.finish_reason.take(),
+            }]);
+        }
+        let chunk = parse(&event.data)?;
+        if let Some(error) = chunk.get("error") {
+            return Err(provider_error(error));
+        }
+        let mut deltas = Vec::new();
+        if let Some(choice) = chunk["choices"].get(0) {
+            if let Some(text) = choice["delta"]["content"]
+                .as_str()
+                .filter(|t| !t.is_empty())
+            {
+                deltas.push(Delta::Text(text.to_owned()));
+            }
+            for call in choice["delta"]["tool_calls"]
+                .as_array()
+                .into_iter()
+                .flatten()
+            {
+                deltas.push(Delta::ToolCall(ToolCallDelta {
+                    index: call["index"].as_u64().unwrap_or_default() as usize,
+                    id: call["id"].as_str().map(str::to_owned),
+                    name: call["function"]["name"].as_str().map(str::to_owned),
+                    arguments: call["function"]["arguments"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_owned(),
+                }));
+            }
+            if let Some(reason) = choice["finish_reason"].as_str() {
+                self.finish_reason = Some(reason.to_owned());
+            }
+        }
+        if let Some(usage) = chunk.get("usage").filter(|u| u.is_object()) {
+            deltas.push(Delta::Usage(Usage {
+                input_tokens: usage["prompt_tokens"].as_u64(),
+                output_tokens: usage["completion_tokens"].as_u64(),
+            }));
+        }
+        Ok(deltas)
+    }
+
+    // Typed events: `message_start`, `content_block_delta`, `message_delta`,
+    // `message_stop`, and `error`.
+    fn anthropic(&mut self, event: &SseEvent) -> Result<Vec<Delta>, LlmError> {
+        let data = parse(&event.data)?;
+        let kind = event
+            .event
+            .as_deref()
+            .or_else(|| data["type"].as_str())
+            .unwrap_or_default();
+        Ok(match kind {
+            "message_start" => {
+                let usage = &data["message"]["usage"];
+                self.usage.input_tokens = usage["input_tokens"].as_u64();
+                self.usage.output_tokens = usage["output_tokens"].as_u64();
+                Vec::new()
+            }
+            "content_block_start" if data["content_block"]["type"] == "tool_use" => {
+                let block = &data["content_block"];
+                vec![Delta::ToolCall(ToolCallDelta {
+                    index: block_index(&data),
+                    id: block["id"].as_str().map(str::to_owned),
+                    name: block["name"].as_str().map(str::to_owned),
+                    arguments: String::new(),
+                })]
+            }
+            "content_block_delta" => match &data["delta"] {
+                delta if delta["type"] == "input_json_delta" => {
+                    vec![Delta::ToolCall(ToolCallDelta {
+                        index: block_index(&data),
+                        id: None,
+                        name: None,
+                        arguments: delta["partial_json"]
+                            .as_str()
+                            .unwrap_or_default()
+                            .to_owned(),
+                    })]
+                }
+                delta => match delta["text"].as_str() {
+                    Some(text) if !text.is_empty() => vec![Delta::Text(text.to_owned())],
+                    _ => Vec::new(),
+                },
+            },
+            "message_delta" => {
+                if let Some(reason) = data["delta"]["stop_reason"].as_str() {
+                    self.finish_reason = Some(reason.to_owned());
+                }
+                if let Some(output) = data["usage"]["output_tokens"].as_u64() {
+                    self.usage.output_tokens = Some(output);
+                }
+                vec![Delta::Usage(self.usage)]
+            }
+            "message_stop" => vec![Delta::Done {
+                finish_reason: self.finish_reason.take(),
+            }],
+            "error" => return Err(provider_error(&data["error"])),
+            // `ping`, text `content_block_start`, `content_block_stop`, and newer events.
+            _ => Vec::new(),
+        })
+    }
+}
+
+fn block_index(data: &Value) -> usize {
+    data["index"].as_u64().unwrap_or_default() as usize
+}
+
+fn parse(data: &str) -> Result<Value, LlmError> {
+    serde_json::from_str(data).map_err(|e| LlmError::Malformed(format!("{e}: {data:?}")))
+}
+
+fn provider_error(error: &Value) -> LlmError {
+    let message = error["message"]
+        .as_str()
+        .map_or_else(|| error.to_string(), str::to_owned);
+    LlmError::Provider(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Splits `body` into `size`-byte pieces so events straddle chunk boundaries.
+    fn stream(provider: Provider, body: &str, size: usize) -> ChatStream {
+        let chunks: Vec<Vec<u8>> = body.as_bytes().chunks(size).map(<[u8]>::to_vec).collect();
+        ChatStream::new(provider, Box::new(chunks.into_iter()))
+    }
+
+    async fn collect(mut stream: ChatStream) -> Vec<Result<Delta, String>> {
+        let mut deltas = Vec::new();
+        while let Some(delta) = stream.next().await {
+            deltas.push(delta.map_err(|e| e.to_string()));
+        }
+        deltas
+    }
+
+    const OPENAI: &str = "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"\"}}]}\n\n\
+        data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\r\n\r\n\
+        : keep-alive\n\n\
+        data: {\"choices\":[{\"delta\":{\"content\":\"lo\"},\"finish_reason\":\"stop\"}]}\n\n\
+        data: {\"choices\":[],\"usage\":{\"prompt_tokens\":5,\"completion_tokens\":2}}\n\n\
+        data: [DONE]\n\n";
+
+    #[tokio::test]
+    async fn decodes_openai_streams() {
+        for size in [1, 7, OPENAI.len()] {
+            assert_eq!(
+                collect(stream(Provider::OpenAi, OPENAI, size)).await,
+                [
+                    Ok(Delta::Text("Hel".into())),
+                    Ok(Delta::Text("lo".into())),
+                    Ok(Delta::Usage(Usage {
+                        input_tokens: Some(5),
+                        output_tokens: Some(2)
+                    })),
+                    Ok(Delta::Done {
+                        finish_reason: Some("stop".into())
+                    }),
+                ],
+                "chunk size {size}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn decodes_anthropic_streams() {
+        let body = "event: message_start\n\
+            data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":9,\"output_tokens\":1}}}\n\n\
+            event: ping\ndata: {\"type\":\"ping\"}\n\n\
+            event: content_block_delta\n\
+            data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n\
+            event: message_delta\n\
+            data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":3}}\n\n\
+            event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n";
+        assert_eq!(
+            collect(stream(Provider::Anthropic, body, 11)).await,
+            [
+                Ok(Delta::Text("Hi".into())),
+                Ok(Delta::Usage(Usage {
+                    input_tokens: Some(9),
+                    output_tokens: Some(3)
+                })),
+                Ok(Delta::Done {
+                    finish_reason: Some("end_turn".into())
+                }),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn assembles_streamed_tool_calls() {
+        let openai = "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"type\":\"function\",\"function\":{\"name\":\"weather\",\"arguments\":\"\"}}]}}]}\n\n\
+            data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"{\\\"city\\\":\"}}]}}]}\n\n\
+            data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"\\\"Oslo\\\"}\"}}]},\"finish_reason\":\"tool_calls\"}]}\n\n\
+            data: [DONE]\n\n";
+        let anthropic = "event: content_block_start\n\
+            data: {\"type\":\"content_block_start\",\"index\":1,\"content_block\":{\"type\":\"tool_use\",\"id\":\"call_1\",\"name\":\"weather\",\"input\":{}}}\n\n\
+            event: content_block_delta\n\
+            data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"city\\\": \\\"Oslo\\\"}\"}}\n\n\
+            event: message_delta\n\
+            data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"tool_use\"},\"usage\":{\"output_tokens\":7}}\n\n\
+            event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n";
+
+        for (provider, body, reason) in [
+            (Provider::OpenAi, openai, "tool_calls"),
+            (Provider::Anthropic, anthropic, "tool_use"),
+        ] {
+            let completion = stream(provider, body, 9).complete().await.unwrap();
+            assert_eq!(completion.text, "");
+            assert_eq!(completion.finish_reason.as_deref(), Some(reason));
+            assert_eq!(completion.tool_calls.len(), 1);
+            let call = &completion.tool_calls[0];
+            assert_eq!(
+                (call.id.as_str(), call.name.as_str()),
+                ("call_1", "weather")
+            );
+            let arguments: Value = serde_json::from_str(&call.arguments).unwrap();
+            assert_eq!(arguments["city"], "Oslo");
+        }
+    }
+
+    #[tokio::test]
+    async fn surfaces_error_frames_and_truncation() {
+        let body = "event: content_block_delta\n\
+            data: {\"type\":\"content_block_delta\",\"delta\":{\"type\":\"text_delta\",\"text\":\"a\"}}\n\n\
+            event: error\n\
+            data: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n";
+        assert_eq!(
+            collect(stream(Provider::Anthropic, body, body.len())).await,
+            [
+                Ok(Delta::Text("a".into())),
+                Err("LLM provider error: Overloaded".into())
+            ]
+        );
+
+        let truncated = "data: {\"choices\":[{\"delta\":{\"content\":\"a\"}}]}\n\n";
+        let deltas = collect(stream(Provider::OpenAi, truncated, 5)).await;
+        assert_eq!(deltas.len(), 2);
+        assert!(deltas[1].as_ref().unwrap_err().contains("ended before"));
+    }
+}
@@ -0,0 +1,174 @@
+//! Counting tokens, exactly with a model's vocabulary or approximately without one.
+//!
+//! Providers bill and limit by tokens, so fitting a conversation into a model's context
+//! window needs a count in the model's own units. A [`TokenCounter`] maps model names to
+//! [`Tokenizer`]s — typically a `Bpe` loaded from the model's tiktoken
+//! vocabulary, with the `tokenizer` feature — and falls back to an [`Estimate`] for
+//! models it has no vocabulary for.
+
+use std::{fmt, sync::Arc};
+
+use super::ChatMessage;
+
+// Tokens a chat message costs beyond its content: role and delimiters.
+const MESSAGE_OVERHEAD: usize = 4;
+
+// Tokens that prime the model's reply.
+const REPLY_OVERHEAD: usize = 3;
+
+/// Counts the tokens in a piece of text.
+pub trait Tokenizer: Send + Sync {
+    /// Returns the number of tokens `text` encodes to.
+    fn count(&self, text: &str) -> usize;
+}
+
+/// An approximate count for when the model's vocabulary is not available.
+///
+/// Counts a token per four characters of each word and one per punctuation mark, which
+/// errs high for English prose: safe for staying under a limit, wasteful for billing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Estimate;
+
+impl Tokenizer for Estimate {
+    fn count(&self, text: &str) -> usize {
+        let mut tokens = 0;
+        let mut word: usize = 0;
+        for c in text.chars() {
+            if c.is_alphanumeric() {
+                word += 1;
+                continue;
+            }
+            tokens += word.div_ceil(4);
+            word = 0;
+            if !c.is_whitespace() {
+                tokens += 1;
+            }
+        }
+        tokens + word.div_ceil(4)
+    }
+}
+
+/// Picks a [`Tokenizer`] by model name and counts with it.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::llm::{ChatMessage, TokenCounter};
+///
+/// let counter = TokenCounter::new();
+/// assert_eq!(counter.count_tokens("any-model", "hello world"), 4);
+/// assert!(counter.count_messages("any-model", &[ChatMessage::user("hi")]) > 1);
+/// ```
+#[derive(Clone, Default)]
+pub struct TokenCounter {
+    // Model name prefixes and their tokenizers, longest prefix wins.
+    tokenizers: Vec<(String, Arc<dyn Tokenizer>)>,
+}
+
+impl TokenCounter {
+    /// Creates a counter that estimates for every model.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses `tokenizer` for models whose names start with `model_prefix`, such as
+    /// `"gpt-4"`.
+    #[must_use]
+    pub fn with(mut self, model_prefix: impl Into<String>, tokenizer: Arc<dyn Tokenizer>) -> Self {
+        self.tokenizers.push((model_prefix.into(), tokenizer));
+        self
+    }
+
+    /// Counts the tokens in `text` for `model`.
+    pub fn count_tokens(&self, model: &str, text: &str) -> usize {
+        self.tokenizer(model)
+            .map_or_else(|| Estimate.count(text), |t| t.count(text))
+    }
+
+    /// Counts the tokens `messages` cost as a chat request to `model`, including the
+    /// per-message framing and the tokens that prime the reply.
+    pub fn count_messages(&self, model: &str, messages: &[ChatMessage]) -> usize {
+        REPLY_OVERHEAD
+            + messages
+                .iter()
+                .map(|m| self.count_message(model, m))
+                .sum::<usize>()
+    }
+
+    /// Counts the tokens one message costs, including its framing and any tool calls.
+    pub fn count_message(&self, model: &str, message: &ChatMessage) -> usize {
+        let calls: usize = message
+            .tool_calls
+            .iter()
+            .map(|call| {
+                self.count_tokens(model, &call.name) + self.count_tokens(model, &call.arguments)
+            })
+            .sum();
+        MESSAGE_OVERHEAD + self.count_tokens(model, &message.content) + calls
+    }
+
+    fn tokenizer(&self, model: &str) -> Option<&dyn Tokenizer> {
+        self.tokenizers
+            .iter()
+            .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, tokenizer)| tokenizer.as_ref())
+    }
+}
+
+impl fmt::Debug for TokenCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefixes: Vec<_> = self.tokenizers.iter().map(|(p, _)| p).collect();
+        f.debug_struct("TokenCounter")
+            .field("models", &prefixes)
+            .finish()
+    }
+}
+
+/// Estimates the tokens in `text` for `model`.
+///
+/// Shorthand for [`TokenCounter::count_tokens`] on a counter with no vocabularies; build
+/// a [`TokenCounter`] to count exactly.
+pub fn count_tokens(model: &str, text: &str) -> usize {
+    TokenCounter::new().count_tokens(model, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Words;
+
+    impl Tokenizer for Words {
+        fn count(&self, text: &str) -> usize {
+            text.split_whitespace().count()
+        }
+    }
+
+    #[test]
+    fn estimates_conservatively() {
+        assert_eq!(Estimate.count(""), 0);
+        assert_eq!(Estimate.count("the cat sat."), 4);
+        assert_eq!(Estimate.count("internationalization"), 5);
+    }
+
+    #[test]
+    fn picks_the_longest_matching_prefix() {
+        let counter = TokenCounter::new()
+            .with("gpt", Arc::new(Words))
+            .with("gpt-4o", Arc::new(Estimate));
+        assert_eq!(
+            counter.count_tokens("gpt-3.5-turbo", "internationalization"),
+            1
+        );
+        assert_eq!(
+            counter.count_tokens("gpt-4o-mini", "internationalization"),
+            5
+        );
+        assert_eq!(counter.count_tokens("claude", "internationalization"), 5);
+        assert_eq!(
+            counter.count_messages("gpt-3.5", &[ChatMessage::user("a b")]),
+            3 + 4 + 2
+        );
+    }
+}
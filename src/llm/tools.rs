@@ -0,0 +1,296 @@
+//! Running the tools a model calls.
+
+use std::{collections::HashMap, fmt, future::Future, pin::Pin, sync::Arc};
+
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+use super::{ChatMessage, ChatProvider, ChatRequest, Completion, LlmError, Tool, ToolCall};
+
+// Completions requested by `Toolbox::run` before giving up on a final answer.
+const DEFAULT_MAX_ROUNDS: usize = 8;
+
+type BoxedTool =
+    Arc<dyn Fn(Value) -> Pin<Box<dyn Future<Output = Result<Value, String>> + Send>> + Send + Sync>;
+
+/// Tools backed by async Rust functions, and the loop that lets a model use them.
+///
+/// A tool's result is sent back to the model as JSON. Failures — an unknown tool,
+/// arguments that do not decode, or an error from the function — are sent back as
+/// `{"error": "…"}` so the model can correct itself.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rttp::llm::{ChatMessage, ChatRequest, LlmClient, Tool, Toolbox};
+/// use serde::Deserialize;
+/// use serde_json::json;
+///
+/// #[derive(Deserialize)]
+/// struct Weather {
+///     city: String,
+/// }
+///
+/// # async fn run() -> Result<(), rttp::llm::LlmError> {
+/// let mut tools = Toolbox::new();
+/// tools.register(
+///     Tool::new(
+///         "get_weather",
+///         "Current temperature for a city, in °C.",
+///         json!({
+///             "type": "object",
+///             "properties": { "city": { "type": "string" } },
+///             "required": ["city"],
+///         }),
+///     ),
+///     |args: Weather| async move { Ok::<_, String>(json!({ "city": args.city, "celsius": 4 })) },
+/// );
+///
+/// let client = LlmClient::openai("http://127.0.0.1:11434/v1");
+/// let mut request = ChatRequest::new("llama3.1").message(ChatMessage::user("Is it cold in Oslo?"));
+/// let answer = tools.run(&client, &mut request).await?;
+/// println!("{}", answer.text);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Toolbox {
+    tools: Vec<Tool>,
+    handlers: HashMap<String, BoxedTool>,
+    max_rounds: usize,
+}
+
+impl Default for Toolbox {
+    fn default() -> Self {
+        Self {
+            tools: Vec::new(),
+            handlers: HashMap::new(),
+            max_rounds: DEFAULT_MAX_ROUNDS,
+        }
+    }
+}
+
+impl Toolbox {
+    /// Creates an empty toolbox that allows eight rounds of tool calls.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how many completions [`run`](Self::run) requests before giving up.
+    #[must_use]
+    pub fn max_rounds(mut self, rounds: usize) -> Self {
+        self.max_rounds = rounds;
+        self
+    }
+
+    /// Registers `tool`, run by `handler`, replacing any earlier tool with its name.
+    ///
+    /// The handler receives the call's arguments decoded as `T`.
+    pub fn register<T, R, E, F, Fut>(&mut self, tool: Tool, handler: F)
+    where
+        T: DeserializeOwned + Send + 'static,
+        R: Serialize,
+        E: fmt::Display,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, E>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let boxed: BoxedTool = Arc::new(move |arguments| {
+            let handler = Arc::clone(&handler);
+            Box::pin(async move {
+                let input = serde_json::from_value(arguments)
+                    .map_err(|e| format!("invalid arguments: {e}"))?;
+                let output = handler(input).await.map_err(|e| e.to_string())?;
+                serde_json::to_value(output).map_err(|e| e.to_string())
+            })
+        });
+        self.tools.retain(|t| t.name != tool.name);
+        self.handlers.insert(tool.name.clone(), boxed);
+        self.tools.push(tool);
+    }
+
+    /// Returns the registered tool definitions.
+    pub fn definitions(&self) -> &[Tool] {
+        &self.tools
+    }
+
+    /// Runs one call and returns its result as the text sent back to the model.
+    pub async fn call(&self, call: &ToolCall) -> String {
+        let result = match self.handlers.get(&call.name) {
+            None => Err(format!("unknown tool {:?}", call.name)),
+            Some(handler) => match serde_json::from_str(&call.arguments) {
+                Ok(arguments) => handler(arguments).await,
+                Err(e) => Err(format!("arguments are not JSON: {e}")),
+            },
+        };
+        match result {
+            Ok(value) => value.to_string(),
+            Err(e) => json!({ "error": e }).to_string(),
+        }
+    }
+
+    /// Completes `request` with `provider`, running the tools the model calls and sending their results
+    /// back until it answers without calling any.
+    ///
+    /// The registered tools are offered alongside any already on the request. The tool
+    /// calls, their results, and the final answer are appended to `request`, so it can be
+    /// sent again with the user's next message.
+    ///
+    /// # Errors
+    ///
+    /// Returns the client's errors, or [`LlmError::ToolRounds`] if the model is still
+    /// calling tools after the allowed number of rounds.
+    pub async fn run(
+        &self,
+        provider: &dyn ChatProvider,
+        request: &mut ChatRequest,
+    ) -> Result<Completion, LlmError> {
+        for tool in &self.tools {
+            if !request.tools.iter().any(|t| t.name == tool.name) {
+                request.tools.push(tool.clone());
+            }
+        }
+        for _ in 0..self.max_rounds {
+            let completion = provider.stream_chat(request).await?.complete().await?;
+            let reply = ChatMessage::assistant(completion.text.clone())
+                .with_tool_calls(completion.tool_calls.iter().cloned());
+            request.messages.push(reply);
+            if completion.tool_calls.is_empty() {
+                return Ok(completion);
+            }
+            for call in &completion.tool_calls {
+                let result = self.call(call).await;
+                request.messages.push(ChatMessage::tool(&call.id, result));
+            }
+        }
+        Err(LlmError::ToolRounds(self.max_rounds))
+    }
+}
+
+impl fmt::Debug for Toolbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<_> = self.tools.iter().map(|t| &t.name).collect();
+        f.debug_struct("Toolbox")
+            .field("tools", &names)
+            .field("max_rounds", &self.max_rounds)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::llm::{
+        LlmClient, Role,
+        transport::{StreamingResponse, Transport, TransportFuture},
+    };
+
+    // Answers with each body in turn and records the requests.
+    struct Script {
+        bodies: Mutex<Vec<&'static str>>,
+        seen: Mutex<Vec<Value>>,
+    }
+
+    impl Transport for Script {
+        fn post<'a>(
+            &'a self,
+            _url: &'a str,
+            _headers: &'a [(String, String)],
+            body: &'a [u8],
+        ) -> TransportFuture<'a, StreamingResponse> {
+            self.seen
+                .lock()
+                .unwrap()
+                .push(serde_json::from_slice(body).unwrap());
+            let body = self.bodies.lock().unwrap().remove(0).as_bytes().to_vec();
+            Box::pin(async move {
+                Ok(StreamingResponse {
+                    status: 200,
+                    body: Box::new(vec![body].into_iter()),
+                })
+            })
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct Add {
+        a: i64,
+        b: i64,
+    }
+
+    fn toolbox() -> Toolbox {
+        let mut tools = Toolbox::new();
+        tools.register(
+            Tool::new("add", "Adds two numbers.", json!({"type": "object"})),
+            |args: Add| async move { Ok::<_, String>(args.a + args.b) },
+        );
+        tools
+    }
+
+    const CALL: &str = "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"c1\",\"function\":{\"name\":\"add\",\"arguments\":\"{\\\"a\\\":2,\\\"b\\\":3}\"}}]},\"finish_reason\":\"tool_calls\"}]}\n\n\
+        data: [DONE]\n\n";
+    const ANSWER: &str = "data: {\"choices\":[{\"delta\":{\"content\":\"5\"},\"finish_reason\":\"stop\"}]}\n\n\
+        data: [DONE]\n\n";
+
+    #[tokio::test]
+    async fn reports_failures_to_the_model() {
+        let tools = toolbox();
+        let call = |name: &str, arguments: &str| ToolCall {
+            id: "c1".into(),
+            name: name.into(),
+            arguments: arguments.into(),
+        };
+        assert_eq!(tools.call(&call("add", "{\"a\":1,\"b\":2}")).await, "3");
+        assert!(
+            tools
+                .call(&call("add", "{\"a\":1}"))
+                .await
+                .contains("invalid arguments")
+        );
+        assert!(
+            tools
+                .call(&call("sub", "{}"))
+                .await
+                .contains("unknown tool")
+        );
+    }
+
+    #[tokio::test]
+    async fn loops_until_a_final_answer() {
+        let transport = Arc::new(Script {
+            bodies: Mutex::new(vec![CALL, ANSWER]),
+            seen: Mutex::default(),
+        });
+        let client = LlmClient::openai("http://llm.internal/v1").transport(transport.clone());
+        let mut request = ChatRequest::new("model-x").message(ChatMessage::user("2 + 3?"));
+        let completion = toolbox().run(&client, &mut request).await.unwrap();
+        assert_eq!(completion.text, "5");
+
+        let roles: Vec<Role> = request.conversation().iter().map(|m| m.role).collect();
+        assert_eq!(
+            roles,
+            [Role::User, Role::Assistant, Role::Tool, Role::Assistant]
+        );
+        assert_eq!(request.conversation()[2].content, "5");
+
+        let seen = transport.seen.lock().unwrap();
+        assert_eq!(seen[0]["tools"][0]["function"]["name"], "add");
+        assert_eq!(seen[1]["messages"][2]["tool_call_id"], "c1");
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_rounds() {
+        let transport = Arc::new(Script {
+            bodies: Mutex::new(vec![CALL, CALL]),
+            seen: Mutex::default(),
+        });
+        let client = LlmClient::openai("http://llm.internal/v1").transport(transport);
+        let mut request = ChatRequest::new("model-x").message(ChatMessage::user("2 + 3?"));
+        let result = toolbox().max_rounds(2).run(&client, &mut request).await;
+        assert!(matches!(result, Err(LlmError::ToolRounds(2))));
+    }
+}
@@ -0,0 +1,163 @@
+//! Outbound HTTP for completion requests, with streamed response bodies.
+
+use std::{future::Future, pin::Pin, time::Duration};
+
+use super::LlmError;
+use crate::http::{
+    Method,
+    client::{Client, ClientResponse},
+};
+
+/// Boxed future returned by [`Transport`] and [`BodyReader`] methods.
+pub type TransportFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, LlmError>> + Send + 'a>>;
+
+/// A response whose body is read as it arrives.
+pub struct StreamingResponse {
+    /// The status code.
+    pub status: u16,
+    /// The body, with any transfer encoding already removed.
+    pub body: Box<dyn BodyReader>,
+}
+
+/// A response body read piece by piece.
+pub trait BodyReader: Send {
+    /// Returns the next piece of the body, or `None` at its end.
+    fn chunk(&mut self) -> TransportFuture<'_, Option<Vec<u8>>>;
+}
+
+/// Sends completion requests to a provider.
+///
+/// Implement it to go through another HTTP client, for example one that pools
+/// connections or routes through an egress proxy.
+pub trait Transport: Send + Sync {
+    /// POSTs `body` to `url` with `headers`, resolving once the response head arrives.
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(String, String)],
+        body: &'a [u8],
+    ) -> TransportFuture<'a, StreamingResponse>;
+}
+
+/// A [`Transport`] over the shared [`Client`], for `http://` and, with the `tls`
+/// feature, `https://` endpoints.
+///
+/// The timeout bounds connecting and reading the response head, and then each wait for
+/// the next piece of the body, so a long completion is fine but a stalled one is not.
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    client: Client,
+}
+
+impl Default for HttpTransport {
+    fn default() -> Self {
+        Self::with_client(Client::new())
+    }
+}
+
+impl HttpTransport {
+    /// Creates a transport with a 60-second timeout.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a transport sending through `client`, for example one trusting a private
+    /// CA. The transport's timeout replaces the client's.
+    pub fn with_client(client: Client) -> Self {
+        Self {
+            client: client.timeout(Duration::from_secs(60)),
+        }
+    }
+
+    /// Sets how long to wait for the response head and between pieces of the body.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.client = self.client.timeout(timeout);
+        self
+    }
+}
+
+impl Transport for HttpTransport {
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(String, String)],
+        body: &'a [u8],
+    ) -> TransportFuture<'a, StreamingResponse> {
+        Box::pin(async move {
+            let response = self
+                .client
+                .request(Method::Post, url, headers, body)
+                .await?;
+            Ok(StreamingResponse {
+                status: response.status(),
+                body: Box::new(response),
+            })
+        })
+    }
+}
+
+impl BodyReader for ClientResponse {
+    fn chunk(&mut self) -> TransportFuture<'_, Option<Vec<u8>>> {
+        Box::pin(async move { Ok(ClientResponse::chunk(self).await?) })
+    }
+}
+
+// Canned bodies for tests.
+#[cfg(test)]
+impl BodyReader for std::vec::IntoIter<Vec<u8>> {
+    fn chunk(&mut self) -> TransportFuture<'_, Option<Vec<u8>>> {
+        let next = self.next();
+        Box::pin(async move { Ok(next) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    async fn serve(response: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1/chat", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf).await.unwrap();
+            stream.write_all(response).await.unwrap();
+        });
+        url
+    }
+
+    async fn read_body(mut body: Box<dyn BodyReader>) -> Vec<u8> {
+        let mut all = Vec::new();
+        while let Some(chunk) = body.chunk().await.unwrap() {
+            all.extend(chunk);
+        }
+        all
+    }
+
+    #[tokio::test]
+    async fn decodes_chunked_bodies() {
+        let url = serve(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+              5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\n\r\n",
+        )
+        .await;
+        let response = HttpTransport::new().post(&url, &[], b"{}").await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(read_body(response.body).await, b"hello, world");
+    }
+
+    #[tokio::test]
+    async fn reads_length_delimited_bodies() {
+        let url = serve(b"HTTP/1.1 429 Too Many Requests\r\nContent-Length: 4\r\n\r\nslow").await;
+        let response = HttpTransport::new().post(&url, &[], b"").await.unwrap();
+        assert_eq!(response.status, 429);
+        assert_eq!(read_body(response.body).await, b"slow");
+    }
+}
@@ -0,0 +1,597 @@
+//! Request and response body recording, for diagnosing client integrations.
+//!
+//! [`CaptureMiddleware`] records each exchange — the request line, headers, and body,
+//! and the response status, headers, and body — into the `tracing` output and, when
+//! given one, into a bounded [`TrafficCapture`] buffer that it can serve as JSON from a
+//! debug endpoint.
+//!
+//! Recording is opt-in and defensive: bodies are capped in size, only textual content
+//! types are kept, credentials in headers are masked, and fields such as `password`
+//! are masked in JSON bodies, form bodies, and query strings.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use rttp::middleware::{CaptureMiddleware, TrafficCapture, from_middleware};
+//!
+//! let capture = TrafficCapture::new(200);
+//! let handler = from_middleware(Arc::new(
+//!     CaptureMiddleware::new()
+//!         .buffer(capture.clone())
+//!         .endpoint("/debug/traffic")
+//!         .redact_field("card_number"),
+//! ));
+//! ```
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{Value, json};
+use tokio::time::Instant;
+
+use super::{Har, Middleware, Next};
+use crate::{
+    Headers, Method, Response, StatusCode, context::Context, http::ContentDisposition,
+    security::crypto::random_bytes,
+};
+
+const MASK: &str = "[redacted]";
+
+const DEFAULT_HEADERS: [&str; 6] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "x-signature",
+];
+
+const DEFAULT_FIELDS: [&str; 8] = [
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "access_token",
+    "refresh_token",
+    "api_key",
+    "client_secret",
+];
+
+const DEFAULT_CONTENT_TYPES: [&str; 5] = [
+    "text/",
+    "application/json",
+    "application/xml",
+    "application/x-www-form-urlencoded",
+    "application/graphql",
+];
+
+/// A recorded body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedBody {
+    /// The `Content-Type` header, if any.
+    pub content_type: Option<String>,
+    /// The full body size in bytes.
+    pub size: usize,
+    /// The redacted text, or `None` when the content type is not recorded or the body
+    /// was streamed.
+    pub text: Option<String>,
+    /// Whether `text` was cut at the size cap.
+    pub truncated: bool,
+}
+
+/// One recorded request and its response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exchange {
+    /// When the request reached the middleware.
+    pub started: SystemTime,
+    /// How long the rest of the pipeline took to respond.
+    pub duration: Duration,
+    /// The request method.
+    pub method: Method,
+    /// The path and redacted query string.
+    pub target: String,
+    /// The HTTP minor version: `1` for HTTP/1.1.
+    pub version: u8,
+    /// The request headers, with credentials masked.
+    pub request_headers: Vec<(String, String)>,
+    /// The request body.
+    pub request_body: CapturedBody,
+    /// The response status.
+    pub status: StatusCode,
+    /// The response headers, with credentials masked.
+    pub response_headers: Vec<(String, String)>,
+    /// The response body.
+    pub response_body: CapturedBody,
+}
+
+impl Exchange {
+    /// Renders the exchange as served by the debug endpoint.
+    pub fn to_json(&self) -> Value {
+        let headers = |headers: &[(String, String)]| -> Value {
+            headers
+                .iter()
+                .map(|(name, value)| json!({ "name": name, "value": value }))
+                .collect()
+        };
+        let body = |body: &CapturedBody| {
+            json!({
+                "content_type": body.content_type,
+                "size": body.size,
+                "text": body.text,
+                "truncated": body.truncated,
+            })
+        };
+        json!({
+            "started_at_ms": self
+                .started
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            "duration_ms": self.duration.as_secs_f64() * 1000.0,
+            "request": {
+                "method": self.method.as_str(),
+                "target": self.target,
+                "version": format!("HTTP/1.{}", self.version),
+                "headers": headers(&self.request_headers),
+                "body": body(&self.request_body),
+            },
+            "response": {
+                "status": self.status.as_u16(),
+                "headers": headers(&self.response_headers),
+                "body": body(&self.response_body),
+            },
+        })
+    }
+}
+
+/// A bounded, cheaply cloneable buffer of the most recent [`Exchange`]s.
+#[derive(Debug, Clone)]
+pub struct TrafficCapture {
+    inner: Arc<Mutex<Buffer>>,
+}
+
+#[derive(Debug)]
+struct Buffer {
+    capacity: usize,
+    exchanges: VecDeque<Exchange>,
+}
+
+impl TrafficCapture {
+    /// Creates a buffer keeping the last `capacity` exchanges.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Buffer {
+                capacity,
+                exchanges: VecDeque::with_capacity(capacity.min(1024)),
+            })),
+        }
+    }
+
+    /// Adds an exchange, evicting the oldest when full.
+    pub fn record(&self, exchange: Exchange) {
+        let mut buffer = self.lock();
+        if buffer.capacity == 0 {
+            return;
+        }
+        if buffer.exchanges.len() == buffer.capacity {
+            buffer.exchanges.pop_front();
+        }
+        buffer.exchanges.push_back(exchange);
+    }
+
+    /// Returns the buffered exchanges, oldest first.
+    pub fn exchanges(&self) -> Vec<Exchange> {
+        self.lock().exchanges.iter().cloned().collect()
+    }
+
+    /// Empties the buffer.
+    pub fn clear(&self) {
+        self.lock().exchanges.clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Buffer> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Records request and response bodies into `tracing` and an optional buffer.
+///
+/// Defaults: bodies capped at 4 KiB, `text/*`, JSON, XML, form, and GraphQL bodies
+/// recorded, common credential headers and fields masked, and each exchange logged at
+/// `DEBUG` level. The middleware exposes what clients send: enable it deliberately, and
+/// protect the [`endpoint`](Self::endpoint) like any admin route.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct CaptureMiddleware {
+    max_body: usize,
+    content_types: Vec<String>,
+    redact_headers: Vec<String>,
+    redact_fields: Vec<String>,
+    buffer: Option<TrafficCapture>,
+    endpoint: Option<String>,
+    har_endpoint: Option<String>,
+    sample: f64,
+    log: bool,
+}
+
+impl CaptureMiddleware {
+    /// Creates the middleware with the defaults above.
+    pub fn new() -> Self {
+        Self {
+            max_body: 4096,
+            content_types: DEFAULT_CONTENT_TYPES.map(str::to_owned).to_vec(),
+            redact_headers: DEFAULT_HEADERS.map(str::to_owned).to_vec(),
+            redact_fields: DEFAULT_FIELDS.map(str::to_owned).to_vec(),
+            buffer: None,
+            endpoint: None,
+            har_endpoint: None,
+            sample: 1.0,
+            log: true,
+        }
+    }
+
+    /// Caps each recorded body at `bytes`.
+    pub fn max_body(mut self, bytes: usize) -> Self {
+        self.max_body = bytes;
+        self
+    }
+
+    /// Also records bodies whose media type starts with `prefix`, e.g. `application/csv`.
+    /// Types ending in `+json` or `+xml` are always recorded.
+    pub fn content_type(mut self, prefix: impl Into<String>) -> Self {
+        self.content_types.push(prefix.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Also masks header `name`.
+    pub fn redact_header(mut self, name: impl Into<String>) -> Self {
+        self.redact_headers.push(name.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Also masks field `name` in JSON bodies, form bodies, and query strings.
+    pub fn redact_field(mut self, name: impl Into<String>) -> Self {
+        self.redact_fields.push(name.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Keeps exchanges in `buffer`.
+    pub fn buffer(mut self, buffer: TrafficCapture) -> Self {
+        self.buffer = Some(buffer);
+        self
+    }
+
+    /// Serves the [buffer](Self::buffer) as a JSON array for `GET path`; the endpoint's
+    /// own requests are not recorded.
+    pub fn endpoint(mut self, path: impl Into<String>) -> Self {
+        self.endpoint = Some(path.into());
+        self
+    }
+
+    /// Serves the [buffer](Self::buffer) as a [HAR](super::Har) download for `GET path`.
+    pub fn har_endpoint(mut self, path: impl Into<String>) -> Self {
+        self.har_endpoint = Some(path.into());
+        self
+    }
+
+    /// Records only a random `rate` of requests, from `0.0` to `1.0` (the default).
+    pub fn sample(mut self, rate: f64) -> Self {
+        self.sample = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Turns the `DEBUG` log line per exchange off or on (the default).
+    pub fn log(mut self, log: bool) -> Self {
+        self.log = log;
+        self
+    }
+
+    fn headers(&self, headers: &Headers) -> Vec<(String, String)> {
+        headers
+            .iter()
+            .map(|(name, value)| {
+                let masked = self
+                    .redact_headers
+                    .iter()
+                    .any(|h| h.eq_ignore_ascii_case(name));
+                let value = if masked { MASK } else { value };
+                (name.to_owned(), value.to_owned())
+            })
+            .collect()
+    }
+
+    fn body(&self, headers: &Headers, body: &[u8], streamed: bool) -> CapturedBody {
+        let content_type = headers.get("Content-Type").map(str::to_owned);
+        let media = content_type
+            .as_deref()
+            .and_then(|t| t.split(';').next())
+            .map(|t| t.trim().to_ascii_lowercase());
+        let textual = media.as_deref().is_some_and(|media| {
+            media.ends_with("+json")
+                || media.ends_with("+xml")
+                || self
+                    .content_types
+                    .iter()
+                    .any(|p| media.starts_with(p.as_str()))
+        });
+        let mut captured = CapturedBody {
+            content_type,
+            size: body.len(),
+            text: None,
+            truncated: false,
+        };
+        if streamed || !(textual || body.is_empty()) {
+            return captured;
+        }
+        let text = String::from_utf8_lossy(body);
+        let mut text = match media.as_deref() {
+            Some(media) if media.ends_with("json") => match serde_json::from_str(&text) {
+                Ok(mut value) => {
+                    self.redact_json(&mut value);
+                    value.to_string()
+                }
+                Err(_) => text.into_owned(),
+            },
+            Some("application/x-www-form-urlencoded") => self.redact_pairs(&text),
+            _ => text.into_owned(),
+        };
+        if text.len() > self.max_body {
+            let mut end = self.max_body;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+            captured.truncated = true;
+        }
+        captured.text = Some(text);
+        captured
+    }
+
+    fn is_secret(&self, field: &str) -> bool {
+        self.redact_fields
+            .iter()
+            .any(|f| f.eq_ignore_ascii_case(field))
+    }
+
+    fn redact_json(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if self.is_secret(key) {
+                        *value = Value::String(MASK.to_owned());
+                    } else {
+                        self.redact_json(value);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|v| self.redact_json(v)),
+            _ => {}
+        }
+    }
+
+    // Masks secret values in `a=1&b=2` pairs, leaving the encoding otherwise untouched.
+    fn redact_pairs(&self, pairs: &str) -> String {
+        pairs
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((key, _)) if self.is_secret(key) => format!("{key}={MASK}"),
+                _ => pair.to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+}
+
+// Draws whether to record a request, with probability `rate`.
+fn sampled(rate: f64) -> bool {
+    let random = u64::from_le_bytes(random_bytes(8).try_into().expect("8 random bytes"));
+    (random as f64 / u64::MAX as f64) < rate
+}
+
+impl Default for CaptureMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Middleware for CaptureMiddleware {
+    fn handle(&self, ctx: Context, next: Next) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let request = ctx.request();
+        if *request.method() == Method::Get && self.endpoint.as_deref() == Some(request.path()) {
+            let exchanges: Vec<Value> = self
+                .buffer
+                .as_ref()
+                .map(|buffer| buffer.exchanges().iter().map(Exchange::to_json).collect())
+                .unwrap_or_default();
+            return Box::pin(async move {
+                Response::new(StatusCode::Ok)
+                    .header("Content-Type", "application/json")
+                    .header("Cache-Control", "no-store")
+                    .body(Value::Array(exchanges).to_string())
+            });
+        }
+        if *request.method() == Method::Get && self.har_endpoint.as_deref() == Some(request.path())
+        {
+            let exchanges = self
+                .buffer
+                .as_ref()
+                .map(TrafficCapture::exchanges)
+                .unwrap_or_default();
+            let har = Har::new().render(&exchanges);
+            return Box::pin(async move {
+                Response::new(StatusCode::Ok)
+                    .header("Content-Type", "application/json")
+                    .header("Cache-Control", "no-store")
+                    .disposition(&ContentDisposition::attachment().filename("traffic.har"))
+                    .body(har.to_string())
+            });
+        }
+        if self.sample < 1.0 && !sampled(self.sample) {
+            return next.run(ctx);
+        }
+
+        let started = SystemTime::now();
+        let target = match request.query_string() {
+            Some(query) => format!("{}?{}", request.path(), self.redact_pairs(query)),
+            None => request.path().to_owned(),
+        };
+        let method = request.method().clone();
+        let version = request.version();
+        let request_headers = self.headers(request.headers());
+        let request_body = self.body(request.headers(), request.body(), false);
+        let this = self.clone();
+        Box::pin(async move {
+            let clock = Instant::now();
+            let response = next.run(ctx).await;
+            let exchange = Exchange {
+                started,
+                duration: clock.elapsed(),
+                method,
+                target,
+                version,
+                request_headers,
+                request_body,
+                status: response.status(),
+                response_headers: this.headers(response.headers()),
+                response_body: this.body(
+                    response.headers(),
+                    response.body_ref(),
+                    response.is_streamed(),
+                ),
+            };
+            if this.log {
+                tracing::debug!(
+                    method = %exchange.method,
+                    target = %exchange.target,
+                    status = exchange.status.as_u16(),
+                    request_body = exchange.request_body.text.as_deref().unwrap_or("<not recorded>"),
+                    response_body = exchange.response_body.text.as_deref().unwrap_or("<not recorded>"),
+                    "captured exchange"
+                );
+            }
+            if let Some(buffer) = &this.buffer {
+                buffer.record(exchange);
+            }
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Request,
+        middleware::{MiddlewareHandler, from_middleware},
+    };
+
+    async fn call(middleware: CaptureMiddleware, raw: &str) -> Response {
+        let ctx = Context::new(Request::parse(raw.as_bytes()).unwrap().0);
+        let handler: MiddlewareHandler = Arc::new(|_ctx: Context, _next: Next| {
+            Box::pin(async {
+                Response::new(StatusCode::Created)
+                    .header("Content-Type", "application/json")
+                    .header("Set-Cookie", "sid=abc")
+                    .body(r#"{"id":7,"token":"t0k3n","items":[{"secret":1}]}"#)
+            })
+        });
+        Next::new(vec![from_middleware(Arc::new(middleware)), handler])
+            .run(ctx)
+            .await
+    }
+
+    #[tokio::test]
+    async fn records_redacted_exchanges_and_serves_them() {
+        let capture = TrafficCapture::new(1);
+        let middleware = CaptureMiddleware::new()
+            .buffer(capture.clone())
+            .endpoint("/debug/traffic")
+            .redact_field("pin")
+            .max_body(20);
+
+        let body = "user=ann&password=hunter2&pin=1234";
+        let raw = format!(
+            "POST /login?next=%2F&token=abc HTTP/1.1\r\nAuthorization: Bearer x\r\n\
+             Content-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        call(middleware.clone(), &raw).await;
+
+        let exchange = &capture.exchanges()[0];
+        assert_eq!(exchange.target, "/login?next=%2F&token=[redacted]");
+        assert!(
+            exchange
+                .request_headers
+                .contains(&("Authorization".to_owned(), "[redacted]".to_owned()))
+        );
+        assert_eq!(exchange.request_body.size, body.len());
+        assert_eq!(
+            exchange.request_body.text.as_deref(),
+            Some("user=ann&password=[r")
+        );
+        assert!(exchange.request_body.truncated);
+        assert_eq!(exchange.status, StatusCode::Created);
+        assert!(
+            exchange
+                .response_headers
+                .contains(&("Set-Cookie".to_owned(), "[redacted]".to_owned()))
+        );
+
+        // A larger cap shows the masked JSON response.
+        let middleware = middleware.max_body(4096);
+        call(middleware.clone(), "GET /orders HTTP/1.1\r\n\r\n").await;
+        let exchanges = capture.exchanges();
+        assert_eq!(exchanges.len(), 1);
+        assert_eq!(
+            exchanges[0].response_body.text.as_deref(),
+            Some(r#"{"id":7,"items":[{"secret":"[redacted]"}],"token":"[redacted]"}"#)
+        );
+
+        let res = call(middleware, "GET /debug/traffic HTTP/1.1\r\n\r\n").await;
+        let served: Value = serde_json::from_slice(res.body_ref()).unwrap();
+        assert_eq!(served[0]["request"]["target"], "/orders");
+        assert_eq!(served[0]["response"]["status"], 201);
+        assert_eq!(capture.exchanges().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn samples_and_serves_har() {
+        let capture = TrafficCapture::new(10);
+        let middleware = CaptureMiddleware::new()
+            .buffer(capture.clone())
+            .har_endpoint("/debug/traffic.har");
+        call(
+            middleware.clone().sample(0.0),
+            "GET /skipped HTTP/1.1\r\n\r\n",
+        )
+        .await;
+        assert!(capture.exchanges().is_empty());
+
+        call(middleware.clone(), "GET /kept HTTP/1.1\r\nHost: x\r\n\r\n").await;
+        let res = call(middleware, "GET /debug/traffic.har HTTP/1.1\r\n\r\n").await;
+        assert_eq!(
+            res.headers().get("Content-Disposition"),
+            Some("attachment; filename=\"traffic.har\"")
+        );
+        let har: Value = serde_json::from_slice(res.body_ref()).unwrap();
+        assert_eq!(har["log"]["entries"][0]["request"]["url"], "http://x/kept");
+    }
+
+    #[tokio::test]
+    async fn skips_binary_bodies() {
+        let capture = TrafficCapture::new(10);
+        let raw =
+            "PUT /avatar HTTP/1.1\r\nContent-Type: image/png\r\nContent-Length: 4\r\n\r\nPNG!";
+        call(CaptureMiddleware::new().buffer(capture.clone()), raw).await;
+        let body = &capture.exchanges()[0].request_body;
+        assert_eq!(body.content_type.as_deref(), Some("image/png"));
+        assert_eq!(body.size, 4);
+        assert_eq!(body.text, None);
+    }
+}
@@ -0,0 +1,468 @@
+//! Limits on how many requests are handled at once.
+//!
+//! [`ConcurrencyLimitMiddleware`] holds a permit for each request while the rest of the
+//! pipeline handles it, from one global pool and from the pool of the first route
+//! pattern the path matches. By default a request that finds its pool empty is shed at
+//! once with `503 Service Unavailable` and a `Retry-After` header, so a slow endpoint
+//! can fill only its own pool and the rest of the application stays responsive.
+//!
+//! With an admission [`queue`](ConcurrencyLimitMiddleware::queue), requests wait for a
+//! permit instead, and each freed permit goes to the waiting request with the highest
+//! [`Priority`] — first come, first served within a class. When the queue is full, a
+//! request evicts the newest waiter of a lower class, so health checks and payments
+//! keep getting through while bulk traffic backs up.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::{sync::Arc, time::Duration};
+//! use rttp::middleware::{ConcurrencyLimitMiddleware, Priority, from_middleware};
+//!
+//! let limits = ConcurrencyLimitMiddleware::new()
+//!     .global(512)
+//!     .route("/reports/:id", 4)
+//!     .route("/exports/*", 2)
+//!     .queue(1024, Duration::from_secs(2))
+//!     .priority("/health", Priority::Critical)
+//!     .priority("/payments/*", Priority::High)
+//!     .priority("/exports/*", Priority::Low)
+//!     .retry_after(Duration::from_secs(5));
+//! let handler = from_middleware(Arc::new(limits));
+//! ```
+
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use tokio::sync::oneshot;
+
+use super::{Middleware, Next};
+use crate::{Response, StatusCode, context::Context, router::Pattern};
+
+/// How urgently a request should be admitted when concurrency limits are reached.
+///
+/// Assigned by route with [`ConcurrencyLimitMiddleware::priority`], or per request by
+/// inserting a `Priority` into the context's extensions ahead of the middleware, which
+/// takes precedence.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Bulk work that can wait, such as exports.
+    Low,
+    /// Ordinary traffic.
+    #[default]
+    Normal,
+    /// Traffic that should not wait behind ordinary requests, such as payments.
+    High,
+    /// Traffic that must get through, such as health checks.
+    Critical,
+}
+
+// How requests wait when a limit is reached.
+#[derive(Debug, Clone, Copy)]
+struct Queue {
+    capacity: usize,
+    max_wait: Duration,
+}
+
+// Waiters ordered by priority, highest first, then by arrival.
+type WaitKey = (Reverse<Priority>, u64);
+
+#[derive(Debug, Default)]
+struct State {
+    in_flight: usize,
+    waiting: BTreeMap<WaitKey, oneshot::Sender<()>>,
+    next_seq: u64,
+}
+
+// A pool of permits with a priority queue of waiting requests.
+#[derive(Debug)]
+struct Limit {
+    max: usize,
+    state: Mutex<State>,
+}
+
+impl Limit {
+    fn new(max: usize) -> Arc<Self> {
+        Arc::new(Self {
+            max,
+            state: Mutex::new(State::default()),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Takes a permit, waiting in the queue for one if allowed; `None` means shed.
+    async fn acquire(self: &Arc<Self>, priority: Priority, queue: Queue) -> Option<Permit> {
+        let (key, receiver) = {
+            let mut state = self.lock();
+            if state.in_flight < self.max && state.waiting.is_empty() {
+                state.in_flight += 1;
+                return Some(Permit(Arc::clone(self)));
+            }
+            if state.waiting.len() >= queue.capacity {
+                // Dropping the evicted waiter's sender sheds it.
+                let (&(Reverse(lowest), _), _) = state.waiting.last_key_value()?;
+                if lowest >= priority {
+                    return None;
+                }
+                state.waiting.pop_last();
+            }
+            let key = (Reverse(priority), state.next_seq);
+            state.next_seq += 1;
+            let (sender, receiver) = oneshot::channel();
+            state.waiting.insert(key, sender);
+            (key, receiver)
+        };
+        let mut waiter = Waiter {
+            limit: self,
+            key,
+            receiver,
+        };
+        match tokio::time::timeout(queue.max_wait, &mut waiter.receiver).await {
+            Ok(Ok(())) => Some(Permit(Arc::clone(self))),
+            _ => None,
+        }
+    }
+
+    // Hands the permit to the next live waiter, or returns it to the pool.
+    fn release(&self) {
+        let mut state = self.lock();
+        while let Some((_, sender)) = state.waiting.pop_first() {
+            if sender.send(()).is_ok() {
+                return;
+            }
+        }
+        state.in_flight -= 1;
+    }
+
+    fn in_flight(&self) -> usize {
+        self.lock().in_flight
+    }
+
+    fn queued(&self) -> usize {
+        self.lock().waiting.len()
+    }
+}
+
+// Held while a request runs; releasing it admits the next waiter.
+struct Permit(Arc<Limit>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+// A place in the queue. Leaving it, by timing out or being cancelled, gives back a
+// permit that was handed over but never taken up.
+struct Waiter<'a> {
+    limit: &'a Arc<Limit>,
+    key: WaitKey,
+    receiver: oneshot::Receiver<()>,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        self.limit.lock().waiting.remove(&self.key);
+        if self.receiver.try_recv().is_ok() {
+            self.limit.release();
+        }
+    }
+}
+
+/// Limits how many requests are handled at once, globally and per route, shedding or
+/// queueing the rest.
+///
+/// Route limits and priorities are matched against the request path with the
+/// [`Router`] pattern syntax, in the order they were added; only the first match of
+/// each applies. A request counts against its route's limit and the global one until
+/// the rest of the pipeline returns its response. Without any limits every request
+/// passes. A shed request gets `503 Service Unavailable` with `Retry-After`.
+///
+/// [`Router`]: crate::Router
+#[derive(Debug)]
+#[must_use]
+pub struct ConcurrencyLimitMiddleware {
+    global: Option<Arc<Limit>>,
+    routes: Vec<(String, Pattern, Arc<Limit>)>,
+    priorities: Vec<(Pattern, Priority)>,
+    queue: Queue,
+    retry_after: Duration,
+}
+
+impl ConcurrencyLimitMiddleware {
+    /// Creates the middleware with no limits, no queue, and a `Retry-After` of one
+    /// second.
+    pub fn new() -> Self {
+        Self {
+            global: None,
+            routes: Vec::new(),
+            priorities: Vec::new(),
+            queue: Queue {
+                capacity: 0,
+                max_wait: Duration::ZERO,
+            },
+            retry_after: Duration::from_secs(1),
+        }
+    }
+
+    /// Allows at most `max` requests through at once, across all routes.
+    pub fn global(mut self, max: usize) -> Self {
+        self.global = Some(Limit::new(max));
+        self
+    }
+
+    /// Allows at most `max` requests whose path matches `pattern` through at once,
+    /// e.g. `/reports/:id` or `/exports/*`.
+    pub fn route(mut self, pattern: &str, max: usize) -> Self {
+        self.routes
+            .push((pattern.to_owned(), Pattern::parse(pattern), Limit::new(max)));
+        self
+    }
+
+    /// Lets up to `capacity` requests per limit wait up to `max_wait` for a permit,
+    /// admitting them by [`Priority`], instead of shedding them at once.
+    pub fn queue(mut self, capacity: usize, max_wait: Duration) -> Self {
+        self.queue = Queue { capacity, max_wait };
+        self
+    }
+
+    /// Gives requests whose path matches `pattern` the priority class `priority`.
+    /// Other requests are [`Priority::Normal`].
+    pub fn priority(mut self, pattern: &str, priority: Priority) -> Self {
+        self.priorities.push((Pattern::parse(pattern), priority));
+        self
+    }
+
+    /// Sets the delay suggested to shed clients in `Retry-After`, rounded up to whole
+    /// seconds.
+    pub fn retry_after(mut self, delay: Duration) -> Self {
+        self.retry_after = delay;
+        self
+    }
+
+    /// Returns the number of requests currently holding a global permit.
+    pub fn in_flight(&self) -> usize {
+        self.global.as_deref().map_or(0, Limit::in_flight)
+    }
+
+    /// Returns the number of requests waiting for a global permit.
+    pub fn queued(&self) -> usize {
+        self.global.as_deref().map_or(0, Limit::queued)
+    }
+
+    /// Returns the number of requests currently holding a permit for `pattern`, as
+    /// passed to [`route`](Self::route).
+    pub fn route_in_flight(&self, pattern: &str) -> usize {
+        self.routes
+            .iter()
+            .find(|(name, ..)| name == pattern)
+            .map_or(0, |(.., limit)| limit.in_flight())
+    }
+
+    fn priority_of(&self, ctx: &Context) -> Priority {
+        if let Some(priority) = ctx.extensions().get::<Priority>() {
+            return *priority;
+        }
+        let path = ctx.request().path();
+        self.priorities
+            .iter()
+            .find(|(pattern, _)| pattern.matches(path).is_some())
+            .map_or(Priority::Normal, |(_, priority)| *priority)
+    }
+}
+
+impl Default for ConcurrencyLimitMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Middleware for ConcurrencyLimitMiddleware {
+    fn handle(&self, ctx: Context, next: Next) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let path = ctx.request().path();
+        let route = self
+            .routes
+            .iter()
+            .find(|(_, pattern, _)| pattern.matches(path).is_some())
+            .map(|(pattern, _, limit)| (pattern.clone(), Arc::clone(limit)));
+        if route.is_none() && self.global.is_none() {
+            return next.run(ctx);
+        }
+        let global = self.global.clone();
+        let priority = self.priority_of(&ctx);
+        let (queue, retry_after) = (self.queue, self.retry_after);
+        Box::pin(async move {
+            // The narrower limit is taken first, so a request shed by its route does
+            // not hold a global permit meanwhile.
+            let route_permit = match &route {
+                Some((pattern, limit)) => match limit.acquire(priority, queue).await {
+                    Some(permit) => Some(permit),
+                    None => {
+                        tracing::warn!(route = %pattern, ?priority, "route concurrency limit reached");
+                        return overloaded(retry_after);
+                    }
+                },
+                None => None,
+            };
+            let global_permit = match &global {
+                Some(limit) => match limit.acquire(priority, queue).await {
+                    Some(permit) => Some(permit),
+                    None => {
+                        tracing::warn!(?priority, "global concurrency limit reached");
+                        return overloaded(retry_after);
+                    }
+                },
+                None => None,
+            };
+            let response = next.run(ctx).await;
+            drop((route_permit, global_permit));
+            response
+        })
+    }
+}
+
+fn overloaded(retry_after: Duration) -> Response {
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    Response::new(StatusCode::ServiceUnavailable)
+        .header("Retry-After", seconds.to_string())
+        .body("Too many concurrent requests")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Request,
+        middleware::{MiddlewareHandler, from_middleware},
+    };
+    use tokio::sync::Notify;
+
+    fn request(path: &str) -> Context {
+        Context::new(Request::builder().path(path).build())
+    }
+
+    // Answers with the request path once `release` is notified.
+    fn blocking_handler(release: &Arc<Notify>) -> MiddlewareHandler {
+        let release = Arc::clone(release);
+        Arc::new(move |ctx: Context, _next: Next| {
+            let release = Arc::clone(&release);
+            Box::pin(async move {
+                release.notified().await;
+                Response::new(StatusCode::Ok).body(ctx.request().path().to_owned())
+            })
+        })
+    }
+
+    #[tokio::test]
+    async fn sheds_requests_over_the_route_and_global_limits() {
+        let release = Arc::new(Notify::new());
+        let limits = Arc::new(
+            ConcurrencyLimitMiddleware::new()
+                .global(2)
+                .route("/slow/:id", 1)
+                .retry_after(Duration::from_millis(1500)),
+        );
+        let chain: Arc<[MiddlewareHandler]> = vec![
+            from_middleware(Arc::clone(&limits)),
+            blocking_handler(&release),
+        ]
+        .into();
+        let send = |path: &str| Next::new(Arc::clone(&chain)).run(request(path));
+
+        let slow = tokio::spawn(send("/slow/1"));
+        tokio::task::yield_now().await;
+        assert_eq!(limits.route_in_flight("/slow/:id"), 1);
+
+        let shed = send("/slow/2").await;
+        assert_eq!(shed.status(), StatusCode::ServiceUnavailable);
+        assert_eq!(shed.headers().get("Retry-After"), Some("2"));
+        assert_eq!(limits.in_flight(), 1, "shed requests hold no global permit");
+
+        let fast = tokio::spawn(send("/fast"));
+        tokio::task::yield_now().await;
+        assert_eq!(limits.in_flight(), 2);
+        let shed = send("/other").await;
+        assert_eq!(shed.status(), StatusCode::ServiceUnavailable);
+
+        release.notify_waiters();
+        assert_eq!(slow.await.unwrap().status(), StatusCode::Ok);
+        assert_eq!(fast.await.unwrap().status(), StatusCode::Ok);
+        assert_eq!(
+            (limits.in_flight(), limits.route_in_flight("/slow/:id")),
+            (0, 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn admits_queued_requests_by_priority() {
+        let limit = Limit::new(1);
+        let queue = Queue {
+            capacity: 2,
+            max_wait: Duration::from_secs(10),
+        };
+        let held = limit.acquire(Priority::Normal, queue).await.unwrap();
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let wait = |priority: Priority| {
+            let (limit, order) = (Arc::clone(&limit), Arc::clone(&order));
+            tokio::spawn(async move {
+                let permit = limit.acquire(priority, queue).await;
+                order.lock().unwrap().push((priority, permit.is_some()));
+                tokio::task::yield_now().await;
+            })
+        };
+        let low = wait(Priority::Low);
+        let normal = wait(Priority::Normal);
+        tokio::task::yield_now().await;
+        assert_eq!(limit.queued(), 2);
+
+        // The queue is full: a critical request evicts the newest lowest waiter, and
+        // another low one is shed outright.
+        let critical = wait(Priority::Critical);
+        tokio::task::yield_now().await;
+        assert!(limit.acquire(Priority::Low, queue).await.is_none());
+
+        drop(held);
+        for task in [low, normal, critical] {
+            task.await.unwrap();
+        }
+        assert_eq!(
+            *order.lock().unwrap(),
+            [
+                (Priority::Low, false),
+                (Priority::Critical, true),
+                (Priority::Normal, true),
+            ]
+        );
+        assert_eq!((limit.in_flight(), limit.queued()), (0, 0));
+
+        // Waiters give up after `max_wait`.
+        let held = limit.acquire(Priority::Normal, queue).await.unwrap();
+        let impatient = Queue {
+            max_wait: Duration::from_millis(10),
+            ..queue
+        };
+        assert!(limit.acquire(Priority::High, impatient).await.is_none());
+        assert_eq!(limit.queued(), 0);
+        drop(held);
+        assert_eq!(limit.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn reads_the_priority_from_extensions_first() {
+        let limits = ConcurrencyLimitMiddleware::new().priority("/health", Priority::Critical);
+        assert_eq!(limits.priority_of(&request("/health")), Priority::Critical);
+        assert_eq!(limits.priority_of(&request("/users")), Priority::Normal);
+        let mut ctx = request("/health");
+        ctx.extensions_mut().insert(Priority::Low);
+        assert_eq!(limits.priority_of(&ctx), Priority::Low);
+    }
+}
@@ -0,0 +1,282 @@
+//! HAR 1.2 export of captured traffic.
+//!
+//! [`Har`] turns the [`Exchange`]s recorded by [`CaptureMiddleware`] into an
+//! [HTTP Archive](http://www.softwareishard.com/blog/har-12-spec/), the format browser
+//! developer tools, proxies, and load-testing tools import to inspect or replay
+//! traffic. Write one to a file, or let the middleware serve the current buffer as a
+//! download with [`CaptureMiddleware::har_endpoint`].
+//!
+//! Bodies appear as recorded: redacted, capped, and missing for content types the
+//! middleware skips, with a `comment` on truncated ones.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rttp::middleware::{CaptureMiddleware, Har, TrafficCapture};
+//!
+//! let capture = TrafficCapture::new(500);
+//! // Record one request in a hundred.
+//! let middleware = CaptureMiddleware::new().buffer(capture.clone()).sample(0.01);
+//! // ... later ...
+//! Har::new()
+//!     .base_url("https://api.example.com")
+//!     .write(&capture.exchanges(), "traffic.har")?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! [`CaptureMiddleware`]: super::CaptureMiddleware
+//! [`CaptureMiddleware::har_endpoint`]: super::CaptureMiddleware::har_endpoint
+
+use std::{
+    fs, io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{Value, json};
+
+use super::capture::{CapturedBody, Exchange};
+
+/// Renders [`Exchange`]s as a HAR 1.2 log.
+#[derive(Debug, Clone, Default)]
+#[must_use]
+pub struct Har {
+    base_url: Option<String>,
+}
+
+impl Har {
+    /// Creates an exporter that builds request URLs from each request's `Host` header
+    /// over `http`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds request URLs from `url`, such as `https://api.example.com`, instead.
+    pub fn base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = Some(url.into().trim_end_matches('/').to_owned());
+        self
+    }
+
+    /// Returns the HAR document for `exchanges`.
+    pub fn render(&self, exchanges: &[Exchange]) -> Value {
+        json!({
+            "log": {
+                "version": "1.2",
+                "creator": { "name": "rttp", "version": env!("CARGO_PKG_VERSION") },
+                "entries": exchanges.iter().map(|e| self.entry(e)).collect::<Vec<_>>(),
+            }
+        })
+    }
+
+    /// Writes the HAR document for `exchanges` to `path`.
+    ///
+    /// # Errors
+    ///
+    /// The error from writing the file.
+    pub fn write(&self, exchanges: &[Exchange], path: impl AsRef<Path>) -> io::Result<()> {
+        let document = serde_json::to_vec_pretty(&self.render(exchanges))?;
+        fs::write(path, document)
+    }
+
+    fn entry(&self, exchange: &Exchange) -> Value {
+        let http_version = format!("HTTP/1.{}", exchange.version);
+        let base = match &self.base_url {
+            Some(base) => base.clone(),
+            None => {
+                let host = header(&exchange.request_headers, "Host").unwrap_or("localhost");
+                format!("http://{host}")
+            }
+        };
+        let query: Vec<Value> = exchange
+            .target
+            .split_once('?')
+            .map(|(_, query)| query)
+            .unwrap_or("")
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                json!({ "name": name, "value": value })
+            })
+            .collect();
+        let mut request = json!({
+            "method": exchange.method.as_str(),
+            "url": format!("{base}{}", exchange.target),
+            "httpVersion": http_version,
+            "cookies": [],
+            "headers": headers(&exchange.request_headers),
+            "queryString": query,
+            "headersSize": -1,
+            "bodySize": exchange.request_body.size,
+        });
+        if exchange.request_body.size > 0 {
+            let body = &exchange.request_body;
+            let mut post = json!({
+                "mimeType": body.content_type.as_deref().unwrap_or(""),
+                "text": body.text.as_deref().unwrap_or(""),
+            });
+            if let Some(comment) = comment(body) {
+                post["comment"] = json!(comment);
+            }
+            request["postData"] = post;
+        }
+
+        let body = &exchange.response_body;
+        let mut content = json!({
+            "size": body.size,
+            "mimeType": body.content_type.as_deref().unwrap_or(""),
+        });
+        if let Some(text) = &body.text {
+            content["text"] = json!(text);
+        }
+        if let Some(comment) = comment(body) {
+            content["comment"] = json!(comment);
+        }
+        let time = exchange.duration.as_secs_f64() * 1000.0;
+        json!({
+            "startedDateTime": iso8601(exchange.started),
+            "time": time,
+            "request": request,
+            "response": {
+                "status": exchange.status.as_u16(),
+                "statusText": exchange.status.canonical_reason(),
+                "httpVersion": http_version,
+                "cookies": [],
+                "headers": headers(&exchange.response_headers),
+                "content": content,
+                "redirectURL": header(&exchange.response_headers, "Location").unwrap_or(""),
+                "headersSize": -1,
+                "bodySize": body.size,
+            },
+            "cache": {},
+            "timings": { "send": 0, "wait": time, "receive": 0 },
+        })
+    }
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+fn headers(headers: &[(String, String)]) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect()
+}
+
+fn comment(body: &CapturedBody) -> Option<&'static str> {
+    if body.truncated {
+        Some("truncated by rttp capture")
+    } else if body.text.is_none() && body.size > 0 {
+        Some("not recorded by rttp capture")
+    } else {
+        None
+    }
+}
+
+// Formats `time` as `2024-01-31T12:00:00.000Z`.
+fn iso8601(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs();
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    // Civil-from-days, after Howard Hinnant's algorithm.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60,
+        since.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{Method, StatusCode};
+
+    fn exchange() -> Exchange {
+        Exchange {
+            started: UNIX_EPOCH + Duration::from_millis(1_709_210_096_789),
+            duration: Duration::from_millis(12),
+            method: Method::Post,
+            target: "/orders?id=7&dry".to_owned(),
+            version: 1,
+            request_headers: vec![
+                ("Host".to_owned(), "shop.local".to_owned()),
+                ("Content-Type".to_owned(), "application/json".to_owned()),
+            ],
+            request_body: CapturedBody {
+                content_type: Some("application/json".to_owned()),
+                size: 9,
+                text: Some(r#"{"n":1}"#.to_owned()),
+                truncated: true,
+            },
+            status: StatusCode::SeeOther,
+            response_headers: vec![("Location".to_owned(), "/orders/7".to_owned())],
+            response_body: CapturedBody {
+                content_type: None,
+                size: 0,
+                text: None,
+                truncated: false,
+            },
+        }
+    }
+
+    #[test]
+    fn renders_har_entries() {
+        let har = Har::new().render(&[exchange()]);
+        assert_eq!(har["log"]["version"], "1.2");
+        let entry = &har["log"]["entries"][0];
+        assert_eq!(entry["startedDateTime"], "2024-02-29T12:34:56.789Z");
+        assert_eq!(entry["time"], 12.0);
+        assert_eq!(entry["request"]["url"], "http://shop.local/orders?id=7&dry");
+        assert_eq!(
+            entry["request"]["queryString"],
+            json!([{ "name": "id", "value": "7" }, { "name": "dry", "value": "" }])
+        );
+        assert_eq!(entry["request"]["postData"]["text"], r#"{"n":1}"#);
+        assert_eq!(
+            entry["request"]["postData"]["comment"],
+            "truncated by rttp capture"
+        );
+        assert_eq!(entry["response"]["status"], 303);
+        assert_eq!(entry["response"]["statusText"], "See Other");
+        assert_eq!(entry["response"]["redirectURL"], "/orders/7");
+        assert!(entry["response"]["content"].get("text").is_none());
+
+        let har = Har::new()
+            .base_url("https://api.example.com/")
+            .render(&[exchange()]);
+        assert_eq!(
+            har["log"]["entries"][0]["request"]["url"],
+            "https://api.example.com/orders?id=7&dry"
+        );
+    }
+
+    #[test]
+    fn writes_files() {
+        let path = std::env::temp_dir().join(format!(
+            "rttp-har-{}.har",
+            crate::security::crypto::random_token(8)
+        ));
+        Har::new().write(&[exchange()], &path).unwrap();
+        let written: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(written, Har::new().render(&[exchange()]));
+    }
+}
@@ -0,0 +1,235 @@
+//! Health checks and the health endpoint.
+//!
+//! [`Health`] is a cheaply cloneable registry of named [`HealthCheck`]s. Subsystems such
+//! as the database pool provide their own checks; [`HealthMiddleware`] runs every check
+//! and serves the combined result as JSON, answering `503 Service Unavailable` when any
+//! check fails so load balancers take the instance out of rotation.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use rttp::middleware::{Health, HealthMiddleware, from_middleware};
+//!
+//! let health = Health::new();
+//! let handler = from_middleware(Arc::new(HealthMiddleware::new(health.clone())));
+//! ```
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use serde_json::{Map, Value, json};
+use tokio::time::Instant;
+
+use super::{Middleware, Next};
+use crate::{Method, Response, StatusCode, context::Context};
+
+/// Boxed future returned by [`HealthCheck::check`]; the error describes the failure.
+pub type HealthFuture<'a> = Pin<Box<dyn Future<Output = Result<(), String>> + Send + 'a>>;
+
+/// A named dependency probe, such as pinging a database.
+///
+/// Checks should bound their own running time; a hung check delays the health endpoint.
+pub trait HealthCheck: Send + Sync {
+    /// Probes the dependency.
+    fn check(&self) -> HealthFuture<'_>;
+}
+
+/// The outcome of one [`HealthCheck`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    /// The name the check was registered under.
+    pub name: String,
+    /// The failure description, or `None` when healthy.
+    pub error: Option<String>,
+    /// How long the check took.
+    pub latency: Duration,
+}
+
+/// The outcome of every registered check, from [`Health::check`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HealthReport {
+    /// One result per check, in registration order.
+    pub checks: Vec<CheckResult>,
+}
+
+impl HealthReport {
+    /// Returns `true` when every check passed.
+    pub fn is_healthy(&self) -> bool {
+        self.checks.iter().all(|check| check.error.is_none())
+    }
+
+    /// Renders the report as `{"status": "ok"|"error", "checks": {name: {...}}}`.
+    pub fn to_json(&self) -> Value {
+        let checks: Map<String, Value> = self
+            .checks
+            .iter()
+            .map(|check| {
+                let mut entry = json!({
+                    "status": if check.error.is_none() { "ok" } else { "error" },
+                    "latency_ms": check.latency.as_secs_f64() * 1000.0,
+                });
+                if let Some(error) = &check.error {
+                    entry["error"] = json!(error);
+                }
+                (check.name.clone(), entry)
+            })
+            .collect();
+        json!({
+            "status": if self.is_healthy() { "ok" } else { "error" },
+            "checks": checks,
+        })
+    }
+}
+
+// Registered checks, in registration order.
+type Checks = Vec<(String, Arc<dyn HealthCheck>)>;
+
+/// A shared registry of health checks. Clones share the same registry.
+#[derive(Clone, Default)]
+pub struct Health {
+    checks: Arc<Mutex<Checks>>,
+}
+
+impl Health {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a check reported under `name`.
+    pub fn register(&self, name: impl Into<String>, check: impl HealthCheck + 'static) {
+        self.checks().push((name.into(), Arc::new(check)));
+    }
+
+    /// Runs every check in registration order.
+    pub async fn check(&self) -> HealthReport {
+        // Run outside the lock so checks may register others or take their own locks.
+        let checks = self.checks().clone();
+        let mut report = HealthReport::default();
+        for (name, check) in checks {
+            let start = Instant::now();
+            let error = check.check().await.err();
+            report.checks.push(CheckResult {
+                name,
+                error,
+                latency: start.elapsed(),
+            });
+        }
+        report
+    }
+
+    fn checks(&self) -> MutexGuard<'_, Checks> {
+        self.checks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Serves a [`Health`] report for `GET` requests to its endpoint (`/health` by default).
+///
+/// Responds `200 OK` when every check passes and `503 Service Unavailable` otherwise,
+/// with the [`HealthReport::to_json`] body. Other requests pass through untouched.
+#[derive(Clone)]
+pub struct HealthMiddleware {
+    health: Health,
+    endpoint: String,
+}
+
+impl HealthMiddleware {
+    /// Creates a middleware serving `health` at `/health`.
+    pub fn new(health: Health) -> Self {
+        Self {
+            health,
+            endpoint: "/health".to_owned(),
+        }
+    }
+
+    /// Serves the report at `path` instead.
+    #[must_use]
+    pub fn endpoint(mut self, path: impl Into<String>) -> Self {
+        self.endpoint = path.into();
+        self
+    }
+}
+
+impl Middleware for HealthMiddleware {
+    fn handle(&self, ctx: Context, next: Next) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let request = ctx.request();
+        if *request.method() != Method::Get || request.path() != self.endpoint {
+            return next.run(ctx);
+        }
+
+        let health = self.health.clone();
+        Box::pin(async move {
+            let report = health.check().await;
+            let status = if report.is_healthy() {
+                StatusCode::Ok
+            } else {
+                StatusCode::ServiceUnavailable
+            };
+            Response::new(status)
+                .header("Content-Type", "application/json")
+                .header("Cache-Control", "no-store")
+                .body(report.to_json().to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Request,
+        middleware::{MiddlewareHandler, from_middleware},
+    };
+
+    struct Fixed(Result<(), &'static str>);
+
+    impl HealthCheck for Fixed {
+        fn check(&self) -> HealthFuture<'_> {
+            let result = self.0.map_err(str::to_owned);
+            Box::pin(async move { result })
+        }
+    }
+
+    async fn get(middleware: HealthMiddleware, path: &str) -> Response {
+        let raw = format!("GET {path} HTTP/1.1\r\nHost: x\r\n\r\n");
+        let ctx = Context::new(Request::parse(raw.as_bytes()).unwrap().0);
+        let handler: MiddlewareHandler = Arc::new(|_ctx: Context, _next: Next| {
+            Box::pin(async { Response::new(StatusCode::Ok).body("app") })
+        });
+        Next::new(vec![from_middleware(Arc::new(middleware)), handler])
+            .run(ctx)
+            .await
+    }
+
+    fn json(res: &Response) -> Value {
+        serde_json::from_slice(res.body_ref()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reports_every_check() {
+        let health = Health::new();
+        health.register("db", Fixed(Ok(())));
+        let middleware = HealthMiddleware::new(health.clone());
+
+        let res = get(middleware.clone(), "/health").await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        let body = json(&res);
+        assert_eq!(body["status"], "ok");
+        assert_eq!(body["checks"]["db"]["status"], "ok");
+
+        health.register("redis", Fixed(Err("connection refused")));
+        let res = get(middleware.clone(), "/health").await;
+        assert_eq!(res.status(), StatusCode::ServiceUnavailable);
+        let body = json(&res);
+        assert_eq!(body["status"], "error");
+        assert_eq!(body["checks"]["redis"]["error"], "connection refused");
+
+        let res = get(middleware.endpoint("/healthz"), "/health").await;
+        assert_eq!(res.body_ref(), b"app");
+    }
+}
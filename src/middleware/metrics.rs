@@ -0,0 +1,376 @@
+//! Request metrics and Prometheus text exposition.
+//!
+//! [`Metrics`] is a cheaply cloneable registry. [`MetricsMiddleware`] records a request
+//! counter and latency histogram into it and can serve the scrape endpoint. Other
+//! subsystems contribute their own series by registering a [`MetricsSource`], which is
+//! asked for current values at scrape time.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use rttp::middleware::{Metrics, MetricsMiddleware, from_middleware};
+//!
+//! let metrics = Metrics::new();
+//! let handler = from_middleware(Arc::new(
+//!     MetricsMiddleware::new(metrics.clone()).endpoint("/metrics"),
+//! ));
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as _,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use tokio::time::Instant;
+
+use super::{Middleware, Next};
+use crate::{Method, Response, StatusCode, context::Context};
+
+/// Upper bounds, in seconds, of the request latency histogram buckets.
+pub(crate) const LATENCY_BUCKETS: [f64; 11] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 10.0,
+];
+
+/// Supplies metric values when the registry is rendered.
+pub trait MetricsSource: Send + Sync {
+    /// Writes the source's current values into `out`.
+    fn collect(&self, out: &mut MetricsWriter);
+}
+
+/// Collects samples into metric families and renders the Prometheus text format.
+///
+/// Samples of the same name are grouped under one `# HELP`/`# TYPE` header, so several
+/// sources may write the same family with different labels.
+#[derive(Debug, Default)]
+pub struct MetricsWriter {
+    families: BTreeMap<String, Family>,
+}
+
+#[derive(Debug)]
+struct Family {
+    help: String,
+    kind: &'static str,
+    lines: Vec<String>,
+}
+
+impl MetricsWriter {
+    /// Writes a monotonically increasing counter sample.
+    pub fn counter(&mut self, name: &str, help: &str, labels: &[(&str, &str)], value: f64) {
+        let line = sample(name, labels, None, value);
+        self.family(name, help, "counter").lines.push(line);
+    }
+
+    /// Writes a gauge sample, a value that can go up and down.
+    pub fn gauge(&mut self, name: &str, help: &str, labels: &[(&str, &str)], value: f64) {
+        let line = sample(name, labels, None, value);
+        self.family(name, help, "gauge").lines.push(line);
+    }
+
+    /// Writes a histogram from cumulative `(upper bound, count)` buckets; the `+Inf`
+    /// bucket is added from `count`.
+    pub fn histogram(
+        &mut self,
+        name: &str,
+        help: &str,
+        labels: &[(&str, &str)],
+        buckets: &[(f64, u64)],
+        sum: f64,
+        count: u64,
+    ) {
+        let bucket = format!("{name}_bucket");
+        let mut lines: Vec<String> = buckets
+            .iter()
+            .map(|(le, n)| sample(&bucket, labels, Some(&le.to_string()), *n as f64))
+            .collect();
+        lines.push(sample(&bucket, labels, Some("+Inf"), count as f64));
+        lines.push(sample(&format!("{name}_sum"), labels, None, sum));
+        lines.push(sample(&format!("{name}_count"), labels, None, count as f64));
+        self.family(name, help, "histogram").lines.extend(lines);
+    }
+
+    /// Renders every family written so far.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, family) in &self.families {
+            let _ = writeln!(out, "# HELP {name} {}", family.help);
+            let _ = writeln!(out, "# TYPE {name} {}", family.kind);
+            for line in &family.lines {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    fn family(&mut self, name: &str, help: &str, kind: &'static str) -> &mut Family {
+        self.families
+            .entry(name.to_owned())
+            .or_insert_with(|| Family {
+                help: help.to_owned(),
+                kind,
+                lines: Vec::new(),
+            })
+    }
+}
+
+// One exposition line: `name{labels} value`.
+fn sample(name: &str, labels: &[(&str, &str)], le: Option<&str>, value: f64) -> String {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{k}=\"{}\"", escape_label(v)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{le}\""));
+    }
+    if pairs.is_empty() {
+        format!("{name} {value}")
+    } else {
+        format!("{name}{{{}}} {value}", pairs.join(","))
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// A shared registry of metrics sources plus the built-in HTTP request metrics.
+///
+/// Clones share the same registry.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    sources: Mutex<Vec<Arc<dyn MetricsSource>>>,
+    http: Mutex<HttpMetrics>,
+}
+
+#[derive(Default)]
+struct HttpMetrics {
+    requests: HashMap<(String, u16), u64>,
+    // Per-bucket (non-cumulative) counts; the last slot is for values above every bound.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Metrics {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a source whose values are included in every [`render`](Self::render).
+    pub fn register(&self, source: impl MetricsSource + 'static) {
+        lock(&self.inner.sources).push(Arc::new(source));
+    }
+
+    /// Records one completed request.
+    pub fn record_request(&self, method: &Method, status: StatusCode, seconds: f64) {
+        let mut http = lock(&self.inner.http);
+        *http
+            .requests
+            .entry((method.as_str().to_owned(), status.as_u16()))
+            .or_default() += 1;
+        let slot = LATENCY_BUCKETS
+            .iter()
+            .position(|&le| seconds <= le)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        http.buckets[slot] += 1;
+        http.sum += seconds;
+        http.count += 1;
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = MetricsWriter::default();
+        {
+            let http = lock(&self.inner.http);
+            let mut requests: Vec<_> = http.requests.iter().collect();
+            requests.sort();
+            for ((method, status), count) in requests {
+                out.counter(
+                    "rttp_http_requests_total",
+                    "HTTP requests handled, by method and status.",
+                    &[("method", method), ("status", &status.to_string())],
+                    *count as f64,
+                );
+            }
+            let mut cumulative = 0;
+            let buckets: Vec<(f64, u64)> = LATENCY_BUCKETS
+                .iter()
+                .zip(http.buckets)
+                .map(|(&le, n)| {
+                    cumulative += n;
+                    (le, cumulative)
+                })
+                .collect();
+            out.histogram(
+                "rttp_http_request_duration_seconds",
+                "HTTP request latency.",
+                &[],
+                &buckets,
+                http.sum,
+                http.count,
+            );
+        }
+        // Collect outside the lock so sources may take their own locks freely.
+        let sources = lock(&self.inner.sources).clone();
+        for source in sources {
+            source.collect(&mut out);
+        }
+        out.render()
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Records request counts and latency into a [`Metrics`] registry, optionally serving
+/// the registry at a scrape endpoint.
+///
+/// Requests to the endpoint are answered directly and not recorded.
+#[derive(Clone)]
+pub struct MetricsMiddleware {
+    metrics: Metrics,
+    endpoint: Option<String>,
+}
+
+impl MetricsMiddleware {
+    /// Creates a middleware recording into `metrics`.
+    pub fn new(metrics: Metrics) -> Self {
+        Self {
+            metrics,
+            endpoint: None,
+        }
+    }
+
+    /// Serves the rendered metrics for `GET path`.
+    #[must_use]
+    pub fn endpoint(mut self, path: impl Into<String>) -> Self {
+        self.endpoint = Some(path.into());
+        self
+    }
+}
+
+impl Middleware for MetricsMiddleware {
+    fn handle(&self, ctx: Context, next: Next) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let request = ctx.request();
+        if *request.method() == Method::Get && self.endpoint.as_deref() == Some(request.path()) {
+            let body = self.metrics.render();
+            return Box::pin(async move {
+                Response::new(StatusCode::Ok)
+                    .header("Content-Type", "text/plain; version=0.0.4")
+                    .body(body)
+            });
+        }
+
+        let metrics = self.metrics.clone();
+        let method = request.method().clone();
+        Box::pin(async move {
+            let start = Instant::now();
+            let response = next.run(ctx).await;
+            metrics.record_request(&method, response.status(), start.elapsed().as_secs_f64());
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Request,
+        middleware::{MiddlewareHandler, from_middleware},
+    };
+
+    fn ctx(method: &str, path: &str) -> Context {
+        let raw = format!("{method} {path} HTTP/1.1\r\nHost: x\r\n\r\n");
+        Context::new(Request::parse(raw.as_bytes()).unwrap().0)
+    }
+
+    async fn send(middleware: &MetricsMiddleware, ctx: Context, status: StatusCode) -> Response {
+        let handler: MiddlewareHandler = Arc::new(move |_ctx: Context, _next: Next| {
+            Box::pin(async move { Response::new(status) })
+        });
+        let chain = vec![from_middleware(Arc::new(middleware.clone())), handler];
+        Next::new(chain).run(ctx).await
+    }
+
+    #[test]
+    fn writer_groups_families_and_escapes_labels() {
+        let mut out = MetricsWriter::default();
+        out.gauge("b_gauge", "B.", &[("k", "a\"b")], 1.5);
+        out.counter("a_total", "A.", &[("x", "1")], 2.0);
+        out.counter("a_total", "A.", &[("x", "2")], 3.0);
+        out.gauge("plain", "P.", &[], 0.0);
+        assert_eq!(
+            out.render(),
+            "# HELP a_total A.\n# TYPE a_total counter\na_total{x=\"1\"} 2\na_total{x=\"2\"} 3\n\
+             # HELP b_gauge B.\n# TYPE b_gauge gauge\nb_gauge{k=\"a\\\"b\"} 1.5\n\
+             # HELP plain P.\n# TYPE plain gauge\nplain 0\n"
+        );
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let metrics = Metrics::new();
+        metrics.record_request(&Method::Get, StatusCode::Ok, 0.002);
+        metrics.record_request(&Method::Get, StatusCode::Ok, 0.002);
+        metrics.record_request(&Method::Post, StatusCode::Created, 30.0);
+        let text = metrics.render();
+        assert!(text.contains("rttp_http_request_duration_seconds_bucket{le=\"0.001\"} 0\n"));
+        assert!(text.contains("rttp_http_request_duration_seconds_bucket{le=\"0.005\"} 2\n"));
+        assert!(text.contains("rttp_http_request_duration_seconds_bucket{le=\"10\"} 2\n"));
+        assert!(text.contains("rttp_http_request_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("rttp_http_request_duration_seconds_count 3\n"));
+        assert!(text.contains("rttp_http_requests_total{method=\"GET\",status=\"200\"} 2\n"));
+        assert!(text.contains("rttp_http_requests_total{method=\"POST\",status=\"201\"} 1\n"));
+    }
+
+    #[tokio::test]
+    async fn middleware_records_and_serves_endpoint() {
+        struct Fixed;
+        impl MetricsSource for Fixed {
+            fn collect(&self, out: &mut MetricsWriter) {
+                out.gauge("fixed", "A fixed value.", &[], 7.0);
+            }
+        }
+
+        let metrics = Metrics::new();
+        metrics.register(Fixed);
+        let middleware = MetricsMiddleware::new(metrics).endpoint("/metrics");
+
+        send(&middleware, ctx("GET", "/a"), StatusCode::Ok).await;
+        send(&middleware, ctx("GET", "/b"), StatusCode::NotFound).await;
+        // Only GET serves the endpoint; other methods pass through and are recorded.
+        send(
+            &middleware,
+            ctx("POST", "/metrics"),
+            StatusCode::MethodNotAllowed,
+        )
+        .await;
+
+        let scrape = send(&middleware, ctx("GET", "/metrics"), StatusCode::Ok).await;
+        assert_eq!(
+            scrape.headers().get("content-type"),
+            Some("text/plain; version=0.0.4")
+        );
+        let text = String::from_utf8(scrape.body_ref().to_vec()).unwrap();
+        assert!(text.contains("rttp_http_requests_total{method=\"GET\",status=\"200\"} 1\n"));
+        assert!(text.contains("rttp_http_requests_total{method=\"GET\",status=\"404\"} 1\n"));
+        assert!(text.contains("rttp_http_requests_total{method=\"POST\",status=\"405\"} 1\n"));
+        assert!(text.contains("rttp_http_request_duration_seconds_count 3\n"));
+        assert!(text.contains("fixed 7\n"));
+    }
+}
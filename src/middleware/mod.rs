@@ -14,6 +14,23 @@
 //! - [`from_middleware`] — converts a [`Middleware`] trait object into a
 //!   [`MiddlewareHandler`].
 //! - [`LoggerMiddleware`] — built-in request/response logger.
+//! - [`MetricsMiddleware`] — request counters and latency histogram, exported with
+//!   every registered [`MetricsSource`] through a [`Metrics`] registry.
+//! - [`HealthMiddleware`] — serves the combined result of every [`HealthCheck`]
+//!   registered with a [`Health`] registry.
+//! - [`RecoveryMiddleware`] — turns panics into `500` responses, logs server errors, and
+//!   renders a developer error page in debug builds.
+//! - [`CaptureMiddleware`] — records redacted request and response bodies into the
+//!   log and a [`TrafficCapture`] buffer, for debugging client integrations; [`Har`]
+//!   exports the buffer as an HTTP Archive.
+//! - [`ConcurrencyLimitMiddleware`] — sheds or queues requests beyond a global or
+//!   per-route concurrency limit, admitting queued requests by [`Priority`].
+//! - [`SlowRequestMiddleware`] — logs requests slower than their route's threshold with
+//!   read, handler, and write times, counting them in [`SlowRequests`].
+//! - [`TracingMiddleware`] — propagates W3C [`TraceContext`] and records a span for the
+//!   requests its [`Sampler`] selects.
+//! - [`ServerTimingMiddleware`] — sends the phase durations recorded through
+//!   [`TimingExt::timing`] in a `Server-Timing` header.
 //!
 //! ## Planned Features
 //!
@@ -26,6 +43,26 @@
 use std::{future::Future, pin::Pin, sync::Arc};
 use tokio::time::Instant;
 
+pub mod capture;
+pub mod concurrency;
+pub mod har;
+pub mod health;
+pub mod metrics;
+pub mod recovery;
+pub mod slow;
+pub mod timing;
+pub mod trace;
+
+pub use capture::{CaptureMiddleware, CapturedBody, Exchange, TrafficCapture};
+pub use concurrency::{ConcurrencyLimitMiddleware, Priority};
+pub use har::Har;
+pub use health::{CheckResult, Health, HealthCheck, HealthMiddleware, HealthReport};
+pub use metrics::{Metrics, MetricsMiddleware, MetricsSource};
+pub use recovery::{RecentLogs, RecoveryMiddleware};
+pub use slow::{SlowRequestMiddleware, SlowRequests};
+pub use timing::{ServerTiming, ServerTimingMiddleware, TimingEntry, TimingExt};
+pub use trace::{Sampler, TraceContext, TracingMiddleware};
+
 use crate::{Response, context::Context};
 
 /// A cursor into the remaining middleware chain for a single request.
@@ -57,7 +94,8 @@ use crate::{Response, context::Context};
 /// }
 /// ```
 pub struct Next {
-    middlewares: Vec<MiddlewareHandler>,
+    // Shared by every request through the same pipeline.
+    middlewares: Arc<[MiddlewareHandler]>,
     // Tracks which middleware to invoke on the next `run` call.
     index: usize,
 }
@@ -106,6 +144,10 @@ where
 impl Next {
     /// Creates a new `Next` positioned at the start of the given middleware stack.
     ///
+    /// Servers build the chain once as an `Arc<[MiddlewareHandler]>` and pass a clone
+    /// per request, which costs one reference count instead of copying the stack. A
+    /// `Vec` is also accepted.
+    ///
     /// # Arguments
     ///
     /// - `middlewares` — the ordered list of handlers that make up the pipeline.
@@ -117,19 +159,20 @@ impl Next {
     ///
     /// let next = Next::new(vec![]);
     /// ```
-    pub fn new(middlewares: Vec<MiddlewareHandler>) -> Self {
+    pub fn new(middlewares: impl Into<Arc<[MiddlewareHandler]>>) -> Self {
         Self {
-            middlewares,
+            middlewares: middlewares.into(),
             index: 0,
         }
     }
 
     /// Invokes the next middleware in the chain and returns its response.
     ///
-    /// Advances the internal cursor by one, clones the handler at the current
-    /// position, and awaits it. If no handler remains (i.e. the chain is
-    /// exhausted without producing a response), a `500 Internal Server Error`
-    /// response is returned as a safe fallback.
+    /// Advances the internal cursor by one and calls the handler at the current
+    /// position, returning its future as is, so a middleware can return
+    /// `next.run(ctx)` from [`Middleware::handle`] without boxing it again. If no
+    /// handler remains (i.e. the chain is exhausted without producing a response), a
+    /// `500 Internal Server Error` response is returned as a safe fallback.
     ///
     /// # Arguments
     ///
@@ -138,15 +181,15 @@ impl Next {
     /// # Returns
     ///
     /// The [`Response`] produced by the next middleware or handler in the chain.
-    pub async fn run(mut self, ctx: Context) -> Response {
-        if self.index < self.middlewares.len() {
-            let handler = self.middlewares[self.index].clone();
-            self.index += 1;
-            handler(ctx, self).await
-        } else {
-            Response::new(crate::StatusCode::InternalServerError)
-                .body("No response generated by middleware pipeline")
-        }
+    pub fn run(mut self, ctx: Context) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let Some(handler) = self.middlewares.get(self.index).cloned() else {
+            return Box::pin(async {
+                Response::new(crate::StatusCode::InternalServerError)
+                    .body("No response generated by middleware pipeline")
+            });
+        };
+        self.index += 1;
+        handler(ctx, self)
     }
 }
 
@@ -0,0 +1,449 @@
+//! Panic recovery and developer error pages.
+//!
+//! [`RecoveryMiddleware`] turns a panicking handler into a `500 Internal Server Error`
+//! instead of a dropped connection, and logs every failure raised through
+//! [`Response::server_error`]. Clients only ever see a generic message, unless developer
+//! mode is on: then a failed request renders an HTML page with the error and its causes,
+//! the backtrace, the request, and the most recent log lines.
+//!
+//! Developer mode defaults to on in debug builds. Backtraces for errors follow the
+//! standard library's rules and need `RUST_BACKTRACE=1`; panics are always captured in
+//! developer mode.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use rttp::middleware::{RecentLogs, RecoveryMiddleware, from_middleware};
+//!
+//! let logs = RecentLogs::new(50);
+//! let writer = logs.clone();
+//! // With tracing-subscriber: `fmt().with_writer(move || writer.clone()).init()`
+//! # drop(writer);
+//! let handler = from_middleware(Arc::new(
+//!     RecoveryMiddleware::new().developer(true).recent_logs(logs),
+//! ));
+//! ```
+
+use std::{
+    any::Any,
+    backtrace::Backtrace,
+    cell::RefCell,
+    collections::VecDeque,
+    fmt::Write as _,
+    future::Future,
+    io,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, Once},
+    task::{Context as TaskContext, Poll},
+};
+
+use super::{Middleware, Next};
+use crate::{Response, StatusCode, context::Context, http::html::escape};
+
+// Headers that never appear on the error page.
+const REDACTED: [&str; 3] = ["authorization", "proxy-authorization", "cookie"];
+
+/// Catches panics and logs server errors, optionally rendering a developer error page.
+///
+/// Install it first, so it covers every later middleware and the router.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct RecoveryMiddleware {
+    developer: bool,
+    logs: Option<RecentLogs>,
+}
+
+impl RecoveryMiddleware {
+    /// Creates the middleware, in developer mode for debug builds only.
+    pub fn new() -> Self {
+        Self {
+            developer: cfg!(debug_assertions),
+            logs: None,
+        }
+    }
+
+    /// Turns the developer error page on or off.
+    ///
+    /// Never enable it in production: the page exposes source locations, request
+    /// headers, and log output.
+    pub fn developer(mut self, developer: bool) -> Self {
+        self.developer = developer;
+        self
+    }
+
+    /// Shows the lines captured by `logs` on the developer error page.
+    pub fn recent_logs(mut self, logs: RecentLogs) -> Self {
+        self.logs = Some(logs);
+        self
+    }
+}
+
+impl Default for RecoveryMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Middleware for RecoveryMiddleware {
+    fn handle(&self, ctx: Context, next: Next) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let developer = self.developer;
+        let logs = self.logs.clone();
+        if developer {
+            install_panic_hook();
+        }
+        Box::pin(async move {
+            let request = developer.then(|| RequestSummary::new(&ctx));
+            let outcome = CatchUnwind(next.run(ctx)).await;
+            let mut response = match outcome {
+                Ok(response) => response,
+                Err(payload) => {
+                    let message = panic_message(payload);
+                    let (location, backtrace) = PANIC
+                        .with(|panic| panic.borrow_mut().take())
+                        .unwrap_or_default();
+                    tracing::error!(%location, "handler panicked: {message}");
+                    if !developer {
+                        return generic();
+                    }
+                    let page = Page {
+                        title: "Handler panicked",
+                        message: &message,
+                        causes: Vec::new(),
+                        location: &location,
+                        backtrace: &backtrace,
+                        request: request.as_ref(),
+                        logs: logs.as_ref(),
+                    };
+                    return page.render();
+                }
+            };
+            let Some(fault) = response.take_fault() else {
+                return response;
+            };
+            tracing::error!(error = %fault.error, "request failed");
+            if !developer || response.status() != StatusCode::InternalServerError {
+                return response;
+            }
+            let mut causes = Vec::new();
+            let mut source = fault.error.source();
+            while let Some(cause) = source {
+                causes.push(cause.to_string());
+                source = cause.source();
+            }
+            Page {
+                title: "Server error",
+                message: &fault.error.to_string(),
+                causes,
+                location: "",
+                backtrace: &fault.backtrace.to_string(),
+                request: request.as_ref(),
+                logs: logs.as_ref(),
+            }
+            .render()
+        })
+    }
+}
+
+fn generic() -> Response {
+    Response::new(StatusCode::InternalServerError).body("Internal Server Error")
+}
+
+// Polls the inner future, turning a panic into `Err` with its payload.
+struct CatchUnwind<F>(F);
+
+impl<F: Future + Unpin> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, Box<dyn Any + Send>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        let inner = &mut self.0;
+        match panic::catch_unwind(AssertUnwindSafe(|| Pin::new(inner).poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+thread_local! {
+    // Where the last panic on this thread happened, set by the panic hook.
+    static PANIC: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+}
+
+// Records each panic's location and backtrace for the error page, then defers to the
+// previous hook so the usual message is still printed.
+fn install_panic_hook() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let location = info.location().map(ToString::to_string).unwrap_or_default();
+            let backtrace = Backtrace::force_capture().to_string();
+            PANIC.with(|panic| *panic.borrow_mut() = Some((location, backtrace)));
+            previous(info);
+        }));
+    });
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| (*s).to_owned())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_owned())
+}
+
+// The parts of the request shown on the error page, taken before the handler runs.
+struct RequestSummary {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+}
+
+impl RequestSummary {
+    fn new(ctx: &Context) -> Self {
+        let request = ctx.request();
+        let path = match request.query_string() {
+            Some(query) => format!("{}?{query}", request.path()),
+            None => request.path().to_owned(),
+        };
+        let headers = request
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                let value = if REDACTED.contains(&name.to_ascii_lowercase().as_str()) {
+                    "[redacted]"
+                } else {
+                    value
+                };
+                (name.to_owned(), value.to_owned())
+            })
+            .collect();
+        Self {
+            method: request.method().as_str().to_owned(),
+            path,
+            headers,
+        }
+    }
+}
+
+struct Page<'a> {
+    title: &'a str,
+    message: &'a str,
+    causes: Vec<String>,
+    location: &'a str,
+    backtrace: &'a str,
+    request: Option<&'a RequestSummary>,
+    logs: Option<&'a RecentLogs>,
+}
+
+impl Page<'_> {
+    fn render(&self) -> Response {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>500 Internal Server Error</title>\n<style>\
+             body{font-family:sans-serif;margin:2em;color:#222}\
+             h1{color:#b00}pre{background:#f4f4f4;padding:1em;overflow:auto}\
+             td{padding:2px 1em 2px 0;vertical-align:top;font-family:monospace}\
+             </style>\n</head>\n<body>\n",
+        );
+        let _ = writeln!(html, "<h1>{}</h1>", escape(self.title));
+        let _ = writeln!(html, "<p><strong>{}</strong></p>", escape(self.message));
+        if !self.location.is_empty() {
+            let _ = writeln!(html, "<p>at <code>{}</code></p>", escape(self.location));
+        }
+        if !self.causes.is_empty() {
+            html.push_str("<h2>Caused by</h2>\n<ol>\n");
+            for cause in &self.causes {
+                let _ = writeln!(html, "<li>{}</li>", escape(cause));
+            }
+            html.push_str("</ol>\n");
+        }
+        html.push_str("<h2>Backtrace</h2>\n");
+        let _ = writeln!(html, "<pre>{}</pre>", escape(self.backtrace));
+        if let Some(request) = self.request {
+            html.push_str("<h2>Request</h2>\n");
+            let _ = writeln!(
+                html,
+                "<p><code>{} {}</code></p>\n<table>",
+                escape(&request.method),
+                escape(&request.path)
+            );
+            for (name, value) in &request.headers {
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{}</td><td>{}</td></tr>",
+                    escape(name),
+                    escape(value)
+                );
+            }
+            html.push_str("</table>\n");
+        }
+        if let Some(logs) = self.logs {
+            html.push_str("<h2>Recent logs</h2>\n");
+            let _ = writeln!(html, "<pre>{}</pre>", escape(&logs.lines().join("\n")));
+        }
+        html.push_str("</body>\n</html>\n");
+        Response::new(StatusCode::InternalServerError)
+            .header("Content-Type", "text/html; charset=utf-8")
+            .header("Cache-Control", "no-store")
+            .body(html)
+    }
+}
+
+/// A bounded buffer of the most recent log lines, for the developer error page.
+///
+/// `RecentLogs` implements [`io::Write`] and is cheap to clone, so it can serve as a
+/// log writer; with `tracing-subscriber`, pass `move || logs.clone()` to `with_writer`.
+/// Only the last `capacity` lines are kept.
+#[derive(Debug, Clone)]
+pub struct RecentLogs {
+    inner: Arc<Mutex<LogBuffer>>,
+}
+
+#[derive(Debug)]
+struct LogBuffer {
+    capacity: usize,
+    lines: VecDeque<String>,
+    // A line written without its trailing newline yet.
+    partial: String,
+}
+
+impl RecentLogs {
+    /// Creates a buffer holding up to `capacity` lines.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(LogBuffer {
+                capacity,
+                lines: VecDeque::with_capacity(capacity),
+                partial: String::new(),
+            })),
+        }
+    }
+
+    /// Returns the buffered lines, oldest first.
+    pub fn lines(&self) -> Vec<String> {
+        self.lock().lines.iter().cloned().collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LogBuffer> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl io::Write for RecentLogs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut buffer = self.lock();
+        buffer.partial.push_str(&String::from_utf8_lossy(buf));
+        while let Some(end) = buffer.partial.find('\n') {
+            let line: String = buffer.partial.drain(..=end).collect();
+            let line = line.trim_end().to_owned();
+            if buffer.capacity == 0 {
+                continue;
+            }
+            if buffer.lines.len() == buffer.capacity {
+                buffer.lines.pop_front();
+            }
+            buffer.lines.push_back(line);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, io::Write as _};
+
+    use super::*;
+    use crate::{
+        Request,
+        middleware::{MiddlewareHandler, from_middleware},
+    };
+
+    async fn call(middleware: RecoveryMiddleware, handler: MiddlewareHandler) -> Response {
+        let raw = "GET /orders?id=7 HTTP/1.1\r\nHost: x\r\nCookie: session=secret\r\n\r\n";
+        let ctx = Context::new(Request::parse(raw.as_bytes()).unwrap().0);
+        Next::new(vec![from_middleware(Arc::new(middleware)), handler])
+            .run(ctx)
+            .await
+    }
+
+    fn panicking() -> MiddlewareHandler {
+        Arc::new(|_ctx: Context, _next: Next| {
+            Box::pin(async {
+                if true {
+                    panic!("boom <script>");
+                }
+                Response::new(StatusCode::Ok)
+            })
+        })
+    }
+
+    #[tokio::test]
+    async fn panics_become_generic_errors_in_production() {
+        let res = call(RecoveryMiddleware::new().developer(false), panicking()).await;
+        assert_eq!(res.status(), StatusCode::InternalServerError);
+        assert_eq!(res.body_text(), "Internal Server Error");
+    }
+
+    #[tokio::test]
+    async fn developer_page_shows_the_panic_and_request() {
+        let logs = RecentLogs::new(2);
+        let mut writer = logs.clone();
+        writer.write_all(b"one\ntwo\nthree\n").unwrap();
+
+        let middleware = RecoveryMiddleware::new().developer(true).recent_logs(logs);
+        let res = call(middleware, panicking()).await;
+        assert_eq!(res.status(), StatusCode::InternalServerError);
+        let body = res.body_text();
+        assert!(body.contains("boom &lt;script&gt;"), "{body}");
+        assert!(body.contains("recovery.rs"), "{body}");
+        assert!(body.contains("GET /orders?id=7"), "{body}");
+        assert!(
+            body.contains("[redacted]") && !body.contains("secret"),
+            "{body}"
+        );
+        assert!(
+            body.contains("two\nthree") && !body.contains("one"),
+            "{body}"
+        );
+    }
+
+    #[tokio::test]
+    async fn developer_page_lists_error_causes() {
+        #[derive(Debug)]
+        struct Outer(io::Error);
+
+        impl std::fmt::Display for Outer {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("loading the order failed")
+            }
+        }
+
+        impl Error for Outer {
+            fn source(&self) -> Option<&(dyn Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        let failing: MiddlewareHandler = Arc::new(|_ctx: Context, _next: Next| {
+            Box::pin(async { Response::server_error(Outer(io::Error::other("connection reset"))) })
+        });
+        let res = call(RecoveryMiddleware::new().developer(true), failing.clone()).await;
+        let body = res.body_text();
+        assert!(body.contains("loading the order failed"), "{body}");
+        assert!(
+            body.contains("Caused by") && body.contains("connection reset"),
+            "{body}"
+        );
+
+        let res = call(RecoveryMiddleware::new().developer(false), failing).await;
+        assert_eq!(res.body_text(), "Internal Server Error");
+        assert!(res.error().is_none());
+    }
+}
@@ -0,0 +1,246 @@
+//! Logging and counting of slow requests.
+//!
+//! [`SlowRequestMiddleware`] times each request in three phases — receiving it, handling
+//! it, and writing the response — and when the total exceeds the threshold for its
+//! route, logs a `WARN` line with the breakdown and counts it in [`SlowRequests`], which
+//! exports `rttp_slow_requests_total` through a [`Metrics`](super::Metrics) registry.
+//!
+//! The read time comes from [`Request::read_duration`](crate::Request::read_duration)
+//! and the write time from [`Response::on_sent`], so both are known only for requests
+//! served by [`Server`](crate::server::Server); elsewhere they count as zero. The
+//! handler time covers everything after this middleware in the pipeline, so install it
+//! first.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::{sync::Arc, time::Duration};
+//! use rttp::middleware::{Metrics, SlowRequestMiddleware, from_middleware};
+//!
+//! let slow = SlowRequestMiddleware::new(Duration::from_millis(500))
+//!     .route("/reports/:id", Duration::from_secs(5));
+//! let metrics = Metrics::new();
+//! metrics.register(slow.slow_requests());
+//! let handler = from_middleware(Arc::new(slow));
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use super::{
+    Middleware, Next,
+    metrics::{MetricsSource, MetricsWriter},
+};
+use crate::{Response, context::Context, router::Pattern};
+
+// The route label of requests judged by the default threshold.
+const DEFAULT_ROUTE: &str = "*";
+
+/// Counts of slow requests by route, shared with the [`SlowRequestMiddleware`] that
+/// records them.
+///
+/// Clones share the same counts. As a [`MetricsSource`] it exports
+/// `rttp_slow_requests_total`, labelled with the route pattern, or `*` for requests
+/// judged by the default threshold.
+#[derive(Debug, Clone, Default)]
+pub struct SlowRequests {
+    counts: Arc<Mutex<BTreeMap<String, u64>>>,
+}
+
+impl SlowRequests {
+    /// Returns the number of slow requests for `route`, a pattern passed to
+    /// [`SlowRequestMiddleware::route`] or `*`.
+    pub fn count(&self, route: &str) -> u64 {
+        self.lock().get(route).copied().unwrap_or(0)
+    }
+
+    /// Returns the number of slow requests across all routes.
+    pub fn total(&self) -> u64 {
+        self.lock().values().sum()
+    }
+
+    fn record(&self, route: &str) {
+        *self.lock().entry(route.to_owned()).or_default() += 1;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, u64>> {
+        self.counts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl MetricsSource for SlowRequests {
+    fn collect(&self, out: &mut MetricsWriter) {
+        for (route, count) in self.lock().iter() {
+            out.counter(
+                "rttp_slow_requests_total",
+                "Requests slower than their route's threshold.",
+                &[("route", route)],
+                *count as f64,
+            );
+        }
+    }
+}
+
+/// Logs requests slower than a threshold with their read, handler, and write times.
+///
+/// Route thresholds are matched against the request path with the [`Router`] pattern
+/// syntax, in the order they were added; the first match applies, and other requests
+/// use the default threshold.
+///
+/// [`Router`]: crate::Router
+#[derive(Debug)]
+#[must_use]
+pub struct SlowRequestMiddleware {
+    threshold: Duration,
+    routes: Vec<(String, Pattern, Duration)>,
+    slow: SlowRequests,
+}
+
+impl SlowRequestMiddleware {
+    /// Flags requests taking longer than `threshold` in total.
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            routes: Vec::new(),
+            slow: SlowRequests::default(),
+        }
+    }
+
+    /// Uses `threshold` instead for requests whose path matches `pattern`, e.g.
+    /// `/reports/:id`.
+    pub fn route(mut self, pattern: &str, threshold: Duration) -> Self {
+        self.routes
+            .push((pattern.to_owned(), Pattern::parse(pattern), threshold));
+        self
+    }
+
+    /// Returns the slow request counts, to read or register with a
+    /// [`Metrics`](super::Metrics) registry.
+    pub fn slow_requests(&self) -> SlowRequests {
+        self.slow.clone()
+    }
+
+    // The route label and threshold that apply to `path`.
+    fn threshold_for(&self, path: &str) -> (&str, Duration) {
+        self.routes
+            .iter()
+            .find(|(_, pattern, _)| pattern.matches(path).is_some())
+            .map_or((DEFAULT_ROUTE, self.threshold), |(route, _, threshold)| {
+                (route.as_str(), *threshold)
+            })
+    }
+}
+
+impl Middleware for SlowRequestMiddleware {
+    fn handle(&self, ctx: Context, next: Next) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let request = ctx.request();
+        let (route, threshold) = self.threshold_for(request.path());
+        let route = route.to_owned();
+        let method = request.method().clone();
+        let path = request.path().to_owned();
+        let read = request.read_duration().unwrap_or_default();
+        let slow = self.slow.clone();
+        Box::pin(async move {
+            let started = Instant::now();
+            let mut response = next.run(ctx).await;
+            let handler = started.elapsed();
+            let status = response.status().as_u16();
+            response.on_sent(move |write| {
+                let write = write.unwrap_or_default();
+                let total = read + handler + write;
+                if total <= threshold {
+                    return;
+                }
+                slow.record(&route);
+                tracing::warn!(
+                    %method,
+                    %path,
+                    %route,
+                    status,
+                    ?total,
+                    ?read,
+                    ?handler,
+                    ?write,
+                    ?threshold,
+                    "slow request"
+                );
+            });
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Request, StatusCode,
+        middleware::{Metrics, MiddlewareHandler, from_middleware},
+    };
+
+    fn sleeping(delay: Duration) -> MiddlewareHandler {
+        Arc::new(move |_ctx: Context, _next: Next| {
+            Box::pin(async move {
+                tokio::time::sleep(delay).await;
+                Response::new(StatusCode::Ok)
+            })
+        })
+    }
+
+    #[tokio::test]
+    async fn counts_requests_over_their_route_threshold() {
+        let slow = SlowRequestMiddleware::new(Duration::from_millis(5))
+            .route("/reports/:id", Duration::from_secs(60));
+        let counts = slow.slow_requests();
+        let chain: Arc<[MiddlewareHandler]> = vec![
+            from_middleware(Arc::new(slow)),
+            sleeping(Duration::from_millis(20)),
+        ]
+        .into();
+
+        for path in ["/users", "/reports/1", "/users/2"] {
+            let ctx = Context::new(Request::builder().path(path).build());
+            // Dropping the response unsent runs the check without a write time.
+            drop(Next::new(Arc::clone(&chain)).run(ctx).await);
+        }
+        assert_eq!(counts.count(DEFAULT_ROUTE), 2);
+        assert_eq!(counts.count("/reports/:id"), 0);
+
+        let metrics = Metrics::new();
+        metrics.register(counts.clone());
+        assert!(
+            metrics
+                .render()
+                .contains(r#"rttp_slow_requests_total{route="*"} 2"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn includes_read_and_write_times() {
+        let counts = SlowRequests::default();
+        let slow = SlowRequestMiddleware {
+            threshold: Duration::from_millis(100),
+            routes: Vec::new(),
+            slow: counts.clone(),
+        };
+        let chain: Arc<[MiddlewareHandler]> =
+            vec![from_middleware(Arc::new(slow)), sleeping(Duration::ZERO)].into();
+
+        let mut request = Request::builder().build();
+        request.set_read_duration(Duration::from_millis(60));
+        let mut response = Next::new(Arc::clone(&chain))
+            .run(Context::new(request))
+            .await;
+        let hooks = response.take_sent_hooks();
+        assert_eq!(counts.total(), 0, "judged once the response is written");
+        hooks.sent(Duration::from_millis(60));
+        assert_eq!(counts.total(), 1);
+    }
+}
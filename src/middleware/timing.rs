@@ -0,0 +1,210 @@
+//! The `Server-Timing` response header.
+//!
+//! Handlers and middleware record how long each phase of a request took with
+//! [`TimingExt::timing`]; [`ServerTimingMiddleware`] gathers the entries and sends them in
+//! a `Server-Timing` header, which browser developer tools show next to the request's
+//! network timings. [`App`](crate::App) installs the middleware by default.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::time::Instant;
+//! use rttp::{Response, Router, StatusCode, context::Context};
+//! use rttp::middleware::TimingExt;
+//!
+//! let mut router = Router::new();
+//! router.get("/users", |ctx: Context| async move {
+//!     let started = Instant::now();
+//!     // ... query the database ...
+//!     ctx.timing("db", started.elapsed(), Some("users query"));
+//!     Response::new(StatusCode::Ok)
+//! });
+//! ```
+
+use std::{
+    fmt::Write as _,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use super::{Middleware, Next};
+use crate::{Response, context::Context};
+
+/// One `Server-Timing` entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimingEntry {
+    /// The metric name, e.g. `db` or `cache`.
+    pub name: String,
+    /// How long the phase took.
+    pub duration: Duration,
+    /// A human-readable description shown by developer tools.
+    pub description: Option<String>,
+}
+
+impl TimingEntry {
+    // Renders `name;dur=<ms>;desc="..."`, with the name reduced to token characters.
+    fn render(&self, out: &mut String) {
+        out.extend(self.name.chars().map(|c| {
+            if c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        }));
+        let _ = write!(out, ";dur={:.1}", self.duration.as_secs_f64() * 1000.0);
+        if let Some(description) = &self.description {
+            out.push_str(";desc=\"");
+            for c in description.chars().filter(|c| !c.is_control()) {
+                if c == '"' || c == '\\' {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+            out.push('"');
+        }
+    }
+}
+
+/// The entries recorded for one request, shared through the request's extensions.
+#[derive(Debug, Clone, Default)]
+pub struct ServerTiming {
+    entries: Arc<Mutex<Vec<TimingEntry>>>,
+}
+
+impl ServerTiming {
+    /// Records an entry.
+    pub fn record(&self, name: &str, duration: Duration, description: Option<&str>) {
+        self.lock().push(TimingEntry {
+            name: name.to_owned(),
+            duration,
+            description: description.map(str::to_owned),
+        });
+    }
+
+    /// Returns the entries recorded so far, in order.
+    pub fn entries(&self) -> Vec<TimingEntry> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<TimingEntry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Collects [`ServerTiming`] entries and sends them in a `Server-Timing` header.
+///
+/// The header is only added when something was recorded, unless
+/// [`total`](Self::total) is on. Timings reveal how the server spends its time; leave
+/// the middleware out where that matters.
+#[derive(Debug, Clone, Default)]
+#[must_use]
+pub struct ServerTimingMiddleware {
+    total: bool,
+}
+
+impl ServerTimingMiddleware {
+    /// Creates the middleware.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a `total` entry measuring everything after this middleware.
+    pub fn total(mut self, total: bool) -> Self {
+        self.total = total;
+        self
+    }
+}
+
+impl Middleware for ServerTimingMiddleware {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let total = self.total;
+        Box::pin(async move {
+            let started = Instant::now();
+            let timing = ServerTiming::default();
+            ctx.extensions_mut().insert(timing.clone());
+            let mut response = next.run(ctx).await;
+            if total {
+                timing.record("total", started.elapsed(), None);
+            }
+            let entries = timing.lock();
+            if !entries.is_empty() {
+                let mut value = String::new();
+                for (i, entry) in entries.iter().enumerate() {
+                    if i > 0 {
+                        value.push_str(", ");
+                    }
+                    entry.render(&mut value);
+                }
+                response.add_header("Server-Timing", value);
+            }
+            drop(entries);
+            response
+        })
+    }
+}
+
+/// Adds `Server-Timing` recording to [`Context`].
+pub trait TimingExt {
+    /// Records that phase `name` took `duration`, with an optional description.
+    ///
+    /// Does nothing unless [`ServerTimingMiddleware`] is installed.
+    fn timing(&self, name: &str, duration: Duration, description: Option<&str>);
+}
+
+impl TimingExt for Context {
+    fn timing(&self, name: &str, duration: Duration, description: Option<&str>) {
+        if let Some(timing) = self.extensions().get::<ServerTiming>() {
+            timing.record(name, duration, description);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Request, StatusCode, middleware::MiddlewareHandler, middleware::from_middleware};
+
+    async fn call(middleware: ServerTimingMiddleware, handler: MiddlewareHandler) -> Response {
+        let ctx = Context::new(Request::parse(b"GET / HTTP/1.1\r\n\r\n").unwrap().0);
+        Next::new(vec![from_middleware(Arc::new(middleware)), handler])
+            .run(ctx)
+            .await
+    }
+
+    #[tokio::test]
+    async fn emits_recorded_entries() {
+        let handler: MiddlewareHandler = Arc::new(|ctx: Context, _next: Next| {
+            Box::pin(async move {
+                ctx.timing("db", Duration::from_micros(12_340), Some("users \"all\""));
+                ctx.timing("cache hit", Duration::ZERO, None);
+                Response::new(StatusCode::Ok)
+            })
+        });
+        let res = call(ServerTimingMiddleware::new(), handler).await;
+        assert_eq!(
+            res.headers().get("Server-Timing"),
+            Some(r#"db;dur=12.3;desc="users \"all\"", cache_hit;dur=0.0"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn adds_nothing_without_entries_unless_totalling() {
+        let handler: MiddlewareHandler = Arc::new(|_ctx: Context, _next: Next| {
+            Box::pin(async { Response::new(StatusCode::Ok) })
+        });
+        let res = call(ServerTimingMiddleware::new(), handler.clone()).await;
+        assert_eq!(res.headers().get("Server-Timing"), None);
+
+        let res = call(ServerTimingMiddleware::new().total(true), handler).await;
+        let header = res.headers().get("Server-Timing").unwrap();
+        assert!(header.starts_with("total;dur="), "{header}");
+    }
+}
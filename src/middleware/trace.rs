@@ -0,0 +1,350 @@
+//! W3C trace context propagation with head-based sampling.
+//!
+//! [`TracingMiddleware`] reads the caller's `traceparent` header, or starts a new trace,
+//! and decides once per request — at the head — whether to trace it. A sampled request
+//! runs inside a `tracing` span carrying its trace and span ids, which a subscriber such
+//! as an OpenTelemetry bridge turns into an exported span; an unsampled one pays only
+//! for the decision. [`Sampler`] sets the rate per route, whether to follow the caller's
+//! decision, and whether failed requests are reported even when unsampled.
+//!
+//! The request's [`TraceContext`] is stored in the context extensions, so handlers can
+//! pass [`TraceContext::child`] on to the services they call.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use rttp::middleware::{Sampler, TracingMiddleware, from_middleware};
+//!
+//! let sampler = Sampler::new(0.01)
+//!     .route("/checkout/*", 1.0)
+//!     .route("/health", 0.0);
+//! let handler = from_middleware(Arc::new(TracingMiddleware::new(sampler)));
+//! ```
+
+use std::{fmt, future::Future, pin::Pin};
+
+use tokio::time::Instant;
+use tracing::Instrument;
+
+use super::{Middleware, Next};
+use crate::{
+    Response,
+    context::Context,
+    router::Pattern,
+    security::crypto::{from_hex, random_bytes, to_hex},
+};
+
+/// The trace a request belongs to, as carried by the W3C `traceparent` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    /// The id shared by every span of the trace.
+    pub trace_id: [u8; 16],
+    /// The id of this request's span.
+    pub span_id: [u8; 8],
+    /// Whether the trace is being recorded.
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    /// Parses a version `00` `traceparent` header value. Returns `None` if it is
+    /// malformed or carries an all-zero id.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rttp::middleware::TraceContext;
+    ///
+    /// let parent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    /// let context = TraceContext::parse(parent).unwrap();
+    /// assert!(context.sampled);
+    /// assert_eq!(context.to_string(), parent);
+    /// ```
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let (version, trace_id, span_id, flags) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        if version != "00" || parts.next().is_some() {
+            return None;
+        }
+        let trace_id: [u8; 16] = from_hex(trace_id)?.try_into().ok()?;
+        let span_id: [u8; 8] = from_hex(span_id)?.try_into().ok()?;
+        let flags = from_hex(flags)?;
+        if flags.len() != 1 || trace_id == [0; 16] || span_id == [0; 8] {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            span_id,
+            sampled: flags[0] & 1 == 1,
+        })
+    }
+
+    /// Starts a new trace with random ids.
+    pub fn new_root(sampled: bool) -> Self {
+        let mut trace_id = [0; 16];
+        trace_id.copy_from_slice(&random_bytes(16));
+        Self {
+            trace_id,
+            span_id: random_span_id(),
+            sampled,
+        }
+    }
+
+    /// Returns a context for a span within this trace, such as an outgoing call.
+    pub fn child(&self) -> Self {
+        Self {
+            span_id: random_span_id(),
+            ..*self
+        }
+    }
+}
+
+impl fmt::Display for TraceContext {
+    /// Formats the context as a `traceparent` header value.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            to_hex(&self.trace_id),
+            to_hex(&self.span_id),
+            u8::from(self.sampled)
+        )
+    }
+}
+
+fn random_span_id() -> [u8; 8] {
+    let mut id = [0; 8];
+    id.copy_from_slice(&random_bytes(8));
+    id
+}
+
+/// Head-based sampling rules for [`TracingMiddleware`].
+///
+/// A request whose caller sent a `traceparent` follows the caller's decision when
+/// [`parent_based`](Self::parent_based) is on, as it is by default, so a trace is
+/// recorded in every service or in none. Otherwise the request is sampled at the rate
+/// of the first route pattern its path matches, or the default rate. The decision is
+/// derived from the trace id, so services using the same rate agree on it.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Sampler {
+    rate: f64,
+    routes: Vec<(Pattern, f64)>,
+    parent_based: bool,
+    always_on_error: bool,
+}
+
+impl Sampler {
+    /// Samples requests at `rate`, from `0.0` (none) to `1.0` (all).
+    pub fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            routes: Vec::new(),
+            parent_based: true,
+            always_on_error: true,
+        }
+    }
+
+    /// Samples requests whose path matches `pattern` at `rate` instead.
+    pub fn route(mut self, pattern: &str, rate: f64) -> Self {
+        self.routes.push((Pattern::parse(pattern), rate));
+        self
+    }
+
+    /// Whether to follow the sampling decision of an incoming `traceparent`.
+    pub fn parent_based(mut self, parent_based: bool) -> Self {
+        self.parent_based = parent_based;
+        self
+    }
+
+    /// Whether to report unsampled requests that fail with a `5xx` status. On by
+    /// default.
+    pub fn always_on_error(mut self, always_on_error: bool) -> Self {
+        self.always_on_error = always_on_error;
+        self
+    }
+
+    /// Decides whether to sample a request for `path` in trace `trace_id`.
+    pub fn should_sample(&self, path: &str, trace_id: &[u8; 16], parent: Option<bool>) -> bool {
+        if let (true, Some(sampled)) = (self.parent_based, parent) {
+            return sampled;
+        }
+        let rate = self
+            .routes
+            .iter()
+            .find(|(pattern, _)| pattern.matches(path).is_some())
+            .map_or(self.rate, |(_, rate)| *rate);
+        // As OpenTelemetry's ratio sampler: compare the id's low 8 bytes to the rate.
+        let mut low = [0; 8];
+        low.copy_from_slice(&trace_id[8..]);
+        let value = u64::from_be_bytes(low) >> 11;
+        (value as f64) < rate * (1u64 << 53) as f64
+    }
+}
+
+impl Default for Sampler {
+    /// Samples every request.
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+/// Propagates trace context and records a span for each sampled request.
+///
+/// Sampled requests run inside an `INFO` span named `request` with the method, path,
+/// trace id, and span id, and end with an event giving the status and duration.
+/// Unsampled requests that fail with a `5xx` status are reported with a `WARN` event
+/// carrying the trace id unless [`Sampler::always_on_error`] is off.
+#[derive(Debug, Clone, Default)]
+pub struct TracingMiddleware {
+    sampler: Sampler,
+}
+
+impl TracingMiddleware {
+    /// Creates the middleware with `sampler`.
+    pub fn new(sampler: Sampler) -> Self {
+        Self { sampler }
+    }
+}
+
+impl Middleware for TracingMiddleware {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let request = ctx.request();
+        let parent = request
+            .headers()
+            .get("traceparent")
+            .and_then(TraceContext::parse);
+        let mut trace = match parent {
+            Some(parent) => parent.child(),
+            None => TraceContext::new_root(false),
+        };
+        trace.sampled = self.sampler.should_sample(
+            request.path(),
+            &trace.trace_id,
+            parent.map(|parent| parent.sampled),
+        );
+        let method = request.method().as_str().to_owned();
+        let path = request.path().to_owned();
+        let trace_id = to_hex(&trace.trace_id);
+        ctx.extensions_mut().insert(trace);
+
+        if !trace.sampled {
+            if !self.sampler.always_on_error {
+                return next.run(ctx);
+            }
+            return Box::pin(async move {
+                let response = next.run(ctx).await;
+                let status = response.status().as_u16();
+                if status >= 500 {
+                    tracing::warn!(%trace_id, %method, %path, status, "unsampled request failed");
+                }
+                response
+            });
+        }
+
+        let span = tracing::info_span!(
+            "request",
+            %method,
+            %path,
+            %trace_id,
+            span_id = %to_hex(&trace.span_id),
+        );
+        Box::pin(
+            async move {
+                let started = Instant::now();
+                let response = next.run(ctx).await;
+                tracing::info!(
+                    status = response.status().as_u16(),
+                    duration = ?started.elapsed(),
+                    "request finished"
+                );
+                response
+            }
+            .instrument(span),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Request, StatusCode,
+        middleware::{MiddlewareHandler, from_middleware},
+    };
+    use std::sync::Arc;
+
+    const PARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn parses_and_formats_traceparent() {
+        let context = TraceContext::parse(PARENT).unwrap();
+        assert_eq!(context.to_string(), PARENT);
+        let child = context.child();
+        assert_eq!(child.trace_id, context.trace_id);
+        assert_ne!(child.span_id, context.span_id);
+
+        for invalid in [
+            "",
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e47-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-x",
+        ] {
+            assert!(TraceContext::parse(invalid).is_none(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn samples_by_route_rate_and_parent() {
+        let sampler = Sampler::new(0.0)
+            .route("/checkout/*", 1.0)
+            .route("/half", 0.5);
+        let low = [0; 16];
+        let mut high = [0xff; 16];
+        high[0] = 1;
+
+        assert!(!sampler.should_sample("/users", &low, None));
+        assert!(sampler.should_sample("/checkout/pay", &high, None));
+        assert!(sampler.should_sample("/half", &low, None));
+        assert!(!sampler.should_sample("/half", &high, None));
+
+        assert!(sampler.should_sample("/users", &low, Some(true)), "parent");
+        assert!(!sampler.should_sample("/checkout/pay", &low, Some(false)));
+        let sampler = sampler.parent_based(false);
+        assert!(!sampler.should_sample("/users", &low, Some(true)));
+    }
+
+    #[tokio::test]
+    async fn stores_the_context_for_handlers() {
+        let handler: MiddlewareHandler = Arc::new(|ctx: Context, _next: Next| {
+            let trace = *ctx.extensions().get::<TraceContext>().unwrap();
+            Box::pin(async move { Response::new(StatusCode::Ok).body(trace.to_string()) })
+        });
+        let chain: Arc<[MiddlewareHandler]> = vec![
+            from_middleware(Arc::new(TracingMiddleware::new(Sampler::new(0.0)))),
+            handler,
+        ]
+        .into();
+
+        let request = Request::builder().header("traceparent", PARENT).build();
+        let response = Next::new(Arc::clone(&chain))
+            .run(Context::new(request))
+            .await;
+        let trace = TraceContext::parse(response.body_text().as_ref()).unwrap();
+        assert_eq!(to_hex(&trace.trace_id), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert!(trace.sampled, "follows the parent");
+
+        let response = Next::new(chain)
+            .run(Context::new(Request::builder().build()))
+            .await;
+        let trace = TraceContext::parse(response.body_text().as_ref()).unwrap();
+        assert!(!trace.sampled);
+    }
+}
@@ -0,0 +1,170 @@
+//! The upstream side of a proxied exchange: one connection per request.
+
+use std::time::Duration;
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufStream},
+    net::TcpStream,
+};
+
+use super::ProxyError;
+
+// The largest upstream response head accepted.
+const MAX_HEAD: usize = 64 * 1024;
+
+// Bytes relayed per read of the upstream body.
+const CHUNK: usize = 16 * 1024;
+
+// A response whose head has been read; the body is still on the connection.
+pub(super) struct UpstreamResponse {
+    pub(super) status: u16,
+    pub(super) headers: Vec<(String, String)>,
+    pub(super) body: Body,
+}
+
+// How the end of the body is marked. The request asks for `Connection: close`, so
+// everything but a length-delimited body simply runs to the end of the connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Framing {
+    Length(u64),
+    UntilClose,
+    Empty,
+}
+
+pub(super) struct Body {
+    stream: BufStream<TcpStream>,
+    framing: Framing,
+}
+
+// Connects to `authority` (`host` or `host:port`) within `timeout`.
+pub(super) async fn connect(
+    authority: &str,
+    timeout: Duration,
+) -> Result<BufStream<TcpStream>, ProxyError> {
+    let addr = if authority.contains(':') {
+        authority.to_owned()
+    } else {
+        format!("{authority}:80")
+    };
+    let stream = tokio::time::timeout(timeout, TcpStream::connect(addr))
+        .await
+        .map_err(|_| ProxyError::Timeout(timeout))?
+        .map_err(ProxyError::Connect)?;
+    let _ = stream.set_nodelay(true);
+    Ok(BufStream::new(stream))
+}
+
+// Writes the request and reads the response head. `bodiless` is set for `HEAD`
+// requests, whose responses carry framing headers but no body.
+pub(super) async fn exchange(
+    mut stream: BufStream<TcpStream>,
+    head: &[u8],
+    body: &[u8],
+    bodiless: bool,
+) -> Result<UpstreamResponse, ProxyError> {
+    stream.write_all(head).await?;
+    stream.write_all(body).await?;
+    stream.flush().await?;
+
+    let mut status_line = String::new();
+    if stream.read_line(&mut status_line).await? == 0 {
+        return Err(ProxyError::Malformed(
+            "connection closed before a response".into(),
+        ));
+    }
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| ProxyError::Malformed(format!("bad status line {status_line:?}")))?;
+
+    let mut headers = Vec::new();
+    let mut read = status_line.len();
+    let mut framing = Framing::UntilClose;
+    let mut chunked = false;
+    loop {
+        let mut line = String::new();
+        let n = stream.read_line(&mut line).await?;
+        read += n;
+        if n == 0 {
+            return Err(ProxyError::Malformed("connection closed in headers".into()));
+        }
+        if read > MAX_HEAD {
+            return Err(ProxyError::Malformed("response head too large".into()));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(ProxyError::Malformed(format!("bad header line {line:?}")));
+        };
+        let (name, value) = (name.trim(), value.trim());
+        if name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = true;
+        } else if name.eq_ignore_ascii_case("content-length") {
+            let length = value
+                .parse()
+                .map_err(|_| ProxyError::Malformed(format!("bad Content-Length {value:?}")))?;
+            framing = Framing::Length(length);
+        }
+        headers.push((name.to_owned(), value.to_owned()));
+    }
+    // A chunked body is relayed still encoded and ends when the upstream closes.
+    if chunked {
+        framing = Framing::UntilClose;
+    }
+    if bodiless || matches!(status, 100..=199 | 204 | 304) {
+        framing = Framing::Empty;
+    }
+    Ok(UpstreamResponse {
+        status,
+        headers,
+        body: Body { stream, framing },
+    })
+}
+
+impl Body {
+    // The connection, for a tunnel after `101 Switching Protocols`. Bytes the upstream
+    // sent after its response head are still buffered in it.
+    pub(super) fn into_stream(self) -> BufStream<TcpStream> {
+        self.stream
+    }
+
+    // Copies the body to `out`, giving up if the upstream is silent for `idle`.
+    pub(super) async fn relay<W>(
+        mut self,
+        out: &mut W,
+        idle: Option<Duration>,
+    ) -> Result<(), ProxyError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut remaining = match self.framing {
+            Framing::Empty => return Ok(()),
+            Framing::Length(length) => length,
+            Framing::UntilClose => u64::MAX,
+        };
+        let mut buf = vec![0; CHUNK];
+        while remaining > 0 {
+            let want = remaining.min(CHUNK as u64) as usize;
+            let read = self.stream.read(&mut buf[..want]);
+            let n = match idle {
+                Some(idle) => tokio::time::timeout(idle, read)
+                    .await
+                    .map_err(|_| ProxyError::Timeout(idle))??,
+                None => read.await?,
+            };
+            if n == 0 {
+                if self.framing == Framing::UntilClose {
+                    break;
+                }
+                return Err(ProxyError::Malformed("connection closed mid-body".into()));
+            }
+            out.write_all(&buf[..n]).await?;
+            out.flush().await?;
+            remaining -= n as u64;
+        }
+        Ok(())
+    }
+}
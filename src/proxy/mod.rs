@@ -0,0 +1,764 @@
+//! Reverse proxying — forward requests to an upstream HTTP server.
+//!
+//! A [`ReverseProxy`] is a route handler: register it for the paths it should cover
+//! (usually a wildcard such as `/api/*`, with [`strip_prefix`](ReverseProxy::strip_prefix))
+//! and it forwards each matched request to its upstream, relaying the response back.
+//!
+//! - **Headers** — hop-by-hop headers (`Connection`, `Keep-Alive`, `Upgrade`, … and
+//!   any named in `Connection`) are dropped. `Host` is rewritten to the upstream
+//!   unless [`preserve_host`](ReverseProxy::preserve_host) is set; the client address is
+//!   appended to `X-Forwarded-For`, and `X-Forwarded-Host` and `X-Forwarded-Proto`
+//!   record what the client asked for.
+//! - **Bodies** — the server hands handlers a fully read request, so the request body
+//!   is sent in one piece. The response body is relayed byte for byte whatever its
+//!   content type — no re-encoding, no added charset — and each piece is flushed to the
+//!   client as soon as it arrives, so large downloads, Server-Sent Events, and framed
+//!   binary protocols such as gRPC-Web all pass through unbuffered. `text/event-stream`
+//!   responses are exempt from the [timeout](ReverseProxy::timeout) between pieces, as
+//!   events may be far apart.
+//! - **WebSockets** — an `Upgrade: websocket` handshake is passed to the upstream with
+//!   its upgrade headers intact. If the upstream switches protocols, the proxy does too
+//!   and then copies bytes both ways until either side closes.
+//! - **Failures** — an upstream that cannot be reached or answers with garbage yields
+//!   `502 Bad Gateway`; one that is too slow yields `504 Gateway Timeout`. Failed
+//!   connection attempts are [retried](ReverseProxy::retries), as are failed exchanges
+//!   for idempotent methods.
+//!
+//! To spread load over several servers, give [`ReverseProxy::balanced`] an
+//! [`UpstreamPool`]: it balances by [`Strategy`], caps each upstream's requests in
+//! flight, and ejects upstreams that keep failing. Retries go to a different upstream
+//! when one is available.
+//!
+//! Only plain `http://` upstreams are supported, and upgrades to protocols other than
+//! WebSocket are not proxied.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use rttp::Router;
+//! use rttp::proxy::ReverseProxy;
+//!
+//! let mut router = Router::new();
+//! ReverseProxy::new("http://127.0.0.1:9000")
+//!     .strip_prefix("/api")
+//!     .timeout(Duration::from_secs(10))
+//!     .retries(2)
+//!     .mount(&mut router, "/api/*");
+//! ```
+
+mod client;
+mod pool;
+
+pub use pool::{Strategy, Upstream, UpstreamPool};
+
+use std::{io, sync::Arc, time::Duration};
+
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::{
+    Method, Request, Response, Router, StatusCode, context::Context, http::upgrade::OnUpgrade,
+    router::IntoHandler,
+};
+
+/// Why a request could not be proxied.
+#[derive(Debug, Error)]
+pub enum ProxyError {
+    #[error("could not connect to upstream: {0}")]
+    Connect(#[source] io::Error),
+
+    #[error("upstream did not respond within {0:?}")]
+    Timeout(Duration),
+
+    #[error("upstream I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("malformed upstream response: {0}")]
+    Malformed(String),
+
+    #[error("no upstream available")]
+    Unavailable,
+}
+
+impl ProxyError {
+    /// The status sent to the client: `504` for timeouts, `503` when every upstream is
+    /// ejected or at its connection limit, `502` otherwise.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::Timeout(_) => StatusCode::GatewayTimeout,
+            Self::Unavailable => StatusCode::ServiceUnavailable,
+            _ => StatusCode::BadGateway,
+        }
+    }
+}
+
+// Headers that describe one connection rather than the message, never forwarded.
+const HOP_BY_HOP: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "upgrade",
+];
+
+// Request headers the proxy sets itself. The body arrives decoded, so its transfer
+// coding and length are restated.
+const REPLACED: [&str; 7] = [
+    "host",
+    "content-length",
+    "transfer-encoding",
+    "expect",
+    "x-forwarded-for",
+    "x-forwarded-host",
+    "x-forwarded-proto",
+];
+
+/// Forwards requests to an upstream `http://` server, or to a balanced
+/// [`UpstreamPool`] of them.
+///
+/// Cheap to clone; clones share configuration and the pool's state. Defaults: a 5-second connect timeout,
+/// a 30-second response timeout, and no retries.
+#[derive(Debug, Clone)]
+pub struct ReverseProxy {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Clone)]
+struct Inner {
+    pool: Arc<UpstreamPool>,
+    strip_prefix: Option<String>,
+    preserve_host: bool,
+    connect_timeout: Duration,
+    timeout: Duration,
+    retries: u32,
+}
+
+impl ReverseProxy {
+    /// Creates a proxy to `upstream`, an `http://host[:port][/base]` URL. A base path is
+    /// prepended to every forwarded path.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `upstream` is not a valid `http://` URL.
+    pub fn new(upstream: &str) -> Self {
+        Self::balanced(UpstreamPool::new(Strategy::RoundRobin).upstream(Upstream::new(upstream)))
+    }
+
+    /// Creates a proxy spreading requests over `pool`.
+    pub fn balanced(pool: UpstreamPool) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                pool: Arc::new(pool),
+                strip_prefix: None,
+                preserve_host: false,
+                connect_timeout: Duration::from_secs(5),
+                timeout: Duration::from_secs(30),
+                retries: 0,
+            }),
+        }
+    }
+
+    /// Removes `prefix` from the start of request paths before forwarding them, so a
+    /// proxy mounted at `/api/*` can send `/api/users` upstream as `/users`.
+    #[must_use]
+    pub fn strip_prefix(mut self, prefix: impl Into<String>) -> Self {
+        let prefix = prefix.into();
+        self.inner_mut().strip_prefix = Some(prefix.trim_end_matches('/').to_owned());
+        self
+    }
+
+    /// Forwards the client's `Host` header instead of the upstream's authority.
+    #[must_use]
+    pub fn preserve_host(mut self, preserve: bool) -> Self {
+        self.inner_mut().preserve_host = preserve;
+        self
+    }
+
+    /// Sets how long connecting to the upstream may take.
+    #[must_use]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.inner_mut().connect_timeout = timeout;
+        self
+    }
+
+    /// Sets how long to wait for the upstream's response head, and then for each piece
+    /// of its body.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.inner_mut().timeout = timeout;
+        self
+    }
+
+    /// Sets how many more times a failed request is tried, on another upstream if the
+    /// pool has one free. Connection failures are always retried; failures after the
+    /// request was sent only for idempotent methods.
+    #[must_use]
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.inner_mut().retries = retries;
+        self
+    }
+
+    /// Registers the proxy on `router` for `path` under every method the router
+    /// supports.
+    pub fn mount(self, router: &mut Router, path: &str) {
+        router.get(path, self.clone());
+        router.post(path, self.clone());
+        router.put(path, self.clone());
+        router.patch(path, self.clone());
+        router.delete(path, self.clone());
+        router.options(path, self);
+    }
+
+    /// Forwards `ctx`'s request, answering with `502` or `504` if the upstream fails.
+    pub async fn handle(&self, ctx: Context) -> Response {
+        match self.forward(ctx.request()).await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!(
+                    path = %ctx.request().path(),
+                    error = %e,
+                    "proxy request failed"
+                );
+                Response::new(e.status()).body(e.status().canonical_reason())
+            }
+        }
+    }
+
+    /// Forwards `request` and returns the upstream's response, its body still to be
+    /// streamed.
+    ///
+    /// # Errors
+    ///
+    /// See [`ProxyError`]. Once the response head has arrived, failures only cut the
+    /// body short.
+    pub async fn forward(&self, request: &Request) -> Result<Response, ProxyError> {
+        let inner = &self.inner;
+        let idempotent = request.method().is_idempotent();
+        let websocket = wants_websocket(request);
+        let bodiless = *request.method() == Method::Head;
+        let mut tried = Vec::new();
+        let mut last_error = None;
+        let (upstream, lease) = loop {
+            let Some(lease) = inner.pool.acquire(&tried) else {
+                return Err(last_error.unwrap_or(ProxyError::Unavailable));
+            };
+            tried.push(lease.index());
+            let target = lease.upstream();
+            let head = self.request_head(request, target, websocket);
+            let (error, retryable) =
+                match client::connect(&target.authority, inner.connect_timeout).await {
+                    Ok(stream) => {
+                        let exchange = client::exchange(stream, &head, request.body(), bodiless);
+                        match tokio::time::timeout(inner.timeout, exchange).await {
+                            Ok(Ok(upstream)) => {
+                                lease.succeeded();
+                                break (upstream, lease);
+                            }
+                            Ok(Err(e)) => (e, idempotent),
+                            Err(_) => (ProxyError::Timeout(inner.timeout), idempotent),
+                        }
+                    }
+                    Err(e) => (e, true),
+                };
+            lease.failed();
+            if !retryable || tried.len() > inner.retries as usize {
+                return Err(error);
+            }
+            debug!(
+                upstream = %target.authority,
+                attempt = tried.len(),
+                error = %error,
+                "retrying proxy request"
+            );
+            last_error = Some(error);
+        };
+
+        if upstream.status == 101 {
+            if !websocket {
+                return Err(ProxyError::Malformed("unrequested protocol switch".into()));
+            }
+            return Ok(tunnel(upstream, lease));
+        }
+
+        let status = StatusCode::from_u16(upstream.status).ok_or_else(|| {
+            ProxyError::Malformed(format!("unsupported status {}", upstream.status))
+        })?;
+        let mut response = Response::new(status);
+        let listed = connection_tokens(
+            upstream
+                .headers
+                .iter()
+                .map(|(n, v)| (n.as_str(), v.as_str())),
+        );
+        for (name, value) in &upstream.headers {
+            // The body is relayed as sent, so its transfer coding stays.
+            if !is_hop_by_hop(name, &listed) {
+                response.add_header(name, value);
+            }
+        }
+        let event_stream = response
+            .headers()
+            .get("content-type")
+            .is_some_and(|t| t.trim_start().starts_with("text/event-stream"));
+        let idle = (!event_stream).then_some(inner.timeout);
+        let body = upstream.body;
+        Ok(response.stream(OnUpgrade::new(move |mut io| async move {
+            if let Err(e) = body.relay(&mut io, idle).await {
+                let upstream = &lease.upstream().authority;
+                warn!(%upstream, error = %e, "proxied response body cut short");
+            }
+            // In flight until the body is through.
+            drop(lease);
+        })))
+    }
+
+    // Builds the request line and headers sent upstream.
+    fn request_head(&self, request: &Request, upstream: &Upstream, websocket: bool) -> Vec<u8> {
+        let inner = &self.inner;
+        let path = request.path();
+        let path = match &inner.strip_prefix {
+            Some(prefix) => match path.strip_prefix(prefix.as_str()) {
+                Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+                _ => path,
+            },
+            None => path,
+        };
+        let path = if path.is_empty() { "/" } else { path };
+        let mut target = format!("{}{path}", upstream.base);
+        if let Some(query) = request.query_string() {
+            target.push('?');
+            target.push_str(query);
+        }
+
+        let headers = request.headers();
+        let original_host = headers.get("host");
+        let host = match original_host {
+            Some(host) if inner.preserve_host => host,
+            _ => &upstream.authority,
+        };
+        let mut head = format!("{} {target} HTTP/1.1\r\nHost: {host}\r\n", request.method());
+        let listed = connection_tokens(headers.iter());
+        for (name, value) in headers.iter() {
+            let replaced = REPLACED.iter().any(|h| name.eq_ignore_ascii_case(h));
+            if !replaced && !is_hop_by_hop(name, &listed) {
+                head.push_str(&format!("{name}: {value}\r\n"));
+            }
+        }
+
+        let connection = request.connection();
+        let mut forwarded_for: Vec<String> = headers
+            .get_all("x-forwarded-for")
+            .map(str::to_owned)
+            .collect();
+        if let Some(connection) = connection {
+            forwarded_for.push(connection.peer_addr().ip().to_string());
+        }
+        if !forwarded_for.is_empty() {
+            head.push_str(&format!(
+                "X-Forwarded-For: {}\r\n",
+                forwarded_for.join(", ")
+            ));
+        }
+        if let Some(host) = original_host {
+            head.push_str(&format!("X-Forwarded-Host: {host}\r\n"));
+        }
+        let proto = if connection.is_some_and(|c| c.is_tls()) {
+            "https"
+        } else {
+            "http"
+        };
+        head.push_str(&format!("X-Forwarded-Proto: {proto}\r\n"));
+
+        let body = request.body();
+        if !body.is_empty()
+            || matches!(request.method(), Method::Post | Method::Put | Method::Patch)
+        {
+            head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        if websocket {
+            head.push_str("Upgrade: websocket\r\nConnection: Upgrade\r\n\r\n");
+        } else {
+            head.push_str("Connection: close\r\n\r\n");
+        }
+        head.into_bytes()
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        Arc::make_mut(&mut self.inner)
+    }
+}
+
+impl IntoHandler for ReverseProxy {
+    fn call(&self, ctx: Context) -> std::pin::Pin<Box<dyn Future<Output = Response> + Send>> {
+        let proxy = self.clone();
+        Box::pin(async move { proxy.handle(ctx).await })
+    }
+}
+
+// True for a WebSocket handshake: `Upgrade: websocket` with `Connection: upgrade`.
+fn wants_websocket(request: &Request) -> bool {
+    let headers = request.headers();
+    headers
+        .get("upgrade")
+        .is_some_and(|u| u.trim().eq_ignore_ascii_case("websocket"))
+        && connection_tokens(headers.iter())
+            .iter()
+            .any(|t| t == "upgrade")
+}
+
+// Answers the client's handshake with the upstream's `101`, then joins the two
+// connections.
+fn tunnel(upstream: client::UpstreamResponse, lease: pool::Lease) -> Response {
+    let mut response = Response::new(StatusCode::SwitchingProtocols)
+        .header("Upgrade", "websocket")
+        .header("Connection", "Upgrade");
+    let listed = connection_tokens(
+        upstream
+            .headers
+            .iter()
+            .map(|(n, v)| (n.as_str(), v.as_str())),
+    );
+    for (name, value) in &upstream.headers {
+        if !is_hop_by_hop(name, &listed) {
+            response.add_header(name, value);
+        }
+    }
+    let mut stream = upstream.body.into_stream();
+    response.on_upgrade(OnUpgrade::new(move |mut io| async move {
+        if let Err(e) = tokio::io::copy_bidirectional(&mut io, &mut stream).await {
+            let upstream = &lease.upstream().authority;
+            debug!(%upstream, error = %e, "proxied WebSocket closed");
+        }
+        drop(lease);
+    }))
+}
+
+// Lower-cased header names listed in `Connection`, which are hop-by-hop too.
+fn connection_tokens<'a>(headers: impl Iterator<Item = (&'a str, &'a str)>) -> Vec<String> {
+    headers
+        .filter(|(name, _)| name.eq_ignore_ascii_case("connection"))
+        .flat_map(|(_, value)| value.split(','))
+        .map(|token| token.trim().to_ascii_lowercase())
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+fn is_hop_by_hop(name: &str, listed: &[String]) -> bool {
+    HOP_BY_HOP.iter().any(|h| name.eq_ignore_ascii_case(h))
+        || listed.iter().any(|h| name.eq_ignore_ascii_case(h))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    use super::*;
+    use crate::testing::TestClient;
+
+    // Serves one connection per reply, returning the requests received; `None` closes
+    // the connection without answering.
+    async fn upstream(replies: Vec<Option<&'static str>>) -> (SocketAddr, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let task = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for reply in replies {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                requests.push(String::from_utf8(request).unwrap());
+                if let Some(reply) = reply {
+                    stream.write_all(reply.as_bytes()).await.unwrap();
+                }
+            }
+            requests
+        });
+        (addr, task)
+    }
+
+    #[tokio::test]
+    async fn forwards_and_rewrites_headers() {
+        let (addr, requests) = upstream(vec![Some(
+            "HTTP/1.1 201 Created\r\nContent-Length: 5\r\nX-Upstream: yes\r\n\
+             Keep-Alive: timeout=5\r\nConnection: close\r\n\r\nhello",
+        )])
+        .await;
+        let mut router = Router::new();
+        ReverseProxy::new(&format!("http://{addr}/v2"))
+            .strip_prefix("/api")
+            .mount(&mut router, "/api/*");
+
+        let mut client = TestClient::new(router);
+        let response = client
+            .post("/api/users?page=2")
+            .header("Host", "example.com")
+            .header("X-Forwarded-For", "203.0.113.7")
+            .header("Connection", "X-Secret")
+            .header("X-Secret", "hop")
+            .header("X-Kept", "end-to-end")
+            .body("{}")
+            .await;
+        response
+            .assert_status(StatusCode::Created)
+            .assert_header("x-upstream", "yes")
+            .assert_text("hello");
+        assert!(response.header("keep-alive").is_none());
+
+        let request = requests.await.unwrap().remove(0);
+        assert!(
+            request.starts_with("POST /v2/users?page=2 HTTP/1.1\r\n"),
+            "{request}"
+        );
+        assert!(request.contains(&format!("Host: {addr}\r\n")));
+        assert!(request.contains("X-Forwarded-For: 203.0.113.7, 127.0.0.1\r\n"));
+        assert!(request.contains("X-Forwarded-Host: example.com\r\n"));
+        assert!(request.contains("X-Forwarded-Proto: http\r\n"));
+        assert!(request.contains("X-Kept: end-to-end\r\n"));
+        assert!(request.contains("Content-Length: 2\r\n"));
+        assert!(!request.contains("X-Secret"));
+    }
+
+    #[tokio::test]
+    async fn retries_and_maps_failures() {
+        let client = |addr: SocketAddr| {
+            let mut router = Router::new();
+            ReverseProxy::new(&format!("http://{addr}"))
+                .retries(1)
+                .timeout(Duration::from_millis(50))
+                .mount(&mut router, "/*");
+            TestClient::new(router)
+        };
+
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let (addr, requests) = upstream(vec![None, Some(ok)]).await;
+        client(addr)
+            .get("/retry")
+            .await
+            .assert_status(StatusCode::Ok)
+            .assert_text("ok");
+        assert_eq!(requests.await.unwrap().len(), 2);
+
+        // A POST may have taken effect, so it is not sent twice.
+        let (addr, requests) = upstream(vec![None]).await;
+        client(addr)
+            .post("/once")
+            .await
+            .assert_status(StatusCode::BadGateway);
+        assert_eq!(requests.await.unwrap().len(), 1);
+
+        // Accepted by the kernel but never answered.
+        let silent = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        client(silent.local_addr().unwrap())
+            .get("/slow")
+            .await
+            .assert_status(StatusCode::GatewayTimeout);
+
+        let closed = silent.local_addr().unwrap();
+        drop(silent);
+        client(closed)
+            .get("/down")
+            .await
+            .assert_status(StatusCode::BadGateway);
+    }
+
+    #[tokio::test]
+    async fn pools_route_around_dead_upstreams() {
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let (live, requests) = upstream(vec![Some(ok), Some(ok)]).await;
+        let dead = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dead_addr = dead.local_addr().unwrap();
+        drop(dead);
+
+        let pool = UpstreamPool::new(Strategy::RoundRobin)
+            .upstream(Upstream::new(&format!("http://{dead_addr}")))
+            .upstream(Upstream::new(&format!("http://{live}")))
+            .eject_after(1, Duration::from_secs(60));
+        let mut router = Router::new();
+        ReverseProxy::balanced(pool)
+            .retries(1)
+            .mount(&mut router, "/*");
+        let mut client = TestClient::new(router);
+
+        // The dead upstream is tried first, fails, and is ejected; the retry succeeds.
+        client.get("/a").await.assert_status(StatusCode::Ok);
+        client.get("/b").await.assert_status(StatusCode::Ok);
+        assert_eq!(requests.await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn event_streams_outlive_the_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\ndata: 1\n\n")
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            stream.write_all(b"data: 2\n\n").await.unwrap();
+        });
+        let mut router = Router::new();
+        ReverseProxy::new(&format!("http://{addr}"))
+            .timeout(Duration::from_millis(30))
+            .mount(&mut router, "/*");
+
+        TestClient::new(router)
+            .get("/events")
+            .await
+            .assert_header("content-type", "text/event-stream")
+            .assert_text("data: 1\n\ndata: 2\n\n");
+    }
+
+    #[tokio::test]
+    async fn tunnels_websockets() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_addr = listener.local_addr().unwrap();
+        let upstream = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(
+                    b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                      Connection: Upgrade\r\nSec-WebSocket-Accept: abc\r\n\r\nhello",
+                )
+                .await
+                .unwrap();
+            // Echo until the client hangs up.
+            loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                stream.write_all(&buf[..n]).await.unwrap();
+            }
+            String::from_utf8(request).unwrap()
+        });
+
+        let mut router = Router::new();
+        ReverseProxy::new(&format!("http://{upstream_addr}")).mount(&mut router, "/*");
+        let server = crate::Server::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr();
+        let pipeline = vec![crate::middleware::from_middleware(Arc::new(router))];
+        tokio::spawn(
+            server.run(move |req| {
+                crate::middleware::Next::new(pipeline.clone()).run(Context::new(req))
+            }),
+        );
+
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(
+                b"GET /chat HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+                  Connection: Upgrade\r\nSec-WebSocket-Key: k\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut received = Vec::new();
+        let mut buf = [0; 1024];
+        while !received.ends_with(b"hello") {
+            let n = client.read(&mut buf).await.unwrap();
+            assert!(n > 0, "{}", String::from_utf8_lossy(&received));
+            received.extend_from_slice(&buf[..n]);
+        }
+        let head = String::from_utf8(received).unwrap();
+        assert!(
+            head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"),
+            "{head}"
+        );
+        assert!(head.contains("Sec-WebSocket-Accept: abc\r\n"));
+
+        client.write_all(b"ping").await.unwrap();
+        let n = client.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"ping");
+        drop(client);
+
+        let request = upstream.await.unwrap();
+        assert!(request.contains("Upgrade: websocket\r\nConnection: Upgrade\r\n"));
+        assert!(request.contains("Sec-WebSocket-Key: k\r\n"));
+    }
+
+    #[tokio::test]
+    async fn binary_frames_pass_through_unchanged() {
+        use bytes::BytesMut;
+        use tokio::{io::duplex, sync::oneshot};
+
+        let first: &[u8] = b"\x00\x00\x00\x00\x03\xff\r\n";
+        let second: &[u8] = b"\x80\x00\x00\x00\x0fgrpc-status:0\r\n";
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (delivered, wait) = oneshot::channel::<()>();
+        let upstream = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\x01\x02") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: application/grpc-web+proto\r\n\r\n")
+                .await
+                .unwrap();
+            stream.write_all(first).await.unwrap();
+            // The second frame is only sent once the first has reached the client.
+            wait.await.unwrap();
+            stream.write_all(second).await.unwrap();
+            request
+        });
+
+        let request = Request::builder()
+            .method(Method::Post)
+            .path("/svc.Echo/Say")
+            .header("Content-Type", "application/grpc-web+proto")
+            .body(&b"\x00\x00\x00\x00\x02\x01\x02"[..])
+            .build();
+        let mut response = ReverseProxy::new(&format!("http://{addr}"))
+            .forward(&request)
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers().get("content-type"),
+            Some("application/grpc-web+proto")
+        );
+        let writer = response.take_upgrade().unwrap();
+        let head = String::from_utf8(response.into_bytes().to_vec()).unwrap();
+        assert!(!head.contains("charset"), "{head}");
+
+        let (server, mut client) = duplex(1024);
+        tokio::spawn(writer.run(crate::http::Upgraded::new(server, BytesMut::new())));
+        let mut buf = vec![0; first.len()];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, first);
+        delivered.send(()).unwrap();
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, second);
+
+        let sent = upstream.await.unwrap();
+        assert!(sent.ends_with(
+            b"Content-Length: 7\r\nConnection: close\r\n\r\n\x00\x00\x00\x00\x02\x01\x02"
+        ));
+    }
+}
@@ -0,0 +1,413 @@
+//! Load-balanced groups of upstream servers.
+
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use tracing::warn;
+
+use crate::{
+    clock::{self, Clock},
+    http::client::parse_url,
+};
+
+/// One server in an [`UpstreamPool`].
+///
+/// Defaults: weight 1 and no connection limit.
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    // `host` or `host:port`.
+    pub(super) authority: String,
+    // Path prepended to forwarded paths, without a trailing slash.
+    pub(super) base: String,
+    weight: u32,
+    max_connections: Option<usize>,
+}
+
+impl Upstream {
+    /// Creates an upstream from an `http://host[:port][/base]` URL. A base path is
+    /// prepended to every forwarded path.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `url` is not a valid `http://` URL.
+    pub fn new(url: &str) -> Self {
+        let target = parse_url(url)
+            .filter(|target| !target.tls && !target.path.contains('?'))
+            .unwrap_or_else(|| panic!("invalid upstream URL {url:?}"));
+        Self {
+            authority: target.authority.to_owned(),
+            base: target.path.trim_end_matches('/').to_owned(),
+            weight: 1,
+            max_connections: None,
+        }
+    }
+
+    /// Sets the share of traffic this upstream gets relative to the others, under
+    /// [`Strategy::Weighted`] and [`Strategy::LeastConnections`]. Zero is treated as one.
+    #[must_use]
+    pub fn weight(mut self, weight: u32) -> Self {
+        self.weight = weight.max(1);
+        self
+    }
+
+    /// Caps the requests in flight to this upstream, counting until each response body
+    /// has been relayed. A full upstream is skipped.
+    #[must_use]
+    pub fn max_connections(mut self, limit: usize) -> Self {
+        self.max_connections = Some(limit);
+        self
+    }
+
+    /// Returns the `host[:port]` requests are sent to.
+    pub fn authority(&self) -> &str {
+        &self.authority
+    }
+}
+
+/// How an [`UpstreamPool`] picks the upstream for each request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    /// Each upstream in turn, ignoring weights.
+    #[default]
+    RoundRobin,
+    /// The upstream with the fewest requests in flight relative to its weight; ties go
+    /// round-robin.
+    LeastConnections,
+    /// Each upstream in proportion to its weight, interleaved rather than in bursts
+    /// (smooth weighted round-robin).
+    Weighted,
+}
+
+/// A group of interchangeable upstreams behind one [`ReverseProxy`](super::ReverseProxy).
+///
+/// Upstreams that are [ejected](Self::eject_after) or at their
+/// [connection limit](Upstream::max_connections) are passed over; when none is left,
+/// the proxy answers `503 Service Unavailable`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use rttp::Router;
+/// use rttp::proxy::{ReverseProxy, Strategy, Upstream, UpstreamPool};
+///
+/// let pool = UpstreamPool::new(Strategy::LeastConnections)
+///     .upstream(Upstream::new("http://10.0.0.1:9000").weight(2))
+///     .upstream(Upstream::new("http://10.0.0.2:9000").max_connections(64))
+///     .eject_after(3, Duration::from_secs(30));
+///
+/// let mut router = Router::new();
+/// ReverseProxy::balanced(pool).retries(1).mount(&mut router, "/*");
+/// ```
+#[derive(Debug)]
+pub struct UpstreamPool {
+    upstreams: Vec<Upstream>,
+    strategy: Strategy,
+    // Consecutive failures that eject an upstream; zero never ejects.
+    eject_after: u32,
+    eject_for: Duration,
+    clock: Arc<dyn Clock>,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    nodes: Vec<Node>,
+    // Where round-robin scans start.
+    cursor: usize,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Node {
+    active: usize,
+    failures: u32,
+    ejected_until: Option<Instant>,
+    // Smooth weighted round-robin's running score.
+    current: i64,
+}
+
+impl UpstreamPool {
+    /// Creates an empty pool balanced by `strategy`. Passive health checks are off until
+    /// [`eject_after`](Self::eject_after) is set.
+    pub fn new(strategy: Strategy) -> Self {
+        Self {
+            upstreams: Vec::new(),
+            strategy,
+            eject_after: 0,
+            eject_for: Duration::ZERO,
+            clock: clock::system(),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Adds `upstream` to the pool.
+    #[must_use]
+    pub fn upstream(mut self, upstream: Upstream) -> Self {
+        self.upstreams.push(upstream);
+        self.state().nodes.push(Node::default());
+        self
+    }
+
+    /// Takes an upstream out of rotation for `duration` once `failures` requests in a row
+    /// have failed to reach it. Afterwards it gets requests again; one more failure ejects
+    /// it anew, and a success restores it fully.
+    ///
+    /// Failures are connection errors, timeouts, and malformed responses. Error statuses
+    /// are the upstream's answer and count as successes.
+    #[must_use]
+    pub fn eject_after(mut self, failures: u32, duration: Duration) -> Self {
+        self.eject_after = failures;
+        self.eject_for = duration;
+        self
+    }
+
+    /// Times ejections by `clock` instead of the system clock.
+    #[must_use]
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the upstreams in the order they were added.
+    pub fn upstreams(&self) -> &[Upstream] {
+        &self.upstreams
+    }
+
+    /// Returns the number of upstreams currently in rotation.
+    pub fn healthy(&self) -> usize {
+        let now = self.clock.now();
+        self.state()
+            .nodes
+            .iter()
+            .filter(|node| !node.is_ejected(now))
+            .count()
+    }
+
+    // Picks an upstream for one attempt, preferring ones not in `tried`.
+    pub(super) fn acquire(self: &Arc<Self>, tried: &[usize]) -> Option<Lease> {
+        let now = self.clock.now();
+        let mut state = self.state();
+        let available: Vec<usize> = (0..self.upstreams.len())
+            .filter(|&i| {
+                let node = &state.nodes[i];
+                !node.is_ejected(now)
+                    && self.upstreams[i]
+                        .max_connections
+                        .is_none_or(|limit| node.active < limit)
+            })
+            .collect();
+        let untried: Vec<usize> = available
+            .iter()
+            .copied()
+            .filter(|i| !tried.contains(i))
+            .collect();
+        let candidates = if untried.is_empty() {
+            available
+        } else {
+            untried
+        };
+        let index = self.pick(&mut state, &candidates)?;
+        state.nodes[index].active += 1;
+        Some(Lease {
+            pool: Arc::clone(self),
+            index,
+        })
+    }
+
+    fn pick(&self, state: &mut State, candidates: &[usize]) -> Option<usize> {
+        if candidates.is_empty() {
+            return None;
+        }
+        let count = self.upstreams.len();
+        // Candidates in round-robin order, starting at the cursor.
+        let start = state.cursor % count;
+        let mut ordered = candidates.to_vec();
+        ordered.sort_by_key(|&i| (i + count - start) % count);
+        let chosen = match self.strategy {
+            Strategy::RoundRobin => ordered[0],
+            Strategy::LeastConnections => {
+                // Compares active / weight without dividing.
+                let load = |i: usize| {
+                    (
+                        state.nodes[i].active as u128,
+                        u128::from(self.upstreams[i].weight),
+                    )
+                };
+                ordered
+                    .iter()
+                    .copied()
+                    .reduce(|best, i| {
+                        let ((a, wa), (b, wb)) = (load(i), load(best));
+                        if a * wb < b * wa { i } else { best }
+                    })
+                    .unwrap_or(ordered[0])
+            }
+            Strategy::Weighted => {
+                let total: i64 = candidates
+                    .iter()
+                    .map(|&i| i64::from(self.upstreams[i].weight))
+                    .sum();
+                for &i in candidates {
+                    state.nodes[i].current += i64::from(self.upstreams[i].weight);
+                }
+                let chosen = ordered
+                    .iter()
+                    .copied()
+                    .reduce(|best, i| {
+                        if state.nodes[i].current > state.nodes[best].current {
+                            i
+                        } else {
+                            best
+                        }
+                    })
+                    .unwrap_or(ordered[0]);
+                state.nodes[chosen].current -= total;
+                chosen
+            }
+        };
+        state.cursor = chosen + 1;
+        Some(chosen)
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Node {
+    fn is_ejected(&self, now: Instant) -> bool {
+        self.ejected_until.is_some_and(|until| until > now)
+    }
+}
+
+// An upstream chosen for one request, counted as in flight until dropped.
+pub(super) struct Lease {
+    pool: Arc<UpstreamPool>,
+    index: usize,
+}
+
+impl Lease {
+    pub(super) fn index(&self) -> usize {
+        self.index
+    }
+
+    pub(super) fn upstream(&self) -> &Upstream {
+        &self.pool.upstreams[self.index]
+    }
+
+    pub(super) fn succeeded(&self) {
+        let mut state = self.pool.state();
+        let node = &mut state.nodes[self.index];
+        node.failures = 0;
+        node.ejected_until = None;
+    }
+
+    pub(super) fn failed(&self) {
+        let pool = &self.pool;
+        if pool.eject_after == 0 {
+            return;
+        }
+        let mut state = pool.state();
+        let node = &mut state.nodes[self.index];
+        node.failures = node.failures.saturating_add(1);
+        if node.failures >= pool.eject_after {
+            node.ejected_until = Some(pool.clock.now() + pool.eject_for);
+            let failures = node.failures;
+            drop(state);
+            warn!(
+                upstream = %self.upstream().authority,
+                failures,
+                "ejecting upstream for {:?}",
+                pool.eject_for
+            );
+        }
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        self.pool.state().nodes[self.index].active -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    fn pool(strategy: Strategy, upstreams: &[(u32, Option<usize>)]) -> Arc<UpstreamPool> {
+        let mut pool = UpstreamPool::new(strategy);
+        for (i, &(weight, limit)) in upstreams.iter().enumerate() {
+            let mut upstream = Upstream::new(&format!("http://10.0.0.{i}:80")).weight(weight);
+            if let Some(limit) = limit {
+                upstream = upstream.max_connections(limit);
+            }
+            pool = pool.upstream(upstream);
+        }
+        Arc::new(pool)
+    }
+
+    // Picks `n` times, releasing each lease at once.
+    fn picks(pool: &Arc<UpstreamPool>, n: usize) -> Vec<usize> {
+        (0..n).map(|_| pool.acquire(&[]).unwrap().index()).collect()
+    }
+
+    #[test]
+    fn strategies_spread_requests() {
+        let even = pool(Strategy::RoundRobin, &[(5, None), (1, None), (1, None)]);
+        assert_eq!(picks(&even, 6), [0, 1, 2, 0, 1, 2]);
+
+        let weighted = pool(Strategy::Weighted, &[(5, None), (1, None), (1, None)]);
+        assert_eq!(picks(&weighted, 7), [0, 0, 1, 0, 2, 0, 0]);
+
+        let least = pool(Strategy::LeastConnections, &[(1, None), (1, None)]);
+        let busy = least.acquire(&[]).unwrap();
+        assert_eq!(busy.index(), 0);
+        assert_eq!(picks(&least, 3), [1, 1, 1]);
+        drop(busy);
+        assert_eq!(picks(&least, 2), [0, 1]);
+    }
+
+    #[test]
+    fn connection_limits_and_retries_skip_upstreams() {
+        let pool = pool(Strategy::RoundRobin, &[(1, Some(1)), (1, Some(1))]);
+        let first = pool.acquire(&[]).unwrap();
+        let second = pool.acquire(&[]).unwrap();
+        assert_eq!((first.index(), second.index()), (0, 1));
+        assert!(pool.acquire(&[]).is_none());
+        drop(second);
+        // A retry avoids the upstream that failed, if another is free.
+        assert_eq!(pool.acquire(&[1]).unwrap().index(), 1);
+        drop(first);
+        assert_eq!(pool.acquire(&[1]).unwrap().index(), 0);
+    }
+
+    #[test]
+    fn failing_upstreams_are_ejected_for_a_while() {
+        let clock = MockClock::new();
+        let pool = UpstreamPool::new(Strategy::RoundRobin)
+            .upstream(Upstream::new("http://a"))
+            .upstream(Upstream::new("http://b"))
+            .eject_after(2, Duration::from_secs(30))
+            .clock(Arc::new(clock.clone()));
+        let pool = Arc::new(pool);
+
+        for _ in 0..2 {
+            pool.acquire(&[1]).unwrap().failed();
+        }
+        assert_eq!(pool.healthy(), 1);
+        assert_eq!(picks(&pool, 3), [1, 1, 1]);
+
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(pool.healthy(), 2);
+        // Back on probation: one more failure ejects it again.
+        pool.acquire(&[1]).unwrap().failed();
+        assert_eq!(pool.healthy(), 1);
+        clock.advance(Duration::from_secs(30));
+        pool.acquire(&[1]).unwrap().succeeded();
+        pool.acquire(&[1]).unwrap().failed();
+        assert_eq!(pool.healthy(), 2);
+    }
+}
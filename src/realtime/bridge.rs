@@ -0,0 +1,273 @@
+//! Replicating [`Broadcast`] topics across instances.
+//!
+//! A [`Broadcast`] hub only reaches connections held by the same process. Behind a load
+//! balancer, the WebSocket or SSE client that should see a message is often connected to
+//! another instance. A [`BroadcastBridge`] publishes through a shared [`Transport`] —
+//! Redis pub/sub via [`RedisTransport`], or anything else implementing the trait — and
+//! republishes what other instances send into the local hub.
+//!
+//! Messages travel as JSON envelopes tagged with the sending instance, so each instance
+//! delivers its own messages locally straight away and ignores their echo.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use rttp::realtime::{Broadcast, LagPolicy};
+//! use rttp::realtime::bridge::{BroadcastBridge, RedisTransport};
+//! use rttp::redis::RedisClient;
+//!
+//! # async fn example() -> Result<(), rttp::realtime::bridge::BridgeError> {
+//! let hub: Broadcast<String> = Broadcast::new(64, LagPolicy::DropOldest);
+//! let transport = RedisTransport::new(RedisClient::new("127.0.0.1:6379"), "realtime");
+//! let bridge = BroadcastBridge::start(hub.clone(), Arc::new(transport)).await?;
+//!
+//! // Reaches subscribers to "orders" on every instance.
+//! bridge.publish("orders", "order 42 paid".to_owned()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use super::hub::Broadcast;
+use crate::redis::{RedisClient, RedisError, Subscription};
+
+/// Errors produced by a [`BroadcastBridge`] or its [`Transport`].
+#[derive(Debug, Error)]
+pub enum BridgeError {
+    #[error("bridge redis error: {0}")]
+    Redis(#[from] RedisError),
+
+    #[error("bridge transport error: {0}")]
+    Transport(String),
+
+    #[error("failed to encode broadcast message: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Boxed future returned by [`Transport`] and [`TransportSubscription`] methods.
+pub type TransportFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, BridgeError>> + Send + 'a>>;
+
+/// A channel every instance publishes to and hears from.
+///
+/// Implement it to bridge over a message bus other than Redis. Delivery is best-effort:
+/// payloads published while an instance is resubscribing may never reach it.
+pub trait Transport: Send + Sync {
+    /// Publishes `payload` to every subscribed instance, this one included.
+    fn publish<'a>(&'a self, payload: &'a [u8]) -> TransportFuture<'a, ()>;
+
+    /// Subscribes to the channel. Payloads published after this resolves must be
+    /// delivered to the returned subscription.
+    fn subscribe(&self) -> TransportFuture<'_, Box<dyn TransportSubscription>>;
+}
+
+/// The receiving end of a [`Transport`].
+pub trait TransportSubscription: Send {
+    /// Waits for the next payload. After an error the bridge subscribes again.
+    fn next(&mut self) -> TransportFuture<'_, Vec<u8>>;
+}
+
+/// A [`Transport`] over one Redis pub/sub channel.
+pub struct RedisTransport {
+    client: RedisClient,
+    channel: String,
+}
+
+impl RedisTransport {
+    /// Publishes with `client` on `channel`; subscriptions open their own connections to
+    /// the same server.
+    pub fn new(client: RedisClient, channel: impl Into<String>) -> Self {
+        Self {
+            client,
+            channel: channel.into(),
+        }
+    }
+}
+
+impl Transport for RedisTransport {
+    fn publish<'a>(&'a self, payload: &'a [u8]) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            self.client.publish(&self.channel, payload).await?;
+            Ok(())
+        })
+    }
+
+    fn subscribe(&self) -> TransportFuture<'_, Box<dyn TransportSubscription>> {
+        Box::pin(async move {
+            let subscription = self.client.subscribe(&[&self.channel]).await?;
+            Ok(Box::new(subscription) as Box<dyn TransportSubscription>)
+        })
+    }
+}
+
+impl TransportSubscription for Subscription {
+    fn next(&mut self) -> TransportFuture<'_, Vec<u8>> {
+        Box::pin(async move { Ok(self.next_message().await?.payload) })
+    }
+}
+
+// Borrowed when sending, owned when receiving.
+#[derive(Serialize, Deserialize)]
+struct Envelope<S, T> {
+    origin: S,
+    topic: S,
+    message: T,
+}
+
+/// Keeps a [`Broadcast`] hub in step with the hubs of other instances.
+///
+/// Publish through the bridge rather than the hub for messages other instances should
+/// see; subscribe through the hub as usual. The listener stops when the bridge is
+/// dropped.
+pub struct BroadcastBridge<T> {
+    hub: Broadcast<T>,
+    transport: Arc<dyn Transport>,
+    // Identifies this instance so it ignores its own messages.
+    origin: String,
+    listener: JoinHandle<()>,
+}
+
+impl<T> BroadcastBridge<T>
+where
+    T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    /// Subscribes to `transport` and starts republishing other instances' messages into
+    /// `hub`.
+    ///
+    /// Resolves once the subscription is established. If it is later lost, the listener
+    /// logs a warning and resubscribes; messages sent in between are missed.
+    ///
+    /// # Errors
+    ///
+    /// Returns the transport's error if subscribing fails.
+    pub async fn start(
+        hub: Broadcast<T>,
+        transport: Arc<dyn Transport>,
+    ) -> Result<Self, BridgeError> {
+        let origin = crate::security::crypto::random_token(12);
+        let subscription = transport.subscribe().await?;
+        let listener = tokio::spawn(listen(
+            hub.clone(),
+            Arc::clone(&transport),
+            origin.clone(),
+            subscription,
+        ));
+        Ok(Self {
+            hub,
+            transport,
+            origin,
+            listener,
+        })
+    }
+
+    /// Returns the local hub.
+    pub fn hub(&self) -> &Broadcast<T> {
+        &self.hub
+    }
+
+    /// Publishes `message` to `topic` on this instance and every other one, returning
+    /// how many local subscribers received it.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error if the message cannot be encoded or the transport fails to
+    /// publish it. Local subscribers have received it either way.
+    pub async fn publish(&self, topic: &str, message: T) -> Result<usize, BridgeError> {
+        let payload = serde_json::to_vec(&Envelope {
+            origin: self.origin.as_str(),
+            topic,
+            message: &message,
+        })?;
+        let delivered = self.hub.publish(topic, message);
+        self.transport.publish(&payload).await?;
+        Ok(delivered)
+    }
+}
+
+impl<T> Drop for BroadcastBridge<T> {
+    fn drop(&mut self) {
+        self.listener.abort();
+    }
+}
+
+async fn listen<T>(
+    hub: Broadcast<T>,
+    transport: Arc<dyn Transport>,
+    origin: String,
+    mut subscription: Box<dyn TransportSubscription>,
+) where
+    T: Clone + DeserializeOwned,
+{
+    loop {
+        match subscription.next().await {
+            Ok(payload) => match serde_json::from_slice::<Envelope<String, T>>(&payload) {
+                Ok(envelope) if envelope.origin == origin => {}
+                Ok(envelope) => {
+                    hub.publish(&envelope.topic, envelope.message);
+                }
+                Err(e) => warn!(error = %e, "ignoring malformed broadcast envelope"),
+            },
+            Err(e) => {
+                warn!(error = %e, "broadcast bridge subscription lost");
+                subscription = loop {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    match transport.subscribe().await {
+                        Ok(subscription) => break subscription,
+                        Err(e) => warn!(error = %e, "broadcast bridge resubscribe failed"),
+                    }
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::realtime::{LagPolicy, Received};
+    use crate::redis::testing::FakeRedis;
+
+    async fn instance(server: &FakeRedis) -> BroadcastBridge<String> {
+        let transport = RedisTransport::new(RedisClient::new(server.addr()), "realtime");
+        let hub = Broadcast::new(8, LagPolicy::DropOldest);
+        BroadcastBridge::start(hub, Arc::new(transport))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn replicates_topics_across_instances() {
+        let server = FakeRedis::start().await;
+        let (a, b) = (instance(&server).await, instance(&server).await);
+        let mut local = a.hub().subscribe("orders");
+        let mut remote = b.hub().subscribe("orders");
+        let mut other_topic = b.hub().subscribe("users");
+
+        assert_eq!(a.publish("orders", "paid".to_owned()).await.unwrap(), 1);
+        let received = tokio::time::timeout(Duration::from_secs(1), remote.recv()).await;
+        assert_eq!(received.unwrap(), Ok(Received::Message("paid".to_owned())));
+        assert_eq!(other_topic.try_recv(), Ok(None));
+
+        // The publisher's own subscribers got it once, not again via Redis.
+        b.publish("orders", "shipped".to_owned()).await.unwrap();
+        let mut seen = Vec::new();
+        while seen.len() < 2 {
+            let next = tokio::time::timeout(Duration::from_secs(1), local.recv()).await;
+            seen.push(next.unwrap().unwrap());
+        }
+        assert_eq!(
+            seen,
+            [
+                Received::Message("paid".to_owned()),
+                Received::Message("shipped".to_owned())
+            ]
+        );
+        assert_eq!(local.try_recv(), Ok(None));
+    }
+}
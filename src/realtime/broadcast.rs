@@ -0,0 +1,424 @@
+//! Backpressure-aware event bus for fanning messages out to realtime connections.
+//!
+//! Every [`Subscriber`] owns a bounded buffer. [`Broadcaster::send`] never waits: when a
+//! subscriber's buffer is full the configured [`LagPolicy`] decides what happens to that
+//! subscriber alone, so one stalled client can neither grow memory without bound nor slow
+//! the broadcaster down for everybody else.
+//!
+//! | Policy                        | Full buffer behavior                                   |
+//! |-------------------------------|--------------------------------------------------------|
+//! | [`LagPolicy::DropOldest`]     | Oldest buffered event is discarded silently            |
+//! | [`LagPolicy::NotifyLagged`]   | Oldest event is discarded; receiver gets [`Received::Lagged`] |
+//! | [`LagPolicy::Disconnect`]     | Subscriber is removed; receiver gets [`RecvError::Lagged`] |
+//!
+//! # Examples
+//!
+//! ```
+//! use rttp::realtime::broadcast::{Broadcaster, LagPolicy, Received};
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let bus = Broadcaster::new(2, LagPolicy::NotifyLagged);
+//! let mut sub = bus.subscribe();
+//!
+//! for n in 1..=3 {
+//!     bus.send(n);
+//! }
+//!
+//! assert_eq!(sub.recv().await, Ok(Received::Lagged(1)));
+//! assert_eq!(sub.recv().await, Ok(Received::Message(2)));
+//! assert_eq!(sub.recv().await, Ok(Received::Message(3)));
+//! # }
+//! ```
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
+
+use thiserror::Error;
+use tokio::sync::Notify;
+
+/// What to do with a subscriber whose buffer is full when a new event arrives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LagPolicy {
+    /// Discard the oldest buffered event to make room. The receiver is not told.
+    #[default]
+    DropOldest,
+    /// Discard the oldest buffered event and report the number of skipped events to the
+    /// receiver as [`Received::Lagged`] before its next message.
+    NotifyLagged,
+    /// Drop the subscriber. Its receiver drains nothing further and gets
+    /// [`RecvError::Lagged`].
+    Disconnect,
+}
+
+/// An item yielded by [`Subscriber::recv`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Received<T> {
+    /// The next event, in send order.
+    Message(T),
+    /// The subscriber fell behind and this many events were skipped.
+    Lagged(u64),
+}
+
+/// Reasons a [`Subscriber`] stops receiving.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum RecvError {
+    #[error("broadcaster closed")]
+    Closed,
+
+    #[error("subscriber disconnected for falling behind")]
+    Lagged,
+}
+
+// Per-subscriber buffer and bookkeeping, guarded by the slot mutex.
+struct SlotState<T> {
+    queue: VecDeque<T>,
+    missed: u64,
+    closed: Option<RecvError>,
+}
+
+// One subscriber's bounded buffer, shared between the broadcaster and the receiver.
+struct Slot<T> {
+    state: Mutex<SlotState<T>>,
+    notify: Notify,
+    // Set when the `Subscriber` is dropped, so the next send prunes the slot.
+    abandoned: AtomicBool,
+}
+
+impl<T> Slot<T> {
+    fn lock(&self) -> MutexGuard<'_, SlotState<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn close(&self, reason: RecvError) {
+        let mut state = self.lock();
+        if state.closed.is_none() {
+            state.closed = Some(reason);
+        }
+        drop(state);
+        self.notify.notify_one();
+    }
+}
+
+struct Shared<T> {
+    slots: Mutex<Vec<Arc<Slot<T>>>>,
+    // Live `Broadcaster` clones; the one that takes this to zero closes the subscribers.
+    senders: AtomicUsize,
+    capacity: usize,
+    policy: LagPolicy,
+}
+
+impl<T> Shared<T> {
+    fn slots(&self) -> MutexGuard<'_, Vec<Arc<Slot<T>>>> {
+        self.slots.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The sending half of the event bus. Cheap to clone; all clones share subscribers.
+///
+/// When the last `Broadcaster` clone is dropped every subscriber is closed with
+/// [`RecvError::Closed`] after draining its buffer.
+pub struct Broadcaster<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Clone for Broadcaster<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T: Clone> Broadcaster<T> {
+    /// Creates a broadcaster whose subscribers buffer at most `capacity` events each.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize, policy: LagPolicy) -> Self {
+        assert!(capacity > 0, "broadcast capacity must be greater than zero");
+        Self {
+            shared: Arc::new(Shared {
+                slots: Mutex::new(Vec::new()),
+                senders: AtomicUsize::new(1),
+                capacity,
+                policy,
+            }),
+        }
+    }
+
+    /// Registers a new subscriber that receives every event sent from now on.
+    pub fn subscribe(&self) -> Subscriber<T> {
+        let slot = Arc::new(Slot {
+            state: Mutex::new(SlotState {
+                queue: VecDeque::with_capacity(self.shared.capacity),
+                missed: 0,
+                closed: None,
+            }),
+            notify: Notify::new(),
+            abandoned: AtomicBool::new(false),
+        });
+        self.shared.slots().push(Arc::clone(&slot));
+        Subscriber { slot }
+    }
+
+    /// Delivers `event` to every live subscriber without waiting.
+    ///
+    /// Subscribers with a full buffer are handled according to the [`LagPolicy`].
+    /// Subscribers that were dropped or disconnected are pruned.
+    ///
+    /// Returns the number of subscribers that received the event.
+    pub fn send(&self, event: T) -> usize {
+        let mut slots = self.shared.slots();
+        let mut delivered = 0;
+
+        slots.retain(|slot| {
+            if slot.abandoned.load(Ordering::Acquire) {
+                return false;
+            }
+
+            let mut state = slot.lock();
+            if state.closed.is_some() {
+                return false;
+            }
+
+            if state.queue.len() >= self.shared.capacity {
+                match self.shared.policy {
+                    LagPolicy::DropOldest => {
+                        state.queue.pop_front();
+                    }
+                    LagPolicy::NotifyLagged => {
+                        state.queue.pop_front();
+                        state.missed += 1;
+                    }
+                    LagPolicy::Disconnect => {
+                        state.queue.clear();
+                        state.closed = Some(RecvError::Lagged);
+                        drop(state);
+                        slot.notify.notify_one();
+                        return false;
+                    }
+                }
+            }
+
+            state.queue.push_back(event.clone());
+            drop(state);
+            slot.notify.notify_one();
+            delivered += 1;
+            true
+        });
+
+        delivered
+    }
+
+    /// Returns the number of subscribers currently registered.
+    ///
+    /// Dropped subscribers are only pruned on the next [`send`](Self::send), so this may
+    /// briefly over-count.
+    pub fn subscriber_count(&self) -> usize {
+        self.shared.slots().len()
+    }
+}
+
+impl<T> Drop for Broadcaster<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            for slot in self.shared.slots().drain(..) {
+                slot.close(RecvError::Closed);
+            }
+        }
+    }
+}
+
+/// The receiving half of the event bus, created by [`Broadcaster::subscribe`].
+pub struct Subscriber<T> {
+    slot: Arc<Slot<T>>,
+}
+
+impl<T> Drop for Subscriber<T> {
+    fn drop(&mut self) {
+        self.slot.abandoned.store(true, Ordering::Release);
+    }
+}
+
+impl<T> Subscriber<T> {
+    /// Waits for the next event.
+    ///
+    /// A pending [`Received::Lagged`] notice is always delivered before the messages that
+    /// follow the gap.
+    ///
+    /// # Errors
+    ///
+    /// - [`RecvError::Closed`] — the broadcaster was dropped and the buffer is drained.
+    /// - [`RecvError::Lagged`] — this subscriber was disconnected by
+    ///   [`LagPolicy::Disconnect`].
+    pub async fn recv(&mut self) -> Result<Received<T>, RecvError> {
+        loop {
+            if let Some(item) = self.try_recv()? {
+                return Ok(item);
+            }
+            self.slot.notify.notified().await;
+        }
+    }
+
+    /// Returns the next event if one is buffered, without waiting.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`recv`](Self::recv).
+    pub fn try_recv(&mut self) -> Result<Option<Received<T>>, RecvError> {
+        let mut state = self.slot.lock();
+        if state.missed > 0 {
+            let missed = std::mem::take(&mut state.missed);
+            return Ok(Some(Received::Lagged(missed)));
+        }
+        if let Some(event) = state.queue.pop_front() {
+            return Ok(Some(Received::Message(event)));
+        }
+        match state.closed {
+            Some(reason) => Err(reason),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the number of events currently buffered for this subscriber.
+    pub fn len(&self) -> usize {
+        self.slot.lock().queue.len()
+    }
+
+    /// Returns `true` if no events are buffered for this subscriber.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn fans_out_to_every_subscriber() {
+        let bus = Broadcaster::new(8, LagPolicy::DropOldest);
+        let mut a = bus.subscribe();
+        let mut b = bus.subscribe();
+
+        assert_eq!(bus.send("hi"), 2);
+        assert_eq!(a.recv().await, Ok(Received::Message("hi")));
+        assert_eq!(b.recv().await, Ok(Received::Message("hi")));
+    }
+
+    #[tokio::test]
+    async fn recv_waits_for_send() {
+        let bus = Broadcaster::new(1, LagPolicy::DropOldest);
+        let mut sub = bus.subscribe();
+
+        let sender = bus.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            sender.send(5);
+        });
+
+        assert_eq!(sub.recv().await, Ok(Received::Message(5)));
+    }
+
+    #[test]
+    fn drop_oldest_keeps_newest_events() {
+        let bus = Broadcaster::new(2, LagPolicy::DropOldest);
+        let mut sub = bus.subscribe();
+        for n in 1..=4 {
+            bus.send(n);
+        }
+        assert_eq!(sub.len(), 2);
+        assert_eq!(sub.try_recv(), Ok(Some(Received::Message(3))));
+        assert_eq!(sub.try_recv(), Ok(Some(Received::Message(4))));
+        assert_eq!(sub.try_recv(), Ok(None));
+    }
+
+    #[test]
+    fn notify_lagged_reports_gap_once() {
+        let bus = Broadcaster::new(1, LagPolicy::NotifyLagged);
+        let mut sub = bus.subscribe();
+        for n in 1..=3 {
+            bus.send(n);
+        }
+        assert_eq!(sub.try_recv(), Ok(Some(Received::Lagged(2))));
+        assert_eq!(sub.try_recv(), Ok(Some(Received::Message(3))));
+        assert_eq!(sub.try_recv(), Ok(None));
+    }
+
+    #[test]
+    fn disconnect_only_affects_slow_subscriber() {
+        let bus = Broadcaster::new(1, LagPolicy::Disconnect);
+        let mut slow = bus.subscribe();
+        let mut fast = bus.subscribe();
+
+        bus.send(1);
+        assert_eq!(fast.try_recv(), Ok(Some(Received::Message(1))));
+        assert_eq!(bus.send(2), 1);
+
+        assert_eq!(slow.try_recv(), Err(RecvError::Lagged));
+        assert_eq!(fast.try_recv(), Ok(Some(Received::Message(2))));
+        assert_eq!(bus.subscriber_count(), 1);
+    }
+
+    #[test]
+    fn dropped_subscribers_are_pruned() {
+        let bus = Broadcaster::new(1, LagPolicy::DropOldest);
+        let sub = bus.subscribe();
+        assert_eq!(bus.subscriber_count(), 1);
+        drop(sub);
+        assert_eq!(bus.send(1), 0);
+        assert_eq!(bus.subscriber_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn dropping_broadcaster_closes_after_drain() {
+        let bus = Broadcaster::new(4, LagPolicy::DropOldest);
+        let mut sub = bus.subscribe();
+        let clone = bus.clone();
+        bus.send(1);
+        drop(bus);
+        // A clone is still alive, so the subscriber stays open.
+        assert_eq!(sub.try_recv(), Ok(Some(Received::Message(1))));
+        assert_eq!(sub.try_recv(), Ok(None));
+
+        clone.send(2);
+        drop(clone);
+        assert_eq!(sub.recv().await, Ok(Received::Message(2)));
+        assert_eq!(sub.recv().await, Err(RecvError::Closed));
+    }
+
+    #[test]
+    fn concurrently_dropped_clones_still_close() {
+        for _ in 0..100 {
+            let bus = Broadcaster::<u8>::new(1, LagPolicy::DropOldest);
+            let mut sub = bus.subscribe();
+            let threads: Vec<_> = (0..4)
+                .map(|_| {
+                    let clone = bus.clone();
+                    std::thread::spawn(move || drop(clone))
+                })
+                .collect();
+            drop(bus);
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(sub.try_recv(), Err(RecvError::Closed));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "greater than zero")]
+    fn zero_capacity_panics() {
+        let _ = Broadcaster::<u8>::new(0, LagPolicy::DropOldest);
+    }
+}
@@ -0,0 +1,239 @@
+//! Per-connection identity and storage for long-lived realtime connections.
+//!
+//! A WebSocket or SSE connection outlives the request that opened it, so request-scoped
+//! [`Context`] extensions are not enough to hold things like the authenticated user, a
+//! subscription list, or a message rate counter. [`ConnectionContext`] gives every
+//! connection:
+//!
+//! - a process-unique [`ConnectionId`],
+//! - its own typed [`Extensions`] map (connection-scoped state), and
+//! - a handle to the application's [`SharedState`], which is the same for every connection.
+
+use std::{
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::context::{Context, Extensions};
+
+/// Application-wide state shared by every realtime connection.
+///
+/// Values are read-only once the state is built; wrap them in a `Mutex`, `RwLock`, or an
+/// atomic when connections need to mutate them.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::AtomicUsize;
+/// use rttp::realtime::SharedState;
+///
+/// let shared = SharedState::new().with(AtomicUsize::new(0));
+/// assert!(shared.get::<AtomicUsize>().is_some());
+/// ```
+#[derive(Clone, Default)]
+pub struct SharedState {
+    inner: Arc<Extensions>,
+}
+
+impl SharedState {
+    /// Creates an empty shared state.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a value while the state is being built.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the state has already been cloned, i.e. handed out to a connection.
+    #[must_use]
+    pub fn with<T>(mut self, value: T) -> Self
+    where
+        T: Send + Sync + 'static,
+    {
+        Arc::get_mut(&mut self.inner)
+            .expect("SharedState::with called after the state was shared")
+            .insert(value);
+        self
+    }
+
+    /// Returns a shared reference to a value of type `T`.
+    pub fn get<T>(&self) -> Option<&T>
+    where
+        T: Send + Sync + 'static,
+    {
+        self.inner.get::<T>()
+    }
+}
+
+/// A process-unique identifier for a realtime connection.
+///
+/// Identifiers are allocated from a monotonically increasing counter and are never reused
+/// while the process runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ConnectionId(u64);
+
+impl ConnectionId {
+    /// Allocates the next unused identifier.
+    pub fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Returns the raw numeric identifier.
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for ConnectionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "conn-{}", self.0)
+    }
+}
+
+/// Identity, connection-scoped state, and shared state for one realtime connection.
+///
+/// Handlers own the `ConnectionContext` for the lifetime of the connection and can stash
+/// typed values in it exactly like request [`Extensions`].
+///
+/// # Examples
+///
+/// ```
+/// use rttp::realtime::{ConnectionContext, SharedState};
+///
+/// struct UserId(u64);
+/// struct MessagesSent(u32);
+///
+/// let mut conn = ConnectionContext::new(SharedState::new());
+/// conn.state_mut().insert(UserId(7));
+/// conn.state_mut().insert(MessagesSent(0));
+///
+/// conn.state_mut().get_mut::<MessagesSent>().unwrap().0 += 1;
+/// assert_eq!(conn.state().get::<MessagesSent>().unwrap().0, 1);
+/// ```
+pub struct ConnectionContext {
+    id: ConnectionId,
+    state: Extensions,
+    shared: SharedState,
+}
+
+impl ConnectionContext {
+    /// Creates a context with a fresh [`ConnectionId`] and empty connection state.
+    pub fn new(shared: SharedState) -> Self {
+        Self {
+            id: ConnectionId::next(),
+            state: Extensions::new(),
+            shared,
+        }
+    }
+
+    /// Creates a context for a connection opened by the request in `ctx`.
+    ///
+    /// The request's extensions become the initial connection state, so values set by
+    /// upstream middleware — such as the authenticated
+    /// [`Principal`](crate::security::Principal) — stay available for the whole connection.
+    pub fn from_request(ctx: Context, shared: SharedState) -> Self {
+        Self {
+            id: ConnectionId::next(),
+            state: ctx.into_extensions(),
+            shared,
+        }
+    }
+
+    /// Returns this connection's identifier.
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    /// Returns the connection-scoped state.
+    pub fn state(&self) -> &Extensions {
+        &self.state
+    }
+
+    /// Returns the connection-scoped state mutably.
+    pub fn state_mut(&mut self) -> &mut Extensions {
+        &mut self.state
+    }
+
+    /// Returns the application-wide shared state.
+    pub fn shared(&self) -> &SharedState {
+        &self.shared
+    }
+}
+
+impl fmt::Debug for ConnectionContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionContext")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::{http::request::Request, security::Principal};
+
+    #[test]
+    fn connection_ids_are_unique() {
+        let a = ConnectionId::next();
+        let b = ConnectionId::next();
+        assert_ne!(a, b);
+        assert!(b > a);
+        assert_eq!(format!("{a}"), format!("conn-{}", a.as_u64()));
+    }
+
+    #[test]
+    fn state_is_per_connection() {
+        let shared = SharedState::new();
+        let mut a = ConnectionContext::new(shared.clone());
+        let b = ConnectionContext::new(shared);
+        a.state_mut().insert(1u32);
+        assert_eq!(a.state().get::<u32>(), Some(&1));
+        assert_eq!(b.state().get::<u32>(), None);
+        assert_ne!(a.id(), b.id());
+    }
+
+    #[test]
+    fn shared_state_is_visible_to_all_connections() {
+        let shared = SharedState::new().with(AtomicUsize::new(0));
+        let a = ConnectionContext::new(shared.clone());
+        let b = ConnectionContext::new(shared);
+        a.shared()
+            .get::<AtomicUsize>()
+            .unwrap()
+            .fetch_add(1, Ordering::SeqCst);
+        assert_eq!(
+            b.shared()
+                .get::<AtomicUsize>()
+                .unwrap()
+                .load(Ordering::SeqCst),
+            1
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "after the state was shared")]
+    fn shared_state_with_after_clone_panics() {
+        let shared = SharedState::new();
+        let _clone = shared.clone();
+        let _ = shared.with(1u32);
+    }
+
+    #[test]
+    fn from_request_carries_extensions() {
+        let (req, _) = Request::parse(b"GET /ws HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut ctx = Context::new(req);
+        ctx.extensions_mut().insert(Principal::new("ada"));
+
+        let conn = ConnectionContext::from_request(ctx, SharedState::new());
+        assert_eq!(conn.state().get::<Principal>().unwrap().id(), "ada");
+    }
+}
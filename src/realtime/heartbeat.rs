@@ -0,0 +1,91 @@
+//! Liveness checks and disconnect notification for long-lived connections.
+//!
+//! A client that vanishes without closing its connection — a laptop lid shut, a mobile
+//! network dropped — leaves the server holding a socket nobody will ever read. A
+//! [`Heartbeat`] on a [`WebSocket`](super::WebSocket) pings the peer when the connection
+//! has been quiet and closes it if nothing comes back in time. An [`Sse`](super::Sse)
+//! stream has no way to hear from the client, so it sends comment keep-alives instead,
+//! which both stop proxies timing the stream out and surface a dead client as a failed
+//! write.
+//!
+//! Either connection can register a hook with `on_disconnect` that runs exactly once when
+//! it ends, with the [`DisconnectReason`].
+
+use std::{fmt, time::Duration};
+
+/// Ping interval and timeout for a [`WebSocket`](super::WebSocket).
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use rttp::realtime::{Heartbeat, WebSocketConfig};
+///
+/// // Ping after 30 seconds of silence; give up 10 seconds later.
+/// let config = WebSocketConfig::new()
+///     .heartbeat(Heartbeat::new(Duration::from_secs(30), Duration::from_secs(10)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Heartbeat {
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl Heartbeat {
+    /// Pings the peer after `interval` without receiving anything from it, and treats
+    /// the connection as dead if nothing arrives within `timeout` of the ping.
+    pub fn new(interval: Duration, timeout: Duration) -> Self {
+        Self { interval, timeout }
+    }
+
+    /// Returns how long the connection may be quiet before a ping is sent.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Returns how long to wait for any reply to a ping.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+/// Why a realtime connection ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The connection was closed deliberately: the closing handshake ran, or the handler
+    /// finished with it.
+    Closed,
+    /// The peer went away without closing: the connection hit end-of-file or a write to
+    /// it failed.
+    Gone,
+    /// The peer stopped answering heartbeats.
+    TimedOut,
+    /// The peer broke the protocol and the connection was closed with an error.
+    Failed,
+}
+
+type Hook = Box<dyn FnOnce(DisconnectReason) + Send>;
+
+// A disconnect callback that runs at most once.
+#[derive(Default)]
+pub(super) struct DisconnectHook(Option<Hook>);
+
+impl DisconnectHook {
+    pub(super) fn set(&mut self, hook: impl FnOnce(DisconnectReason) + Send + 'static) {
+        self.0 = Some(Box::new(hook));
+    }
+
+    pub(super) fn fire(&mut self, reason: DisconnectReason) {
+        if let Some(hook) = self.0.take() {
+            hook(reason);
+        }
+    }
+}
+
+impl fmt::Debug for DisconnectHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DisconnectHook")
+            .field(&self.0.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,173 @@
+//! Topic-based pub/sub on top of [`Broadcaster`].
+//!
+//! A [`Broadcast`] hub keeps one [`Broadcaster`] per topic. Handlers publish to a topic
+//! by name; WebSocket and SSE connections subscribe to the topics they care about and
+//! forward what they receive. Each subscriber gets its own bounded buffer and the hub's
+//! [`LagPolicy`], exactly as with a single broadcaster.
+//!
+//! # Examples
+//!
+//! ```
+//! use rttp::realtime::{Broadcast, LagPolicy, Received};
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let hub = Broadcast::new(16, LagPolicy::DropOldest);
+//! let mut orders = hub.subscribe("orders");
+//!
+//! hub.publish("orders", "order 42 paid".to_owned());
+//! hub.publish("users", "nobody is listening".to_owned());
+//!
+//! assert_eq!(
+//!     orders.recv().await,
+//!     Ok(Received::Message("order 42 paid".to_owned()))
+//! );
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use super::broadcast::{Broadcaster, LagPolicy, Subscriber};
+
+struct Topics<T> {
+    topics: Mutex<HashMap<String, Broadcaster<T>>>,
+    capacity: usize,
+    policy: LagPolicy,
+}
+
+/// A pub/sub hub of named topics. Cheap to clone; all clones share topics.
+///
+/// Topics are created on first subscription and removed once their last subscriber is
+/// gone. When the last hub clone is dropped every subscriber is closed with
+/// [`RecvError::Closed`](super::RecvError::Closed) after draining its buffer.
+pub struct Broadcast<T> {
+    inner: Arc<Topics<T>>,
+}
+
+impl<T> Clone for Broadcast<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T: Clone> Broadcast<T> {
+    /// Creates a hub whose subscribers buffer at most `capacity` messages each and fall
+    /// behind according to `policy`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize, policy: LagPolicy) -> Self {
+        assert!(capacity > 0, "broadcast capacity must be greater than zero");
+        Self {
+            inner: Arc::new(Topics {
+                topics: Mutex::new(HashMap::new()),
+                capacity,
+                policy,
+            }),
+        }
+    }
+
+    /// Subscribes to `topic`, receiving every message published to it from now on.
+    pub fn subscribe(&self, topic: &str) -> Subscriber<T> {
+        self.lock()
+            .entry(topic.to_owned())
+            .or_insert_with(|| Broadcaster::new(self.inner.capacity, self.inner.policy))
+            .subscribe()
+    }
+
+    /// Publishes `message` to every subscriber of `topic` without waiting.
+    ///
+    /// Returns the number of subscribers that received it; zero if the topic has none.
+    pub fn publish(&self, topic: &str, message: T) -> usize {
+        let mut topics = self.lock();
+        let Some(broadcaster) = topics.get(topic) else {
+            return 0;
+        };
+        let delivered = broadcaster.send(message);
+        // `send` pruned dropped and disconnected subscribers.
+        if broadcaster.subscriber_count() == 0 {
+            topics.remove(topic);
+        }
+        delivered
+    }
+
+    /// Returns the topics that currently have subscribers, in no particular order.
+    ///
+    /// Topics whose subscribers have all gone are only removed on the next
+    /// [`publish`](Self::publish) to them, so this may briefly include them.
+    pub fn topics(&self) -> Vec<String> {
+        self.lock().keys().cloned().collect()
+    }
+
+    /// Returns the number of subscribers to `topic`, with the same caveat as
+    /// [`Broadcaster::subscriber_count`].
+    pub fn subscriber_count(&self, topic: &str) -> usize {
+        self.lock()
+            .get(topic)
+            .map_or(0, Broadcaster::subscriber_count)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Broadcaster<T>>> {
+        self.inner.topics.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::realtime::{Received, RecvError};
+
+    #[test]
+    fn topics_are_isolated() {
+        let hub = Broadcast::new(4, LagPolicy::DropOldest);
+        let mut a = hub.subscribe("a");
+        let mut also_a = hub.clone().subscribe("a");
+        let mut b = hub.subscribe("b");
+
+        assert_eq!(hub.publish("a", 1), 2);
+        assert_eq!(hub.publish("missing", 2), 0);
+        assert_eq!(a.try_recv(), Ok(Some(Received::Message(1))));
+        assert_eq!(also_a.try_recv(), Ok(Some(Received::Message(1))));
+        assert_eq!(b.try_recv(), Ok(None));
+        assert_eq!(hub.subscriber_count("a"), 2);
+    }
+
+    #[test]
+    fn applies_the_lag_policy_per_subscriber() {
+        let hub = Broadcast::new(1, LagPolicy::Disconnect);
+        let mut slow = hub.subscribe("feed");
+        let mut fast = hub.subscribe("feed");
+
+        hub.publish("feed", 1);
+        assert_eq!(fast.try_recv(), Ok(Some(Received::Message(1))));
+        assert_eq!(hub.publish("feed", 2), 1);
+        assert_eq!(slow.try_recv(), Err(RecvError::Lagged));
+        assert_eq!(fast.try_recv(), Ok(Some(Received::Message(2))));
+    }
+
+    #[test]
+    fn empty_topics_are_removed() {
+        let hub = Broadcast::new(1, LagPolicy::DropOldest);
+        let sub = hub.subscribe("gone");
+        assert_eq!(hub.topics(), ["gone"]);
+        drop(sub);
+        assert_eq!(hub.publish("gone", 1), 0);
+        assert!(hub.topics().is_empty());
+    }
+
+    #[tokio::test]
+    async fn dropping_the_hub_closes_subscribers() {
+        let hub = Broadcast::new(1, LagPolicy::DropOldest);
+        let mut sub = hub.subscribe("t");
+        hub.publish("t", 1);
+        drop(hub);
+        assert_eq!(sub.recv().await, Ok(Received::Message(1)));
+        assert_eq!(sub.recv().await, Err(RecvError::Closed));
+    }
+}
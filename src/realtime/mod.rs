@@ -1,16 +1,43 @@
 //! Real-time communication — WebSocket and Server-Sent Events.
 //!
-//! ## Planned Features
+//! ## Implemented
 //!
-//! - WebSocket upgrade handshake (RFC 6455)
-//! - Async WebSocket frame send/receive
-//! - Server-Sent Events (SSE) response streams
-//! - Broadcast channels for pub/sub patterns
-//! - Heartbeat / ping-pong handling
+//! - [`ConnectionContext`] — per-connection identity, typed connection-scoped state, and
+//!   access to the application-wide [`SharedState`].
+//! - [`Broadcaster`] — bounded, non-blocking event fan-out with per-subscriber
+//!   [`LagPolicy`] handling for slow consumers.
+//! - [`Broadcast`] — a pub/sub hub of named topics, each fanned out by a [`Broadcaster`].
+//! - [`bridge`] — replicates [`Broadcast`] topics across instances over Redis or another
+//!   [`Transport`](bridge::Transport).
+//! - [`room`] — named [`Rooms`] with member lists, per-member metadata, and presence
+//!   events.
+//! - [`WebSocket`] — RFC 6455 framing over an upgraded connection: message reassembly,
+//!   UTF-8 validation, size limits, automatic pongs, and the closing handshake.
+//! - [`WsRouter`] — typed JSON messages dispatched to handlers by their `type` field.
+//! - The RFC 6455 upgrade handshake, via [`Router::ws`](crate::Router::ws) or
+//!   [`websocket::accept`].
+//! - [`sse`] — Server-Sent Events streams with `Last-Event-ID` resume from a
+//!   [`ReplayBuffer`].
+//! - [`heartbeat`] — WebSocket ping/pong [`Heartbeat`]s, SSE keep-alives, and
+//!   disconnect hooks.
 //!
-//! ## Status: PLANNED
+//! ## Status: IN PROGRESS
 
-// TODO: Implement WebSocket and SSE support
+pub mod bridge;
+pub mod broadcast;
+pub mod connection;
+pub mod heartbeat;
+pub mod hub;
+pub mod room;
+pub mod sse;
+pub mod websocket;
 
-/// Placeholder — will become the `WebSocket` connection type.
-pub struct WebSocket;
+pub use broadcast::{Broadcaster, LagPolicy, Received, RecvError, Subscriber};
+pub use connection::{ConnectionContext, ConnectionId, SharedState};
+pub use heartbeat::{DisconnectReason, Heartbeat};
+pub use hub::Broadcast;
+pub use room::{Member, Membership, RoomEvent, Rooms};
+pub use sse::{Event, ReplayBuffer, Sse};
+pub use websocket::{
+    CloseFrame, Message, MessageError, WebSocket, WebSocketConfig, WsError, WsRouter,
+};
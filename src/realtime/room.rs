@@ -0,0 +1,358 @@
+//! Named rooms with member lists and presence events, on top of the [`Broadcast`] hub.
+//!
+//! A connection [`join`](Rooms::join)s a room with its [`ConnectionId`] and some
+//! application metadata — a display name, say — and gets a [`Membership`] back. The
+//! membership receives everything sent to the room along with [`RoomEvent::Joined`] and
+//! [`RoomEvent::Left`] notices as other members come and go. Dropping it leaves the room,
+//! so a connection that ends for any reason never lingers in the member list.
+//!
+//! # Examples
+//!
+//! ```
+//! use rttp::realtime::{ConnectionId, LagPolicy, Received};
+//! use rttp::realtime::room::{RoomEvent, Rooms};
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let rooms: Rooms<String, &str> = Rooms::new(64, LagPolicy::DropOldest);
+//! let mut ada = rooms.join("lobby", ConnectionId::next(), "ada");
+//! let grace = rooms.join("lobby", ConnectionId::next(), "grace");
+//!
+//! let Ok(Received::Message(RoomEvent::Joined(member))) = ada.recv().await else {
+//!     panic!("expected a join");
+//! };
+//! assert_eq!(*member.meta(), "grace");
+//!
+//! grace.send("hello".to_owned());
+//! drop(grace);
+//! assert!(matches!(
+//!     ada.recv().await,
+//!     Ok(Received::Message(RoomEvent::Message { .. }))
+//! ));
+//! assert!(matches!(
+//!     ada.recv().await,
+//!     Ok(Received::Message(RoomEvent::Left(_)))
+//! ));
+//! assert_eq!(rooms.members("lobby").len(), 1);
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use super::{
+    broadcast::{LagPolicy, Received, RecvError, Subscriber},
+    connection::ConnectionId,
+    hub::Broadcast,
+};
+
+/// A room member: its connection and the metadata it joined with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member<M> {
+    id: ConnectionId,
+    meta: M,
+}
+
+impl<M> Member<M> {
+    /// Returns the member's connection identifier.
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    /// Returns the metadata the member joined with.
+    pub fn meta(&self) -> &M {
+        &self.meta
+    }
+}
+
+/// Something that happened in a room, as seen by its members.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoomEvent<T, M> {
+    /// A message sent to the room. `from` is the sending member, or `None` when the
+    /// application sent it with [`Rooms::broadcast`].
+    Message {
+        from: Option<ConnectionId>,
+        message: T,
+    },
+    /// A member joined.
+    Joined(Member<M>),
+    /// A member left.
+    Left(Member<M>),
+}
+
+// A member entry, tagged with the membership that created it so a stale membership for
+// the same connection cannot remove a newer one.
+struct Entry<M> {
+    meta: M,
+    membership: u64,
+}
+
+struct Shared<T, M> {
+    hub: Broadcast<RoomEvent<T, M>>,
+    members: Mutex<HashMap<String, HashMap<ConnectionId, Entry<M>>>>,
+    next_membership: AtomicU64,
+}
+
+/// A set of named rooms. Cheap to clone; all clones share rooms and members.
+///
+/// Rooms exist while they have members. Each membership buffers at most the configured
+/// capacity of events and falls behind according to the [`LagPolicy`].
+pub struct Rooms<T, M = ()> {
+    inner: Arc<Shared<T, M>>,
+}
+
+impl<T, M> Clone for Rooms<T, M> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T: Clone, M: Clone> Rooms<T, M> {
+    /// Creates an empty set of rooms whose memberships buffer at most `capacity` events.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize, policy: LagPolicy) -> Self {
+        Self {
+            inner: Arc::new(Shared {
+                hub: Broadcast::new(capacity, policy),
+                members: Mutex::new(HashMap::new()),
+                next_membership: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Adds connection `id` to `room` with `meta`, announcing it to the existing members.
+    ///
+    /// Joining a room the connection is already in replaces its metadata and takes over
+    /// the membership; the earlier [`Membership`] then leaves nothing behind when dropped.
+    pub fn join(&self, room: &str, id: ConnectionId, meta: M) -> Membership<T, M> {
+        let membership = self.inner.next_membership.fetch_add(1, Ordering::Relaxed);
+        self.lock().entry(room.to_owned()).or_default().insert(
+            id,
+            Entry {
+                meta: meta.clone(),
+                membership,
+            },
+        );
+        self.inner
+            .hub
+            .publish(room, RoomEvent::Joined(Member { id, meta }));
+        Membership {
+            rooms: self.clone(),
+            room: room.to_owned(),
+            id,
+            membership,
+            events: self.inner.hub.subscribe(room),
+        }
+    }
+
+    /// Sends `message` to every member of `room` on behalf of the application, returning
+    /// how many members received it.
+    pub fn broadcast(&self, room: &str, message: T) -> usize {
+        self.inner.hub.publish(
+            room,
+            RoomEvent::Message {
+                from: None,
+                message,
+            },
+        )
+    }
+
+    /// Returns the members of `room`, ordered by connection identifier.
+    pub fn members(&self, room: &str) -> Vec<Member<M>> {
+        let mut members: Vec<_> = self
+            .lock()
+            .get(room)
+            .into_iter()
+            .flatten()
+            .map(|(&id, entry)| Member {
+                id,
+                meta: entry.meta.clone(),
+            })
+            .collect();
+        members.sort_by_key(Member::id);
+        members
+    }
+
+    /// Returns the number of members in `room`.
+    pub fn member_count(&self, room: &str) -> usize {
+        self.lock().get(room).map_or(0, HashMap::len)
+    }
+
+    /// Returns the rooms that have members, in no particular order.
+    pub fn rooms(&self) -> Vec<String> {
+        self.lock().keys().cloned().collect()
+    }
+
+    fn leave(&self, room: &str, id: ConnectionId, membership: u64) {
+        let left = {
+            let mut rooms = self.lock();
+            let Some(members) = rooms.get_mut(room) else {
+                return;
+            };
+            if members.get(&id).is_none_or(|e| e.membership != membership) {
+                return;
+            }
+            let entry = members.remove(&id).expect("member was just found");
+            if members.is_empty() {
+                rooms.remove(room);
+            }
+            entry
+        };
+        self.inner.hub.publish(
+            room,
+            RoomEvent::Left(Member {
+                id,
+                meta: left.meta,
+            }),
+        );
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, HashMap<ConnectionId, Entry<M>>>> {
+        self.inner.members.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// One connection's place in a room. Dropping it leaves the room.
+pub struct Membership<T: Clone, M: Clone> {
+    rooms: Rooms<T, M>,
+    room: String,
+    id: ConnectionId,
+    membership: u64,
+    events: Subscriber<RoomEvent<T, M>>,
+}
+
+impl<T: Clone, M: Clone> Membership<T, M> {
+    /// Returns the room's name.
+    pub fn room(&self) -> &str {
+        &self.room
+    }
+
+    /// Returns the member's connection identifier.
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    /// Sends `message` to the room, returning how many members received it.
+    ///
+    /// The sender receives its own message too; compare the `from` of a [`RoomEvent::Message`]
+    /// with [`id`](Self::id) to skip the echo.
+    pub fn send(&self, message: T) -> usize {
+        self.rooms.inner.hub.publish(
+            &self.room,
+            RoomEvent::Message {
+                from: Some(self.id),
+                message,
+            },
+        )
+    }
+
+    /// Waits for the next event in the room. See [`Subscriber::recv`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RecvError::Lagged`] if the membership fell too far behind under
+    /// [`LagPolicy::Disconnect`]; it stays in the member list until dropped.
+    pub async fn recv(&mut self) -> Result<Received<RoomEvent<T, M>>, RecvError> {
+        self.events.recv().await
+    }
+
+    /// Leaves the room, announcing it to the remaining members.
+    pub fn leave(self) {}
+}
+
+impl<T: Clone, M: Clone> Drop for Membership<T, M> {
+    fn drop(&mut self) {
+        self.rooms.leave(&self.room, self.id, self.membership);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn next<T: Clone, M: Clone>(membership: &mut Membership<T, M>) -> Option<RoomEvent<T, M>> {
+        match membership.events.try_recv() {
+            Ok(Some(Received::Message(event))) => Some(event),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn announces_joins_and_leaves() {
+        let rooms: Rooms<&str, &str> = Rooms::new(8, LagPolicy::DropOldest);
+        let (a, b) = (ConnectionId::next(), ConnectionId::next());
+        let mut ada = rooms.join("lobby", a, "ada");
+        let grace = rooms.join("lobby", b, "grace");
+
+        assert_eq!(
+            next(&mut ada),
+            Some(RoomEvent::Joined(Member {
+                id: b,
+                meta: "grace"
+            }))
+        );
+        let names: Vec<_> = rooms.members("lobby").iter().map(|m| *m.meta()).collect();
+        assert_eq!(names, ["ada", "grace"]);
+
+        grace.leave();
+        assert_eq!(
+            next(&mut ada),
+            Some(RoomEvent::Left(Member {
+                id: b,
+                meta: "grace"
+            }))
+        );
+        assert_eq!(rooms.member_count("lobby"), 1);
+        drop(ada);
+        assert!(rooms.rooms().is_empty());
+    }
+
+    #[test]
+    fn messages_stay_in_their_room() {
+        let rooms: Rooms<u32> = Rooms::new(8, LagPolicy::DropOldest);
+        let id = ConnectionId::next();
+        let mut lobby = rooms.join("lobby", id, ());
+        let mut other = rooms.join("other", ConnectionId::next(), ());
+
+        assert_eq!(lobby.send(1), 1);
+        assert_eq!(rooms.broadcast("other", 2), 1);
+        assert_eq!(
+            next(&mut lobby),
+            Some(RoomEvent::Message {
+                from: Some(id),
+                message: 1
+            })
+        );
+        assert_eq!(next(&mut lobby), None);
+        assert_eq!(
+            next(&mut other),
+            Some(RoomEvent::Message {
+                from: None,
+                message: 2
+            })
+        );
+    }
+
+    #[test]
+    fn rejoining_replaces_the_membership() {
+        let rooms: Rooms<(), u8> = Rooms::new(8, LagPolicy::DropOldest);
+        let id = ConnectionId::next();
+        let first = rooms.join("lobby", id, 1);
+        let second = rooms.join("lobby", id, 2);
+
+        drop(first);
+        assert_eq!(rooms.members("lobby"), [Member { id, meta: 2 }]);
+        drop(second);
+        assert_eq!(rooms.member_count("lobby"), 0);
+    }
+}
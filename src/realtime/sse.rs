@@ -0,0 +1,439 @@
+//! Server-Sent Events (`text/event-stream`).
+//!
+//! [`stream`] answers a request with an event stream and hands the handler an [`Sse`]
+//! sender for [`Event`]s. Browsers' `EventSource` reconnects automatically after a
+//! dropped connection and sends the ID of the last event it saw as `Last-Event-ID`;
+//! [`Sse::last_event_id`] exposes it, and a [`ReplayBuffer`] of recent events lets the
+//! handler send whatever the client missed before carrying on.
+//!
+//! While the handler waits for something to send, [`Sse::wait_for`] keeps the stream
+//! alive with comment lines so proxies do not time it out and a departed client is
+//! noticed.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rttp::Router;
+//! use rttp::realtime::sse::{self, Event, ReplayBuffer};
+//!
+//! let feed = ReplayBuffer::new(100);
+//! let mut router = Router::new();
+//! router.get("/notifications", move |ctx| {
+//!     let feed = feed.clone();
+//!     async move {
+//!         sse::stream(ctx, move |_ctx, mut sse| async move {
+//!             if sse.replay(&feed).await.is_err() {
+//!                 return;
+//!             }
+//!             let event = feed.record(Event::new().event("ping").data("hello"));
+//!             let _ = sse.send(&event).await;
+//!         })
+//!     }
+//! });
+//! ```
+
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    future::Future,
+    io,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use serde::Serialize;
+use tokio::{io::AsyncWriteExt, time::Instant};
+
+use super::heartbeat::{DisconnectHook, DisconnectReason};
+use crate::{
+    Request, Response, StatusCode,
+    context::Context,
+    http::upgrade::{OnUpgrade, Upgraded},
+};
+
+/// One server-sent event.
+///
+/// Newlines in the ID and event name are removed; multi-line data is sent as several
+/// `data:` lines and arrives joined with `\n`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Event {
+    id: Option<String>,
+    event: Option<String>,
+    data: Option<String>,
+    retry: Option<Duration>,
+    comment: Option<String>,
+}
+
+impl Event {
+    /// Creates an empty event.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a comment line, which clients ignore; useful as a keep-alive.
+    pub fn comment(text: impl Into<String>) -> Self {
+        Self {
+            comment: Some(text.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Sets the event ID, which the client echoes as `Last-Event-ID` when it reconnects.
+    #[must_use]
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(single_line(id.into()));
+        self
+    }
+
+    /// Sets the event name; `EventSource` dispatches it to listeners for that name
+    /// instead of `onmessage`.
+    #[must_use]
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(single_line(event.into()));
+        self
+    }
+
+    /// Sets the data.
+    #[must_use]
+    pub fn data(mut self, data: impl Into<String>) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// Sets the data to `value` encoded as JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns the serializer's error if `value` cannot be encoded.
+    pub fn json_data<T: Serialize>(self, value: &T) -> Result<Self, serde_json::Error> {
+        Ok(self.data(serde_json::to_string(value)?))
+    }
+
+    /// Tells the client how long to wait before reconnecting.
+    #[must_use]
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Returns the event ID, if set.
+    pub fn event_id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Encodes the event in the `text/event-stream` format, including the blank line
+    /// that terminates it.
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        if let Some(comment) = &self.comment {
+            for line in comment.lines() {
+                let _ = writeln!(out, ": {line}");
+            }
+        }
+        if let Some(id) = &self.id {
+            let _ = writeln!(out, "id: {id}");
+        }
+        if let Some(event) = &self.event {
+            let _ = writeln!(out, "event: {event}");
+        }
+        if let Some(retry) = self.retry {
+            let _ = writeln!(out, "retry: {}", retry.as_millis());
+        }
+        if let Some(data) = &self.data {
+            for line in data.split('\n') {
+                let _ = writeln!(out, "data: {}", line.strip_suffix('\r').unwrap_or(line));
+            }
+        }
+        out.push('\n');
+        out
+    }
+}
+
+fn single_line(mut value: String) -> String {
+    value.retain(|c| !matches!(c, '\r' | '\n' | '\0'));
+    value
+}
+
+/// Returns the request's `Last-Event-ID` header: the ID of the last event a reconnecting
+/// client received.
+pub fn last_event_id(request: &Request) -> Option<&str> {
+    request
+        .headers()
+        .get("Last-Event-ID")
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+}
+
+/// Answers a request with an event stream and runs `handler` to produce it.
+///
+/// `handler` receives the request's [`Context`] and the [`Sse`] sender; the stream
+/// ends when it returns or the client disconnects.
+pub fn stream<F, Fut>(ctx: Context, handler: F) -> Response
+where
+    F: FnOnce(Context, Sse) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let last_event_id = last_event_id(ctx.request()).map(str::to_owned);
+    Response::new(StatusCode::Ok)
+        .header("Content-Type", "text/event-stream")
+        .header("Cache-Control", "no-cache")
+        // Stops reverse proxies such as nginx from buffering the stream.
+        .header("X-Accel-Buffering", "no")
+        .stream(OnUpgrade::new(move |io| async move {
+            let sse = Sse {
+                io,
+                last_event_id,
+                keep_alive: KEEP_ALIVE,
+                last_sent: Instant::now(),
+                on_disconnect: DisconnectHook::default(),
+            };
+            handler(ctx, sse).await;
+        }))
+}
+
+// Comfortably inside the 30–60 second idle timeouts common in proxies.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// The sending half of an event stream.
+#[derive(Debug)]
+pub struct Sse {
+    io: Upgraded,
+    last_event_id: Option<String>,
+    keep_alive: Duration,
+    last_sent: Instant,
+    on_disconnect: DisconnectHook,
+}
+
+impl Sse {
+    /// Sets how long the stream may go without an event before
+    /// [`wait_for`](Self::wait_for) sends a keep-alive comment. Defaults to 15 seconds.
+    #[must_use]
+    pub fn keep_alive(mut self, interval: Duration) -> Self {
+        self.keep_alive = interval;
+        self
+    }
+
+    /// Registers `hook` to run once when the stream ends: with
+    /// [`DisconnectReason::Gone`] when a write fails because the client left, or
+    /// [`DisconnectReason::Closed`] when the handler drops the stream.
+    pub fn on_disconnect(&mut self, hook: impl FnOnce(DisconnectReason) + Send + 'static) {
+        self.on_disconnect.set(hook);
+    }
+
+    /// The `Last-Event-ID` the client reconnected with, if any.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+
+    /// Sends `event`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the I/O error if the client has disconnected.
+    pub async fn send(&mut self, event: &Event) -> io::Result<()> {
+        let written = self.write(event).await;
+        if written.is_err() {
+            self.on_disconnect.fire(DisconnectReason::Gone);
+        }
+        written
+    }
+
+    /// Waits for `future`, sending a keep-alive comment whenever the stream has been
+    /// idle for the [`keep_alive`](Self::keep_alive) interval.
+    ///
+    /// Wrap whatever the handler waits on between events — typically a
+    /// [`Subscriber::recv`](super::Subscriber::recv) — so an idle stream stays open and a
+    /// client that has gone is detected without waiting for the next event.
+    ///
+    /// # Errors
+    ///
+    /// Returns the I/O error if a keep-alive fails because the client has disconnected;
+    /// `future` is dropped.
+    pub async fn wait_for<F: Future>(&mut self, future: F) -> io::Result<F::Output> {
+        let mut future = std::pin::pin!(future);
+        loop {
+            let deadline = self.last_sent + self.keep_alive;
+            if let Ok(output) = tokio::time::timeout_at(deadline, future.as_mut()).await {
+                return Ok(output);
+            }
+            self.send(&Event::comment("keep-alive")).await?;
+        }
+    }
+
+    async fn write(&mut self, event: &Event) -> io::Result<()> {
+        self.io.write_all(event.encode().as_bytes()).await?;
+        self.io.flush().await?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    /// Sends the events in `buffer` recorded after [`last_event_id`](Self::last_event_id),
+    /// returning how many were sent.
+    ///
+    /// Sends nothing for a first connection, or when the client's last event has already
+    /// been evicted from the buffer — the gap cannot be filled, so the application should
+    /// send a fresh snapshot instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns the I/O error if the client has disconnected.
+    pub async fn replay(&mut self, buffer: &ReplayBuffer) -> io::Result<usize> {
+        let Some(missed) = self.last_event_id().and_then(|id| buffer.since(id)) else {
+            return Ok(0);
+        };
+        for event in &missed {
+            self.send(event).await?;
+        }
+        Ok(missed.len())
+    }
+}
+
+impl Drop for Sse {
+    fn drop(&mut self) {
+        self.on_disconnect.fire(DisconnectReason::Closed);
+    }
+}
+
+/// A bounded, shared history of recent events, for replaying to reconnecting clients.
+///
+/// Clones share the same history. Once full, the oldest event is dropped.
+#[derive(Clone)]
+pub struct ReplayBuffer {
+    inner: Arc<Mutex<Replay>>,
+}
+
+struct Replay {
+    capacity: usize,
+    next_id: u64,
+    events: VecDeque<Event>,
+}
+
+impl ReplayBuffer {
+    /// Creates a buffer that keeps the last `capacity` events.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Replay {
+                capacity,
+                next_id: 1,
+                events: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// Stores `event`, giving it the next sequential ID unless it already has one, and
+    /// returns it ready to send.
+    pub fn record(&self, mut event: Event) -> Event {
+        let mut replay = self.lock();
+        if event.id.is_none() {
+            event.id = Some(replay.next_id.to_string());
+            replay.next_id += 1;
+        }
+        if replay.capacity > 0 {
+            if replay.events.len() >= replay.capacity {
+                replay.events.pop_front();
+            }
+            replay.events.push_back(event.clone());
+        }
+        event
+    }
+
+    /// Returns the events recorded after the one with `last_event_id`, oldest first, or
+    /// `None` if that event is not in the buffer.
+    pub fn since(&self, last_event_id: &str) -> Option<Vec<Event>> {
+        let replay = self.lock();
+        let seen = replay
+            .events
+            .iter()
+            .position(|event| event.id.as_deref() == Some(last_event_id))?;
+        Some(replay.events.iter().skip(seen + 1).cloned().collect())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Replay> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use tokio::io::{AsyncReadExt, duplex};
+
+    use super::*;
+
+    #[test]
+    fn encodes_events() {
+        let event = Event::new()
+            .id("7\n")
+            .event("update")
+            .retry(Duration::from_secs(3))
+            .data("line one\nline two");
+        assert_eq!(
+            event.encode(),
+            "id: 7\nevent: update\nretry: 3000\ndata: line one\ndata: line two\n\n"
+        );
+        assert_eq!(Event::comment("keep-alive").encode(), ": keep-alive\n\n");
+    }
+
+    #[test]
+    fn replay_buffer_returns_missed_events() {
+        let buffer = ReplayBuffer::new(3);
+        for n in 0..4 {
+            buffer.record(Event::new().data(n.to_string()));
+        }
+        // Event 1 was evicted; 2..=4 remain.
+        assert_eq!(buffer.since("1"), None);
+        let missed = buffer.since("2").unwrap();
+        let ids: Vec<_> = missed.iter().filter_map(Event::event_id).collect();
+        assert_eq!(ids, ["3", "4"]);
+        assert_eq!(buffer.since("4"), Some(Vec::new()));
+    }
+
+    #[tokio::test]
+    async fn streams_missed_events_after_reconnect() {
+        let buffer = ReplayBuffer::new(10);
+        for n in 1..=3 {
+            buffer.record(Event::new().data(format!("event {n}")));
+        }
+
+        let raw = b"GET /feed HTTP/1.1\r\nHost: localhost\r\nLast-Event-ID: 2\r\n\r\n";
+        let ctx = Context::new(Request::parse(raw).unwrap().0);
+        let replayed = buffer.clone();
+        let mut response = stream(ctx, move |_, mut sse| async move {
+            assert_eq!(sse.last_event_id(), Some("2"));
+            sse.replay(&replayed).await.unwrap();
+        });
+        assert_eq!(
+            response.headers().get("Content-Type"),
+            Some("text/event-stream")
+        );
+
+        let (server, mut client) = duplex(1024);
+        let writer = response.take_upgrade().unwrap();
+        writer.run(Upgraded::new(server, BytesMut::new())).await;
+        let mut body = String::new();
+        client.read_to_string(&mut body).await.unwrap();
+        assert_eq!(body, "id: 3\ndata: event 3\n\n");
+    }
+
+    #[tokio::test]
+    async fn keeps_idle_streams_alive() {
+        let ctx = Context::new(Request::parse(b"GET /feed HTTP/1.1\r\n\r\n").unwrap().0);
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut response = stream(ctx, move |_, sse| async move {
+            let mut sse = sse.keep_alive(Duration::from_millis(10));
+            sse.on_disconnect(move |reason| tx.send(reason).unwrap());
+            let slow = tokio::time::sleep(Duration::from_millis(35));
+            sse.wait_for(slow).await.unwrap();
+        });
+
+        let (server, mut client) = duplex(1024);
+        let writer = response.take_upgrade().unwrap();
+        writer.run(Upgraded::new(server, BytesMut::new())).await;
+        let mut body = String::new();
+        client.read_to_string(&mut body).await.unwrap();
+        let keep_alives = body.matches(": keep-alive\n\n").count();
+        assert!(keep_alives >= 2, "{body:?}");
+        assert_eq!(body.len(), keep_alives * ": keep-alive\n\n".len());
+        assert_eq!(rx.try_recv(), Ok(DisconnectReason::Closed));
+    }
+}
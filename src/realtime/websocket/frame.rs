@@ -0,0 +1,314 @@
+//! RFC 6455 frame encoding and decoding.
+
+use bytes::{Buf, BytesMut};
+
+use super::{CloseFrame, WsError};
+
+/// Frame opcodes (RFC 6455 §5.2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum OpCode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl OpCode {
+    fn from_u8(bits: u8) -> Option<Self> {
+        Some(match bits {
+            0x0 => Self::Continuation,
+            0x1 => Self::Text,
+            0x2 => Self::Binary,
+            0x8 => Self::Close,
+            0x9 => Self::Ping,
+            0xA => Self::Pong,
+            _ => return None,
+        })
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            Self::Continuation => 0x0,
+            Self::Text => 0x1,
+            Self::Binary => 0x2,
+            Self::Close => 0x8,
+            Self::Ping => 0x9,
+            Self::Pong => 0xA,
+        }
+    }
+
+    pub(super) fn is_control(self) -> bool {
+        matches!(self, Self::Close | Self::Ping | Self::Pong)
+    }
+}
+
+/// One decoded frame, already unmasked.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct Frame {
+    pub(super) fin: bool,
+    pub(super) opcode: OpCode,
+    pub(super) payload: Vec<u8>,
+}
+
+/// Control frames carry at most 125 bytes of payload (RFC 6455 §5.5).
+const MAX_CONTROL_PAYLOAD: usize = 125;
+
+/// Decodes the next frame from the front of `buf`, consuming it.
+///
+/// Returns `Ok(None)` if `buf` does not hold a complete frame yet. `masked` is whether
+/// incoming frames must be masked — true on the server side, where every client frame is.
+pub(super) fn decode(
+    buf: &mut BytesMut,
+    masked: bool,
+    max_frame_size: usize,
+) -> Result<Option<Frame>, WsError> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let (b0, b1) = (buf[0], buf[1]);
+    let fin = b0 & 0x80 != 0;
+    if b0 & 0x70 != 0 {
+        return Err(WsError::Protocol(
+            "reserved bits set without a negotiated extension",
+        ));
+    }
+    let opcode = OpCode::from_u8(b0 & 0x0F).ok_or(WsError::Protocol("reserved opcode"))?;
+    if (b1 & 0x80 != 0) != masked {
+        return Err(WsError::Protocol(if masked {
+            "client frames must be masked"
+        } else {
+            "server frames must not be masked"
+        }));
+    }
+
+    let (len, mut header) = match b1 & 0x7F {
+        126 => {
+            if buf.len() < 4 {
+                return Ok(None);
+            }
+            (u64::from(u16::from_be_bytes([buf[2], buf[3]])), 4)
+        }
+        127 => {
+            if buf.len() < 10 {
+                return Ok(None);
+            }
+            let len = u64::from_be_bytes(buf[2..10].try_into().expect("8 bytes"));
+            if len >> 63 != 0 {
+                return Err(WsError::Protocol("payload length has its high bit set"));
+            }
+            (len, 10)
+        }
+        len => (u64::from(len), 2),
+    };
+
+    if opcode.is_control() {
+        if !fin {
+            return Err(WsError::Protocol("fragmented control frame"));
+        }
+        if len > MAX_CONTROL_PAYLOAD as u64 {
+            return Err(WsError::Protocol("control frame payload over 125 bytes"));
+        }
+    }
+    if len > max_frame_size as u64 {
+        return Err(WsError::FrameTooLarge {
+            size: len,
+            limit: max_frame_size,
+        });
+    }
+    // Bounded by `max_frame_size`, so it fits in a usize.
+    let len = len as usize;
+
+    let mask = if masked {
+        if buf.len() < header + 4 {
+            return Ok(None);
+        }
+        let key: [u8; 4] = buf[header..header + 4].try_into().expect("4 bytes");
+        header += 4;
+        Some(key)
+    } else {
+        None
+    };
+    if buf.len() < header + len {
+        return Ok(None);
+    }
+
+    buf.advance(header);
+    let mut payload = buf.split_to(len).to_vec();
+    if let Some(key) = mask {
+        apply_mask(&mut payload, key);
+    }
+    Ok(Some(Frame {
+        fin,
+        opcode,
+        payload,
+    }))
+}
+
+/// Appends a single frame to `out`, masking the payload with `mask` if given.
+pub(super) fn encode(
+    opcode: OpCode,
+    fin: bool,
+    payload: &[u8],
+    mask: Option<[u8; 4]>,
+    out: &mut Vec<u8>,
+) {
+    out.push(if fin { 0x80 } else { 0 } | opcode.as_u8());
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        len @ 0..=125 => out.push(mask_bit | len as u8),
+        len @ 126..=0xFFFF => {
+            out.push(mask_bit | 126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.push(mask_bit | 127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(key) => {
+            out.extend_from_slice(&key);
+            let start = out.len();
+            out.extend_from_slice(payload);
+            apply_mask(&mut out[start..], key);
+        }
+        None => out.extend_from_slice(payload),
+    }
+}
+
+fn apply_mask(data: &mut [u8], key: [u8; 4]) {
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte ^= key[i % 4];
+    }
+}
+
+/// Parses a close frame body: empty, or a status code followed by a UTF-8 reason.
+pub(super) fn parse_close(payload: &[u8]) -> Result<Option<CloseFrame>, WsError> {
+    match payload {
+        [] => Ok(None),
+        [_] => Err(WsError::Protocol("close frame with a one-byte body")),
+        [hi, lo, reason @ ..] => {
+            let code = u16::from_be_bytes([*hi, *lo]);
+            if !is_valid_close_code(code) {
+                return Err(WsError::Protocol("invalid close code"));
+            }
+            let reason = std::str::from_utf8(reason).map_err(|_| WsError::InvalidUtf8)?;
+            Ok(Some(CloseFrame::new(code, reason)))
+        }
+    }
+}
+
+/// Encodes a close frame body.
+pub(super) fn close_payload(frame: Option<&CloseFrame>) -> Vec<u8> {
+    let Some(frame) = frame else {
+        return Vec::new();
+    };
+    let mut payload = frame.code.to_be_bytes().to_vec();
+    // Keep the whole control frame within 125 bytes, cutting on a char boundary.
+    let mut end = frame.reason.len().min(MAX_CONTROL_PAYLOAD - 2);
+    while !frame.reason.is_char_boundary(end) {
+        end -= 1;
+    }
+    payload.extend_from_slice(&frame.reason.as_bytes()[..end]);
+    payload
+}
+
+// Codes an endpoint may put on the wire (RFC 6455 §7.4); 1005, 1006, and 1015 are
+// reserved for reporting and never sent.
+fn is_valid_close_code(code: u16) -> bool {
+    matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(opcode: OpCode, payload: &[u8], mask: Option<[u8; 4]>) -> Frame {
+        let mut out = Vec::new();
+        encode(opcode, true, payload, mask, &mut out);
+        let mut buf = BytesMut::from(&out[..]);
+        // Truncated headers and payloads are incomplete.
+        for end in (0..out.len()).filter(|&end| end < 16 || end + 1 == out.len()) {
+            let mut partial = BytesMut::from(&out[..end]);
+            assert_eq!(decode(&mut partial, mask.is_some(), 1 << 20).unwrap(), None);
+        }
+        let frame = decode(&mut buf, mask.is_some(), 1 << 20).unwrap().unwrap();
+        assert!(buf.is_empty());
+        frame
+    }
+
+    #[test]
+    fn round_trips_every_length_encoding() {
+        for len in [0, 125, 126, 0xFFFF, 0x10000] {
+            let payload = vec![7u8; len];
+            let frame = round_trip(OpCode::Binary, &payload, Some([1, 2, 3, 4]));
+            assert_eq!(frame.payload, payload);
+            let frame = round_trip(OpCode::Binary, &payload, None);
+            assert_eq!(frame.payload, payload);
+        }
+    }
+
+    #[test]
+    fn unmasks_the_rfc_example() {
+        // RFC 6455 §5.7: a masked text frame containing "Hello".
+        let mut buf = BytesMut::from(
+            &[
+                0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+            ][..],
+        );
+        let frame = decode(&mut buf, true, 1024).unwrap().unwrap();
+        assert_eq!(frame.opcode, OpCode::Text);
+        assert!(frame.fin);
+        assert_eq!(frame.payload, b"Hello");
+    }
+
+    #[test]
+    fn rejects_protocol_violations() {
+        let cases: [&[u8]; 5] = [
+            &[0x81, 0x00],                   // unmasked client frame
+            &[0xC1, 0x80, 0, 0, 0, 0],       // RSV1 set
+            &[0x83, 0x80, 0, 0, 0, 0],       // reserved opcode
+            &[0x09, 0x80, 0, 0, 0, 0],       // fragmented ping
+            &[0x89, 0xFE, 0x00, 0x7E, 0, 0], // 126-byte ping
+        ];
+        for bytes in cases {
+            let mut buf = BytesMut::from(bytes);
+            assert!(
+                matches!(decode(&mut buf, true, 1024), Err(WsError::Protocol(_))),
+                "{bytes:x?}"
+            );
+        }
+
+        let mut buf = BytesMut::from(&[0x82, 0xFE, 0x04, 0x01][..]);
+        assert!(matches!(
+            decode(&mut buf, true, 1024),
+            Err(WsError::FrameTooLarge {
+                size: 1025,
+                limit: 1024
+            })
+        ));
+    }
+
+    #[test]
+    fn parses_close_bodies() {
+        assert_eq!(parse_close(&[]).unwrap(), None);
+        assert_eq!(
+            parse_close(&[0x03, 0xE8, b'b', b'y', b'e']).unwrap(),
+            Some(CloseFrame::new(1000, "bye"))
+        );
+        assert!(matches!(parse_close(&[0x03]), Err(WsError::Protocol(_))));
+        assert!(matches!(
+            parse_close(&[0x03, 0xED]),
+            Err(WsError::Protocol(_))
+        ));
+        assert!(matches!(
+            parse_close(&[0x03, 0xE8, 0xFF]),
+            Err(WsError::InvalidUtf8)
+        ));
+
+        let long = CloseFrame::new(1000, "é".repeat(100));
+        assert!(close_payload(Some(&long)).len() <= MAX_CONTROL_PAYLOAD);
+    }
+}
@@ -0,0 +1,135 @@
+//! The server side of the opening handshake (RFC 6455 §4.2).
+
+use std::future::Future;
+
+use super::{WebSocket, WebSocketConfig};
+use crate::{
+    Method, Response, StatusCode,
+    context::Context,
+    http::upgrade::OnUpgrade,
+    security::crypto::{base64_decode, base64_encode, sha1},
+};
+
+// Appended to the client's key before hashing (RFC 6455 §1.3).
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Computes `Sec-WebSocket-Accept` for a client's `Sec-WebSocket-Key`.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::realtime::websocket::accept_key;
+///
+/// // RFC 6455 §1.3.
+/// assert_eq!(
+///     accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+///     "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+/// );
+/// ```
+pub fn accept_key(key: &str) -> String {
+    base64_encode(&sha1(format!("{key}{GUID}").as_bytes()))
+}
+
+/// Answers a WebSocket upgrade request, running `handler` on the connection once the
+/// `101 Switching Protocols` response is sent.
+///
+/// `handler` receives the request's [`Context`] — path parameters and any extensions
+/// set by middleware, such as the authenticated principal — alongside the socket.
+/// Requests that are not valid upgrades get `400 Bad Request`, or `426 Upgrade Required`
+/// when they lack the upgrade headers or ask for an unsupported protocol version.
+///
+/// [`Router::ws`](crate::Router::ws) wraps this; call it directly from a `GET` handler to
+/// pick the [`WebSocketConfig`] or decide per request whether to accept.
+pub fn accept<F, Fut>(ctx: Context, config: WebSocketConfig, handler: F) -> Response
+where
+    F: FnOnce(Context, WebSocket) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let key = match validate(&ctx) {
+        Ok(key) => key,
+        Err(response) => return response,
+    };
+    Response::new(StatusCode::SwitchingProtocols)
+        .header("Upgrade", "websocket")
+        .header("Connection", "Upgrade")
+        .header("Sec-WebSocket-Accept", accept_key(&key))
+        .on_upgrade(OnUpgrade::new(move |upgraded| async move {
+            handler(ctx, WebSocket::from_stream(upgraded, config)).await;
+        }))
+}
+
+// Checks the upgrade headers, returning the client's key or the response refusing it.
+fn validate(ctx: &Context) -> Result<String, Response> {
+    let request = ctx.request();
+    if request.method() != &Method::Get {
+        return Err(Response::new(StatusCode::MethodNotAllowed).header("Allow", "GET"));
+    }
+    let has_token = |name: &str, token: &str| {
+        request.headers().get(name).is_some_and(|value| {
+            value
+                .split(',')
+                .any(|t| t.trim().eq_ignore_ascii_case(token))
+        })
+    };
+    if !has_token("Upgrade", "websocket") || !has_token("Connection", "upgrade") {
+        return Err(Response::new(StatusCode::UpgradeRequired)
+            .header("Upgrade", "websocket")
+            .header("Connection", "Upgrade")
+            .body("Expected a WebSocket upgrade request"));
+    }
+    if request.headers().get("Sec-WebSocket-Version") != Some("13") {
+        return Err(Response::new(StatusCode::UpgradeRequired)
+            .header("Sec-WebSocket-Version", "13")
+            .body("Unsupported WebSocket version"));
+    }
+    match request.headers().get("Sec-WebSocket-Key") {
+        Some(key) if base64_decode(key.trim()).is_some_and(|nonce| nonce.len() == 16) => {
+            Ok(key.trim().to_owned())
+        }
+        _ => Err(Response::new(StatusCode::BadRequest).body("Invalid Sec-WebSocket-Key")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Request;
+
+    fn context(headers: &str) -> Context {
+        let raw = format!("GET /chat HTTP/1.1\r\nHost: localhost\r\n{headers}\r\n");
+        Context::new(Request::parse(raw.as_bytes()).unwrap().0)
+    }
+
+    const UPGRADE: &str = "Upgrade: websocket\r\nConnection: keep-alive, Upgrade\r\n\
+        Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n";
+
+    #[test]
+    fn accepts_valid_upgrades() {
+        let mut response = accept(context(UPGRADE), WebSocketConfig::new(), |_, _| async {});
+        assert_eq!(response.status(), StatusCode::SwitchingProtocols);
+        assert_eq!(
+            response.headers().get("Sec-WebSocket-Accept"),
+            Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+        );
+        assert!(response.take_upgrade().is_some());
+    }
+
+    #[test]
+    fn refuses_invalid_upgrades() {
+        let cases = [
+            (String::new(), StatusCode::UpgradeRequired),
+            (
+                UPGRADE.replace("Version: 13", "Version: 8"),
+                StatusCode::UpgradeRequired,
+            ),
+            (
+                UPGRADE.replace("dGhlIHNhbXBsZSBub25jZQ==", "c2hvcnQ="),
+                StatusCode::BadRequest,
+            ),
+        ];
+        for (headers, status) in cases {
+            let response = accept(context(&headers), WebSocketConfig::new(), |_, _| async {});
+            assert_eq!(response.status(), status, "{headers}");
+        }
+    }
+}
@@ -0,0 +1,742 @@
+//! WebSocket connections (RFC 6455).
+//!
+//! A [`WebSocket`] wraps an already-upgraded stream and speaks the framing protocol on
+//! it: [`next`](WebSocket::next) yields whole [`Message`]s — reassembling fragmented
+//! ones, validating UTF-8 in text, and enforcing [`WebSocketConfig`] size limits — and
+//! [`send`](WebSocket::send) writes them.
+//!
+//! Control frames are handled as the RFC requires: pings are answered with a pong
+//! automatically, and a close frame from the peer is echoed before the connection is
+//! shut down. Both are still surfaced to the handler. A protocol violation closes the
+//! connection with the matching status code and is reported as a [`WsError`].
+//!
+//! With a [`Heartbeat`] configured, a connection that has been quiet for the interval is
+//! pinged while the handler waits in [`next`](WebSocket::next), and one that stays
+//! silent past the timeout is dropped with [`WsError::TimedOut`].
+//!
+//! Register endpoints with [`Router::ws`](crate::Router::ws), or answer the upgrade by
+//! hand with [`accept`]. For JSON protocols, [`WsRouter`] dispatches messages to typed
+//! handlers by their `type` field.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rttp::realtime::{Message, WebSocket};
+//!
+//! async fn echo(mut ws: WebSocket) {
+//!     while let Some(Ok(message)) = ws.next().await {
+//!         match message {
+//!             Message::Text(_) | Message::Binary(_) => {
+//!                 if ws.send(message).await.is_err() {
+//!                     break;
+//!                 }
+//!             }
+//!             _ => {}
+//!         }
+//!     }
+//! }
+//! ```
+
+mod frame;
+mod handshake;
+mod router;
+
+use std::{fmt, io};
+
+use bytes::BytesMut;
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    time::{Instant, timeout_at},
+};
+
+use super::heartbeat::{DisconnectHook, DisconnectReason, Heartbeat};
+use crate::security::crypto::random_bytes;
+use frame::OpCode;
+pub use handshake::{accept, accept_key};
+pub use router::{MessageError, WsRouter, message_error};
+
+/// Close status codes (RFC 6455 §7.4.1).
+pub mod close_code {
+    /// The purpose of the connection has been fulfilled.
+    pub const NORMAL: u16 = 1000;
+    /// The endpoint is going away, e.g. the server is shutting down.
+    pub const GOING_AWAY: u16 = 1001;
+    /// The peer violated the protocol.
+    pub const PROTOCOL_ERROR: u16 = 1002;
+    /// The peer sent a kind of data the endpoint cannot accept.
+    pub const UNSUPPORTED_DATA: u16 = 1003;
+    /// A text message was not valid UTF-8.
+    pub const INVALID_PAYLOAD: u16 = 1007;
+    /// A message violated the endpoint's policy.
+    pub const POLICY_VIOLATION: u16 = 1008;
+    /// A frame or message exceeded the size limits.
+    pub const MESSAGE_TOO_BIG: u16 = 1009;
+    /// The server hit an unexpected condition.
+    pub const INTERNAL_ERROR: u16 = 1011;
+}
+
+/// Errors produced by a [`WebSocket`].
+#[derive(Debug, Error)]
+pub enum WsError {
+    #[error("WebSocket I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("WebSocket protocol violation: {0}")]
+    Protocol(&'static str),
+
+    #[error("WebSocket frame of {size} bytes exceeds the {limit}-byte limit")]
+    FrameTooLarge { size: u64, limit: usize },
+
+    #[error("WebSocket message exceeds the {limit}-byte limit")]
+    MessageTooLarge { limit: usize },
+
+    #[error("WebSocket text is not valid UTF-8")]
+    InvalidUtf8,
+
+    #[error("WebSocket peer did not answer a ping within {0:?}")]
+    TimedOut(std::time::Duration),
+
+    #[error("the WebSocket is closed")]
+    Closed,
+}
+
+impl WsError {
+    // The status code to close the connection with after this error, if any.
+    fn close_code(&self) -> Option<u16> {
+        match self {
+            Self::Protocol(_) => Some(close_code::PROTOCOL_ERROR),
+            Self::FrameTooLarge { .. } | Self::MessageTooLarge { .. } => {
+                Some(close_code::MESSAGE_TOO_BIG)
+            }
+            Self::InvalidUtf8 => Some(close_code::INVALID_PAYLOAD),
+            // The peer is unresponsive, so a close frame could block on a full buffer.
+            Self::Io(_) | Self::TimedOut(_) | Self::Closed => None,
+        }
+    }
+}
+
+/// The status code and reason carried by a close frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseFrame {
+    /// See [`close_code`] for common values.
+    pub code: u16,
+    /// Human-readable reason; truncated to fit in one control frame when sent.
+    pub reason: String,
+}
+
+impl CloseFrame {
+    /// Creates a close frame.
+    pub fn new(code: u16, reason: impl Into<String>) -> Self {
+        Self {
+            code,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// A complete WebSocket message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// UTF-8 text.
+    Text(String),
+    /// Binary data.
+    Binary(Vec<u8>),
+    /// A ping; the connection answers it automatically.
+    Ping(Vec<u8>),
+    /// A pong, usually answering a ping this side sent.
+    Pong(Vec<u8>),
+    /// The closing handshake, with the peer's status code if it sent one.
+    Close(Option<CloseFrame>),
+}
+
+impl Message {
+    /// Creates a text message.
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    /// Creates a binary message.
+    pub fn binary(data: impl Into<Vec<u8>>) -> Self {
+        Self::Binary(data.into())
+    }
+
+    /// Returns the text of a [`Text`](Self::Text) message.
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` for [`Close`](Self::Close).
+    pub fn is_close(&self) -> bool {
+        matches!(self, Self::Close(_))
+    }
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for Message {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_owned())
+    }
+}
+
+impl From<Vec<u8>> for Message {
+    fn from(data: Vec<u8>) -> Self {
+        Self::Binary(data)
+    }
+}
+
+/// Size limits for incoming frames and messages, and the optional [`Heartbeat`].
+///
+/// Defaults: 1 MiB per frame, 4 MiB per reassembled message, and no heartbeat.
+#[derive(Debug, Clone, Copy)]
+pub struct WebSocketConfig {
+    max_frame_size: usize,
+    max_message_size: usize,
+    heartbeat: Option<Heartbeat>,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            max_frame_size: 1 << 20,
+            max_message_size: 4 << 20,
+            heartbeat: None,
+        }
+    }
+}
+
+impl WebSocketConfig {
+    /// Creates the default limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the largest frame payload accepted.
+    #[must_use]
+    pub fn max_frame_size(mut self, bytes: usize) -> Self {
+        self.max_frame_size = bytes;
+        self
+    }
+
+    /// Sets the largest message accepted, summed over its fragments.
+    #[must_use]
+    pub fn max_message_size(mut self, bytes: usize) -> Self {
+        self.max_message_size = bytes;
+        self
+    }
+
+    /// Pings quiet connections and drops unresponsive ones.
+    #[must_use]
+    pub fn heartbeat(mut self, heartbeat: Heartbeat) -> Self {
+        self.heartbeat = Some(heartbeat);
+        self
+    }
+}
+
+// The upgraded connection, type-erased so handlers see one `WebSocket` type for plain
+// and TLS streams alike.
+trait Io: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Io for T {}
+
+// Which end of the connection this is; decides masking in both directions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Server,
+    Client,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Open,
+    // We sent a close frame and are waiting for the peer's.
+    CloseSent,
+    Closed,
+}
+
+/// A WebSocket connection.
+pub struct WebSocket {
+    io: Box<dyn Io>,
+    role: Role,
+    config: WebSocketConfig,
+    state: State,
+    read_buf: BytesMut,
+    // The opcode and payload so far of a fragmented message.
+    fragments: Option<(OpCode, Vec<u8>)>,
+    // When the peer last sent anything, and when we pinged it since, for the heartbeat.
+    last_seen: Instant,
+    ping_sent: Option<Instant>,
+    on_disconnect: DisconnectHook,
+}
+
+impl WebSocket {
+    /// Wraps the server side of a stream whose upgrade handshake has completed.
+    pub fn from_stream<S>(stream: S, config: WebSocketConfig) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        Self::with_role(stream, Role::Server, config)
+    }
+
+    // Wraps the client side of a completed handshake, for `TestClient`.
+    pub(crate) fn client<S>(stream: S, config: WebSocketConfig) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        Self::with_role(stream, Role::Client, config)
+    }
+
+    fn with_role<S>(stream: S, role: Role, config: WebSocketConfig) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        Self {
+            io: Box::new(stream),
+            role,
+            config,
+            state: State::Open,
+            read_buf: BytesMut::with_capacity(4096),
+            fragments: None,
+            last_seen: Instant::now(),
+            ping_sent: None,
+            on_disconnect: DisconnectHook::default(),
+        }
+    }
+
+    /// Registers `hook` to run once when the connection ends, with the reason.
+    ///
+    /// The hook runs when [`next`](Self::next) observes the end of the connection, or
+    /// with [`DisconnectReason::Closed`] when the `WebSocket` is dropped first. It
+    /// replaces any hook registered earlier.
+    pub fn on_disconnect(&mut self, hook: impl FnOnce(DisconnectReason) + Send + 'static) {
+        self.on_disconnect.set(hook);
+    }
+
+    /// Returns `true` until a close frame has been sent or received.
+    pub fn is_open(&self) -> bool {
+        self.state == State::Open
+    }
+
+    /// Receives the next message.
+    ///
+    /// Returns `None` once the connection is closed: after the closing handshake, after
+    /// the peer disconnects, or after an error has been returned.
+    pub async fn next(&mut self) -> Option<Result<Message, WsError>> {
+        if self.state == State::Closed {
+            return None;
+        }
+        match self.read_message().await {
+            Ok(Some(message)) => Some(Ok(message)),
+            Ok(None) => {
+                let reason = match self.state {
+                    State::Open => DisconnectReason::Gone,
+                    _ => DisconnectReason::Closed,
+                };
+                self.state = State::Closed;
+                self.on_disconnect.fire(reason);
+                None
+            }
+            Err(e) => {
+                if let (State::Open, Some(code)) = (self.state, e.close_code()) {
+                    let close = CloseFrame::new(code, e.to_string());
+                    let _ = self
+                        .write_frame(OpCode::Close, &frame::close_payload(Some(&close)))
+                        .await;
+                }
+                let reason = match e {
+                    WsError::Io(_) => DisconnectReason::Gone,
+                    WsError::TimedOut(_) => DisconnectReason::TimedOut,
+                    _ => DisconnectReason::Failed,
+                };
+                self.finish(reason).await;
+                Some(Err(e))
+            }
+        }
+    }
+
+    /// Sends a message.
+    ///
+    /// Sending [`Message::Close`] starts the closing handshake; keep calling
+    /// [`next`](Self::next) to receive the peer's reply.
+    ///
+    /// # Errors
+    ///
+    /// [`WsError::Closed`] once a close frame has been sent or received, or
+    /// [`WsError::Io`] if the write fails.
+    pub async fn send(&mut self, message: impl Into<Message>) -> Result<(), WsError> {
+        if self.state != State::Open {
+            return Err(WsError::Closed);
+        }
+        match message.into() {
+            Message::Text(text) => self.write_frame(OpCode::Text, text.as_bytes()).await,
+            Message::Binary(data) => self.write_frame(OpCode::Binary, &data).await,
+            Message::Ping(data) => self.write_frame(OpCode::Ping, &data).await,
+            Message::Pong(data) => self.write_frame(OpCode::Pong, &data).await,
+            Message::Close(close) => {
+                self.state = State::CloseSent;
+                let payload = frame::close_payload(close.as_ref());
+                self.write_frame(OpCode::Close, &payload).await
+            }
+        }
+    }
+
+    /// Starts the closing handshake with `code` and `reason`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`send`](Self::send).
+    pub async fn close(&mut self, code: u16, reason: impl Into<String>) -> Result<(), WsError> {
+        self.send(Message::Close(Some(CloseFrame::new(code, reason))))
+            .await
+    }
+
+    async fn read_message(&mut self) -> Result<Option<Message>, WsError> {
+        loop {
+            let masked = self.role == Role::Server;
+            let Some(frame) =
+                frame::decode(&mut self.read_buf, masked, self.config.max_frame_size)?
+            else {
+                if self.fill_read_buf().await? == 0 {
+                    if self.read_buf.is_empty() && self.fragments.is_none() {
+                        return Ok(None);
+                    }
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+                continue;
+            };
+
+            match frame.opcode {
+                OpCode::Ping => {
+                    if self.state == State::Open {
+                        self.write_frame(OpCode::Pong, &frame.payload).await?;
+                    }
+                    return Ok(Some(Message::Ping(frame.payload)));
+                }
+                OpCode::Pong => return Ok(Some(Message::Pong(frame.payload))),
+                OpCode::Close => {
+                    let close = frame::parse_close(&frame.payload)?;
+                    if self.state == State::Open {
+                        // Echo the status code, as the RFC recommends.
+                        let echo = close.as_ref().map(|c| CloseFrame::new(c.code, ""));
+                        let _ = self
+                            .write_frame(OpCode::Close, &frame::close_payload(echo.as_ref()))
+                            .await;
+                    }
+                    self.finish(DisconnectReason::Closed).await;
+                    return Ok(Some(Message::Close(close)));
+                }
+                OpCode::Text | OpCode::Binary => {
+                    if self.fragments.is_some() {
+                        return Err(WsError::Protocol(
+                            "new message started before the fragmented one finished",
+                        ));
+                    }
+                    self.check_message_size(frame.payload.len())?;
+                    if frame.fin {
+                        return message(frame.opcode, frame.payload).map(Some);
+                    }
+                    self.fragments = Some((frame.opcode, frame.payload));
+                }
+                OpCode::Continuation => {
+                    let Some((_, so_far)) = &self.fragments else {
+                        return Err(WsError::Protocol(
+                            "continuation frame with nothing to continue",
+                        ));
+                    };
+                    self.check_message_size(so_far.len() + frame.payload.len())?;
+                    let (opcode, mut payload) = self.fragments.take().expect("checked above");
+                    payload.extend_from_slice(&frame.payload);
+                    if frame.fin {
+                        return message(opcode, payload).map(Some);
+                    }
+                    self.fragments = Some((opcode, payload));
+                }
+            }
+        }
+    }
+
+    // Reads more of the stream, pinging the peer or giving up on it as the heartbeat
+    // requires. Deadlines are derived from stored instants, so a read cancelled by the
+    // caller loses no heartbeat state.
+    async fn fill_read_buf(&mut self) -> Result<usize, WsError> {
+        loop {
+            let Some(heartbeat) = self.config.heartbeat else {
+                return Ok(self.io.read_buf(&mut self.read_buf).await?);
+            };
+            let deadline = match self.ping_sent {
+                Some(sent) => sent + heartbeat.timeout(),
+                None => self.last_seen + heartbeat.interval(),
+            };
+            match timeout_at(deadline, self.io.read_buf(&mut self.read_buf)).await {
+                Ok(read) => {
+                    self.last_seen = Instant::now();
+                    self.ping_sent = None;
+                    return Ok(read?);
+                }
+                Err(_) if self.ping_sent.is_some() => {
+                    return Err(WsError::TimedOut(heartbeat.timeout()));
+                }
+                Err(_) => {
+                    if self.state == State::Open {
+                        self.write_frame(OpCode::Ping, &[]).await?;
+                    }
+                    self.ping_sent = Some(Instant::now());
+                }
+            }
+        }
+    }
+
+    fn check_message_size(&self, size: usize) -> Result<(), WsError> {
+        if size > self.config.max_message_size {
+            return Err(WsError::MessageTooLarge {
+                limit: self.config.max_message_size,
+            });
+        }
+        Ok(())
+    }
+
+    async fn write_frame(&mut self, opcode: OpCode, payload: &[u8]) -> Result<(), WsError> {
+        let mask = match self.role {
+            Role::Server => None,
+            Role::Client => {
+                let key = random_bytes(4);
+                Some([key[0], key[1], key[2], key[3]])
+            }
+        };
+        let mut out = Vec::with_capacity(payload.len() + 14);
+        frame::encode(opcode, true, payload, mask, &mut out);
+        self.io.write_all(&out).await?;
+        self.io.flush().await?;
+        Ok(())
+    }
+
+    // Marks the connection closed and shuts down the write half; the peer may already be
+    // gone, so failure is not worth reporting.
+    async fn finish(&mut self, reason: DisconnectReason) {
+        self.state = State::Closed;
+        self.fragments = None;
+        let _ = self.io.shutdown().await;
+        self.on_disconnect.fire(reason);
+    }
+}
+
+impl Drop for WebSocket {
+    fn drop(&mut self) {
+        self.on_disconnect.fire(DisconnectReason::Closed);
+    }
+}
+
+impl fmt::Debug for WebSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSocket")
+            .field("role", &self.role)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+fn message(opcode: OpCode, payload: Vec<u8>) -> Result<Message, WsError> {
+    match opcode {
+        OpCode::Text => String::from_utf8(payload)
+            .map(Message::Text)
+            .map_err(|_| WsError::InvalidUtf8),
+        _ => Ok(Message::Binary(payload)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::{DuplexStream, duplex};
+
+    use super::*;
+
+    fn pair(config: WebSocketConfig) -> (WebSocket, WebSocket) {
+        let (server, client) = duplex(1 << 20);
+        (
+            WebSocket::from_stream(server, config),
+            WebSocket::with_role(client, Role::Client, WebSocketConfig::new()),
+        )
+    }
+
+    // Writes raw client frames, masked with a fixed key.
+    async fn write_raw(io: &mut DuplexStream, frames: &[(OpCode, bool, &[u8])]) {
+        let mut out = Vec::new();
+        for (opcode, fin, payload) in frames {
+            frame::encode(*opcode, *fin, payload, Some([9, 8, 7, 6]), &mut out);
+        }
+        io.write_all(&out).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn exchanges_messages() {
+        let (mut server, mut client) = pair(WebSocketConfig::new());
+        client.send("hello").await.unwrap();
+        client.send(vec![1, 2, 3]).await.unwrap();
+        assert_eq!(
+            server.next().await.unwrap().unwrap(),
+            Message::text("hello")
+        );
+        assert_eq!(
+            server.next().await.unwrap().unwrap(),
+            Message::binary([1, 2, 3])
+        );
+
+        server.send("x".repeat(70_000)).await.unwrap();
+        let echoed = client.next().await.unwrap().unwrap();
+        assert_eq!(echoed.as_text().map(str::len), Some(70_000));
+    }
+
+    #[tokio::test]
+    async fn reassembles_fragments_around_control_frames() {
+        let (server, mut client) = duplex(1024);
+        let mut server = WebSocket::from_stream(server, WebSocketConfig::new());
+        write_raw(
+            &mut client,
+            &[
+                (OpCode::Text, false, "hé".as_bytes()),
+                (OpCode::Ping, true, b"p"),
+                (OpCode::Continuation, false, &[0xC3]), // split UTF-8 sequence
+                (OpCode::Continuation, true, &[0xA9]),
+            ],
+        )
+        .await;
+        assert_eq!(
+            server.next().await.unwrap().unwrap(),
+            Message::Ping(b"p".to_vec())
+        );
+        assert_eq!(server.next().await.unwrap().unwrap(), Message::text("héé"));
+
+        // The ping was answered with an unmasked pong.
+        let mut pong = [0; 3];
+        client.read_exact(&mut pong).await.unwrap();
+        assert_eq!(pong, [0x8A, 0x01, b'p']);
+    }
+
+    #[tokio::test]
+    async fn closing_handshake() {
+        let (mut server, mut client) = pair(WebSocketConfig::new());
+        client.close(close_code::NORMAL, "bye").await.unwrap();
+        assert!(!client.is_open());
+        assert!(matches!(client.send("late").await, Err(WsError::Closed)));
+
+        assert_eq!(
+            server.next().await.unwrap().unwrap(),
+            Message::Close(Some(CloseFrame::new(close_code::NORMAL, "bye")))
+        );
+        assert!(server.next().await.is_none());
+
+        // The client receives the echo, then the end of the stream.
+        assert_eq!(
+            client.next().await.unwrap().unwrap(),
+            Message::Close(Some(CloseFrame::new(close_code::NORMAL, "")))
+        );
+        assert!(client.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_utf8_and_closes() {
+        let (server, mut client) = duplex(1024);
+        let mut server = WebSocket::from_stream(server, WebSocketConfig::new());
+        write_raw(&mut client, &[(OpCode::Text, true, &[0xFF])]).await;
+        assert!(matches!(
+            server.next().await,
+            Some(Err(WsError::InvalidUtf8))
+        ));
+        assert!(server.next().await.is_none());
+
+        let mut close = [0; 4];
+        client.read_exact(&mut close).await.unwrap();
+        assert_eq!(close[0], 0x88);
+        assert_eq!(
+            u16::from_be_bytes([close[2], close[3]]),
+            close_code::INVALID_PAYLOAD
+        );
+    }
+
+    #[tokio::test]
+    async fn enforces_message_size_across_fragments() {
+        let (server, mut client) = duplex(1024);
+        let config = WebSocketConfig::new()
+            .max_frame_size(8)
+            .max_message_size(10);
+        let mut server = WebSocket::from_stream(server, config);
+        write_raw(
+            &mut client,
+            &[
+                (OpCode::Binary, false, &[0; 6]),
+                (OpCode::Continuation, true, &[0; 6]),
+            ],
+        )
+        .await;
+        assert!(matches!(
+            server.next().await,
+            Some(Err(WsError::MessageTooLarge { limit: 10 }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_unexpected_continuations() {
+        let (server, mut client) = duplex(1024);
+        let mut server = WebSocket::from_stream(server, WebSocketConfig::new());
+        write_raw(&mut client, &[(OpCode::Continuation, true, b"x")]).await;
+        assert!(matches!(
+            server.next().await,
+            Some(Err(WsError::Protocol(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn heartbeat_reaps_silent_peers() {
+        let (server, mut client) = duplex(1024);
+        let heartbeat = Heartbeat::new(Duration::from_millis(20), Duration::from_millis(20));
+        let mut server =
+            WebSocket::from_stream(server, WebSocketConfig::new().heartbeat(heartbeat));
+        let (tx, rx) = std::sync::mpsc::channel();
+        server.on_disconnect(move |reason| tx.send(reason).unwrap());
+
+        assert!(matches!(
+            server.next().await,
+            Some(Err(WsError::TimedOut(_)))
+        ));
+        assert_eq!(rx.try_recv(), Ok(DisconnectReason::TimedOut));
+        let mut ping = Vec::new();
+        client.read_to_end(&mut ping).await.unwrap();
+        assert_eq!(ping, [0x89, 0x00]);
+    }
+
+    #[tokio::test]
+    async fn heartbeat_keeps_responsive_peers() {
+        let heartbeat = Heartbeat::new(Duration::from_millis(10), Duration::from_millis(20));
+        let (mut server, mut client) = pair(WebSocketConfig::new().heartbeat(heartbeat));
+        // The client answers pings while it waits for messages.
+        tokio::spawn(async move { while client.next().await.is_some() {} });
+
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(100);
+        while let Ok(next) = tokio::time::timeout_at(deadline, server.next()).await {
+            assert!(matches!(next, Some(Ok(Message::Pong(_)))), "{next:?}");
+        }
+        assert!(server.is_open());
+    }
+
+    #[tokio::test]
+    async fn reports_disconnects_once() {
+        let (mut server, client) = pair(WebSocketConfig::new());
+        let (tx, rx) = std::sync::mpsc::channel();
+        server.on_disconnect(move |reason| tx.send(reason).unwrap());
+        drop(client);
+
+        assert!(server.next().await.is_none());
+        drop(server);
+        assert_eq!(rx.iter().collect::<Vec<_>>(), [DisconnectReason::Gone]);
+    }
+}
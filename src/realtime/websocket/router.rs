@@ -0,0 +1,277 @@
+//! Typed JSON messages over a [`WebSocket`], dispatched by their `type` field.
+//!
+//! Clients send text messages shaped as an envelope:
+//!
+//! ```json
+//! {"type": "chat.send", "id": 7, "data": {"room": "lobby", "text": "hi"}}
+//! ```
+//!
+//! [`WsRouter`] decodes `data` into the type the handler registered for `type` declares
+//! and runs it. `id` is optional and opaque; it is copied into the reply so clients can
+//! match replies to requests. A handler's result is sent back as
+//! `{"type": "chat.send", "id": 7, "data": ...}` — unless it is `()` or otherwise encodes
+//! to `null`, in which case nothing is sent — and a failure as
+//!
+//! ```json
+//! {"type": "error", "id": 7, "error": {"code": "unknown_type", "message": "..."}}
+//! ```
+//!
+//! The router replies with the codes in [`message_error`] for envelopes it cannot
+//! dispatch; handlers choose their own with [`MessageError::new`].
+
+use std::{collections::HashMap, fmt, future::Future, pin::Pin, sync::Arc};
+
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use thiserror::Error;
+
+use super::{Message, WebSocket};
+use crate::realtime::ConnectionContext;
+
+/// Error codes the router itself replies with.
+pub mod message_error {
+    /// The message was binary, not JSON, or had no string `type`.
+    pub const INVALID_MESSAGE: &str = "invalid_message";
+    /// No handler is registered for the message's `type`.
+    pub const UNKNOWN_TYPE: &str = "unknown_type";
+    /// The message's `data` did not decode into the handler's input type.
+    pub const INVALID_DATA: &str = "invalid_data";
+    /// The handler's output could not be encoded as JSON.
+    pub const INTERNAL: &str = "internal";
+}
+
+/// A failure reported to the client as an `error` message.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{code}: {message}")]
+pub struct MessageError {
+    code: String,
+    message: String,
+}
+
+impl MessageError {
+    /// Creates an error with a machine-readable `code` and a human-readable `message`.
+    pub fn new(code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            code: code.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Returns the error code.
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// Returns the error message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+type BoxedHandler = Arc<
+    dyn Fn(
+            Arc<ConnectionContext>,
+            Value,
+        ) -> Pin<Box<dyn Future<Output = Result<Value, MessageError>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// Dispatches JSON envelopes received on a WebSocket to handlers by message type.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::sync::Arc;
+/// use rttp::Router;
+/// use rttp::realtime::{ConnectionContext, SharedState, WsRouter};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Add {
+///     a: i64,
+///     b: i64,
+/// }
+///
+/// let mut messages = WsRouter::new();
+/// messages.on("add", |_conn, add: Add| async move { Ok(add.a + add.b) });
+/// let messages = Arc::new(messages);
+///
+/// let mut router = Router::new();
+/// router.ws("/rpc", move |ctx, ws| {
+///     let messages = Arc::clone(&messages);
+///     async move {
+///         let conn = ConnectionContext::from_request(ctx, SharedState::new());
+///         messages.serve(ws, conn).await;
+///     }
+/// });
+/// ```
+#[derive(Clone, Default)]
+pub struct WsRouter {
+    handlers: HashMap<String, BoxedHandler>,
+}
+
+impl WsRouter {
+    /// Creates a router with no handlers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `handler` for messages whose `type` is `kind`, replacing any earlier one.
+    ///
+    /// The handler receives the connection and the envelope's `data` decoded as `T`; a
+    /// missing `data` decodes as `null`, which suits `()` and `Option` inputs.
+    pub fn on<T, R, F, Fut>(&mut self, kind: &str, handler: F)
+    where
+        T: DeserializeOwned + Send + 'static,
+        R: Serialize,
+        F: Fn(Arc<ConnectionContext>, T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, MessageError>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let boxed: BoxedHandler = Arc::new(move |conn, data| {
+            let handler = Arc::clone(&handler);
+            Box::pin(async move {
+                let input = serde_json::from_value(data)
+                    .map_err(|e| MessageError::new(message_error::INVALID_DATA, e.to_string()))?;
+                let output = handler(conn, input).await?;
+                serde_json::to_value(output)
+                    .map_err(|e| MessageError::new(message_error::INTERNAL, e.to_string()))
+            })
+        });
+        self.handlers.insert(kind.to_owned(), boxed);
+    }
+
+    /// Handles one text message, returning the reply to send, if any.
+    pub async fn dispatch(&self, conn: &Arc<ConnectionContext>, text: &str) -> Option<String> {
+        let envelope: Value = match serde_json::from_str(text) {
+            Ok(envelope @ Value::Object(_)) => envelope,
+            _ => return Some(invalid_message(Value::Null)),
+        };
+        let id = envelope.get("id").cloned().unwrap_or(Value::Null);
+        let Some(kind) = envelope.get("type").and_then(Value::as_str) else {
+            return Some(invalid_message(id));
+        };
+        let Some(handler) = self.handlers.get(kind) else {
+            let error = MessageError::new(
+                message_error::UNKNOWN_TYPE,
+                format!("no handler for message type `{kind}`"),
+            );
+            return Some(error_reply(id, &error));
+        };
+
+        let data = envelope.get("data").cloned().unwrap_or(Value::Null);
+        match handler(Arc::clone(conn), data).await {
+            Ok(Value::Null) => None,
+            Ok(data) => Some(with_id(json!({ "type": kind, "data": data }), id).to_string()),
+            Err(error) => Some(error_reply(id, &error)),
+        }
+    }
+
+    /// Reads messages from `ws` until it closes, dispatching text messages and sending the
+    /// replies. Binary messages are answered with an `invalid_message` error.
+    pub async fn serve(&self, mut ws: WebSocket, conn: ConnectionContext) {
+        let conn = Arc::new(conn);
+        while let Some(Ok(message)) = ws.next().await {
+            let reply = match message {
+                Message::Text(text) => self.dispatch(&conn, &text).await,
+                Message::Binary(_) => Some(invalid_message(Value::Null)),
+                _ => None,
+            };
+            let Some(reply) = reply else { continue };
+            if ws.send(reply).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for WsRouter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut kinds: Vec<_> = self.handlers.keys().collect();
+        kinds.sort();
+        f.debug_struct("WsRouter").field("types", &kinds).finish()
+    }
+}
+
+fn invalid_message(id: Value) -> String {
+    let error = MessageError::new(
+        message_error::INVALID_MESSAGE,
+        "expected a JSON object with a string `type`",
+    );
+    error_reply(id, &error)
+}
+
+fn error_reply(id: Value, error: &MessageError) -> String {
+    let reply = json!({
+        "type": "error",
+        "error": { "code": error.code, "message": error.message },
+    });
+    with_id(reply, id).to_string()
+}
+
+fn with_id(mut reply: Value, id: Value) -> Value {
+    if !id.is_null() {
+        reply["id"] = id;
+    }
+    reply
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::realtime::SharedState;
+
+    #[derive(Deserialize)]
+    struct Add {
+        a: i64,
+        b: i64,
+    }
+
+    fn router() -> WsRouter {
+        let mut router = WsRouter::new();
+        router.on("add", |_, add: Add| async move { Ok(add.a + add.b) });
+        router.on("ping", |_, (): ()| async { Ok(()) });
+        router.on("fail", |_, (): ()| async {
+            Err::<(), _>(MessageError::new("forbidden", "not allowed"))
+        });
+        router
+    }
+
+    async fn reply(text: &str) -> Option<Value> {
+        let conn = Arc::new(ConnectionContext::new(SharedState::new()));
+        let reply = router().dispatch(&conn, text).await?;
+        Some(serde_json::from_str(&reply).unwrap())
+    }
+
+    #[tokio::test]
+    async fn dispatches_by_type() {
+        assert_eq!(
+            reply(r#"{"type":"add","id":"r1","data":{"a":2,"b":3}}"#).await,
+            Some(json!({"type": "add", "id": "r1", "data": 5}))
+        );
+        assert_eq!(reply(r#"{"type":"ping"}"#).await, None);
+    }
+
+    #[tokio::test]
+    async fn replies_with_errors() {
+        let code = |reply: Option<Value>| reply.unwrap()["error"]["code"].clone();
+        assert_eq!(code(reply("not json").await), "invalid_message");
+        assert_eq!(code(reply(r#"{"id":1}"#).await), "invalid_message");
+        assert_eq!(code(reply(r#"{"type":"nope"}"#).await), "unknown_type");
+        assert_eq!(
+            code(reply(r#"{"type":"add","data":{"a":"x"}}"#).await),
+            "invalid_data"
+        );
+        assert_eq!(
+            reply(r#"{"type":"fail","id":9}"#).await,
+            Some(json!({
+                "type": "error",
+                "id": 9,
+                "error": {"code": "forbidden", "message": "not allowed"},
+            }))
+        );
+    }
+}
@@ -0,0 +1,640 @@
+//! Minimal async Redis client speaking RESP2.
+//!
+//! Backends that keep state in Redis (sessions, caches, pub/sub) share this client instead
+//! of each pulling in a full driver. It supports exactly what those backends need:
+//! arbitrary commands via [`RedisClient::command`] plus typed helpers for the common
+//! key/value, set, list, and sorted-set operations, and pub/sub through [`RedisClient::publish`] and
+//! [`RedisClient::subscribe`].
+//!
+//! The client owns a single connection that is opened lazily on first use and re-opened
+//! after any I/O or protocol error, so a Redis restart does not require rebuilding the
+//! application. Commands are serialized through an async mutex; for higher throughput,
+//! create several clients.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use rttp::redis::RedisClient;
+//!
+//! # async fn example() -> Result<(), rttp::redis::RedisError> {
+//! let redis = RedisClient::new("127.0.0.1:6379");
+//! redis.set("greeting", b"hello", Some(Duration::from_secs(60))).await?;
+//! assert_eq!(redis.get("greeting").await?.as_deref(), Some(&b"hello"[..]));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{pin::Pin, time::Duration};
+
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufStream},
+    net::TcpStream,
+    sync::Mutex,
+};
+
+pub mod pubsub;
+#[cfg(test)]
+pub(crate) mod testing;
+
+pub use pubsub::{Message, Subscription};
+
+/// Errors produced by [`RedisClient`].
+#[derive(Debug, Error)]
+pub enum RedisError {
+    #[error("redis I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("redis protocol error: {0}")]
+    Protocol(String),
+
+    #[error("redis server error: {0}")]
+    Server(String),
+
+    #[error("unexpected redis reply: {0:?}")]
+    UnexpectedReply(Value),
+}
+
+/// A RESP2 reply value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// `+OK` style status reply.
+    Simple(String),
+    /// `-ERR …` error reply.
+    Error(String),
+    /// `:42` integer reply.
+    Integer(i64),
+    /// `$n` bulk string; `None` for the null bulk string `$-1`.
+    Bulk(Option<Vec<u8>>),
+    /// `*n` array; `None` for the null array `*-1`.
+    Array(Option<Vec<Value>>),
+}
+
+/// When [`RedisClient::pexpire_if`] replaces a key's expiry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpireIf {
+    /// `NX` — only if the key has no expiry.
+    Unset,
+    /// `GT` — only if the new expiry is later than the current one. A key without an
+    /// expiry never expires, so it is never extended.
+    Later,
+}
+
+/// A lazily connected, self-healing Redis connection.
+pub struct RedisClient {
+    addr: String,
+    conn: Mutex<Option<BufStream<TcpStream>>>,
+}
+
+impl RedisClient {
+    /// Creates a client for the server at `addr` (e.g. `"127.0.0.1:6379"`).
+    ///
+    /// No connection is made until the first command.
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            conn: Mutex::new(None),
+        }
+    }
+
+    /// Returns the server address this client connects to.
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// Sends one command and returns its reply.
+    ///
+    /// Server error replies are returned as [`RedisError::Server`]. After an I/O or
+    /// protocol error, or if the returned future is dropped before the reply is read,
+    /// the connection is discarded and re-opened on the next call.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn command(&self, args: &[&[u8]]) -> Result<Value, RedisError> {
+        let mut guard = self.conn.lock().await;
+        // The connection is held outside the slot until the reply is read, so a
+        // cancelled command drops it instead of leaving its reply for the next caller.
+        let mut conn = match guard.take() {
+            Some(conn) => conn,
+            None => BufStream::new(TcpStream::connect(&self.addr).await?),
+        };
+
+        write_command(&mut conn, args).await?;
+        let value = read_value(&mut conn).await?;
+        *guard = Some(conn);
+        match value {
+            Value::Error(message) => Err(RedisError::Server(message)),
+            value => Ok(value),
+        }
+    }
+
+    /// `GET key` — returns the value, or `None` if the key does not exist.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, RedisError> {
+        match self.command(&[b"GET", key.as_bytes()]).await? {
+            Value::Bulk(value) => Ok(value),
+            other => Err(RedisError::UnexpectedReply(other)),
+        }
+    }
+
+    /// `SET key value [PX ttl]` — stores a value with an optional expiry.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn set(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<Duration>,
+    ) -> Result<(), RedisError> {
+        let reply = match ttl {
+            Some(ttl) => {
+                let millis = ttl.as_millis().max(1).to_string();
+                self.command(&[b"SET", key.as_bytes(), value, b"PX", millis.as_bytes()])
+                    .await?
+            }
+            None => self.command(&[b"SET", key.as_bytes(), value]).await?,
+        };
+        match reply {
+            Value::Simple(_) => Ok(()),
+            other => Err(RedisError::UnexpectedReply(other)),
+        }
+    }
+
+    /// `DEL key…` — deletes keys, returning how many existed.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn del(&self, keys: &[&str]) -> Result<i64, RedisError> {
+        let mut args: Vec<&[u8]> = vec![b"DEL"];
+        args.extend(keys.iter().map(|k| k.as_bytes()));
+        match self.command(&args).await? {
+            Value::Integer(n) => Ok(n),
+            other => Err(RedisError::UnexpectedReply(other)),
+        }
+    }
+
+    /// `INCRBY key delta` — adds to the integer at `key` (zero if absent), returning the
+    /// new value.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`]; Redis replies with an error if the value is not an integer.
+    pub async fn incr_by(&self, key: &str, delta: i64) -> Result<i64, RedisError> {
+        let delta = delta.to_string();
+        match self
+            .command(&[b"INCRBY", key.as_bytes(), delta.as_bytes()])
+            .await?
+        {
+            Value::Integer(n) => Ok(n),
+            other => Err(RedisError::UnexpectedReply(other)),
+        }
+    }
+
+    /// `PEXPIRE key ttl` — sets the expiry of an existing key, returning whether it
+    /// existed.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn pexpire(&self, key: &str, ttl: Duration) -> Result<bool, RedisError> {
+        let millis = ttl.as_millis().max(1).to_string();
+        match self
+            .command(&[b"PEXPIRE", key.as_bytes(), millis.as_bytes()])
+            .await?
+        {
+            Value::Integer(n) => Ok(n == 1),
+            other => Err(RedisError::UnexpectedReply(other)),
+        }
+    }
+
+    /// `PEXPIRE key ttl NX|GT` — sets the expiry of an existing key when `condition`
+    /// holds, returning whether it was set.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`]; the options need Redis 7.
+    pub async fn pexpire_if(
+        &self,
+        key: &str,
+        ttl: Duration,
+        condition: ExpireIf,
+    ) -> Result<bool, RedisError> {
+        let millis = ttl.as_millis().max(1).to_string();
+        let condition: &[u8] = match condition {
+            ExpireIf::Unset => b"NX",
+            ExpireIf::Later => b"GT",
+        };
+        match self
+            .command(&[b"PEXPIRE", key.as_bytes(), millis.as_bytes(), condition])
+            .await?
+        {
+            Value::Integer(n) => Ok(n == 1),
+            other => Err(RedisError::UnexpectedReply(other)),
+        }
+    }
+
+    /// `PTTL key` — returns the remaining time to live in milliseconds, `-1` if the key
+    /// has no expiry, or `-2` if it does not exist.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn pttl(&self, key: &str) -> Result<i64, RedisError> {
+        match self.command(&[b"PTTL", key.as_bytes()]).await? {
+            Value::Integer(n) => Ok(n),
+            other => Err(RedisError::UnexpectedReply(other)),
+        }
+    }
+
+    /// `PERSIST key` — removes the expiry of a key, returning whether it had one.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn persist(&self, key: &str) -> Result<bool, RedisError> {
+        match self.command(&[b"PERSIST", key.as_bytes()]).await? {
+            Value::Integer(n) => Ok(n == 1),
+            other => Err(RedisError::UnexpectedReply(other)),
+        }
+    }
+
+    /// `EXISTS key` — returns whether the key exists.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn exists(&self, key: &str) -> Result<bool, RedisError> {
+        match self.command(&[b"EXISTS", key.as_bytes()]).await? {
+            Value::Integer(n) => Ok(n > 0),
+            other => Err(RedisError::UnexpectedReply(other)),
+        }
+    }
+
+    /// `SADD key member…` — adds members to a set, returning how many were new.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn sadd(&self, key: &str, members: &[&str]) -> Result<i64, RedisError> {
+        self.set_command(b"SADD", key, members).await
+    }
+
+    /// `SREM key member…` — removes members from a set, returning how many existed.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn srem(&self, key: &str, members: &[&str]) -> Result<i64, RedisError> {
+        self.set_command(b"SREM", key, members).await
+    }
+
+    /// `SMEMBERS key` — returns every member of a set (empty if the key does not exist).
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn smembers(&self, key: &str) -> Result<Vec<Vec<u8>>, RedisError> {
+        bulk_array(self.command(&[b"SMEMBERS", key.as_bytes()]).await?)
+    }
+
+    /// `RPUSH key value` — appends to a list, returning its new length.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn rpush(&self, key: &str, value: &[u8]) -> Result<i64, RedisError> {
+        integer(self.command(&[b"RPUSH", key.as_bytes(), value]).await?)
+    }
+
+    /// `LPOP key` — removes and returns the first element of a list, or `None` if the
+    /// list is empty.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn lpop(&self, key: &str) -> Result<Option<Vec<u8>>, RedisError> {
+        match self.command(&[b"LPOP", key.as_bytes()]).await? {
+            Value::Bulk(value) => Ok(value),
+            other => Err(RedisError::UnexpectedReply(other)),
+        }
+    }
+
+    /// `LLEN key` — returns the length of a list (0 if the key does not exist).
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn llen(&self, key: &str) -> Result<i64, RedisError> {
+        integer(self.command(&[b"LLEN", key.as_bytes()]).await?)
+    }
+
+    /// `ZADD key score member` — adds a member to a sorted set or updates its score,
+    /// returning whether it was new.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn zadd(&self, key: &str, score: i64, member: &[u8]) -> Result<bool, RedisError> {
+        let score = score.to_string();
+        let args: [&[u8]; 4] = [b"ZADD", key.as_bytes(), score.as_bytes(), member];
+        Ok(integer(self.command(&args).await?)? == 1)
+    }
+
+    /// `ZRANGEBYSCORE key -inf max LIMIT 0 count` — returns up to `count` members with a
+    /// score of at most `max`, lowest first.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn zrange_by_score(
+        &self,
+        key: &str,
+        max: i64,
+        count: usize,
+    ) -> Result<Vec<Vec<u8>>, RedisError> {
+        let (max, count) = (max.to_string(), count.to_string());
+        let args: [&[u8]; 7] = [
+            b"ZRANGEBYSCORE",
+            key.as_bytes(),
+            b"-inf",
+            max.as_bytes(),
+            b"LIMIT",
+            b"0",
+            count.as_bytes(),
+        ];
+        bulk_array(self.command(&args).await?)
+    }
+
+    /// `ZREM key member` — removes a member from a sorted set, returning whether it
+    /// existed.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn zrem(&self, key: &str, member: &[u8]) -> Result<bool, RedisError> {
+        Ok(integer(self.command(&[b"ZREM", key.as_bytes(), member]).await?)? == 1)
+    }
+
+    /// `ZCARD key` — returns the number of members in a sorted set (0 if the key does
+    /// not exist).
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn zcard(&self, key: &str) -> Result<i64, RedisError> {
+        integer(self.command(&[b"ZCARD", key.as_bytes()]).await?)
+    }
+
+    async fn set_command(
+        &self,
+        command: &[u8],
+        key: &str,
+        members: &[&str],
+    ) -> Result<i64, RedisError> {
+        let mut args: Vec<&[u8]> = vec![command, key.as_bytes()];
+        args.extend(members.iter().map(|m| m.as_bytes()));
+        integer(self.command(&args).await?)
+    }
+}
+
+fn integer(value: Value) -> Result<i64, RedisError> {
+    match value {
+        Value::Integer(n) => Ok(n),
+        other => Err(RedisError::UnexpectedReply(other)),
+    }
+}
+
+fn bulk_array(value: Value) -> Result<Vec<Vec<u8>>, RedisError> {
+    match value {
+        Value::Array(Some(items)) => items
+            .into_iter()
+            .map(|item| match item {
+                Value::Bulk(Some(member)) => Ok(member),
+                other => Err(RedisError::UnexpectedReply(other)),
+            })
+            .collect(),
+        Value::Array(None) => Ok(Vec::new()),
+        other => Err(RedisError::UnexpectedReply(other)),
+    }
+}
+
+/// Writes `args` as a RESP array of bulk strings.
+pub(crate) async fn write_command<W>(writer: &mut W, args: &[&[u8]]) -> Result<(), RedisError>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = Vec::with_capacity(16 + args.iter().map(|a| a.len() + 16).sum::<usize>());
+    buf.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+    for arg in args {
+        buf.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        buf.extend_from_slice(arg);
+        buf.extend_from_slice(b"\r\n");
+    }
+    writer.write_all(&buf).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Reads one RESP value. Arrays recurse, so the future is boxed.
+pub(crate) fn read_value<'a, R>(
+    reader: &'a mut R,
+) -> Pin<Box<dyn Future<Output = Result<Value, RedisError>> + Send + 'a>>
+where
+    R: AsyncBufRead + Unpin + Send,
+{
+    Box::pin(async move {
+        let line = read_line(reader).await?;
+        let (kind, rest) = line.split_at(1);
+        match kind {
+            "+" => Ok(Value::Simple(rest.to_owned())),
+            "-" => Ok(Value::Error(rest.to_owned())),
+            ":" => Ok(Value::Integer(parse_int(rest)?)),
+            "$" => {
+                let len = parse_int(rest)?;
+                if len < 0 {
+                    return Ok(Value::Bulk(None));
+                }
+                let mut data = vec![0u8; len as usize + 2];
+                reader.read_exact(&mut data).await?;
+                if !data.ends_with(b"\r\n") {
+                    return Err(RedisError::Protocol("bulk string missing CRLF".into()));
+                }
+                data.truncate(len as usize);
+                Ok(Value::Bulk(Some(data)))
+            }
+            "*" => {
+                let len = parse_int(rest)?;
+                if len < 0 {
+                    return Ok(Value::Array(None));
+                }
+                let mut items = Vec::with_capacity(len as usize);
+                for _ in 0..len {
+                    items.push(read_value(reader).await?);
+                }
+                Ok(Value::Array(Some(items)))
+            }
+            other => Err(RedisError::Protocol(format!("unknown type byte {other:?}"))),
+        }
+    })
+}
+
+// Reads a CRLF-terminated line, returning it without the terminator.
+async fn read_line<R>(reader: &mut R) -> Result<String, RedisError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Err(RedisError::Protocol("connection closed".into()));
+    }
+    let line = line
+        .strip_suffix("\r\n")
+        .ok_or_else(|| RedisError::Protocol("line missing CRLF".into()))?;
+    if line.is_empty() {
+        return Err(RedisError::Protocol("empty line".into()));
+    }
+    Ok(line.to_owned())
+}
+
+fn parse_int(s: &str) -> Result<i64, RedisError> {
+    s.parse()
+        .map_err(|_| RedisError::Protocol(format!("invalid integer {s:?}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::BufReader;
+
+    use super::*;
+
+    async fn parse(bytes: &[u8]) -> Result<Value, RedisError> {
+        let mut reader = BufReader::new(bytes);
+        read_value(&mut reader).await
+    }
+
+    #[tokio::test]
+    async fn write_command_encodes_bulk_array() {
+        let mut out = Vec::new();
+        write_command(&mut out, &[b"SET", b"k", b"v"])
+            .await
+            .unwrap();
+        assert_eq!(out, b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n");
+    }
+
+    #[tokio::test]
+    async fn read_scalar_values() {
+        assert_eq!(parse(b"+OK\r\n").await.unwrap(), Value::Simple("OK".into()));
+        assert_eq!(
+            parse(b"-ERR x\r\n").await.unwrap(),
+            Value::Error("ERR x".into())
+        );
+        assert_eq!(parse(b":-7\r\n").await.unwrap(), Value::Integer(-7));
+        assert_eq!(parse(b"$-1\r\n").await.unwrap(), Value::Bulk(None));
+        assert_eq!(
+            parse(b"$5\r\nhe\r\no\r\n").await.unwrap(),
+            Value::Bulk(Some(b"he\r\no".to_vec()))
+        );
+    }
+
+    #[tokio::test]
+    async fn read_nested_array() {
+        let value = parse(b"*2\r\n:1\r\n*1\r\n$1\r\na\r\n").await.unwrap();
+        assert_eq!(
+            value,
+            Value::Array(Some(vec![
+                Value::Integer(1),
+                Value::Array(Some(vec![Value::Bulk(Some(b"a".to_vec()))])),
+            ]))
+        );
+    }
+
+    #[tokio::test]
+    async fn read_rejects_garbage() {
+        assert!(matches!(
+            parse(b"?x\r\n").await,
+            Err(RedisError::Protocol(_))
+        ));
+        assert!(matches!(
+            parse(b":abc\r\n").await,
+            Err(RedisError::Protocol(_))
+        ));
+        assert!(matches!(parse(b"").await, Err(RedisError::Protocol(_))));
+    }
+
+    #[tokio::test]
+    async fn client_round_trip_against_fake_server() {
+        let server = testing::FakeRedis::start().await;
+        let client = RedisClient::new(server.addr());
+
+        assert_eq!(client.get("missing").await.unwrap(), None);
+        client.set("k", b"v", None).await.unwrap();
+        assert_eq!(client.get("k").await.unwrap(), Some(b"v".to_vec()));
+        assert_eq!(client.del(&["k", "missing"]).await.unwrap(), 1);
+        assert_eq!(client.get("k").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn client_surfaces_server_errors() {
+        let server = testing::FakeRedis::start().await;
+        let client = RedisClient::new(server.addr());
+        assert!(matches!(
+            client.command(&[b"NOPE"]).await,
+            Err(RedisError::Server(_))
+        ));
+        // The connection stays usable after an error reply.
+        client
+            .set("k", b"v", Some(Duration::from_secs(5)))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancelled_command_does_not_leak_its_reply() {
+        // Answers `SLOW` after a delay and anything else at once, on every connection.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = [0; 256];
+                    while let Ok(n @ 1..) = socket.read(&mut buf).await {
+                        let reply: &[u8] = if buf[..n].ends_with(b"SLOW\r\n") {
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                            b"+slow\r\n"
+                        } else {
+                            b"+fast\r\n"
+                        };
+                        if socket.write_all(reply).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+
+        let client = RedisClient::new(addr.to_string());
+        let slow = tokio::time::timeout(Duration::from_millis(10), client.command(&[b"SLOW"]));
+        assert!(slow.await.is_err());
+        assert_eq!(
+            client.command(&[b"FAST"]).await.unwrap(),
+            Value::Simple("fast".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn client_reports_connect_failure() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let client = RedisClient::new(addr.to_string());
+        assert!(matches!(client.get("k").await, Err(RedisError::Io(_))));
+    }
+}
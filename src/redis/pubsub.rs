@@ -0,0 +1,138 @@
+//! Redis pub/sub subscriptions.
+//!
+//! A subscribed connection can only receive pushed messages, so each [`Subscription`]
+//! owns a dedicated connection separate from the [`RedisClient`]'s command connection.
+//! Publishing goes through the ordinary client with [`RedisClient::publish`].
+
+use tokio::{io::BufStream, net::TcpStream};
+
+use super::{RedisClient, RedisError, Value, read_value, write_command};
+
+/// A message received on a subscribed channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// The channel the message was published to.
+    pub channel: String,
+    /// The published payload.
+    pub payload: Vec<u8>,
+}
+
+/// A connection subscribed to one or more channels.
+///
+/// Unlike [`RedisClient`], a subscription does not reconnect by itself: after an error
+/// the caller should subscribe again, keeping in mind that messages published in
+/// between were missed.
+pub struct Subscription {
+    conn: BufStream<TcpStream>,
+}
+
+impl RedisClient {
+    /// `PUBLISH channel payload` — returns how many subscribers received the message.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn publish(&self, channel: &str, payload: &[u8]) -> Result<i64, RedisError> {
+        match self
+            .command(&[b"PUBLISH", channel.as_bytes(), payload])
+            .await?
+        {
+            Value::Integer(n) => Ok(n),
+            other => Err(RedisError::UnexpectedReply(other)),
+        }
+    }
+
+    /// `SUBSCRIBE channel…` on a new connection to this client's server.
+    ///
+    /// Returns once the server has confirmed every channel, so messages published after
+    /// this resolves are guaranteed to be delivered.
+    ///
+    /// # Errors
+    ///
+    /// See [`RedisError`].
+    pub async fn subscribe(&self, channels: &[&str]) -> Result<Subscription, RedisError> {
+        let mut conn = BufStream::new(TcpStream::connect(self.addr()).await?);
+        let mut args: Vec<&[u8]> = vec![b"SUBSCRIBE"];
+        args.extend(channels.iter().map(|c| c.as_bytes()));
+        write_command(&mut conn, &args).await?;
+
+        for _ in channels {
+            match read_value(&mut conn).await? {
+                Value::Array(Some(items)) if push_kind(&items) == Some(b"subscribe") => {}
+                Value::Error(message) => return Err(RedisError::Server(message)),
+                other => return Err(RedisError::UnexpectedReply(other)),
+            }
+        }
+        Ok(Subscription { conn })
+    }
+}
+
+impl Subscription {
+    /// Waits for the next message on any subscribed channel.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RedisError::Protocol`] once the server closes the connection, and any
+    /// other [`RedisError`] on I/O failures or malformed pushes.
+    pub async fn next_message(&mut self) -> Result<Message, RedisError> {
+        loop {
+            let Value::Array(Some(items)) = read_value(&mut self.conn).await? else {
+                return Err(RedisError::Protocol("expected a pushed array".into()));
+            };
+            match <[Value; 3]>::try_from(items) {
+                Ok(
+                    [
+                        Value::Bulk(Some(kind)),
+                        Value::Bulk(Some(channel)),
+                        Value::Bulk(Some(payload)),
+                    ],
+                ) if kind == b"message" => {
+                    return Ok(Message {
+                        channel: String::from_utf8_lossy(&channel).into_owned(),
+                        payload,
+                    });
+                }
+                // Confirmations and other pushes carry no message.
+                _ => continue,
+            }
+        }
+    }
+}
+
+// The leading bulk string of a pushed array, e.g. `subscribe` or `message`.
+fn push_kind(items: &[Value]) -> Option<&[u8]> {
+    match items.first() {
+        Some(Value::Bulk(Some(kind))) => Some(kind),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redis::testing::FakeRedis;
+
+    #[tokio::test]
+    async fn publish_reaches_subscribers() {
+        let server = FakeRedis::start().await;
+        let client = RedisClient::new(server.addr());
+
+        assert_eq!(client.publish("news", b"nobody").await.unwrap(), 0);
+        let mut a = client.subscribe(&["news", "other"]).await.unwrap();
+        let mut b = client.subscribe(&["news"]).await.unwrap();
+
+        assert_eq!(client.publish("news", b"hello").await.unwrap(), 2);
+        assert_eq!(client.publish("other", b"only a").await.unwrap(), 1);
+
+        let expected = |channel: &str, payload: &[u8]| Message {
+            channel: channel.to_owned(),
+            payload: payload.to_vec(),
+        };
+        assert_eq!(a.next_message().await.unwrap(), expected("news", b"hello"));
+        assert_eq!(
+            a.next_message().await.unwrap(),
+            expected("other", b"only a")
+        );
+        assert_eq!(b.next_message().await.unwrap(), expected("news", b"hello"));
+    }
+}
@@ -0,0 +1,364 @@
+//! In-process fake Redis server for unit tests.
+//!
+//! Understands `PING`, `GET`, `SET` (with optional `PX`/`EX`), `DEL`, `EXISTS`, `INCRBY`,
+//! `PEXPIRE` (with optional `NX`/`GT`), `PTTL`, `PERSIST`, the set commands `SADD`,
+//! `SREM`, and `SMEMBERS`, the list commands `RPUSH`, `LPOP`, and `LLEN`, the sorted-set
+//! commands `ZADD`, `ZRANGEBYSCORE` (with `LIMIT`), `ZREM`, and `ZCARD`, and
+//! `PUBLISH`/`SUBSCRIBE`; every other command gets an error reply. Lists and sorted sets
+//! never expire. A subscribed connection only receives
+//! messages from then on. Good enough to exercise the client and the Redis-backed stores
+//! without a real server.
+
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    io::{AsyncWriteExt, BufStream},
+    net::TcpListener,
+    sync::mpsc,
+};
+
+use super::{Value, read_value};
+
+type Store = Arc<Mutex<Data>>;
+
+// A value and when it expires, if ever.
+type Expiring<T> = (T, Option<Instant>);
+
+#[derive(Default)]
+struct Data {
+    strings: HashMap<Vec<u8>, Expiring<Vec<u8>>>,
+    sets: HashMap<Vec<u8>, Expiring<BTreeSet<Vec<u8>>>>,
+    lists: HashMap<Vec<u8>, VecDeque<Vec<u8>>>,
+    sorted_sets: HashMap<Vec<u8>, HashMap<Vec<u8>, i64>>,
+    channels: HashMap<Vec<u8>, Vec<mpsc::UnboundedSender<Vec<u8>>>>,
+}
+
+pub(crate) struct FakeRedis {
+    addr: SocketAddr,
+}
+
+impl FakeRedis {
+    pub(crate) async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let store: Store = Arc::default();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let store = Arc::clone(&store);
+                tokio::spawn(async move {
+                    let mut conn = BufStream::new(stream);
+                    while let Ok(Value::Array(Some(args))) = read_value(&mut conn).await {
+                        let args: Vec<Vec<u8>> = args
+                            .into_iter()
+                            .filter_map(|v| match v {
+                                Value::Bulk(Some(b)) => Some(b),
+                                _ => None,
+                            })
+                            .collect();
+                        if args.len() > 1 && args[0].eq_ignore_ascii_case(b"SUBSCRIBE") {
+                            subscribed(conn, &store, &args[1..]).await;
+                            return;
+                        }
+                        let reply = execute(&store, &args);
+                        if conn.write_all(&reply).await.is_err() || conn.flush().await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        Self { addr }
+    }
+
+    pub(crate) fn addr(&self) -> String {
+        self.addr.to_string()
+    }
+}
+
+// Serves a connection in subscribed mode until the client goes away.
+async fn subscribed(
+    mut conn: BufStream<tokio::net::TcpStream>,
+    store: &Store,
+    channels: &[Vec<u8>],
+) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut confirmations = Vec::new();
+    {
+        let mut store = store.lock().unwrap();
+        for (n, channel) in channels.iter().enumerate() {
+            store
+                .channels
+                .entry(channel.clone())
+                .or_default()
+                .push(tx.clone());
+            confirmations.extend_from_slice(b"*3\r\n");
+            confirmations.extend_from_slice(&bulk(b"subscribe"));
+            confirmations.extend_from_slice(&bulk(channel));
+            confirmations.extend_from_slice(format!(":{}\r\n", n + 1).as_bytes());
+        }
+    }
+    drop(tx);
+    if conn.write_all(&confirmations).await.is_err() || conn.flush().await.is_err() {
+        return;
+    }
+    while let Some(frame) = rx.recv().await {
+        if conn.write_all(&frame).await.is_err() || conn.flush().await.is_err() {
+            break;
+        }
+    }
+}
+
+fn execute(store: &Store, args: &[Vec<u8>]) -> Vec<u8> {
+    let mut store = store.lock().unwrap();
+    let now = Instant::now();
+    store
+        .strings
+        .retain(|_, (_, expiry)| expiry.is_none_or(|e| e > now));
+    store
+        .sets
+        .retain(|_, (_, expiry)| expiry.is_none_or(|e| e > now));
+
+    let command = args
+        .first()
+        .map(|c| String::from_utf8_lossy(c).to_ascii_uppercase())
+        .unwrap_or_default();
+
+    match (command.as_str(), args.len()) {
+        ("PING", 1) => b"+PONG\r\n".to_vec(),
+        ("GET", 2) => match store.strings.get(&args[1]) {
+            Some((value, _)) => bulk(value),
+            None => b"$-1\r\n".to_vec(),
+        },
+        ("SET", 3 | 5) => {
+            let expiry = if args.len() == 5 {
+                let n: u64 = String::from_utf8_lossy(&args[4]).parse().unwrap_or(0);
+                match String::from_utf8_lossy(&args[3])
+                    .to_ascii_uppercase()
+                    .as_str()
+                {
+                    "PX" => Some(now + Duration::from_millis(n)),
+                    "EX" => Some(now + Duration::from_secs(n)),
+                    _ => return b"-ERR syntax error\r\n".to_vec(),
+                }
+            } else {
+                None
+            };
+            store.sets.remove(&args[1]);
+            store
+                .strings
+                .insert(args[1].clone(), (args[2].clone(), expiry));
+            b"+OK\r\n".to_vec()
+        }
+        ("DEL", n) if n > 1 => {
+            let removed = args[1..]
+                .iter()
+                .filter(|k| {
+                    store.strings.remove(*k).is_some()
+                        | store.sets.remove(*k).is_some()
+                        | store.lists.remove(*k).is_some()
+                        | store.sorted_sets.remove(*k).is_some()
+                })
+                .count();
+            format!(":{removed}\r\n").into_bytes()
+        }
+        ("INCRBY", 3) => {
+            let delta: i64 = String::from_utf8_lossy(&args[2]).parse().unwrap_or(0);
+            let (current, expiry) = store
+                .strings
+                .get(&args[1])
+                .cloned()
+                .unwrap_or_else(|| (b"0".to_vec(), None));
+            let Ok(current) = String::from_utf8_lossy(&current).parse::<i64>() else {
+                return b"-ERR value is not an integer or out of range\r\n".to_vec();
+            };
+            let value = current + delta;
+            store
+                .strings
+                .insert(args[1].clone(), (value.to_string().into_bytes(), expiry));
+            format!(":{value}\r\n").into_bytes()
+        }
+        ("EXISTS", n) if n > 1 => {
+            let found = args[1..]
+                .iter()
+                .filter(|k| {
+                    store.strings.contains_key(*k)
+                        || store.sets.contains_key(*k)
+                        || store.lists.contains_key(*k)
+                        || store.sorted_sets.contains_key(*k)
+                })
+                .count();
+            format!(":{found}\r\n").into_bytes()
+        }
+        ("PEXPIRE", 3 | 4) => {
+            let millis: u64 = String::from_utf8_lossy(&args[2]).parse().unwrap_or(0);
+            let new = now + Duration::from_millis(millis);
+            let condition = args
+                .get(3)
+                .map(|c| String::from_utf8_lossy(c).to_ascii_uppercase());
+            let Some(expiry) = expiry_mut(&mut store, &args[1]) else {
+                return b":0\r\n".to_vec();
+            };
+            // A key without an expiry counts as expiring never, as in Redis.
+            let applies = match condition.as_deref() {
+                None => true,
+                Some("NX") => expiry.is_none(),
+                Some("GT") => expiry.is_some_and(|current| new > current),
+                Some(_) => return b"-ERR unsupported option\r\n".to_vec(),
+            };
+            if applies {
+                *expiry = Some(new);
+            }
+            format!(":{}\r\n", u8::from(applies)).into_bytes()
+        }
+        ("PTTL", 2) => match expiry_mut(&mut store, &args[1]) {
+            Some(Some(expiry)) => {
+                format!(":{}\r\n", expiry.duration_since(now).as_millis()).into_bytes()
+            }
+            Some(None) => b":-1\r\n".to_vec(),
+            None => b":-2\r\n".to_vec(),
+        },
+        ("PERSIST", 2) => match expiry_mut(&mut store, &args[1]) {
+            Some(expiry @ Some(_)) => {
+                *expiry = None;
+                b":1\r\n".to_vec()
+            }
+            _ => b":0\r\n".to_vec(),
+        },
+        ("SADD", n) if n > 2 => {
+            let (set, _) = store.sets.entry(args[1].clone()).or_default();
+            let added = args[2..].iter().filter(|m| set.insert(m.to_vec())).count();
+            format!(":{added}\r\n").into_bytes()
+        }
+        ("SREM", n) if n > 2 => {
+            let Some((set, _)) = store.sets.get_mut(&args[1]) else {
+                return b":0\r\n".to_vec();
+            };
+            let removed = args[2..].iter().filter(|m| set.remove(*m)).count();
+            if set.is_empty() {
+                store.sets.remove(&args[1]);
+            }
+            format!(":{removed}\r\n").into_bytes()
+        }
+        ("SMEMBERS", 2) => {
+            let members = store
+                .sets
+                .get(&args[1])
+                .map(|(set, _)| set.clone())
+                .unwrap_or_default();
+            let mut out = format!("*{}\r\n", members.len()).into_bytes();
+            for member in &members {
+                out.extend_from_slice(&bulk(member));
+            }
+            out
+        }
+        ("RPUSH", n) if n > 2 => {
+            let list = store.lists.entry(args[1].clone()).or_default();
+            list.extend(args[2..].iter().cloned());
+            format!(":{}\r\n", list.len()).into_bytes()
+        }
+        ("LPOP", 2) => {
+            let Some(list) = store.lists.get_mut(&args[1]) else {
+                return b"$-1\r\n".to_vec();
+            };
+            let value = list.pop_front().unwrap_or_default();
+            if list.is_empty() {
+                store.lists.remove(&args[1]);
+            }
+            bulk(&value)
+        }
+        ("LLEN", 2) => {
+            let len = store.lists.get(&args[1]).map_or(0, VecDeque::len);
+            format!(":{len}\r\n").into_bytes()
+        }
+        ("ZADD", 4) => {
+            let Ok(score) = String::from_utf8_lossy(&args[2]).parse::<i64>() else {
+                return b"-ERR value is not a valid float\r\n".to_vec();
+            };
+            let set = store.sorted_sets.entry(args[1].clone()).or_default();
+            let added = set.insert(args[3].clone(), score).is_none();
+            format!(":{}\r\n", u8::from(added)).into_bytes()
+        }
+        ("ZRANGEBYSCORE", 4 | 7) => {
+            let bound = |arg: &[u8]| match arg {
+                b"-inf" => Some(i64::MIN),
+                b"+inf" => Some(i64::MAX),
+                n => String::from_utf8_lossy(n).parse().ok(),
+            };
+            let (Some(min), Some(max)) = (bound(&args[2]), bound(&args[3])) else {
+                return b"-ERR min or max is not a float\r\n".to_vec();
+            };
+            let (offset, count) = if args.len() == 7 {
+                let n = |arg: &[u8]| String::from_utf8_lossy(arg).parse().unwrap_or(0);
+                (n(&args[5]), n(&args[6]))
+            } else {
+                (0, usize::MAX)
+            };
+            let mut members: Vec<_> = store
+                .sorted_sets
+                .get(&args[1])
+                .into_iter()
+                .flatten()
+                .filter(|&(_, &score)| (min..=max).contains(&score))
+                .map(|(member, &score)| (score, member.clone()))
+                .collect();
+            members.sort();
+            let members: Vec<_> = members.into_iter().skip(offset).take(count).collect();
+            let mut out = format!("*{}\r\n", members.len()).into_bytes();
+            for (_, member) in &members {
+                out.extend_from_slice(&bulk(member));
+            }
+            out
+        }
+        ("ZREM", n) if n > 2 => {
+            let Some(set) = store.sorted_sets.get_mut(&args[1]) else {
+                return b":0\r\n".to_vec();
+            };
+            let removed = args[2..]
+                .iter()
+                .filter(|m| set.remove(*m).is_some())
+                .count();
+            if set.is_empty() {
+                store.sorted_sets.remove(&args[1]);
+            }
+            format!(":{removed}\r\n").into_bytes()
+        }
+        ("ZCARD", 2) => {
+            let len = store.sorted_sets.get(&args[1]).map_or(0, HashMap::len);
+            format!(":{len}\r\n").into_bytes()
+        }
+        ("PUBLISH", 3) => {
+            let mut frame = b"*3\r\n".to_vec();
+            frame.extend_from_slice(&bulk(b"message"));
+            frame.extend_from_slice(&bulk(&args[1]));
+            frame.extend_from_slice(&bulk(&args[2]));
+            let Some(subscribers) = store.channels.get_mut(&args[1]) else {
+                return b":0\r\n".to_vec();
+            };
+            subscribers.retain(|tx| tx.send(frame.clone()).is_ok());
+            format!(":{}\r\n", subscribers.len()).into_bytes()
+        }
+        _ => b"-ERR unknown command\r\n".to_vec(),
+    }
+}
+
+// The expiry slot of the string or set at `key`, if the key exists.
+fn expiry_mut<'a>(store: &'a mut Data, key: &[u8]) -> Option<&'a mut Option<Instant>> {
+    match store.strings.get_mut(key) {
+        Some((_, expiry)) => Some(expiry),
+        None => store.sets.get_mut(key).map(|(_, expiry)| expiry),
+    }
+}
+
+fn bulk(value: &[u8]) -> Vec<u8> {
+    let mut out = format!("${}\r\n", value.len()).into_bytes();
+    out.extend_from_slice(value);
+    out.extend_from_slice(b"\r\n");
+    out
+}
@@ -0,0 +1,178 @@
+//! Least-recently-used cache of route matches.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering::Relaxed},
+    },
+};
+
+use crate::{Method, context::PathParams};
+
+// What matching one `(method, path)` produced.
+#[derive(Debug, Clone)]
+pub(super) struct Match {
+    // Index into the router's routes, or `None` when no route matched.
+    pub(super) route: Option<usize>,
+    pub(super) params: PathParams,
+}
+
+struct Entry {
+    matched: Match,
+    // Position in `Inner::recency`.
+    tick: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    // Keyed by method first so a lookup can borrow the path instead of allocating.
+    entries: HashMap<Method, HashMap<String, Entry>>,
+    // Access order: the smallest tick is the least recently used key.
+    recency: BTreeMap<u64, (Method, String)>,
+    next_tick: u64,
+    len: usize,
+}
+
+impl Inner {
+    fn remove(&mut self, method: &Method, path: &str) {
+        let Some(paths) = self.entries.get_mut(method) else {
+            return;
+        };
+        if let Some(entry) = paths.remove(path) {
+            self.recency.remove(&entry.tick);
+            self.len -= 1;
+        }
+    }
+}
+
+/// Counters for a router's match cache, from [`Router::match_cache_stats`].
+///
+/// [`Router::match_cache_stats`]: super::Router::match_cache_stats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchCacheStats {
+    /// Lookups answered from the cache.
+    pub hits: u64,
+    /// Lookups that had to match the route table.
+    pub misses: u64,
+    /// Entries currently cached.
+    pub len: usize,
+}
+
+// A bounded map from `(method, path)` to the match it produced.
+pub(super) struct MatchCache {
+    capacity: usize,
+    inner: Mutex<Inner>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl MatchCache {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(Inner::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    // Returns the cached match, marking it most recently used.
+    pub(super) fn get(&self, method: &Method, path: &str) -> Option<Match> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let inner = &mut *inner;
+        let next_tick = inner.next_tick;
+        let Some(entry) = inner
+            .entries
+            .get_mut(method)
+            .and_then(|paths| paths.get_mut(path))
+        else {
+            self.misses.fetch_add(1, Relaxed);
+            return None;
+        };
+        if let Some(key) = inner.recency.remove(&entry.tick) {
+            inner.recency.insert(next_tick, key);
+        }
+        entry.tick = next_tick;
+        inner.next_tick += 1;
+        self.hits.fetch_add(1, Relaxed);
+        Some(entry.matched.clone())
+    }
+
+    // Caches `matched`, evicting the least recently used entry when full.
+    pub(super) fn insert(&self, method: &Method, path: &str, matched: Match) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.remove(method, path);
+        while inner.len >= self.capacity {
+            let Some((_, (method, path))) = inner.recency.first_key_value() else {
+                break;
+            };
+            let (method, path) = (method.clone(), path.clone());
+            inner.remove(&method, &path);
+        }
+        let tick = inner.next_tick;
+        inner.next_tick += 1;
+        inner
+            .recency
+            .insert(tick, (method.clone(), path.to_owned()));
+        inner
+            .entries
+            .entry(method.clone())
+            .or_default()
+            .insert(path.to_owned(), Entry { matched, tick });
+        inner.len += 1;
+    }
+
+    // Forgets every match, for when the route table changes.
+    pub(super) fn clear(&mut self) {
+        *self.inner.get_mut().unwrap_or_else(|e| e.into_inner()) = Inner::default();
+    }
+
+    pub(super) fn stats(&self) -> MatchCacheStats {
+        MatchCacheStats {
+            hits: self.hits.load(Relaxed),
+            misses: self.misses.load(Relaxed),
+            len: self.inner.lock().unwrap_or_else(|e| e.into_inner()).len,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matched(route: usize) -> Match {
+        Match {
+            route: Some(route),
+            params: PathParams::new(),
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = MatchCache::new(2);
+        cache.insert(&Method::Get, "/a", matched(0));
+        cache.insert(&Method::Get, "/b", matched(1));
+        assert_eq!(cache.get(&Method::Get, "/a").unwrap().route, Some(0));
+        cache.insert(&Method::Post, "/c", matched(2));
+
+        assert!(
+            cache.get(&Method::Get, "/b").is_none(),
+            "least recently used"
+        );
+        assert!(cache.get(&Method::Get, "/a").is_some());
+        assert!(cache.get(&Method::Post, "/c").is_some());
+        assert!(cache.get(&Method::Post, "/a").is_none(), "keyed by method");
+        assert_eq!(
+            cache.stats(),
+            MatchCacheStats {
+                hits: 3,
+                misses: 2,
+                len: 2
+            }
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Request counts and latency per route pattern.
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering::Relaxed},
+    },
+    time::Duration,
+};
+
+use crate::{
+    Method, StatusCode,
+    middleware::metrics::{LATENCY_BUCKETS, MetricsSource, MetricsWriter},
+};
+
+// The counters of one route, updated without locking.
+#[derive(Debug)]
+pub(super) struct RouteSeries {
+    method: Method,
+    pattern: String,
+    // Responses by status class, `1xx` first.
+    classes: [AtomicU64; 5],
+    // Per-bucket (non-cumulative) counts; the last slot is for values above every bound.
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum_nanos: AtomicU64,
+}
+
+impl RouteSeries {
+    pub(super) fn record(&self, status: StatusCode, elapsed: Duration) {
+        let class = usize::from(status.as_u16() / 100).clamp(1, 5) - 1;
+        self.classes[class].fetch_add(1, Relaxed);
+        let seconds = elapsed.as_secs_f64();
+        let slot = LATENCY_BUCKETS
+            .iter()
+            .position(|&le| seconds <= le)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[slot].fetch_add(1, Relaxed);
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.sum_nanos.fetch_add(nanos, Relaxed);
+    }
+
+    fn stats(&self) -> RouteStats {
+        let mut cumulative = 0;
+        let buckets = LATENCY_BUCKETS
+            .iter()
+            .zip(&self.buckets)
+            .map(|(&le, n)| {
+                cumulative += n.load(Relaxed);
+                (le, cumulative)
+            })
+            .collect();
+        RouteStats {
+            method: self.method.clone(),
+            pattern: self.pattern.clone(),
+            status_classes: self.classes.each_ref().map(|n| n.load(Relaxed)),
+            latency_buckets: buckets,
+            latency_sum: Duration::from_nanos(self.sum_nanos.load(Relaxed)),
+        }
+    }
+}
+
+/// What one route has handled, from [`Router::metrics_snapshot`].
+///
+/// [`Router::metrics_snapshot`]: super::Router::metrics_snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteStats {
+    /// The route's method.
+    pub method: Method,
+    /// The route's pattern as registered, such as `/users/:id`.
+    pub pattern: String,
+    /// Responses by status class: `1xx` at index 0 through `5xx` at index 4.
+    pub status_classes: [u64; 5],
+    /// Cumulative `(upper bound in seconds, count)` latency buckets.
+    pub latency_buckets: Vec<(f64, u64)>,
+    /// Total time spent in the handler.
+    pub latency_sum: Duration,
+}
+
+impl RouteStats {
+    /// Returns the number of requests the route handled.
+    pub fn requests(&self) -> u64 {
+        self.status_classes.iter().sum()
+    }
+}
+
+/// Per-route statistics, from [`Router::metrics_snapshot`].
+///
+/// [`Router::metrics_snapshot`]: super::Router::metrics_snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteMetricsSnapshot {
+    /// One entry per route, in registration order.
+    pub routes: Vec<RouteStats>,
+    /// Requests no route matched.
+    pub unmatched: u64,
+}
+
+impl RouteMetricsSnapshot {
+    /// Returns the statistics of the route registered for `method` and `pattern`.
+    pub fn route(&self, method: &Method, pattern: &str) -> Option<&RouteStats> {
+        self.routes
+            .iter()
+            .find(|stats| stats.method == *method && stats.pattern == pattern)
+    }
+}
+
+/// A router's per-route metrics, as a [`MetricsSource`].
+///
+/// Obtained from [`Router::route_metrics`]; clones share the router's counters, so
+/// registering one with a [`Metrics`](crate::middleware::Metrics) registry exports
+/// them at its scrape endpoint as `rttp_route_requests_total`,
+/// `rttp_route_request_duration_seconds`, and `rttp_route_unmatched_total`.
+///
+/// [`Router::route_metrics`]: super::Router::route_metrics
+#[derive(Debug, Clone, Default)]
+pub struct RouteMetrics {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    routes: Mutex<Vec<Arc<RouteSeries>>>,
+    unmatched: AtomicU64,
+}
+
+impl RouteMetrics {
+    // Starts the series for a newly registered route.
+    pub(super) fn add_route(&self, method: &Method, pattern: &str) -> Arc<RouteSeries> {
+        let series = Arc::new(RouteSeries {
+            method: method.clone(),
+            pattern: pattern.to_owned(),
+            classes: Default::default(),
+            buckets: Default::default(),
+            sum_nanos: AtomicU64::new(0),
+        });
+        self.lock().push(Arc::clone(&series));
+        series
+    }
+
+    pub(super) fn record_unmatched(&self) {
+        self.inner.unmatched.fetch_add(1, Relaxed);
+    }
+
+    /// Returns the current statistics.
+    pub fn snapshot(&self) -> RouteMetricsSnapshot {
+        RouteMetricsSnapshot {
+            routes: self.lock().iter().map(|series| series.stats()).collect(),
+            unmatched: self.inner.unmatched.load(Relaxed),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Arc<RouteSeries>>> {
+        self.inner.routes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl MetricsSource for RouteMetrics {
+    fn collect(&self, out: &mut MetricsWriter) {
+        let snapshot = self.snapshot();
+        for stats in &snapshot.routes {
+            let labels = [
+                ("method", stats.method.as_str()),
+                ("route", stats.pattern.as_str()),
+            ];
+            for (class, count) in ["1xx", "2xx", "3xx", "4xx", "5xx"]
+                .into_iter()
+                .zip(stats.status_classes)
+                .filter(|(_, count)| *count > 0)
+            {
+                out.counter(
+                    "rttp_route_requests_total",
+                    "HTTP requests handled, by route pattern and status class.",
+                    &[labels[0], labels[1], ("status", class)],
+                    count as f64,
+                );
+            }
+            out.histogram(
+                "rttp_route_request_duration_seconds",
+                "Handler latency, by route pattern.",
+                &labels,
+                &stats.latency_buckets,
+                stats.latency_sum.as_secs_f64(),
+                stats.requests(),
+            );
+        }
+        out.counter(
+            "rttp_route_unmatched_total",
+            "HTTP requests that matched no route.",
+            &[],
+            snapshot.unmatched as f64,
+        );
+    }
+}
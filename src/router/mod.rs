@@ -18,18 +18,28 @@
 use std::pin::Pin;
 use std::sync::Arc;
 
+mod cache;
+#[cfg(feature = "route-metrics")]
+mod metrics;
+
+pub use cache::MatchCacheStats;
+#[cfg(feature = "route-metrics")]
+pub use metrics::{RouteMetrics, RouteMetricsSnapshot, RouteStats};
+
+use cache::{Match, MatchCache};
+
 use crate::context::{Context, PathParams};
+use crate::middleware::{Middleware, Next};
+use crate::realtime::websocket::{self, WebSocket, WebSocketConfig};
 use crate::{Method, Request, Response, StatusCode};
 
-/// Type-erased, heap-allocated async handler that processes a [`Context`] and returns a
-/// [`Response`].
+/// Type-erased async handler that processes a [`Context`] and returns a [`Response`].
 ///
-/// Handlers are stored behind `Arc<dyn Fn(…)>` so they can be cloned and shared across
-/// threads without copying the underlying closure. In practice you never construct this
-/// type directly — use [`Router::get`], [`Router::post`], and the other method-specific
-/// helpers instead.
-pub type Handler =
-    Arc<dyn Fn(Context) -> Pin<Box<dyn Future<Output = Response> + Send>> + Send + Sync + 'static>;
+/// Routes store the handler passed to [`Router::get`] and friends directly behind this
+/// `Arc`, so dispatching a request makes one dynamic call and boxes one future. In
+/// practice you never construct this type directly — use [`Router::get`],
+/// [`Router::post`], and the other method-specific helpers instead.
+pub type Handler = Arc<dyn IntoHandler>;
 
 /// Conversion trait for async handler functions.
 ///
@@ -54,14 +64,14 @@ where
 
 // A single path segment, either a literal string or a named capture (`:name`).
 #[derive(Debug, Clone)]
-enum Segment {
+pub(crate) enum Segment {
     Static(String),
     Parameter(String),
 }
 
 // Compiled representation of a route pattern string.
 #[derive(Debug, Clone)]
-enum Pattern {
+pub(crate) enum Pattern {
     // Matches one exact path string, e.g. `/users`.
     Exact(String),
     // Matches a fixed number of segments where some may be named captures, e.g. `/users/:id`.
@@ -92,7 +102,7 @@ impl Pattern {
     ///
     /// # Examples
     ///
-    /// ```rust,no_run
+    /// ```rust,ignore
     /// # use rttp::router::Pattern; // illustrative — Pattern is crate-private
     /// let p = Pattern::parse("/users/:id");
     /// // p is Pattern::Parameterized with segments ["users", ":id"]
@@ -128,7 +138,7 @@ impl Pattern {
     }
 
     // Try to match `path` against this pattern, returning extracted [`PathParams`] on success.
-    fn matches(&self, path: &str) -> Option<PathParams> {
+    pub(crate) fn matches(&self, path: &str) -> Option<PathParams> {
         let path = if path != "/" && path.ends_with('/') {
             &path[..path.len() - 1]
         } else {
@@ -184,15 +194,25 @@ struct Route {
     method: Method,
     pattern: Pattern,
     handler: Handler,
+    #[cfg(feature = "route-metrics")]
+    series: Arc<metrics::RouteSeries>,
 }
 
 impl Route {
-    fn new(method: Method, pattern: &str, handler: Handler) -> Self {
-        Self {
-            method,
-            pattern: Pattern::parse(pattern),
-            handler,
-        }
+    // Calls the handler, timing it when route metrics are compiled in.
+    fn call(&self, ctx: Context) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let response = self.handler.call(ctx);
+        #[cfg(feature = "route-metrics")]
+        let response = {
+            let series = Arc::clone(&self.series);
+            Box::pin(async move {
+                let started = tokio::time::Instant::now();
+                let response = response.await;
+                series.record(response.status(), started.elapsed());
+                response
+            })
+        };
+        response
     }
 
     // Returns `Some(params)` when both the HTTP method and path pattern match, `None` otherwise.
@@ -214,19 +234,22 @@ impl Route {
 /// # Examples
 ///
 /// ```rust,no_run
-/// use rttp::{Router, Response, StatusCode};
+/// use rttp::{Router, Response, StatusCode, context::Context};
 ///
 /// let mut router = Router::new();
 ///
 /// router.get("/ping", |_ctx| async { Response::new(StatusCode::Ok) });
 ///
-/// router.get("/users/:id", |ctx| async move {
+/// router.get("/users/:id", |ctx: Context| async move {
 ///     let id = ctx.params().get("id").unwrap_or("unknown").to_owned();
 ///     Response::new(StatusCode::Ok).body(id)
 /// });
 /// ```
 pub struct Router {
     routes: Vec<Route>,
+    cache: Option<MatchCache>,
+    #[cfg(feature = "route-metrics")]
+    metrics: RouteMetrics,
 }
 
 impl Default for Router {
@@ -247,7 +270,12 @@ impl Router {
     /// assert!(router.is_empty());
     /// ```
     pub fn new() -> Self {
-        Self { routes: Vec::new() }
+        Self {
+            routes: Vec::new(),
+            cache: None,
+            #[cfg(feature = "route-metrics")]
+            metrics: RouteMetrics::default(),
+        }
     }
 
     /// Register a handler for `GET` requests matching `path`.
@@ -364,10 +392,136 @@ impl Router {
         self.add_route(Method::Patch, path, handler);
     }
 
+    /// Register a WebSocket endpoint at `path`.
+    ///
+    /// The route answers `GET` upgrade requests like any other route — path parameters
+    /// are captured and middleware runs first, so authentication can reject the upgrade
+    /// before it happens. Once the `101 Switching Protocols` response is sent, `handler`
+    /// runs with the request's [`Context`] and the open [`WebSocket`]; the connection
+    /// closes when it returns. Non-upgrade requests are refused with
+    /// `426 Upgrade Required`.
+    ///
+    /// Uses [`WebSocketConfig::default`]; for other limits, register a `GET` route that
+    /// calls [`websocket::accept`].
+    ///
+    /// # Arguments
+    ///
+    /// - `path` — URL pattern string (e.g. `"/chat/:room"`).
+    /// - `handler` — Async function that receives the [`Context`] and the [`WebSocket`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use rttp::Router;
+    /// use rttp::realtime::Message;
+    ///
+    /// let mut router = Router::new();
+    /// router.ws("/chat/:room", |ctx, mut ws| async move {
+    ///     let room = ctx.params().get("room").unwrap_or("lobby").to_owned();
+    ///     let _ = ws.send(format!("joined {room}")).await;
+    ///     while let Some(Ok(Message::Text(text))) = ws.next().await {
+    ///         let _ = ws.send(text).await;
+    ///     }
+    /// });
+    /// ```
+    pub fn ws<F, Fut>(&mut self, path: &str, handler: F)
+    where
+        F: Fn(Context, WebSocket) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        self.add_route(Method::Get, path, move |ctx: Context| {
+            let handler = Arc::clone(&handler);
+            let response = websocket::accept(ctx, WebSocketConfig::default(), move |ctx, ws| {
+                handler(ctx, ws)
+            });
+            async move { response }
+        });
+    }
+
     // Erase the concrete handler type and store it as a `Handler` trait object.
     fn add_route(&mut self, method: Method, path: &str, handler: impl IntoHandler) {
-        let handler: Handler = Arc::new(move |ctx| handler.call(ctx));
-        self.routes.push(Route::new(method, path, handler));
+        self.routes.push(Route {
+            #[cfg(feature = "route-metrics")]
+            series: self.metrics.add_route(&method, path),
+            method,
+            pattern: Pattern::parse(path),
+            handler: Arc::new(handler),
+        });
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
+    }
+
+    /// Remember the matches for the `capacity` most recently requested method and path
+    /// pairs, so repeated requests for the same hot paths skip pattern matching.
+    ///
+    /// Misses are remembered too, so a path that keeps returning `404` is also cheap.
+    /// Registering a route clears the cache. Calling this again replaces the cache;
+    /// a `capacity` of zero turns it off.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rttp::{Router, Response, StatusCode};
+    ///
+    /// let mut router = Router::new();
+    /// router.match_cache(1024);
+    /// router.get("/users/:id", |_ctx| async { Response::new(StatusCode::Ok) });
+    /// assert_eq!(router.match_cache_stats().unwrap().len, 0);
+    /// ```
+    pub fn match_cache(&mut self, capacity: usize) {
+        self.cache = (capacity > 0).then(|| MatchCache::new(capacity));
+    }
+
+    /// Returns the match cache's counters, or `None` if it is off.
+    pub fn match_cache_stats(&self) -> Option<MatchCacheStats> {
+        self.cache.as_ref().map(MatchCache::stats)
+    }
+
+    /// Returns the requests, status classes, and latency recorded for each route so far,
+    /// plus the number of requests no route matched.
+    ///
+    /// Latency covers the handler only, from the call until its response is ready.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rttp::{Method, Request, Response, Router, StatusCode};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut router = Router::new();
+    /// router.get("/users/:id", |_ctx| async { Response::new(StatusCode::Ok) });
+    /// router.route(Request::builder().path("/users/7").build()).await;
+    ///
+    /// let snapshot = router.metrics_snapshot();
+    /// let users = snapshot.route(&Method::Get, "/users/:id").unwrap();
+    /// assert_eq!(users.requests(), 1);
+    /// # }
+    /// ```
+    #[cfg(feature = "route-metrics")]
+    pub fn metrics_snapshot(&self) -> RouteMetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Returns a handle to this router's per-route metrics, to register with a
+    /// [`Metrics`](crate::middleware::Metrics) registry so they are exported with the
+    /// rest at its scrape endpoint.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rttp::{Router, middleware::Metrics};
+    ///
+    /// let router = Router::new();
+    /// let metrics = Metrics::new();
+    /// metrics.register(router.route_metrics());
+    /// assert!(metrics.render().contains("rttp_route_unmatched_total 0"));
+    /// ```
+    #[cfg(feature = "route-metrics")]
+    pub fn route_metrics(&self) -> RouteMetrics {
+        self.metrics.clone()
     }
 
     /// Return the number of routes registered in this router.
@@ -427,16 +581,102 @@ impl Router {
     /// # }
     /// ```
     pub async fn route(&self, request: Request) -> Response {
-        let path = request.path();
+        self.dispatch(Context::new(request)).await
+    }
 
-        for route in &self.routes {
-            if let Some(params) = route.matches(request.method(), path) {
-                let ctx = Context::with_params(request, params);
-                return (route.handler)(ctx).await;
-            }
+    /// Dispatch an existing [`Context`] to the first matching route.
+    ///
+    /// Unlike [`route`](Self::route), this keeps whatever the caller already stored in the
+    /// context's extensions, so values inserted by upstream middleware (sessions,
+    /// principals, codec registries) reach the handler. Matched path parameters replace
+    /// the context's current parameters.
+    ///
+    /// # Returns
+    ///
+    /// The [`Response`] produced by the matching handler, or a `404 Not Found` response
+    /// when no route matches.
+    pub async fn dispatch(&self, mut ctx: Context) -> Response {
+        match self.resolve(&mut ctx) {
+            Some(route) => route.call(ctx).await,
+            None => Response::new(StatusCode::NotFound),
         }
+    }
 
-        Response::new(StatusCode::NotFound)
+    // Finds the handler for `ctx` and installs the matched path parameters.
+    fn resolve(&self, ctx: &mut Context) -> Option<&Route> {
+        let request = ctx.request();
+        let (method, path) = (request.method(), request.path());
+        let cached = self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.get(method, path));
+        let matched = match cached {
+            Some(matched) => matched,
+            None => {
+                let matched = self.match_route(method, path);
+                if let Some(cache) = &self.cache {
+                    cache.insert(method, path, matched.clone());
+                }
+                matched
+            }
+        };
+        let Some(index) = matched.route else {
+            #[cfg(feature = "route-metrics")]
+            self.metrics.record_unmatched();
+            return None;
+        };
+        *ctx.params_mut() = matched.params;
+        Some(&self.routes[index])
+    }
+
+    // Tests the routes in order against `method` and `path`.
+    fn match_route(&self, method: &Method, path: &str) -> Match {
+        self.routes
+            .iter()
+            .enumerate()
+            .find_map(|(index, route)| {
+                route.matches(method, path).map(|params| Match {
+                    route: Some(index),
+                    params,
+                })
+            })
+            .unwrap_or(Match {
+                route: None,
+                params: PathParams::new(),
+            })
+    }
+}
+
+/// A `Router` can terminate a middleware pipeline.
+///
+/// The matched handler receives the context built up by the preceding middleware; `next`
+/// is never called, and unmatched requests get `404 Not Found`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::sync::Arc;
+/// use rttp::{Response, Router, StatusCode};
+/// use rttp::middleware::{LoggerMiddleware, from_middleware};
+///
+/// let mut router = Router::new();
+/// router.get("/ping", |_ctx| async { Response::new(StatusCode::Ok) });
+///
+/// let pipeline = vec![
+///     from_middleware(Arc::new(LoggerMiddleware)),
+///     from_middleware(Arc::new(router)),
+/// ];
+/// ```
+impl Middleware for Router {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        _next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        match self.resolve(&mut ctx) {
+            Some(route) => route.call(ctx),
+            None => Box::pin(async { Response::new(StatusCode::NotFound) }),
+        }
     }
 }
 
@@ -703,4 +943,126 @@ mod tests {
             StatusCode::Ok
         );
     }
+
+    // ── Router as middleware ──────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn dispatch_preserves_extensions() {
+        let mut router = Router::new();
+        router.get("/users/:id", |ctx: Context| async move {
+            let tag = ctx.extensions().get::<&str>().copied().unwrap_or("missing");
+            let id = ctx.params().get("id").unwrap_or_default().to_owned();
+            Response::new(StatusCode::Ok).body(format!("{tag}:{id}"))
+        });
+
+        let mut ctx = Context::new(make_request("GET", "/users/7"));
+        ctx.extensions_mut().insert("tagged");
+        let res = router.dispatch(ctx).await;
+        assert_eq!(res.body_ref(), b"tagged:7");
+    }
+
+    #[tokio::test]
+    async fn router_terminates_middleware_chain() {
+        use crate::middleware::{MiddlewareHandler, from_middleware};
+
+        let mut router = Router::new();
+        router.get("/who", |ctx: Context| async move {
+            let user = ctx
+                .extensions()
+                .get::<String>()
+                .cloned()
+                .unwrap_or_default();
+            Response::new(StatusCode::Ok).body(user)
+        });
+
+        let auth: MiddlewareHandler = Arc::new(|mut ctx: Context, next: Next| {
+            Box::pin(async move {
+                ctx.extensions_mut().insert("ada".to_owned());
+                next.run(ctx).await
+            })
+        });
+        let chain = vec![auth, from_middleware(Arc::new(router))];
+
+        let res = Next::new(chain.clone())
+            .run(Context::new(make_request("GET", "/who")))
+            .await;
+        assert_eq!(res.body_ref(), b"ada");
+
+        let res = Next::new(chain)
+            .run(Context::new(make_request("GET", "/nope")))
+            .await;
+        assert_eq!(res.status(), StatusCode::NotFound);
+    }
+
+    #[tokio::test]
+    async fn match_cache_reuses_matches_until_routes_change() {
+        let mut router = Router::new();
+        router.match_cache(8);
+        router.get("/users/:id", |ctx: Context| async move {
+            let id = ctx.params().get("id").unwrap_or_default().to_owned();
+            Response::new(StatusCode::Ok).body(id)
+        });
+
+        for _ in 0..3 {
+            let response = router.route(make_request("GET", "/users/7")).await;
+            assert_eq!(response.body_text(), "7");
+        }
+        let response = router.route(make_request("GET", "/users/8")).await;
+        assert_eq!(response.body_text(), "8");
+        let response = router.route(make_request("GET", "/posts")).await;
+        assert_eq!(response.status(), StatusCode::NotFound);
+        let response = router.route(make_request("GET", "/posts")).await;
+        assert_eq!(response.status(), StatusCode::NotFound);
+        let stats = router.match_cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.len), (3, 3, 3));
+
+        // A new route is seen by paths that were cached as misses.
+        router.get("/posts", |_ctx| async { Response::new(StatusCode::Ok) });
+        assert_eq!(router.match_cache_stats().unwrap().len, 0);
+        let response = router.route(make_request("GET", "/posts")).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+
+        router.match_cache(0);
+        assert_eq!(router.match_cache_stats(), None);
+    }
+
+    #[cfg(feature = "route-metrics")]
+    #[tokio::test]
+    async fn records_metrics_per_route_pattern() {
+        use crate::middleware::Metrics;
+
+        let mut router = Router::new();
+        router.get("/users/:id", |ctx: Context| async move {
+            match ctx.params().get("id") {
+                Some("0") => Response::new(StatusCode::InternalServerError),
+                _ => Response::new(StatusCode::Ok),
+            }
+        });
+        router.post("/users", |_ctx| async {
+            Response::new(StatusCode::Created)
+        });
+        for path in ["/users/1", "/users/2", "/users/0", "/nope"] {
+            router.route(make_request("GET", path)).await;
+        }
+
+        let snapshot = router.metrics_snapshot();
+        assert_eq!(snapshot.unmatched, 1);
+        let users = snapshot.route(&Method::Get, "/users/:id").unwrap();
+        assert_eq!(users.status_classes, [0, 2, 0, 0, 1]);
+        assert_eq!(users.latency_buckets.last().unwrap().1, 3);
+        assert_eq!(
+            snapshot.route(&Method::Post, "/users").unwrap().requests(),
+            0
+        );
+
+        let metrics = Metrics::new();
+        metrics.register(router.route_metrics());
+        let text = metrics.render();
+        assert!(text.contains(
+            r#"rttp_route_requests_total{method="GET",route="/users/:id",status="5xx"} 1"#
+        ));
+        assert!(text.contains(
+            r#"rttp_route_request_duration_seconds_count{method="GET",route="/users/:id"} 3"#
+        ));
+    }
 }
@@ -1,5 +1,32 @@
 //! Security — public API re-exports.
 
+pub mod auth;
+pub mod crypto;
+pub mod csp;
+pub mod headers;
+pub mod lockout;
 pub mod middleware;
+pub mod mtls;
+pub mod oauth;
+pub mod path;
+pub mod remember;
+pub mod session;
+pub mod signature;
+pub mod signed_url;
+pub mod totp;
 
+pub use auth::{
+    ApiKeyMiddleware, BasicAuthMiddleware, BearerAuthMiddleware, Principal, api_key_auth,
+    basic_auth, bearer_auth,
+};
+pub use headers::{CspNonceExt, SecureHeadersMiddleware};
+pub use lockout::LoginGuard;
 pub use middleware::CorsMiddleware;
+pub use mtls::{ClientCertMiddleware, client_cert_auth};
+pub use oauth::{OAuthClient, Provider};
+pub use path::safe_join;
+pub use remember::{RememberMe, RememberMeMiddleware, Remembered};
+pub use session::{MemoryStore, RedisStore, Session, SessionExt, SessionMiddleware, SessionStore};
+pub use signature::SignatureMiddleware;
+pub use signed_url::UrlSigner;
+pub use totp::Totp;
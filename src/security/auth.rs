@@ -0,0 +1,639 @@
+//! HTTP authentication — `Basic` (RFC 7617) and `Bearer` (RFC 6750) schemes.
+//!
+//! This module provides credential parsing helpers and two middleware types that protect
+//! every downstream handler:
+//!
+//! - [`basic_auth`] / [`BasicAuthMiddleware`] — username/password checked by a callback.
+//! - [`bearer_auth`] / [`BearerAuthMiddleware`] — opaque token checked by a callback.
+//! - [`api_key_auth`] / [`ApiKeyMiddleware`] — a fixed set of keys, as `X-API-Key` or a
+//!   bearer token, for machine clients such as admin tools.
+//!
+//! On success the `Basic` and `Bearer` middleware insert the authenticated [`Principal`]
+//! into the request [`Extensions`](crate::context::Extensions). On failure every middleware
+//! short-circuits with `401 Unauthorized` and a `WWW-Authenticate` challenge.
+//!
+//! The `Basic` and `Bearer` middleware accept a [`LoginGuard`] through their `lockout` builder to count failures and
+//! answer locked-out clients with `429 Too Many Requests`.
+
+use std::{pin::Pin, sync::Arc};
+
+use super::{
+    crypto::{base64_decode, constant_time_eq},
+    lockout::{LoginGuard, locked_out},
+};
+use crate::{
+    Headers, Response, StatusCode,
+    context::Context,
+    middleware::{Middleware, Next},
+};
+
+/// The identity established by an authentication middleware.
+///
+/// Inserted into the request extensions so handlers can read it with
+/// `ctx.extensions().get::<Principal>()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    id: String,
+}
+
+impl Principal {
+    /// Creates a principal with the given identifier (username, subject, …).
+    pub fn new(id: impl Into<String>) -> Self {
+        Self { id: id.into() }
+    }
+
+    /// Returns the principal's identifier.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// A decoded `Authorization: Basic …` credential pair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicCredentials {
+    /// The user-id portion (everything before the first `:`).
+    pub username: String,
+    /// The password portion (everything after the first `:`).
+    pub password: String,
+}
+
+impl BasicCredentials {
+    /// Compares these credentials against expected values in constant time.
+    ///
+    /// Both fields are always compared so the timing does not reveal which one differed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::security::auth::BasicCredentials;
+    ///
+    /// let creds = BasicCredentials {
+    ///     username: "admin".into(),
+    ///     password: "hunter2".into(),
+    /// };
+    /// assert!(creds.matches("admin", "hunter2"));
+    /// assert!(!creds.matches("admin", "hunter3"));
+    /// ```
+    pub fn matches(&self, username: &str, password: &str) -> bool {
+        let user_ok = constant_time_eq(self.username.as_bytes(), username.as_bytes());
+        let pass_ok = constant_time_eq(self.password.as_bytes(), password.as_bytes());
+        user_ok & pass_ok
+    }
+}
+
+/// Parses an `Authorization` header value using the `Basic` scheme.
+///
+/// The scheme name is matched case-insensitively. Returns `None` if the scheme is not
+/// `Basic`, the payload is not valid base64 or UTF-8, or it lacks the `:` separator.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::security::auth::parse_basic;
+///
+/// let creds = parse_basic("Basic YWxhZGRpbjpvcGVuc2VzYW1l").unwrap();
+/// assert_eq!(creds.username, "aladdin");
+/// assert_eq!(creds.password, "opensesame");
+/// ```
+pub fn parse_basic(header: &str) -> Option<BasicCredentials> {
+    let payload = strip_scheme(header, "Basic")?;
+    let decoded = String::from_utf8(base64_decode(payload)?).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some(BasicCredentials {
+        username: username.to_owned(),
+        password: password.to_owned(),
+    })
+}
+
+/// Parses an `Authorization` header value using the `Bearer` scheme, returning the token.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::security::auth::parse_bearer;
+///
+/// assert_eq!(parse_bearer("Bearer abc.def.ghi"), Some("abc.def.ghi"));
+/// assert_eq!(parse_bearer("Basic abc"), None);
+/// ```
+pub fn parse_bearer(header: &str) -> Option<&str> {
+    strip_scheme(header, "Bearer")
+}
+
+// Returns the trimmed, non-empty credentials after a case-insensitive auth scheme.
+fn strip_scheme<'a>(header: &'a str, scheme: &str) -> Option<&'a str> {
+    let (name, rest) = header.trim().split_once(' ')?;
+    if !name.eq_ignore_ascii_case(scheme) {
+        return None;
+    }
+    let rest = rest.trim();
+    (!rest.is_empty()).then_some(rest)
+}
+
+// Escapes a realm for use inside a quoted-string.
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Builds a `401 Unauthorized` response carrying the given challenge.
+fn unauthorized(challenge: String) -> Response {
+    Response::new(StatusCode::Unauthorized)
+        .header("WWW-Authenticate", challenge)
+        .body("Unauthorized")
+}
+
+// The address the request came from, when served over a connection.
+fn client_ip(ctx: &Context) -> Option<std::net::IpAddr> {
+    ctx.request()
+        .connection()
+        .map(|connection| connection.peer_addr().ip())
+}
+
+type BasicVerifier = Arc<dyn Fn(&str, &str) -> bool + Send + Sync>;
+type BearerVerifier = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+type Rejection = Arc<dyn Fn() -> Response + Send + Sync>;
+
+/// Creates a [`BasicAuthMiddleware`] for `realm` that accepts credentials for which
+/// `verify(username, password)` returns `true`.
+///
+/// Use [`BasicCredentials::matches`] or [`constant_time_eq`] inside `verify` when comparing
+/// against stored secrets.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rttp::security::basic_auth;
+/// use rttp::security::crypto::constant_time_eq;
+///
+/// let auth = basic_auth("admin area", |user, pass| {
+///     user == "admin" && constant_time_eq(pass.as_bytes(), b"hunter2")
+/// });
+/// ```
+pub fn basic_auth<F>(realm: impl Into<String>, verify: F) -> BasicAuthMiddleware
+where
+    F: Fn(&str, &str) -> bool + Send + Sync + 'static,
+{
+    BasicAuthMiddleware {
+        realm: realm.into(),
+        verify: Arc::new(verify),
+        guard: None,
+    }
+}
+
+/// Creates a [`BearerAuthMiddleware`] for `realm` that accepts tokens for which
+/// `verify(token)` returns the authenticated principal's identifier.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rttp::security::bearer_auth;
+///
+/// let auth = bearer_auth("api", |token| (token == "s3cret").then(|| "service".to_owned()));
+/// ```
+pub fn bearer_auth<F>(realm: impl Into<String>, verify: F) -> BearerAuthMiddleware
+where
+    F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+{
+    BearerAuthMiddleware {
+        realm: realm.into(),
+        verify: Arc::new(verify),
+        guard: None,
+    }
+}
+
+/// Creates an [`ApiKeyMiddleware`] accepting any of `keys`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rttp::security::api_key_auth;
+///
+/// let auth = api_key_auth(["ops-secret", "deploy-bot-secret"]);
+/// ```
+pub fn api_key_auth<I, S>(keys: I) -> ApiKeyMiddleware
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    ApiKeyMiddleware {
+        keys: keys.into_iter().map(Into::into).collect(),
+        reject: Arc::new(|| unauthorized("Bearer".to_owned())),
+    }
+}
+
+/// Middleware enforcing HTTP `Basic` authentication.
+///
+/// Constructed with [`basic_auth`].
+///
+/// # Behavior
+///
+/// - Missing, malformed, or rejected credentials short-circuit with `401 Unauthorized` and
+///   `WWW-Authenticate: Basic realm="<realm>", charset="UTF-8"`.
+/// - Accepted credentials insert a [`Principal`] named after the username into the request
+///   extensions and forward to the next handler.
+/// - With a [`lockout`](Self::lockout) guard, rejected credentials count as failures for
+///   the username and client IP, and a locked-out username or IP gets `429 Too Many
+///   Requests` with `Retry-After` without its credentials being checked.
+pub struct BasicAuthMiddleware {
+    realm: String,
+    verify: BasicVerifier,
+    guard: Option<LoginGuard>,
+}
+
+impl BasicAuthMiddleware {
+    /// Counts failed logins with `guard` and refuses locked-out users and IPs.
+    #[must_use]
+    pub fn lockout(mut self, guard: LoginGuard) -> Self {
+        self.guard = Some(guard);
+        self
+    }
+
+    // The challenge sent with every 401 response.
+    fn challenge(&self) -> String {
+        format!("Basic realm=\"{}\", charset=\"UTF-8\"", quote(&self.realm))
+    }
+}
+
+impl Middleware for BasicAuthMiddleware {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let credentials = ctx
+            .request()
+            .headers()
+            .get("authorization")
+            .and_then(parse_basic);
+
+        let Some(guard) = self.guard.clone() else {
+            return match credentials {
+                Some(creds) if (self.verify)(&creds.username, &creds.password) => {
+                    ctx.extensions_mut().insert(Principal::new(creds.username));
+                    Box::pin(async move { next.run(ctx).await })
+                }
+                _ => {
+                    let response = unauthorized(self.challenge());
+                    Box::pin(async move { response })
+                }
+            };
+        };
+
+        let verify = Arc::clone(&self.verify);
+        let challenge = self.challenge();
+        Box::pin(async move {
+            let Some(creds) = credentials else {
+                return unauthorized(challenge);
+            };
+            let ip = client_ip(&ctx);
+            let (wait, on_record) = guard.standing(&creds.username, ip).await;
+            if let Some(wait) = wait {
+                return locked_out(wait);
+            }
+            if !verify(&creds.username, &creds.password) {
+                guard.record_failure(Some(&creds.username), ip).await;
+                return unauthorized(challenge);
+            }
+            // Most logins have nothing to clear; skip the write for them.
+            if on_record {
+                guard.record_success(&creds.username).await;
+            }
+            ctx.extensions_mut().insert(Principal::new(creds.username));
+            next.run(ctx).await
+        })
+    }
+}
+
+/// Middleware enforcing `Bearer` token authentication.
+///
+/// Constructed with [`bearer_auth`].
+///
+/// # Behavior
+///
+/// - A missing `Authorization` header yields `401` with `WWW-Authenticate: Bearer
+///   realm="<realm>"`.
+/// - A malformed or rejected token yields `401` with the additional
+///   `error="invalid_token"` parameter defined by RFC 6750 §3.1.
+/// - An accepted token inserts the returned [`Principal`] into the request extensions.
+/// - With a [`lockout`](Self::lockout) guard, rejected tokens count as failures for the
+///   client IP, and a locked-out IP gets `429 Too Many Requests` with `Retry-After`.
+pub struct BearerAuthMiddleware {
+    realm: String,
+    verify: BearerVerifier,
+    guard: Option<LoginGuard>,
+}
+
+impl BearerAuthMiddleware {
+    /// Counts rejected tokens with `guard` and refuses locked-out IPs.
+    #[must_use]
+    pub fn lockout(mut self, guard: LoginGuard) -> Self {
+        self.guard = Some(guard);
+        self
+    }
+}
+
+impl Middleware for BearerAuthMiddleware {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let realm = quote(&self.realm);
+        let Some(header) = ctx.request().headers().get("authorization") else {
+            let response = unauthorized(format!("Bearer realm=\"{realm}\""));
+            return Box::pin(async move { response });
+        };
+
+        let invalid = format!("Bearer realm=\"{realm}\", error=\"invalid_token\"");
+        let Some(guard) = self.guard.clone() else {
+            return match parse_bearer(header).and_then(|token| (self.verify)(token)) {
+                Some(id) => {
+                    ctx.extensions_mut().insert(Principal::new(id));
+                    Box::pin(async move { next.run(ctx).await })
+                }
+                None => {
+                    let response = unauthorized(invalid);
+                    Box::pin(async move { response })
+                }
+            };
+        };
+
+        let token = parse_bearer(header).map(str::to_owned);
+        let verify = Arc::clone(&self.verify);
+        Box::pin(async move {
+            let ip = client_ip(&ctx);
+            if let Some(wait) = guard.check(None, ip).await {
+                return locked_out(wait);
+            }
+            match token.and_then(|token| verify(&token)) {
+                Some(id) => {
+                    ctx.extensions_mut().insert(Principal::new(id));
+                    next.run(ctx).await
+                }
+                None => {
+                    guard.record_failure(None, ip).await;
+                    unauthorized(invalid)
+                }
+            }
+        })
+    }
+}
+
+/// Middleware admitting requests that present one of a fixed set of API keys.
+///
+/// Constructed with [`api_key_auth`].
+///
+/// # Behavior
+///
+/// - The key is read from `X-API-Key`, or else from `Authorization: Bearer <key>`, and
+///   compared in constant time.
+/// - A missing or unknown key yields `401` with `WWW-Authenticate: Bearer`, or the
+///   response built by [`on_reject`](Self::on_reject). With no keys configured, every
+///   request is refused.
+/// - An accepted key forwards the request unchanged; no [`Principal`] is inserted.
+#[derive(Clone)]
+pub struct ApiKeyMiddleware {
+    keys: Vec<String>,
+    reject: Rejection,
+}
+
+impl ApiKeyMiddleware {
+    /// Accepts `key` as well.
+    #[must_use]
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.keys.push(key.into());
+        self
+    }
+
+    /// Answers refused requests with `reject()` instead of the default `401`, for APIs
+    /// with their own error format.
+    #[must_use]
+    pub fn on_reject<F>(mut self, reject: F) -> Self
+    where
+        F: Fn() -> Response + Send + Sync + 'static,
+    {
+        self.reject = Arc::new(reject);
+        self
+    }
+
+    /// Returns `true` if `headers` carry one of the accepted keys.
+    pub fn accepts(&self, headers: &Headers) -> bool {
+        let Some(presented) = headers
+            .get("x-api-key")
+            .or_else(|| headers.get("authorization").and_then(parse_bearer))
+        else {
+            return false;
+        };
+        self.keys
+            .iter()
+            .any(|key| constant_time_eq(key.as_bytes(), presented.as_bytes()))
+    }
+}
+
+impl Middleware for ApiKeyMiddleware {
+    fn handle(&self, ctx: Context, next: Next) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        if self.accepts(ctx.request().headers()) {
+            return next.run(ctx);
+        }
+        let response = (self.reject)();
+        Box::pin(async move { response })
+    }
+}
+
+impl std::fmt::Debug for ApiKeyMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKeyMiddleware")
+            .field("keys", &self.keys.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cache::MemoryCache, http::request::Request, middleware::MiddlewareHandler};
+
+    fn context(authorization: Option<&str>) -> Context {
+        let auth = authorization
+            .map(|a| format!("Authorization: {a}\r\n"))
+            .unwrap_or_default();
+        let raw = format!("GET /admin HTTP/1.1\r\nHost: localhost\r\n{auth}\r\n");
+        let (req, _) = Request::parse(raw.as_bytes()).unwrap();
+        Context::new(req)
+    }
+
+    // Terminal handler that echoes the principal id, or 500 if none was set.
+    fn echo_principal() -> MiddlewareHandler {
+        Arc::new(|ctx: Context, _next: Next| {
+            Box::pin(async move {
+                match ctx.extensions().get::<Principal>() {
+                    Some(p) => Response::new(StatusCode::Ok).body(p.id().to_owned()),
+                    None => Response::new(StatusCode::InternalServerError),
+                }
+            })
+        })
+    }
+
+    async fn run(middleware: impl Middleware + 'static, ctx: Context) -> Response {
+        let chain = vec![
+            crate::middleware::from_middleware(Arc::new(middleware)),
+            echo_principal(),
+        ];
+        Next::new(chain).run(ctx).await
+    }
+
+    // ── Parsing ───────────────────────────────────────────────────────────────
+
+    #[test]
+    fn parse_basic_valid() {
+        let creds = parse_basic("basic dXNlcjpwYTpzcw==").unwrap();
+        assert_eq!(creds.username, "user");
+        assert_eq!(creds.password, "pa:ss");
+    }
+
+    #[test]
+    fn parse_basic_rejects_wrong_scheme_and_garbage() {
+        assert_eq!(parse_basic("Bearer dXNlcjpwYXNz"), None);
+        assert_eq!(parse_basic("Basic !!!"), None);
+        assert_eq!(parse_basic("Basic "), None);
+        // "nocolon" has no separator
+        assert_eq!(parse_basic("Basic bm9jb2xvbg=="), None);
+    }
+
+    #[test]
+    fn parse_bearer_trims() {
+        assert_eq!(parse_bearer("  bearer   tok  "), Some("tok"));
+        assert_eq!(parse_bearer("Bearer"), None);
+    }
+
+    // ── BasicAuthMiddleware ───────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn basic_missing_credentials_challenges() {
+        let mw = basic_auth("ops \"internal\"", |_, _| true);
+        let res = run(mw, context(None)).await;
+        assert_eq!(res.status(), StatusCode::Unauthorized);
+        assert_eq!(
+            res.headers().get("www-authenticate"),
+            Some("Basic realm=\"ops \\\"internal\\\"\", charset=\"UTF-8\"")
+        );
+    }
+
+    #[tokio::test]
+    async fn basic_rejected_credentials_challenges() {
+        let mw = basic_auth("ops", |u, p| u == "admin" && p == "secret");
+        // admin:wrong
+        let res = run(mw, context(Some("Basic YWRtaW46d3Jvbmc="))).await;
+        assert_eq!(res.status(), StatusCode::Unauthorized);
+    }
+
+    #[tokio::test]
+    async fn basic_accepted_credentials_set_principal() {
+        let mw = basic_auth("ops", |u, p| u == "admin" && p == "secret");
+        // admin:secret
+        let res = run(mw, context(Some("Basic YWRtaW46c2VjcmV0"))).await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(res.body_ref(), b"admin");
+    }
+
+    // ── BearerAuthMiddleware ──────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn bearer_missing_header_has_plain_challenge() {
+        let mw = bearer_auth("api", |_| Some("x".into()));
+        let res = run(mw, context(None)).await;
+        assert_eq!(res.status(), StatusCode::Unauthorized);
+        assert_eq!(
+            res.headers().get("www-authenticate"),
+            Some("Bearer realm=\"api\"")
+        );
+    }
+
+    #[tokio::test]
+    async fn bearer_invalid_token_reports_error() {
+        let mw = bearer_auth("api", |_| None);
+        let res = run(mw, context(Some("Bearer nope"))).await;
+        assert_eq!(
+            res.headers().get("www-authenticate"),
+            Some("Bearer realm=\"api\", error=\"invalid_token\"")
+        );
+    }
+
+    #[tokio::test]
+    async fn basic_lockout_refuses_after_repeated_failures() {
+        let guard = LoginGuard::new(Arc::new(MemoryCache::new(100))).principal_limit(2);
+        let chain: Arc<[MiddlewareHandler]> = vec![
+            crate::middleware::from_middleware(Arc::new(
+                basic_auth("ops", |u, p| u == "admin" && p == "secret").lockout(guard),
+            )),
+            echo_principal(),
+        ]
+        .into();
+        let send = |auth: &str| Next::new(Arc::clone(&chain)).run(context(Some(auth)));
+
+        // admin:wrong, twice
+        for _ in 0..2 {
+            let res = send("Basic YWRtaW46d3Jvbmc=").await;
+            assert_eq!(res.status(), StatusCode::Unauthorized);
+        }
+        // admin:secret is refused until the lockout ends.
+        let res = send("Basic YWRtaW46c2VjcmV0").await;
+        assert_eq!(res.status(), StatusCode::TooManyRequests);
+        assert!(res.headers().contains("retry-after"));
+    }
+
+    // ── ApiKeyMiddleware ──────────────────────────────────────────────────────
+
+    async fn run_api_key(mw: ApiKeyMiddleware, header: Option<(&str, &str)>) -> Response {
+        let header = header
+            .map(|(name, value)| format!("{name}: {value}\r\n"))
+            .unwrap_or_default();
+        let raw = format!("POST /admin HTTP/1.1\r\nHost: localhost\r\n{header}\r\n");
+        let (req, _) = Request::parse(raw.as_bytes()).unwrap();
+        let ok: MiddlewareHandler = Arc::new(|_ctx: Context, _next: Next| {
+            Box::pin(async { Response::new(StatusCode::Ok) })
+        });
+        let chain = vec![crate::middleware::from_middleware(Arc::new(mw)), ok];
+        Next::new(chain).run(Context::new(req)).await
+    }
+
+    #[tokio::test]
+    async fn api_key_accepts_header_or_bearer() {
+        let mw = api_key_auth(["first"]).key("second");
+        let res = run_api_key(mw.clone(), Some(("X-API-Key", "first"))).await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        let res = run_api_key(mw, Some(("Authorization", "Bearer second"))).await;
+        assert_eq!(res.status(), StatusCode::Ok);
+    }
+
+    #[tokio::test]
+    async fn api_key_rejects_missing_and_unknown_keys() {
+        let mw = api_key_auth(["secret"]);
+        let res = run_api_key(mw.clone(), None).await;
+        assert_eq!(res.status(), StatusCode::Unauthorized);
+        assert_eq!(res.headers().get("www-authenticate"), Some("Bearer"));
+        let res = run_api_key(mw, Some(("X-API-Key", "wrong"))).await;
+        assert_eq!(res.status(), StatusCode::Unauthorized);
+    }
+
+    #[tokio::test]
+    async fn api_key_without_keys_refuses_everything() {
+        let mw = api_key_auth(Vec::<String>::new());
+        let res = run_api_key(mw, Some(("X-API-Key", ""))).await;
+        assert_eq!(res.status(), StatusCode::Unauthorized);
+    }
+
+    #[tokio::test]
+    async fn api_key_uses_the_custom_rejection() {
+        let mw = api_key_auth(["secret"]).on_reject(|| Response::new(StatusCode::Forbidden));
+        let res = run_api_key(mw, None).await;
+        assert_eq!(res.status(), StatusCode::Forbidden);
+    }
+
+    #[tokio::test]
+    async fn bearer_valid_token_sets_principal() {
+        let mw = bearer_auth("api", |t| (t == "tok").then(|| "svc".to_owned()));
+        let res = run(mw, context(Some("Bearer tok"))).await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(res.body_ref(), b"svc");
+    }
+}
@@ -0,0 +1,441 @@
+//! Typed `Content-Security-Policy` builder (CSP Level 3).
+//!
+//! Policies are assembled from [`Source`] values instead of hand-concatenated strings, so
+//! keyword quoting (`'self'`, `'none'`, …) is always right and stray `;` or `,` characters
+//! cannot smuggle extra directives into the header.
+//!
+//! # Examples
+//!
+//! ```
+//! use rttp::security::csp::{ContentSecurityPolicy, Source};
+//!
+//! let csp = ContentSecurityPolicy::new()
+//!     .default_src([Source::SelfOrigin])
+//!     .script_src([Source::SelfOrigin, Source::host("cdn.example.com")])
+//!     .img_src([Source::SelfOrigin, Source::scheme("data")])
+//!     .object_src([Source::None])
+//!     .upgrade_insecure_requests();
+//!
+//! assert_eq!(
+//!     csp.to_string(),
+//!     "default-src 'self'; script-src 'self' cdn.example.com; img-src 'self' data:; \
+//!      object-src 'none'; upgrade-insecure-requests"
+//! );
+//! ```
+
+use std::fmt;
+
+/// A single source expression within a fetch directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// `'none'` — matches nothing. Only meaningful on its own.
+    None,
+    /// `'self'` — the document's own origin.
+    SelfOrigin,
+    /// `'unsafe-inline'` — inline scripts/styles and event handlers.
+    UnsafeInline,
+    /// `'unsafe-eval'` — `eval()` and friends.
+    UnsafeEval,
+    /// `'unsafe-hashes'` — allows hashed inline event handlers.
+    UnsafeHashes,
+    /// `'wasm-unsafe-eval'` — WebAssembly compilation without `'unsafe-eval'`.
+    WasmUnsafeEval,
+    /// `'strict-dynamic'` — trust propagates to scripts loaded by trusted scripts.
+    StrictDynamic,
+    /// `*` — any URL except `data:`, `blob:`, and `filesystem:`.
+    Any,
+    /// A host source such as `cdn.example.com` or `https://*.example.com:443`.
+    Host(String),
+    /// A scheme source such as `data:` or `https:` (stored without the colon).
+    Scheme(String),
+    /// `'nonce-<value>'` — a per-response random nonce (base64).
+    Nonce(String),
+    /// `'sha256-<value>'` — the base64 SHA-256 digest of an allowed inline block.
+    Sha256(String),
+}
+
+impl Source {
+    /// Creates a host source.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `host` is empty or contains whitespace, `;`, `,`, or `'`, any of which
+    /// would break out of the directive.
+    pub fn host(host: impl Into<String>) -> Self {
+        let host = host.into();
+        assert_token(&host, "host");
+        Self::Host(host)
+    }
+
+    /// Creates a scheme source; a trailing `:` is accepted and dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`Source::host`].
+    pub fn scheme(scheme: impl Into<String>) -> Self {
+        let scheme = scheme.into();
+        let scheme = scheme.strip_suffix(':').unwrap_or(&scheme).to_owned();
+        assert_token(&scheme, "scheme");
+        Self::Scheme(scheme)
+    }
+
+    /// Creates a nonce source from an already-encoded nonce value.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`Source::host`].
+    pub fn nonce(nonce: impl Into<String>) -> Self {
+        let nonce = nonce.into();
+        assert_token(&nonce, "nonce");
+        Self::Nonce(nonce)
+    }
+
+    /// Creates a hash source from a base64-encoded SHA-256 digest.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`Source::host`].
+    pub fn sha256(digest: impl Into<String>) -> Self {
+        let digest = digest.into();
+        assert_token(&digest, "hash");
+        Self::Sha256(digest)
+    }
+}
+
+// Rejects values that would terminate the source list or the directive.
+fn assert_token(value: &str, what: &str) {
+    assert!(
+        !value.is_empty()
+            && !value
+                .chars()
+                .any(|c| c.is_whitespace() || matches!(c, ';' | ',' | '\'')),
+        "invalid CSP {what} source: {value:?}"
+    );
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => f.write_str("'none'"),
+            Self::SelfOrigin => f.write_str("'self'"),
+            Self::UnsafeInline => f.write_str("'unsafe-inline'"),
+            Self::UnsafeEval => f.write_str("'unsafe-eval'"),
+            Self::UnsafeHashes => f.write_str("'unsafe-hashes'"),
+            Self::WasmUnsafeEval => f.write_str("'wasm-unsafe-eval'"),
+            Self::StrictDynamic => f.write_str("'strict-dynamic'"),
+            Self::Any => f.write_str("*"),
+            Self::Host(host) => f.write_str(host),
+            Self::Scheme(scheme) => write!(f, "{scheme}:"),
+            Self::Nonce(nonce) => write!(f, "'nonce-{nonce}'"),
+            Self::Sha256(digest) => write!(f, "'sha256-{digest}'"),
+        }
+    }
+}
+
+// One directive: its name and value tokens (empty for boolean directives).
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Directive {
+    name: &'static str,
+    values: Vec<String>,
+}
+
+/// A `Content-Security-Policy` header value.
+///
+/// Directives are rendered in the order they were first set. Setting the same directive
+/// again appends to its source list rather than adding a duplicate (browsers ignore all
+/// but the first occurrence of a directive).
+///
+/// The [`Display`](fmt::Display) implementation renders the header value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContentSecurityPolicy {
+    directives: Vec<Directive>,
+}
+
+macro_rules! fetch_directives {
+    ($($(#[$doc:meta])* $method:ident => $name:literal;)*) => {
+        $(
+            $(#[$doc])*
+            #[must_use]
+            pub fn $method(self, sources: impl IntoIterator<Item = Source>) -> Self {
+                self.sources($name, sources)
+            }
+        )*
+    };
+}
+
+impl ContentSecurityPolicy {
+    /// Creates an empty policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fetch_directives! {
+        /// Sets `default-src`, the fallback for every other fetch directive.
+        default_src => "default-src";
+        /// Sets `script-src`.
+        script_src => "script-src";
+        /// Sets `style-src`.
+        style_src => "style-src";
+        /// Sets `img-src`.
+        img_src => "img-src";
+        /// Sets `connect-src` (fetch, XHR, WebSocket, EventSource).
+        connect_src => "connect-src";
+        /// Sets `font-src`.
+        font_src => "font-src";
+        /// Sets `object-src`; `[Source::None]` is strongly recommended.
+        object_src => "object-src";
+        /// Sets `media-src`.
+        media_src => "media-src";
+        /// Sets `frame-src`.
+        frame_src => "frame-src";
+        /// Sets `worker-src`.
+        worker_src => "worker-src";
+        /// Sets `manifest-src`.
+        manifest_src => "manifest-src";
+        /// Sets `base-uri`, restricting `<base href>`.
+        base_uri => "base-uri";
+        /// Sets `form-action`, restricting form submission targets.
+        form_action => "form-action";
+        /// Sets `frame-ancestors`, the CSP replacement for `X-Frame-Options`.
+        frame_ancestors => "frame-ancestors";
+    }
+
+    /// Adds `upgrade-insecure-requests`, rewriting `http:` subresource URLs to `https:`.
+    #[must_use]
+    pub fn upgrade_insecure_requests(mut self) -> Self {
+        self.directive("upgrade-insecure-requests");
+        self
+    }
+
+    /// Sets `report-uri`, the legacy violation-report endpoint.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `uri` contains whitespace, `;`, `,`, or `'`.
+    #[must_use]
+    pub fn report_uri(mut self, uri: impl Into<String>) -> Self {
+        let uri = uri.into();
+        assert_token(&uri, "report-uri");
+        self.directive("report-uri").values.push(uri);
+        self
+    }
+
+    /// Sets `report-to`, naming a `Reporting-Endpoints` group.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `group` contains whitespace, `;`, `,`, or `'`.
+    #[must_use]
+    pub fn report_to(mut self, group: impl Into<String>) -> Self {
+        let group = group.into();
+        assert_token(&group, "report-to");
+        self.directive("report-to").values.push(group);
+        self
+    }
+
+    /// Allows `nonce` on `script-src` and `style-src`.
+    ///
+    /// A directive that is not set falls back to `default-src`, so the nonce is added
+    /// there instead. Directives that are `'none'` are left alone: a nonce next to
+    /// `'none'` would silently reopen them.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`Source::nonce`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::security::csp::{ContentSecurityPolicy, Source};
+    ///
+    /// let csp = ContentSecurityPolicy::new()
+    ///     .default_src([Source::SelfOrigin])
+    ///     .script_src([Source::SelfOrigin])
+    ///     .with_nonce("r4nd0m");
+    /// assert_eq!(
+    ///     csp.to_string(),
+    ///     "default-src 'self' 'nonce-r4nd0m'; script-src 'self' 'nonce-r4nd0m'"
+    /// );
+    /// ```
+    #[must_use]
+    pub fn with_nonce(mut self, nonce: &str) -> Self {
+        let source = Source::nonce(nonce).to_string();
+        let mut targets = Vec::with_capacity(3);
+        for name in ["script-src", "style-src"] {
+            if self.directives.iter().any(|d| d.name == name) {
+                targets.push(name);
+            } else if !targets.contains(&"default-src") {
+                targets.push("default-src");
+            }
+        }
+        for directive in &mut self.directives {
+            if targets.contains(&directive.name)
+                && directive.values != ["'none'"]
+                && !directive.values.contains(&source)
+            {
+                directive.values.push(source.clone());
+            }
+        }
+        self
+    }
+
+    /// Returns `true` if no directives have been set.
+    pub fn is_empty(&self) -> bool {
+        self.directives.is_empty()
+    }
+
+    /// Returns the rendered source list for `name`, if that directive is set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::security::csp::{ContentSecurityPolicy, Source};
+    ///
+    /// let csp = ContentSecurityPolicy::new().script_src([Source::SelfOrigin]);
+    /// assert_eq!(csp.get("script-src").as_deref(), Some("'self'"));
+    /// assert_eq!(csp.get("style-src"), None);
+    /// ```
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.directives
+            .iter()
+            .find(|d| d.name == name)
+            .map(|d| d.values.join(" "))
+    }
+
+    fn sources(mut self, name: &'static str, sources: impl IntoIterator<Item = Source>) -> Self {
+        let values = &mut self.directive(name).values;
+        for source in sources {
+            let source = source.to_string();
+            if !values.contains(&source) {
+                values.push(source);
+            }
+        }
+        self
+    }
+
+    // Returns the directive called `name`, appending an empty one if it is not set yet.
+    fn directive(&mut self, name: &'static str) -> &mut Directive {
+        let index = match self.directives.iter().position(|d| d.name == name) {
+            Some(index) => index,
+            None => {
+                self.directives.push(Directive {
+                    name,
+                    values: Vec::new(),
+                });
+                self.directives.len() - 1
+            }
+        };
+        &mut self.directives[index]
+    }
+}
+
+impl fmt::Display for ContentSecurityPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, directive) in self.directives.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            f.write_str(directive.name)?;
+            for value in &directive.values {
+                write!(f, " {value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords_are_quoted() {
+        let csp = ContentSecurityPolicy::new().script_src([
+            Source::SelfOrigin,
+            Source::UnsafeInline,
+            Source::StrictDynamic,
+            Source::nonce("abc"),
+            Source::sha256("xyz="),
+        ]);
+        assert_eq!(
+            csp.to_string(),
+            "script-src 'self' 'unsafe-inline' 'strict-dynamic' 'nonce-abc' 'sha256-xyz='"
+        );
+    }
+
+    #[test]
+    fn repeated_directive_appends_without_duplicates() {
+        let csp = ContentSecurityPolicy::new()
+            .default_src([Source::SelfOrigin])
+            .img_src([Source::SelfOrigin])
+            .default_src([Source::SelfOrigin, Source::host("a.example")]);
+        assert_eq!(
+            csp.to_string(),
+            "default-src 'self' a.example; img-src 'self'"
+        );
+    }
+
+    #[test]
+    fn scheme_colon_is_normalized() {
+        let csp =
+            ContentSecurityPolicy::new().img_src([Source::scheme("data:"), Source::scheme("blob")]);
+        assert_eq!(csp.to_string(), "img-src data: blob:");
+    }
+
+    #[test]
+    fn reporting_directives() {
+        let csp = ContentSecurityPolicy::new()
+            .default_src([Source::None])
+            .report_uri("/csp-reports")
+            .report_to("csp");
+        assert_eq!(
+            csp.to_string(),
+            "default-src 'none'; report-uri /csp-reports; report-to csp"
+        );
+    }
+
+    #[test]
+    fn nonce_targets_script_and_style() {
+        let csp = ContentSecurityPolicy::new()
+            .default_src([Source::SelfOrigin])
+            .script_src([Source::SelfOrigin])
+            .style_src([Source::SelfOrigin])
+            .with_nonce("n1");
+        assert_eq!(
+            csp.to_string(),
+            "default-src 'self'; script-src 'self' 'nonce-n1'; style-src 'self' 'nonce-n1'"
+        );
+    }
+
+    #[test]
+    fn nonce_skips_none_and_unset_default() {
+        let csp = ContentSecurityPolicy::new()
+            .default_src([Source::None])
+            .img_src([Source::SelfOrigin])
+            .with_nonce("n1");
+        assert_eq!(csp.to_string(), "default-src 'none'; img-src 'self'");
+
+        let csp = ContentSecurityPolicy::new()
+            .script_src([Source::None])
+            .style_src([Source::SelfOrigin])
+            .with_nonce("n1")
+            .with_nonce("n1");
+        assert_eq!(
+            csp.to_string(),
+            "script-src 'none'; style-src 'self' 'nonce-n1'"
+        );
+    }
+
+    #[test]
+    fn empty_policy() {
+        let csp = ContentSecurityPolicy::new();
+        assert!(csp.is_empty());
+        assert_eq!(csp.to_string(), "");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid CSP host source")]
+    fn host_injection_panics() {
+        let _ = Source::host("evil.com; script-src *");
+    }
+}
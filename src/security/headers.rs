@@ -0,0 +1,505 @@
+//! Security response headers — HSTS, framing, MIME sniffing, referrer, permissions, CSP.
+//!
+//! [`SecureHeadersMiddleware`] adds a hardened default set of headers to every response.
+//! Headers the handler already set are left untouched, so individual routes can relax or
+//! tighten a policy without reconfiguring the middleware.
+//!
+//! With [`csp_nonce`](SecureHeadersMiddleware::csp_nonce) enabled, every request gets a
+//! fresh nonce that handlers read through [`CspNonceExt::csp_nonce`] and embed in their
+//! `<script nonce="…">` and `<style nonce="…">` tags.
+
+use std::{pin::Pin, time::Duration};
+
+use super::{crypto::random_token, csp::ContentSecurityPolicy};
+use crate::{
+    Response,
+    context::Context,
+    middleware::{Middleware, Next},
+};
+
+/// `Strict-Transport-Security` settings.
+///
+/// Browsers ignore the header on plain-HTTP responses, so it is safe to send
+/// unconditionally when the application is served behind TLS termination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hsts {
+    max_age: Duration,
+    include_subdomains: bool,
+    preload: bool,
+}
+
+impl Hsts {
+    /// Creates an HSTS policy with the given `max-age` and no flags.
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            include_subdomains: false,
+            preload: false,
+        }
+    }
+
+    /// Adds `includeSubDomains`.
+    #[must_use]
+    pub fn include_subdomains(mut self) -> Self {
+        self.include_subdomains = true;
+        self
+    }
+
+    /// Adds `preload`, opting in to browser preload lists.
+    ///
+    /// Preload lists require `includeSubDomains` and a `max-age` of at least one year;
+    /// removal from them takes months, so enable this deliberately.
+    #[must_use]
+    pub fn preload(mut self) -> Self {
+        self.preload = true;
+        self
+    }
+
+    /// Renders the header value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use rttp::security::headers::Hsts;
+    ///
+    /// let hsts = Hsts::new(Duration::from_secs(31_536_000)).include_subdomains();
+    /// assert_eq!(hsts.header_value(), "max-age=31536000; includeSubDomains");
+    /// ```
+    pub fn header_value(&self) -> String {
+        let mut value = format!("max-age={}", self.max_age.as_secs());
+        if self.include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        if self.preload {
+            value.push_str("; preload");
+        }
+        value
+    }
+}
+
+impl Default for Hsts {
+    /// One year, including subdomains.
+    fn default() -> Self {
+        Self::new(Duration::from_secs(365 * 24 * 60 * 60)).include_subdomains()
+    }
+}
+
+/// `X-Frame-Options` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameOptions {
+    /// The page may not be framed at all.
+    Deny,
+    /// The page may only be framed by same-origin pages.
+    SameOrigin,
+}
+
+impl FrameOptions {
+    /// Returns the header value.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Deny => "DENY",
+            Self::SameOrigin => "SAMEORIGIN",
+        }
+    }
+}
+
+/// `Referrer-Policy` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferrerPolicy {
+    NoReferrer,
+    NoReferrerWhenDowngrade,
+    Origin,
+    OriginWhenCrossOrigin,
+    SameOrigin,
+    StrictOrigin,
+    StrictOriginWhenCrossOrigin,
+    UnsafeUrl,
+}
+
+impl ReferrerPolicy {
+    /// Returns the header value.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::NoReferrer => "no-referrer",
+            Self::NoReferrerWhenDowngrade => "no-referrer-when-downgrade",
+            Self::Origin => "origin",
+            Self::OriginWhenCrossOrigin => "origin-when-cross-origin",
+            Self::SameOrigin => "same-origin",
+            Self::StrictOrigin => "strict-origin",
+            Self::StrictOriginWhenCrossOrigin => "strict-origin-when-cross-origin",
+            Self::UnsafeUrl => "unsafe-url",
+        }
+    }
+}
+
+/// Middleware that adds security headers to every response.
+///
+/// # Defaults
+///
+/// | Header                      | Value                                          |
+/// |-----------------------------|------------------------------------------------|
+/// | `Strict-Transport-Security` | `max-age=31536000; includeSubDomains`          |
+/// | `X-Content-Type-Options`    | `nosniff`                                      |
+/// | `X-Frame-Options`           | `DENY`                                         |
+/// | `Referrer-Policy`           | `strict-origin-when-cross-origin`              |
+/// | `Permissions-Policy`        | `camera=(), microphone=(), geolocation=()`     |
+///
+/// No `Content-Security-Policy` is sent by default because a useful policy depends on
+/// the application; set one with [`content_security_policy`](Self::content_security_policy)
+/// or start from [`strict`](Self::strict).
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rttp::security::csp::{ContentSecurityPolicy, Source};
+/// use rttp::security::headers::{FrameOptions, SecureHeadersMiddleware};
+///
+/// let headers = SecureHeadersMiddleware::new()
+///     .frame_options(FrameOptions::SameOrigin)
+///     .content_security_policy(
+///         ContentSecurityPolicy::new()
+///             .default_src([Source::SelfOrigin])
+///             .img_src([Source::SelfOrigin, Source::scheme("data")]),
+///     );
+/// ```
+#[derive(Debug, Clone)]
+pub struct SecureHeadersMiddleware {
+    hsts: Option<Hsts>,
+    frame_options: Option<FrameOptions>,
+    referrer_policy: Option<ReferrerPolicy>,
+    permissions_policy: Option<String>,
+    csp: Option<ContentSecurityPolicy>,
+    csp_report_only: bool,
+    csp_nonce: bool,
+}
+
+impl Default for SecureHeadersMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SecureHeadersMiddleware {
+    /// Creates the middleware with the defaults listed above.
+    pub fn new() -> Self {
+        Self {
+            hsts: Some(Hsts::default()),
+            frame_options: Some(FrameOptions::Deny),
+            referrer_policy: Some(ReferrerPolicy::StrictOriginWhenCrossOrigin),
+            permissions_policy: Some("camera=(), microphone=(), geolocation=()".to_owned()),
+            csp: None,
+            csp_report_only: false,
+            csp_nonce: false,
+        }
+    }
+
+    /// The defaults plus a locked-down CSP suited to server-rendered pages with no inline
+    /// scripts: `default-src 'self'; object-src 'none'; base-uri 'self';
+    /// frame-ancestors 'none'`, and `Referrer-Policy: no-referrer`.
+    pub fn strict() -> Self {
+        use super::csp::Source;
+
+        Self::new()
+            .referrer_policy(ReferrerPolicy::NoReferrer)
+            .content_security_policy(
+                ContentSecurityPolicy::new()
+                    .default_src([Source::SelfOrigin])
+                    .object_src([Source::None])
+                    .base_uri([Source::SelfOrigin])
+                    .frame_ancestors([Source::None]),
+            )
+    }
+
+    /// Sets the `Strict-Transport-Security` policy.
+    #[must_use]
+    pub fn hsts(mut self, hsts: Hsts) -> Self {
+        self.hsts = Some(hsts);
+        self
+    }
+
+    /// Stops sending `Strict-Transport-Security`.
+    #[must_use]
+    pub fn without_hsts(mut self) -> Self {
+        self.hsts = None;
+        self
+    }
+
+    /// Sets `X-Frame-Options`.
+    #[must_use]
+    pub fn frame_options(mut self, frame_options: FrameOptions) -> Self {
+        self.frame_options = Some(frame_options);
+        self
+    }
+
+    /// Stops sending `X-Frame-Options`, e.g. when pages are meant to be embedded.
+    #[must_use]
+    pub fn without_frame_options(mut self) -> Self {
+        self.frame_options = None;
+        self
+    }
+
+    /// Sets `Referrer-Policy`.
+    #[must_use]
+    pub fn referrer_policy(mut self, policy: ReferrerPolicy) -> Self {
+        self.referrer_policy = Some(policy);
+        self
+    }
+
+    /// Sets the raw `Permissions-Policy` value, e.g. `"camera=(), fullscreen=(self)"`.
+    #[must_use]
+    pub fn permissions_policy(mut self, policy: impl Into<String>) -> Self {
+        self.permissions_policy = Some(policy.into());
+        self
+    }
+
+    /// Sets an enforced `Content-Security-Policy`.
+    #[must_use]
+    pub fn content_security_policy(mut self, csp: ContentSecurityPolicy) -> Self {
+        self.csp = Some(csp);
+        self.csp_report_only = false;
+        self
+    }
+
+    /// Sets a `Content-Security-Policy-Report-Only` policy, which reports violations
+    /// without blocking — useful while rolling out a new policy.
+    #[must_use]
+    pub fn content_security_policy_report_only(mut self, csp: ContentSecurityPolicy) -> Self {
+        self.csp = Some(csp);
+        self.csp_report_only = true;
+        self
+    }
+
+    /// Generates a nonce for every request, exposes it through
+    /// [`CspNonceExt::csp_nonce`], and adds it to the policy's `script-src` and
+    /// `style-src` (see [`ContentSecurityPolicy::with_nonce`]).
+    ///
+    /// Has no effect on the header unless a policy is also set.
+    #[must_use]
+    pub fn csp_nonce(mut self) -> Self {
+        self.csp_nonce = true;
+        self
+    }
+
+    // Resolves the configured headers to `(name, value)` pairs.
+    fn header_pairs(&self, nonce: Option<&str>) -> Vec<(&'static str, String)> {
+        let mut pairs = vec![("X-Content-Type-Options", "nosniff".to_owned())];
+        if let Some(hsts) = &self.hsts {
+            pairs.push(("Strict-Transport-Security", hsts.header_value()));
+        }
+        if let Some(frame_options) = self.frame_options {
+            pairs.push(("X-Frame-Options", frame_options.as_str().to_owned()));
+        }
+        if let Some(policy) = self.referrer_policy {
+            pairs.push(("Referrer-Policy", policy.as_str().to_owned()));
+        }
+        if let Some(policy) = &self.permissions_policy {
+            pairs.push(("Permissions-Policy", policy.clone()));
+        }
+        if let Some(csp) = self.csp.as_ref().filter(|csp| !csp.is_empty()) {
+            let name = if self.csp_report_only {
+                "Content-Security-Policy-Report-Only"
+            } else {
+                "Content-Security-Policy"
+            };
+            let value = match nonce {
+                Some(nonce) => csp.clone().with_nonce(nonce).to_string(),
+                None => csp.to_string(),
+            };
+            pairs.push((name, value));
+        }
+        pairs
+    }
+}
+
+impl Middleware for SecureHeadersMiddleware {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let nonce = self.csp_nonce.then(|| random_token(16));
+        let pairs = self.header_pairs(nonce.as_deref());
+        if let Some(nonce) = nonce {
+            ctx.extensions_mut().insert(CspNonce(nonce));
+        }
+
+        Box::pin(async move {
+            let mut response = next.run(ctx).await;
+            for (name, value) in pairs {
+                if !response.headers().contains(name) {
+                    response.add_header(name, value);
+                }
+            }
+            response
+        })
+    }
+}
+
+// The per-request nonce stored in the context's extensions.
+#[derive(Debug, Clone)]
+struct CspNonce(String);
+
+/// Access to the per-request CSP nonce from a [`Context`].
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rttp::{Response, StatusCode, context::Context, security::headers::CspNonceExt};
+///
+/// async fn page(ctx: Context) -> Response {
+///     let nonce = ctx.csp_nonce().unwrap_or_default();
+///     Response::new(StatusCode::Ok).body(format!(
+///         "<script nonce=\"{nonce}\" src=\"/app.js\"></script>"
+///     ))
+/// }
+/// ```
+pub trait CspNonceExt {
+    /// Returns the nonce generated by [`SecureHeadersMiddleware::csp_nonce`], if enabled.
+    fn csp_nonce(&self) -> Option<&str>;
+}
+
+impl CspNonceExt for Context {
+    fn csp_nonce(&self) -> Option<&str> {
+        self.extensions()
+            .get::<CspNonce>()
+            .map(|nonce| nonce.0.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        StatusCode,
+        http::request::Request,
+        middleware::{MiddlewareHandler, from_middleware},
+        security::csp::Source,
+    };
+
+    fn context() -> Context {
+        let raw = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let (req, _) = Request::parse(raw).unwrap();
+        Context::new(req)
+    }
+
+    async fn run(middleware: SecureHeadersMiddleware, handler: MiddlewareHandler) -> Response {
+        Next::new(vec![from_middleware(Arc::new(middleware)), handler])
+            .run(context())
+            .await
+    }
+
+    fn ok() -> MiddlewareHandler {
+        Arc::new(|_ctx: Context, _next: Next| Box::pin(async { Response::new(StatusCode::Ok) }))
+    }
+
+    #[tokio::test]
+    async fn default_preset() {
+        let res = run(SecureHeadersMiddleware::new(), ok()).await;
+        let h = res.headers();
+        assert_eq!(
+            h.get("strict-transport-security"),
+            Some("max-age=31536000; includeSubDomains")
+        );
+        assert_eq!(h.get("x-content-type-options"), Some("nosniff"));
+        assert_eq!(h.get("x-frame-options"), Some("DENY"));
+        assert_eq!(
+            h.get("referrer-policy"),
+            Some("strict-origin-when-cross-origin")
+        );
+        assert!(h.contains("permissions-policy"));
+        assert!(!h.contains("content-security-policy"));
+    }
+
+    #[tokio::test]
+    async fn strict_preset_sends_csp() {
+        let res = run(SecureHeadersMiddleware::strict(), ok()).await;
+        assert_eq!(
+            res.headers().get("content-security-policy"),
+            Some("default-src 'self'; object-src 'none'; base-uri 'self'; frame-ancestors 'none'")
+        );
+        assert_eq!(res.headers().get("referrer-policy"), Some("no-referrer"));
+    }
+
+    #[tokio::test]
+    async fn handler_headers_take_precedence() {
+        let handler: MiddlewareHandler = Arc::new(|_ctx: Context, _next: Next| {
+            Box::pin(async {
+                Response::new(StatusCode::Ok).header("X-Frame-Options", "SAMEORIGIN")
+            })
+        });
+        let res = run(SecureHeadersMiddleware::new(), handler).await;
+        assert_eq!(res.headers().get("x-frame-options"), Some("SAMEORIGIN"));
+        assert_eq!(res.headers().get_all("x-frame-options").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn disabled_and_report_only() {
+        let mw = SecureHeadersMiddleware::new()
+            .without_hsts()
+            .without_frame_options()
+            .content_security_policy_report_only(
+                ContentSecurityPolicy::new().default_src([Source::SelfOrigin]),
+            );
+        let res = run(mw, ok()).await;
+        let h = res.headers();
+        assert!(!h.contains("strict-transport-security"));
+        assert!(!h.contains("x-frame-options"));
+        assert!(!h.contains("content-security-policy"));
+        assert_eq!(
+            h.get("content-security-policy-report-only"),
+            Some("default-src 'self'")
+        );
+    }
+
+    fn echo_nonce() -> MiddlewareHandler {
+        Arc::new(|ctx: Context, _next: Next| {
+            let nonce = ctx.csp_nonce().unwrap_or_default().to_owned();
+            Box::pin(async move { Response::new(StatusCode::Ok).body(nonce) })
+        })
+    }
+
+    #[tokio::test]
+    async fn nonce_is_fresh_and_injected() {
+        let mw = SecureHeadersMiddleware::strict().csp_nonce();
+        let first = run(mw.clone(), echo_nonce()).await;
+        let second = run(mw, echo_nonce()).await;
+
+        let nonce = first.body_text().into_owned();
+        assert_eq!(nonce.len(), 22);
+        assert_eq!(
+            first.headers().get("content-security-policy"),
+            Some(
+                format!(
+                    "default-src 'self' 'nonce-{nonce}'; object-src 'none'; base-uri 'self'; \
+                     frame-ancestors 'none'"
+                )
+                .as_str()
+            )
+        );
+        assert_ne!(second.body_text(), nonce);
+    }
+
+    #[tokio::test]
+    async fn nonce_disabled_by_default() {
+        let res = run(SecureHeadersMiddleware::strict(), echo_nonce()).await;
+        assert!(res.body_ref().is_empty());
+        assert!(
+            !res.headers()
+                .get("content-security-policy")
+                .unwrap()
+                .contains("nonce")
+        );
+    }
+
+    #[test]
+    fn hsts_preload() {
+        let hsts = Hsts::default().preload();
+        assert_eq!(
+            hsts.header_value(),
+            "max-age=31536000; includeSubDomains; preload"
+        );
+    }
+}
@@ -0,0 +1,463 @@
+//! Brute-force protection for logins: failure counting and escalating lockouts.
+//!
+//! [`LoginGuard`] counts failed attempts per principal (the username tried) and per
+//! client IP in a [`Cache`], so the counts are shared between instances when the cache
+//! is. Once a principal or an IP reaches its threshold it is locked out, for a period
+//! that doubles with each lockout in a row, and an [`on_lockout`](LoginGuard::on_lockout)
+//! hook is called, for example to email the account owner. A successful login clears
+//! the principal's record; the IP's record only expires, so one valid account cannot
+//! be used to reset the count for password-spraying the rest.
+//!
+//! [`BasicAuthMiddleware`](super::BasicAuthMiddleware) and
+//! [`BearerAuthMiddleware`](super::BearerAuthMiddleware) use a guard given with their
+//! `lockout` builder; login handlers call [`check`](LoginGuard::check),
+//! [`record_failure`](LoginGuard::record_failure), and
+//! [`record_success`](LoginGuard::record_success) themselves.
+//!
+//! Failures are counted with [`Cache::increment`], so concurrent guesses are all
+//! counted and exactly one of them starts each lockout. Cache errors are logged and
+//! treated as no record, so an outage does not lock every user out.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use rttp::cache::MemoryCache;
+//! use rttp::security::basic_auth;
+//! use rttp::security::lockout::LoginGuard;
+//!
+//! let guard = LoginGuard::new(Arc::new(MemoryCache::new(10_000)))
+//!     .on_lockout(|event| tracing::warn!(?event.scope, "notify the account owner"));
+//! let auth = basic_auth("admin", |user, pass| user == "admin" && pass == "hunter2")
+//!     .lockout(guard);
+//! ```
+
+use std::{
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Response, StatusCode,
+    cache::{Cache, CacheExt},
+    clock::{self, Clock},
+};
+
+type LockoutHook = Arc<dyn Fn(&LockoutEvent) + Send + Sync>;
+
+/// What a lockout applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockScope {
+    /// A username or other account identifier.
+    Principal(String),
+    /// A client address.
+    Ip(IpAddr),
+}
+
+/// A lockout that has just begun, passed to the [`LoginGuard::on_lockout`] hook.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockoutEvent {
+    /// What is locked out.
+    pub scope: LockScope,
+    /// How many lockouts in a row this is, starting at 1.
+    pub lockouts: u32,
+    /// How long it lasts.
+    pub duration: Duration,
+}
+
+// The lockout record of one principal or IP, stored beside its failure counter.
+#[derive(Debug, Serialize, Deserialize)]
+struct Lock {
+    lockouts: u32,
+    // Unix milliseconds.
+    locked_until: u64,
+}
+
+/// Counts failed logins and locks out principals and IPs that fail too often.
+///
+/// Clones share the same cache, settings, and hook. Defaults: 5 failures lock a
+/// principal out and 20 lock an IP out, the first lockout lasts 30 seconds and each
+/// one after doubles up to an hour, and failures are forgotten 15 minutes after the
+/// last one. The doubling restarts once 15 minutes pass after a lockout ends.
+#[derive(Clone)]
+pub struct LoginGuard {
+    cache: Arc<dyn Cache>,
+    clock: Arc<dyn Clock>,
+    prefix: String,
+    principal_limit: u32,
+    ip_limit: u32,
+    base: Duration,
+    max: Duration,
+    window: Duration,
+    hook: Option<LockoutHook>,
+}
+
+impl LoginGuard {
+    /// Creates a guard keeping its counts in `cache`.
+    pub fn new(cache: Arc<dyn Cache>) -> Self {
+        Self {
+            cache,
+            clock: clock::system(),
+            prefix: "lockout:".to_owned(),
+            principal_limit: 5,
+            ip_limit: 20,
+            base: Duration::from_secs(30),
+            max: Duration::from_secs(60 * 60),
+            window: Duration::from_secs(15 * 60),
+            hook: None,
+        }
+    }
+
+    /// Sets how many failures in a row lock a principal out.
+    #[must_use]
+    pub fn principal_limit(mut self, failures: u32) -> Self {
+        self.principal_limit = failures.max(1);
+        self
+    }
+
+    /// Sets how many failures from one IP lock it out.
+    #[must_use]
+    pub fn ip_limit(mut self, failures: u32) -> Self {
+        self.ip_limit = failures.max(1);
+        self
+    }
+
+    /// Sets the length of the first lockout and the cap the doubling stops at.
+    #[must_use]
+    pub fn lockout(mut self, first: Duration, max: Duration) -> Self {
+        self.base = first;
+        self.max = max.max(first);
+        self
+    }
+
+    /// Sets how long after the last failure the counts are forgotten, and how long
+    /// after a lockout ends it still counts towards doubling the next.
+    #[must_use]
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets the prefix of the cache keys. Defaults to `"lockout:"`.
+    #[must_use]
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    #[must_use]
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Calls `hook` whenever a lockout begins.
+    #[must_use]
+    pub fn on_lockout(mut self, hook: impl Fn(&LockoutEvent) + Send + Sync + 'static) -> Self {
+        self.hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Returns how much longer `principal` or `ip` is locked out, whichever is longer,
+    /// or `None` if neither is.
+    pub async fn check(&self, principal: Option<&str>, ip: Option<IpAddr>) -> Option<Duration> {
+        let principal = match principal {
+            Some(principal) => self.load(&principal_subject(principal)).await,
+            None => None,
+        };
+        let ip = match ip {
+            Some(ip) => self.load(&ip_subject(ip)).await,
+            None => None,
+        };
+        self.remaining([principal, ip])
+    }
+
+    // Like `check` for a login by `principal`, also reporting whether the principal
+    // has failures or lockouts on record for `record_success` to clear.
+    pub(crate) async fn standing(
+        &self,
+        principal: &str,
+        ip: Option<IpAddr>,
+    ) -> (Option<Duration>, bool) {
+        let subject = principal_subject(principal);
+        let lock = self.load(&subject).await;
+        let on_record = match lock {
+            Some(_) => true,
+            None => match self.cache.get(&self.failures_key(&subject)).await {
+                Ok(failures) => failures.is_some(),
+                Err(e) => {
+                    tracing::error!(error = %e, "failed to read login failures");
+                    false
+                }
+            },
+        };
+        let ip = match ip {
+            Some(ip) => self.load(&ip_subject(ip)).await,
+            None => None,
+        };
+        (self.remaining([lock, ip]), on_record)
+    }
+
+    /// Counts a failed login by `principal` from `ip`, locking either out if it has
+    /// reached its limit.
+    pub async fn record_failure(&self, principal: Option<&str>, ip: Option<IpAddr>) {
+        if let Some(principal) = principal {
+            let scope = LockScope::Principal(principal.to_owned());
+            self.fail(&principal_subject(principal), scope, self.principal_limit)
+                .await;
+        }
+        if let Some(ip) = ip {
+            self.fail(&ip_subject(ip), LockScope::Ip(ip), self.ip_limit)
+                .await;
+        }
+    }
+
+    /// Clears the failures and lockout history of `principal` after a successful login.
+    pub async fn record_success(&self, principal: &str) {
+        let subject = principal_subject(principal);
+        for key in [self.lock_key(&subject), self.failures_key(&subject)] {
+            if let Err(e) = self.cache.delete(&key).await {
+                tracing::error!(error = %e, "failed to clear login failures");
+            }
+        }
+    }
+
+    async fn fail(&self, subject: &str, scope: LockScope, limit: u32) {
+        let key = self.failures_key(subject);
+        let failures = match self.cache.increment(&key, 1, Some(self.window)).await {
+            Ok(failures) => failures,
+            Err(e) => {
+                tracing::error!(error = %e, "failed to record login failure");
+                return;
+            }
+        };
+        // Only the failure that reaches a multiple of the limit starts a lockout, so
+        // concurrent failures can neither skip one nor start it twice.
+        if failures % u64::from(limit) != 0 {
+            return;
+        }
+        let lockouts = self.load(subject).await.map_or(0, |lock| lock.lockouts) + 1;
+        let factor = 2u32.saturating_pow(lockouts - 1);
+        let duration = self.base.saturating_mul(factor).min(self.max);
+        let lock = Lock {
+            lockouts,
+            locked_until: self.now_millis() + duration.as_millis() as u64,
+        };
+        // Kept while locked, and for the window after, so lockouts keep escalating.
+        let ttl = Some(duration + self.window);
+        if let Err(e) = self
+            .cache
+            .set_json(&self.lock_key(subject), &lock, ttl)
+            .await
+        {
+            tracing::error!(error = %e, "failed to record lockout");
+        }
+        let event = LockoutEvent {
+            scope,
+            lockouts,
+            duration,
+        };
+        tracing::warn!(scope = ?event.scope, lockouts = event.lockouts, duration = ?event.duration, "login locked out");
+        if let Some(hook) = &self.hook {
+            hook(&event);
+        }
+    }
+
+    // Reads the lockout record of `subject`.
+    async fn load(&self, subject: &str) -> Option<Lock> {
+        match self.cache.get_json::<Lock>(&self.lock_key(subject)).await {
+            Ok(lock) => lock,
+            Err(e) => {
+                tracing::error!(error = %e, "failed to read login lockout");
+                None
+            }
+        }
+    }
+
+    // The longest lockout still running among `locks`.
+    fn remaining(&self, locks: [Option<Lock>; 2]) -> Option<Duration> {
+        let now = self.now_millis();
+        locks
+            .into_iter()
+            .flatten()
+            .filter(|lock| lock.locked_until > now)
+            .map(|lock| Duration::from_millis(lock.locked_until - now))
+            .max()
+    }
+
+    // Failure counters and lockout records live in separate namespaces, so no
+    // principal's key can coincide with another's.
+    fn failures_key(&self, subject: &str) -> String {
+        format!("{}fail:{subject}", self.prefix)
+    }
+
+    fn lock_key(&self, subject: &str) -> String {
+        format!("{}lock:{subject}", self.prefix)
+    }
+
+    fn now_millis(&self) -> u64 {
+        self.clock
+            .system_time()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64)
+    }
+}
+
+// Identifies a principal in cache keys; the length prefix keeps a name containing `:`
+// from reading as another name followed by more fields.
+fn principal_subject(principal: &str) -> String {
+    format!("principal:{}:{principal}", principal.len())
+}
+
+fn ip_subject(ip: IpAddr) -> String {
+    format!("ip:{ip}")
+}
+
+/// Builds the `429 Too Many Requests` response for a login locked out for `wait`.
+pub fn locked_out(wait: Duration) -> Response {
+    let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    Response::new(StatusCode::TooManyRequests)
+        .header("Retry-After", seconds.to_string())
+        .body("Too many failed login attempts")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{cache::MemoryCache, clock::MockClock};
+
+    const IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::LOCALHOST);
+
+    fn guard(clock: &MockClock) -> LoginGuard {
+        let cache = MemoryCache::new(100).clock(Arc::new(clock.clone()));
+        LoginGuard::new(Arc::new(cache))
+            .clock(Arc::new(clock.clone()))
+            .principal_limit(3)
+            .ip_limit(5)
+            .lockout(Duration::from_secs(10), Duration::from_secs(25))
+    }
+
+    #[tokio::test]
+    async fn locks_out_with_doubling_durations() {
+        let clock = MockClock::new();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        let guard = guard(&clock).on_lockout(move |event| {
+            recorded.lock().unwrap().push(event.clone());
+        });
+
+        for expected in [10, 20, 25] {
+            for _ in 0..3 {
+                assert_eq!(guard.check(Some("ada"), None).await, None);
+                guard.record_failure(Some("ada"), None).await;
+            }
+            let wait = Duration::from_secs(expected);
+            assert_eq!(guard.check(Some("ada"), None).await, Some(wait));
+            clock.advance(wait);
+        }
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2].scope, LockScope::Principal("ada".into()));
+        assert_eq!(events[2].lockouts, 3);
+    }
+
+    #[tokio::test]
+    async fn success_clears_the_principal_but_not_the_ip() {
+        let clock = MockClock::new();
+        let guard = guard(&clock);
+        for user in ["a", "b", "c", "d"] {
+            guard.record_failure(Some(user), Some(IP)).await;
+        }
+        guard.record_success("a").await;
+        guard.record_failure(Some("a"), Some(IP)).await;
+        assert_eq!(
+            guard.check(None, Some(IP)).await,
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(guard.check(Some("a"), None).await, None);
+    }
+
+    #[tokio::test]
+    async fn principals_cannot_reach_each_others_records() {
+        let clock = MockClock::new();
+        let guard = guard(&clock);
+        for _ in 0..3 {
+            guard.record_failure(Some("ada"), None).await;
+        }
+        // "ada:lock" once named the cache key of ada's lockout.
+        guard.record_success("ada:lock").await;
+        assert_eq!(
+            guard.check(Some("ada"), None).await,
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(guard.check(Some("ada:lock"), None).await, None);
+    }
+
+    #[tokio::test]
+    async fn failures_are_forgotten_after_the_window() {
+        let clock = MockClock::new();
+        let guard = guard(&clock).window(Duration::from_secs(60));
+        for _ in 0..3 {
+            guard.record_failure(Some("ada"), None).await;
+        }
+        clock.advance(Duration::from_secs(10 + 61));
+        for _ in 0..3 {
+            guard.record_failure(Some("ada"), None).await;
+        }
+        assert_eq!(
+            guard.check(Some("ada"), None).await,
+            Some(Duration::from_secs(10)),
+            "escalation restarts"
+        );
+    }
+
+    #[tokio::test]
+    async fn concurrent_failures_are_all_counted() {
+        let clock = MockClock::new();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        let guard = guard(&clock).on_lockout(move |event| {
+            recorded.lock().unwrap().push(event.lockouts);
+        });
+
+        let tasks: Vec<_> = (0..9)
+            .map(|_| {
+                let guard = guard.clone();
+                tokio::spawn(async move { guard.record_failure(Some("ada"), None).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        // Nine failures against a limit of three: exactly three lockouts.
+        let mut events = events.lock().unwrap().clone();
+        events.sort_unstable();
+        assert_eq!(events, [1, 2, 3]);
+        assert!(guard.check(Some("ada"), None).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn standing_reports_records_to_clear() {
+        let clock = MockClock::new();
+        let guard = guard(&clock);
+        assert_eq!(guard.standing("ada", Some(IP)).await, (None, false));
+
+        guard.record_failure(Some("ada"), Some(IP)).await;
+        assert_eq!(guard.standing("ada", Some(IP)).await, (None, true));
+        guard.record_success("ada").await;
+        assert_eq!(guard.standing("ada", Some(IP)).await, (None, false));
+    }
+
+    #[test]
+    fn locked_out_rounds_retry_after_up() {
+        let response = locked_out(Duration::from_millis(1500));
+        assert_eq!(response.status(), StatusCode::TooManyRequests);
+        assert_eq!(response.headers().get("retry-after"), Some("2"));
+    }
+}
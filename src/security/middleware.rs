@@ -4,7 +4,8 @@
 //! Currently implemented:
 //!
 //! - [`CorsMiddleware`] — Cross-Origin Resource Sharing header injection and
-//!   preflight (`OPTIONS`) short-circuiting.
+//!   preflight (`OPTIONS`) short-circuiting, with credentialed requests, exposed headers,
+//!   and pattern/predicate origin matching.
 //!
 //! ## Planned Features
 //!
@@ -12,9 +13,8 @@
 //! - API key validation
 //! - Per-route rate limiting (token bucket / sliding window)
 //! - CSRF protection
-//! - Secure header injection (HSTS, CSP, X-Frame-Options)
 
-use std::pin::Pin;
+use std::{pin::Pin, sync::Arc, time::Duration};
 
 use crate::{
     Response,
@@ -27,7 +27,10 @@ use crate::{
 ///
 /// Constructed via [`CorsMiddleware::new`] and further configured through the
 /// builder methods [`allow_origin`](Self::allow_origin),
-/// [`allow_method`](Self::allow_method), and [`allow_header`](Self::allow_header).
+/// [`allow_origin_pattern`](Self::allow_origin_pattern),
+/// [`allow_origin_fn`](Self::allow_origin_fn), [`allow_method`](Self::allow_method),
+/// [`allow_header`](Self::allow_header), [`expose_header`](Self::expose_header),
+/// [`allow_credentials`](Self::allow_credentials), and [`max_age`](Self::max_age).
 ///
 /// # Behavior
 ///
@@ -39,6 +42,12 @@ use crate::{
 ///   to the response.
 /// - When the wildcard origin `"*"` is used, a `Vary: Origin` header is **not** added;
 ///   for specific origins it is added to ensure correct cache behavior.
+/// - When credentials are allowed, the wildcard origin is ignored: browsers reject
+///   `Access-Control-Allow-Origin: *` on credentialed responses, and reflecting any origin
+///   instead would let every site make authenticated requests. Only origins matched by
+///   [`allow_origin`](Self::allow_origin) (with a concrete value),
+///   [`allow_origin_pattern`](Self::allow_origin_pattern), or
+///   [`allow_origin_fn`](Self::allow_origin_fn) are accepted.
 ///
 /// # Examples
 ///
@@ -51,9 +60,56 @@ use crate::{
 ///     .allow_header("X-Custom-Header");
 /// ```
 pub struct CorsMiddleware {
-    allowed_origins: Vec<String>,
+    allowed_origins: Vec<OriginMatcher>,
     allowed_methods: Vec<String>,
     allowed_headers: Vec<String>,
+    exposed_headers: Vec<String>,
+    allow_credentials: bool,
+    max_age: Duration,
+}
+
+// A single entry in the origin allow-list.
+#[derive(Clone)]
+enum OriginMatcher {
+    Any,
+    Exact(String),
+    Pattern(String),
+    Predicate(Arc<dyn Fn(&str) -> bool + Send + Sync>),
+}
+
+impl OriginMatcher {
+    fn matches(&self, origin: &str) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Exact(allowed) => allowed == origin,
+            Self::Pattern(pattern) => origin_pattern_matches(pattern, origin),
+            Self::Predicate(predicate) => predicate(origin),
+        }
+    }
+}
+
+// Matches `origin` against a pattern in which each `*` stands for one or more host
+// characters (letters, digits, `-`, `.`). The wildcard never spans `:` or `/`, so
+// `https://*.example.com` cannot match `https://evil.com:443` or a different scheme.
+fn origin_pattern_matches(pattern: &str, origin: &str) -> bool {
+    fn is_host_char(b: u8) -> bool {
+        b.is_ascii_alphanumeric() || b == b'-' || b == b'.'
+    }
+
+    fn go(pattern: &[u8], origin: &[u8]) -> bool {
+        match pattern.split_first() {
+            None => origin.is_empty(),
+            Some((b'*', rest)) => {
+                let run = origin.iter().take_while(|&&b| is_host_char(b)).count();
+                (1..=run).any(|n| go(rest, &origin[n..]))
+            }
+            Some((&p, rest)) => origin
+                .split_first()
+                .is_some_and(|(&o, tail)| o.eq_ignore_ascii_case(&p) && go(rest, tail)),
+        }
+    }
+
+    go(pattern.as_bytes(), origin.as_bytes())
 }
 
 impl Default for CorsMiddleware {
@@ -73,6 +129,9 @@ impl CorsMiddleware {
     /// | Allowed origins  | `*` (all origins)                      |
     /// | Allowed methods  | `GET`, `POST`, `PUT`, `DELETE`         |
     /// | Allowed headers  | `Content-Type`, `Authorization`        |
+    /// | Exposed headers  | *(none)*                               |
+    /// | Credentials      | not allowed                            |
+    /// | Preflight max age| 1 hour                                 |
     ///
     /// # Examples
     ///
@@ -83,7 +142,7 @@ impl CorsMiddleware {
     /// ```
     pub fn new() -> Self {
         Self {
-            allowed_origins: vec!["*".to_string()],
+            allowed_origins: vec![OriginMatcher::Any],
             allowed_methods: vec![
                 "GET".to_string(),
                 "POST".to_string(),
@@ -91,6 +150,9 @@ impl CorsMiddleware {
                 "DELETE".to_string(),
             ],
             allowed_headers: vec!["Content-Type".to_string(), "Authorization".to_string()],
+            exposed_headers: Vec::new(),
+            allow_credentials: false,
+            max_age: Duration::from_secs(3600),
         }
     }
 
@@ -115,7 +177,65 @@ impl CorsMiddleware {
     /// ```
     #[must_use]
     pub fn allow_origin(mut self, origin: impl Into<String>) -> Self {
-        self.allowed_origins.push(origin.into());
+        let origin = origin.into();
+        self.allowed_origins.push(if origin == "*" {
+            OriginMatcher::Any
+        } else {
+            OriginMatcher::Exact(origin)
+        });
+        self
+    }
+
+    /// Adds an allowed origin pattern in which `*` matches one or more host characters.
+    ///
+    /// Useful for preview deployments and tenant subdomains that cannot be listed up
+    /// front. The wildcard only matches letters, digits, `-`, and `.`, so it cannot cross
+    /// into the scheme or port. Matching is ASCII case-insensitive.
+    ///
+    /// # Arguments
+    ///
+    /// - `pattern` — an origin with wildcards (e.g. `"https://*.example.com"`).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rttp::security::CorsMiddleware;
+    ///
+    /// let cors = CorsMiddleware::new()
+    ///     .allow_origin_pattern("https://*.preview.example.com")
+    ///     .allow_credentials(true);
+    /// ```
+    #[must_use]
+    pub fn allow_origin_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.allowed_origins
+            .push(OriginMatcher::Pattern(pattern.into()));
+        self
+    }
+
+    /// Adds a predicate that decides whether an origin is allowed.
+    ///
+    /// The predicate receives the raw `Origin` header value and is consulted after the
+    /// exact and pattern entries registered before it.
+    ///
+    /// # Arguments
+    ///
+    /// - `predicate` — returns `true` for allowed origins.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rttp::security::CorsMiddleware;
+    ///
+    /// let cors = CorsMiddleware::new()
+    ///     .allow_origin_fn(|origin| origin.starts_with("http://localhost:"));
+    /// ```
+    #[must_use]
+    pub fn allow_origin_fn<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.allowed_origins
+            .push(OriginMatcher::Predicate(Arc::new(predicate)));
         self
     }
 
@@ -163,6 +283,93 @@ impl CorsMiddleware {
         self.allowed_headers.push(header.into());
         self
     }
+
+    /// Adds a response header that browser scripts are allowed to read.
+    ///
+    /// Sent in `Access-Control-Expose-Headers` on actual (non-preflight) responses.
+    /// Without it, scripts only see the CORS-safelisted response headers.
+    ///
+    /// # Arguments
+    ///
+    /// - `header` — an HTTP header name (e.g. `"X-Total-Count"`).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rttp::security::CorsMiddleware;
+    ///
+    /// let cors = CorsMiddleware::new().expose_header("X-Total-Count");
+    /// ```
+    #[must_use]
+    pub fn expose_header(mut self, header: impl Into<String>) -> Self {
+        self.exposed_headers.push(header.into());
+        self
+    }
+
+    /// Allows or forbids credentialed requests (cookies, HTTP auth, client certificates).
+    ///
+    /// When enabled, responses carry `Access-Control-Allow-Credentials: true` and always
+    /// echo the concrete request origin; the wildcard origin stops matching (see the
+    /// type-level docs).
+    ///
+    /// # Arguments
+    ///
+    /// - `allow` — whether credentialed requests are allowed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rttp::security::CorsMiddleware;
+    ///
+    /// let cors = CorsMiddleware::new()
+    ///     .allow_origin("https://app.example.com")
+    ///     .allow_credentials(true);
+    /// ```
+    #[must_use]
+    pub fn allow_credentials(mut self, allow: bool) -> Self {
+        self.allow_credentials = allow;
+        self
+    }
+
+    /// Sets how long browsers may cache a preflight result (`Access-Control-Max-Age`).
+    ///
+    /// Sent in whole seconds. Browsers cap this value (Chromium at two hours).
+    ///
+    /// # Arguments
+    ///
+    /// - `max_age` — the preflight cache lifetime.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use rttp::security::CorsMiddleware;
+    ///
+    /// let cors = CorsMiddleware::new().max_age(Duration::from_secs(600));
+    /// ```
+    #[must_use]
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    // Resolves the `Access-Control-Allow-Origin` value for `origin`, or `None` if the
+    // origin is not allowed. The wildcard never applies to credentialed configurations.
+    fn allowed_origin(&self, origin: &str) -> Option<String> {
+        if !self.allow_credentials
+            && self
+                .allowed_origins
+                .iter()
+                .any(|m| matches!(m, OriginMatcher::Any))
+        {
+            return Some("*".to_owned());
+        }
+        self.allowed_origins
+            .iter()
+            .filter(|m| !matches!(m, OriginMatcher::Any))
+            .any(|m| m.matches(origin))
+            .then(|| origin.to_owned())
+    }
 }
 
 impl Middleware for CorsMiddleware {
@@ -177,9 +384,13 @@ impl Middleware for CorsMiddleware {
     ///    `Access-Control-Allow-Headers`, and `Access-Control-Max-Age` headers set.
     ///    The downstream handler is **not** called.
     /// 3. **Actual request** — calls the next handler and appends
-    ///    `Access-Control-Allow-Origin`, `Access-Control-Allow-Methods`, and
-    ///    `Access-Control-Allow-Headers` to its response. A `Vary: Origin` header is
-    ///    added when a specific (non-wildcard) origin is echoed back.
+    ///    `Access-Control-Allow-Origin`, `Access-Control-Allow-Methods`,
+    ///    `Access-Control-Allow-Headers`, and (if configured)
+    ///    `Access-Control-Expose-Headers` to its response.
+    ///
+    /// In both allowed cases `Access-Control-Allow-Credentials: true` is added when
+    /// credentials are enabled, and a `Vary: Origin` header is added when a specific
+    /// (non-wildcard) origin is echoed back.
     ///
     /// # Arguments
     ///
@@ -193,45 +404,39 @@ impl Middleware for CorsMiddleware {
     /// A [`Response`] with CORS headers applied, or the unmodified downstream
     /// response when the origin check does not pass.
     fn handle(&self, ctx: Context, next: Next) -> Pin<Box<dyn Future<Output = Response> + Send>> {
-        let allowed_origins = self.allowed_origins.clone();
-        let allowed_methods = self.allowed_methods.clone();
-        let allowed_headers = self.allowed_headers.clone();
-
-        Box::pin(async move {
-            let request_origin = ctx.request().headers().get("origin").map(str::to_owned);
-            let is_preflight = ctx.request().method() == &crate::Method::Options;
-            let Some(origin) = request_origin else {
-                return next.run(ctx).await;
-            };
+        let request_origin = ctx.request().headers().get("origin");
+        let Some(allow_origin) = request_origin.and_then(|origin| self.allowed_origin(origin))
+        else {
+            return next.run(ctx);
+        };
 
-            let allow_origin = if allowed_origins.iter().any(|o| o == "*") {
-                "*".to_owned()
-            } else if allowed_origins.contains(&origin) {
-                origin.clone()
-            } else {
-                return next.run(ctx).await;
-            };
+        let is_preflight = ctx.request().method() == &crate::Method::Options;
+        let methods_str = self.allowed_methods.join(", ");
+        let headers_str = self.allowed_headers.join(", ");
+        let exposed_str = self.exposed_headers.join(", ");
+        let allow_credentials = self.allow_credentials;
+        let max_age = self.max_age.as_secs().to_string();
 
-            let methods_str = allowed_methods.join(", ");
-            let headers_str = allowed_headers.join(", ");
+        Box::pin(async move {
             let is_wildcard = allow_origin == "*";
 
-            if is_preflight {
-                let mut resp = Response::new(crate::StatusCode::NoContent)
-                    .header("Access-Control-Allow-Origin", &allow_origin)
-                    .header("Access-Control-Allow-Methods", &methods_str)
-                    .header("Access-Control-Allow-Headers", &headers_str)
-                    .header("Access-Control-Max-Age", "3600");
-                if !is_wildcard {
-                    resp.add_header("Vary", "Origin");
+            let mut resp = if is_preflight {
+                Response::new(crate::StatusCode::NoContent)
+                    .header("Access-Control-Max-Age", max_age)
+            } else {
+                let mut resp = next.run(ctx).await;
+                if !exposed_str.is_empty() {
+                    resp.add_header("Access-Control-Expose-Headers", &exposed_str);
                 }
-                return resp;
-            }
+                resp
+            };
 
-            let mut resp = next.run(ctx).await;
             resp.add_header("Access-Control-Allow-Origin", &allow_origin);
             resp.add_header("Access-Control-Allow-Methods", &methods_str);
             resp.add_header("Access-Control-Allow-Headers", &headers_str);
+            if allow_credentials {
+                resp.add_header("Access-Control-Allow-Credentials", "true");
+            }
             if !is_wildcard {
                 resp.add_header("Vary", "Origin");
             }
@@ -239,3 +444,124 @@ impl Middleware for CorsMiddleware {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{StatusCode, http::request::Request, middleware::from_middleware};
+
+    fn context(method: &str, origin: Option<&str>) -> Context {
+        let origin = origin
+            .map(|o| format!("Origin: {o}\r\n"))
+            .unwrap_or_default();
+        let raw = format!("{method} /api HTTP/1.1\r\nHost: localhost\r\n{origin}\r\n");
+        let (req, _) = Request::parse(raw.as_bytes()).unwrap();
+        Context::new(req)
+    }
+
+    async fn run(cors: CorsMiddleware, ctx: Context) -> Response {
+        let ok: crate::middleware::MiddlewareHandler = Arc::new(|_ctx: Context, _next: Next| {
+            Box::pin(async { Response::new(StatusCode::Ok) })
+        });
+        Next::new(vec![from_middleware(Arc::new(cors)), ok])
+            .run(ctx)
+            .await
+    }
+
+    // ── Origin matching ───────────────────────────────────────────────────────
+
+    #[test]
+    fn pattern_matches_subdomains_only() {
+        let p = "https://*.example.com";
+        assert!(origin_pattern_matches(p, "https://app.example.com"));
+        assert!(origin_pattern_matches(
+            p,
+            "https://pr-42.preview.example.com"
+        ));
+        assert!(origin_pattern_matches(p, "HTTPS://App.Example.com"));
+        assert!(!origin_pattern_matches(p, "https://example.com"));
+        assert!(!origin_pattern_matches(p, "http://app.example.com"));
+        assert!(!origin_pattern_matches(
+            p,
+            "https://app.example.com.evil.io"
+        ));
+        assert!(!origin_pattern_matches(p, "https://evil.io:1/.example.com"));
+    }
+
+    #[tokio::test]
+    async fn wildcard_origin_without_credentials() {
+        let res = run(CorsMiddleware::new(), context("GET", Some("https://a.io"))).await;
+        assert_eq!(res.headers().get("access-control-allow-origin"), Some("*"));
+        assert!(!res.headers().contains("vary"));
+        assert!(!res.headers().contains("access-control-allow-credentials"));
+    }
+
+    #[tokio::test]
+    async fn no_origin_passes_through() {
+        let res = run(CorsMiddleware::new(), context("GET", None)).await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert!(!res.headers().contains("access-control-allow-origin"));
+    }
+
+    // ── Credentials ───────────────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn credentials_ignore_wildcard() {
+        let cors = CorsMiddleware::new().allow_credentials(true);
+        let res = run(cors, context("GET", Some("https://evil.io"))).await;
+        assert!(!res.headers().contains("access-control-allow-origin"));
+    }
+
+    #[tokio::test]
+    async fn credentials_echo_matched_origin() {
+        let cors = CorsMiddleware::new()
+            .allow_origin_pattern("https://*.example.com")
+            .allow_credentials(true);
+        let res = run(cors, context("GET", Some("https://pr-7.example.com"))).await;
+        let h = res.headers();
+        assert_eq!(
+            h.get("access-control-allow-origin"),
+            Some("https://pr-7.example.com")
+        );
+        assert_eq!(h.get("access-control-allow-credentials"), Some("true"));
+        assert_eq!(h.get("vary"), Some("Origin"));
+    }
+
+    #[tokio::test]
+    async fn predicate_origin() {
+        let cors = CorsMiddleware::new()
+            .allow_origin_fn(|o| o.starts_with("http://localhost:"))
+            .allow_credentials(true);
+        let res = run(cors, context("GET", Some("http://localhost:5173"))).await;
+        assert_eq!(
+            res.headers().get("access-control-allow-origin"),
+            Some("http://localhost:5173")
+        );
+    }
+
+    // ── Preflight and exposed headers ─────────────────────────────────────────
+
+    #[tokio::test]
+    async fn preflight_uses_max_age_and_skips_expose() {
+        let cors = CorsMiddleware::new()
+            .max_age(Duration::from_secs(600))
+            .expose_header("X-Total-Count");
+        let res = run(cors, context("OPTIONS", Some("https://a.io"))).await;
+        assert_eq!(res.status(), StatusCode::NoContent);
+        assert_eq!(res.headers().get("access-control-max-age"), Some("600"));
+        assert!(!res.headers().contains("access-control-expose-headers"));
+    }
+
+    #[tokio::test]
+    async fn actual_response_exposes_headers() {
+        let cors = CorsMiddleware::new()
+            .expose_header("X-Total-Count")
+            .expose_header("ETag");
+        let res = run(cors, context("GET", Some("https://a.io"))).await;
+        assert_eq!(
+            res.headers().get("access-control-expose-headers"),
+            Some("X-Total-Count, ETag")
+        );
+        assert!(!res.headers().contains("access-control-max-age"));
+    }
+}
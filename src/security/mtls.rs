@@ -0,0 +1,150 @@
+//! Mutual-TLS client authentication — peer certificate identities and principal mapping.
+//!
+//! When a TLS listener is configured to request client certificates (see
+//! [`TlsConfig`](crate::server::tls::TlsConfig)), the verified chain is parsed into
+//! [`PeerCertificate`]s and exposed on every request through
+//! [`ConnectionInfo`](crate::http::ConnectionInfo). [`ClientCertMiddleware`] then maps
+//! the leaf certificate to an application [`Principal`].
+
+use std::{pin::Pin, sync::Arc};
+
+use super::auth::Principal;
+pub use crate::http::certificate::{DistinguishedName, PeerCertificate, SubjectAltName};
+use crate::{
+    Response, StatusCode,
+    context::Context,
+    middleware::{Middleware, Next},
+};
+
+/// Middleware that maps the client certificate to a [`Principal`].
+///
+/// The leaf certificate from [`ConnectionInfo`](crate::http::ConnectionInfo) is passed
+/// to the mapping callback; a returned principal is inserted into the request extensions.
+///
+/// # Behavior
+///
+/// - No client certificate (plain HTTP, or TLS with optional client auth and none
+///   presented) → `401 Unauthorized`.
+/// - The callback returns `None` → `403 Forbidden`: the certificate chained to a trusted
+///   root but does not belong to an authorized identity.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rttp::security::{Principal, mtls::client_cert_auth};
+///
+/// // Trust certificates from the internal CA whose CN is a known service.
+/// let auth = client_cert_auth(|cert| {
+///     let cn = cert.subject().common_name()?;
+///     ["billing", "orders"].contains(&cn).then(|| Principal::new(cn))
+/// });
+/// ```
+pub struct ClientCertMiddleware {
+    map: CertificateMapper,
+}
+
+type CertificateMapper = Arc<dyn Fn(&PeerCertificate) -> Option<Principal> + Send + Sync>;
+
+/// Creates a [`ClientCertMiddleware`] that maps certificates with `map`.
+pub fn client_cert_auth<F>(map: F) -> ClientCertMiddleware
+where
+    F: Fn(&PeerCertificate) -> Option<Principal> + Send + Sync + 'static,
+{
+    ClientCertMiddleware { map: Arc::new(map) }
+}
+
+impl Middleware for ClientCertMiddleware {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let leaf = ctx
+            .request()
+            .connection()
+            .and_then(|conn| conn.peer_certificates().first());
+        let Some(leaf) = leaf else {
+            return Box::pin(async {
+                Response::new(StatusCode::Unauthorized).body("client certificate required")
+            });
+        };
+        match (self.map)(leaf) {
+            Some(principal) => {
+                ctx.extensions_mut().insert(principal);
+                next.run(ctx)
+            }
+            None => {
+                tracing::warn!(subject = %leaf.subject(), "client certificate not authorized");
+                Box::pin(async { Response::new(StatusCode::Forbidden) })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rcgen::{CertificateParams, DnType, KeyPair};
+
+    use super::*;
+    use crate::{
+        http::{ConnectionInfo, request::Request},
+        middleware::from_middleware,
+    };
+
+    fn certificate() -> PeerCertificate {
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "billing");
+        let key = KeyPair::generate().unwrap();
+        PeerCertificate::from_der(params.self_signed(&key).unwrap().der()).unwrap()
+    }
+
+    async fn run(certificates: Option<Vec<PeerCertificate>>) -> Response {
+        let (mut req, _) = Request::parse(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+        if let Some(chain) = certificates {
+            let addr = "127.0.0.1:443".parse().unwrap();
+            req.set_connection(Arc::new(ConnectionInfo::new(addr, addr).with_tls(chain)));
+        }
+        let auth = client_cert_auth(|cert| {
+            let cn = cert.subject().common_name()?;
+            (cn == "billing").then(|| Principal::new(cn))
+        });
+        let echo: crate::middleware::MiddlewareHandler = Arc::new(|ctx: Context, _next| {
+            Box::pin(async move {
+                let id = ctx.extensions().get::<Principal>().unwrap().id().to_owned();
+                Response::new(StatusCode::Ok).body(id)
+            })
+        });
+        Next::new(vec![from_middleware(Arc::new(auth)), echo])
+            .run(Context::new(req))
+            .await
+    }
+
+    #[tokio::test]
+    async fn missing_certificate_is_unauthorized() {
+        assert_eq!(run(None).await.status(), StatusCode::Unauthorized);
+        assert_eq!(
+            run(Some(Vec::new())).await.status(),
+            StatusCode::Unauthorized
+        );
+    }
+
+    #[tokio::test]
+    async fn mapped_certificate_sets_principal() {
+        let response = run(Some(vec![certificate()])).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert_eq!(response.body_ref(), b"billing");
+    }
+
+    #[tokio::test]
+    async fn unmapped_certificate_is_forbidden() {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "intruder");
+        let cert = PeerCertificate::from_der(params.self_signed(&key).unwrap().der()).unwrap();
+        assert_eq!(run(Some(vec![cert])).await.status(), StatusCode::Forbidden);
+    }
+}
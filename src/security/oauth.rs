@@ -0,0 +1,953 @@
+//! OAuth 2.0 and OpenID Connect login with the authorization-code flow and PKCE.
+//!
+//! ## Core types
+//!
+//! - [`Provider`] — the identity provider's endpoints, configured by hand or read from
+//!   its discovery document with [`Provider::discover`].
+//! - [`OAuthClient`] — builds authorization URLs and exchanges codes for tokens.
+//! - [`login`] and [`callback`] — handler factories for the two routes of the flow.
+//! - [`Transport`] — outbound HTTP, by default [`HttpTransport`] over the shared [`Client`].
+//!
+//! [`login`] stores a random `state`, an OpenID `nonce`, and a PKCE code verifier in
+//! the session and redirects to the provider. [`callback`] checks the returned `state`
+//! against the session, exchanges the code together with the verifier, checks the ID
+//! token's issuer, audience, expiry, and nonce, and passes the resulting [`Login`] to
+//! the application, which typically stores the subject in the session.
+//!
+//! The ID token comes straight from the token endpoint, so its origin is established by
+//! that connection (OpenID Connect Core §3.1.3.7) rather than by its signature; only
+//! `HS256` tokens, signed with the client secret, are also checked cryptographically,
+//! and tokens with an algorithm outside the JWS registry are refused. ID tokens are
+//! therefore only checked as part of the code exchange, never on their own. Use
+//! `https://` endpoints, which [`HttpTransport`] verifies with the `tls` feature.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use rttp::{Response, StatusCode, Router, context::Context};
+//! use rttp::security::oauth::{self, Login, OAuthClient, Provider};
+//! use rttp::security::session::SessionExt;
+//!
+//! # async fn example(transport: Arc<dyn oauth::Transport>) -> Result<(), oauth::OAuthError> {
+//! let provider = Provider::discover("https://accounts.example", transport.as_ref()).await?;
+//! let client = Arc::new(
+//!     OAuthClient::new(provider, "my-client", "https://app.example/auth/callback")
+//!         .client_secret("s3cret")
+//!         .scope("email")
+//!         .transport(transport),
+//! );
+//!
+//! let mut router = Router::new();
+//! router.get("/auth/login", oauth::login(Arc::clone(&client)));
+//! router.get(
+//!     "/auth/callback",
+//!     oauth::callback(client, |ctx: Context, login: Login| async move {
+//!         let session = ctx.session().expect("SessionMiddleware installed");
+//!         session.regenerate();
+//!         session.insert("user_id", &login.claims.subject).unwrap();
+//!         Response::new(StatusCode::Ok).body("signed in")
+//!     }),
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use thiserror::Error;
+
+use super::{
+    crypto::{
+        base64url_decode, base64url_encode, constant_time_eq, hmac_sha256, random_token, sha256,
+    },
+    session::SessionExt,
+};
+use crate::{
+    Response, StatusCode,
+    clock::{self, Clock},
+    codec::{Codec, FormCodec},
+    context::Context,
+    http::{
+        Method,
+        client::{Client, ClientError},
+    },
+    router::IntoHandler,
+};
+
+// The session key of the login in progress.
+const PENDING_KEY: &str = "oauth_pending";
+
+// How far the provider's clock may be ahead of or behind ours.
+const LEEWAY: u64 = 60;
+
+/// Errors produced by the OAuth flow.
+#[derive(Debug, Error)]
+pub enum OAuthError {
+    #[error("invalid URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("transport error: {0}")]
+    Transport(String),
+
+    #[error("request timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("provider returned {status}: {body}")]
+    Provider { status: u16, body: String },
+
+    #[error("authorization failed: {0}")]
+    Denied(String),
+
+    #[error("state does not match the login in progress")]
+    InvalidState,
+
+    #[error("invalid ID token: {0}")]
+    InvalidIdToken(String),
+
+    #[error("malformed provider response: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl From<ClientError> for OAuthError {
+    fn from(err: ClientError) -> Self {
+        match err {
+            ClientError::InvalidUrl(url) => Self::InvalidUrl(url),
+            ClientError::Timeout(timeout) => Self::Timeout(timeout),
+            err => Self::Transport(err.to_string()),
+        }
+    }
+}
+
+/// A response read in full by a [`Transport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransportResponse {
+    /// The status code.
+    pub status: u16,
+    /// The body.
+    pub body: Vec<u8>,
+}
+
+/// Boxed future returned by [`Transport`] methods.
+pub type TransportFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<TransportResponse, OAuthError>> + Send + 'a>>;
+
+/// Sends requests to the identity provider.
+///
+/// Implement it to go through another HTTP client, for example one that routes through
+/// an egress proxy, or to fake the provider in tests.
+pub trait Transport: Send + Sync {
+    /// GETs `url` with `headers`.
+    fn get<'a>(&'a self, url: &'a str, headers: &'a [(String, String)]) -> TransportFuture<'a>;
+
+    /// POSTs `body` to `url` with `headers`.
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(String, String)],
+        body: &'a [u8],
+    ) -> TransportFuture<'a>;
+}
+
+/// A [`Transport`] over the shared [`Client`], for `http://` and, with the `tls`
+/// feature, `https://` endpoints.
+///
+/// The timeout bounds the whole request, from connecting to reading the last byte of
+/// the response.
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    client: Client,
+    timeout: Duration,
+}
+
+impl Default for HttpTransport {
+    fn default() -> Self {
+        Self::with_client(Client::new())
+    }
+}
+
+impl HttpTransport {
+    /// Creates a transport with a 10-second timeout.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a transport sending through `client`, for example one trusting a private
+    /// CA. The transport's timeout replaces the client's.
+    pub fn with_client(client: Client) -> Self {
+        let timeout = Duration::from_secs(10);
+        Self {
+            client: client.timeout(timeout),
+            timeout,
+        }
+    }
+
+    /// Sets how long a request may take, from connecting to reading the whole response.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.client = self.client.timeout(timeout);
+        self.timeout = timeout;
+        self
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        url: &str,
+        headers: &[(String, String)],
+        body: &[u8],
+    ) -> Result<TransportResponse, OAuthError> {
+        let response = self.client.request(method, url, headers, body).await?;
+        let status = response.status();
+        Ok(TransportResponse {
+            status,
+            body: response.bytes().await?,
+        })
+    }
+}
+
+impl Transport for HttpTransport {
+    fn get<'a>(&'a self, url: &'a str, headers: &'a [(String, String)]) -> TransportFuture<'a> {
+        Box::pin(async move {
+            tokio::time::timeout(self.timeout, self.send(Method::Get, url, headers, &[]))
+                .await
+                .map_err(|_| OAuthError::Timeout(self.timeout))?
+        })
+    }
+
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(String, String)],
+        body: &'a [u8],
+    ) -> TransportFuture<'a> {
+        Box::pin(async move {
+            tokio::time::timeout(self.timeout, self.send(Method::Post, url, headers, body))
+                .await
+                .map_err(|_| OAuthError::Timeout(self.timeout))?
+        })
+    }
+}
+
+/// An identity provider's endpoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct Provider {
+    issuer: Option<String>,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: Option<String>,
+}
+
+// The fields of an OpenID discovery document this module uses.
+#[derive(Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: Option<String>,
+}
+
+impl Provider {
+    /// Configures a provider from its authorization and token endpoint URLs.
+    pub fn new(
+        authorization_endpoint: impl Into<String>,
+        token_endpoint: impl Into<String>,
+    ) -> Self {
+        Self {
+            issuer: None,
+            authorization_endpoint: authorization_endpoint.into(),
+            token_endpoint: token_endpoint.into(),
+            userinfo_endpoint: None,
+        }
+    }
+
+    /// Reads the provider's endpoints from `<issuer>/.well-known/openid-configuration`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document cannot be fetched or parsed, or if it names a
+    /// different issuer.
+    pub async fn discover(issuer: &str, transport: &dyn Transport) -> Result<Self, OAuthError> {
+        let issuer = issuer.trim_end_matches('/');
+        let url = format!("{issuer}/.well-known/openid-configuration");
+        let response = transport.get(&url, &accept_json()).await?;
+        let document: Discovery = serde_json::from_slice(&success(response)?)?;
+        if document.issuer.trim_end_matches('/') != issuer {
+            return Err(OAuthError::Provider {
+                status: 200,
+                body: format!("discovery document names issuer {:?}", document.issuer),
+            });
+        }
+        Ok(Self {
+            issuer: Some(document.issuer),
+            authorization_endpoint: document.authorization_endpoint,
+            token_endpoint: document.token_endpoint,
+            userinfo_endpoint: document.userinfo_endpoint,
+        })
+    }
+
+    /// Sets the issuer ID tokens must name.
+    pub fn issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    /// Sets the endpoint identity claims are read from when the token response has no
+    /// ID token, as with plain OAuth 2.0 providers.
+    pub fn userinfo_endpoint(mut self, url: impl Into<String>) -> Self {
+        self.userinfo_endpoint = Some(url.into());
+        self
+    }
+}
+
+/// The secrets of a login in progress, kept in the session between the redirect to the
+/// provider and the callback.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingLogin {
+    /// The `state` sent to the provider, which the callback must return.
+    pub state: String,
+    /// The `nonce` the ID token must carry.
+    pub nonce: String,
+    /// The PKCE code verifier.
+    pub verifier: String,
+}
+
+/// The tokens returned by the provider.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Tokens {
+    /// The access token, for calling the provider's APIs on the user's behalf.
+    pub access_token: String,
+    /// The refresh token, if one was issued.
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    /// Seconds until the access token expires, if the provider said.
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+    /// The raw ID token, for OpenID Connect providers.
+    #[serde(default)]
+    pub id_token: Option<String>,
+}
+
+/// Verified claims about the user who logged in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdentityClaims {
+    /// The provider's stable identifier for the user (`sub`).
+    pub subject: String,
+    /// The user's email address, if shared.
+    pub email: Option<String>,
+    /// Whether the provider verified the email address.
+    pub email_verified: bool,
+    /// The user's display name, if shared.
+    pub name: Option<String>,
+    /// Every claim, including those above.
+    pub claims: Map<String, Value>,
+}
+
+impl IdentityClaims {
+    fn from_claims(claims: Map<String, Value>) -> Result<Self, OAuthError> {
+        let text = |key: &str| claims.get(key).and_then(Value::as_str).map(str::to_owned);
+        let subject =
+            text("sub").ok_or_else(|| OAuthError::InvalidIdToken("missing `sub` claim".into()))?;
+        Ok(Self {
+            subject,
+            email: text("email"),
+            email_verified: claims.get("email_verified").and_then(Value::as_bool) == Some(true),
+            name: text("name"),
+            claims,
+        })
+    }
+}
+
+/// A completed login, passed to the [`callback`] handler's continuation.
+#[derive(Debug, Clone)]
+pub struct Login {
+    /// Who logged in.
+    pub claims: IdentityClaims,
+    /// The tokens the provider issued.
+    pub tokens: Tokens,
+}
+
+/// A client registered with one identity provider.
+///
+/// Requests the `openid` scope by default. Without a [`client_secret`](Self::client_secret)
+/// it acts as a public client, relying on PKCE alone.
+#[must_use]
+pub struct OAuthClient {
+    provider: Provider,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_uri: String,
+    scopes: Vec<String>,
+    transport: Arc<dyn Transport>,
+    clock: Arc<dyn Clock>,
+}
+
+impl OAuthClient {
+    /// Creates a client for `provider` with the credentials it was registered with.
+    pub fn new(
+        provider: Provider,
+        client_id: impl Into<String>,
+        redirect_uri: impl Into<String>,
+    ) -> Self {
+        Self {
+            provider,
+            client_id: client_id.into(),
+            client_secret: None,
+            redirect_uri: redirect_uri.into(),
+            scopes: vec!["openid".to_owned()],
+            transport: Arc::new(HttpTransport::new()),
+            clock: clock::system(),
+        }
+    }
+
+    /// Sets the client secret, sent in the token request body.
+    pub fn client_secret(mut self, secret: impl Into<String>) -> Self {
+        self.client_secret = Some(secret.into());
+        self
+    }
+
+    /// Requests `scope` in addition to those already added.
+    pub fn scope(mut self, scope: impl Into<String>) -> Self {
+        self.scopes.push(scope.into());
+        self
+    }
+
+    /// Sends requests to the provider through `transport`.
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Starts a login: returns the URL to redirect the user to and the secrets to keep
+    /// until the callback.
+    pub fn authorize(&self) -> (String, PendingLogin) {
+        let pending = PendingLogin {
+            state: random_token(16),
+            nonce: random_token(16),
+            verifier: random_token(32),
+        };
+        let challenge = base64url_encode(&sha256(pending.verifier.as_bytes()));
+        let query = json!({
+            "response_type": "code",
+            "client_id": self.client_id,
+            "redirect_uri": self.redirect_uri,
+            "scope": self.scopes.join(" "),
+            "state": pending.state,
+            "nonce": pending.nonce,
+            "code_challenge": challenge,
+            "code_challenge_method": "S256",
+        });
+        let query = form_encode(&query);
+        let endpoint = &self.provider.authorization_endpoint;
+        let separator = if endpoint.contains('?') { '&' } else { '?' };
+        (format!("{endpoint}{separator}{query}"), pending)
+    }
+
+    /// Exchanges the authorization `code` for tokens and verifies who logged in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the provider rejects the code, or if the ID token is
+    /// malformed, expired, or meant for another client or login.
+    pub async fn exchange(&self, pending: &PendingLogin, code: &str) -> Result<Login, OAuthError> {
+        let mut form = json!({
+            "grant_type": "authorization_code",
+            "code": code,
+            "redirect_uri": self.redirect_uri,
+            "client_id": self.client_id,
+            "code_verifier": pending.verifier,
+        });
+        if let Some(secret) = &self.client_secret {
+            form["client_secret"] = json!(secret);
+        }
+        let mut headers = accept_json();
+        headers.push((
+            "Content-Type".to_owned(),
+            "application/x-www-form-urlencoded".to_owned(),
+        ));
+        let response = self
+            .transport
+            .post(
+                &self.provider.token_endpoint,
+                &headers,
+                form_encode(&form).as_bytes(),
+            )
+            .await?;
+        let tokens: Tokens = serde_json::from_slice(&success(response)?)?;
+
+        let claims = match (&tokens.id_token, &self.provider.userinfo_endpoint) {
+            (Some(id_token), _) => self.verify_id_token(id_token, &pending.nonce)?,
+            (None, Some(userinfo)) => {
+                let mut headers = accept_json();
+                headers.push((
+                    "Authorization".to_owned(),
+                    format!("Bearer {}", tokens.access_token),
+                ));
+                let response = self.transport.get(userinfo, &headers).await?;
+                IdentityClaims::from_claims(serde_json::from_slice(&success(response)?)?)?
+            }
+            (None, None) => {
+                return Err(OAuthError::InvalidIdToken(
+                    "no ID token and no userinfo endpoint".into(),
+                ));
+            }
+        };
+        Ok(Login { claims, tokens })
+    }
+
+    // Checks an ID token received from the token endpoint: its issuer, audience, expiry,
+    // and `nonce`, and its signature if it is `HS256`. Asymmetric signatures are left
+    // unchecked, so this must never be applied to a token from anywhere else.
+    fn verify_id_token(&self, token: &str, nonce: &str) -> Result<IdentityClaims, OAuthError> {
+        let invalid = |reason: &str| OAuthError::InvalidIdToken(reason.to_owned());
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid("not a JWS compact serialization"));
+        };
+        let decode = |part: &str| -> Result<Map<String, Value>, OAuthError> {
+            let bytes = base64url_decode(part).ok_or_else(|| invalid("bad base64url"))?;
+            Ok(serde_json::from_slice(&bytes)?)
+        };
+        let alg = decode(header)?
+            .get("alg")
+            .and_then(Value::as_str)
+            .map(str::to_owned)
+            .ok_or_else(|| invalid("missing `alg`"))?;
+        match alg.as_str() {
+            "none" => return Err(invalid("unsigned token")),
+            "HS256" => {
+                let secret = self
+                    .client_secret
+                    .as_deref()
+                    .ok_or_else(|| invalid("HS256 token without a client secret"))?;
+                let signed = &token[..header.len() + 1 + payload.len()];
+                let mac = hmac_sha256(secret.as_bytes(), signed.as_bytes());
+                let signature = base64url_decode(signature).unwrap_or_default();
+                if !constant_time_eq(&mac, &signature) {
+                    return Err(invalid("bad signature"));
+                }
+            }
+            "RS256" | "RS384" | "RS512" | "PS256" | "PS384" | "PS512" | "ES256" | "ES384"
+            | "ES512" | "EdDSA" => {}
+            _ => return Err(invalid("unsupported `alg`")),
+        }
+
+        let claims = decode(payload)?;
+        let text = |key: &str| claims.get(key).and_then(Value::as_str);
+        if let Some(issuer) = &self.provider.issuer {
+            if text("iss") != Some(issuer.as_str()) {
+                return Err(invalid("wrong issuer"));
+            }
+        }
+        let audience = match claims.get("aud") {
+            Some(Value::String(aud)) => aud == &self.client_id,
+            Some(Value::Array(auds)) => auds.iter().any(|aud| aud == &json!(self.client_id)),
+            _ => false,
+        };
+        if !audience {
+            return Err(invalid("wrong audience"));
+        }
+        let now = self
+            .clock
+            .system_time()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let expires = claims.get("exp").and_then(Value::as_u64).unwrap_or(0);
+        if expires + LEEWAY < now {
+            return Err(invalid("expired"));
+        }
+        if !text("nonce").is_some_and(|n| constant_time_eq(n.as_bytes(), nonce.as_bytes())) {
+            return Err(invalid("wrong nonce"));
+        }
+        IdentityClaims::from_claims(claims)
+    }
+}
+
+fn accept_json() -> Vec<(String, String)> {
+    vec![("Accept".to_owned(), "application/json".to_owned())]
+}
+
+// The body of a `2xx` response, or the provider's error.
+fn success(response: TransportResponse) -> Result<Vec<u8>, OAuthError> {
+    if (200..300).contains(&response.status) {
+        return Ok(response.body);
+    }
+    Err(OAuthError::Provider {
+        status: response.status,
+        body: String::from_utf8_lossy(&response.body).into_owned(),
+    })
+}
+
+fn form_encode(fields: &Value) -> String {
+    let bytes = FormCodec
+        .encode(fields)
+        .expect("OAuth parameters are flat strings");
+    String::from_utf8(bytes).expect("form encoding is ASCII")
+}
+
+/// Returns a handler that starts a login: it stores a [`PendingLogin`] in the session
+/// and redirects to the provider with `302 Found`.
+///
+/// Requires [`SessionMiddleware`](super::session::SessionMiddleware); without it the
+/// handler answers `500 Internal Server Error`.
+pub fn login(client: Arc<OAuthClient>) -> impl IntoHandler {
+    move |ctx: Context| {
+        let response = match ctx.session() {
+            Some(session) => {
+                let (url, pending) = client.authorize();
+                match session.insert(PENDING_KEY, pending) {
+                    Ok(()) => Response::new(StatusCode::Found)
+                        .header("Location", url)
+                        .header("Cache-Control", "no-store"),
+                    Err(e) => {
+                        tracing::error!(error = %e, "failed to store pending login");
+                        Response::new(StatusCode::InternalServerError)
+                    }
+                }
+            }
+            None => {
+                tracing::error!("OAuth login requires SessionMiddleware");
+                Response::new(StatusCode::InternalServerError)
+            }
+        };
+        async move { response }
+    }
+}
+
+/// Returns a handler for the provider's redirect back to the application.
+///
+/// It checks the `state` parameter against the session's [`PendingLogin`], exchanges
+/// the `code`, and calls `on_login` with the verified [`Login`]. A missing or
+/// mismatched state, or an `error` from the provider, gets `400 Bad Request`; a failed
+/// exchange or ID token check gets `502 Bad Gateway`. The pending login is removed from
+/// the session either way, so each can be completed once.
+pub fn callback<F, Fut>(client: Arc<OAuthClient>, on_login: F) -> impl IntoHandler
+where
+    F: Fn(Context, Login) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Response> + Send + 'static,
+{
+    let on_login = Arc::new(on_login);
+    move |ctx: Context| {
+        let client = Arc::clone(&client);
+        let on_login = Arc::clone(&on_login);
+        async move {
+            match complete(&client, &ctx).await {
+                Ok(login) => on_login(ctx, login).await,
+                Err(e) => {
+                    tracing::warn!(error = %e, "OAuth login failed");
+                    let status = match e {
+                        OAuthError::Denied(_) | OAuthError::InvalidState => StatusCode::BadRequest,
+                        _ => StatusCode::BadGateway,
+                    };
+                    Response::new(status)
+                }
+            }
+        }
+    }
+}
+
+// Checks the callback request and exchanges its code.
+async fn complete(client: &OAuthClient, ctx: &Context) -> Result<Login, OAuthError> {
+    let Some(session) = ctx.session() else {
+        return Err(OAuthError::InvalidState);
+    };
+    let pending = session.get::<PendingLogin>(PENDING_KEY);
+    session.remove(PENDING_KEY);
+    let request = ctx.request();
+    if let Some(error) = request.query_param("error") {
+        return Err(OAuthError::Denied(error.to_owned()));
+    }
+    let state = request.query_param("state").unwrap_or_default();
+    let Some(pending) =
+        pending.filter(|pending| constant_time_eq(pending.state.as_bytes(), state.as_bytes()))
+    else {
+        return Err(OAuthError::InvalidState);
+    };
+    let code = request
+        .query_param("code")
+        .ok_or_else(|| OAuthError::Denied("no authorization code".into()))?;
+    client.exchange(&pending, code).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::{
+        Request,
+        clock::MockClock,
+        middleware::{MiddlewareHandler, Next, from_middleware},
+        security::session::{MemoryStore, SessionMiddleware},
+    };
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+    const ISSUER: &str = "https://id.example";
+
+    // Answers by URL and records what was posted.
+    #[derive(Default)]
+    struct FakeProvider {
+        responses: Mutex<Vec<(String, u16, String)>>,
+        posted: Mutex<Vec<String>>,
+    }
+
+    impl FakeProvider {
+        fn respond(&self, url: &str, status: u16, body: impl Into<String>) {
+            let mut responses = self.responses.lock().unwrap();
+            responses.push((url.to_owned(), status, body.into()));
+        }
+
+        fn answer(&self, url: &str) -> TransportFuture<'_> {
+            let responses = self.responses.lock().unwrap();
+            let found = responses.iter().find(|(u, _, _)| u == url);
+            let result = found
+                .map(|(_, status, body)| TransportResponse {
+                    status: *status,
+                    body: body.clone().into_bytes(),
+                })
+                .ok_or_else(|| OAuthError::Transport(format!("no route to {url}")));
+            Box::pin(async move { result })
+        }
+    }
+
+    impl Transport for FakeProvider {
+        fn get<'a>(
+            &'a self,
+            url: &'a str,
+            _headers: &'a [(String, String)],
+        ) -> TransportFuture<'a> {
+            self.answer(url)
+        }
+
+        fn post<'a>(
+            &'a self,
+            url: &'a str,
+            _headers: &'a [(String, String)],
+            body: &'a [u8],
+        ) -> TransportFuture<'a> {
+            self.posted
+                .lock()
+                .unwrap()
+                .push(String::from_utf8_lossy(body).into_owned());
+            self.answer(url)
+        }
+    }
+
+    fn id_token(claims: Value) -> String {
+        let header = base64url_encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+        let payload = base64url_encode(claims.to_string().as_bytes());
+        let signed = format!("{header}.{payload}");
+        let mac = hmac_sha256(b"s3cret", signed.as_bytes());
+        format!("{signed}.{}", base64url_encode(&mac))
+    }
+
+    fn client(transport: Arc<FakeProvider>) -> OAuthClient {
+        let provider =
+            Provider::new(format!("{ISSUER}/authorize"), format!("{ISSUER}/token")).issuer(ISSUER);
+        OAuthClient::new(provider, "app", "https://app.example/callback")
+            .client_secret("s3cret")
+            .transport(transport)
+    }
+
+    fn claims(nonce: &str) -> Value {
+        json!({
+            "iss": ISSUER,
+            "aud": "app",
+            "sub": "user-1",
+            "email": "ada@example.com",
+            "email_verified": true,
+            "exp": 4_102_444_800u64,
+            "nonce": nonce,
+        })
+    }
+
+    #[tokio::test]
+    async fn discovers_the_provider() {
+        let transport = FakeProvider::default();
+        transport.respond(
+            &format!("{ISSUER}/.well-known/openid-configuration"),
+            200,
+            json!({
+                "issuer": ISSUER,
+                "authorization_endpoint": format!("{ISSUER}/authorize"),
+                "token_endpoint": format!("{ISSUER}/token"),
+                "userinfo_endpoint": format!("{ISSUER}/userinfo"),
+            })
+            .to_string(),
+        );
+        let provider = Provider::discover(&format!("{ISSUER}/"), &transport)
+            .await
+            .unwrap();
+        assert_eq!(
+            provider,
+            Provider::new(format!("{ISSUER}/authorize"), format!("{ISSUER}/token"))
+                .issuer(ISSUER)
+                .userinfo_endpoint(format!("{ISSUER}/userinfo"))
+        );
+        assert!(
+            Provider::discover("https://other.example", &transport)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn authorization_url_carries_pkce_and_state() {
+        let client = client(Arc::new(FakeProvider::default()));
+        let (url, pending) = client.authorize();
+        let request = Request::builder()
+            .path(url.trim_start_matches(ISSUER))
+            .build();
+        assert_eq!(request.path(), "/authorize");
+        assert_eq!(request.query_param("state"), Some(pending.state.as_str()));
+        assert_eq!(request.query_param("nonce"), Some(pending.nonce.as_str()));
+        assert_eq!(request.query_param("scope"), Some("openid"));
+        assert_eq!(request.query_param("code_challenge_method"), Some("S256"));
+        assert_eq!(
+            request.query_param("code_challenge"),
+            Some(base64url_encode(&sha256(pending.verifier.as_bytes())).as_str())
+        );
+    }
+
+    #[test]
+    fn verifies_id_token_claims() {
+        let clock = MockClock::new();
+        clock.set_system_time(UNIX_EPOCH + Duration::from_secs(1_000_000));
+        let client = client(Arc::new(FakeProvider::default())).clock(Arc::new(clock));
+
+        let identity = client.verify_id_token(&id_token(claims("n")), "n").unwrap();
+        assert_eq!(identity.subject, "user-1");
+        assert!(identity.email_verified);
+
+        let mut wrong = claims("n");
+        wrong["aud"] = json!(["other"]);
+        let cases = [
+            (id_token(claims("n")), "m", "wrong nonce"),
+            (id_token(wrong), "n", "wrong audience"),
+            (
+                id_token(json!({"aud": "app", "iss": ISSUER, "exp": 1, "nonce": "n"})),
+                "n",
+                "expired",
+            ),
+            (format!("{}x", id_token(claims("n"))), "n", "bad signature"),
+            (
+                format!(
+                    "{}.{}.sig",
+                    base64url_encode(br#"{"alg":"HS512"}"#),
+                    base64url_encode(claims("n").to_string().as_bytes())
+                ),
+                "n",
+                "unsupported `alg`",
+            ),
+        ];
+        for (token, nonce, reason) in cases {
+            match client.verify_id_token(&token, nonce) {
+                Err(OAuthError::InvalidIdToken(got)) => assert_eq!(got, reason),
+                other => panic!("{reason}: {other:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn callback_completes_the_login_once() {
+        let transport = Arc::new(FakeProvider::default());
+        let client = Arc::new(client(Arc::clone(&transport)));
+        let sessions = Arc::new(SessionMiddleware::new(MemoryStore::new(), SECRET));
+        let chain = |handler: Box<dyn IntoHandler>| -> Arc<[MiddlewareHandler]> {
+            let handler: Arc<dyn IntoHandler> = Arc::from(handler);
+            vec![
+                from_middleware(Arc::clone(&sessions)),
+                Arc::new(move |ctx: Context, _next: Next| handler.call(ctx)),
+            ]
+            .into()
+        };
+        let start = chain(Box::new(login(Arc::clone(&client))));
+        let finish = chain(Box::new(callback(
+            client,
+            |_ctx, login: Login| async move { Response::new(StatusCode::Ok).body(login.claims.subject) },
+        )));
+
+        let response = Next::new(start)
+            .run(Context::new(Request::builder().build()))
+            .await;
+        assert_eq!(response.status(), StatusCode::Found);
+        let location = response.headers().get("location").unwrap();
+        let authorize = Request::builder()
+            .path(location.trim_start_matches(ISSUER))
+            .build();
+        let state = authorize.query_param("state").unwrap().to_owned();
+        let nonce = authorize.query_param("nonce").unwrap();
+        let cookie = response.headers().get("set-cookie").unwrap();
+        let cookie = cookie.split(';').next().unwrap().to_owned();
+        let token_response = json!({
+            "access_token": "at",
+            "token_type": "Bearer",
+            "id_token": id_token(claims(nonce)),
+        });
+        transport.respond(&format!("{ISSUER}/token"), 200, token_response.to_string());
+
+        let returned = |state: &str| {
+            let request = Request::builder()
+                .path(format!("/callback?code=abc&state={state}"))
+                .header("cookie", cookie.as_str())
+                .build();
+            Next::new(Arc::clone(&finish)).run(Context::new(request))
+        };
+        let response = returned(&state).await;
+        assert_eq!(response.body_ref(), b"user-1");
+        let posted = transport.posted.lock().unwrap().clone();
+        assert!(posted[0].contains("code=abc"));
+        assert!(posted[0].contains("code_verifier="));
+
+        let replayed = returned(&state).await;
+        assert_eq!(replayed.status(), StatusCode::BadRequest, "used once");
+        assert_eq!(returned("forged").await.status(), StatusCode::BadRequest);
+    }
+
+    #[tokio::test]
+    async fn http_transport_reads_the_whole_response() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/token", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf).await.unwrap();
+            stream
+                .write_all(
+                    b"HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n{\"ok\":true}",
+                )
+                .await
+                .unwrap();
+        });
+        let response = HttpTransport::new().post(&url, &[], b"a=1").await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, br#"{"ok":true}"#);
+        assert!(
+            HttpTransport::new()
+                .get("ftp://id.example/", &[])
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Directory traversal protection for anything that maps URL paths onto the filesystem.
+//!
+//! [`safe_join`] turns an untrusted, still percent-encoded request path into a file path
+//! that is guaranteed to live under a root directory. It is applied in two stages:
+//!
+//! 1. **Lexical** — the path is percent-decoded exactly once and split into segments.
+//!    `..` segments, NUL and other control characters, backslashes, drive prefixes, and
+//!    escapes that are still present after decoding (double encoding such as `%252e`)
+//!    are rejected outright rather than normalized away.
+//! 2. **Physical** — the joined path and the root are canonicalized, resolving every
+//!    symlink, and the result must still start with the canonical root.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rttp::security::path::{PathError, safe_join};
+//!
+//! let file = safe_join("public", "/css/site.css")?;
+//! assert!(file.ends_with("css/site.css"));
+//!
+//! assert!(matches!(safe_join("public", "/../etc/passwd"), Err(PathError::Traversal)));
+//! assert!(matches!(safe_join("public", "/%2e%2e/etc/passwd"), Err(PathError::Traversal)));
+//! # Ok::<(), PathError>(())
+//! ```
+
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use thiserror::Error;
+
+/// Why a requested path was refused by [`safe_join`].
+#[derive(Debug, Error)]
+pub enum PathError {
+    #[error("path contains a `..` segment")]
+    Traversal,
+
+    #[error("path is not validly percent-encoded")]
+    Encoding,
+
+    #[error("path contains a forbidden character or prefix")]
+    InvalidCharacter,
+
+    #[error("path resolves outside the root directory")]
+    Escape,
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl PathError {
+    /// Returns `true` if the path was well-formed but simply does not exist.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Self::Io(e) if e.kind() == io::ErrorKind::NotFound)
+    }
+}
+
+/// Resolves `requested` (a URL path, still percent-encoded) to an existing file or
+/// directory under `root`.
+///
+/// The returned path is canonical: absolute, with every symlink resolved. Symlinks inside
+/// `root` are followed as long as their target also lies inside `root`.
+///
+/// This performs blocking filesystem calls (`canonicalize`); from async code, run it on
+/// a blocking thread or accept the cost for small, cached directory trees.
+///
+/// # Errors
+///
+/// - [`PathError::Traversal`], [`PathError::Encoding`], [`PathError::InvalidCharacter`]
+///   if the path is rejected lexically (see [`sanitize`]).
+/// - [`PathError::Escape`] if the path exists but resolves outside `root`, e.g. through
+///   a symlink.
+/// - [`PathError::Io`] if `root` or the target cannot be resolved; a missing target
+///   reports [`io::ErrorKind::NotFound`].
+pub fn safe_join(root: impl AsRef<Path>, requested: &str) -> Result<PathBuf, PathError> {
+    resolve(root.as_ref(), &sanitize(requested)?)
+}
+
+// The physical half of `safe_join`, for callers holding an already-sanitized path.
+pub(crate) fn resolve(root: &Path, relative: &Path) -> Result<PathBuf, PathError> {
+    let root = fs::canonicalize(root)?;
+    let resolved = fs::canonicalize(root.join(relative))?;
+    if resolved.starts_with(&root) {
+        Ok(resolved)
+    } else {
+        Err(PathError::Escape)
+    }
+}
+
+/// Lexically validates `requested` and returns it as a relative path, without touching
+/// the filesystem.
+///
+/// Leading, repeated, and trailing slashes and `.` segments are dropped; the empty path
+/// maps to the empty relative path (the root itself).
+///
+/// # Errors
+///
+/// See [`safe_join`]; only the lexical variants are returned.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use rttp::security::path::sanitize;
+///
+/// assert_eq!(sanitize("/docs//./read%20me.txt").unwrap(), Path::new("docs/read me.txt"));
+/// assert!(sanitize("/docs/%2e%2e/%2e%2e/secret").is_err());
+/// ```
+pub fn sanitize(requested: &str) -> Result<PathBuf, PathError> {
+    let decoded = percent_decode(requested)?;
+    if has_escape(&decoded) {
+        return Err(PathError::Encoding);
+    }
+
+    let mut relative = PathBuf::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => continue,
+            ".." => return Err(PathError::Traversal),
+            _ => {}
+        }
+        if segment.chars().any(|c| c.is_control() || c == '\\') {
+            return Err(PathError::InvalidCharacter);
+        }
+        // Each segment must be a single plain name: this rejects drive letters and UNC
+        // prefixes on Windows.
+        let mut components = Path::new(segment).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(name)), None) => relative.push(name),
+            _ => return Err(PathError::InvalidCharacter),
+        }
+    }
+    Ok(relative)
+}
+
+// Decodes `%XX` escapes once and validates the result as UTF-8.
+fn percent_decode(input: &str) -> Result<String, PathError> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or(PathError::Encoding)?;
+            out.push(hex);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).map_err(|_| PathError::Encoding)
+}
+
+// A valid `%XX` escape surviving one round of decoding means the client double-encoded
+// the path, which is only ever done to sneak `..` or `/` past a decoder.
+fn has_escape(decoded: &str) -> bool {
+    decoded
+        .as_bytes()
+        .windows(3)
+        .any(|w| w[0] == b'%' && w[1].is_ascii_hexdigit() && w[2].is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory under the system temp dir, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "rttp-path-{name}-{}",
+                crate::security::crypto::random_token(8)
+            ));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // ── Lexical ───────────────────────────────────────────────────────────────
+
+    #[test]
+    fn sanitize_normalizes_harmless_segments() {
+        assert_eq!(sanitize("").unwrap(), PathBuf::new());
+        assert_eq!(sanitize("///").unwrap(), PathBuf::new());
+        assert_eq!(sanitize("/a/./b//c/").unwrap(), Path::new("a/b/c"));
+        assert_eq!(sanitize("/100%25.txt").unwrap(), Path::new("100%.txt"));
+        assert_eq!(sanitize("/caf%C3%A9").unwrap(), Path::new("café"));
+    }
+
+    #[test]
+    fn sanitize_rejects_traversal() {
+        for path in [
+            "/..",
+            "/a/../b",
+            "../x",
+            "/%2e%2e/x",
+            "/%2E%2E/x",
+            "/a%2f..%2fb",
+        ] {
+            assert!(
+                matches!(sanitize(path), Err(PathError::Traversal)),
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn sanitize_rejects_double_and_invalid_encoding() {
+        for path in ["/%252e%252e/x", "/%25%32%65", "/%zz", "/%2", "/%ff"] {
+            assert!(matches!(sanitize(path), Err(PathError::Encoding)), "{path}");
+        }
+    }
+
+    #[test]
+    fn sanitize_rejects_dangerous_characters() {
+        for path in ["/a%00b", "/..%5c..%5cwindows", "/a\\b", "/a%0ab"] {
+            assert!(
+                matches!(sanitize(path), Err(PathError::InvalidCharacter)),
+                "{path}"
+            );
+        }
+    }
+
+    // ── Filesystem ────────────────────────────────────────────────────────────
+
+    #[test]
+    fn safe_join_resolves_inside_root() {
+        let root = TempDir::new("inside");
+        fs::create_dir(root.0.join("docs")).unwrap();
+        fs::write(root.0.join("docs/readme.txt"), "hi").unwrap();
+
+        let path = safe_join(&root.0, "/docs/readme.txt").unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), "hi");
+        assert!(
+            safe_join(&root.0, "/docs/missing.txt")
+                .unwrap_err()
+                .is_not_found()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn safe_join_rejects_symlink_escape() {
+        let outside = TempDir::new("outside");
+        fs::write(outside.0.join("secret"), "s3cret").unwrap();
+        let root = TempDir::new("root");
+        fs::write(root.0.join("target.txt"), "ok").unwrap();
+        std::os::unix::fs::symlink(outside.0.join("secret"), root.0.join("leak")).unwrap();
+        std::os::unix::fs::symlink(&outside.0, root.0.join("dir")).unwrap();
+        std::os::unix::fs::symlink(root.0.join("target.txt"), root.0.join("alias")).unwrap();
+
+        assert!(matches!(
+            safe_join(&root.0, "/leak"),
+            Err(PathError::Escape)
+        ));
+        assert!(matches!(
+            safe_join(&root.0, "/dir/secret"),
+            Err(PathError::Escape)
+        ));
+        // Links that stay inside the root are fine.
+        assert!(safe_join(&root.0, "/alias").is_ok());
+    }
+}
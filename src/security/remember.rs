@@ -0,0 +1,548 @@
+//! Persistent "remember me" logins that outlive the session.
+//!
+//! [`RememberMe`] issues a long-lived cookie holding a random *series* id and a
+//! single-use *token*. The store keeps, per series, the user and a SHA-256 hash of the
+//! current token; the token itself never rests on the server. Each time the cookie
+//! logs a user back in, the token is replaced and the cookie reissued, so a stolen
+//! cookie works at most until the real owner's next visit. When a series turns up
+//! with a token that is neither current nor just rotated, the cookie has been copied:
+//! every series of that user is deleted and they must log in again everywhere.
+//!
+//! Records live in any [`SessionStore`], under a key prefix, so the same
+//! [`MemoryStore`](super::session::MemoryStore) or
+//! [`RedisStore`](super::session::RedisStore) used for sessions can hold them.
+//!
+//! [`RememberMeMiddleware`] runs after [`SessionMiddleware`]: when the session has no
+//! logged-in user but the request carries a valid remember-me cookie, it stores the
+//! user in the session, regenerates the session id, and marks the request with
+//! [`Remembered`] so handlers can ask for the password again before sensitive changes.
+//!
+//! [`SessionMiddleware`]: super::session::SessionMiddleware
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use rttp::{Response, StatusCode, context::Context};
+//! use rttp::middleware::from_middleware;
+//! use rttp::security::remember::{RememberMe, RememberMeMiddleware};
+//! use rttp::security::session::{MemoryStore, SessionExt, SessionMiddleware};
+//!
+//! let remember = RememberMe::new(MemoryStore::new());
+//! let pipeline = vec![
+//!     from_middleware(Arc::new(SessionMiddleware::new(
+//!         MemoryStore::new(),
+//!         b"a secret of at least 32 bytes!!!",
+//!     ))),
+//!     from_middleware(Arc::new(RememberMeMiddleware::new(remember.clone()))),
+//! ];
+//!
+//! async fn login(ctx: Context, remember: RememberMe) -> Response {
+//!     let session = ctx.session().expect("SessionMiddleware installed");
+//!     session.regenerate();
+//!     session.insert("user_id", "ada").unwrap();
+//!     let mut response = Response::new(StatusCode::Ok);
+//!     remember.issue("ada", &mut response).await.unwrap();
+//!     response
+//! }
+//! ```
+
+use std::{
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use serde_json::Value;
+
+use super::{
+    crypto::{constant_time_eq, random_token, sha256, to_hex},
+    session::{SessionError, SessionExt, SessionRecord, SessionStore},
+};
+use crate::{
+    Request, Response, StatusCode,
+    clock::{self, Clock},
+    context::Context,
+    http::{Cookie, SameSite},
+    middleware::{Middleware, Next},
+};
+
+/// Marks a request whose user was logged in from a remember-me cookie rather than a
+/// password, as an extension in the [`Context`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remembered {
+    /// The user the cookie belongs to.
+    pub user: String,
+}
+
+// What a remember-me cookie turned out to be.
+#[derive(Debug, PartialEq, Eq)]
+enum Check {
+    // A valid login; carries the rotated cookie value, or `None` within the grace
+    // period after a rotation, when the newer cookie is already on its way.
+    Valid {
+        user: String,
+        cookie: Option<String>,
+    },
+    // A known series with a stale token: the cookie was copied.
+    Stolen {
+        user: String,
+    },
+    // An unknown, expired, or malformed cookie.
+    Invalid,
+}
+
+/// Issues, checks, and rotates remember-me cookies.
+///
+/// Clones share the same store and settings, so one handle can go to the
+/// [`RememberMeMiddleware`] and others to the login and logout handlers.
+///
+/// Cookie defaults: name `rttp_remember`, `Path=/`, `HttpOnly`, `Secure`,
+/// `SameSite=Lax`, and a 30 day lifetime.
+#[derive(Clone)]
+#[must_use]
+pub struct RememberMe {
+    store: Arc<dyn SessionStore>,
+    clock: Arc<dyn Clock>,
+    prefix: String,
+    name: String,
+    ttl: Duration,
+    grace: Duration,
+    secure: bool,
+    same_site: SameSite,
+}
+
+impl RememberMe {
+    /// Creates a handle keeping its records in `store`.
+    pub fn new(store: impl SessionStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            clock: clock::system(),
+            prefix: "remember:".to_owned(),
+            name: "rttp_remember".to_owned(),
+            ttl: Duration::from_secs(30 * 24 * 60 * 60),
+            grace: Duration::from_secs(30),
+            secure: true,
+            same_site: SameSite::Lax,
+        }
+    }
+
+    /// Sets the prefix of the store keys, to share a store with sessions. Defaults to
+    /// `"remember:"`.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the cookie name.
+    pub fn cookie_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Sets how long a login is remembered; each use starts the period again.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets how long the token replaced by a rotation is still accepted, so requests
+    /// sent in parallel with the old cookie are not taken for theft. Defaults to 30
+    /// seconds.
+    pub fn grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Sets the cookie `Secure` flag. Disable only for plain-HTTP local development.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Sets the cookie `SameSite` attribute.
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = same_site;
+        self
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Starts a new series for `user` and sets its cookie on `response`. Call this on
+    /// login when the user asked to be remembered.
+    ///
+    /// # Errors
+    ///
+    /// Returns the store's error.
+    pub async fn issue(&self, user: &str, response: &mut Response) -> Result<(), SessionError> {
+        let series = random_token(16);
+        let token = random_token(32);
+        self.save_series(&series, user, &token, None).await?;
+        // Drop the series that have expired since the last login.
+        let mut index = Vec::new();
+        for known in self.load_index(user).await? {
+            if self.store.load(&self.key(&known)).await?.is_some() {
+                index.push(known);
+            }
+        }
+        index.push(series.clone());
+        self.save_index(user, &index).await?;
+        response.add_cookie(&self.cookie(format!("{series}.{token}"), self.ttl));
+        Ok(())
+    }
+
+    /// Deletes the series of the request's cookie and expires the cookie. Call this on
+    /// logout.
+    ///
+    /// # Errors
+    ///
+    /// Returns the store's error.
+    pub async fn forget(
+        &self,
+        request: &Request,
+        response: &mut Response,
+    ) -> Result<(), SessionError> {
+        if let Some((series, _)) = request.cookie(&self.name).and_then(split) {
+            self.store.destroy(&self.key(series)).await?;
+        }
+        response.add_cookie(&self.cookie(String::new(), Duration::ZERO));
+        Ok(())
+    }
+
+    /// Deletes every series of `user`, logging them out on all devices. Call this when
+    /// they change their password.
+    ///
+    /// # Errors
+    ///
+    /// Returns the store's error.
+    pub async fn forget_user(&self, user: &str) -> Result<(), SessionError> {
+        for series in self.load_index(user).await? {
+            self.store.destroy(&self.key(&series)).await?;
+        }
+        self.store.destroy(&self.index_key(user)).await
+    }
+
+    // Checks a cookie value, rotating the token of a valid one.
+    async fn check(&self, value: &str) -> Result<Check, SessionError> {
+        let Some((series, token)) = split(value) else {
+            return Ok(Check::Invalid);
+        };
+        let Some(record) = self.store.load(&self.key(series)).await? else {
+            return Ok(Check::Invalid);
+        };
+        let (Some(user), Some(current)) = (text(&record, "user"), text(&record, "token")) else {
+            return Ok(Check::Invalid);
+        };
+        let user = user.to_owned();
+        let hash = hash(token);
+        if constant_time_eq(hash.as_bytes(), current.as_bytes()) {
+            let token = random_token(32);
+            let previous = Some((current.to_owned(), self.unix_now()));
+            self.save_series(series, &user, &token, previous).await?;
+            return Ok(Check::Valid {
+                user,
+                cookie: Some(format!("{series}.{token}")),
+            });
+        }
+        let within_grace = text(&record, "previous")
+            .zip(record.get("rotated_at").and_then(Value::as_u64))
+            .is_some_and(|(previous, rotated_at)| {
+                constant_time_eq(hash.as_bytes(), previous.as_bytes())
+                    && self.unix_now().saturating_sub(rotated_at) <= self.grace.as_secs()
+            });
+        if within_grace {
+            return Ok(Check::Valid { user, cookie: None });
+        }
+        Ok(Check::Stolen { user })
+    }
+
+    async fn save_series(
+        &self,
+        series: &str,
+        user: &str,
+        token: &str,
+        previous: Option<(String, u64)>,
+    ) -> Result<(), SessionError> {
+        let mut record = SessionRecord::new();
+        record.insert("user".into(), user.into());
+        record.insert("token".into(), hash(token).into());
+        if let Some((previous, rotated_at)) = previous {
+            record.insert("previous".into(), previous.into());
+            record.insert("rotated_at".into(), rotated_at.into());
+        }
+        self.store.save(&self.key(series), &record, self.ttl).await
+    }
+
+    // The series of a user, some of which may have expired.
+    async fn load_index(&self, user: &str) -> Result<Vec<String>, SessionError> {
+        let record = self.store.load(&self.index_key(user)).await?;
+        Ok(record
+            .and_then(|mut record| record.remove("series"))
+            .map(serde_json::from_value)
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    async fn save_index(&self, user: &str, series: &[String]) -> Result<(), SessionError> {
+        let mut record = SessionRecord::new();
+        record.insert("series".into(), serde_json::to_value(series)?);
+        self.store
+            .save(&self.index_key(user), &record, self.ttl)
+            .await
+    }
+
+    fn key(&self, series: &str) -> String {
+        format!("{}series:{series}", self.prefix)
+    }
+
+    fn index_key(&self, user: &str) -> String {
+        format!("{}user:{user}", self.prefix)
+    }
+
+    fn cookie(&self, value: String, max_age: Duration) -> Cookie {
+        Cookie::new(self.name.clone(), value)
+            .path("/")
+            .max_age(max_age)
+            .http_only(true)
+            .secure(self.secure)
+            .same_site(self.same_site)
+    }
+
+    fn unix_now(&self) -> u64 {
+        self.clock
+            .system_time()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs())
+    }
+}
+
+// `<series>.<token>`
+fn split(value: &str) -> Option<(&str, &str)> {
+    value
+        .split_once('.')
+        .filter(|(series, token)| !series.is_empty() && !token.is_empty())
+}
+
+fn hash(token: &str) -> String {
+    to_hex(&sha256(token.as_bytes()))
+}
+
+fn text<'a>(record: &'a SessionRecord, key: &str) -> Option<&'a str> {
+    record.get(key).and_then(Value::as_str)
+}
+
+/// Logs users back in from their remember-me cookie.
+///
+/// Install after [`SessionMiddleware`](super::session::SessionMiddleware). Requests
+/// whose session already holds the user key, or that carry no cookie, pass through
+/// untouched. Otherwise:
+///
+/// - A valid cookie stores its user under the user key (default `user_id`),
+///   regenerates the session id, inserts [`Remembered`], and reissues the cookie with
+///   a fresh token.
+/// - A cookie with a stale token deletes every series of its user, logs a `WARN`, and
+///   expires the cookie.
+/// - Any other cookie is expired.
+///
+/// Store failures produce `500 Internal Server Error`.
+#[must_use]
+pub struct RememberMeMiddleware {
+    remember: RememberMe,
+    user_key: String,
+}
+
+impl RememberMeMiddleware {
+    /// Creates the middleware using `remember`.
+    pub fn new(remember: RememberMe) -> Self {
+        Self {
+            remember,
+            user_key: "user_id".to_owned(),
+        }
+    }
+
+    /// Sets the session key the logged-in user is stored under.
+    pub fn user_key(mut self, key: impl Into<String>) -> Self {
+        self.user_key = key.into();
+        self
+    }
+}
+
+impl Middleware for RememberMeMiddleware {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let Some(session) = ctx.session().cloned() else {
+            tracing::error!("RememberMeMiddleware requires SessionMiddleware");
+            return Box::pin(async { Response::new(StatusCode::InternalServerError) });
+        };
+        let value = match ctx.request().cookie(&self.remember.name) {
+            Some(value) if !session.contains(&self.user_key) => value.to_owned(),
+            _ => return next.run(ctx),
+        };
+        let remember = self.remember.clone();
+        let user_key = self.user_key.clone();
+
+        Box::pin(async move {
+            let check = match remember.check(&value).await {
+                Ok(check) => check,
+                Err(e) => {
+                    tracing::error!(error = %e, "failed to check remember-me cookie");
+                    return Response::new(StatusCode::InternalServerError);
+                }
+            };
+            let (user, cookie) = match check {
+                Check::Valid { user, cookie } => (user, cookie),
+                Check::Stolen { user } => {
+                    tracing::warn!(%user, "remember-me token reused; forgetting all logins");
+                    if let Err(e) = remember.forget_user(&user).await {
+                        tracing::error!(error = %e, "failed to forget remember-me logins");
+                    }
+                    let mut response = next.run(ctx).await;
+                    response.add_cookie(&remember.cookie(String::new(), Duration::ZERO));
+                    return response;
+                }
+                Check::Invalid => {
+                    let mut response = next.run(ctx).await;
+                    response.add_cookie(&remember.cookie(String::new(), Duration::ZERO));
+                    return response;
+                }
+            };
+
+            session.regenerate();
+            if let Err(e) = session.insert(&user_key, &user) {
+                tracing::error!(error = %e, "failed to store remembered user");
+                return Response::new(StatusCode::InternalServerError);
+            }
+            ctx.extensions_mut().insert(Remembered { user });
+            let mut response = next.run(ctx).await;
+            if let Some(value) = cookie {
+                response.add_cookie(&remember.cookie(value, remember.ttl));
+            }
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::MockClock,
+        middleware::{MiddlewareHandler, from_middleware},
+        security::session::{MemoryStore, SessionMiddleware},
+    };
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    fn cookie_value(response: &Response) -> String {
+        let header = response.headers().get("set-cookie").unwrap();
+        let pair = header.split(';').next().unwrap();
+        pair.split_once('=').unwrap().1.to_owned()
+    }
+
+    async fn issued(remember: &RememberMe, user: &str) -> String {
+        let mut response = Response::new(StatusCode::Ok);
+        remember.issue(user, &mut response).await.unwrap();
+        cookie_value(&response)
+    }
+
+    #[tokio::test]
+    async fn rotates_the_token_on_each_use() {
+        let clock = MockClock::new();
+        let remember = RememberMe::new(MemoryStore::new()).clock(Arc::new(clock.clone()));
+        let first = issued(&remember, "ada").await;
+
+        let Check::Valid {
+            user,
+            cookie: Some(second),
+        } = remember.check(&first).await.unwrap()
+        else {
+            panic!("first use is valid");
+        };
+        assert_eq!(user, "ada");
+        assert_ne!(first, second);
+        assert_eq!(
+            remember.check(&first).await.unwrap(),
+            Check::Valid {
+                user: "ada".into(),
+                cookie: None
+            },
+            "within the grace period"
+        );
+
+        clock.advance(Duration::from_secs(31));
+        assert_eq!(
+            remember.check(&first).await.unwrap(),
+            Check::Stolen { user: "ada".into() }
+        );
+        assert_eq!(remember.check("x.y").await.unwrap(), Check::Invalid);
+        assert_eq!(remember.check("garbage").await.unwrap(), Check::Invalid);
+    }
+
+    #[tokio::test]
+    async fn forget_user_ends_every_series() {
+        let remember = RememberMe::new(MemoryStore::new());
+        let laptop = issued(&remember, "ada").await;
+        let phone = issued(&remember, "ada").await;
+        let other = issued(&remember, "bob").await;
+
+        remember.forget_user("ada").await.unwrap();
+        assert_eq!(remember.check(&laptop).await.unwrap(), Check::Invalid);
+        assert_eq!(remember.check(&phone).await.unwrap(), Check::Invalid);
+        assert!(matches!(
+            remember.check(&other).await.unwrap(),
+            Check::Valid { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn middleware_restores_the_session() {
+        let remember = RememberMe::new(MemoryStore::new());
+        let cookie = issued(&remember, "ada").await;
+        let handler: MiddlewareHandler = Arc::new(|ctx: Context, _next: Next| {
+            Box::pin(async move {
+                let user = ctx.session().unwrap().get::<String>("user_id");
+                let remembered = ctx.extensions().get::<Remembered>().is_some();
+                Response::new(StatusCode::Ok).body(format!("{user:?} {remembered}"))
+            })
+        });
+        let chain: Arc<[MiddlewareHandler]> = vec![
+            from_middleware(Arc::new(SessionMiddleware::new(MemoryStore::new(), SECRET))),
+            from_middleware(Arc::new(RememberMeMiddleware::new(remember.clone()))),
+            handler,
+        ]
+        .into();
+        let run = |cookie: &str| {
+            let request = Request::builder()
+                .header("cookie", format!("rttp_remember={cookie}"))
+                .build();
+            Next::new(Arc::clone(&chain)).run(Context::new(request))
+        };
+
+        let response = run(&cookie).await;
+        assert_eq!(response.body_ref(), br#"Some("ada") true"#);
+        let cookies: Vec<_> = response.headers().get_all("set-cookie").collect();
+        assert!(cookies.iter().any(|c| c.starts_with("rttp_session=")));
+        assert!(
+            cookies
+                .iter()
+                .any(|c| c.starts_with("rttp_remember=") && !c.contains(&cookie))
+        );
+
+        let response = run("unknown.token").await;
+        assert_eq!(response.body_ref(), b"None false");
+        assert!(
+            response
+                .headers()
+                .get("set-cookie")
+                .unwrap()
+                .contains("Max-Age=0")
+        );
+    }
+}
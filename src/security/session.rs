@@ -0,0 +1,792 @@
+//! Server-side sessions — pluggable stores, signed cookies, and typed access from handlers.
+//!
+//! ## Core types
+//!
+//! - [`SessionStore`] — persistence backend trait, with [`MemoryStore`] and [`RedisStore`].
+//! - [`Session`] — cheap-to-clone handle to the current request's session data.
+//! - [`SessionMiddleware`] — loads the session before the handler and saves it afterwards.
+//! - [`SessionExt`] — adds `ctx.session()` to [`Context`].
+//!
+//! The cookie carries only an opaque, random session id plus an HMAC-SHA256 signature
+//! (`<id>.<mac>`); the data itself never leaves the server. Cookies with a missing or
+//! invalid signature are ignored and a fresh session is started.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use rttp::{Response, StatusCode, context::Context};
+//! use rttp::middleware::from_middleware;
+//! use rttp::security::session::{MemoryStore, SessionExt, SessionMiddleware};
+//!
+//! let sessions = SessionMiddleware::new(MemoryStore::new(), b"a secret of at least 32 bytes!!!");
+//! let middleware = from_middleware(Arc::new(sessions));
+//!
+//! async fn visit(ctx: Context) -> Response {
+//!     let session = ctx.session().expect("SessionMiddleware installed");
+//!     let visits = session.get::<u32>("visits").unwrap_or(0) + 1;
+//!     session.insert("visits", visits).unwrap();
+//!     Response::new(StatusCode::Ok).body(format!("visit #{visits}"))
+//! }
+//! ```
+
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use super::crypto::{
+    base64url_decode, base64url_encode, constant_time_eq, hmac_sha256, random_token,
+};
+use crate::{
+    Response, StatusCode,
+    clock::{self, Clock},
+    context::Context,
+    http::{Cookie, SameSite},
+    middleware::{Middleware, Next},
+    redis::{RedisClient, RedisError},
+};
+
+/// The serialized form of a session's data.
+pub type SessionRecord = Map<String, Value>;
+
+/// Errors produced by session stores.
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error("session store backend error: {0}")]
+    Backend(String),
+
+    #[error("session data could not be (de)serialized: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+impl From<RedisError> for SessionError {
+    fn from(err: RedisError) -> Self {
+        Self::Backend(err.to_string())
+    }
+}
+
+/// Boxed future returned by [`SessionStore`] methods.
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, SessionError>> + Send + 'a>>;
+
+/// A persistence backend for session data.
+///
+/// Methods return boxed futures so the trait stays object-safe, mirroring
+/// [`Middleware::handle`].
+pub trait SessionStore: Send + Sync {
+    /// Loads the record for `id`, or `None` if it does not exist or has expired.
+    fn load<'a>(&'a self, id: &'a str) -> StoreFuture<'a, Option<SessionRecord>>;
+
+    /// Stores `record` under `id`, expiring it after `ttl`.
+    fn save<'a>(
+        &'a self,
+        id: &'a str,
+        record: &'a SessionRecord,
+        ttl: Duration,
+    ) -> StoreFuture<'a, ()>;
+
+    /// Deletes the record for `id`. Deleting a missing record is not an error.
+    fn destroy<'a>(&'a self, id: &'a str) -> StoreFuture<'a, ()>;
+}
+
+/// How many saves pass between sweeps of a [`MemoryStore`]'s expired records.
+const SWEEP_INTERVAL: u64 = 64;
+
+/// An in-process [`SessionStore`].
+///
+/// Expired records are dropped when loaded, and every 64th save sweeps out the ones no
+/// client came back for; [`purge_expired`](Self::purge_expired) sweeps on demand.
+///
+/// Sessions are lost on restart and are not shared between processes; use
+/// [`RedisStore`] for multi-instance deployments.
+pub struct MemoryStore {
+    records: Mutex<HashMap<String, (SessionRecord, Instant)>>,
+    clock: Arc<dyn Clock>,
+    saves: AtomicU64,
+}
+
+impl MemoryStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads expiry times from `clock` instead of the system clock.
+    #[must_use]
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the number of live (unexpired) sessions.
+    pub fn len(&self) -> usize {
+        let now = self.clock.now();
+        self.records()
+            .values()
+            .filter(|(_, exp)| *exp > now)
+            .count()
+    }
+
+    /// Returns `true` if there are no live sessions.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all expired records, returning how many were dropped.
+    pub fn purge_expired(&self) -> usize {
+        let now = self.clock.now();
+        let mut records = self.records();
+        let before = records.len();
+        records.retain(|_, (_, expires)| *expires > now);
+        before - records.len()
+    }
+
+    fn records(&self) -> MutexGuard<'_, HashMap<String, (SessionRecord, Instant)>> {
+        self.records.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self {
+            records: Mutex::new(HashMap::new()),
+            clock: clock::system(),
+            saves: AtomicU64::new(0),
+        }
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load<'a>(&'a self, id: &'a str) -> StoreFuture<'a, Option<SessionRecord>> {
+        let now = self.clock.now();
+        let mut records = self.records();
+        let record = match records.get(id) {
+            Some((record, expires)) if *expires > now => Some(record.clone()),
+            Some(_) => {
+                records.remove(id);
+                None
+            }
+            None => None,
+        };
+        Box::pin(async move { Ok(record) })
+    }
+
+    fn save<'a>(
+        &'a self,
+        id: &'a str,
+        record: &'a SessionRecord,
+        ttl: Duration,
+    ) -> StoreFuture<'a, ()> {
+        if self.saves.fetch_add(1, Ordering::Relaxed) % SWEEP_INTERVAL == SWEEP_INTERVAL - 1 {
+            self.purge_expired();
+        }
+        self.records()
+            .insert(id.to_owned(), (record.clone(), self.clock.now() + ttl));
+        Box::pin(async { Ok(()) })
+    }
+
+    fn destroy<'a>(&'a self, id: &'a str) -> StoreFuture<'a, ()> {
+        self.records().remove(id);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// A Redis-backed [`SessionStore`].
+///
+/// Each session is stored as a JSON string under `<prefix><id>` with a native Redis
+/// expiry, so sessions are shared across instances and survive restarts.
+pub struct RedisStore {
+    client: RedisClient,
+    prefix: String,
+}
+
+impl RedisStore {
+    /// Creates a store using `client` and the default key prefix `"session:"`.
+    pub fn new(client: RedisClient) -> Self {
+        Self {
+            client,
+            prefix: "session:".to_owned(),
+        }
+    }
+
+    /// Sets the key prefix used to namespace session keys.
+    #[must_use]
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    fn key(&self, id: &str) -> String {
+        format!("{}{id}", self.prefix)
+    }
+}
+
+impl SessionStore for RedisStore {
+    fn load<'a>(&'a self, id: &'a str) -> StoreFuture<'a, Option<SessionRecord>> {
+        Box::pin(async move {
+            match self.client.get(&self.key(id)).await? {
+                Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+                None => Ok(None),
+            }
+        })
+    }
+
+    fn save<'a>(
+        &'a self,
+        id: &'a str,
+        record: &'a SessionRecord,
+        ttl: Duration,
+    ) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let bytes = serde_json::to_vec(record)?;
+            self.client.set(&self.key(id), &bytes, Some(ttl)).await?;
+            Ok(())
+        })
+    }
+
+    fn destroy<'a>(&'a self, id: &'a str) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            self.client.del(&[&self.key(id)]).await?;
+            Ok(())
+        })
+    }
+}
+
+// Mutable session state shared between the middleware and the handler.
+#[derive(Debug, Default)]
+struct SessionState {
+    id: Option<String>,
+    data: SessionRecord,
+    modified: bool,
+    destroyed: bool,
+    regenerated_from: Option<String>,
+}
+
+/// A handle to the current request's session.
+///
+/// Clones share the same underlying data, so changes made by the handler are seen by
+/// [`SessionMiddleware`] when it saves the session.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    state: Arc<Mutex<SessionState>>,
+}
+
+impl Session {
+    fn from_parts(id: Option<String>, data: SessionRecord) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(SessionState {
+                id,
+                data,
+                ..SessionState::default()
+            })),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, SessionState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the session id, or `None` for a brand-new session that has not been saved.
+    pub fn id(&self) -> Option<String> {
+        self.state().id.clone()
+    }
+
+    /// Returns the value stored under `key`, deserialized as `T`.
+    ///
+    /// Returns `None` if the key is absent or holds a value of a different shape.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let value = self.state().data.get(key)?.clone();
+        serde_json::from_value(value).ok()
+    }
+
+    /// Stores `value` under `key`, marking the session as modified.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `value` cannot be serialized.
+    pub fn insert<T: Serialize>(&self, key: &str, value: T) -> Result<(), serde_json::Error> {
+        let value = serde_json::to_value(value)?;
+        let mut state = self.state();
+        state.data.insert(key.to_owned(), value);
+        state.modified = true;
+        Ok(())
+    }
+
+    /// Removes `key`, returning `true` if it was present.
+    pub fn remove(&self, key: &str) -> bool {
+        let mut state = self.state();
+        let removed = state.data.remove(key).is_some();
+        state.modified |= removed;
+        removed
+    }
+
+    /// Returns `true` if `key` is present.
+    pub fn contains(&self, key: &str) -> bool {
+        self.state().data.contains_key(key)
+    }
+
+    /// Removes every key but keeps the session id.
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.data.clear();
+        state.modified = true;
+    }
+
+    /// Assigns a new session id while keeping the data.
+    ///
+    /// Call this after a privilege change such as logging in to prevent session fixation.
+    /// The record under the old id is deleted when the response is sent.
+    pub fn regenerate(&self) {
+        let mut state = self.state();
+        if let Some(old) = state.id.take() {
+            state.regenerated_from.get_or_insert(old);
+        }
+        state.modified = true;
+    }
+
+    /// Deletes the session from the store and expires the cookie.
+    pub fn destroy(&self) {
+        let mut state = self.state();
+        state.data.clear();
+        state.destroyed = true;
+    }
+}
+
+/// Adds session access to [`Context`].
+pub trait SessionExt {
+    /// Returns the session installed by [`SessionMiddleware`], if any.
+    fn session(&self) -> Option<&Session>;
+}
+
+impl SessionExt for Context {
+    fn session(&self) -> Option<&Session> {
+        self.extensions().get::<Session>()
+    }
+}
+
+/// Cookie settings shared by every response produced by one [`SessionMiddleware`].
+#[derive(Debug, Clone)]
+struct CookieConfig {
+    name: String,
+    ttl: Duration,
+    secure: bool,
+    same_site: SameSite,
+}
+
+impl CookieConfig {
+    fn build(&self, value: impl Into<String>, max_age: Duration) -> Cookie {
+        Cookie::new(self.name.clone(), value)
+            .path("/")
+            .max_age(max_age)
+            .http_only(true)
+            .secure(self.secure)
+            .same_site(self.same_site)
+    }
+}
+
+/// Middleware that loads the session before the handler runs and persists it afterwards.
+///
+/// # Behavior
+///
+/// - A valid signed cookie loads the matching record; anything else starts an empty
+///   session. Store failures while loading produce `500 Internal Server Error`.
+/// - After the handler, a modified session is saved and the cookie (re)issued; an
+///   unmodified session is left alone, and a [`Session::destroy`]ed one is deleted and its
+///   cookie expired.
+/// - Empty new sessions are never persisted, so anonymous traffic does not fill the store.
+///
+/// Cookie defaults: name `rttp_session`, `Path=/`, `HttpOnly`, `Secure`, `SameSite=Lax`,
+/// and a 24 hour lifetime.
+pub struct SessionMiddleware {
+    store: Arc<dyn SessionStore>,
+    secret: Arc<[u8]>,
+    cookie: CookieConfig,
+}
+
+impl SessionMiddleware {
+    /// Creates a middleware backed by `store` that signs cookies with `secret`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `secret` is shorter than 32 bytes.
+    pub fn new(store: impl SessionStore + 'static, secret: &[u8]) -> Self {
+        assert!(
+            secret.len() >= 32,
+            "session secret must be at least 32 bytes"
+        );
+        Self {
+            store: Arc::new(store),
+            secret: Arc::from(secret),
+            cookie: CookieConfig {
+                name: "rttp_session".to_owned(),
+                ttl: Duration::from_secs(24 * 60 * 60),
+                secure: true,
+                same_site: SameSite::Lax,
+            },
+        }
+    }
+
+    /// Sets the cookie name.
+    #[must_use]
+    pub fn cookie_name(mut self, name: impl Into<String>) -> Self {
+        self.cookie.name = name.into();
+        self
+    }
+
+    /// Sets how long a session lives after its last modification.
+    #[must_use]
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.cookie.ttl = ttl;
+        self
+    }
+
+    /// Sets the cookie `Secure` flag. Disable only for plain-HTTP local development.
+    #[must_use]
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.cookie.secure = secure;
+        self
+    }
+
+    /// Sets the cookie `SameSite` attribute.
+    #[must_use]
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.cookie.same_site = same_site;
+        self
+    }
+}
+
+// `<id>.<base64url(hmac(id))>`
+fn sign(secret: &[u8], id: &str) -> String {
+    format!(
+        "{id}.{}",
+        base64url_encode(&hmac_sha256(secret, id.as_bytes()))
+    )
+}
+
+// Returns the id from a signed cookie value if the signature checks out.
+fn verify<'a>(secret: &[u8], value: &'a str) -> Option<&'a str> {
+    let (id, mac) = value.rsplit_once('.')?;
+    let mac = base64url_decode(mac)?;
+    constant_time_eq(&mac, &hmac_sha256(secret, id.as_bytes())).then_some(id)
+}
+
+// Persists (or deletes) the session after the handler ran, decorating `response` with the
+// resulting `Set-Cookie` header.
+async fn commit(
+    store: &dyn SessionStore,
+    secret: &[u8],
+    cookie: &CookieConfig,
+    session: &Session,
+    response: &mut Response,
+) -> Result<(), SessionError> {
+    let (id, record, modified, destroyed, stale) = {
+        let mut state = session.state();
+        (
+            state.id.clone(),
+            state.data.clone(),
+            state.modified,
+            state.destroyed,
+            state.regenerated_from.take(),
+        )
+    };
+
+    if let Some(old) = &stale {
+        store.destroy(old).await?;
+    }
+
+    if destroyed {
+        if let Some(id) = &id {
+            store.destroy(id).await?;
+        }
+        if id.is_some() || stale.is_some() {
+            response.add_cookie(&cookie.build("", Duration::ZERO));
+        }
+        return Ok(());
+    }
+
+    if !modified || (id.is_none() && record.is_empty()) {
+        return Ok(());
+    }
+
+    let id = id.unwrap_or_else(|| random_token(32));
+    store.save(&id, &record, cookie.ttl).await?;
+    session.state().id = Some(id.clone());
+    response.add_cookie(&cookie.build(sign(secret, &id), cookie.ttl));
+    Ok(())
+}
+
+impl Middleware for SessionMiddleware {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let store = Arc::clone(&self.store);
+        let secret = Arc::clone(&self.secret);
+        let cookie = self.cookie.clone();
+
+        Box::pin(async move {
+            let id = ctx
+                .request()
+                .cookie(&cookie.name)
+                .and_then(|value| verify(&secret, value))
+                .map(str::to_owned);
+
+            let session = match id {
+                Some(id) => match store.load(&id).await {
+                    Ok(Some(record)) => Session::from_parts(Some(id), record),
+                    Ok(None) => Session::default(),
+                    Err(e) => {
+                        tracing::error!(error = %e, "failed to load session");
+                        return Response::new(StatusCode::InternalServerError);
+                    }
+                },
+                None => Session::default(),
+            };
+
+            ctx.extensions_mut().insert(session.clone());
+            let mut response = next.run(ctx).await;
+
+            match commit(store.as_ref(), &secret, &cookie, &session, &mut response).await {
+                Ok(()) => response,
+                Err(e) => {
+                    tracing::error!(error = %e, "failed to persist session");
+                    Response::new(StatusCode::InternalServerError)
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http::request::Request,
+        middleware::{MiddlewareHandler, from_middleware},
+        redis::testing::FakeRedis,
+    };
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    fn context(cookie: Option<&str>) -> Context {
+        let cookie = cookie
+            .map(|c| format!("Cookie: rttp_session={c}\r\n"))
+            .unwrap_or_default();
+        let raw = format!("GET / HTTP/1.1\r\nHost: localhost\r\n{cookie}\r\n");
+        let (req, _) = Request::parse(raw.as_bytes()).unwrap();
+        Context::new(req)
+    }
+
+    // Counts visits in the session and echoes the count.
+    fn counter() -> MiddlewareHandler {
+        Arc::new(|ctx: Context, _next: Next| {
+            Box::pin(async move {
+                let session = ctx.session().unwrap();
+                let visits = session.get::<u32>("visits").unwrap_or(0) + 1;
+                session.insert("visits", visits).unwrap();
+                Response::new(StatusCode::Ok).body(visits.to_string())
+            })
+        })
+    }
+
+    async fn run(
+        mw: &Arc<SessionMiddleware>,
+        handler: MiddlewareHandler,
+        ctx: Context,
+    ) -> Response {
+        Next::new(vec![from_middleware(Arc::clone(mw)), handler])
+            .run(ctx)
+            .await
+    }
+
+    // Extracts the signed cookie value from a `Set-Cookie` header.
+    fn cookie_value(res: &Response) -> String {
+        let header = res.headers().get("set-cookie").unwrap();
+        header
+            .split(';')
+            .next()
+            .unwrap()
+            .trim_start_matches("rttp_session=")
+            .to_owned()
+    }
+
+    // ── Session ───────────────────────────────────────────────────────────────
+
+    #[test]
+    fn session_typed_get_insert_remove() {
+        let s = Session::default();
+        s.insert("user", "ada").unwrap();
+        s.insert("roles", vec!["admin"]).unwrap();
+        assert_eq!(s.get::<String>("user").as_deref(), Some("ada"));
+        assert_eq!(s.get::<Vec<String>>("roles").unwrap(), vec!["admin"]);
+        assert_eq!(s.get::<u32>("user"), None);
+        assert!(s.remove("user"));
+        assert!(!s.contains("user"));
+    }
+
+    #[test]
+    fn signature_round_trip() {
+        let signed = sign(SECRET, "abc");
+        assert_eq!(verify(SECRET, &signed), Some("abc"));
+        assert_eq!(verify(b"another secret entirely........!", &signed), None);
+        assert_eq!(verify(SECRET, "abc.tampered"), None);
+        assert_eq!(verify(SECRET, "no-dot"), None);
+    }
+
+    // ── SessionMiddleware ─────────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn new_session_is_saved_and_resumed() {
+        let mw = Arc::new(SessionMiddleware::new(MemoryStore::new(), SECRET));
+
+        let first = run(&mw, counter(), context(None)).await;
+        assert_eq!(first.body_ref(), b"1");
+        let set_cookie = first.headers().get("set-cookie").unwrap();
+        assert!(set_cookie.contains("HttpOnly"));
+        assert!(set_cookie.contains("SameSite=Lax"));
+
+        let cookie = cookie_value(&first);
+        let second = run(&mw, counter(), context(Some(&cookie))).await;
+        assert_eq!(second.body_ref(), b"2");
+    }
+
+    #[tokio::test]
+    async fn forged_cookie_starts_fresh_session() {
+        let mw = Arc::new(SessionMiddleware::new(MemoryStore::new(), SECRET));
+        let res = run(&mw, counter(), context(Some("guessed-id.AAAA"))).await;
+        assert_eq!(res.body_ref(), b"1");
+    }
+
+    #[tokio::test]
+    async fn untouched_session_sets_no_cookie() {
+        let mw = Arc::new(SessionMiddleware::new(MemoryStore::new(), SECRET));
+        let noop: MiddlewareHandler = Arc::new(|_ctx: Context, _next: Next| {
+            Box::pin(async { Response::new(StatusCode::Ok) })
+        });
+        let res = run(&mw, noop, context(None)).await;
+        assert!(!res.headers().contains("set-cookie"));
+    }
+
+    #[tokio::test]
+    async fn destroy_expires_cookie_and_record() {
+        let mw = Arc::new(SessionMiddleware::new(MemoryStore::new(), SECRET));
+        let cookie = cookie_value(&run(&mw, counter(), context(None)).await);
+
+        let logout: MiddlewareHandler = Arc::new(|ctx: Context, _next: Next| {
+            Box::pin(async move {
+                ctx.session().unwrap().destroy();
+                Response::new(StatusCode::Ok)
+            })
+        });
+        let res = run(&mw, logout, context(Some(&cookie))).await;
+        assert!(
+            res.headers()
+                .get("set-cookie")
+                .unwrap()
+                .contains("Max-Age=0")
+        );
+
+        let res = run(&mw, counter(), context(Some(&cookie))).await;
+        assert_eq!(res.body_ref(), b"1");
+    }
+
+    #[tokio::test]
+    async fn regenerate_issues_new_id_and_drops_old() {
+        let mw = Arc::new(SessionMiddleware::new(MemoryStore::new(), SECRET));
+        let old = cookie_value(&run(&mw, counter(), context(None)).await);
+
+        let login: MiddlewareHandler = Arc::new(|ctx: Context, _next: Next| {
+            Box::pin(async move {
+                ctx.session().unwrap().regenerate();
+                Response::new(StatusCode::Ok)
+            })
+        });
+        let new = cookie_value(&run(&mw, login, context(Some(&old))).await);
+        assert_ne!(old, new);
+
+        assert_eq!(
+            run(&mw, counter(), context(Some(&new))).await.body_ref(),
+            b"2"
+        );
+        assert_eq!(
+            run(&mw, counter(), context(Some(&old))).await.body_ref(),
+            b"1"
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_store_expires_records() {
+        let store = MemoryStore::new();
+        let record = SessionRecord::new();
+        store.save("a", &record, Duration::ZERO).await.unwrap();
+        assert!(store.load("a").await.unwrap().is_none());
+        assert!(store.is_empty());
+
+        let clock = crate::clock::MockClock::new();
+        let store = MemoryStore::new().clock(Arc::new(clock.clone()));
+        store
+            .save("b", &record, Duration::from_secs(1800))
+            .await
+            .unwrap();
+        clock.advance(Duration::from_secs(1799));
+        assert!(store.load("b").await.unwrap().is_some());
+        clock.advance(Duration::from_secs(1));
+        assert!(store.load("b").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn memory_store_sweeps_abandoned_records() {
+        let clock = crate::clock::MockClock::new();
+        let store = MemoryStore::new().clock(Arc::new(clock.clone()));
+        let record = SessionRecord::new();
+        let ttl = Duration::from_secs(60);
+        store.save("abandoned", &record, ttl).await.unwrap();
+        clock.advance(ttl);
+
+        let live = SWEEP_INTERVAL as usize - 1;
+        for i in 1..live {
+            store.save(&i.to_string(), &record, ttl).await.unwrap();
+        }
+        assert_eq!(store.records().len(), live);
+        store.save("last", &record, ttl).await.unwrap();
+        assert_eq!(store.records().len(), live);
+        assert!(!store.records().contains_key("abandoned"));
+
+        clock.advance(ttl);
+        assert_eq!(store.purge_expired(), live);
+        assert!(store.is_empty());
+    }
+
+    #[tokio::test]
+    async fn redis_store_round_trip() {
+        let server = FakeRedis::start().await;
+        let store = RedisStore::new(RedisClient::new(server.addr())).prefix("s:");
+
+        let mut record = SessionRecord::new();
+        record.insert("user".into(), Value::String("ada".into()));
+        store
+            .save("id1", &record, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(store.load("id1").await.unwrap(), Some(record));
+
+        store.destroy("id1").await.unwrap();
+        assert_eq!(store.load("id1").await.unwrap(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 32 bytes")]
+    fn short_secret_panics() {
+        let _ = SessionMiddleware::new(MemoryStore::new(), b"short");
+    }
+}
@@ -0,0 +1,407 @@
+//! HMAC request signature verification for incoming webhooks.
+//!
+//! [`SignatureMiddleware`] checks that the raw request body was signed with a shared
+//! secret before any handler sees it, the way GitHub, Shopify, Slack, and similar
+//! providers sign their deliveries:
+//!
+//! ```text
+//! X-Signature: sha256=<hex(hmac_sha256(secret, signed_payload))>
+//! X-Signature-Timestamp: <unix seconds>
+//! ```
+//!
+//! With a timestamp header configured, the signed payload is `"<timestamp>.<body>"` and
+//! requests whose timestamp is outside the tolerance window are rejected, so a captured
+//! delivery cannot be replayed later. Signatures are compared in constant time.
+
+use std::{
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use thiserror::Error;
+
+use super::crypto::{
+    base64_decode, base64_encode, constant_time_eq, from_hex, hmac_sha256, to_hex,
+};
+use crate::{
+    Response, StatusCode,
+    context::Context,
+    middleware::{Middleware, Next},
+};
+
+/// Why a request's signature was rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SignatureError {
+    #[error("missing signature header")]
+    Missing,
+
+    #[error("malformed signature header")]
+    Malformed,
+
+    #[error("signature does not match")]
+    Mismatch,
+
+    #[error("missing or malformed timestamp header")]
+    InvalidTimestamp,
+
+    #[error("timestamp outside the tolerance window")]
+    Expired,
+}
+
+/// How the digest is written in the signature header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureEncoding {
+    /// Lowercase hex (GitHub, Slack). Uppercase is accepted when verifying.
+    Hex,
+    /// Standard padded base64 (Shopify).
+    Base64,
+}
+
+// Timestamp header name and the accepted clock skew in either direction.
+#[derive(Debug, Clone)]
+struct Freshness {
+    header: String,
+    tolerance: Duration,
+}
+
+/// Middleware that rejects requests whose body signature does not verify.
+///
+/// Failed requests are answered with `401 Unauthorized` and never reach the handler.
+///
+/// # Defaults
+///
+/// | Setting            | Default                                   |
+/// |--------------------|-------------------------------------------|
+/// | Signature header   | `X-Signature`                             |
+/// | Value prefix       | `sha256=`                                 |
+/// | Encoding           | hex                                       |
+/// | Timestamp header   | `X-Signature-Timestamp`, ±5 minutes       |
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::sync::Arc;
+/// use rttp::middleware::from_middleware;
+/// use rttp::security::signature::SignatureMiddleware;
+///
+/// // GitHub webhooks: `X-Hub-Signature-256: sha256=<hex>` over the raw body.
+/// let github = SignatureMiddleware::github(b"webhook secret");
+/// let middleware = from_middleware(Arc::new(github));
+/// ```
+#[derive(Clone)]
+pub struct SignatureMiddleware {
+    secrets: Vec<Arc<[u8]>>,
+    header: String,
+    prefix: String,
+    encoding: SignatureEncoding,
+    freshness: Option<Freshness>,
+}
+
+impl SignatureMiddleware {
+    /// Creates a verifier for `secret` with the defaults listed above.
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            secrets: vec![Arc::from(secret)],
+            header: "X-Signature".to_owned(),
+            prefix: "sha256=".to_owned(),
+            encoding: SignatureEncoding::Hex,
+            freshness: Some(Freshness {
+                header: "X-Signature-Timestamp".to_owned(),
+                tolerance: Duration::from_secs(5 * 60),
+            }),
+        }
+    }
+
+    /// GitHub's scheme: `X-Hub-Signature-256: sha256=<hex>` over the raw body.
+    ///
+    /// GitHub does not sign a timestamp, so this preset has no replay window; pair it
+    /// with idempotent handling of the `X-GitHub-Delivery` id.
+    pub fn github(secret: &[u8]) -> Self {
+        Self::new(secret)
+            .header("X-Hub-Signature-256")
+            .without_timestamp()
+    }
+
+    /// Also accepts signatures made with `secret`, for zero-downtime secret rotation.
+    #[must_use]
+    pub fn additional_secret(mut self, secret: &[u8]) -> Self {
+        self.secrets.push(Arc::from(secret));
+        self
+    }
+
+    /// Sets the header carrying the signature.
+    #[must_use]
+    pub fn header(mut self, header: impl Into<String>) -> Self {
+        self.header = header.into();
+        self
+    }
+
+    /// Sets the prefix that precedes the digest in the header value (may be empty).
+    #[must_use]
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets how the digest is encoded.
+    #[must_use]
+    pub fn encoding(mut self, encoding: SignatureEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Requires a Unix-seconds timestamp in `header`, signs it as `"<timestamp>.<body>"`,
+    /// and rejects requests more than `tolerance` away from the current time.
+    #[must_use]
+    pub fn timestamp(mut self, header: impl Into<String>, tolerance: Duration) -> Self {
+        self.freshness = Some(Freshness {
+            header: header.into(),
+            tolerance,
+        });
+        self
+    }
+
+    /// Signs the body alone, with no timestamp and therefore no replay protection.
+    #[must_use]
+    pub fn without_timestamp(mut self) -> Self {
+        self.freshness = None;
+        self
+    }
+
+    /// Computes the signature header value a sender would attach to `body`.
+    ///
+    /// Uses the first configured secret. `timestamp` is ignored when no timestamp header
+    /// is configured.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rttp::security::signature::SignatureMiddleware;
+    ///
+    /// let verifier = SignatureMiddleware::github(b"It's a Secret to Everybody");
+    /// assert_eq!(
+    ///     verifier.sign(b"Hello, World!", 0),
+    ///     "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17"
+    /// );
+    /// ```
+    pub fn sign(&self, body: &[u8], timestamp: u64) -> String {
+        let mac = hmac_sha256(&self.secrets[0], &self.payload(body, timestamp));
+        let digest = match self.encoding {
+            SignatureEncoding::Hex => to_hex(&mac),
+            SignatureEncoding::Base64 => base64_encode(&mac),
+        };
+        format!("{}{digest}", self.prefix)
+    }
+
+    /// Verifies the signature on `ctx`'s request against the current time.
+    ///
+    /// # Errors
+    ///
+    /// See [`SignatureError`].
+    pub fn verify(&self, ctx: &Context) -> Result<(), SignatureError> {
+        self.verify_at(ctx, SystemTime::now())
+    }
+
+    fn verify_at(&self, ctx: &Context, now: SystemTime) -> Result<(), SignatureError> {
+        let headers = ctx.request().headers();
+
+        let timestamp = match &self.freshness {
+            Some(freshness) => {
+                let ts: u64 = headers
+                    .get(&freshness.header)
+                    .and_then(|v| v.trim().parse().ok())
+                    .ok_or(SignatureError::InvalidTimestamp)?;
+                let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+                if now.abs_diff(ts) > freshness.tolerance.as_secs() {
+                    return Err(SignatureError::Expired);
+                }
+                ts
+            }
+            None => 0,
+        };
+
+        let value = headers.get(&self.header).ok_or(SignatureError::Missing)?;
+        let digest = value
+            .trim()
+            .strip_prefix(self.prefix.as_str())
+            .ok_or(SignatureError::Malformed)?;
+        let provided = match self.encoding {
+            SignatureEncoding::Hex => from_hex(digest),
+            SignatureEncoding::Base64 => base64_decode(digest),
+        }
+        .ok_or(SignatureError::Malformed)?;
+
+        let payload = self.payload(ctx.request().body(), timestamp);
+        // Check every secret so timing does not reveal which one matched.
+        let matched = self.secrets.iter().fold(false, |ok, secret| {
+            ok | constant_time_eq(&provided, &hmac_sha256(secret, &payload))
+        });
+        if matched {
+            Ok(())
+        } else {
+            Err(SignatureError::Mismatch)
+        }
+    }
+
+    // The bytes covered by the MAC: the body, prefixed with `"<timestamp>."` when
+    // timestamps are in use.
+    fn payload(&self, body: &[u8], timestamp: u64) -> Vec<u8> {
+        match self.freshness {
+            Some(_) => {
+                let mut payload = format!("{timestamp}.").into_bytes();
+                payload.extend_from_slice(body);
+                payload
+            }
+            None => body.to_vec(),
+        }
+    }
+}
+
+impl Middleware for SignatureMiddleware {
+    fn handle(&self, ctx: Context, next: Next) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        match self.verify(&ctx) {
+            Ok(()) => next.run(ctx),
+            Err(e) => {
+                tracing::warn!(path = %ctx.request().path(), error = %e, "rejected webhook signature");
+                Box::pin(async move { Response::new(StatusCode::Unauthorized).body(e.to_string()) })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http::request::Request, middleware::from_middleware};
+
+    const SECRET: &[u8] = b"shhh";
+
+    fn context(headers: &[(&str, &str)], body: &str) -> Context {
+        let mut raw = String::from("POST /hooks HTTP/1.1\r\nHost: localhost\r\n");
+        for (name, value) in headers {
+            raw.push_str(&format!("{name}: {value}\r\n"));
+        }
+        raw.push_str(&format!("Content-Length: {}\r\n\r\n{body}", body.len()));
+        let (req, _) = Request::parse(raw.as_bytes()).unwrap();
+        Context::new(req)
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    // ── Verification ──────────────────────────────────────────────────────────
+
+    #[test]
+    fn github_style_round_trip() {
+        let v = SignatureMiddleware::github(SECRET);
+        let sig = v.sign(b"{\"a\":1}", 0);
+        let ctx = context(&[("X-Hub-Signature-256", &sig)], "{\"a\":1}");
+        assert_eq!(v.verify(&ctx), Ok(()));
+
+        let tampered = context(&[("X-Hub-Signature-256", &sig)], "{\"a\":2}");
+        assert_eq!(v.verify(&tampered), Err(SignatureError::Mismatch));
+    }
+
+    #[test]
+    fn missing_and_malformed_headers() {
+        let v = SignatureMiddleware::github(SECRET);
+        assert_eq!(v.verify(&context(&[], "x")), Err(SignatureError::Missing));
+        assert_eq!(
+            v.verify(&context(&[("X-Hub-Signature-256", "sha1=abcd")], "x")),
+            Err(SignatureError::Malformed)
+        );
+        assert_eq!(
+            v.verify(&context(&[("X-Hub-Signature-256", "sha256=zz")], "x")),
+            Err(SignatureError::Malformed)
+        );
+    }
+
+    #[test]
+    fn timestamp_is_signed_and_checked() {
+        let v = SignatureMiddleware::new(SECRET);
+        let ts = now();
+        let sig = v.sign(b"body", ts);
+        let ok = context(
+            &[
+                ("X-Signature", &sig),
+                ("X-Signature-Timestamp", &ts.to_string()),
+            ],
+            "body",
+        );
+        assert_eq!(v.verify(&ok), Ok(()));
+
+        // Same signature presented with a different timestamp.
+        let moved = context(
+            &[
+                ("X-Signature", &sig),
+                ("X-Signature-Timestamp", &(ts + 1).to_string()),
+            ],
+            "body",
+        );
+        assert_eq!(v.verify(&moved), Err(SignatureError::Mismatch));
+
+        let no_ts = context(&[("X-Signature", &sig)], "body");
+        assert_eq!(v.verify(&no_ts), Err(SignatureError::InvalidTimestamp));
+    }
+
+    #[test]
+    fn stale_timestamp_is_rejected() {
+        let v = SignatureMiddleware::new(SECRET);
+        let ts = now();
+        let sig = v.sign(b"body", ts);
+        let ctx = context(
+            &[
+                ("X-Signature", &sig),
+                ("X-Signature-Timestamp", &ts.to_string()),
+            ],
+            "body",
+        );
+        let later = SystemTime::now() + Duration::from_secs(301);
+        assert_eq!(v.verify_at(&ctx, later), Err(SignatureError::Expired));
+    }
+
+    #[test]
+    fn rotated_secret_and_base64() {
+        let old = SignatureMiddleware::new(b"old")
+            .without_timestamp()
+            .encoding(SignatureEncoding::Base64)
+            .prefix("");
+        let v = SignatureMiddleware::new(b"new")
+            .additional_secret(b"old")
+            .without_timestamp()
+            .encoding(SignatureEncoding::Base64)
+            .prefix("");
+        let sig = old.sign(b"x", 0);
+        assert_eq!(v.verify(&context(&[("X-Signature", &sig)], "x")), Ok(()));
+    }
+
+    // ── Middleware ────────────────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn middleware_short_circuits_on_failure() {
+        let v = SignatureMiddleware::github(SECRET);
+        let sig = v.sign(b"ok", 0);
+        let chain = |v: SignatureMiddleware| {
+            let handler: crate::middleware::MiddlewareHandler =
+                Arc::new(|_ctx: Context, _next: Next| {
+                    Box::pin(async { Response::new(StatusCode::Ok) })
+                });
+            Next::new(vec![from_middleware(Arc::new(v)), handler])
+        };
+
+        let res = chain(v.clone())
+            .run(context(&[("X-Hub-Signature-256", &sig)], "ok"))
+            .await;
+        assert_eq!(res.status(), StatusCode::Ok);
+
+        let res = chain(v)
+            .run(context(&[("X-Hub-Signature-256", &sig)], "nope"))
+            .await;
+        assert_eq!(res.status(), StatusCode::Unauthorized);
+    }
+}
@@ -0,0 +1,283 @@
+//! Signed, expiring URLs for links that work without a session.
+//!
+//! [`UrlSigner`] appends an expiry and an HMAC to a path, so a download link can be
+//! handed out by email or embedded in a page and honoured until it expires:
+//!
+//! ```text
+//! /files/report.pdf?expires=1767225600&user=42&signature=<base64url(hmac_sha256(…))>
+//! ```
+//!
+//! The MAC covers the path and every other query parameter, so neither the expiry nor
+//! any claim added with [`sign_with`](UrlSigner::sign_with) can be changed. Verify with
+//! [`UrlSigner::verify`], by installing the signer as middleware, or per route with
+//! [`UrlSigner::guard`].
+
+use std::{
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use thiserror::Error;
+
+use super::crypto::{base64url_decode, base64url_encode, constant_time_eq, hmac_sha256};
+use crate::{
+    Request, Response, StatusCode,
+    context::Context,
+    http::uri::{Space, encode_component},
+    middleware::{Middleware, Next},
+    router::IntoHandler,
+};
+
+/// Why a signed URL was rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SignedUrlError {
+    #[error("URL is not signed")]
+    Missing,
+
+    #[error("malformed signature or expiry")]
+    Malformed,
+
+    #[error("signature does not match")]
+    Mismatch,
+
+    #[error("link has expired")]
+    Expired,
+}
+
+/// Signs and verifies expiring URLs.
+///
+/// Rejected requests are answered with `403 Forbidden`.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use rttp::security::signed_url::UrlSigner;
+///
+/// let signer = UrlSigner::new(b"link secret");
+/// let url = signer.sign_with("/files/report.pdf", Duration::from_secs(3600), &[("user", "42")]);
+/// assert!(url.starts_with("/files/report.pdf?expires="));
+/// assert!(url.contains("&user=42&signature="));
+/// ```
+#[derive(Clone)]
+pub struct UrlSigner {
+    secrets: Vec<Arc<[u8]>>,
+}
+
+impl UrlSigner {
+    /// Creates a signer keyed with `secret`.
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            secrets: vec![Arc::from(secret)],
+        }
+    }
+
+    /// Also accepts URLs signed with `secret`, for zero-downtime secret rotation. New
+    /// URLs are always signed with the first secret.
+    #[must_use]
+    pub fn additional_secret(mut self, secret: &[u8]) -> Self {
+        self.secrets.push(Arc::from(secret));
+        self
+    }
+
+    /// Returns `path` (already percent-encoded, without a query) signed to be valid for
+    /// `ttl`.
+    pub fn sign(&self, path: &str, ttl: Duration) -> String {
+        self.sign_with(path, ttl, &[])
+    }
+
+    /// Like [`sign`](Self::sign), adding `claims` as query parameters covered by the
+    /// signature; handlers read them back with [`Request::query_param`].
+    pub fn sign_with(&self, path: &str, ttl: Duration, claims: &[(&str, &str)]) -> String {
+        let expires = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .saturating_add(ttl)
+            .as_secs();
+        self.sign_until(path, expires, claims)
+    }
+
+    fn sign_until(&self, path: &str, expires: u64, claims: &[(&str, &str)]) -> String {
+        let mut query = format!("expires={expires}");
+        for (name, value) in claims {
+            query.push('&');
+            encode_component(name, Space::Percent, &mut query);
+            query.push('=');
+            encode_component(value, Space::Percent, &mut query);
+        }
+        let mac = hmac_sha256(&self.secrets[0], &payload(path, &query));
+        format!("{path}?{query}&signature={}", base64url_encode(&mac))
+    }
+
+    /// Verifies `request`'s URL against the current time.
+    ///
+    /// # Errors
+    ///
+    /// See [`SignedUrlError`].
+    pub fn verify(&self, request: &Request) -> Result<(), SignedUrlError> {
+        self.verify_at(request, SystemTime::now())
+    }
+
+    fn verify_at(&self, request: &Request, now: SystemTime) -> Result<(), SignedUrlError> {
+        let query = request.query_string().unwrap_or_default();
+        let mut signature = None;
+        let mut expires = None;
+        let mut signed: Vec<&str> = Vec::new();
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            match pair.split_once('=') {
+                Some(("signature", value)) => signature = Some(value),
+                Some(("expires", value)) => {
+                    expires = Some(value);
+                    signed.push(pair);
+                }
+                _ => signed.push(pair),
+            }
+        }
+        let signature = signature.ok_or(SignedUrlError::Missing)?;
+        let provided = base64url_decode(signature).ok_or(SignedUrlError::Malformed)?;
+        let expires: u64 = expires
+            .and_then(|e| e.parse().ok())
+            .ok_or(SignedUrlError::Malformed)?;
+
+        // Parameters are covered in URL order. The MAC is checked first, so a forged
+        // expiry is reported as a mismatch rather than as expired.
+        let payload = payload(request.path(), &signed.join("&"));
+        let matched = self.secrets.iter().fold(false, |ok, secret| {
+            ok | constant_time_eq(&provided, &hmac_sha256(secret, &payload))
+        });
+        if !matched {
+            return Err(SignedUrlError::Mismatch);
+        }
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        if now >= expires {
+            return Err(SignedUrlError::Expired);
+        }
+        Ok(())
+    }
+
+    /// Wraps `handler` so it only runs for requests with a valid signature.
+    pub fn guard(&self, handler: impl IntoHandler) -> impl IntoHandler {
+        let signer = self.clone();
+        let handler = Arc::new(handler);
+        move |ctx: Context| {
+            let verified = signer.verify(ctx.request());
+            let handler = Arc::clone(&handler);
+            async move {
+                match verified {
+                    Ok(()) => handler.call(ctx).await,
+                    Err(e) => rejected(&ctx, &e),
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for UrlSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UrlSigner")
+            .field("secrets", &self.secrets.len())
+            .finish()
+    }
+}
+
+impl Middleware for UrlSigner {
+    fn handle(&self, ctx: Context, next: Next) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        match self.verify(ctx.request()) {
+            Ok(()) => next.run(ctx),
+            Err(e) => {
+                let response = rejected(&ctx, &e);
+                Box::pin(async move { response })
+            }
+        }
+    }
+}
+
+fn rejected(ctx: &Context, e: &SignedUrlError) -> Response {
+    tracing::warn!(path = %ctx.request().path(), error = %e, "rejected signed URL");
+    Response::new(StatusCode::Forbidden).body(e.to_string())
+}
+
+// The bytes covered by the MAC: the path and the query without the signature.
+fn payload(path: &str, query: &str) -> Vec<u8> {
+    format!("{path}?{query}").into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Router;
+
+    const SECRET: &[u8] = b"shhh";
+
+    fn request(url: &str) -> Request {
+        let raw = format!("GET {url} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        Request::parse(raw.as_bytes()).unwrap().0
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn round_trip_until_expiry() {
+        let signer = UrlSigner::new(SECRET);
+        let url = signer.sign_until("/files/a%20b.pdf", 1_000, &[("user", "42 & co")]);
+        assert!(url.contains("user=42%20%26%20co"));
+        let req = request(&url);
+        assert_eq!(signer.verify_at(&req, at(999)), Ok(()));
+        assert_eq!(
+            signer.verify_at(&req, at(1_000)),
+            Err(SignedUrlError::Expired)
+        );
+
+        let rotated = UrlSigner::new(b"new").additional_secret(SECRET);
+        assert_eq!(rotated.verify_at(&req, at(999)), Ok(()));
+    }
+
+    #[test]
+    fn tampering_is_rejected() {
+        let signer = UrlSigner::new(SECRET);
+        let url = signer.sign_until("/files/a.pdf", 1_000, &[("user", "42")]);
+        for forged in [
+            url.replace("/a.pdf", "/b.pdf"),
+            url.replace("expires=1000", "expires=9999"),
+            url.replace("user=42", "user=43"),
+            url.replace("&signature=", "&admin=1&signature="),
+        ] {
+            assert_eq!(
+                signer.verify_at(&request(&forged), at(0)),
+                Err(SignedUrlError::Mismatch),
+                "{forged}"
+            );
+        }
+        assert_eq!(
+            signer.verify_at(&request("/files/a.pdf?expires=1000"), at(0)),
+            Err(SignedUrlError::Missing)
+        );
+        assert_eq!(
+            signer.verify_at(&request("/files/a.pdf?signature=abc"), at(0)),
+            Err(SignedUrlError::Malformed)
+        );
+        assert_eq!(
+            UrlSigner::new(b"other").verify_at(&request(&url), at(0)),
+            Err(SignedUrlError::Mismatch)
+        );
+    }
+
+    #[tokio::test]
+    async fn guard_protects_a_route() {
+        let signer = UrlSigner::new(SECRET);
+        let mut router = Router::new();
+        router.get(
+            "/download",
+            signer.guard(|_ctx| async { Response::new(StatusCode::Ok).body("file") }),
+        );
+
+        let url = signer.sign("/download", Duration::from_secs(60));
+        let response = router.route(request(&url)).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+        let response = router.route(request("/download")).await;
+        assert_eq!(response.status(), StatusCode::Forbidden);
+    }
+}
@@ -0,0 +1,342 @@
+//! Time-based one-time passwords (RFC 6238) and recovery codes for two-factor login.
+//!
+//! ## Core types
+//!
+//! - [`Totp`] — generates and checks the six-digit codes authenticator apps show, and
+//!   builds the `otpauth://` URI they scan from a QR code.
+//! - [`recovery_codes`] and [`redeem_recovery_code`] — single-use fallback codes for
+//!   users who lose their device.
+//!
+//! Enrolment generates a secret, shows [`Totp::provisioning_uri`] as a QR code, and
+//! stores the secret once the user has entered a valid code. At login, the step
+//! returned by [`Totp::verify`] is stored too, so the same code cannot be replayed.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::time::SystemTime;
+//! use rttp::security::totp::Totp;
+//!
+//! let totp = Totp::new(Totp::generate_secret());
+//! let uri = totp.provisioning_uri("Example", "ada@example.com");
+//! assert!(uri.starts_with("otpauth://totp/Example:ada%40example.com?secret="));
+//!
+//! let now = SystemTime::now();
+//! let code = totp.generate(now);
+//! let step = totp.verify(&code, now, None).expect("current code");
+//! assert_eq!(totp.verify(&code, now, Some(step)), None, "used once");
+//! ```
+
+use std::{
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::crypto::{
+    base32_decode, base32_encode, constant_time_eq, hmac_sha1, hmac_sha256, random_bytes, sha256,
+    to_hex,
+};
+use crate::http::uri::{Space, encode_component};
+
+// Crockford's base32 alphabet, without the letters read as digits.
+const RECOVERY_ALPHABET: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
+
+/// The HMAC a [`Totp`] is computed with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    /// HMAC-SHA1, the RFC 6238 default and the only one every app supports.
+    #[default]
+    Sha1,
+    /// HMAC-SHA256.
+    Sha256,
+}
+
+impl Algorithm {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Sha1 => "SHA1",
+            Self::Sha256 => "SHA256",
+        }
+    }
+}
+
+/// A TOTP generator and verifier for one user's secret.
+///
+/// Defaults to six digits, a 30-second period, HMAC-SHA1, and accepting codes one step
+/// either side of the current one for clock drift.
+#[derive(Clone, PartialEq, Eq)]
+#[must_use]
+pub struct Totp {
+    secret: Vec<u8>,
+    digits: u32,
+    period: u64,
+    skew: u64,
+    algorithm: Algorithm,
+}
+
+impl fmt::Debug for Totp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Totp")
+            .field("secret", &"<redacted>")
+            .field("digits", &self.digits)
+            .field("period", &self.period)
+            .field("skew", &self.skew)
+            .field("algorithm", &self.algorithm)
+            .finish()
+    }
+}
+
+impl Totp {
+    /// Creates a generator for `secret`, the raw shared key.
+    pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            secret: secret.into(),
+            digits: 6,
+            period: 30,
+            skew: 1,
+            algorithm: Algorithm::Sha1,
+        }
+    }
+
+    /// Creates a generator for a secret in the base32 form apps display. Returns
+    /// `None` if it is not valid base32.
+    pub fn from_base32(secret: &str) -> Option<Self> {
+        base32_decode(secret).map(Self::new)
+    }
+
+    /// Returns a new random 160-bit secret, the size RFC 4226 recommends.
+    pub fn generate_secret() -> Vec<u8> {
+        random_bytes(20)
+    }
+
+    /// Sets the number of digits, from 6 to 9.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `digits` is outside that range.
+    pub fn digits(mut self, digits: u32) -> Self {
+        assert!((6..=9).contains(&digits), "TOTP codes have 6 to 9 digits");
+        self.digits = digits;
+        self
+    }
+
+    /// Sets how long each code is valid, in whole seconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is shorter than a second.
+    pub fn period(mut self, period: Duration) -> Self {
+        assert!(
+            period.as_secs() > 0,
+            "TOTP period must be at least a second"
+        );
+        self.period = period.as_secs();
+        self
+    }
+
+    /// Sets how many steps before and after the current one are also accepted.
+    pub fn skew(mut self, steps: u64) -> Self {
+        self.skew = steps;
+        self
+    }
+
+    /// Sets the HMAC algorithm.
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Returns the secret in base32, for entering it into an app by hand.
+    pub fn secret_base32(&self) -> String {
+        base32_encode(&self.secret)
+    }
+
+    /// Returns the time step `time` falls in.
+    pub fn step(&self, time: SystemTime) -> u64 {
+        let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        since.as_secs() / self.period
+    }
+
+    /// Returns the code for `time`.
+    pub fn generate(&self, time: SystemTime) -> String {
+        self.code(self.step(time))
+    }
+
+    /// Checks `code` against the steps around `time`, returning the step it matched.
+    ///
+    /// Steps up to and including `last_step`, the value returned by the previous
+    /// successful check, are rejected so a code cannot be used twice.
+    pub fn verify(&self, code: &str, time: SystemTime, last_step: Option<u64>) -> Option<u64> {
+        let code = code.trim();
+        let current = self.step(time);
+        let first = current.saturating_sub(self.skew);
+        let first = last_step.map_or(first, |last| first.max(last + 1));
+        // Every candidate is compared, so the time taken does not reveal which matched.
+        (first..=current + self.skew).fold(None, |found, step| {
+            let matches = constant_time_eq(self.code(step).as_bytes(), code.as_bytes());
+            found.or(matches.then_some(step))
+        })
+    }
+
+    /// Returns the `otpauth://` URI to show as a QR code, labelled with `issuer` (the
+    /// application) and `account` (the user).
+    pub fn provisioning_uri(&self, issuer: &str, account: &str) -> String {
+        let mut uri = String::from("otpauth://totp/");
+        encode_component(issuer, Space::Percent, &mut uri);
+        uri.push(':');
+        encode_component(account, Space::Percent, &mut uri);
+        uri.push_str("?secret=");
+        uri.push_str(&self.secret_base32());
+        uri.push_str("&issuer=");
+        encode_component(issuer, Space::Percent, &mut uri);
+        if self.algorithm != Algorithm::Sha1 {
+            uri.push_str("&algorithm=");
+            uri.push_str(self.algorithm.as_str());
+        }
+        if self.digits != 6 {
+            uri.push_str(&format!("&digits={}", self.digits));
+        }
+        if self.period != 30 {
+            uri.push_str(&format!("&period={}", self.period));
+        }
+        uri
+    }
+
+    // The RFC 4226 HOTP value for counter `step`.
+    fn code(&self, step: u64) -> String {
+        let counter = step.to_be_bytes();
+        let mac = match self.algorithm {
+            Algorithm::Sha1 => hmac_sha1(&self.secret, &counter).to_vec(),
+            Algorithm::Sha256 => hmac_sha256(&self.secret, &counter).to_vec(),
+        };
+        let offset = usize::from(mac[mac.len() - 1] & 0x0f);
+        let binary = u32::from_be_bytes([
+            mac[offset] & 0x7f,
+            mac[offset + 1],
+            mac[offset + 2],
+            mac[offset + 3],
+        ]);
+        let value = binary % 10u32.pow(self.digits);
+        format!("{value:0width$}", width = self.digits as usize)
+    }
+}
+
+/// Returns `count` random recovery codes such as `7kq2m-x9vtd`, to show the user once.
+///
+/// Each carries 50 bits of entropy. Store only their [hashes](hash_recovery_code).
+pub fn recovery_codes(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|_| {
+            let bytes = random_bytes(10);
+            let chars: String = bytes
+                .iter()
+                .map(|b| RECOVERY_ALPHABET[usize::from(b & 31)] as char)
+                .collect();
+            format!("{}-{}", &chars[..5], &chars[5..])
+        })
+        .collect()
+}
+
+/// Returns the hash of a recovery code to store in its place.
+///
+/// Case, dashes, and spaces are ignored, and the letters `o`, `i`, and `l` read as the
+/// digits they resemble.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| !matches!(c, '-' | ' '))
+        .map(|c| match c.to_ascii_lowercase() {
+            'o' => '0',
+            'i' | 'l' => '1',
+            c => c,
+        })
+        .collect();
+    to_hex(&sha256(normalized.as_bytes()))
+}
+
+/// Checks `code` against the stored `hashes`, removing its hash if it matches so the
+/// code works only once. Returns whether it matched.
+pub fn redeem_recovery_code(hashes: &mut Vec<String>, code: &str) -> bool {
+    let hash = hash_recovery_code(code);
+    let found = hashes.iter().fold(None, |found, stored| {
+        found.or(constant_time_eq(stored.as_bytes(), hash.as_bytes()).then_some(stored.clone()))
+    });
+    match found {
+        Some(found) => {
+            hashes.retain(|stored| *stored != found);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn rfc6238_vectors() {
+        let sha1 = Totp::new(*b"12345678901234567890").digits(8);
+        let sha256 = Totp::new(*b"12345678901234567890123456789012")
+            .digits(8)
+            .algorithm(Algorithm::Sha256);
+        for (time, expected_sha1, expected_sha256) in [
+            (59, "94287082", "46119246"),
+            (1_111_111_109, "07081804", "68084774"),
+            (2_000_000_000, "69279037", "90698825"),
+        ] {
+            assert_eq!(sha1.generate(at(time)), expected_sha1, "{time}");
+            assert_eq!(sha256.generate(at(time)), expected_sha256, "{time}");
+        }
+    }
+
+    #[test]
+    fn accepts_drift_and_rejects_replays() {
+        let totp = Totp::new(*b"12345678901234567890");
+        let now = at(1_000_000_020);
+        let previous = totp.generate(at(1_000_000_020 - 30));
+        let next = totp.generate(at(1_000_000_020 + 30));
+        let stale = totp.generate(at(1_000_000_020 - 60));
+
+        let step = totp.step(now);
+        assert_eq!(totp.verify(&previous, now, None), Some(step - 1));
+        assert_eq!(totp.verify(&next, now, None), Some(step + 1));
+        assert_eq!(totp.verify(&stale, now, None), None);
+        assert_eq!(totp.verify(&previous, now, Some(step - 1)), None);
+        assert_eq!(totp.skew(0).verify(&next, now, None), None);
+    }
+
+    #[test]
+    fn provisioning_uri_round_trips_the_secret() {
+        let totp = Totp::new(*b"12345678901234567890").period(Duration::from_secs(60));
+        assert_eq!(
+            totp.provisioning_uri("Acme Co", "ada@example.com"),
+            "otpauth://totp/Acme%20Co:ada%40example.com\
+             ?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Acme%20Co&period=60"
+        );
+        let parsed = Totp::from_base32(&totp.secret_base32().to_lowercase()).unwrap();
+        assert_eq!(
+            parsed.generate(at(59)),
+            Totp::new(*b"12345678901234567890").generate(at(59))
+        );
+        assert!(!format!("{totp:?}").contains("GEZ"));
+    }
+
+    #[test]
+    fn recovery_codes_work_once() {
+        let codes = recovery_codes(10);
+        assert_eq!(codes.len(), 10);
+        assert!(codes.iter().all(|code| code.len() == 11));
+        let mut hashes: Vec<String> = codes.iter().map(|c| hash_recovery_code(c)).collect();
+
+        let typed = codes[3].to_uppercase().replace('-', " ");
+        assert!(redeem_recovery_code(&mut hashes, &typed));
+        assert!(!redeem_recovery_code(&mut hashes, &codes[3]));
+        assert_eq!(hashes.len(), 9);
+        assert_eq!(hash_recovery_code("0o1il"), hash_recovery_code("00111"));
+    }
+}
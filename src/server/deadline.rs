@@ -0,0 +1,119 @@
+//! A per-write deadline for connections handed to streamed response writers.
+
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::Sleep,
+};
+use tracing::warn;
+
+use super::ServerMetrics;
+
+/// Wraps a connection so that any single write or flush left pending for longer than
+/// `timeout` fails with [`io::ErrorKind::TimedOut`], counting the expiry.
+///
+/// The clock only runs while a write is waiting on the peer: a stream that is idle
+/// between chunks (server-sent events, say) is never timed out.
+pub(super) struct WriteDeadline<S> {
+    inner: S,
+    timeout: Duration,
+    metrics: ServerMetrics,
+    // Armed when a write first returns `Pending`, cleared when one completes.
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> WriteDeadline<S> {
+    pub(super) fn new(inner: S, timeout: Duration, metrics: ServerMetrics) -> Self {
+        Self {
+            inner,
+            timeout,
+            metrics,
+            sleep: None,
+        }
+    }
+
+    // Passes a finished write through, or checks the deadline for a pending one.
+    fn check<T>(&mut self, cx: &mut Context<'_>, poll: Poll<io::Result<T>>) -> Poll<io::Result<T>> {
+        if poll.is_ready() {
+            self.sleep = None;
+            return poll;
+        }
+        let timeout = self.timeout;
+        let sleep = self
+            .sleep
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        if sleep.as_mut().poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+        self.sleep = None;
+        self.metrics.write_timed_out();
+        warn!(timeout = ?self.timeout, "streamed response write timed out");
+        Poll::Ready(Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "response write timed out",
+        )))
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for WriteDeadline<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for WriteDeadline<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.check(cx, poll)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.inner).poll_flush(cx);
+        self.check(cx, poll)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.inner).poll_shutdown(cx);
+        self.check(cx, poll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, duplex};
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn stalled_write_times_out_and_is_counted() {
+        let (server, mut client) = duplex(8);
+        let metrics = ServerMetrics::default();
+        let mut stream = WriteDeadline::new(server, Duration::from_secs(5), metrics.clone());
+
+        stream.write_all(b"12345678").await.unwrap();
+        let err = stream.write_all(b"more").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(metrics.write_timeouts(), 1);
+
+        // Once the peer reads, writes make progress again.
+        let mut read = [0; 8];
+        client.read_exact(&mut read).await.unwrap();
+        stream.write_all(b"more").await.unwrap();
+        assert_eq!(metrics.write_timeouts(), 1);
+    }
+}
@@ -0,0 +1,232 @@
+//! Listening sockets passed between processes, for restarts without downtime.
+//!
+//! A listening socket outlives the process that opened it as long as another process
+//! holds a copy, and connections waiting in its backlog stay queued meanwhile. Two
+//! sources of such a socket are recognized:
+//!
+//! - **systemd socket activation** — `LISTEN_PID` names this process and `LISTEN_FDS`
+//!   counts the sockets passed from descriptor 3 on.
+//! - **[`Handoff`]** — an old rttp process starts its replacement with
+//!   [`Handoff::spawn`], which leaves the listener open across `exec` and names it in
+//!   `RTTP_LISTEN_FD`.
+//!
+//! [`Server::bind_or_inherit`](super::Server::bind_or_inherit) uses an inherited
+//! socket when there is one and binds otherwise, so the same binary works under
+//! systemd, after a handoff, and when started by hand.
+//!
+//! # Examples
+//!
+//! On `SIGUSR2`, start the new binary on the same socket, then stop accepting and let
+//! the connections in progress finish:
+//!
+//! ```rust,no_run
+//! use std::process::Command;
+//! use rttp::{Response, StatusCode, server::Server};
+//! use tokio::signal::unix::{SignalKind, signal};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let server = Server::bind_or_inherit("0.0.0.0:8080").await?;
+//! let handoff = server.handoff()?;
+//! let mut upgrade = signal(SignalKind::user_defined2())?;
+//! let shutdown = async move {
+//!     upgrade.recv().await;
+//!     let binary = std::env::current_exe().expect("own path");
+//!     handoff.spawn(&mut Command::new(binary)).expect("replacement starts");
+//! };
+//! server
+//!     .run_until(|_req| async { Response::new(StatusCode::Ok) }, shutdown)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    io,
+    net::TcpListener,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    os::unix::process::CommandExt,
+    process::{Child, Command},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// The environment variable [`Handoff::spawn`] names the listener's descriptor in.
+pub const LISTEN_FD_VAR: &str = "RTTP_LISTEN_FD";
+
+// The first descriptor systemd passes.
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+// Set once a descriptor has been adopted, so no second owner closes it.
+static INHERITED: AtomicBool = AtomicBool::new(false);
+
+/// A copy of a server's listening socket that can be passed to a new process.
+///
+/// Taken with [`Server::handoff`](super::Server::handoff) before the server starts
+/// running. The copy is closed on `exec` except in processes started with
+/// [`spawn`](Self::spawn), and when the `Handoff` is dropped.
+#[derive(Debug)]
+pub struct Handoff {
+    fd: OwnedFd,
+}
+
+impl Handoff {
+    pub(super) fn new(fd: OwnedFd) -> Self {
+        Self { fd }
+    }
+
+    /// Starts `command` with the listening socket open and named in
+    /// [`LISTEN_FD_VAR`], so [`inherited_listener`] in the new process finds it.
+    ///
+    /// Any systemd `LISTEN_*` variables are removed from the command's environment.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from starting the process.
+    pub fn spawn(&self, command: &mut Command) -> io::Result<Child> {
+        let fd = self.fd.as_raw_fd();
+        command
+            .env(LISTEN_FD_VAR, fd.to_string())
+            .env_remove("LISTEN_PID")
+            .env_remove("LISTEN_FDS")
+            .env_remove("LISTEN_FDNAMES");
+        // SAFETY: `fcntl` is async-signal-safe, and `fd` stays open in the child because
+        // `self` borrows it for the duration of the spawn.
+        unsafe {
+            command.pre_exec(move || set_cloexec(fd, false));
+        }
+        command.spawn()
+    }
+}
+
+/// Takes the listening socket this process inherited, if any.
+///
+/// Looks first for a [`Handoff`] socket named in [`LISTEN_FD_VAR`], then for the
+/// first systemd socket when `LISTEN_PID` is this process. The descriptor must be a
+/// listening TCP socket. The socket is adopted at most once per process; later calls
+/// return `None`. Once adopted, it is closed on `exec` again.
+///
+/// # Errors
+///
+/// Returns an error if a descriptor is named but is not a listening TCP socket.
+pub fn inherited_listener() -> io::Result<Option<TcpListener>> {
+    let var = |name: &str| std::env::var(name).ok();
+    let Some(fd) = inherited_fd(var, std::process::id()) else {
+        return Ok(None);
+    };
+    if INHERITED.swap(true, Ordering::SeqCst) {
+        return Ok(None);
+    }
+    if !is_listening(fd)? {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("inherited descriptor {fd} is not a listening socket"),
+        ));
+    }
+    set_cloexec(fd, true)?;
+    // SAFETY: `fd` is an open listening socket that nothing else in this process owns;
+    // `INHERITED` keeps a second call from adopting it again.
+    let listener = unsafe { TcpListener::from_raw_fd(fd) };
+    // Fails for sockets that are not TCP, such as Unix domain sockets.
+    listener.local_addr()?;
+    Ok(Some(listener))
+}
+
+// Finds the inherited descriptor named by the environment `var` reads.
+fn inherited_fd(var: impl Fn(&str) -> Option<String>, pid: u32) -> Option<RawFd> {
+    if let Some(fd) = var(LISTEN_FD_VAR) {
+        return fd.parse().ok().filter(|fd| *fd >= 0);
+    }
+    let for_us = var("LISTEN_PID").and_then(|p| p.parse::<u32>().ok()) == Some(pid);
+    let count: RawFd = var("LISTEN_FDS")?.parse().ok()?;
+    (for_us && count > 0).then_some(SD_LISTEN_FDS_START)
+}
+
+fn is_listening(fd: RawFd) -> io::Result<bool> {
+    let mut accepting: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: `accepting` and `len` are valid for writes of the sizes passed.
+    let result = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_ACCEPTCONN,
+            (&raw mut accepting).cast(),
+            &raw mut len,
+        )
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(accepting != 0)
+}
+
+fn set_cloexec(fd: RawFd, on: bool) -> io::Result<()> {
+    // SAFETY: `F_GETFD` and `F_SETFD` only read and write the descriptor's flags.
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let flags = if on {
+            flags | libc::FD_CLOEXEC
+        } else {
+            flags & !libc::FD_CLOEXEC
+        };
+        if libc::fcntl(fd, libc::F_SETFD, flags) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        |name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| (*value).to_owned())
+        }
+    }
+
+    #[test]
+    fn finds_the_inherited_descriptor() {
+        assert_eq!(inherited_fd(env(&[]), 7), None);
+        assert_eq!(inherited_fd(env(&[(LISTEN_FD_VAR, "12")]), 7), Some(12));
+        assert_eq!(inherited_fd(env(&[(LISTEN_FD_VAR, "-1")]), 7), None);
+
+        let systemd = [("LISTEN_PID", "7"), ("LISTEN_FDS", "2")];
+        assert_eq!(inherited_fd(env(&systemd), 7), Some(3));
+        assert_eq!(
+            inherited_fd(env(&systemd), 8),
+            None,
+            "meant for another process"
+        );
+        let none = [("LISTEN_PID", "7"), ("LISTEN_FDS", "0")];
+        assert_eq!(inherited_fd(env(&none), 7), None);
+    }
+
+    #[test]
+    fn spawned_process_holds_the_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let fd = listener.as_raw_fd();
+        assert!(is_listening(fd).unwrap());
+        let handoff = Handoff::new(OwnedFd::from(listener.try_clone().unwrap()));
+
+        // Redirecting from the descriptor fails unless it is open in the child.
+        let status = handoff
+            .spawn(Command::new("sh").args(["-c", "exec 9<&\"$RTTP_LISTEN_FD\""]))
+            .unwrap()
+            .wait()
+            .unwrap();
+        assert!(status.success());
+
+        let status = Command::new("sh")
+            .args(["-c", "exec 9<&\"$FD\""])
+            .env("FD", handoff.fd.as_raw_fd().to_string())
+            .status()
+            .unwrap();
+        assert!(!status.success(), "closed on exec otherwise");
+    }
+}
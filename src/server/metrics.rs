@@ -0,0 +1,127 @@
+//! Counters of connection-level events.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering::Relaxed},
+};
+
+use crate::middleware::metrics::{MetricsSource, MetricsWriter};
+
+/// Counters of events the server handles below the request pipeline.
+///
+/// Obtained from [`Server::metrics`](super::Server::metrics); clones share the same
+/// counters. As a [`MetricsSource`] it exports `rttp_server_write_timeouts_total`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rttp::{middleware::Metrics, server::Server};
+///
+/// # async fn example() -> Result<(), rttp::server::ServerError> {
+/// let server = Server::bind("127.0.0.1:8080").await?;
+/// let metrics = Metrics::new();
+/// metrics.register(server.metrics());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ServerMetrics {
+    write_timeouts: Arc<AtomicU64>,
+}
+
+impl ServerMetrics {
+    /// Returns the number of connections closed because a response could not be
+    /// written within the [write timeout](super::Server::write_timeout).
+    pub fn write_timeouts(&self) -> u64 {
+        self.write_timeouts.load(Relaxed)
+    }
+
+    pub(super) fn write_timed_out(&self) {
+        self.write_timeouts.fetch_add(1, Relaxed);
+    }
+}
+
+impl MetricsSource for ServerMetrics {
+    fn collect(&self, out: &mut MetricsWriter) {
+        out.counter(
+            "rttp_server_write_timeouts_total",
+            "Connections closed because a response write timed out.",
+            &[],
+            self.write_timeouts() as f64,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::{io::AsyncWriteExt, net::TcpStream};
+
+    use crate::{Response, StatusCode, server::Server};
+
+    #[tokio::test]
+    async fn counts_writes_to_a_client_that_stops_reading() {
+        let server = Server::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .write_timeout(Duration::from_millis(50));
+        let addr = server.local_addr();
+        let metrics = server.metrics();
+        // Far larger than the socket buffers, so the write stalls.
+        let body = "x".repeat(64 * 1024 * 1024);
+        tokio::spawn(server.run(move |_req| {
+            let body = body.clone();
+            async move { Response::new(StatusCode::Ok).body(body) }
+        }));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        for _ in 0..200 {
+            if metrics.write_timeouts() == 1 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("write did not time out");
+    }
+
+    #[tokio::test]
+    async fn counts_stalled_file_downloads() {
+        let path = std::env::temp_dir().join(format!(
+            "rttp-write-timeout-{}",
+            crate::security::crypto::random_token(8)
+        ));
+        std::fs::write(&path, vec![b'x'; 64 * 1024 * 1024]).unwrap();
+        let server = Server::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .write_timeout(Duration::from_millis(50));
+        let addr = server.local_addr();
+        let metrics = server.metrics();
+        let served = path.clone();
+        tokio::spawn(server.run(move |_req| {
+            let served = served.clone();
+            async move { Response::file(&served).await.unwrap() }
+        }));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut timed_out = false;
+        for _ in 0..200 {
+            if metrics.write_timeouts() == 1 {
+                timed_out = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let _ = std::fs::remove_file(&path);
+        assert!(timed_out, "file download did not time out");
+    }
+}
@@ -1,22 +1,43 @@
 //! Async TCP server using Tokio.
 //!
 //! Accepts TCP connections and dispatches HTTP/1.1 requests to a handler function.
-//! Supports HTTP/1.1 persistent connections (keep-alive) out of the box.
+//! Supports HTTP/1.1 persistent connections (keep-alive) out of the box, and TLS
+//! (including mutual TLS) with the `tls` feature.
+
+mod deadline;
+#[cfg(unix)]
+pub mod handoff;
+pub mod metrics;
+pub mod pool;
+#[cfg(feature = "tls")]
+pub mod tls;
+
+pub use crate::http::{ConnectionInfo, PeerCertificate};
+#[cfg(unix)]
+pub use handoff::Handoff;
+pub use metrics::ServerMetrics;
+pub use pool::BufferPool;
+
+use deadline::WriteDeadline;
 
 use std::future::Future;
+use std::io::{self, IoSlice};
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use bytes::BytesMut;
 use thiserror::Error;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::time::Instant;
 use tracing::{debug, error, info, warn};
 
 use crate::http::{
-    StatusCode,
-    request::{Request, RequestError},
+    Interim, StatusCode,
+    request::{ParseLimits, Request, RequestError},
     response::Response,
+    upgrade::Upgraded,
 };
 
 /// Errors produced by the server.
@@ -31,13 +52,21 @@ pub enum ServerError {
         #[source]
         source: std::io::Error,
     },
+
+    #[cfg(feature = "tls")]
+    #[error("TLS error: {0}")]
+    Tls(#[from] tls::TlsError),
 }
 
 /// Maximum size of a complete HTTP request we will buffer before rejecting it (8 MiB).
 const MAX_REQUEST_SIZE: usize = 8 * 1024 * 1024;
 
-/// Initial read buffer capacity per connection.
-const INITIAL_BUF_SIZE: usize = 4096;
+/// How long writing a response may take by default.
+const DEFAULT_WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a client may take to complete the TLS handshake by default.
+#[cfg(feature = "tls")]
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// The rttp HTTP server.
 ///
@@ -62,6 +91,21 @@ const INITIAL_BUF_SIZE: usize = 4096;
 pub struct Server {
     listener: TcpListener,
     local_addr: SocketAddr,
+    limits: ParseLimits,
+    write_timeout: Duration,
+    metrics: ServerMetrics,
+    pool: Arc<BufferPool>,
+    #[cfg(feature = "tls")]
+    tls: Option<tokio_rustls::TlsAcceptor>,
+    #[cfg(feature = "tls")]
+    handshake_timeout: Duration,
+}
+
+// What every connection's request loop needs from the server.
+struct Settings {
+    limits: ParseLimits,
+    write_timeout: Duration,
+    metrics: ServerMetrics,
 }
 
 impl Server {
@@ -79,18 +123,142 @@ impl Server {
                 addr: addr.to_owned(),
                 source: e,
             })?;
+        Self::from_tokio(listener)
+    }
+
+    /// Serves on a listener opened elsewhere, such as one passed down by a supervisor.
+    ///
+    /// Must be called from within a Tokio runtime.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ServerError::Io`] if the listener cannot be registered with the
+    /// runtime.
+    pub fn from_listener(listener: std::net::TcpListener) -> Result<Self, ServerError> {
+        listener.set_nonblocking(true)?;
+        Self::from_tokio(TcpListener::from_std(listener)?)
+    }
+
+    /// Serves on the listening socket this process inherited through systemd socket
+    /// activation or a [`Handoff`], or binds `addr` if there is none.
+    ///
+    /// See [`handoff`] for how the socket is found.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ServerError::Io`] if an inherited descriptor is not a listening TCP
+    /// socket, and [`ServerError::Bind`] if `addr` cannot be bound.
+    #[cfg(unix)]
+    pub async fn bind_or_inherit(addr: impl AsRef<str>) -> Result<Self, ServerError> {
+        match handoff::inherited_listener()? {
+            Some(listener) => {
+                let server = Self::from_listener(listener)?;
+                info!(address = %server.local_addr, "inherited listening socket");
+                Ok(server)
+            }
+            None => Self::bind(addr).await,
+        }
+    }
+
+    fn from_tokio(listener: TcpListener) -> Result<Self, ServerError> {
         let local_addr = listener.local_addr()?;
         Ok(Self {
             listener,
             local_addr,
+            limits: ParseLimits::default(),
+            write_timeout: DEFAULT_WRITE_TIMEOUT,
+            metrics: ServerMetrics::default(),
+            pool: Arc::new(BufferPool::default()),
+            #[cfg(feature = "tls")]
+            tls: None,
+            #[cfg(feature = "tls")]
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
         })
     }
 
+    /// Sets the query-parameter and cookie caps enforced on every request.
+    ///
+    /// Requests exceeding a cap are answered with `400 Bad Request` and the connection is
+    /// closed. Defaults to [`ParseLimits::default`].
+    #[must_use]
+    pub fn parse_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets how long writing a response may take before the connection is closed, so a
+    /// client that stops reading cannot hold the connection and its buffers forever.
+    /// Defaults to 30 seconds.
+    ///
+    /// The timeout covers informational heads, the response head and in-memory body,
+    /// and each write of a streamed body such as a file download; connections upgraded
+    /// by `101 Switching Protocols` manage their own writes. Expiry is logged and counted
+    /// in [`ServerMetrics::write_timeouts`].
+    #[must_use]
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    /// Returns the server's connection-level counters, to read or register with a
+    /// [`Metrics`](crate::middleware::Metrics) registry.
+    pub fn metrics(&self) -> ServerMetrics {
+        self.metrics.clone()
+    }
+
+    /// Sets the pool connection buffers are taken from. Defaults to
+    /// [`BufferPool::default`].
+    #[must_use]
+    pub fn buffer_pool(mut self, pool: BufferPool) -> Self {
+        self.pool = Arc::new(pool);
+        self
+    }
+
+    /// Serves HTTPS instead of plain HTTP.
+    ///
+    /// Every accepted connection performs a TLS handshake before any request is read;
+    /// failed handshakes are logged and the connection is dropped. When client
+    /// certificates are configured, the verified chain is exposed through
+    /// [`ConnectionInfo::peer_certificates`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ServerError::Tls`] if the certificate, key, or client CA set is
+    /// rejected by the TLS stack.
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, config: tls::TlsConfig) -> Result<Self, ServerError> {
+        self.tls = Some(config.into_acceptor()?);
+        Ok(self)
+    }
+
+    /// Sets how long a client may take to complete the TLS handshake before the
+    /// connection is dropped, so one that connects and stalls cannot hold a task and a
+    /// socket forever. Defaults to 10 seconds.
+    #[cfg(feature = "tls")]
+    #[must_use]
+    pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
     /// Returns the local address the server is bound to.
     pub fn local_addr(&self) -> SocketAddr {
         self.local_addr
     }
 
+    /// Copies the listening socket so a replacement process can take it over with
+    /// [`Handoff::spawn`] while this one finishes its connections.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ServerError::Io`] if the descriptor cannot be duplicated.
+    #[cfg(unix)]
+    pub fn handoff(&self) -> Result<Handoff, ServerError> {
+        use std::os::fd::AsFd;
+
+        Ok(Handoff::new(self.listener.as_fd().try_clone_to_owned()?))
+    }
+
     /// Starts accepting connections and dispatching requests to `handler`.
     ///
     /// The handler receives a [`Request`] and must return a [`Future`] that
@@ -107,12 +275,44 @@ impl Server {
     where
         H: Fn(Request) -> F + Send + Sync + 'static,
         F: Future<Output = Response> + Send + 'static,
+    {
+        self.run_until(handler, std::future::pending()).await
+    }
+
+    /// Like [`run`](Self::run), but stops accepting connections once `shutdown`
+    /// resolves and then returns.
+    ///
+    /// Connections already accepted keep being served by their own tasks. Use the
+    /// return as the point to drain other subsystems, such as
+    /// [`TaskQueue::shutdown`](crate::background::TaskQueue::shutdown).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ServerError::Io`] if the TCP listener itself fails.
+    pub async fn run_until<H, F, S>(self, handler: H, shutdown: S) -> Result<(), ServerError>
+    where
+        H: Fn(Request) -> F + Send + Sync + 'static,
+        F: Future<Output = Response> + Send + 'static,
+        S: Future<Output = ()>,
     {
         let handler = Arc::new(handler);
+        let settings = Arc::new(Settings {
+            limits: self.limits,
+            write_timeout: self.write_timeout,
+            metrics: self.metrics.clone(),
+        });
         info!(address = %self.local_addr, "rttp listening");
+        let mut shutdown = std::pin::pin!(shutdown);
 
         loop {
-            let (stream, peer_addr) = match self.listener.accept().await {
+            let accepted = tokio::select! {
+                accepted = self.listener.accept() => accepted,
+                () = &mut shutdown => {
+                    info!(address = %self.local_addr, "rttp shutting down");
+                    return Ok(());
+                }
+            };
+            let (stream, peer_addr) = match accepted {
                 Ok(pair) => pair,
                 Err(e) => {
                     error!(error = %e, "failed to accept connection");
@@ -122,9 +322,52 @@ impl Server {
 
             debug!(peer = %peer_addr, "connection accepted");
             let handler = Arc::clone(&handler);
+            let settings = Arc::clone(&settings);
+            let pool = Arc::clone(&self.pool);
+            let info = ConnectionInfo::new(peer_addr, self.local_addr);
+            #[cfg(feature = "tls")]
+            let (acceptor, handshake_timeout) = (self.tls.clone(), self.handshake_timeout);
 
             tokio::spawn(async move {
-                if let Err(e) = handle_connection(stream, peer_addr, handler).await {
+                #[cfg(feature = "tls")]
+                if let Some(acceptor) = acceptor {
+                    let handshake = acceptor.accept(stream);
+                    let stream = match tokio::time::timeout(handshake_timeout, handshake).await {
+                        Ok(Ok(stream)) => stream,
+                        Ok(Err(e)) => {
+                            warn!(peer = %peer_addr, error = %e, "TLS handshake failed");
+                            return;
+                        }
+                        Err(_) => {
+                            warn!(peer = %peer_addr, timeout = ?handshake_timeout, "TLS handshake timed out");
+                            return;
+                        }
+                    };
+                    // Every certificate must parse: skipping one would shift the chain and
+                    // present an issuer as the client.
+                    let chain = stream
+                        .get_ref()
+                        .1
+                        .peer_certificates()
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|der| PeerCertificate::from_der(der))
+                        .collect::<Option<Vec<_>>>();
+                    let Some(chain) = chain else {
+                        warn!(peer = %peer_addr, "unparseable client certificate");
+                        return;
+                    };
+                    let info = Arc::new(info.with_tls(chain));
+                    if let Err(e) = handle_connection(stream, info, handler, &settings, &pool).await
+                    {
+                        warn!(peer = %peer_addr, error = %e, "connection closed with error");
+                    }
+                    return;
+                }
+
+                if let Err(e) =
+                    handle_connection(stream, Arc::new(info), handler, &settings, &pool).await
+                {
                     warn!(peer = %peer_addr, error = %e, "connection closed with error");
                 }
             });
@@ -132,29 +375,129 @@ impl Server {
     }
 }
 
-/// Handles a single TCP connection over its lifetime.
+// Writes the response head and body together, without copying the body next to the
+// head; writers that cannot take both at once get them over several writes.
+async fn write_response<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    response: Response,
+    buf: &mut BytesMut,
+) -> io::Result<()> {
+    buf.clear();
+    let body = response.encode_head(buf);
+    let (mut head, mut body) = (&buf[..], &body[..]);
+    while !head.is_empty() || !body.is_empty() {
+        let n = stream
+            .write_vectored(&[IoSlice::new(head), IoSlice::new(body)])
+            .await?;
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        let from_head = n.min(head.len());
+        head = &head[from_head..];
+        body = &body[n - from_head..];
+    }
+    Ok(())
+}
+
+// Writes and flushes a response within the write timeout, counting expiry.
+async fn send_response<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    response: Response,
+    buf: &mut BytesMut,
+    settings: &Settings,
+) -> io::Result<()> {
+    let writing = async {
+        write_response(stream, response, buf).await?;
+        stream.flush().await
+    };
+    within_write_timeout(writing, settings).await
+}
+
+// Writes and flushes an informational head within the write timeout, counting expiry.
+async fn send_interim<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    head: &[u8],
+    settings: &Settings,
+) -> io::Result<()> {
+    let writing = async {
+        stream.write_all(head).await?;
+        stream.flush().await
+    };
+    within_write_timeout(writing, settings).await
+}
+
+async fn within_write_timeout(
+    writing: impl Future<Output = io::Result<()>>,
+    settings: &Settings,
+) -> io::Result<()> {
+    match tokio::time::timeout(settings.write_timeout, writing).await {
+        Ok(result) => result,
+        Err(_) => {
+            settings.metrics.write_timed_out();
+            warn!(timeout = ?settings.write_timeout, "response write timed out");
+            Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "response write timed out",
+            ))
+        }
+    }
+}
+
+/// Handles a single connection (plain TCP or TLS) over its lifetime.
 ///
 /// HTTP/1.1 connections are persistent by default: we loop, reading one
 /// request per iteration, until the peer closes the connection or signals
-/// `Connection: close`.
-async fn handle_connection<H, F>(
-    mut stream: TcpStream,
-    peer_addr: SocketAddr,
+/// `Connection: close`. A `101 Switching Protocols` response with an upgrade callback,
+/// or a streamed response, ends the HTTP loop and hands the connection to its callback.
+async fn handle_connection<S, H, F>(
+    stream: S,
+    info: Arc<ConnectionInfo>,
     handler: Arc<H>,
+    settings: &Settings,
+    pool: &BufferPool,
 ) -> Result<(), std::io::Error>
 where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
     H: Fn(Request) -> F + Send + Sync + 'static,
     F: Future<Output = Response> + Send + 'static,
 {
-    let mut buf = BytesMut::with_capacity(INITIAL_BUF_SIZE);
+    let mut buf = pool.get();
+    let mut head = pool.get();
+    let result = serve_connection(stream, info, handler, settings, &mut buf, &mut head).await;
+    pool.put(buf);
+    pool.put(head);
+    result
+}
+
+// The request loop of `handle_connection`, reading into `buf` and serializing response
+// heads into `head`.
+async fn serve_connection<S, H, F>(
+    mut stream: S,
+    info: Arc<ConnectionInfo>,
+    handler: Arc<H>,
+    settings: &Settings,
+    buf: &mut BytesMut,
+    head: &mut BytesMut,
+) -> Result<(), std::io::Error>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    H: Fn(Request) -> F + Send + Sync + 'static,
+    F: Future<Output = Response> + Send + 'static,
+{
+    let peer_addr = info.peer_addr();
+    // Whether `100 Continue` has gone out for the request being read.
+    let mut continued = false;
+    // When the first bytes of the request being read arrived.
+    let mut read_started = None;
 
     loop {
-        let bytes_read = stream.read_buf(&mut buf).await?;
+        let bytes_read = stream.read_buf(buf).await?;
 
         if bytes_read == 0 {
             debug!(peer = %peer_addr, "connection closed by peer");
             break;
         }
+        let started = *read_started.get_or_insert_with(Instant::now);
 
         // Guard against excessively large requests.
         if buf.len() > MAX_REQUEST_SIZE {
@@ -162,12 +505,12 @@ where
             let response = Response::new(StatusCode::PayloadTooLarge)
                 .body("Request entity too large")
                 .keep_alive(false);
-            stream.write_all(&response.into_bytes()).await?;
+            send_response(&mut stream, response, head, settings).await?;
             break;
         }
 
         // Attempt to parse the buffered data as an HTTP request.
-        let (request, body_offset) = match Request::parse(&buf) {
+        let (mut request, body_offset) = match Request::parse_with_limits(buf, &settings.limits) {
             Ok(pair) => pair,
             Err(RequestError::Incomplete) => {
                 // Headers not yet fully received — read more data.
@@ -178,7 +521,7 @@ where
                 let response = Response::new(StatusCode::BadRequest)
                     .body(format!("Bad Request: {e}"))
                     .keep_alive(false);
-                stream.write_all(&response.into_bytes()).await?;
+                send_response(&mut stream, response, head, settings).await?;
                 break;
             }
         };
@@ -187,10 +530,22 @@ where
         let content_length = request.content_length().unwrap_or(0);
         let total_needed = body_offset + content_length;
         if buf.len() < total_needed {
+            let expects_continue = request
+                .headers()
+                .get("expect")
+                .is_some_and(|e| e.eq_ignore_ascii_case("100-continue"));
+            if expects_continue && request.version() == 1 && !continued {
+                send_interim(&mut stream, b"HTTP/1.1 100 Continue\r\n\r\n", settings).await?;
+                continued = true;
+            }
             continue;
         }
+        continued = false;
+        read_started = None;
 
         let keep_alive = request.is_keep_alive();
+        request.set_connection(Arc::clone(&info));
+        request.set_read_duration(started.elapsed());
 
         debug!(
             peer = %peer_addr,
@@ -199,18 +554,62 @@ where
             "dispatching request"
         );
 
-        let response = handler(request).await;
-        stream.write_all(&response.into_bytes()).await?;
-        stream.flush().await?;
+        // Informational responses the handler sends are written while it runs; any
+        // still queued when it returns go out before the final response.
+        let (interim, mut interim_rx) = Interim::channel();
+        if request.version() == 1 {
+            request.set_interim(interim);
+        } else {
+            drop(interim);
+        }
+        let mut handling = std::pin::pin!(handler(request));
+        let mut response = loop {
+            tokio::select! {
+                biased;
+                Some(head) = interim_rx.recv() => {
+                    send_interim(&mut stream, &head, settings).await?;
+                }
+                response = &mut handling => break response,
+            }
+        };
+        interim_rx.close();
+        while let Ok(head) = interim_rx.try_recv() {
+            send_interim(&mut stream, &head, settings).await?;
+        }
+        let streamed = response.is_streamed();
+        let takes_over = response.status() == StatusCode::SwitchingProtocols || streamed;
+        let upgrade = takes_over.then(|| response.take_upgrade()).flatten();
+        let sent = response.take_sent_hooks();
+        let writing = Instant::now();
+        send_response(&mut stream, response, head, settings).await?;
+        sent.sent(writing.elapsed());
 
         // Drop the consumed request bytes from the buffer.
         let _ = buf.split_to(total_needed);
 
+        if let Some(upgrade) = upgrade {
+            debug!(peer = %peer_addr, "connection upgraded");
+            let buffered = std::mem::take(buf);
+            let upgraded = if streamed {
+                // A streamed body is still a response: each of its writes gets a deadline.
+                let stream =
+                    WriteDeadline::new(stream, settings.write_timeout, settings.metrics.clone());
+                Upgraded::new(stream, buffered)
+            } else {
+                Upgraded::new(stream, buffered)
+            };
+            upgrade.run(upgraded).await;
+            return Ok(());
+        }
+
         if !keep_alive {
             debug!(peer = %peer_addr, "Connection: close — shutting down");
             break;
         }
     }
 
+    // Orderly close: a TCP FIN, or a `close_notify` alert on TLS streams. The peer may
+    // already be gone, so failure here is not worth reporting.
+    let _ = stream.shutdown().await;
     Ok(())
 }
@@ -0,0 +1,124 @@
+//! Byte buffers reused across connections.
+
+use std::sync::Mutex;
+
+use bytes::BytesMut;
+
+/// Capacity of a freshly allocated buffer.
+const INITIAL_CAPACITY: usize = 4096;
+
+/// A shared pool of connection buffers.
+///
+/// Each connection takes its read buffer and its response-head buffer from the pool
+/// and returns them when it closes, so a server under steady load stops allocating
+/// them. Within a connection the same two buffers serve every request. Buffers that
+/// grew past [`max_capacity`](Self::max_capacity) while reading a large request are
+/// freed rather than kept, and the pool holds at most `max_buffers` idle buffers.
+///
+/// # Examples
+///
+/// ```rust
+/// use rttp::server::BufferPool;
+///
+/// let pool = BufferPool::new(2);
+/// let mut buf = pool.get();
+/// buf.extend_from_slice(b"GET / HTTP/1.1\r\n\r\n");
+/// pool.put(buf);
+/// assert_eq!(pool.idle(), 1);
+/// assert!(pool.get().is_empty());
+/// ```
+#[derive(Debug)]
+pub struct BufferPool {
+    buffers: Mutex<Vec<BytesMut>>,
+    max_buffers: usize,
+    max_capacity: usize,
+}
+
+impl BufferPool {
+    /// Creates a pool that keeps up to `max_buffers` idle buffers of up to 64 KiB each.
+    pub fn new(max_buffers: usize) -> Self {
+        Self {
+            buffers: Mutex::new(Vec::new()),
+            max_buffers,
+            max_capacity: 64 * 1024,
+        }
+    }
+
+    /// Sets the largest capacity a returned buffer may have and still be kept.
+    #[must_use]
+    pub fn max_capacity(mut self, bytes: usize) -> Self {
+        self.max_capacity = bytes;
+        self
+    }
+
+    /// Takes an empty buffer, reusing an idle one if there is one.
+    pub fn get(&self) -> BytesMut {
+        self.buffers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop()
+            .unwrap_or_else(|| BytesMut::with_capacity(INITIAL_CAPACITY))
+    }
+
+    /// Returns a buffer to the pool. Its contents are discarded.
+    pub fn put(&self, mut buf: BytesMut) {
+        buf.clear();
+        // Reading a connection advances the buffer through its allocation; when nothing
+        // else shares it, this moves the start back and recovers the full capacity.
+        buf.reserve(INITIAL_CAPACITY);
+        if buf.capacity() > self.max_capacity {
+            return;
+        }
+        let mut buffers = self.buffers.lock().unwrap_or_else(|e| e.into_inner());
+        if buffers.len() < self.max_buffers {
+            buffers.push(buf);
+        }
+    }
+
+    /// Returns the number of idle buffers.
+    pub fn idle(&self) -> usize {
+        self.buffers.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+impl Default for BufferPool {
+    /// A pool of up to 1024 idle buffers.
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_buffers_within_limits() {
+        let pool = BufferPool::new(2).max_capacity(INITIAL_CAPACITY * 2);
+        let mut buf = pool.get();
+        buf.extend_from_slice(b"data");
+        let ptr = buf.as_ptr();
+        pool.put(buf);
+        let buf = pool.get();
+        assert!(buf.is_empty());
+        assert_eq!(buf.as_ptr(), ptr, "the same allocation comes back");
+
+        // Space consumed from the front is recovered.
+        let mut buf = pool.get();
+        buf.extend_from_slice(&[b'x'; INITIAL_CAPACITY]);
+        let _ = buf.split_to(INITIAL_CAPACITY - 1);
+        pool.put(buf);
+        let buf = pool.get();
+        assert!(buf.capacity() >= INITIAL_CAPACITY);
+        drop(buf);
+
+        // Too large to keep.
+        pool.put(BytesMut::with_capacity(INITIAL_CAPACITY * 4));
+        assert_eq!(pool.idle(), 0);
+
+        for _ in 0..3 {
+            pool.put(BytesMut::with_capacity(INITIAL_CAPACITY));
+        }
+        assert_eq!(pool.idle(), 2);
+    }
+}
@@ -0,0 +1,160 @@
+//! TLS listener configuration, including mutual-TLS client authentication.
+//!
+//! Available with the `tls` feature (on by default). Certificates and keys are loaded
+//! from PEM; the handshake is performed by rustls.
+
+use std::sync::Arc;
+
+use rustls::{
+    RootCertStore, ServerConfig,
+    crypto::{CryptoProvider, ring},
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    server::WebPkiClientVerifier,
+};
+use thiserror::Error;
+use tokio_rustls::TlsAcceptor;
+
+/// Errors produced while building a [`TlsConfig`].
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("invalid PEM: {0}")]
+    Pem(String),
+
+    #[error("no certificates found in PEM input")]
+    NoCertificates,
+
+    #[error("TLS configuration error: {0}")]
+    Rustls(#[from] rustls::Error),
+
+    #[error("client certificate verifier error: {0}")]
+    Verifier(String),
+}
+
+// Whether and how client certificates are requested during the handshake.
+#[derive(Debug, Clone)]
+enum ClientAuth {
+    None,
+    Optional(Vec<CertificateDer<'static>>),
+    Required(Vec<CertificateDer<'static>>),
+}
+
+/// Server-side TLS settings.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rttp::server::{Server, tls::TlsConfig};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let tls = TlsConfig::from_pem(
+///     &std::fs::read("server.crt")?,
+///     &std::fs::read("server.key")?,
+/// )?
+/// .require_client_cert(&std::fs::read("clients-ca.crt")?)?;
+///
+/// let server = Server::bind("0.0.0.0:8443").await?.tls(tls)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TlsConfig {
+    cert_chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+    client_auth: ClientAuth,
+}
+
+impl TlsConfig {
+    /// Creates a configuration from a PEM certificate chain (leaf first) and a PEM
+    /// private key (PKCS#8, PKCS#1, or SEC1).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TlsError::Pem`] if either input cannot be decoded, or
+    /// [`TlsError::NoCertificates`] if `cert_chain_pem` holds no certificates.
+    pub fn from_pem(cert_chain_pem: &[u8], key_pem: &[u8]) -> Result<Self, TlsError> {
+        let cert_chain = parse_certificates(cert_chain_pem)?;
+        let key =
+            PrivateKeyDer::from_pem_slice(key_pem).map_err(|e| TlsError::Pem(e.to_string()))?;
+        Ok(Self {
+            cert_chain,
+            key,
+            client_auth: ClientAuth::None,
+        })
+    }
+
+    /// Requires every client to present a certificate issued by one of the CAs in
+    /// `ca_pem`. Handshakes without a valid client certificate fail.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `ca_pem` cannot be decoded or holds no certificates.
+    pub fn require_client_cert(mut self, ca_pem: &[u8]) -> Result<Self, TlsError> {
+        self.client_auth = ClientAuth::Required(parse_certificates(ca_pem)?);
+        Ok(self)
+    }
+
+    /// Requests, but does not require, a client certificate issued by one of the CAs in
+    /// `ca_pem`. A presented certificate must still verify; clients that present none
+    /// are admitted, and routes can demand one with
+    /// [`ClientCertMiddleware`](crate::security::mtls::ClientCertMiddleware).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `ca_pem` cannot be decoded or holds no certificates.
+    pub fn request_client_cert(mut self, ca_pem: &[u8]) -> Result<Self, TlsError> {
+        self.client_auth = ClientAuth::Optional(parse_certificates(ca_pem)?);
+        Ok(self)
+    }
+
+    // Builds the rustls acceptor.
+    pub(crate) fn into_acceptor(self) -> Result<TlsAcceptor, TlsError> {
+        let provider = Arc::new(ring::default_provider());
+        let builder = ServerConfig::builder_with_provider(Arc::clone(&provider))
+            .with_safe_default_protocol_versions()?;
+
+        let builder = match self.client_auth {
+            ClientAuth::None => builder.with_no_client_auth(),
+            ClientAuth::Optional(roots) => {
+                builder.with_client_cert_verifier(client_verifier(roots, &provider, true)?)
+            }
+            ClientAuth::Required(roots) => {
+                builder.with_client_cert_verifier(client_verifier(roots, &provider, false)?)
+            }
+        };
+
+        let mut config = builder.with_single_cert(self.cert_chain, self.key)?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+fn parse_certificates(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certs = CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| TlsError::Pem(e.to_string()))?;
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificates);
+    }
+    Ok(certs)
+}
+
+fn client_verifier(
+    roots: Vec<CertificateDer<'static>>,
+    provider: &Arc<CryptoProvider>,
+    allow_anonymous: bool,
+) -> Result<Arc<dyn rustls::server::danger::ClientCertVerifier>, TlsError> {
+    let mut store = RootCertStore::empty();
+    for root in roots {
+        store.add(root)?;
+    }
+    let builder =
+        WebPkiClientVerifier::builder_with_provider(Arc::new(store), Arc::clone(provider));
+    let builder = if allow_anonymous {
+        builder.allow_unauthenticated()
+    } else {
+        builder
+    };
+    builder
+        .build()
+        .map_err(|e| TlsError::Verifier(e.to_string()))
+}
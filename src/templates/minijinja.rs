@@ -0,0 +1,223 @@
+//! [MiniJinja](https://docs.rs/minijinja) templates, behind the `minijinja` feature.
+
+use std::{path::PathBuf, sync::RwLock};
+
+use minijinja::{Environment, ErrorKind};
+use serde_json::Value;
+
+use super::{Reload, TemplateEngine, TemplateError, error_chain};
+
+/// Templates in Jinja2 syntax, rendered by MiniJinja.
+///
+/// Templates named `*.html`, `*.htm`, and `*.xml` are HTML-escaped, as MiniJinja does
+/// by default. Templates loaded from a directory are read on first use; with hot reload
+/// — on by default in debug builds — they are all dropped and re-read once any file
+/// under the directory changes.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::templates::{MiniJinja, TemplateEngine};
+/// use serde_json::json;
+///
+/// let engine = MiniJinja::new()
+///     .template("base.html", "<title>{% block title %}{% endblock %}</title>")
+///     .unwrap()
+///     .template(
+///         "post.html",
+///         r#"{% extends "base.html" %}{% block title %}{{ title | upper }}{% endblock %}"#,
+///     )
+///     .unwrap();
+/// let html = engine.render("post.html", &json!({ "title": "a & b" })).unwrap();
+/// assert_eq!(html, "<title>A &amp; B</title>");
+/// ```
+#[derive(Debug)]
+pub struct MiniJinja {
+    env: RwLock<Environment<'static>>,
+    dir: Option<Reload>,
+    hot_reload: bool,
+    // Templates added from strings, re-added after the loaded ones are dropped.
+    inline: Vec<(String, String)>,
+}
+
+impl MiniJinja {
+    /// Creates an engine with no templates.
+    pub fn new() -> Self {
+        Self::from(Environment::new())
+    }
+
+    /// Loads templates from files under `dir` on first use, named by their path relative
+    /// to `dir` with `/` separators (`emails/welcome.html`). Hot reload is on in debug
+    /// builds.
+    pub fn from_dir(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        let mut env = Environment::new();
+        env.set_loader(minijinja::path_loader(&dir));
+        Self {
+            dir: Some(Reload::new(dir)),
+            hot_reload: cfg!(debug_assertions),
+            ..Self::from(env)
+        }
+    }
+
+    /// Adds a template from a string, replacing any with the same name.
+    ///
+    /// # Errors
+    ///
+    /// [`TemplateError::Syntax`] if `source` does not parse.
+    pub fn template(mut self, name: &str, source: &str) -> Result<Self, TemplateError> {
+        let env = self.env.get_mut().unwrap_or_else(|e| e.into_inner());
+        add(env, name, source)?;
+        self.inline.push((name.to_owned(), source.to_owned()));
+        Ok(self)
+    }
+
+    /// Turns re-reading changed template files on or off.
+    #[must_use]
+    pub fn hot_reload(mut self, enabled: bool) -> Self {
+        self.hot_reload = enabled;
+        self
+    }
+
+    // Drops the templates read from the directory if a file there changed.
+    fn refresh(&self) -> Result<(), TemplateError> {
+        let Some(dir) = self.dir.as_ref().filter(|_| self.hot_reload) else {
+            return Ok(());
+        };
+        if dir.changed() {
+            let mut env = self.env.write().unwrap_or_else(|e| e.into_inner());
+            env.clear_templates();
+            for (name, source) in &self.inline {
+                add(&mut env, name, source)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for MiniJinja {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<Environment<'static>> for MiniJinja {
+    /// Wraps an already configured [`Environment`], for custom filters and functions.
+    fn from(env: Environment<'static>) -> Self {
+        Self {
+            env: RwLock::new(env),
+            dir: None,
+            hot_reload: false,
+            inline: Vec::new(),
+        }
+    }
+}
+
+impl TemplateEngine for MiniJinja {
+    fn render(&self, name: &str, context: &Value) -> Result<String, TemplateError> {
+        self.refresh()?;
+        let env = self.env.read().unwrap_or_else(|e| e.into_inner());
+        env.get_template(name)
+            .and_then(|template| template.render(context))
+            .map_err(|e| match e.kind() {
+                ErrorKind::TemplateNotFound => TemplateError::NotFound(name.to_owned()),
+                ErrorKind::SyntaxError => TemplateError::Syntax {
+                    name: e.name().unwrap_or(name).to_owned(),
+                    message: error_chain(&e),
+                },
+                _ => TemplateError::Render {
+                    name: name.to_owned(),
+                    message: error_chain(&e),
+                },
+            })
+    }
+}
+
+fn add(env: &mut Environment<'static>, name: &str, source: &str) -> Result<(), TemplateError> {
+    env.add_template_owned(name.to_owned(), source.to_owned())
+        .map_err(|e| TemplateError::Syntax {
+            name: name.to_owned(),
+            message: error_chain(&e),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::SystemTime};
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn renders_and_reports_errors() {
+        let mut env = Environment::new();
+        env.set_undefined_behavior(minijinja::UndefinedBehavior::Strict);
+        let engine = MiniJinja::from(env)
+            .template(
+                "list.html",
+                "{% for item in items %}<li>{{ item }}</li>{% endfor %}",
+            )
+            .unwrap()
+            .template("strict.txt", "{{ missing.field }}")
+            .unwrap();
+        assert_eq!(
+            engine
+                .render("list.html", &json!({ "items": ["<a>", "b"] }))
+                .unwrap(),
+            "<li>&lt;a&gt;</li><li>b</li>"
+        );
+        assert!(matches!(
+            engine.render("nope.html", &json!({})),
+            Err(TemplateError::NotFound(name)) if name == "nope.html"
+        ));
+        assert!(matches!(
+            engine.render("strict.txt", &json!({})),
+            Err(TemplateError::Render { .. })
+        ));
+        assert!(matches!(
+            MiniJinja::new().template("bad.html", "{% if %}"),
+            Err(TemplateError::Syntax { .. })
+        ));
+    }
+
+    #[test]
+    fn loads_and_reloads_files() {
+        let dir = std::env::temp_dir().join(format!(
+            "rttp-minijinja-{}",
+            crate::security::crypto::random_token(8)
+        ));
+        fs::create_dir_all(dir.join("emails")).unwrap();
+        let file = dir.join("emails/welcome.html");
+        fs::write(&file, "Hi {{ name }}").unwrap();
+
+        let engine = MiniJinja::from_dir(&dir)
+            .hot_reload(true)
+            .template("inline.txt", "{{ name }}!")
+            .unwrap();
+        let context = json!({ "name": "Ada" });
+        assert_eq!(
+            engine.render("emails/welcome.html", &context).unwrap(),
+            "Hi Ada"
+        );
+
+        fs::write(&file, "Hello {{ name }}").unwrap();
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(
+            engine.render("emails/welcome.html", &context).unwrap(),
+            "Hello Ada"
+        );
+        assert_eq!(engine.render("inline.txt", &context).unwrap(), "Ada!");
+        assert!(matches!(
+            engine.render("../secret", &context),
+            Err(TemplateError::NotFound(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
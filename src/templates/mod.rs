@@ -0,0 +1,332 @@
+//! Server-side template rendering.
+//!
+//! A [`TemplateEngine`] turns a template name and a JSON context into text. [`Templates`]
+//! wraps an engine for handlers: installed as middleware, it lets any handler answer
+//! with `ctx.render("page.html", &data)` through [`TemplateExt`].
+//!
+//! - [`Mustache`] — the built-in engine, loading templates from a directory and
+//!   reloading changed files during development.
+//! - `Tera` — [Tera](https://keats.github.io/tera/) templates, with the `tera` feature.
+//! - `MiniJinja` — [MiniJinja](https://docs.rs/minijinja) templates, with the
+//!   `minijinja` feature.
+//! - [`TemplateEngine`] — implement it to render with another engine.
+//!
+//! All three engines load templates from strings or a directory and, in debug builds,
+//! pick up edited files without a restart.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::Arc;
+//! use rttp::{Router, StatusCode, context::Context, middleware::from_middleware};
+//! use rttp::templates::{Mustache, TemplateExt, Templates};
+//! use rttp::testing::TestClient;
+//! use serde_json::json;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let engine = Mustache::new()
+//!     .template("hello.html", "<h1>Hello, {{name}}!</h1>")
+//!     .unwrap();
+//!
+//! let mut router = Router::new();
+//! router.get("/hello/:name", |ctx: Context| async move {
+//!     let name = ctx.params().get("name").unwrap_or_default().to_owned();
+//!     ctx.render("hello.html", &json!({ "name": name }))
+//! });
+//!
+//! let mut client = TestClient::pipeline(vec![
+//!     from_middleware(Arc::new(Templates::new(engine))),
+//!     from_middleware(Arc::new(router)),
+//! ]);
+//! client
+//!     .get("/hello/Ada")
+//!     .await
+//!     .assert_status(StatusCode::Ok)
+//!     .assert_header("Content-Type", "text/html; charset=utf-8")
+//!     .assert_text("<h1>Hello, Ada!</h1>");
+//! # }
+//! ```
+
+#[cfg(any(feature = "tera", feature = "minijinja"))]
+use std::{fs, path::PathBuf, sync::Mutex, time::SystemTime};
+use std::{io, path::Path, pin::Pin, sync::Arc};
+
+use serde::Serialize;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{
+    Response, StatusCode,
+    context::Context,
+    http::Mime,
+    middleware::{Middleware, Next},
+};
+
+#[cfg(feature = "minijinja")]
+mod minijinja;
+mod mustache;
+#[cfg(feature = "tera")]
+mod tera;
+
+#[cfg(feature = "minijinja")]
+pub use self::minijinja::MiniJinja;
+#[cfg(feature = "tera")]
+pub use self::tera::Tera;
+pub use mustache::Mustache;
+
+/// Errors produced while loading or rendering templates.
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    #[error("template {0:?} not found")]
+    NotFound(String),
+
+    #[error("syntax error in template {name:?}: {message}")]
+    Syntax { name: String, message: String },
+
+    #[error("failed to render template {name:?}: {message}")]
+    Render { name: String, message: String },
+
+    #[error("failed to serialize template context: {0}")]
+    Context(String),
+
+    #[error("template io error: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl TemplateError {
+    /// Converts the error into a `500 Internal Server Error` response.
+    ///
+    /// The body is deliberately generic; the details are logged instead, since they
+    /// describe the application's templates rather than anything the client sent.
+    pub fn into_response(self) -> Response {
+        tracing::error!(error = %self, "template rendering failed");
+        Response::new(StatusCode::InternalServerError).body("Internal Server Error")
+    }
+}
+
+/// A template engine: renders a named template with a JSON context.
+///
+/// Implementations must be `Send + Sync` because one engine is shared by every request
+/// on every worker thread.
+pub trait TemplateEngine: Send + Sync {
+    /// Renders template `name` with `context`.
+    ///
+    /// # Errors
+    ///
+    /// [`TemplateError::NotFound`] for an unknown name, otherwise whatever the engine
+    /// reports while loading or rendering.
+    fn render(&self, name: &str, context: &Value) -> Result<String, TemplateError>;
+}
+
+/// A shared handle to a [`TemplateEngine`], and the middleware that installs it.
+///
+/// Cloning is cheap; clones render with the same engine.
+#[derive(Clone)]
+pub struct Templates {
+    engine: Arc<dyn TemplateEngine>,
+}
+
+impl Templates {
+    /// Wraps `engine`.
+    pub fn new(engine: impl TemplateEngine + 'static) -> Self {
+        Self {
+            engine: Arc::new(engine),
+        }
+    }
+
+    /// Renders template `name` to a string.
+    ///
+    /// # Errors
+    ///
+    /// [`TemplateError::Context`] if `context` cannot be serialized, otherwise the
+    /// engine's error.
+    pub fn render_string<T: Serialize + ?Sized>(
+        &self,
+        name: &str,
+        context: &T,
+    ) -> Result<String, TemplateError> {
+        let context =
+            serde_json::to_value(context).map_err(|e| TemplateError::Context(e.to_string()))?;
+        self.engine.render(name, &context)
+    }
+
+    /// Renders template `name` into a `200 OK` response.
+    ///
+    /// The `Content-Type` follows the template's extension (`feed.xml` is served as
+    /// `application/xml`), defaulting to `text/html; charset=utf-8`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`render_string`](Self::render_string).
+    pub fn render<T: Serialize + ?Sized>(
+        &self,
+        name: &str,
+        context: &T,
+    ) -> Result<Response, TemplateError> {
+        let body = self.render_string(name, context)?;
+        let content_type = Path::new(name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Mime::from_extension)
+            .map_or_else(
+                || "text/html; charset=utf-8".to_owned(),
+                |mime| mime.to_string(),
+            );
+        Ok(Response::new(StatusCode::Ok)
+            .header("Content-Type", content_type)
+            .body(body))
+    }
+}
+
+impl std::fmt::Debug for Templates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Templates").finish_non_exhaustive()
+    }
+}
+
+impl Middleware for Templates {
+    fn handle(
+        &self,
+        mut ctx: Context,
+        next: Next,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        ctx.extensions_mut().insert(self.clone());
+        next.run(ctx)
+    }
+}
+
+/// Adds template rendering to [`Context`].
+pub trait TemplateExt {
+    /// Returns the templates installed by the [`Templates`] middleware, if any.
+    fn templates(&self) -> Option<&Templates>;
+
+    /// Renders template `name` into a response, as [`Templates::render`] does.
+    ///
+    /// Failures — including a missing [`Templates`] middleware — become
+    /// `500 Internal Server Error` and are logged.
+    fn render<T: Serialize + ?Sized>(&self, name: &str, context: &T) -> Response;
+}
+
+impl TemplateExt for Context {
+    fn templates(&self) -> Option<&Templates> {
+        self.extensions().get::<Templates>()
+    }
+
+    fn render<T: Serialize + ?Sized>(&self, name: &str, context: &T) -> Response {
+        match self.templates() {
+            Some(templates) => templates
+                .render(name, context)
+                .unwrap_or_else(TemplateError::into_response),
+            None => TemplateError::Render {
+                name: name.to_owned(),
+                message: "no Templates middleware is installed".into(),
+            }
+            .into_response(),
+        }
+    }
+}
+
+// Watches a template directory for engines that reload every template when any file
+// changes, since templates may extend or include one another.
+#[cfg(any(feature = "tera", feature = "minijinja"))]
+#[derive(Debug)]
+struct Reload {
+    dir: PathBuf,
+    latest: Mutex<Option<SystemTime>>,
+}
+
+#[cfg(any(feature = "tera", feature = "minijinja"))]
+impl Reload {
+    fn new(dir: PathBuf) -> Self {
+        let latest = Mutex::new(latest_modified(&dir));
+        Self { dir, latest }
+    }
+
+    fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    // Returns `true` once per change to the directory's newest modification time.
+    fn changed(&self) -> bool {
+        let now = latest_modified(&self.dir);
+        let mut latest = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut *latest, now) != now
+    }
+}
+
+// The newest modification time of any file under `dir`.
+#[cfg(any(feature = "tera", feature = "minijinja"))]
+fn latest_modified(dir: &Path) -> Option<SystemTime> {
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| match entry.file_type() {
+            Ok(kind) if kind.is_dir() => latest_modified(&entry.path()),
+            Ok(_) => entry.metadata().and_then(|m| m.modified()).ok(),
+            Err(_) => None,
+        })
+        .max()
+}
+
+// An error with its causes, which engines report separately.
+#[cfg(any(feature = "tera", feature = "minijinja"))]
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Router, middleware::from_middleware, testing::TestClient};
+
+    #[tokio::test]
+    async fn renders_through_the_context() {
+        let engine = Mustache::new()
+            .template("feed.xml", "<feed>{{title}}</feed>")
+            .unwrap()
+            .template("broken.html", "{{> missing.html}}")
+            .unwrap();
+        let router = || {
+            let mut router = Router::new();
+            router.get("/feed", |ctx: Context| async move {
+                ctx.render("feed.xml", &json!({ "title": "News" }))
+            });
+            router.get("/broken", |ctx: Context| async move {
+                ctx.render("broken.html", &json!({}))
+            });
+            router
+        };
+
+        let mut client = TestClient::pipeline(vec![
+            from_middleware(Arc::new(Templates::new(engine))),
+            from_middleware(Arc::new(router())),
+        ]);
+        client
+            .get("/feed")
+            .await
+            .assert_status(StatusCode::Ok)
+            .assert_header("Content-Type", "application/xml")
+            .assert_text("<feed>News</feed>");
+        client
+            .get("/broken")
+            .await
+            .assert_status(StatusCode::InternalServerError)
+            .assert_text("Internal Server Error");
+
+        // Without the middleware there is nothing to render with.
+        TestClient::new(router())
+            .get("/feed")
+            .await
+            .assert_status(StatusCode::InternalServerError);
+    }
+}
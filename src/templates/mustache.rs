@@ -0,0 +1,466 @@
+//! A small built-in engine using Mustache syntax.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+use serde_json::Value;
+
+use super::{TemplateEngine, TemplateError};
+use crate::http::html::escape_into;
+
+// How deeply partials may include each other before rendering gives up.
+const MAX_DEPTH: usize = 32;
+
+/// Templates in [Mustache](https://mustache.github.io/mustache.5.html) syntax.
+///
+/// Supports the core of the language:
+///
+/// - `{{name}}` and dotted `{{user.name}}` — a value, HTML-escaped; `{{{name}}}` and
+///   `{{& name}}` insert it unescaped. `{{.}}` is the current item of a section.
+/// - `{{#items}}…{{/items}}` — repeated for each element of an array, rendered once
+///   with the value in scope if it is any other truthy value, and skipped for `false`,
+///   `null`, a missing name, or an empty array. `{{^items}}…{{/items}}` renders only when
+///   `{{#items}}` would not.
+/// - `{{> header.html}}` — includes another template, rendered in the current scope.
+/// - `{{! comment}}` — ignored.
+///
+/// Templates are parsed when loaded, so syntax errors surface at startup. With hot
+/// reload — on by default in debug builds — templates loaded from a directory are
+/// re-read whenever their file changes, and new files are picked up on first use.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::templates::{Mustache, TemplateEngine};
+/// use serde_json::json;
+///
+/// let engine = Mustache::new()
+///     .template("greeting.html", "<p>Hello, {{name}}!</p>{{#admin}} (admin){{/admin}}")
+///     .unwrap();
+/// let html = engine
+///     .render("greeting.html", &json!({ "name": "<Ada>", "admin": true }))
+///     .unwrap();
+/// assert_eq!(html, "<p>Hello, &lt;Ada&gt;!</p> (admin)");
+/// ```
+#[derive(Debug, Default)]
+pub struct Mustache {
+    dir: Option<PathBuf>,
+    hot_reload: bool,
+    templates: RwLock<HashMap<String, Loaded>>,
+}
+
+#[derive(Debug, Clone)]
+struct Loaded {
+    nodes: Arc<[Node]>,
+    // The file's modification time, for templates loaded from the directory.
+    modified: Option<SystemTime>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Value {
+        path: String,
+        escape: bool,
+    },
+    Section {
+        path: String,
+        inverted: bool,
+        children: Vec<Node>,
+    },
+    Partial(String),
+}
+
+impl Mustache {
+    /// Creates an engine with no templates.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads every file under `dir`, named by its path relative to `dir` with `/`
+    /// separators (`emails/welcome.html`). Hot reload is on in debug builds.
+    ///
+    /// # Errors
+    ///
+    /// [`TemplateError::Io`] if the directory cannot be read, or
+    /// [`TemplateError::Syntax`] for the first template that does not parse.
+    pub fn from_dir(dir: impl Into<PathBuf>) -> Result<Self, TemplateError> {
+        let engine = Self {
+            dir: Some(dir.into()),
+            hot_reload: cfg!(debug_assertions),
+            templates: RwLock::default(),
+        };
+        let mut files = Vec::new();
+        let root = engine.dir.as_deref().unwrap_or(Path::new("."));
+        collect_files(root, root, &mut files)?;
+        for name in files {
+            engine.load_file(&name)?;
+        }
+        Ok(engine)
+    }
+
+    /// Adds a template from a string, replacing any with the same name.
+    ///
+    /// # Errors
+    ///
+    /// [`TemplateError::Syntax`] if `source` does not parse.
+    pub fn template(self, name: &str, source: &str) -> Result<Self, TemplateError> {
+        let nodes = parse(name, source)?;
+        self.write().insert(
+            name.to_owned(),
+            Loaded {
+                nodes: nodes.into(),
+                modified: None,
+            },
+        );
+        Ok(self)
+    }
+
+    /// Turns re-reading changed template files on or off.
+    #[must_use]
+    pub fn hot_reload(mut self, enabled: bool) -> Self {
+        self.hot_reload = enabled;
+        self
+    }
+
+    // Returns the parsed template `name`, re-reading its file if it changed.
+    fn get(&self, name: &str) -> Result<Arc<[Node]>, TemplateError> {
+        let cached = self.read().get(name).cloned();
+        match cached {
+            Some(loaded) if !self.hot_reload || loaded.modified.is_none() => Ok(loaded.nodes),
+            Some(loaded) => {
+                let path = self.path(name)?;
+                match fs::metadata(&path).and_then(|m| m.modified()) {
+                    Ok(modified) if Some(modified) != loaded.modified => self.load_file(name),
+                    _ => Ok(loaded.nodes),
+                }
+            }
+            None if self.hot_reload && self.dir.is_some() => self.load_file(name),
+            None => Err(TemplateError::NotFound(name.to_owned())),
+        }
+    }
+
+    fn load_file(&self, name: &str) -> Result<Arc<[Node]>, TemplateError> {
+        let path = self.path(name)?;
+        let (source, modified) = fs::read_to_string(&path)
+            .and_then(|source| Ok((source, fs::metadata(&path)?.modified()?)))
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => TemplateError::NotFound(name.to_owned()),
+                _ => TemplateError::Io(e),
+            })?;
+        let nodes: Arc<[Node]> = parse(name, &source)?.into();
+        self.write().insert(
+            name.to_owned(),
+            Loaded {
+                nodes: Arc::clone(&nodes),
+                modified: Some(modified),
+            },
+        );
+        Ok(nodes)
+    }
+
+    // The file behind template `name`, refusing names that leave the directory.
+    fn path(&self, name: &str) -> Result<PathBuf, TemplateError> {
+        let dir = self
+            .dir
+            .as_deref()
+            .ok_or_else(|| TemplateError::NotFound(name.to_owned()))?;
+        if name
+            .split('/')
+            .any(|part| part.is_empty() || part == "." || part == ".." || part.contains('\\'))
+        {
+            return Err(TemplateError::NotFound(name.to_owned()));
+        }
+        Ok(dir.join(name))
+    }
+
+    fn render_nodes(
+        &self,
+        name: &str,
+        nodes: &[Node],
+        scopes: &mut Vec<Value>,
+        depth: usize,
+        out: &mut String,
+    ) -> Result<(), TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Value { path, escape } => {
+                    let value = lookup(scopes, path);
+                    let text = match value {
+                        None | Some(Value::Null) => continue,
+                        Some(Value::String(s)) => s.clone(),
+                        Some(other) => other.to_string(),
+                    };
+                    if *escape {
+                        escape_into(&text, out);
+                    } else {
+                        out.push_str(&text);
+                    }
+                }
+                Node::Section {
+                    path,
+                    inverted,
+                    children,
+                } => {
+                    let value = lookup(scopes, path).cloned().unwrap_or(Value::Null);
+                    let truthy = match &value {
+                        Value::Null | Value::Bool(false) => false,
+                        Value::Array(items) => !items.is_empty(),
+                        _ => true,
+                    };
+                    if *inverted {
+                        if !truthy {
+                            self.render_nodes(name, children, scopes, depth, out)?;
+                        }
+                        continue;
+                    }
+                    if !truthy {
+                        continue;
+                    }
+                    let items = match value {
+                        Value::Array(items) => items,
+                        other => vec![other],
+                    };
+                    for item in items {
+                        scopes.push(item);
+                        let result = self.render_nodes(name, children, scopes, depth, out);
+                        scopes.pop();
+                        result?;
+                    }
+                }
+                Node::Partial(partial) => {
+                    if depth >= MAX_DEPTH {
+                        return Err(TemplateError::Render {
+                            name: name.to_owned(),
+                            message: format!("partials nested more than {MAX_DEPTH} deep"),
+                        });
+                    }
+                    let nodes = self.get(partial)?;
+                    self.render_nodes(partial, &nodes, scopes, depth + 1, out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, Loaded>> {
+        self.templates.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, Loaded>> {
+        self.templates.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl TemplateEngine for Mustache {
+    fn render(&self, name: &str, context: &Value) -> Result<String, TemplateError> {
+        let nodes = self.get(name)?;
+        let mut out = String::new();
+        let mut scopes = vec![context.clone()];
+        self.render_nodes(name, &nodes, &mut scopes, 0, &mut out)?;
+        Ok(out)
+    }
+}
+
+// Resolves a dotted name against the innermost scope whose first segment matches.
+fn lookup<'a>(scopes: &'a [Value], path: &str) -> Option<&'a Value> {
+    if path == "." {
+        return scopes.last();
+    }
+    let mut segments = path.split('.');
+    let first = segments.next()?;
+    let mut value = scopes.iter().rev().find_map(|scope| scope.get(first))?;
+    for segment in segments {
+        value = match value {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+            other => other.get(segment)?,
+        };
+    }
+    Some(value)
+}
+
+fn parse(name: &str, source: &str) -> Result<Vec<Node>, TemplateError> {
+    let syntax = |message: String| TemplateError::Syntax {
+        name: name.to_owned(),
+        message,
+    };
+    // Open sections: the nodes before each, with its name and kind.
+    let mut stack: Vec<(Vec<Node>, String, bool)> = Vec::new();
+    let mut nodes = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            nodes.push(Node::Text(rest[..start].to_owned()));
+        }
+        let after = &rest[start + 2..];
+        let (tag, len) = if let Some(inner) = after.strip_prefix('{') {
+            let end = inner
+                .find("}}}")
+                .ok_or_else(|| syntax("unclosed {{{ tag".into()))?;
+            (format!("&{}", &inner[..end]), end + 4)
+        } else {
+            let end = after
+                .find("}}")
+                .ok_or_else(|| syntax("unclosed {{ tag".into()))?;
+            (after[..end].to_owned(), end + 2)
+        };
+        rest = &after[len..];
+
+        let tag = tag.trim();
+        let (sigil, body) = match tag.chars().next() {
+            Some(c @ ('#' | '^' | '/' | '>' | '&' | '!')) => (Some(c), tag[1..].trim()),
+            _ => (None, tag),
+        };
+        if body.is_empty() && sigil != Some('!') {
+            return Err(syntax("empty tag".into()));
+        }
+        match sigil {
+            Some('!') => {}
+            Some('#' | '^') => {
+                let parent = std::mem::take(&mut nodes);
+                stack.push((parent, body.to_owned(), sigil == Some('^')));
+            }
+            Some('/') => {
+                let (parent, open, inverted) = stack
+                    .pop()
+                    .ok_or_else(|| syntax(format!("{{{{/{body}}}}} closes nothing")))?;
+                if open != body {
+                    return Err(syntax(format!(
+                        "{{{{/{body}}}}} closes section {{{{#{open}}}}}"
+                    )));
+                }
+                let children = std::mem::replace(&mut nodes, parent);
+                nodes.push(Node::Section {
+                    path: open,
+                    inverted,
+                    children,
+                });
+            }
+            Some('>') => nodes.push(Node::Partial(body.to_owned())),
+            Some('&') => nodes.push(Node::Value {
+                path: body.to_owned(),
+                escape: false,
+            }),
+            _ => nodes.push(Node::Value {
+                path: body.to_owned(),
+                escape: true,
+            }),
+        }
+    }
+    if let Some((_, open, _)) = stack.last() {
+        return Err(syntax(format!("section {{{{#{open}}}}} is never closed")));
+    }
+    if !rest.is_empty() {
+        nodes.push(Node::Text(rest.to_owned()));
+    }
+    Ok(nodes)
+}
+
+// Lists the files under `dir` as `/`-separated paths relative to `root`.
+fn collect_files(root: &Path, dir: &Path, out: &mut Vec<String>) -> Result<(), TemplateError> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_files(root, &path, out)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            let parts: Vec<_> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            out.push(parts.join("/"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn renders_sections_and_partials() {
+        let engine = Mustache::new()
+            .template("item.html", "<li>{{name}}{{#tags}} #{{.}}{{/tags}}</li>")
+            .unwrap()
+            .template(
+                "list.html",
+                "{{! a list }}<ul>{{#items}}{{> item.html}}{{/items}}</ul>\
+                 {{^items}}<p>No {{kind}}.</p>{{/items}}{{{footer}}}",
+            )
+            .unwrap();
+        let html = engine
+            .render(
+                "list.html",
+                &json!({
+                    "kind": "items",
+                    "items": [{ "name": "a & b", "tags": ["x", "y"] }, { "name": "c" }],
+                    "footer": "<hr>",
+                }),
+            )
+            .unwrap();
+        assert_eq!(html, "<ul><li>a &amp; b #x #y</li><li>c</li></ul><hr>");
+        let empty = engine
+            .render("list.html", &json!({ "kind": "items", "items": [] }))
+            .unwrap();
+        assert_eq!(empty, "<ul></ul><p>No items.</p>");
+
+        assert!(matches!(
+            engine.render("missing.html", &json!({})),
+            Err(TemplateError::NotFound(_))
+        ));
+        for bad in ["{{#a}}", "{{/a}}", "{{#a}}{{/b}}", "{{name", "{{}}"] {
+            assert!(
+                matches!(
+                    Mustache::new().template("bad", bad),
+                    Err(TemplateError::Syntax { .. })
+                ),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn reloads_changed_files() {
+        let dir = std::env::temp_dir().join(format!(
+            "rttp-templates-{}",
+            crate::security::crypto::random_token(8)
+        ));
+        fs::create_dir_all(dir.join("emails")).unwrap();
+        let file = dir.join("emails/welcome.html");
+        fs::write(&file, "Hi {{name}}").unwrap();
+
+        let engine = Mustache::from_dir(&dir).unwrap().hot_reload(true);
+        let context = json!({ "name": "Ada" });
+        assert_eq!(
+            engine.render("emails/welcome.html", &context).unwrap(),
+            "Hi Ada"
+        );
+
+        fs::write(&file, "Hello {{name}}").unwrap();
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(
+            engine.render("emails/welcome.html", &context).unwrap(),
+            "Hello Ada"
+        );
+        assert!(matches!(
+            engine.render("../secret", &context),
+            Err(TemplateError::NotFound(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
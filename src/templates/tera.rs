@@ -0,0 +1,242 @@
+//! [Tera](https://keats.github.io/tera/) templates, behind the `tera` feature.
+
+use std::{
+    path::PathBuf,
+    sync::{RwLock, RwLockReadGuard},
+};
+
+use serde_json::Value;
+
+use super::{Reload, TemplateEngine, TemplateError, error_chain};
+
+/// Templates in Tera's Jinja2-like syntax, with inheritance, macros, and filters.
+///
+/// Templates named `*.html`, `*.htm`, and `*.xml` are HTML-escaped, as Tera does by
+/// default. With hot reload — on by default in debug builds — every template is
+/// re-read once any file under the directory changes, since a change to a base template
+/// affects every page extending it.
+///
+/// # Examples
+///
+/// ```
+/// use rttp::templates::{TemplateEngine, Tera};
+/// use serde_json::json;
+///
+/// let engine = Tera::new()
+///     .template("base.html", "<title>{% block title %}{% endblock %}</title>")
+///     .unwrap()
+///     .template(
+///         "post.html",
+///         r#"{% extends "base.html" %}{% block title %}{{ title | upper }}{% endblock %}"#,
+///     )
+///     .unwrap();
+/// let html = engine.render("post.html", &json!({ "title": "a & b" })).unwrap();
+/// assert_eq!(html, "<title>A &amp; B</title>");
+/// ```
+#[derive(Debug)]
+pub struct Tera {
+    tera: RwLock<tera::Tera>,
+    dir: Option<Reload>,
+    hot_reload: bool,
+    // Templates added from strings, re-added after a reload from the directory.
+    inline: Vec<(String, String)>,
+}
+
+impl Tera {
+    /// Creates an engine with no templates.
+    pub fn new() -> Self {
+        Self {
+            tera: RwLock::new(tera::Tera::default()),
+            dir: None,
+            hot_reload: false,
+            inline: Vec::new(),
+        }
+    }
+
+    /// Loads every file under `dir`, named by its path relative to `dir` with `/`
+    /// separators (`emails/welcome.html`). Hot reload is on in debug builds.
+    ///
+    /// # Errors
+    ///
+    /// [`TemplateError::Syntax`] for the first template that does not parse, or if
+    /// `dir` is not valid UTF-8.
+    pub fn from_dir(dir: impl Into<PathBuf>) -> Result<Self, TemplateError> {
+        let dir = dir.into();
+        let glob = format!(
+            "{}/**/*",
+            dir.to_str().ok_or_else(|| TemplateError::Syntax {
+                name: dir.display().to_string(),
+                message: "template directory is not valid UTF-8".into(),
+            })?
+        );
+        let tera = tera::Tera::new(&glob).map_err(|e| TemplateError::Syntax {
+            name: dir.display().to_string(),
+            message: error_chain(&e),
+        })?;
+        Ok(Self {
+            tera: RwLock::new(tera),
+            dir: Some(Reload::new(dir)),
+            hot_reload: cfg!(debug_assertions),
+            inline: Vec::new(),
+        })
+    }
+
+    /// Adds a template from a string, replacing any with the same name.
+    ///
+    /// # Errors
+    ///
+    /// [`TemplateError::Syntax`] if `source` does not parse.
+    pub fn template(mut self, name: &str, source: &str) -> Result<Self, TemplateError> {
+        add(
+            self.tera.get_mut().unwrap_or_else(|e| e.into_inner()),
+            name,
+            source,
+        )?;
+        self.inline.push((name.to_owned(), source.to_owned()));
+        Ok(self)
+    }
+
+    /// Turns re-reading changed template files on or off.
+    #[must_use]
+    pub fn hot_reload(mut self, enabled: bool) -> Self {
+        self.hot_reload = enabled;
+        self
+    }
+
+    // The templates, first reloaded from the directory if a file there changed.
+    fn current(&self) -> Result<RwLockReadGuard<'_, tera::Tera>, TemplateError> {
+        if let Some(dir) = self.dir.as_ref().filter(|_| self.hot_reload) {
+            if dir.changed() {
+                let mut tera = self.tera.write().unwrap_or_else(|e| e.into_inner());
+                tera.full_reload().map_err(|e| TemplateError::Syntax {
+                    name: dir.path().display().to_string(),
+                    message: error_chain(&e),
+                })?;
+                for (name, source) in &self.inline {
+                    add(&mut tera, name, source)?;
+                }
+            }
+        }
+        Ok(self.tera.read().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl Default for Tera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<tera::Tera> for Tera {
+    /// Wraps an already configured [`tera::Tera`], for custom filters and functions.
+    fn from(tera: tera::Tera) -> Self {
+        Self {
+            tera: RwLock::new(tera),
+            ..Self::new()
+        }
+    }
+}
+
+impl TemplateEngine for Tera {
+    fn render(&self, name: &str, context: &Value) -> Result<String, TemplateError> {
+        let context = tera::Context::from_value(context.clone())
+            .map_err(|e| TemplateError::Context(error_chain(&e)))?;
+        self.current()?
+            .render(name, &context)
+            .map_err(|e| match e.kind {
+                tera::ErrorKind::TemplateNotFound(_) => TemplateError::NotFound(name.to_owned()),
+                _ => TemplateError::Render {
+                    name: name.to_owned(),
+                    message: error_chain(&e),
+                },
+            })
+    }
+}
+
+fn add(tera: &mut tera::Tera, name: &str, source: &str) -> Result<(), TemplateError> {
+    tera.add_raw_template(name, source)
+        .map_err(|e| TemplateError::Syntax {
+            name: name.to_owned(),
+            message: error_chain(&e),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::SystemTime};
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn renders_and_reports_errors() {
+        let engine = Tera::new()
+            .template(
+                "list.html",
+                "{% for item in items %}<li>{{ item }}</li>{% endfor %}",
+            )
+            .unwrap()
+            .template("strict.txt", "{{ missing.field }}")
+            .unwrap();
+        assert_eq!(
+            engine
+                .render("list.html", &json!({ "items": ["<a>", "b"] }))
+                .unwrap(),
+            "<li>&lt;a&gt;</li><li>b</li>"
+        );
+        assert!(matches!(
+            engine.render("nope.html", &json!({})),
+            Err(TemplateError::NotFound(name)) if name == "nope.html"
+        ));
+        assert!(matches!(
+            engine.render("strict.txt", &json!({})),
+            Err(TemplateError::Render { .. })
+        ));
+        assert!(matches!(
+            engine.render("list.html", &json!([1])),
+            Err(TemplateError::Context(_))
+        ));
+        assert!(matches!(
+            Tera::new().template("bad.html", "{% if %}"),
+            Err(TemplateError::Syntax { .. })
+        ));
+    }
+
+    #[test]
+    fn reloads_changed_files() {
+        let dir = std::env::temp_dir().join(format!(
+            "rttp-tera-{}",
+            crate::security::crypto::random_token(8)
+        ));
+        fs::create_dir_all(dir.join("emails")).unwrap();
+        let file = dir.join("emails/welcome.html");
+        fs::write(&file, "Hi {{ name }}").unwrap();
+
+        let engine = Tera::from_dir(&dir)
+            .unwrap()
+            .hot_reload(true)
+            .template("inline.txt", "{{ name }}!")
+            .unwrap();
+        let context = json!({ "name": "Ada" });
+        assert_eq!(
+            engine.render("emails/welcome.html", &context).unwrap(),
+            "Hi Ada"
+        );
+
+        fs::write(&file, "Hello {{ name }}").unwrap();
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(
+            engine.render("emails/welcome.html", &context).unwrap(),
+            "Hello Ada"
+        );
+        assert_eq!(engine.render("inline.txt", &context).unwrap(), "Ada!");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
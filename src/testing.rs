@@ -0,0 +1,663 @@
+//! Testing applications without an external HTTP client.
+//!
+//! [`TestClient`] sends requests to a [`Router`] or middleware pipeline in-process, or
+//! to a running [`Server`](crate::Server) over a real socket, and returns a
+//! [`TestResponse`] with assertion helpers. In-process requests are serialized and
+//! parsed like real ones, so handlers see the same [`Request`] a server would give them,
+//! and streamed bodies such as Server-Sent Events are collected until the stream ends.
+//!
+//! Cookies set by responses are remembered and sent with later requests, so session
+//! flows can be tested end to end. [`TestRequest::websocket`] performs the upgrade
+//! handshake instead and returns the client end of the [`WebSocket`].
+//!
+//! [`replay`] sends recorded traffic through a client, and diffs the responses of two
+//! versions of an application.
+//!
+//! # Examples
+//!
+//! ```
+//! use rttp::{Response, Router, StatusCode, testing::TestClient};
+//! use serde_json::json;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let mut router = Router::new();
+//! router.get("/users/:id", |ctx: rttp::context::Context| async move {
+//!     let id = ctx.params().get("id").unwrap_or_default().to_owned();
+//!     Response::new(StatusCode::Ok)
+//!         .header("Content-Type", "application/json")
+//!         .body(json!({ "id": id }).to_string())
+//! });
+//!
+//! let mut client = TestClient::new(router);
+//! client
+//!     .get("/users/1")
+//!     .header("Accept", "application/json")
+//!     .await
+//!     .assert_status(StatusCode::Ok)
+//!     .assert_json(&json!({ "id": "1" }));
+//! # }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    future::{Future, IntoFuture},
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+};
+
+use bytes::BytesMut;
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, duplex},
+    net::TcpStream,
+};
+
+use crate::{
+    Headers, Method, Request, Response, Router, StatusCode,
+    context::Context,
+    http::{ConnectionInfo, HeaderName, Upgraded, chunked},
+    middleware::{MiddlewareHandler, Next, from_middleware},
+    realtime::{WebSocket, WebSocketConfig, websocket::accept_key},
+    security::crypto::{base64_encode, random_bytes},
+};
+
+pub mod replay;
+
+// Where requests go.
+#[derive(Clone)]
+enum Target {
+    Pipeline(Arc<[MiddlewareHandler]>),
+    Socket(SocketAddr),
+}
+
+/// Sends requests to an application under test.
+#[derive(Clone)]
+pub struct TestClient {
+    target: Target,
+    cookies: BTreeMap<String, String>,
+}
+
+impl TestClient {
+    /// Sends requests straight to `router`, in-process.
+    pub fn new(router: Router) -> Self {
+        Self::pipeline(vec![from_middleware(Arc::new(router))])
+    }
+
+    /// Sends requests through a middleware pipeline, in-process, as
+    /// [`Server::run`](crate::Server::run) would with
+    /// [`Next::new(pipeline).run(…)`](Next::run).
+    pub fn pipeline(pipeline: Vec<MiddlewareHandler>) -> Self {
+        Self {
+            target: Target::Pipeline(pipeline.into()),
+            cookies: BTreeMap::new(),
+        }
+    }
+
+    /// Sends requests to a server listening on `addr`, one connection per request.
+    pub fn connect(addr: SocketAddr) -> Self {
+        Self {
+            target: Target::Socket(addr),
+            cookies: BTreeMap::new(),
+        }
+    }
+
+    /// Starts a `GET` request for `path`, which may include a query string.
+    pub fn get(&mut self, path: &str) -> TestRequest<'_> {
+        self.request(Method::Get, path)
+    }
+
+    /// Starts a `POST` request.
+    pub fn post(&mut self, path: &str) -> TestRequest<'_> {
+        self.request(Method::Post, path)
+    }
+
+    /// Starts a `PUT` request.
+    pub fn put(&mut self, path: &str) -> TestRequest<'_> {
+        self.request(Method::Put, path)
+    }
+
+    /// Starts a `PATCH` request.
+    pub fn patch(&mut self, path: &str) -> TestRequest<'_> {
+        self.request(Method::Patch, path)
+    }
+
+    /// Starts a `DELETE` request.
+    pub fn delete(&mut self, path: &str) -> TestRequest<'_> {
+        self.request(Method::Delete, path)
+    }
+
+    /// Starts a request with any method.
+    pub fn request(&mut self, method: Method, path: &str) -> TestRequest<'_> {
+        TestRequest {
+            client: self,
+            method,
+            path: path.to_owned(),
+            headers: Headers::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Returns the value of the remembered cookie `name`.
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies.get(name).map(String::as_str)
+    }
+
+    /// Forgets every remembered cookie.
+    pub fn clear_cookies(&mut self) {
+        self.cookies.clear();
+    }
+
+    async fn upgrade(&mut self, mut request: TestRequestParts) -> WebSocket {
+        let key = base64_encode(&random_bytes(16));
+        request.headers.insert("Upgrade", "websocket");
+        request.headers.insert("Connection", "Upgrade");
+        request.headers.insert("Sec-WebSocket-Version", "13");
+        request.headers.insert("Sec-WebSocket-Key", key.clone());
+        let raw = self.encode(&request);
+        let expect_switch = |status: StatusCode, accept: Option<&str>| {
+            assert_eq!(
+                status,
+                StatusCode::SwitchingProtocols,
+                "WebSocket upgrade refused"
+            );
+            assert_eq!(
+                accept,
+                Some(accept_key(&key).as_str()),
+                "wrong Sec-WebSocket-Accept"
+            );
+        };
+        match &self.target {
+            Target::Pipeline(pipeline) => {
+                let (mut parsed, _) = Request::parse(&raw).expect("test request does not parse");
+                let loopback = SocketAddr::from(([127, 0, 0, 1], 0));
+                parsed.set_connection(Arc::new(ConnectionInfo::new(loopback, loopback)));
+                let mut response = Next::new(Arc::clone(pipeline))
+                    .run(Context::new(parsed))
+                    .await;
+                expect_switch(
+                    response.status(),
+                    response.headers().get("sec-websocket-accept"),
+                );
+                let writer = response
+                    .take_upgrade()
+                    .expect("101 response without an upgrade handler");
+                let (server, client) = duplex(64 * 1024);
+                tokio::spawn(writer.run(Upgraded::new(server, BytesMut::new())));
+                WebSocket::client(client, WebSocketConfig::new())
+            }
+            Target::Socket(addr) => {
+                let mut stream = TcpStream::connect(addr)
+                    .await
+                    .expect("cannot connect to the server under test");
+                stream.write_all(&raw).await.expect("cannot send request");
+                // Read byte by byte so no frame sent right after the head is consumed.
+                let mut head = Vec::new();
+                while !head.ends_with(b"\r\n\r\n") {
+                    let byte = stream.read_u8().await.expect("handshake cut short");
+                    head.push(byte);
+                }
+                let response = TestResponse::parse(&head);
+                expect_switch(response.status, response.header("sec-websocket-accept"));
+                WebSocket::client(stream, WebSocketConfig::new())
+            }
+        }
+    }
+
+    async fn send(&mut self, request: TestRequestParts) -> TestResponse {
+        let raw = self.encode(&request);
+        let response = match &self.target {
+            Target::Pipeline(pipeline) => {
+                let (mut parsed, _) = Request::parse(&raw).expect("test request does not parse");
+                let loopback = SocketAddr::from(([127, 0, 0, 1], 0));
+                parsed.set_connection(Arc::new(ConnectionInfo::new(loopback, loopback)));
+                let response = Next::new(Arc::clone(pipeline))
+                    .run(Context::new(parsed))
+                    .await;
+                TestResponse::from_response(response).await
+            }
+            Target::Socket(addr) => {
+                let mut stream = TcpStream::connect(addr)
+                    .await
+                    .expect("cannot connect to the server under test");
+                stream.write_all(&raw).await.expect("cannot send request");
+                let mut bytes = Vec::new();
+                stream
+                    .read_to_end(&mut bytes)
+                    .await
+                    .expect("cannot read response");
+                TestResponse::parse(&bytes)
+            }
+        };
+        self.remember_cookies(&response);
+        response
+    }
+
+    fn encode(&self, request: &TestRequestParts) -> Vec<u8> {
+        let mut head = format!("{} {} HTTP/1.1\r\n", request.method, request.path);
+        if !request.headers.contains("host") {
+            head.push_str("Host: localhost\r\n");
+        }
+        for (name, value) in request.headers.iter() {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        if !self.cookies.is_empty() && !request.headers.contains("cookie") {
+            let pairs: Vec<String> = self
+                .cookies
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect();
+            head.push_str(&format!("Cookie: {}\r\n", pairs.join("; ")));
+        }
+        if matches!(self.target, Target::Socket(_)) && !request.headers.contains("connection") {
+            head.push_str("Connection: close\r\n");
+        }
+        if !request.body.is_empty() || !matches!(request.method, Method::Get | Method::Head) {
+            head.push_str(&format!("Content-Length: {}\r\n", request.body.len()));
+        }
+        head.push_str("\r\n");
+        let mut raw = head.into_bytes();
+        raw.extend_from_slice(&request.body);
+        raw
+    }
+
+    fn remember_cookies(&mut self, response: &TestResponse) {
+        for set_cookie in response.headers.get_all("set-cookie") {
+            let mut attributes = set_cookie.split(';');
+            let Some((name, value)) = attributes.next().and_then(|pair| pair.split_once('='))
+            else {
+                continue;
+            };
+            let expired = attributes.any(|a| {
+                let a = a.trim().to_ascii_lowercase();
+                a == "max-age=0" || a.starts_with("max-age=-")
+            });
+            if expired {
+                self.cookies.remove(name.trim());
+            } else {
+                self.cookies
+                    .insert(name.trim().to_owned(), value.trim().to_owned());
+            }
+        }
+    }
+}
+
+impl fmt::Debug for TestClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let target = match &self.target {
+            Target::Pipeline(pipeline) => format!("pipeline of {}", pipeline.len()),
+            Target::Socket(addr) => addr.to_string(),
+        };
+        f.debug_struct("TestClient")
+            .field("target", &target)
+            .field("cookies", &self.cookies)
+            .finish()
+    }
+}
+
+/// A request being built; send it by awaiting it.
+#[must_use = "a test request does nothing until awaited"]
+pub struct TestRequest<'a> {
+    client: &'a mut TestClient,
+    method: Method,
+    path: String,
+    headers: Headers,
+    body: Vec<u8>,
+}
+
+// The request without the client it is sent through.
+struct TestRequestParts {
+    method: Method,
+    path: String,
+    headers: Headers,
+    body: Vec<u8>,
+}
+
+impl<'a> TestRequest<'a> {
+    /// Adds a request header.
+    pub fn header(mut self, name: impl Into<HeaderName>, value: impl Into<String>) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Adds `Authorization: Bearer <token>`.
+    pub fn bearer(self, token: &str) -> Self {
+        self.header("Authorization", format!("Bearer {token}"))
+    }
+
+    /// Sets the raw body.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Sets a JSON body and `Content-Type: application/json`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` does not serialize.
+    pub fn json<T: Serialize + ?Sized>(self, value: &T) -> Self {
+        let body = serde_json::to_vec(value).expect("test request body does not serialize");
+        self.header("Content-Type", "application/json").body(body)
+    }
+
+    /// Sends the request. Awaiting the request does the same.
+    pub async fn send(self) -> TestResponse {
+        let (client, request) = self.into_parts();
+        client.send(request).await
+    }
+
+    /// Sends the request as a WebSocket upgrade and returns the client end of the
+    /// connection.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless the application answers `101 Switching Protocols` with the
+    /// matching `Sec-WebSocket-Accept`.
+    pub async fn websocket(self) -> WebSocket {
+        let (client, request) = self.into_parts();
+        client.upgrade(request).await
+    }
+
+    fn into_parts(self) -> (&'a mut TestClient, TestRequestParts) {
+        let TestRequest {
+            client,
+            method,
+            path,
+            headers,
+            body,
+        } = self;
+        let request = TestRequestParts {
+            method,
+            path,
+            headers,
+            body,
+        };
+        (client, request)
+    }
+}
+
+impl<'a> IntoFuture for TestRequest<'a> {
+    type Output = TestResponse;
+    type IntoFuture = Pin<Box<dyn Future<Output = TestResponse> + Send + 'a>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.send())
+    }
+}
+
+/// A response received by a [`TestClient`].
+///
+/// The `assert_*` methods panic with the response body in the message, and return the
+/// response so checks can be chained.
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    status: StatusCode,
+    headers: Headers,
+    body: Vec<u8>,
+    trailers: Headers,
+}
+
+impl TestResponse {
+    // Collects a handler's response, running its body writer if it streams one.
+    async fn from_response(mut response: Response) -> Self {
+        let mut body = response.body_ref().to_vec();
+        if response.is_streamed() {
+            if let Some(writer) = response.take_upgrade() {
+                let (server, mut client) = duplex(64 * 1024);
+                let (_, read) = tokio::join!(
+                    writer.run(Upgraded::new(server, BytesMut::new())),
+                    client.read_to_end(&mut body)
+                );
+                read.expect("cannot read streamed body");
+            }
+        }
+        Self::dechunked(response.status(), response.headers().clone(), body)
+    }
+
+    // Parses a close-delimited HTTP/1.1 response.
+    fn parse(bytes: &[u8]) -> Self {
+        let split = bytes
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .expect("incomplete response");
+        let head = String::from_utf8_lossy(&bytes[..split]);
+        let mut lines = head.lines();
+        let code: u16 = lines
+            .next()
+            .and_then(|line| line.split(' ').nth(1))
+            .and_then(|code| code.parse().ok())
+            .expect("malformed status line");
+        let status =
+            StatusCode::from_u16(code).unwrap_or_else(|| panic!("unsupported status code {code}"));
+        let mut headers = Headers::new();
+        for (name, value) in lines.filter_map(|line| line.split_once(':')) {
+            headers.insert(name.trim(), value.trim());
+        }
+        Self::dechunked(status, headers, bytes[split + 4..].to_vec())
+    }
+
+    // Decodes a chunked body, keeping its trailers.
+    fn dechunked(status: StatusCode, headers: Headers, body: Vec<u8>) -> Self {
+        let chunked = headers
+            .get("transfer-encoding")
+            .is_some_and(|te| te.eq_ignore_ascii_case("chunked"));
+        let (body, trailers) = if chunked {
+            chunked::decode(&body).expect("malformed chunked response body")
+        } else {
+            (body, Headers::new())
+        };
+        Self {
+            status,
+            headers,
+            body,
+            trailers,
+        }
+    }
+
+    /// Returns the status code.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the response headers.
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Returns the first header named `name` (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// Returns the trailer field named `name` (case-insensitive) sent after a chunked
+    /// body.
+    pub fn trailer(&self, name: &str) -> Option<&str> {
+        self.trailers.get(name)
+    }
+
+    /// Returns the body.
+    pub fn bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns the body as text, replacing invalid UTF-8.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Deserializes the body as JSON.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the body is not JSON of that shape.
+    #[track_caller]
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body).unwrap_or_else(|e| {
+            panic!(
+                "response body is not the expected JSON ({e}): {}",
+                self.text()
+            )
+        })
+    }
+
+    /// Asserts the status code.
+    #[track_caller]
+    pub fn assert_status(&self, status: StatusCode) -> &Self {
+        assert_eq!(
+            self.status,
+            status,
+            "unexpected status; body: {}",
+            self.text()
+        );
+        self
+    }
+
+    /// Asserts that header `name` is present with `value`.
+    #[track_caller]
+    pub fn assert_header(&self, name: &str, value: &str) -> &Self {
+        assert_eq!(self.header(name), Some(value), "unexpected `{name}` header");
+        self
+    }
+
+    /// Asserts that the body is exactly `text`.
+    #[track_caller]
+    pub fn assert_text(&self, text: &str) -> &Self {
+        assert_eq!(self.text(), text, "unexpected body");
+        self
+    }
+
+    /// Asserts that the body is JSON equal to `expected`.
+    #[track_caller]
+    pub fn assert_json(&self, expected: &Value) -> &Self {
+        assert_eq!(&self.json::<Value>(), expected, "unexpected JSON body");
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        Server,
+        http::Cookie,
+        realtime::{Message, sse},
+    };
+
+    fn router() -> Router {
+        let mut router = Router::new();
+        router.post("/echo", |ctx: Context| async move {
+            let request = ctx.request();
+            Response::new(StatusCode::Created)
+                .header(
+                    "Content-Type",
+                    request.headers().get("content-type").unwrap_or_default(),
+                )
+                .body_bytes(request.body().to_vec())
+        });
+        router.get("/login", |_ctx| async {
+            Response::new(StatusCode::Ok).cookie(&Cookie::new("session", "abc"))
+        });
+        router.get("/whoami", |ctx: Context| async move {
+            let session = ctx
+                .request()
+                .cookie("session")
+                .unwrap_or("nobody")
+                .to_owned();
+            Response::new(StatusCode::Ok).body(session)
+        });
+        router.get("/events", |ctx: Context| async move {
+            sse::stream(ctx, |_, mut sse| async move {
+                let _ = sse.send(&sse::Event::new().data("hi")).await;
+            })
+        });
+        router.ws("/socket", |_ctx, mut ws| async move {
+            while let Some(Ok(Message::Text(text))) = ws.next().await {
+                if ws.send(text.to_uppercase()).await.is_err() {
+                    break;
+                }
+            }
+        });
+        router.get("/download", |_ctx| async {
+            Response::new(StatusCode::Ok)
+                .trailer("X-Bytes")
+                .chunked(|mut body| async move {
+                    body.write_all(b"part one, ").await?;
+                    body.write_all(b"part two").await?;
+                    body.trailer("X-Bytes", body.bytes_written().to_string());
+                    Ok(body)
+                })
+        });
+        router
+    }
+
+    #[tokio::test]
+    async fn drives_a_router_in_process() {
+        let mut client = TestClient::new(router());
+        client
+            .post("/echo")
+            .json(&json!({ "name": "Ada" }))
+            .await
+            .assert_status(StatusCode::Created)
+            .assert_header("content-type", "application/json")
+            .assert_json(&json!({ "name": "Ada" }));
+
+        client
+            .get("/missing")
+            .await
+            .assert_status(StatusCode::NotFound);
+        client.get("/whoami").await.assert_text("nobody");
+        client.get("/login").await;
+        assert_eq!(client.cookie("session"), Some("abc"));
+        client.get("/whoami").await.assert_text("abc");
+
+        client.get("/events").await.assert_text("data: hi\n\n");
+        let download = client.get("/download").await;
+        download
+            .assert_header("Trailer", "X-Bytes")
+            .assert_text("part one, part two");
+        assert_eq!(download.trailer("x-bytes"), Some("18"));
+
+        let mut ws = client.get("/socket").websocket().await;
+        ws.send("hi").await.unwrap();
+        assert_eq!(ws.next().await.unwrap().unwrap(), Message::text("HI"));
+    }
+
+    #[tokio::test]
+    async fn drives_a_running_server() {
+        let server = Server::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr();
+        let pipeline = vec![from_middleware(Arc::new(router()))];
+        tokio::spawn(server.run(move |req| Next::new(pipeline.clone()).run(Context::new(req))));
+
+        let mut client = TestClient::connect(addr);
+        let response = client
+            .post("/echo")
+            .body("plain")
+            .header("Content-Type", "text/plain")
+            .await;
+        response
+            .assert_status(StatusCode::Created)
+            .assert_text("plain");
+        client.get("/login").await;
+        client.get("/whoami").await.assert_text("abc");
+        let download = client.get("/download").await;
+        download.assert_text("part one, part two");
+        assert_eq!(download.trailer("X-Bytes"), Some("18"));
+
+        let mut ws = client.get("/socket").websocket().await;
+        ws.send("hi").await.unwrap();
+        assert_eq!(ws.next().await.unwrap().unwrap(), Message::text("HI"));
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "WebSocket upgrade refused")]
+    async fn websocket_panics_when_refused() {
+        TestClient::new(router()).get("/whoami").websocket().await;
+    }
+}
@@ -0,0 +1,609 @@
+//! Replaying recorded traffic, and diffing two versions of an application.
+//!
+//! A [`Replay`] holds recorded requests — from a HAR file such as the one
+//! [`Har`](crate::middleware::Har) writes, from [`Exchange`]s captured in-process, or
+//! from a plain `METHOD /path` list — and sends them through one or two
+//! [`TestClient`]s at a chosen concurrency and speed. [`Replay::compare`] sends every
+//! request to a baseline and a candidate and reports where their responses differ,
+//! which makes refactoring route handlers safe to check against real traffic.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rttp::{Router, testing::{TestClient, replay::Replay}};
+//!
+//! # async fn example(old_router: Router, new_router: Router) -> Result<(), rttp::testing::replay::ReplayError> {
+//! let replay = Replay::from_har_file("traffic.har")?.concurrency(8);
+//! let report = replay
+//!     .compare(&TestClient::new(old_router), &TestClient::new(new_router))
+//!     .await;
+//! report.assert_clean();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fmt, fs, io,
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::Value;
+use thiserror::Error;
+use tokio::{sync::Semaphore, task::JoinSet, time::Instant};
+
+use super::{TestClient, TestResponse};
+use crate::{
+    Method,
+    http::{TargetForm, Uri},
+    middleware::Exchange,
+};
+
+// Recorded headers the client recomputes for each replayed request.
+const SKIPPED_HEADERS: [&str; 4] = ["content-length", "transfer-encoding", "connection", "host"];
+
+/// Errors from loading recorded traffic.
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    /// The recording could not be read.
+    #[error("cannot read recording: {0}")]
+    Io(#[from] io::Error),
+
+    /// The recording is not valid HAR or request-list input.
+    #[error("invalid recording: {0}")]
+    Invalid(String),
+}
+
+/// One recorded request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayRequest {
+    /// The request method.
+    pub method: Method,
+    /// The path and query string.
+    pub target: String,
+    /// The recorded headers, minus those the client sets itself.
+    pub headers: Vec<(String, String)>,
+    /// The request body.
+    pub body: Vec<u8>,
+    /// When the request was sent, relative to the first one.
+    pub offset: Duration,
+}
+
+impl fmt::Display for ReplayRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.target)
+    }
+}
+
+/// Recorded requests and how to send them.
+///
+/// By default requests are sent one at a time, as fast as possible.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Replay {
+    requests: Vec<ReplayRequest>,
+    concurrency: usize,
+    speed: f64,
+    compared_headers: Vec<String>,
+}
+
+impl Replay {
+    /// Replays `requests` in order.
+    pub fn new(requests: Vec<ReplayRequest>) -> Self {
+        Self {
+            requests,
+            concurrency: 1,
+            speed: 0.0,
+            compared_headers: vec!["content-type".to_owned(), "location".to_owned()],
+        }
+    }
+
+    /// Reads the entries of a HAR 1.2 document.
+    ///
+    /// # Errors
+    ///
+    /// [`ReplayError::Invalid`] if `har` has no `log.entries` array or an entry lacks a
+    /// method or URL.
+    pub fn from_har(har: &Value) -> Result<Self, ReplayError> {
+        let entries = har["log"]["entries"]
+            .as_array()
+            .ok_or_else(|| ReplayError::Invalid("missing log.entries".into()))?;
+        let mut requests = Vec::with_capacity(entries.len());
+        let mut times = Vec::with_capacity(entries.len());
+        for (i, entry) in entries.iter().enumerate() {
+            let request = &entry["request"];
+            let field = |name: &str| {
+                request[name]
+                    .as_str()
+                    .ok_or_else(|| ReplayError::Invalid(format!("entry {i} has no request.{name}")))
+            };
+            let method: Method = field("method")?.parse().unwrap_or(Method::Get);
+            let headers: Vec<(&str, &str)> = request["headers"]
+                .as_array()
+                .map(|headers| {
+                    headers
+                        .iter()
+                        .filter_map(|h| Some((h["name"].as_str()?, h["value"].as_str()?)))
+                        .collect()
+                })
+                .unwrap_or_default();
+            let body = request["postData"]["text"]
+                .as_str()
+                .unwrap_or("")
+                .as_bytes()
+                .to_vec();
+            times.push(entry["startedDateTime"].as_str().and_then(parse_iso8601));
+            requests.push(ReplayRequest {
+                method,
+                target: path_of(field("url")?),
+                headers: kept(headers),
+                body,
+                offset: Duration::ZERO,
+            });
+        }
+        set_offsets(&mut requests, &times);
+        Ok(Self::new(requests))
+    }
+
+    /// Reads a HAR file.
+    ///
+    /// # Errors
+    ///
+    /// [`ReplayError::Io`] if the file cannot be read, or as for
+    /// [`from_har`](Self::from_har).
+    pub fn from_har_file(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let bytes = fs::read(path)?;
+        let har = serde_json::from_slice(&bytes)
+            .map_err(|e| ReplayError::Invalid(format!("not JSON: {e}")))?;
+        Self::from_har(&har)
+    }
+
+    /// Replays exchanges recorded by [`CaptureMiddleware`](crate::middleware::CaptureMiddleware).
+    ///
+    /// Bodies the middleware did not record, or truncated, are replayed as recorded.
+    pub fn from_exchanges(exchanges: &[Exchange]) -> Self {
+        let times: Vec<_> = exchanges.iter().map(|e| Some(e.started)).collect();
+        let mut requests: Vec<_> = exchanges
+            .iter()
+            .map(|exchange| ReplayRequest {
+                method: exchange.method.clone(),
+                target: exchange.target.clone(),
+                headers: kept(
+                    exchange
+                        .request_headers
+                        .iter()
+                        .map(|(n, v)| (n.as_str(), v.as_str())),
+                ),
+                body: exchange
+                    .request_body
+                    .text
+                    .as_deref()
+                    .unwrap_or("")
+                    .as_bytes()
+                    .to_vec(),
+                offset: Duration::ZERO,
+            })
+            .collect();
+        set_offsets(&mut requests, &times);
+        Self::new(requests)
+    }
+
+    /// Reads one `METHOD /path?query` request per line, such as from an access log
+    /// cut down with `awk`. Blank lines and lines starting with `#` are skipped.
+    ///
+    /// # Errors
+    ///
+    /// [`ReplayError::Invalid`] for a line without a target.
+    pub fn from_lines(lines: &str) -> Result<Self, ReplayError> {
+        let mut requests = Vec::new();
+        for (i, line) in lines.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let (Some(method), Some(target)) = (words.next(), words.next()) else {
+                return Err(ReplayError::Invalid(format!(
+                    "line {}: expected `METHOD /path`",
+                    i + 1
+                )));
+            };
+            requests.push(ReplayRequest {
+                method: method.parse().unwrap_or(Method::Get),
+                target: target.to_owned(),
+                headers: Vec::new(),
+                body: Vec::new(),
+                offset: Duration::ZERO,
+            });
+        }
+        Ok(Self::new(requests))
+    }
+
+    /// Sends up to `concurrency` requests at once.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Keeps the recorded spacing between requests, compressed by `speed`: `1.0`
+    /// replays in real time, `10.0` ten times faster. `0.0`, the default, sends
+    /// requests as fast as the concurrency allows.
+    pub fn speed(mut self, speed: f64) -> Self {
+        self.speed = speed.max(0.0);
+        self
+    }
+
+    /// Also compares response header `name` in [`compare`](Self::compare), besides the
+    /// status, body, `Content-Type`, and `Location`.
+    pub fn compare_header(mut self, name: impl Into<String>) -> Self {
+        self.compared_headers.push(name.into().to_ascii_lowercase());
+        self
+    }
+
+    /// The recorded requests.
+    pub fn requests(&self) -> &[ReplayRequest] {
+        &self.requests
+    }
+
+    /// Sends every request through `client` and returns the responses in request
+    /// order. Each request gets a fresh copy of `client`, so cookies do not carry over.
+    pub async fn run(&self, client: &TestClient) -> Vec<TestResponse> {
+        let clients = [client.clone()];
+        self.drive(&clients)
+            .await
+            .into_iter()
+            .map(|mut responses| responses.remove(0))
+            .collect()
+    }
+
+    /// Sends every request through `baseline` and then `candidate`, and reports the
+    /// requests whose responses differ.
+    ///
+    /// Responses differ when their status, compared headers, or body differ; bodies
+    /// that are both JSON are compared as values, so key order does not matter.
+    pub async fn compare(&self, baseline: &TestClient, candidate: &TestClient) -> ReplayReport {
+        let clients = [baseline.clone(), candidate.clone()];
+        let results = self.drive(&clients).await;
+        let mut differences = Vec::new();
+        for (index, mut responses) in results.into_iter().enumerate() {
+            let candidate = responses.pop().expect("two responses");
+            let baseline = responses.pop().expect("two responses");
+            let reasons = self.differences(&baseline, &candidate);
+            if !reasons.is_empty() {
+                differences.push(Difference {
+                    index,
+                    request: self.requests[index].clone(),
+                    baseline,
+                    candidate,
+                    reasons,
+                });
+            }
+        }
+        ReplayReport {
+            total: self.requests.len(),
+            differences,
+        }
+    }
+
+    // Sends each request to every client in turn, honouring speed and concurrency.
+    async fn drive(&self, clients: &[TestClient]) -> Vec<Vec<TestResponse>> {
+        let permits = Arc::new(Semaphore::new(self.concurrency));
+        let started = Instant::now();
+        let mut tasks = JoinSet::new();
+        for (index, request) in self.requests.iter().enumerate() {
+            if self.speed > 0.0 {
+                tokio::time::sleep_until(started + request.offset.div_f64(self.speed)).await;
+            }
+            let permit = Arc::clone(&permits)
+                .acquire_owned()
+                .await
+                .expect("replay semaphore is never closed");
+            let request = request.clone();
+            let clients = clients.to_vec();
+            tasks.spawn(async move {
+                let mut responses = Vec::with_capacity(clients.len());
+                for mut client in clients {
+                    client.clear_cookies();
+                    let mut builder = client.request(request.method.clone(), &request.target);
+                    for (name, value) in &request.headers {
+                        builder = builder.header(name.as_str(), value.as_str());
+                    }
+                    responses.push(builder.body(request.body.clone()).send().await);
+                }
+                drop(permit);
+                (index, responses)
+            });
+        }
+        let mut results = vec![Vec::new(); self.requests.len()];
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok((index, responses)) => results[index] = responses,
+                Err(e) => std::panic::resume_unwind(e.into_panic()),
+            }
+        }
+        results
+    }
+
+    fn differences(&self, baseline: &TestResponse, candidate: &TestResponse) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if baseline.status() != candidate.status() {
+            reasons.push(format!(
+                "status {} != {}",
+                baseline.status().as_u16(),
+                candidate.status().as_u16()
+            ));
+        }
+        for name in &self.compared_headers {
+            let (old, new) = (baseline.header(name), candidate.header(name));
+            if old != new {
+                reasons.push(format!("header `{name}` {old:?} != {new:?}"));
+            }
+        }
+        let json = |r: &TestResponse| serde_json::from_slice::<Value>(r.bytes()).ok();
+        let same_body = match (json(baseline), json(candidate)) {
+            (Some(old), Some(new)) => old == new,
+            _ => baseline.bytes() == candidate.bytes(),
+        };
+        if !same_body {
+            reasons.push(format!(
+                "body {:?} != {:?}",
+                preview(baseline),
+                preview(candidate)
+            ));
+        }
+        reasons
+    }
+}
+
+/// A request whose responses differed in [`Replay::compare`].
+#[derive(Debug, Clone)]
+pub struct Difference {
+    /// The request's position in the replay.
+    pub index: usize,
+    /// The request.
+    pub request: ReplayRequest,
+    /// The baseline's response.
+    pub baseline: TestResponse,
+    /// The candidate's response.
+    pub candidate: TestResponse,
+    /// What differed, one description per mismatch.
+    pub reasons: Vec<String>,
+}
+
+/// The outcome of [`Replay::compare`].
+#[derive(Debug, Clone)]
+pub struct ReplayReport {
+    /// How many requests were replayed.
+    pub total: usize,
+    /// The requests whose responses differed, in request order.
+    pub differences: Vec<Difference>,
+}
+
+impl ReplayReport {
+    /// Whether every response matched.
+    pub fn is_clean(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    /// Asserts that every response matched.
+    ///
+    /// # Panics
+    ///
+    /// Panics listing each differing request and what differed.
+    #[track_caller]
+    pub fn assert_clean(&self) {
+        assert!(self.is_clean(), "{self}");
+    }
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} replayed requests differ",
+            self.differences.len(),
+            self.total
+        )?;
+        for difference in &self.differences {
+            write!(
+                f,
+                "\n  #{} {}: {}",
+                difference.index,
+                difference.request,
+                difference.reasons.join("; ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// The first 80 characters of a body.
+fn preview(response: &TestResponse) -> String {
+    let text = response.text();
+    match text.char_indices().nth(80) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+fn kept<'a>(headers: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<(String, String)> {
+    headers
+        .into_iter()
+        .filter(|(name, _)| !SKIPPED_HEADERS.iter().any(|s| s.eq_ignore_ascii_case(name)))
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .collect()
+}
+
+// `https://host:8080/a?b` -> `/a?b`.
+fn path_of(url: &str) -> String {
+    let uri = Uri::parse(url);
+    if uri.form() != TargetForm::Absolute {
+        return url.to_owned();
+    }
+    match uri.query() {
+        Some(query) => format!("{}?{query}", uri.path()),
+        None => uri.path().to_owned(),
+    }
+}
+
+// Offsets from the earliest known start time; unknown times replay immediately.
+fn set_offsets(requests: &mut [ReplayRequest], times: &[Option<SystemTime>]) {
+    let Some(first) = times.iter().flatten().min().copied() else {
+        return;
+    };
+    for (request, time) in requests.iter_mut().zip(times) {
+        request.offset = time
+            .and_then(|t| t.duration_since(first).ok())
+            .unwrap_or_default();
+    }
+}
+
+// Parses `2024-02-29T12:34:56.789Z` or `...+02:00`.
+fn parse_iso8601(value: &str) -> Option<SystemTime> {
+    let (date, time) = value.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let (time, offset) = match time.find(['Z', 'z', '+', '-']) {
+        Some(i) => time.split_at(i),
+        None => (time, ""),
+    };
+    let mut clock = time.splitn(3, ':');
+    let hours: i64 = clock.next()?.parse().ok()?;
+    let minutes: i64 = clock.next()?.parse().ok()?;
+    let seconds = clock.next().unwrap_or("0");
+    let (seconds, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    let seconds: i64 = seconds.parse().ok()?;
+    let nanos: u32 = format!("{fraction:0<9}").get(..9)?.parse().ok()?;
+    let offset_secs = match offset.as_bytes().first() {
+        Some(sign @ (b'+' | b'-')) => {
+            let (h, m) = offset[1..].split_once(':').unwrap_or((&offset[1..], "0"));
+            let secs = h.parse::<i64>().ok()? * 3600 + m.parse::<i64>().ok()? * 60;
+            if *sign == b'+' { secs } else { -secs }
+        }
+        _ => 0,
+    };
+    // Days-from-civil, after Howard Hinnant's algorithm.
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    let secs = days * 86_400 + hours * 3600 + minutes * 60 + seconds - offset_secs;
+    let secs = u64::try_from(secs).ok()?;
+    Some(UNIX_EPOCH + Duration::new(secs, nanos))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Response, Router, StatusCode, context::Context, middleware::Har};
+
+    fn router(version: u8) -> Router {
+        let mut router = Router::new();
+        router.get("/users/:id", move |ctx: Context| async move {
+            let id = ctx.params().get("id").unwrap_or_default().to_owned();
+            let body = if version == 1 {
+                json!({ "id": id, "name": "Ada" })
+            } else {
+                json!({ "name": "Ada", "id": id })
+            };
+            Response::new(StatusCode::Ok)
+                .header("Content-Type", "application/json")
+                .body(body.to_string())
+        });
+        router.post("/echo", move |ctx: Context| async move {
+            let mut body = ctx.request().body().to_vec();
+            if version == 2 {
+                body.reverse();
+            }
+            Response::new(StatusCode::Ok).body_bytes(body)
+        });
+        router
+    }
+
+    #[tokio::test]
+    async fn compares_two_routers() {
+        let replay = Replay::from_lines("# smoke\nGET /users/1\n\nGET /missing\n")
+            .unwrap()
+            .concurrency(4);
+        let report = replay
+            .compare(&TestClient::new(router(1)), &TestClient::new(router(2)))
+            .await;
+        report.assert_clean();
+        assert_eq!(report.total, 2);
+
+        let mut echo = replay.requests()[0].clone();
+        echo.method = Method::Post;
+        echo.target = "/echo".to_owned();
+        echo.body = b"abc".to_vec();
+        let report = Replay::new(vec![echo])
+            .compare(&TestClient::new(router(1)), &TestClient::new(router(2)))
+            .await;
+        assert_eq!(report.differences.len(), 1);
+        assert_eq!(report.differences[0].reasons, [r#"body "abc" != "cba""#]);
+        assert!(
+            report
+                .to_string()
+                .starts_with("1 of 1 replayed requests differ")
+        );
+    }
+
+    #[tokio::test]
+    async fn replays_har_with_recorded_timing() {
+        let exchange = |millis: u64, target: &str| Exchange {
+            started: UNIX_EPOCH + Duration::from_millis(1_709_210_096_000 + millis),
+            duration: Duration::ZERO,
+            method: Method::Post,
+            target: target.to_owned(),
+            version: 1,
+            request_headers: vec![
+                ("Host".to_owned(), "shop.local".to_owned()),
+                ("Content-Length".to_owned(), "2".to_owned()),
+                ("X-Trace".to_owned(), "1".to_owned()),
+            ],
+            request_body: crate::middleware::CapturedBody {
+                content_type: Some("text/plain".to_owned()),
+                size: 2,
+                text: Some("hi".to_owned()),
+                truncated: false,
+            },
+            status: StatusCode::Ok,
+            response_headers: Vec::new(),
+            response_body: crate::middleware::CapturedBody {
+                content_type: None,
+                size: 0,
+                text: None,
+                truncated: false,
+            },
+        };
+        let exchanges = [exchange(0, "/echo"), exchange(200, "/echo?x=1")];
+        let har = Har::new()
+            .base_url("https://shop.example")
+            .render(&exchanges);
+        let replay = Replay::from_har(&har).unwrap();
+        assert_eq!(
+            replay.requests(),
+            Replay::from_exchanges(&exchanges).requests()
+        );
+        let second = &replay.requests()[1];
+        assert_eq!(second.target, "/echo?x=1");
+        assert_eq!(second.offset, Duration::from_millis(200));
+        assert_eq!(second.headers, [("X-Trace".to_owned(), "1".to_owned())]);
+
+        let started = Instant::now();
+        let responses = replay.speed(4.0).run(&TestClient::new(router(1))).await;
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert_eq!(responses.len(), 2);
+        responses[1].assert_text("hi");
+
+        assert!(matches!(
+            Replay::from_har(&json!({})),
+            Err(ReplayError::Invalid(_))
+        ));
+    }
+}
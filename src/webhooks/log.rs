@@ -0,0 +1,96 @@
+//! Delivery-attempt history.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime},
+};
+
+/// One attempt to deliver an event to a subscriber.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeliveryAttempt {
+    /// Identifies the delivery; shared by its retries and sent as `X-Webhook-Delivery`.
+    pub delivery_id: String,
+    /// The subscription delivered to.
+    pub subscription_id: String,
+    /// The event type.
+    pub event: String,
+    /// The URL posted to.
+    pub url: String,
+    /// 1 for the first attempt, 2 for the first retry, and so on.
+    pub attempt: u32,
+    /// The response status, if the subscriber answered.
+    pub status: Option<u16>,
+    /// Why the attempt failed, if it did.
+    pub error: Option<String>,
+    /// How long the request took.
+    pub duration: Duration,
+    /// When the attempt finished.
+    pub at: SystemTime,
+}
+
+impl DeliveryAttempt {
+    /// Returns `true` if the subscriber answered with a `2xx` status.
+    pub fn is_success(&self) -> bool {
+        self.status
+            .is_some_and(|status| (200..300).contains(&status))
+    }
+}
+
+/// A bounded, shared history of recent [`DeliveryAttempt`]s, oldest first.
+///
+/// Clones share the same history. Once full, the oldest attempt is dropped.
+#[derive(Clone)]
+pub struct DeliveryLog {
+    attempts: Arc<Mutex<VecDeque<DeliveryAttempt>>>,
+    capacity: usize,
+}
+
+impl DeliveryLog {
+    /// Creates a log that keeps the last `capacity` attempts.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            attempts: Arc::default(),
+            capacity,
+        }
+    }
+
+    /// Appends `attempt`, dropping the oldest if the log is full.
+    pub fn record(&self, attempt: DeliveryAttempt) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut attempts = self.attempts();
+        if attempts.len() >= self.capacity {
+            attempts.pop_front();
+        }
+        attempts.push_back(attempt);
+    }
+
+    /// Returns every logged attempt.
+    pub fn recent(&self) -> Vec<DeliveryAttempt> {
+        self.attempts().iter().cloned().collect()
+    }
+
+    /// Returns the logged attempts for one subscription.
+    pub fn for_subscription(&self, subscription_id: &str) -> Vec<DeliveryAttempt> {
+        self.filter(|a| a.subscription_id == subscription_id)
+    }
+
+    /// Returns the logged attempts of one delivery.
+    pub fn for_delivery(&self, delivery_id: &str) -> Vec<DeliveryAttempt> {
+        self.filter(|a| a.delivery_id == delivery_id)
+    }
+
+    fn filter(&self, keep: impl Fn(&DeliveryAttempt) -> bool) -> Vec<DeliveryAttempt> {
+        self.attempts()
+            .iter()
+            .filter(|a| keep(a))
+            .cloned()
+            .collect()
+    }
+
+    fn attempts(&self) -> MutexGuard<'_, VecDeque<DeliveryAttempt>> {
+        self.attempts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,601 @@
+//! Outgoing webhooks — delivering events to subscriber URLs.
+//!
+//! The outbound counterpart of [`SignatureMiddleware`]:
+//!
+//! - [`Subscription`] — a subscriber URL, its signing secret, and the event types it
+//!   wants, kept in a [`SubscriptionStore`] ([`MemoryStore`] or the persistent
+//!   [`RedisStore`]).
+//! - [`Webhooks`] — registers subscriptions and fans each event out to them. Every
+//!   delivery is a job on a [`TaskQueue`], so it is retried with backoff under a
+//!   [`RetryPolicy`] and ends up in the queue's dead-letter list if it never succeeds.
+//! - [`Transport`] — how deliveries are sent; [`HttpTransport`] handles `http://` and
+//!   `https://` URLs.
+//! - [`DeliveryLog`] — a bounded history of [`DeliveryAttempt`]s.
+//!
+//! Each delivery is a JSON `POST`:
+//!
+//! ```text
+//! POST /hooks/orders HTTP/1.1
+//! Content-Type: application/json
+//! X-Webhook-Event: order.paid
+//! X-Webhook-Delivery: <delivery id, stable across retries>
+//! X-Signature-Timestamp: <unix seconds>
+//! X-Signature: sha256=<hex(hmac_sha256(secret, "<timestamp>.<body>"))>
+//!
+//! {"id":"<event id>","event":"order.paid","created_at":1700000000,"data":{...}}
+//! ```
+//!
+//! which a receiver built on this crate checks with
+//! `SignatureMiddleware::new(secret)`. Subscribers answering `2xx` succeed; `408`,
+//! `429`, `5xx`, and network errors are retried; other statuses fail the delivery
+//! immediately.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use serde_json::json;
+//! use rttp::background::TaskQueueOptions;
+//! use rttp::webhooks::{MemoryStore, Subscription, Webhooks};
+//!
+//! # async fn example() -> Result<(), rttp::webhooks::WebhookError> {
+//! let webhooks = Webhooks::new(MemoryStore::new(), TaskQueueOptions::new().start());
+//! let subscription = Subscription::new("http://billing.internal/hooks").events(["order.paid"]);
+//! println!("share this secret with the subscriber: {}", subscription.secret);
+//! webhooks.subscribe(subscription).await?;
+//!
+//! webhooks.send("order.paid", &json!({ "order": 42 })).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`SignatureMiddleware`]: crate::security::signature::SignatureMiddleware
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::{
+    background::{Job, JobError, JobFuture, JobId, QueueError, RetryPolicy, TaskQueue},
+    http::{
+        Link,
+        client::{ClientError, parse_url},
+    },
+    redis::RedisError,
+    security::{crypto::random_token, signature::SignatureMiddleware},
+};
+
+pub mod log;
+pub mod store;
+pub mod transport;
+
+pub use log::{DeliveryAttempt, DeliveryLog};
+pub use store::{MemoryStore, RedisStore, StoreFuture, SubscriptionStore};
+pub use transport::{HttpTransport, Transport, TransportFuture};
+
+/// Errors produced by the webhook subsystem.
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    #[error("subscription store backend error: {0}")]
+    Store(String),
+
+    #[error("subscription could not be (de)serialized: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("unsupported webhook URL {0:?}")]
+    InvalidUrl(String),
+
+    #[error("webhook I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("webhook transport error: {0}")]
+    Transport(String),
+
+    #[error("webhook delivery timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error(transparent)]
+    Queue(#[from] QueueError),
+}
+
+impl From<RedisError> for WebhookError {
+    fn from(err: RedisError) -> Self {
+        Self::Store(err.to_string())
+    }
+}
+
+impl From<ClientError> for WebhookError {
+    fn from(err: ClientError) -> Self {
+        match err {
+            ClientError::InvalidUrl(url) => Self::InvalidUrl(url),
+            ClientError::Io(err) => Self::Io(err),
+            ClientError::Timeout(timeout) => Self::Timeout(timeout),
+            err => Self::Transport(err.to_string()),
+        }
+    }
+}
+
+/// A subscriber endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Subscription {
+    /// Unique ID.
+    pub id: String,
+    /// Where deliveries are posted.
+    pub url: String,
+    /// The HMAC secret deliveries are signed with; share it with the subscriber.
+    pub secret: String,
+    /// Event types to deliver; empty means every event.
+    pub events: Vec<String>,
+    /// Inactive subscriptions are kept but receive nothing.
+    pub active: bool,
+}
+
+impl Subscription {
+    /// Creates an active subscription to every event, with a random ID and secret.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            id: random_token(12),
+            url: url.into(),
+            secret: random_token(32),
+            events: Vec::new(),
+            active: true,
+        }
+    }
+
+    /// Replaces the generated ID.
+    #[must_use]
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = id.into();
+        self
+    }
+
+    /// Replaces the generated secret.
+    #[must_use]
+    pub fn secret(mut self, secret: impl Into<String>) -> Self {
+        self.secret = secret.into();
+        self
+    }
+
+    /// Limits the subscription to the given event types.
+    #[must_use]
+    pub fn events<I, S>(mut self, events: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.events = events.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Returns `true` if this subscription should receive `event`.
+    pub fn wants(&self, event: &str) -> bool {
+        self.active && (self.events.is_empty() || self.events.iter().any(|e| e == event))
+    }
+}
+
+/// A delivery queued by [`Webhooks::send`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    /// Sent as `X-Webhook-Delivery`; see [`DeliveryLog::for_delivery`].
+    pub id: String,
+    /// The subscription it goes to.
+    pub subscription_id: String,
+    /// The background job carrying it; see [`TaskQueue::status`].
+    pub job: JobId,
+}
+
+/// The webhook dispatcher. Cloning is cheap and clones share the store, queue, and log.
+///
+/// Defaults: [`HttpTransport`], up to 6 attempts per delivery starting from a 10-second
+/// backoff capped at 1 hour, and a log of the last 1000 attempts.
+#[derive(Clone)]
+pub struct Webhooks {
+    store: Arc<dyn SubscriptionStore>,
+    queue: TaskQueue,
+    transport: Arc<dyn Transport>,
+    retry: RetryPolicy,
+    log: DeliveryLog,
+    links: Arc<[Link]>,
+}
+
+impl Webhooks {
+    /// Creates a dispatcher that reads subscriptions from `store` and delivers through
+    /// jobs on `queue`.
+    pub fn new(store: impl SubscriptionStore + 'static, queue: TaskQueue) -> Self {
+        Self {
+            store: Arc::new(store),
+            queue,
+            transport: Arc::new(HttpTransport::new()),
+            retry: RetryPolicy::exponential(6)
+                .initial_backoff(Duration::from_secs(10))
+                .max_backoff(Duration::from_secs(60 * 60)),
+            log: DeliveryLog::new(1000),
+            links: Arc::new([]),
+        }
+    }
+
+    /// Sets how deliveries are sent.
+    #[must_use]
+    pub fn transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Arc::new(transport);
+        self
+    }
+
+    /// Sets how failed deliveries are retried. Only errors the policy
+    /// [classifies](RetryPolicy::retry_if) as retryable are retried; rejected
+    /// deliveries fail with [`JobError::Permanent`].
+    #[must_use]
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Replaces the attempt log, e.g. to change its capacity or share it.
+    #[must_use]
+    pub fn log(mut self, log: DeliveryLog) -> Self {
+        self.log = log;
+        self
+    }
+
+    /// Sends `links` as `Link` headers with every delivery, e.g. a `rel="hub"` link to
+    /// the subscription API or a `rel="help"` link to the event documentation.
+    #[must_use]
+    pub fn links(mut self, links: impl IntoIterator<Item = Link>) -> Self {
+        self.links = links.into_iter().collect();
+        self
+    }
+
+    /// The subscription registry.
+    pub fn store(&self) -> &dyn SubscriptionStore {
+        &*self.store
+    }
+
+    /// The attempt log.
+    pub fn deliveries(&self) -> &DeliveryLog {
+        &self.log
+    }
+
+    /// Validates and saves `subscription`, replacing any with the same ID.
+    ///
+    /// # Errors
+    ///
+    /// [`WebhookError::InvalidUrl`] for URLs the transport cannot reach in principle
+    /// (only checked for [`HttpTransport`]-style `http(s)://` URLs), or the store's error.
+    pub async fn subscribe(&self, subscription: Subscription) -> Result<(), WebhookError> {
+        if parse_url(&subscription.url).is_none() {
+            return Err(WebhookError::InvalidUrl(subscription.url));
+        }
+        self.store.save(&subscription).await
+    }
+
+    /// Deletes the subscription with `id`, returning whether it existed. Pending
+    /// retries to it are dropped.
+    ///
+    /// # Errors
+    ///
+    /// The store's error.
+    pub async fn unsubscribe(&self, id: &str) -> Result<bool, WebhookError> {
+        self.store.remove(id).await
+    }
+
+    /// Queues a delivery of `event` with payload `data` to every subscription that
+    /// [wants](Subscription::wants) it.
+    ///
+    /// # Errors
+    ///
+    /// The store's error, or [`WebhookError::Queue`] if the queue is shut down.
+    pub async fn send(
+        &self,
+        event: &str,
+        data: &serde_json::Value,
+    ) -> Result<Vec<Delivery>, WebhookError> {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let body = json!({
+            "id": random_token(12),
+            "event": event,
+            "created_at": created_at,
+            "data": data,
+        });
+        let body: Arc<[u8]> = serde_json::to_vec(&body)?.into();
+
+        let mut deliveries = Vec::new();
+        for subscription in self.store.list().await? {
+            if !subscription.wants(event) {
+                continue;
+            }
+            let id = random_token(12);
+            let job = DeliveryJob {
+                id: id.clone(),
+                subscription_id: subscription.id.clone(),
+                event: event.to_owned(),
+                body: Arc::clone(&body),
+                store: Arc::clone(&self.store),
+                transport: Arc::clone(&self.transport),
+                retry: self.retry.clone(),
+                log: self.log.clone(),
+                links: Arc::clone(&self.links),
+                attempts: AtomicU32::new(0),
+            };
+            let job = self.queue.spawn(job).await?;
+            deliveries.push(Delivery {
+                id,
+                subscription_id: subscription.id,
+                job,
+            });
+        }
+        Ok(deliveries)
+    }
+}
+
+// One event on its way to one subscription; each run is one attempt.
+struct DeliveryJob {
+    id: String,
+    subscription_id: String,
+    event: String,
+    body: Arc<[u8]>,
+    store: Arc<dyn SubscriptionStore>,
+    transport: Arc<dyn Transport>,
+    retry: RetryPolicy,
+    log: DeliveryLog,
+    links: Arc<[Link]>,
+    attempts: AtomicU32,
+}
+
+impl DeliveryJob {
+    async fn deliver(&self) -> Result<(), JobError> {
+        // Re-read on every attempt so unsubscribes and secret rotations take effect.
+        let subscription = match self.store.get(&self.subscription_id).await {
+            Ok(Some(subscription)) if subscription.active => subscription,
+            Ok(_) => {
+                debug!(subscription = %self.subscription_id, delivery = %self.id, "subscription gone; dropping webhook delivery");
+                return Ok(());
+            }
+            Err(e) => return Err(JobError::failed(e)),
+        };
+        let attempt = self.attempts.fetch_add(1, Ordering::Relaxed) + 1;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let signature =
+            SignatureMiddleware::new(subscription.secret.as_bytes()).sign(&self.body, timestamp);
+        let mut headers: Vec<_> = [
+            ("Content-Type", "application/json".to_owned()),
+            ("User-Agent", "rttp-webhooks".to_owned()),
+            ("X-Webhook-Event", self.event.clone()),
+            ("X-Webhook-Delivery", self.id.clone()),
+            ("X-Signature-Timestamp", timestamp.to_string()),
+            ("X-Signature", signature),
+        ]
+        .map(|(name, value)| (name.to_owned(), value))
+        .into();
+        headers.extend(
+            self.links
+                .iter()
+                .map(|link| ("Link".to_owned(), link.to_string())),
+        );
+
+        let started = Instant::now();
+        let result = self
+            .transport
+            .post(&subscription.url, &headers, &self.body)
+            .await;
+        let (status, outcome) = match result {
+            Ok(status) if (200..300).contains(&status) => (Some(status), Ok(())),
+            Ok(status) if status == 408 || status == 429 || status >= 500 => (
+                Some(status),
+                Err(JobError::failed(format!("subscriber answered {status}"))),
+            ),
+            Ok(status) => (
+                Some(status),
+                Err(JobError::permanent(format!("subscriber answered {status}"))),
+            ),
+            Err(e @ WebhookError::InvalidUrl(_)) => (None, Err(JobError::permanent(e))),
+            Err(e) => (None, Err(JobError::failed(e))),
+        };
+
+        if let Err(e) = &outcome {
+            warn!(subscription = %subscription.id, delivery = %self.id, attempt, error = %e, "webhook delivery failed");
+        }
+        self.log.record(DeliveryAttempt {
+            delivery_id: self.id.clone(),
+            subscription_id: subscription.id,
+            event: self.event.clone(),
+            url: subscription.url,
+            attempt,
+            status,
+            error: outcome.as_ref().err().map(ToString::to_string),
+            duration: started.elapsed(),
+            at: SystemTime::now(),
+        });
+        outcome
+    }
+}
+
+impl Job for DeliveryJob {
+    fn run(&self) -> JobFuture<'_> {
+        Box::pin(self.deliver())
+    }
+
+    fn name(&self) -> &str {
+        "webhook delivery"
+    }
+
+    fn retry_policy(&self) -> Option<RetryPolicy> {
+        Some(self.retry.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        Request, background::TaskQueueOptions, context::Context,
+        security::signature::SignatureMiddleware,
+    };
+
+    type Sent = Arc<Mutex<Vec<(String, Vec<(String, String)>, Vec<u8>)>>>;
+
+    // Answers each post with the next scripted status (repeating the last) and records
+    // what was sent.
+    struct Scripted {
+        statuses: Mutex<Vec<u16>>,
+        sent: Sent,
+    }
+
+    impl Transport for Scripted {
+        fn post<'a>(
+            &'a self,
+            url: &'a str,
+            headers: &'a [(String, String)],
+            body: &'a [u8],
+        ) -> TransportFuture<'a> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push((url.to_owned(), headers.to_vec(), body.to_vec()));
+            let mut statuses = self.statuses.lock().unwrap();
+            let status = if statuses.len() > 1 {
+                statuses.remove(0)
+            } else {
+                statuses[0]
+            };
+            Box::pin(async move { Ok(status) })
+        }
+    }
+
+    fn webhooks(statuses: &[u16]) -> (Webhooks, TaskQueue, Sent) {
+        let sent = Sent::default();
+        let queue = TaskQueueOptions::new().start();
+        let webhooks = Webhooks::new(MemoryStore::new(), queue.clone())
+            .transport(Scripted {
+                statuses: Mutex::new(statuses.to_vec()),
+                sent: Arc::clone(&sent),
+            })
+            .retry(
+                RetryPolicy::exponential(3)
+                    .initial_backoff(Duration::from_millis(5))
+                    .jitter(false),
+            );
+        (webhooks, queue, sent)
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_events_to_matching_subscriptions() {
+        let (webhooks, queue, sent) = webhooks(&[200]);
+        let webhooks = webhooks.links([Link::new("https://api.example/hooks").rel("hub")]);
+        let orders = Subscription::new("http://orders.example/hook")
+            .with_id("orders")
+            .events(["order.paid"]);
+        webhooks.subscribe(orders.clone()).await.unwrap();
+        webhooks
+            .subscribe(Subscription::new("http://users.example/hook").events(["user.created"]))
+            .await
+            .unwrap();
+        assert!(matches!(
+            webhooks.subscribe(Subscription::new("ftp://x")).await,
+            Err(WebhookError::InvalidUrl(_))
+        ));
+
+        let deliveries = webhooks
+            .send("order.paid", &json!({ "order": 42 }))
+            .await
+            .unwrap();
+        assert_eq!(deliveries.len(), 1);
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+
+        let sent = sent.lock().unwrap();
+        let (url, headers, body) = &sent[0];
+        assert_eq!(url, "http://orders.example/hook");
+        assert!(headers.contains(&(
+            "Link".to_owned(),
+            r#"<https://api.example/hooks>; rel="hub""#.to_owned()
+        )));
+        let body_json: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(body_json["event"], "order.paid");
+        assert_eq!(body_json["data"]["order"], 42);
+
+        // A receiver using the signature middleware accepts the delivery.
+        let mut raw = format!("POST /hook HTTP/1.1\r\nContent-Length: {}\r\n", body.len());
+        for (name, value) in headers {
+            raw.push_str(&format!("{name}: {value}\r\n"));
+        }
+        let mut raw = raw.into_bytes();
+        raw.extend_from_slice(b"\r\n");
+        raw.extend_from_slice(body);
+        let ctx = Context::new(Request::parse(&raw).unwrap().0);
+        assert_eq!(
+            SignatureMiddleware::new(orders.secret.as_bytes()).verify(&ctx),
+            Ok(())
+        );
+
+        let log = webhooks.deliveries().for_delivery(&deliveries[0].id);
+        assert_eq!(log.len(), 1);
+        assert!(log[0].is_success());
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let (webhooks, queue, _) = webhooks(&[503, 500, 200]);
+        webhooks
+            .subscribe(Subscription::new("http://flaky.example/"))
+            .await
+            .unwrap();
+        let deliveries = webhooks.send("ping", &json!({})).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+
+        let attempts = webhooks.deliveries().for_delivery(&deliveries[0].id);
+        let statuses: Vec<_> = attempts.iter().map(|a| (a.attempt, a.status)).collect();
+        assert_eq!(statuses, [(1, Some(503)), (2, Some(500)), (3, Some(200))]);
+        assert_eq!(queue.stats().retried, 2);
+    }
+
+    #[tokio::test]
+    async fn rejections_are_not_retried() {
+        let (webhooks, queue, _) = webhooks(&[410]);
+        webhooks
+            .subscribe(Subscription::new("http://gone.example/"))
+            .await
+            .unwrap();
+        let deliveries = webhooks.send("ping", &json!({})).await.unwrap();
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+
+        let attempts = webhooks.deliveries().for_delivery(&deliveries[0].id);
+        assert_eq!(attempts.len(), 1);
+        assert_eq!(attempts[0].status, Some(410));
+        assert_eq!(queue.dead_letters().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn unsubscribing_drops_pending_deliveries() {
+        let (webhooks, queue, sent) = webhooks(&[500]);
+        webhooks
+            .subscribe(Subscription::new("http://a.example/").with_id("a"))
+            .await
+            .unwrap();
+        webhooks.send("ping", &json!({})).await.unwrap();
+        while sent.lock().unwrap().is_empty() {
+            tokio::task::yield_now().await;
+        }
+        assert!(webhooks.unsubscribe("a").await.unwrap());
+        queue.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(sent.lock().unwrap().len(), 1);
+        assert_eq!(queue.stats().completed, 1);
+    }
+}
@@ -0,0 +1,202 @@
+//! Subscription registries.
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::{Mutex, MutexGuard},
+};
+
+use super::{Subscription, WebhookError};
+use crate::redis::RedisClient;
+
+/// Boxed future returned by [`SubscriptionStore`] methods.
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, WebhookError>> + Send + 'a>>;
+
+/// Where webhook subscriptions are kept.
+///
+/// Methods return boxed futures so the trait stays object-safe, mirroring
+/// [`SessionStore`](crate::security::session::SessionStore).
+pub trait SubscriptionStore: Send + Sync {
+    /// Returns every subscription, ordered by ID.
+    fn list(&self) -> StoreFuture<'_, Vec<Subscription>>;
+
+    /// Returns the subscription with `id`, if any.
+    fn get<'a>(&'a self, id: &'a str) -> StoreFuture<'a, Option<Subscription>>;
+
+    /// Inserts `subscription`, replacing any with the same ID.
+    fn save<'a>(&'a self, subscription: &'a Subscription) -> StoreFuture<'a, ()>;
+
+    /// Deletes the subscription with `id`, returning whether it existed.
+    fn remove<'a>(&'a self, id: &'a str) -> StoreFuture<'a, bool>;
+}
+
+/// An in-process [`SubscriptionStore`].
+///
+/// Subscriptions are lost on restart; use [`RedisStore`] to keep them.
+#[derive(Default)]
+pub struct MemoryStore {
+    subscriptions: Mutex<BTreeMap<String, Subscription>>,
+}
+
+impl MemoryStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn subscriptions(&self) -> MutexGuard<'_, BTreeMap<String, Subscription>> {
+        self.subscriptions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl SubscriptionStore for MemoryStore {
+    fn list(&self) -> StoreFuture<'_, Vec<Subscription>> {
+        let all = self.subscriptions().values().cloned().collect();
+        Box::pin(async move { Ok(all) })
+    }
+
+    fn get<'a>(&'a self, id: &'a str) -> StoreFuture<'a, Option<Subscription>> {
+        let found = self.subscriptions().get(id).cloned();
+        Box::pin(async move { Ok(found) })
+    }
+
+    fn save<'a>(&'a self, subscription: &'a Subscription) -> StoreFuture<'a, ()> {
+        self.subscriptions()
+            .insert(subscription.id.clone(), subscription.clone());
+        Box::pin(async { Ok(()) })
+    }
+
+    fn remove<'a>(&'a self, id: &'a str) -> StoreFuture<'a, bool> {
+        let removed = self.subscriptions().remove(id).is_some();
+        Box::pin(async move { Ok(removed) })
+    }
+}
+
+/// A Redis-backed [`SubscriptionStore`].
+///
+/// Each subscription is stored as a JSON string under `<prefix>subscription:<id>`, and
+/// the set `<prefix>subscriptions` indexes their IDs, so every instance sees the same
+/// subscribers and they survive restarts.
+pub struct RedisStore {
+    client: RedisClient,
+    prefix: String,
+}
+
+impl RedisStore {
+    /// Creates a store using `client` and the default key prefix `"webhooks:"`.
+    pub fn new(client: RedisClient) -> Self {
+        Self {
+            client,
+            prefix: "webhooks:".to_owned(),
+        }
+    }
+
+    /// Sets the key prefix used to namespace webhook keys.
+    #[must_use]
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    fn index(&self) -> String {
+        format!("{}subscriptions", self.prefix)
+    }
+
+    fn key(&self, id: &str) -> String {
+        format!("{}subscription:{id}", self.prefix)
+    }
+
+    async fn load(&self, id: &str) -> Result<Option<Subscription>, WebhookError> {
+        match self.client.get(&self.key(id)).await? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl SubscriptionStore for RedisStore {
+    fn list(&self) -> StoreFuture<'_, Vec<Subscription>> {
+        Box::pin(async move {
+            let mut ids: Vec<String> = self
+                .client
+                .smembers(&self.index())
+                .await?
+                .into_iter()
+                .map(|id| String::from_utf8_lossy(&id).into_owned())
+                .collect();
+            ids.sort();
+            let mut all = Vec::with_capacity(ids.len());
+            for id in ids {
+                // Skip index entries whose record was deleted concurrently.
+                if let Some(subscription) = self.load(&id).await? {
+                    all.push(subscription);
+                }
+            }
+            Ok(all)
+        })
+    }
+
+    fn get<'a>(&'a self, id: &'a str) -> StoreFuture<'a, Option<Subscription>> {
+        Box::pin(self.load(id))
+    }
+
+    fn save<'a>(&'a self, subscription: &'a Subscription) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let bytes = serde_json::to_vec(subscription)?;
+            self.client
+                .set(&self.key(&subscription.id), &bytes, None)
+                .await?;
+            self.client.sadd(&self.index(), &[&subscription.id]).await?;
+            Ok(())
+        })
+    }
+
+    fn remove<'a>(&'a self, id: &'a str) -> StoreFuture<'a, bool> {
+        Box::pin(async move {
+            let removed = self.client.del(&[&self.key(id)]).await?;
+            self.client.srem(&self.index(), &[id]).await?;
+            Ok(removed > 0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redis::testing::FakeRedis;
+
+    async fn round_trip(store: &dyn SubscriptionStore) {
+        let a = Subscription::new("http://a.example/hook").with_id("a");
+        let b = Subscription::new("http://b.example/hook")
+            .with_id("b")
+            .events(["order.paid"]);
+        store.save(&b).await.unwrap();
+        store.save(&a).await.unwrap();
+        assert_eq!(store.list().await.unwrap(), [a.clone(), b.clone()]);
+        assert_eq!(store.get("b").await.unwrap(), Some(b.clone()));
+
+        let paused = Subscription {
+            active: false,
+            ..b.clone()
+        };
+        store.save(&paused).await.unwrap();
+        assert_eq!(store.get("b").await.unwrap(), Some(paused));
+
+        assert!(store.remove("a").await.unwrap());
+        assert!(!store.remove("a").await.unwrap());
+        assert_eq!(store.get("a").await.unwrap(), None);
+        assert_eq!(store.list().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn memory_store_round_trips() {
+        round_trip(&MemoryStore::new()).await;
+    }
+
+    #[tokio::test]
+    async fn redis_store_round_trips() {
+        let server = FakeRedis::start().await;
+        round_trip(&RedisStore::new(RedisClient::new(server.addr())).prefix("test:")).await;
+    }
+}
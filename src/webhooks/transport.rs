@@ -0,0 +1,128 @@
+//! Outbound HTTP for webhook deliveries.
+
+use std::{future::Future, pin::Pin, time::Duration};
+
+use super::WebhookError;
+use crate::http::{Method, client::Client};
+
+/// Boxed future returned by [`Transport::post`]; resolves to the response status code.
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = Result<u16, WebhookError>> + Send + 'a>>;
+
+/// Sends a delivery to a subscriber.
+///
+/// Implement it to deliver through another HTTP client, for example one that routes
+/// through an egress proxy.
+pub trait Transport: Send + Sync {
+    /// POSTs `body` to `url` with `headers` and returns the response status code.
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(String, String)],
+        body: &'a [u8],
+    ) -> TransportFuture<'a>;
+}
+
+/// A [`Transport`] over the shared [`Client`], for `http://` and, with the `tls`
+/// feature, `https://` subscriber URLs.
+///
+/// Reads only the response head, and does not follow redirects.
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    client: Client,
+}
+
+impl Default for HttpTransport {
+    fn default() -> Self {
+        Self::with_client(Client::new())
+    }
+}
+
+impl HttpTransport {
+    /// Creates a transport with a 10-second timeout.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a transport sending through `client`, for example one trusting a private
+    /// CA. The transport's timeout replaces the client's.
+    pub fn with_client(client: Client) -> Self {
+        Self {
+            client: client.timeout(Duration::from_secs(10)),
+        }
+    }
+
+    /// Sets how long a delivery may take, from connecting to reading the response head.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.client = self.client.timeout(timeout);
+        self
+    }
+}
+
+impl Transport for HttpTransport {
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(String, String)],
+        body: &'a [u8],
+    ) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let response = self
+                .client
+                .request(Method::Post, url, headers, body)
+                .await?;
+            Ok(response.status())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn posts_and_reads_the_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"{}") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let headers = [("X-Test".to_owned(), "1".to_owned())];
+        let status = HttpTransport::new()
+            .post(&url, &headers, b"{}")
+            .await
+            .unwrap();
+        assert_eq!(status, 204);
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /hook HTTP/1.1\r\n"));
+        assert!(request.contains("Content-Length: 2\r\n"));
+        assert!(request.contains("X-Test: 1\r\n"));
+    }
+
+    #[tokio::test]
+    async fn times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let transport = HttpTransport::new().timeout(Duration::from_millis(20));
+        let result = transport.post(&url, &[], b"").await;
+        assert!(matches!(result, Err(WebhookError::Timeout(_))));
+        drop(listener);
+    }
+}
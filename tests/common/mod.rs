@@ -0,0 +1,17 @@
+//! Shared harness for integration tests: serves a pipeline on a real bound socket.
+
+use rttp::{
+    Server,
+    context::Context,
+    middleware::{MiddlewareHandler, Next},
+    testing::TestClient,
+};
+
+/// Binds `127.0.0.1:0`, serves `pipeline` until the test runtime shuts down, and returns
+/// a client connected to it.
+pub async fn serve(pipeline: Vec<MiddlewareHandler>) -> TestClient {
+    let server = Server::bind("127.0.0.1:0").await.unwrap();
+    let addr = server.local_addr();
+    tokio::spawn(server.run(move |req| Next::new(pipeline.clone()).run(Context::new(req))));
+    TestClient::connect(addr)
+}
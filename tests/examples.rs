@@ -0,0 +1,305 @@
+//! Runs the applications under `examples/` against a real bound server.
+//!
+//! Each example exposes an `app()` pipeline; these tests serve it over TCP and drive it
+//! with [`TestClient`](rttp::testing::TestClient), covering the router/middleware/subsystem interplay that the
+//! per-module unit tests cannot see.
+
+mod common;
+
+#[allow(dead_code)]
+#[path = "../examples/hello_world.rs"]
+mod hello_world;
+
+#[allow(dead_code)]
+#[path = "../examples/todo_api.rs"]
+mod todo_api;
+
+#[allow(dead_code)]
+#[path = "../examples/chat.rs"]
+mod chat;
+
+#[allow(dead_code)]
+#[path = "../examples/llm_proxy.rs"]
+mod llm_proxy;
+
+use std::time::Duration;
+
+use common::serve;
+use rttp::{
+    StatusCode,
+    realtime::{Message, WebSocket},
+    testing::TestClient,
+};
+use serde_json::{Value, json};
+
+// ── hello_world ───────────────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn hello_world_routes_and_404s() {
+    let mut client = serve(hello_world::app()).await;
+
+    client
+        .get("/")
+        .await
+        .assert_status(StatusCode::Ok)
+        .assert_text("Hello, World!");
+    client.get("/hello/rttp").await.assert_text("Hello, rttp!");
+    client
+        .get("/missing")
+        .await
+        .assert_status(StatusCode::NotFound);
+}
+
+// ── todo_api ──────────────────────────────────────────────────────────────────
+
+async fn todo_client() -> TestClient {
+    serve(todo_api::app(todo_api::MemoryDb::default()).await).await
+}
+
+#[tokio::test]
+async fn todo_api_crud_lifecycle() {
+    let mut client = todo_client().await;
+
+    let res = client
+        .post("/todos")
+        .json(&json!({ "title": "write docs" }))
+        .await;
+    res.assert_status(StatusCode::Created)
+        .assert_header("content-type", "application/json");
+    let created: todo_api::Todo = res.json();
+    assert_eq!(created.title, "write docs");
+    assert!(!created.done);
+
+    let path = format!("/todos/{}", created.id);
+    let res = client.patch(&path).json(&json!({ "done": true })).await;
+    res.assert_status(StatusCode::Ok);
+    assert!(res.json::<todo_api::Todo>().done);
+
+    let all: Vec<todo_api::Todo> = client.get("/todos").await.json();
+    assert_eq!(all.len(), 1);
+    assert_eq!(all[0].title, "write docs");
+
+    client
+        .delete(&path)
+        .await
+        .assert_status(StatusCode::NoContent);
+    client.get(&path).await.assert_status(StatusCode::NotFound);
+    client
+        .delete(&path)
+        .await
+        .assert_status(StatusCode::NotFound);
+}
+
+#[tokio::test]
+async fn todo_api_accepts_form_bodies() {
+    let mut client = todo_client().await;
+    let res = client
+        .post("/todos")
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("title=buy+milk")
+        .await;
+    res.assert_status(StatusCode::Created);
+    assert_eq!(res.json::<todo_api::Todo>().title, "buy milk");
+}
+
+#[tokio::test]
+async fn todo_api_rejects_bad_bodies() {
+    let mut client = todo_client().await;
+
+    client
+        .post("/todos")
+        .header("Content-Type", "application/json")
+        .body("{not json")
+        .await
+        .assert_status(StatusCode::BadRequest);
+    client
+        .post("/todos")
+        .header("Content-Type", "text/csv")
+        .body("title\nx")
+        .await
+        .assert_status(StatusCode::UnsupportedMediaType);
+}
+
+#[tokio::test]
+async fn todo_api_caches_reads_until_a_write() {
+    let mut client = todo_client().await;
+
+    let res = client.get("/todos").await;
+    res.assert_header("x-cache", "MISS");
+    assert_eq!(res.json::<Vec<todo_api::Todo>>().len(), 0);
+    client.get("/todos").await.assert_header("x-cache", "HIT");
+
+    // Creating a todo purges the cached listing.
+    client
+        .post("/todos")
+        .json(&json!({ "title": "write docs" }))
+        .await
+        .assert_status(StatusCode::Created);
+    let res = client.get("/todos").await;
+    res.assert_header("x-cache", "MISS");
+    assert_eq!(res.json::<Vec<todo_api::Todo>>().len(), 1);
+
+    client
+        .get("/todos/1")
+        .await
+        .assert_header("x-cache", "MISS");
+    client.get("/todos/1").await.assert_header("x-cache", "HIT");
+    client
+        .patch("/todos/1")
+        .json(&json!({ "done": true }))
+        .await
+        .assert_status(StatusCode::Ok);
+    let res = client.get("/todos/1").await;
+    res.assert_header("x-cache", "MISS");
+    assert!(res.json::<todo_api::Todo>().done);
+}
+
+// ── chat ──────────────────────────────────────────────────────────────────────
+
+// Reads the next chat message relayed to `socket`.
+async fn recv_chat(socket: &mut WebSocket) -> (String, String) {
+    match socket.next().await {
+        Some(Ok(Message::Text(text))) => {
+            let message: chat::ChatMessage = serde_json::from_str(&text).unwrap();
+            (message.from, message.text)
+        }
+        other => panic!("expected a chat message, got {other:?}"),
+    }
+}
+
+// Joins `room` as `name`, returning once the member hears its own greeting back and so
+// is known to be subscribed.
+async fn join(client: &mut TestClient, room: &str, name: &str) -> WebSocket {
+    let mut socket = client
+        .get(&format!("/rooms/{room}?name={name}"))
+        .websocket()
+        .await;
+    socket.send(format!("{name} joined")).await.unwrap();
+    assert_eq!(
+        recv_chat(&mut socket).await,
+        (name.to_owned(), format!("{name} joined"))
+    );
+    socket
+}
+
+#[tokio::test]
+async fn chat_requires_a_name() {
+    let mut client = serve(chat::app()).await;
+    let mut socket = client.get("/rooms/lobby").websocket().await;
+    match socket.next().await {
+        Some(Ok(Message::Close(Some(close)))) => assert_eq!(close.code, chat::POLICY_VIOLATION),
+        other => panic!("expected a close frame, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn chat_fans_out_within_a_room() {
+    let mut client = serve(chat::app()).await;
+    let mut ada = join(&mut client, "lobby", "ada").await;
+    let mut bob = join(&mut client, "lobby", "bob").await;
+    assert_eq!(
+        recv_chat(&mut ada).await,
+        ("bob".to_owned(), "bob joined".to_owned())
+    );
+    let mut eve = join(&mut client, "attic", "eve").await;
+
+    ada.send("hello").await.unwrap();
+    for socket in [&mut ada, &mut bob] {
+        assert_eq!(
+            recv_chat(socket).await,
+            ("ada".to_owned(), "hello".to_owned())
+        );
+    }
+
+    // Plain-HTTP posts reach the room's sockets too, and only that room's.
+    let post = |room: &str| format!("/rooms/{room}/messages");
+    client
+        .post(&post("attic"))
+        .json(&json!({ "from": "bot", "text": "ping" }))
+        .await
+        .assert_status(StatusCode::Accepted)
+        .assert_text("1");
+    assert_eq!(
+        recv_chat(&mut eve).await,
+        ("bot".to_owned(), "ping".to_owned())
+    );
+    client
+        .post(&post("lobby"))
+        .json(&json!({ "from": "bot", "text": "pong" }))
+        .await
+        .assert_text("2");
+    // Messages arrive in publish order, so the attic ping would have come first.
+    assert_eq!(
+        recv_chat(&mut bob).await,
+        ("bot".to_owned(), "pong".to_owned())
+    );
+}
+
+// ── llm_proxy ─────────────────────────────────────────────────────────────────
+
+const COMPLETION: &str = r#"{"model":"mock","messages":[{"role":"user","content":"hi"}]}"#;
+const STREAMED: &str =
+    r#"{"model":"mock","stream":true,"messages":[{"role":"user","content":"hi"}]}"#;
+
+async fn complete(
+    client: &mut TestClient,
+    key: Option<&str>,
+    body: &str,
+) -> rttp::testing::TestResponse {
+    let request = client
+        .post("/v1/chat/completions")
+        .header("Content-Type", "application/json")
+        .body(body);
+    match key {
+        Some(key) => request.bearer(key).await,
+        None => request.await,
+    }
+}
+
+#[tokio::test]
+async fn llm_proxy_streams_completions() {
+    let mut client = serve(llm_proxy::app(Duration::from_millis(5))).await;
+    let res = complete(&mut client, Some(llm_proxy::CLIENT_KEY), STREAMED).await;
+    res.assert_status(StatusCode::Ok)
+        .assert_header("content-type", "text/event-stream");
+
+    let body = res.text();
+    let events: Vec<&str> = body
+        .split("\n\n")
+        .filter_map(|event| event.strip_prefix("data: "))
+        .collect();
+    assert_eq!(events.last(), Some(&"[DONE]"));
+    let text: String = events[..events.len() - 1]
+        .iter()
+        .map(|event| {
+            let chunk: Value = serde_json::from_str(event).unwrap();
+            chunk["choices"][0]["delta"]["content"]
+                .as_str()
+                .unwrap()
+                .to_owned()
+        })
+        .collect();
+    assert_eq!(text, llm_proxy::REPLY);
+}
+
+#[tokio::test]
+async fn llm_proxy_relays_whole_completions() {
+    let mut client = serve(llm_proxy::app(Duration::ZERO)).await;
+    let res = complete(&mut client, Some(llm_proxy::CLIENT_KEY), COMPLETION).await;
+    res.assert_status(StatusCode::Ok);
+    let completion: Value = res.json();
+    assert_eq!(
+        completion["choices"][0]["message"]["content"],
+        llm_proxy::REPLY
+    );
+}
+
+#[tokio::test]
+async fn llm_proxy_rejects_unknown_keys() {
+    let mut client = serve(llm_proxy::app(Duration::ZERO)).await;
+    for key in [None, Some("not-a-key")] {
+        let res = complete(&mut client, key, COMPLETION).await;
+        res.assert_status(StatusCode::Unauthorized);
+        assert_eq!(res.json::<Value>()["error"]["type"], "invalid_api_key");
+    }
+}
@@ -0,0 +1,213 @@
+//! HTTPS and mutual-TLS against a real bound server, with throwaway certificates.
+
+#![cfg(feature = "tls")]
+
+use std::{sync::Arc, time::Duration};
+
+use rcgen::{BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+use rttp::{
+    Response, Router, Server, StatusCode,
+    context::Context,
+    http::{Method, client::Client},
+    middleware::{MiddlewareHandler, Next, from_middleware},
+    security::{Principal, client_cert_auth},
+    server::tls::TlsConfig,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    TlsConnector,
+    rustls::{
+        ClientConfig, RootCertStore,
+        crypto::ring,
+        pki_types::{CertificateDer, PrivateKeyDer, ServerName, pem::PemObject},
+    },
+};
+
+// A CA plus a server certificate and a client certificate it issued, all as PEM.
+struct Pki {
+    ca: String,
+    server: (String, String),
+    client: (String, String),
+}
+
+fn pki() -> Pki {
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca.distinguished_name
+        .push(DnType::CommonName, "rttp test CA");
+    let ca_cert = ca.self_signed(&ca_key).unwrap();
+
+    let server_key = KeyPair::generate().unwrap();
+    let server = CertificateParams::new(vec!["localhost".to_owned()])
+        .unwrap()
+        .signed_by(&server_key, &ca_cert, &ca_key)
+        .unwrap();
+
+    let client_key = KeyPair::generate().unwrap();
+    let mut client = CertificateParams::new(Vec::<String>::new()).unwrap();
+    client
+        .distinguished_name
+        .push(DnType::CommonName, "billing");
+    client.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+    let client = client.signed_by(&client_key, &ca_cert, &ca_key).unwrap();
+
+    Pki {
+        ca: ca_cert.pem(),
+        server: (server.pem(), server_key.serialize_pem()),
+        client: (client.pem(), client_key.serialize_pem()),
+    }
+}
+
+// Maps CN=billing to a principal and echoes it, along with the TLS flag.
+fn pipeline() -> Vec<MiddlewareHandler> {
+    let mut router = Router::new();
+    router.get("/whoami", |ctx: Context| async move {
+        let id = ctx.extensions().get::<Principal>().unwrap().id().to_owned();
+        let tls = ctx.request().connection().unwrap().is_tls();
+        Response::new(StatusCode::Ok).body(format!("{id} tls={tls}"))
+    });
+    let auth = client_cert_auth(|cert| {
+        let cn = cert.subject().common_name()?;
+        (cn == "billing").then(|| Principal::new(cn))
+    });
+    vec![
+        from_middleware(Arc::new(auth)),
+        from_middleware(Arc::new(router)),
+    ]
+}
+
+async fn start(tls: TlsConfig) -> std::net::SocketAddr {
+    let server = Server::bind("127.0.0.1:0").await.unwrap().tls(tls).unwrap();
+    let addr = server.local_addr();
+    let pipeline = pipeline();
+    tokio::spawn(server.run(move |req| Next::new(pipeline.clone()).run(Context::new(req))));
+    addr
+}
+
+// Performs one HTTPS GET, returning the raw response or `None` if TLS failed.
+async fn get(addr: std::net::SocketAddr, pki: &Pki, with_client_cert: bool) -> Option<String> {
+    let mut roots = RootCertStore::empty();
+    roots
+        .add(CertificateDer::from_pem_slice(pki.ca.as_bytes()).unwrap())
+        .unwrap();
+    let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots);
+    let config = if with_client_cert {
+        let chain = vec![CertificateDer::from_pem_slice(pki.client.0.as_bytes()).unwrap()];
+        let key = PrivateKeyDer::from_pem_slice(pki.client.1.as_bytes()).unwrap();
+        builder.with_client_auth_cert(chain, key).unwrap()
+    } else {
+        builder.with_no_client_auth()
+    };
+
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    let name = ServerName::try_from("localhost").unwrap();
+    let mut stream = TlsConnector::from(Arc::new(config))
+        .connect(name, tcp)
+        .await
+        .ok()?;
+    stream
+        .write_all(b"GET /whoami HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .ok()?;
+    let mut out = Vec::new();
+    stream.read_to_end(&mut out).await.ok()?;
+    (!out.is_empty()).then(|| String::from_utf8_lossy(&out).into_owned())
+}
+
+#[tokio::test]
+async fn mutual_tls_maps_certificate_to_principal() {
+    let pki = pki();
+    let tls = TlsConfig::from_pem(pki.server.0.as_bytes(), pki.server.1.as_bytes())
+        .unwrap()
+        .require_client_cert(pki.ca.as_bytes())
+        .unwrap();
+    let addr = start(tls).await;
+
+    let response = get(addr, &pki, true).await.expect("handshake failed");
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    assert!(response.ends_with("billing tls=true"), "{response}");
+
+    // Required client auth: no certificate, no response.
+    assert_eq!(get(addr, &pki, false).await, None);
+}
+
+#[tokio::test]
+async fn optional_client_auth_leaves_decision_to_middleware() {
+    let pki = pki();
+    let tls = TlsConfig::from_pem(pki.server.0.as_bytes(), pki.server.1.as_bytes())
+        .unwrap()
+        .request_client_cert(pki.ca.as_bytes())
+        .unwrap();
+    let addr = start(tls).await;
+
+    let response = get(addr, &pki, false).await.expect("handshake failed");
+    assert!(response.starts_with("HTTP/1.1 401"), "{response}");
+
+    let response = get(addr, &pki, true).await.expect("handshake failed");
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+}
+
+#[tokio::test]
+async fn stalled_handshakes_are_dropped() {
+    let pki = pki();
+    let tls = TlsConfig::from_pem(pki.server.0.as_bytes(), pki.server.1.as_bytes()).unwrap();
+    let server = Server::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .tls(tls)
+        .unwrap()
+        .handshake_timeout(Duration::from_millis(50));
+    let addr = server.local_addr();
+    let pipeline = pipeline();
+    tokio::spawn(server.run(move |req| Next::new(pipeline.clone()).run(Context::new(req))));
+
+    // Connect and never send a ClientHello: the server hangs up rather than wait.
+    let mut tcp = TcpStream::connect(addr).await.unwrap();
+    let mut out = Vec::new();
+    let read = tokio::time::timeout(Duration::from_secs(5), tcp.read_to_end(&mut out)).await;
+    assert!(matches!(read, Ok(Ok(0))), "connection left open: {read:?}");
+}
+
+#[test]
+fn rejects_bad_pem() {
+    assert!(TlsConfig::from_pem(b"not pem", b"not pem").is_err());
+}
+
+#[tokio::test]
+async fn shared_client_speaks_https() {
+    let pki = pki();
+    let tls = TlsConfig::from_pem(pki.server.0.as_bytes(), pki.server.1.as_bytes()).unwrap();
+    let server = Server::bind("127.0.0.1:0").await.unwrap().tls(tls).unwrap();
+    let port = server.local_addr().port();
+    let mut router = Router::new();
+    router.get("/hello", |_ctx: Context| async {
+        Response::new(StatusCode::Ok).body("hello over tls")
+    });
+    let pipeline = vec![from_middleware(Arc::new(router))];
+    tokio::spawn(server.run(move |req| Next::new(pipeline.clone()).run(Context::new(req))));
+
+    let url = format!("https://localhost:{port}/hello");
+    let client = Client::new().root_certificates(pki.ca.as_bytes()).unwrap();
+    let response = client.request(Method::Get, &url, &[], b"").await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.bytes().await.unwrap(), b"hello over tls");
+
+    // A server the roots do not vouch for fails the handshake.
+    let other = crate::pki();
+    let untrusted = Client::new()
+        .root_certificates(other.ca.as_bytes())
+        .unwrap();
+    assert!(
+        untrusted
+            .request(Method::Get, &url, &[], b"")
+            .await
+            .is_err()
+    );
+}
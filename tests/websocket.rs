@@ -0,0 +1,111 @@
+//! WebSocket routes against a real bound server: handshake, middleware, and framing.
+
+use std::sync::Arc;
+
+use rttp::{
+    Response, Router, Server, StatusCode,
+    context::Context,
+    middleware::{MiddlewareHandler, Next, from_middleware},
+    realtime::Message,
+    security::Principal,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+// Authenticates `?token=secret` as "ada" and refuses everything else.
+fn auth() -> MiddlewareHandler {
+    Arc::new(|mut ctx: Context, next: Next| {
+        Box::pin(async move {
+            if ctx.request().query_param("token") != Some("secret") {
+                return Response::new(StatusCode::Unauthorized);
+            }
+            ctx.extensions_mut().insert(Principal::new("ada"));
+            next.run(ctx).await
+        })
+    })
+}
+
+async fn start() -> std::net::SocketAddr {
+    let mut router = Router::new();
+    router.ws("/chat/:room", |ctx, mut ws| async move {
+        let room = ctx.params().get("room").unwrap_or_default().to_owned();
+        let user = ctx.extensions().get::<Principal>().unwrap().id().to_owned();
+        while let Some(Ok(message)) = ws.next().await {
+            if let Message::Text(text) = message {
+                let _ = ws.send(format!("{user}@{room}: {text}")).await;
+            }
+        }
+    });
+    let pipeline = vec![auth(), from_middleware(Arc::new(router))];
+
+    let server = Server::bind("127.0.0.1:0").await.unwrap();
+    let addr = server.local_addr();
+    tokio::spawn(server.run(move |req| Next::new(pipeline.clone()).run(Context::new(req))));
+    addr
+}
+
+// Sends an upgrade request for `path` and returns the stream plus the response head.
+async fn handshake(addr: std::net::SocketAddr, path: &str) -> (TcpStream, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "GET {path} HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+         Connection: Upgrade\r\nSec-WebSocket-Version: 13\r\n\
+         Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n"
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        if stream.read(&mut byte).await.unwrap() == 0 {
+            break;
+        }
+        head.push(byte[0]);
+    }
+    (stream, String::from_utf8(head).unwrap())
+}
+
+// A masked client text frame.
+fn text_frame(text: &str) -> Vec<u8> {
+    let mask = [1, 2, 3, 4];
+    let mut frame = vec![0x81, 0x80 | text.len() as u8];
+    frame.extend_from_slice(&mask);
+    frame.extend(text.bytes().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    frame
+}
+
+#[tokio::test]
+async fn upgrades_and_exchanges_messages() {
+    let addr = start().await;
+    let (mut stream, head) = handshake(addr, "/chat/rust?token=secret").await;
+    assert!(
+        head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"),
+        "{head}"
+    );
+    assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+    stream.write_all(&text_frame("hi")).await.unwrap();
+    let expected = b"ada@rust: hi";
+    let mut reply = vec![0; 2 + expected.len()];
+    stream.read_exact(&mut reply).await.unwrap();
+    assert_eq!(reply[..2], [0x81, expected.len() as u8]);
+    assert_eq!(&reply[2..], expected);
+
+    // Close handshake: the server echoes the code, then closes the connection.
+    stream
+        .write_all(&[0x88, 0x82, 0, 0, 0, 0, 0x03, 0xE8])
+        .await
+        .unwrap();
+    let mut rest = Vec::new();
+    stream.read_to_end(&mut rest).await.unwrap();
+    assert_eq!(rest, [0x88, 0x02, 0x03, 0xE8]);
+}
+
+#[tokio::test]
+async fn middleware_can_refuse_the_upgrade() {
+    let addr = start().await;
+    let (_, head) = handshake(addr, "/chat/rust").await;
+    assert!(head.starts_with("HTTP/1.1 401 Unauthorized\r\n"), "{head}");
+}